@@ -1,15 +1,70 @@
 use crate::mir_loc::{Local, MirLocId};
 use serde::{Deserialize, Serialize};
+use std::cell::Cell;
 use std::fmt;
 use std::fmt::Debug;
 use std::fmt::Formatter;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
 
 pub type Pointer = usize;
 
+/// Identifies the thread that sent an [`Event`].  Threads are numbered in the order they sent their
+/// first [`Event`], so the main thread is usually `0`.
+pub type ThreadId = u32;
+
+static NEXT_THREAD_ID: AtomicU32 = AtomicU32::new(0);
+
+thread_local! {
+    static THREAD_ID: Cell<Option<ThreadId>> = const { Cell::new(None) };
+}
+
+/// The `pthread_t` of each thread that has a [`ThreadId`], so that a [`EventKind::Join`] can say
+/// which thread was joined.  A `pthread_t` can be reused once its thread is joined, so the latest
+/// entry for it is the one that counts.
+static PTHREADS: Mutex<Vec<(usize, ThreadId)>> = Mutex::new(Vec::new());
+
+extern "C" {
+    /// `libc::pthread_self`, whose `pthread_t` fits in a `usize` on the targets we support.
+    fn pthread_self() -> usize;
+}
+
+/// The [`ThreadId`] of the current thread.
+///
+/// # Safety
+/// Safe to call before or after `main`, as the thread local is const-initialized and has no
+/// destructor.
+pub fn current_thread() -> ThreadId {
+    THREAD_ID.with(|id| match id.get() {
+        Some(id) => id,
+        None => {
+            let new_id = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+            id.set(Some(new_id));
+            // SAFETY: `pthread_self` always succeeds.
+            let pthread = unsafe { pthread_self() };
+            if let Ok(mut pthreads) = PTHREADS.lock() {
+                pthreads.push((pthread, new_id));
+            }
+            new_id
+        }
+    })
+}
+
+/// The [`ThreadId`] of the thread with the `pthread_t` `pthread`, if it has sent an [`Event`].
+pub fn pthread_thread(pthread: usize) -> Option<ThreadId> {
+    let pthreads = PTHREADS.lock().ok()?;
+    pthreads
+        .iter()
+        .rev()
+        .find(|&&(p, _)| p == pthread)
+        .map(|&(_, id)| id)
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Event {
     pub mir_loc: MirLocId,
     pub kind: EventKind,
+    pub thread: ThreadId,
 }
 
 impl Event {
@@ -17,6 +72,7 @@ impl Event {
         Self {
             mir_loc: 0,
             kind: EventKind::Done,
+            thread: current_thread(),
         }
     }
 }
@@ -82,6 +138,18 @@ pub enum EventKind {
     /// events after a [`BeginFuncBody`](Self::BeginFuncBody) event are treated as copies.
     BeginFuncBody,
 
+    /// The mutex at this address was locked, as by `pthread_mutex_lock`.
+    Lock(Pointer),
+
+    /// The mutex at this address was unlocked, as by `pthread_mutex_unlock`.
+    Unlock(Pointer),
+
+    /// The thread waited for the thread with this [`ThreadId`] to exit, as by `pthread_join`.
+    ///
+    /// There's no event for spawning a thread, as `pthread_create` can't be hooked, but each
+    /// thread's first [`Event`] is sent after the thread was spawned.
+    Join(ThreadId),
+
     Done,
 }
 
@@ -115,6 +183,9 @@ impl Debug for EventKind {
             Offset(ptr, offset, new_ptr) => {
                 write!(f, "offset(0x{:x}, {:?}, 0x{:x})", ptr, offset, new_ptr)
             }
+            Lock(ptr) => write!(f, "lock(0x{:x})", ptr),
+            Unlock(ptr) => write!(f, "unlock(0x{:x})", ptr),
+            Join(thread) => write!(f, "join(thread {})", thread),
         }
     }
 }
@@ -1,4 +1,4 @@
-use crate::events::{Event, EventKind};
+use crate::events::{current_thread, pthread_thread, Event, EventKind};
 use crate::mir_loc::MirLocId;
 use crate::runtime::global_runtime::RUNTIME;
 
@@ -12,6 +12,7 @@ pub fn malloc(mir_loc: MirLocId, size: u64, ptr: usize) {
             size: size as usize,
             ptr,
         },
+        thread: current_thread(),
     });
 }
 
@@ -22,6 +23,7 @@ pub fn free(mir_loc: MirLocId, ptr: usize, _free_ret_val: ()) {
     RUNTIME.send_event(Event {
         mir_loc,
         kind: EventKind::Free { ptr },
+        thread: current_thread(),
     });
 }
 
@@ -35,6 +37,7 @@ pub fn calloc(mir_loc: MirLocId, nmemb: u64, size: u64, ptr: usize) {
             size: (nmemb * size) as usize,
             ptr,
         },
+        thread: current_thread(),
     });
 }
 
@@ -44,14 +47,12 @@ pub fn calloc(mir_loc: MirLocId, nmemb: u64, size: u64, ptr: usize) {
 pub fn realloc(mir_loc: MirLocId, old_ptr: usize, size: u64, new_ptr: usize) {
     RUNTIME.send_event(Event {
         mir_loc,
-        kind: EventKind::Free { ptr: old_ptr },
-    });
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::Alloc {
+        kind: EventKind::Realloc {
+            old_ptr,
             size: size as usize,
-            ptr: new_ptr,
+            new_ptr,
         },
+        thread: current_thread(),
     });
 }
 
@@ -80,9 +81,60 @@ pub fn offset(mir_loc: MirLocId, ptr: usize, offset: isize, new_ptr: usize) {
     RUNTIME.send_event(Event {
         mir_loc,
         kind: EventKind::Offset(ptr, offset, new_ptr),
+        thread: current_thread(),
     });
 }
 
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments `libc::pthread_mutex_lock`, if it succeeded.
+pub fn pthread_mutex_lock(mir_loc: MirLocId, mutex: usize, ret: i32) {
+    if ret == 0 {
+        RUNTIME.send_event(Event {
+            mir_loc,
+            kind: EventKind::Lock(mutex),
+            thread: current_thread(),
+        });
+    }
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments `libc::pthread_mutex_trylock`, if it succeeded.
+pub fn pthread_mutex_trylock(mir_loc: MirLocId, mutex: usize, ret: i32) {
+    pthread_mutex_lock(mir_loc, mutex, ret)
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments `libc::pthread_mutex_unlock`, if it succeeded.
+pub fn pthread_mutex_unlock(mir_loc: MirLocId, mutex: usize, ret: i32) {
+    if ret == 0 {
+        RUNTIME.send_event(Event {
+            mir_loc,
+            kind: EventKind::Unlock(mutex),
+            thread: current_thread(),
+        });
+    }
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `libc::pthread_join`, if it succeeded and the joined thread sent any
+/// events.
+pub fn pthread_join(mir_loc: MirLocId, thread: u64, _retval: usize, ret: i32) {
+    if ret != 0 {
+        return;
+    }
+    if let Some(joined) = pthread_thread(thread as usize) {
+        RUNTIME.send_event(Event {
+            mir_loc,
+            kind: EventKind::Join(joined),
+            thread: current_thread(),
+        });
+    }
+}
+
 macro_rules! hook_fn {
     ($name:ident) => {{
         // Ensure it exists and allow rust-analyzer to see through it.
@@ -103,12 +155,17 @@ pub const HOOK_FUNCTIONS: &[&str] = &[
     hook_fn!(realloc),
     hook_fn!(reallocarray),
     hook_fn!(offset),
+    hook_fn!(pthread_mutex_lock),
+    hook_fn!(pthread_mutex_trylock),
+    hook_fn!(pthread_mutex_unlock),
+    hook_fn!(pthread_join),
 ];
 
 pub fn ptr_field(mir_loc: MirLocId, ptr: usize, field_id: u32) {
     RUNTIME.send_event(Event {
         mir_loc,
         kind: EventKind::Field(ptr, field_id),
+        thread: current_thread(),
     });
 }
 
@@ -116,6 +173,7 @@ pub fn ptr_copy(mir_loc: MirLocId, ptr: usize) {
     RUNTIME.send_event(Event {
         mir_loc,
         kind: EventKind::CopyPtr(ptr as usize),
+        thread: current_thread(),
     });
 }
 
@@ -123,6 +181,7 @@ pub fn ptr_contrive(mir_loc: MirLocId, ptr: usize) {
     RUNTIME.send_event(Event {
         mir_loc,
         kind: EventKind::FromInt(ptr as usize),
+        thread: current_thread(),
     });
 }
 
@@ -130,6 +189,7 @@ pub fn ptr_to_int(mir_loc: MirLocId, ptr: usize) {
     RUNTIME.send_event(Event {
         mir_loc,
         kind: EventKind::ToInt(ptr as usize),
+        thread: current_thread(),
     });
 }
 
@@ -137,6 +197,7 @@ pub fn addr_of_local(mir_loc: MirLocId, ptr: usize, local: u32) {
     RUNTIME.send_event(Event {
         mir_loc,
         kind: EventKind::AddrOfLocal(ptr, local.into()),
+        thread: current_thread(),
     });
 }
 
@@ -144,6 +205,7 @@ pub fn load_value(mir_loc: MirLocId, ptr: usize) {
     RUNTIME.send_event(Event {
         mir_loc,
         kind: EventKind::LoadValue(ptr),
+        thread: current_thread(),
     });
 }
 
@@ -151,6 +213,7 @@ pub fn store_value(mir_loc: MirLocId, ptr: usize) {
     RUNTIME.send_event(Event {
         mir_loc,
         kind: EventKind::StoreValue(ptr),
+        thread: current_thread(),
     });
 }
 
@@ -158,6 +221,7 @@ pub fn ptr_ret(mir_loc: MirLocId, ptr: usize) {
     RUNTIME.send_event(Event {
         mir_loc,
         kind: EventKind::Ret(ptr),
+        thread: current_thread(),
     });
 }
 
@@ -165,6 +229,7 @@ pub fn ptr_load(mir_loc: MirLocId, ptr: usize) {
     RUNTIME.send_event(Event {
         mir_loc,
         kind: EventKind::LoadAddr(ptr),
+        thread: current_thread(),
     });
 }
 
@@ -172,6 +237,7 @@ pub fn ptr_store(mir_loc: MirLocId, ptr: usize) {
     RUNTIME.send_event(Event {
         mir_loc,
         kind: EventKind::StoreAddr(ptr),
+        thread: current_thread(),
     });
 }
 
@@ -179,6 +245,7 @@ pub fn ptr_store_addr_taken(mir_loc: MirLocId, ptr: usize) {
     RUNTIME.send_event(Event {
         mir_loc,
         kind: EventKind::StoreAddrTaken(ptr),
+        thread: current_thread(),
     });
 }
 
@@ -186,5 +253,6 @@ pub fn mark_begin_body(mir_loc: MirLocId) {
     RUNTIME.send_event(Event {
         mir_loc,
         kind: EventKind::BeginFuncBody,
+        thread: current_thread(),
     })
 }
@@ -7,12 +7,16 @@ use std::{
 
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use crate::mir_loc::{Func, FuncId, MirLoc, MirLocId};
+use crate::mir_loc::{Func, FuncId, Local, MirLoc, MirLocId};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Metadata {
+    /// Every instrumentation point, whether or not it sends an event in a given run.
     pub locs: Vec<MirLoc>,
     pub functions: HashMap<FuncId, String>,
+    /// The locals of raw pointer or reference type in each instrumented function, as they were
+    /// before instrumentation, including functions without any [`MirLoc`]s.
+    pub pointer_locals: HashMap<FuncId, Vec<Local>>,
 }
 
 impl Metadata {
@@ -46,11 +50,17 @@ impl FromIterator<Metadata> for Metadata {
     fn from_iter<I: IntoIterator<Item = Metadata>>(iter: I) -> Self {
         let mut locs = Vec::new();
         let mut functions = HashMap::new();
+        let mut pointer_locals = HashMap::new();
         for metadata in iter {
             locs.extend(metadata.locs);
             functions.extend(metadata.functions);
+            pointer_locals.extend(metadata.pointer_locals);
+        }
+        Self {
+            locs,
+            functions,
+            pointer_locals,
         }
-        Self { locs, functions }
     }
 }
 
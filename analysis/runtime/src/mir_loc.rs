@@ -189,6 +189,24 @@ impl Default for TransferKind {
     }
 }
 
+/// A position in the source code, for pointing users at it.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone)]
+pub struct SourceSpan {
+    /// The path of the source file, as given to `rustc`.
+    pub file: String,
+    /// The 1-based line number.
+    pub line: usize,
+    /// The 1-based column number, in `char`s.
+    pub col: usize,
+}
+
+impl Display for SourceSpan {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let Self { file, line, col } = self;
+        write!(f, "{file}:{line}:{col}")
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Eq, Clone, Default)]
 pub struct EventMetadata {
     /// Input [`Local`]s for an [`Event`](crate::events::Event).
@@ -199,6 +217,8 @@ pub struct EventMetadata {
     pub transfer_kind: TransferKind,
     /// Any string useful for debugging.
     pub debug_info: String,
+    /// Where the MIR statement or terminator came from in the source code, if anywhere.
+    pub span: Option<SourceSpan>,
 }
 
 impl EventMetadata {
@@ -208,6 +228,7 @@ impl EventMetadata {
             destination,
             transfer_kind,
             debug_info: _,
+            span: _,
         } = self;
         (source, destination, transfer_kind)
     }
@@ -0,0 +1,215 @@
+//! Shims that keep the ABI of exported functions.
+//!
+//! Functions exported with `#[no_mangle]` or `#[export_name]` are called from C, so their
+//! signatures can't change, but their bodies should still be rewritten like any other function.
+//! When the analysis rewrites the signature of an exported function, the function loses its
+//! `#[no_mangle]` attribute and becomes an ordinary safe-typed function, and a thin `unsafe` shim
+//! with the original signature is added after it, under the original symbol name.  The shim
+//! converts each raw pointer argument to the new parameter type, such as `&mut *p` or
+//! `p.as_ref()`, calls the function, and converts the result back to a raw pointer.
+//!
+//! Some types can't be produced from a raw pointer at the boundary: a slice needs a length the
+//! caller doesn't pass, a `Box` or `Vec` would take ownership of memory C allocated, and a nested
+//! pointer like `&mut &mut T` can't be built from a `*mut *mut T` without copying.  An exported
+//! function with such a pointer in its signature keeps its whole signature raw instead, as
+//! [`fix_unconvertible`] arranges before the rest of the crate is rewritten to match.
+use crate::context::{FlagSet, GlobalAnalysisCtxt, GlobalAssignment, LFnSig, LTy, PermissionSet};
+use crate::pointer_id::PointerTable;
+use crate::rewrite::TextEdit;
+use crate::type_desc::{self, Ownership};
+use rustc_hir as hir;
+use rustc_hir::def_id::{DefId, LocalDefId};
+use rustc_middle::ty::TyCtxt;
+use rustc_span::sym;
+use std::fmt::Write as _;
+
+/// Check whether function `did` is exported under a fixed symbol name, so its ABI must not change.
+pub fn is_exported(tcx: TyCtxt, did: DefId) -> bool {
+    tcx.codegen_fn_attrs(did).contains_extern_indicator()
+}
+
+/// Check whether the pointer in `lty` can be converted from and to a raw pointer in a shim.
+fn convertible(lty: LTy, gasn: &GlobalAssignment) -> bool {
+    let ptr = lty.label;
+    if ptr.is_none() {
+        return lty.iter().all(|l| l.label.is_none());
+    }
+    if lty.iter().skip(1).any(|l| !l.label.is_none()) {
+        return false;
+    }
+    let bad_perms = PermissionSet::OFFSET_ADD | PermissionSet::OFFSET_SUB | PermissionSet::FREE;
+    !gasn.perms[ptr].intersects(bad_perms)
+        && !gasn.flags[ptr].intersects(FlagSet::RC | FlagSet::CVOID)
+}
+
+/// Mark every pointer in the signatures of the exported functions in `fns` that can't be given a
+/// shim as `FIXED`.  Returns those functions.
+pub fn fix_unconvertible(
+    gacx: &GlobalAnalysisCtxt,
+    gasn: &mut GlobalAssignment,
+    fns: &[LocalDefId],
+) -> Vec<LocalDefId> {
+    let mut fixed = Vec::new();
+    for &ldid in fns {
+        let did = ldid.to_def_id();
+        if !is_exported(gacx.tcx, did) {
+            continue;
+        }
+        let lsig = &gacx.fn_sigs[&did];
+        let ltys = || lsig.inputs.iter().copied().chain(Some(lsig.output));
+        if ltys().all(|lty| convertible(lty, gasn)) {
+            continue;
+        }
+        for lty in ltys() {
+            for ptr in lty.iter().map(|lty| lty.label) {
+                if !ptr.is_none() {
+                    gasn.flags[ptr].insert(FlagSet::FIXED);
+                }
+            }
+        }
+        fixed.push(ldid);
+    }
+    fixed
+}
+
+/// The new type of the pointer in `lty`, and whether it's nullable, or `None` if it's not a
+/// pointer or stays a raw pointer.
+fn new_desc(
+    lty: LTy,
+    perms: &PointerTable<PermissionSet>,
+    flags: &PointerTable<FlagSet>,
+) -> Option<(Ownership, bool)> {
+    let ptr = lty.label;
+    if ptr.is_none() {
+        return None;
+    }
+    match type_desc::perms_to_desc(perms[ptr], flags[ptr]) {
+        (Ownership::Raw | Ownership::RawMut, _) => None,
+        (Ownership::NonNull, _) => Some((Ownership::NonNull, false)),
+        (own, _) => Some((own, flags[ptr].contains(FlagSet::NULLABLE))),
+    }
+}
+
+/// Convert the raw pointer argument `arg` to a pointer of type `desc`.
+fn convert_arg(arg: &str, desc: Option<(Ownership, bool)>) -> String {
+    match desc {
+        None => arg.to_owned(),
+        Some((Ownership::Imm, false)) => format!("&*{}", arg),
+        Some((Ownership::Imm, true)) => format!("{}.as_ref()", arg),
+        Some((Ownership::Mut, false)) => format!("&mut *{}", arg),
+        Some((Ownership::Mut, true)) => format!("{}.as_mut()", arg),
+        Some((Ownership::Cell, false)) => format!("std::cell::Cell::from_mut(&mut *{})", arg),
+        Some((Ownership::Cell, true)) => {
+            format!("{}.as_mut().map(std::cell::Cell::from_mut)", arg)
+        }
+        Some((Ownership::NonNull, _)) => format!("std::ptr::NonNull::new_unchecked({})", arg),
+        Some((Ownership::CStr, false)) => format!("std::ffi::CStr::from_ptr({})", arg),
+        Some((Ownership::CStr, true)) => format!(
+            "(!{}.is_null()).then(|| std::ffi::CStr::from_ptr({}))",
+            arg, arg
+        ),
+        Some((own, _)) => unreachable!("{:?} pointers don't get shims", own),
+    }
+}
+
+/// Convert the result `ret` back to the raw pointer type `ty`.
+fn convert_ret(ret: &str, desc: Option<(Ownership, bool)>, ty: &str) -> String {
+    let ptr = match desc {
+        None => return ret.to_owned(),
+        Some((Ownership::Imm, false)) => format!("{} as *const _", ret),
+        Some((Ownership::Imm, true)) => {
+            format!("{}.map_or(std::ptr::null(), |r| r as *const _)", ret)
+        }
+        Some((Ownership::Mut, false)) => format!("{} as *mut _", ret),
+        Some((Ownership::Mut, true)) => {
+            format!("{}.map_or(std::ptr::null_mut(), |r| r as *mut _)", ret)
+        }
+        Some((Ownership::Cell, false)) => format!("{}.as_ptr()", ret),
+        Some((Ownership::Cell, true)) => {
+            format!("{}.map_or(std::ptr::null_mut(), |r| r.as_ptr())", ret)
+        }
+        Some((Ownership::NonNull, _)) => format!("{}.as_ptr()", ret),
+        Some((Ownership::CStr, false)) => format!("{}.as_ptr()", ret),
+        Some((Ownership::CStr, true)) => {
+            format!("{}.map_or(std::ptr::null(), |s| s.as_ptr())", ret)
+        }
+        Some((own, _)) => unreachable!("{:?} pointers don't get shims", own),
+    };
+    format!("{} as {}", ptr, ty)
+}
+
+/// A shim for an exported function, and the edits that add it.
+pub struct Shim {
+    pub text: String,
+    pub edits: Vec<TextEdit>,
+}
+
+/// Build the shim for the exported function `ldid`, if its signature is rewritten.
+pub fn build<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    ldid: LocalDefId,
+    lsig: LFnSig<'tcx>,
+    perms: &PointerTable<PermissionSet>,
+    flags: &PointerTable<FlagSet>,
+) -> Option<Shim> {
+    let did = ldid.to_def_id();
+    let ltys = lsig.inputs.iter().copied().chain(Some(lsig.output));
+    if ltys
+        .map(|lty| new_desc(lty, perms, flags))
+        .all(|d| d.is_none())
+    {
+        return None;
+    }
+    let hir_id = tcx.hir().local_def_id_to_hir_id(ldid);
+    let decl = tcx.hir().fn_decl_by_hir_id(hir_id)?;
+    let sm = tcx.sess.source_map();
+    let snippet = |span| sm.span_to_snippet(span).ok();
+
+    let name = tcx.item_name(did);
+    let symbol = match tcx.codegen_fn_attrs(did).export_name {
+        Some(export_name) => export_name.to_string(),
+        None => name.to_string(),
+    };
+    let abi = tcx.fn_sig(did).abi();
+
+    let mut params = Vec::new();
+    let mut args = Vec::new();
+    for (i, (hir_ty, &lty)) in decl.inputs.iter().zip(lsig.inputs).enumerate() {
+        let arg = format!("arg{}", i);
+        params.push(format!("{}: {}", arg, snippet(hir_ty.span)?));
+        args.push(convert_arg(&arg, new_desc(lty, perms, flags)));
+    }
+    let call = format!("{}({})", name, args.join(", "));
+
+    let mut text = String::new();
+    writeln!(text, "\n\n#[export_name = {:?}]", symbol).unwrap();
+    write!(
+        text,
+        "pub unsafe extern {:?} fn {}_export({})",
+        abi.name(),
+        name,
+        params.join(", ")
+    )
+    .unwrap();
+    let body = match decl.output {
+        hir::FnRetTy::Return(hir_ty) => {
+            let ty = snippet(hir_ty.span)?;
+            write!(text, " -> {}", ty).unwrap();
+            convert_ret(&call, new_desc(lsig.output, perms, flags), &ty)
+        }
+        hir::FnRetTy::DefaultReturn(_) => call,
+    };
+    write!(text, " {{\n    {}\n}}", body).unwrap();
+
+    // The original function no longer claims the symbol.
+    let mut edits = tcx
+        .hir()
+        .attrs(hir_id)
+        .iter()
+        .filter(|attr| attr.has_name(sym::no_mangle) || attr.has_name(sym::export_name))
+        .map(|attr| TextEdit::new(attr.span, ""))
+        .collect::<Vec<_>>();
+    let item_span = tcx.hir().span_with_body(hir_id);
+    edits.push(TextEdit::new(item_span.shrink_to_hi(), text.clone()));
+    Some(Shim { text, edits })
+}
@@ -0,0 +1,226 @@
+//! Alternative rewrites of signature and field pointers, for `--alternatives`.
+//!
+//! The analysis gives each pointer the safest type its permissions allow, but that's not the only
+//! legal choice.  Any pointer can be given a weaker type: an owning pointer can be borrowed
+//! instead, a `&mut T` can become a `&Cell<T>`, and anything can stay raw.  The weaker types form
+//! a chain from the chosen type down to the raw pointer, and each step keeps every access to the
+//! pointer legal.  What a step can break is the pointers that receive their value from it: a
+//! pointer copied from a raw pointer must be raw too, one copied from a `&Cell<T>` can't be a
+//! `&mut T`, and one copied from a borrowed pointer can't own its target.
+//!
+//! For each pointer in a signature or field, [`Alternatives::for_type`] lists the distinct types
+//! along this chain, best first, along with the other pointers whose types would change to match.
+//! They're printed after each rewritten signature and struct, and recorded in the rewrite plan
+//! (see [`crate::rewrite_plan`]), where one can be picked by copying it into the entry's
+//! `new_type`.
+use crate::context::{
+    FlagSet, GlobalAnalysisCtxt, GlobalAssignment, LTy, LocalAssignment, PermissionSet,
+};
+use crate::dataflow::DataflowConstraints;
+use crate::lifetimes::TypePrinter;
+use crate::pointer_id::{GlobalPointerTable, PointerId};
+use crate::type_desc;
+use rustc_hir::def_id::LocalDefId;
+use rustc_middle::ty::{TyCtxt, TyKind};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+/// Another type a pointer could be rewritten to.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Alternative {
+    pub new_type: String,
+    /// The other pointers whose types would change, as `g5` or `f:l3`, in the syntax of
+    /// `--explain`.
+    #[serde(default)]
+    pub also_changes: Vec<String>,
+}
+
+/// One step down the chain of weaker types.
+#[derive(Clone, Copy, Debug)]
+enum Weaken {
+    /// Borrow the target instead of owning it.
+    Borrowed,
+    /// Use `Cell` instead of a unique reference.
+    Cell,
+    /// Keep the raw pointer.
+    Raw,
+}
+
+impl Weaken {
+    const ALL: [Weaken; 3] = [Weaken::Borrowed, Weaken::Cell, Weaken::Raw];
+
+    fn apply(self, perms: &mut PermissionSet, flags: &mut FlagSet) {
+        match self {
+            Weaken::Borrowed => flags.remove(FlagSet::OWNED | FlagSet::VEC | FlagSet::RC),
+            Weaken::Cell => {
+                if perms.contains(PermissionSet::WRITE) {
+                    perms.remove(PermissionSet::UNIQUE);
+                    flags.insert(FlagSet::CELL);
+                }
+            }
+            Weaken::Raw => flags.insert(FlagSet::FIXED),
+        }
+    }
+
+    /// Apply the first `n` steps.
+    fn apply_first(
+        n: usize,
+        (mut perms, mut flags): (PermissionSet, FlagSet),
+    ) -> (PermissionSet, FlagSet) {
+        for w in &Weaken::ALL[..n] {
+            w.apply(&mut perms, &mut flags);
+        }
+        (perms, flags)
+    }
+}
+
+/// A pointer, qualified by its function if it's local.
+type Node = (Option<LocalDefId>, PointerId);
+
+fn node(ldid: LocalDefId, ptr: PointerId) -> Node {
+    if ptr.is_global() {
+        (None, ptr)
+    } else {
+        (Some(ldid), ptr)
+    }
+}
+
+/// The final assignment and the dataflow between pointers, across the whole crate.
+pub struct Alternatives<'tcx> {
+    tcx: TyCtxt<'tcx>,
+    perms: GlobalPointerTable<PermissionSet>,
+    flags: GlobalPointerTable<FlagSet>,
+    /// The permissions and flags of each local pointer.
+    locals: HashMap<Node, (PermissionSet, FlagSet)>,
+    /// The pointers that receive their value from each pointer.
+    dests: HashMap<Node, Vec<Node>>,
+    empty: LocalAssignment,
+}
+
+impl<'tcx> Alternatives<'tcx> {
+    pub fn new<'a>(
+        gacx: &GlobalAnalysisCtxt<'tcx>,
+        gasn: &GlobalAssignment,
+        fns: impl Iterator<Item = (LocalDefId, &'a DataflowConstraints, &'a LocalAssignment)>,
+    ) -> Alternatives<'tcx> {
+        let mut locals = HashMap::new();
+        let mut dests = HashMap::<Node, Vec<Node>>::new();
+        for (ldid, dataflow, lasn) in fns {
+            for (ptr, &perms) in lasn.perms.iter() {
+                locals.insert((Some(ldid), ptr), (perms, lasn.flags[ptr]));
+            }
+            for (a, b) in dataflow.subsets() {
+                dests.entry(node(ldid, b)).or_default().push(node(ldid, a));
+            }
+        }
+        Alternatives {
+            tcx: gacx.tcx,
+            perms: gasn.perms.clone(),
+            flags: gasn.flags.clone(),
+            locals,
+            dests,
+            empty: LocalAssignment::new(0, PermissionSet::UNIQUE, FlagSet::empty()),
+        }
+    }
+
+    fn get(&self, n: Node) -> (PermissionSet, FlagSet) {
+        match n {
+            (None, ptr) => (self.perms[ptr], self.flags[ptr]),
+            (Some(_), _) => self.locals[&n],
+        }
+    }
+
+    fn name(&self, (ldid, ptr): Node) -> String {
+        match ldid {
+            Some(ldid) => format!("{}:{}", self.tcx.def_path_str(ldid.to_def_id()), ptr),
+            None => ptr.to_string(),
+        }
+    }
+
+    /// The pointers other than `start` whose types change when the first `n` steps are applied
+    /// to `start` and to every pointer that receives a value from a changed pointer.
+    fn knock_on(&self, start: PointerId, n: usize) -> Vec<String> {
+        let start = (None, start);
+        let mut seen = HashSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+        let mut changed = Vec::new();
+        while let Some(cur) = queue.pop_front() {
+            for &dest in self.dests.get(&cur).into_iter().flatten() {
+                if !seen.insert(dest) {
+                    continue;
+                }
+                let (perms, flags) = self.get(dest);
+                let (new_perms, new_flags) = Weaken::apply_first(n, (perms, flags));
+                if type_desc::perms_to_desc(perms, flags)
+                    != type_desc::perms_to_desc(new_perms, new_flags)
+                {
+                    changed.push(self.name(dest));
+                    queue.push_back(dest);
+                }
+            }
+        }
+        changed.sort();
+        changed
+    }
+
+    /// The other types the outermost pointer of `lty` could be rewritten to, best first.  The
+    /// arguments are as for [`TypePrinter::print_with`].
+    pub fn for_type(
+        &self,
+        printer: &TypePrinter<'_, 'tcx>,
+        lty: LTy<'tcx>,
+        name: &dyn Fn(PointerId) -> Option<String>,
+        behind: &dyn Fn(PointerId) -> Option<String>,
+        adt_name: Option<&str>,
+    ) -> Vec<Alternative> {
+        let ptr = lty.label;
+        if ptr.is_none() || !ptr.is_global() || !matches!(lty.ty.kind(), TyKind::RawPtr(..)) {
+            return Vec::new();
+        }
+        let mut seen = vec![printer.print_with(lty, name, behind, adt_name)];
+        let mut alts = Vec::new();
+        for n in 1..=Weaken::ALL.len() {
+            let (perms, flags) = Weaken::apply_first(n, (self.perms[ptr], self.flags[ptr]));
+            let mut perms_table = self.perms.clone();
+            let mut flags_table = self.flags.clone();
+            perms_table[ptr] = perms;
+            flags_table[ptr] = flags;
+            let printer = printer.with_tables(
+                perms_table.and(&self.empty.perms),
+                flags_table.and(&self.empty.flags),
+            );
+            let new_type = printer.print_with(lty, name, behind, adt_name);
+            if seen.contains(&new_type) {
+                continue;
+            }
+            seen.push(new_type.clone());
+            alts.push(Alternative {
+                new_type,
+                also_changes: self.knock_on(ptr, n),
+            });
+        }
+        alts
+    }
+}
+
+impl fmt::Display for Alternative {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.new_type)?;
+        if !self.also_changes.is_empty() {
+            write!(f, " (also changes {})", self.also_changes.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Print the alternatives for the type at `position` in `item`, if there are any.
+pub fn print(item: &str, position: &str, alts: &[Alternative]) {
+    if alts.is_empty() {
+        return;
+    }
+    eprintln!("alternatives for {} {}:", item, position);
+    for alt in alts {
+        eprintln!("  {}", alt);
+    }
+}
@@ -0,0 +1,259 @@
+//! Source-level attributes that constrain the analysis.
+//!
+//! These give users an escape hatch for pointers the analysis handles badly:
+//!
+//! * `#[c2rust::assume(perms = "READ|OFFSET_ADD")]` on a function parameter asserts that the
+//!   parameter's pointer needs the listed permissions, even if the function body doesn't use
+//!   them.  This is useful for functions whose callers rely on more than the body shows, such as
+//!   a public API that will later be extended.  `UNIQUE` is removed from the pointer unless it's
+//!   listed, and is kept only if the analysis also finds the pointer to be unique.
+//! * `#[c2rust::keep_raw]` on a function or struct field keeps every pointer in its signature or
+//!   type as a raw pointer.  On a function, this also applies to pointers local to its body.
+//! * `#[c2rust::rc]` on a function or struct field rewrites the pointers in its signature or type
+//!   to `Rc<RefCell<T>>` if they point to heap objects with several owners that are never freed.
+//!   On a function, this also applies to pointers local to its body.  Without it, such pointers
+//!   are rewritten to references like any other pointer, which usually means the owning code has
+//!   to be fixed by hand.
+//! * `#[c2rust::skip]` on a function excludes it from the analysis entirely.  Its signature and
+//!   body are left unchanged, and calls to it are treated like calls to an unknown function.  This
+//!   is meant for the few functions in a large crate that the analysis can't handle, so the rest
+//!   of the crate can still be analyzed.
+//!
+//! Functions can also be excluded from the command line: with `--function PATTERN`, every function
+//! whose def path doesn't match one of the patterns is treated as if it were marked `skip`.
+//!
+//! `c2rust-analyze` registers the `c2rust` tool namespace and sets `--cfg c2rust_analyze` when it
+//! compiles a crate, so the attributes need no declaration.  To keep the crate building normally,
+//! write them as `#[cfg_attr(c2rust_analyze, c2rust::keep_raw)]`.
+use crate::context::{FlagSet, PermissionSet};
+use crate::extern_models::parse_perm;
+use rustc_ast::Attribute;
+use rustc_hir::def::DefKind;
+use rustc_hir::def_id::DefId;
+use rustc_middle::ty::TyCtxt;
+use std::collections::{HashMap, HashSet};
+
+/// Extra arguments to pass to `rustc` so that crates can use our attributes.
+pub const RUSTC_ARGS: &[&str] = &[
+    "-Zcrate-attr=feature(register_tool)",
+    "-Zcrate-attr=register_tool(c2rust)",
+    "--cfg=c2rust_analyze",
+];
+
+/// All the `c2rust` attributes found in the current crate.
+#[derive(Clone, Debug, Default)]
+pub struct Annotations {
+    /// Permissions assumed for function parameters, as a list of parameter indices and
+    /// permissions for each function.
+    assume_perms: HashMap<DefId, Vec<(usize, PermissionSet)>>,
+    /// Functions and fields marked `keep_raw`.
+    keep_raw: HashSet<DefId>,
+    /// Functions and fields marked `rc`.
+    rc: HashSet<DefId>,
+    /// Functions marked `skip`.
+    skip: HashSet<DefId>,
+    /// Functions excluded by `--function`.
+    deselected: HashSet<DefId>,
+}
+
+/// Check whether `attr` is `#[c2rust::<name>]`.
+fn is_c2rust_attr(attr: &Attribute, name: &str) -> bool {
+    if attr.is_doc_comment() {
+        return false;
+    }
+    let segments = &attr.get_normal_item().path.segments;
+    segments.len() == 2
+        && segments[0].ident.as_str() == "c2rust"
+        && segments[1].ident.as_str() == name
+}
+
+/// Parse the `perms = "..."` argument of an `assume` attribute.
+fn parse_assume(tcx: TyCtxt, attr: &Attribute) -> Option<PermissionSet> {
+    let items = attr.meta_item_list().unwrap_or_default();
+    let value = items
+        .iter()
+        .find(|item| item.name_or_empty().as_str() == "perms")
+        .and_then(|item| item.value_str());
+    let value = match value {
+        Some(x) => x,
+        None => {
+            tcx.sess
+                .span_err(attr.span, "expected `#[c2rust::assume(perms = \"...\")]`");
+            return None;
+        }
+    };
+
+    let mut perms = PermissionSet::empty();
+    for name in value.as_str().split('|').map(|s| s.trim()) {
+        match parse_perm(name) {
+            Some(p) => perms.insert(p),
+            None => {
+                tcx.sess
+                    .span_err(attr.span, &format!("unknown permission {name:?}"));
+                return None;
+            }
+        }
+    }
+    Some(perms)
+}
+
+impl Annotations {
+    /// Collect the annotations on all functions, parameters, and fields in the current crate.
+    pub fn collect(tcx: TyCtxt) -> Annotations {
+        let hir = tcx.hir();
+        let mut annotations = Annotations::default();
+
+        for ldid in tcx.hir_crate_items(()).definitions() {
+            let did = ldid.to_def_id();
+            let def_kind = tcx.def_kind(did);
+            if !matches!(def_kind, DefKind::Fn | DefKind::AssocFn | DefKind::Field) {
+                continue;
+            }
+
+            let attrs = hir.attrs(hir.local_def_id_to_hir_id(ldid));
+            if attrs.iter().any(|attr| is_c2rust_attr(attr, "keep_raw")) {
+                annotations.keep_raw.insert(did);
+            }
+            if attrs.iter().any(|attr| is_c2rust_attr(attr, "rc")) {
+                annotations.rc.insert(did);
+            }
+
+            if def_kind == DefKind::Field {
+                continue;
+            }
+            if attrs.iter().any(|attr| is_c2rust_attr(attr, "skip")) {
+                annotations.skip.insert(did);
+            }
+            let body_id = match hir.maybe_body_owned_by(ldid) {
+                Some(x) => x,
+                None => continue,
+            };
+            for (i, param) in hir.body(body_id).params.iter().enumerate() {
+                for attr in hir.attrs(param.hir_id) {
+                    if !is_c2rust_attr(attr, "assume") {
+                        continue;
+                    }
+                    if let Some(perms) = parse_assume(tcx, attr) {
+                        annotations
+                            .assume_perms
+                            .entry(did)
+                            .or_default()
+                            .push((i, perms));
+                    }
+                }
+            }
+        }
+
+        annotations
+    }
+
+    /// The permissions assumed for the parameters of function `did`, as pairs of a parameter
+    /// index and its permissions.
+    pub fn assumed_perms(&self, did: DefId) -> &[(usize, PermissionSet)] {
+        self.assume_perms.get(&did).map_or(&[], |v| v)
+    }
+
+    /// Check whether the function or field `did` is marked `keep_raw`.
+    pub fn keep_raw(&self, did: DefId) -> bool {
+        self.keep_raw.contains(&did)
+    }
+
+    /// Check whether the function or field `did` is marked `rc`.
+    pub fn rc(&self, did: DefId) -> bool {
+        self.rc.contains(&did)
+    }
+
+    /// The flags to set on all pointers in the signature and body of function `did`, or in the
+    /// type of field `did`.
+    pub fn flags(&self, did: DefId) -> FlagSet {
+        let mut flags = FlagSet::empty();
+        if self.keep_raw(did) {
+            flags.insert(FlagSet::FIXED);
+        }
+        if self.rc(did) {
+            flags.insert(FlagSet::RC);
+        }
+        flags
+    }
+
+    /// Exclude every function whose def path doesn't match one of the glob `patterns`.  Does
+    /// nothing if `patterns` is empty.
+    pub fn select_fns(&mut self, tcx: TyCtxt, patterns: &[String]) {
+        if patterns.is_empty() {
+            return;
+        }
+        for ldid in tcx.hir().body_owners() {
+            let did = ldid.to_def_id();
+            let path = tcx.def_path_str(did);
+            if !patterns.iter().any(|pat| glob_match(pat, &path)) {
+                self.deselected.insert(did);
+            }
+        }
+    }
+
+    /// Check whether the function `did` is marked `skip`, or was excluded by `--function`.
+    pub fn skip(&self, did: DefId) -> bool {
+        self.skip.contains(&did) || self.deselected.contains(&did)
+    }
+
+    /// Check whether the function `did` was excluded by `--function`.
+    pub fn deselected(&self, did: DefId) -> bool {
+        self.deselected.contains(&did)
+    }
+
+    /// Describe every annotation, one per line, in a fixed order, so that [`crate::cache`] can
+    /// tell when any of them changed.
+    pub fn describe(&self, tcx: TyCtxt) -> Vec<String> {
+        let mut lines = self
+            .assume_perms
+            .iter()
+            .map(|(&did, perms)| format!("assume {} {:?}", tcx.def_path_str(did), perms))
+            .collect::<Vec<_>>();
+        let sets = [
+            ("keep_raw", &self.keep_raw),
+            ("rc", &self.rc),
+            ("skip", &self.skip),
+            ("deselected", &self.deselected),
+        ];
+        for (kind, dids) in sets {
+            lines.extend(
+                dids.iter()
+                    .map(|&did| format!("{} {}", kind, tcx.def_path_str(did))),
+            );
+        }
+        lines.sort();
+        lines
+    }
+}
+
+/// Match `s` against a glob `pattern`, where `*` matches any sequence of characters (including
+/// `::`) and `?` matches any single character.
+fn glob_match(pattern: &str, s: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let s = s.chars().collect::<Vec<_>>();
+    // Position in `pattern` just after the last `*`, and the position in `s` it was matched at.
+    let mut backtrack = None;
+    let (mut pi, mut si) = (0, 0);
+    while si < s.len() {
+        match pattern.get(pi) {
+            Some('*') => {
+                pi += 1;
+                backtrack = Some((pi, si));
+            }
+            Some(&c) if c == '?' || c == s[si] => {
+                pi += 1;
+                si += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` match one more character, and try again.
+                Some((bpi, bsi)) => {
+                    pi = bpi;
+                    si = bsi + 1;
+                    backtrack = Some((bpi, bsi + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[pi..].iter().all(|&c| c == '*')
+}
@@ -1,13 +1,13 @@
 use self::atoms::{AllFacts, AtomMaps, Origin, Output, SubPoint};
-use crate::context::{AnalysisCtxt, PermissionSet};
-use crate::dataflow::DataflowConstraints;
+use crate::context::{AnalysisCtxt, PermissionSet, PointerId};
+use crate::dataflow::{self, DataflowConstraints};
 use crate::labeled_ty::{LabeledTy, LabeledTyCtxt};
 use crate::pointer_id::PointerTableMut;
 use crate::util::{describe_rvalue, RvalueDesc};
 use crate::AdtMetadataTable;
 use indexmap::{IndexMap, IndexSet};
 use rustc_hir::def_id::DefId;
-use rustc_middle::mir::{Body, LocalKind, Place, StatementKind, START_BLOCK};
+use rustc_middle::mir::{traversal, Body, LocalKind, Place, StatementKind, START_BLOCK};
 use rustc_middle::ty::{EarlyBoundRegion, List, Region, Ty, TyKind};
 use rustc_type_ir::RegionKind::ReEarlyBound;
 use std::collections::HashMap;
@@ -113,10 +113,15 @@ impl std::fmt::Debug for OriginParam {
     }
 }
 
+/// Remove `UNIQUE` from the pointers whose borrows conflict with other uses of the same memory,
+/// until polonius accepts the function.  Each pointer that loses `UNIQUE` is added to `removed`,
+/// with the statement that issued the conflicting borrow.
+#[allow(clippy::too_many_arguments)]
 pub fn borrowck_mir<'tcx>(
     acx: &AnalysisCtxt<'_, 'tcx>,
     dataflow: &DataflowConstraints,
     hypothesis: &mut PointerTableMut<PermissionSet>,
+    removed: &mut Vec<(PointerId, dataflow::Origin)>,
     name: &str,
     mir: &Body<'tcx>,
     adt_metadata: &AdtMetadataTable<'tcx>,
@@ -124,10 +129,10 @@ pub fn borrowck_mir<'tcx>(
 ) {
     let mut i = 0;
     loop {
-        eprintln!("run polonius");
+        log::debug!("run polonius");
         let (facts, maps, output) =
             run_polonius(acx, hypothesis, name, mir, adt_metadata, &field_tys);
-        eprintln!(
+        log::debug!(
             "polonius: iteration {}: {} errors, {} move_errors",
             i,
             output.errors.len(),
@@ -135,12 +140,12 @@ pub fn borrowck_mir<'tcx>(
         );
         i += 1;
 
+        // Each iteration either removes `UNIQUE` from at least one pointer or stops, so this
+        // terminates.  Functions with a tangled CFG, as from C code with many `goto`s, can need
+        // many iterations, since each one may only fix the errors on one path through a loop.
         if output.errors.is_empty() {
             break;
         }
-        if i >= 20 {
-            panic!()
-        }
 
         let mut changed = false;
         for loans in output.errors.values() {
@@ -170,21 +175,29 @@ pub fn borrowck_mir<'tcx>(
                     },
                     _ => panic!("loan {:?} was issued by non-assign stmt {:?}?", loan, stmt),
                 };
-                eprintln!("want to drop UNIQUE from pointer {:?}", ptr);
+                log::debug!("want to drop UNIQUE from pointer {:?}", ptr);
 
                 if hypothesis[ptr].contains(PermissionSet::UNIQUE) {
                     hypothesis[ptr].remove(PermissionSet::UNIQUE);
+                    removed.push((
+                        ptr,
+                        dataflow::Origin {
+                            loc: issued_loc,
+                            span: stmt.source_info.span,
+                            reason: "pointer is borrowed while another use of its memory is live",
+                        },
+                    ));
                     changed = true;
                 }
             }
         }
 
-        eprintln!("propagate");
+        log::debug!("propagate");
         changed |= dataflow.propagate(hypothesis);
-        eprintln!("done propagating");
+        log::debug!("done propagating");
 
         if !changed {
-            eprintln!(
+            log::debug!(
                 "{} unresolved borrowck errors in function {:?} (after {} iterations)",
                 output.errors.len(),
                 name,
@@ -215,12 +228,18 @@ fn run_polonius<'tcx>(
 
     //pretty::write_mir_fn(tcx, mir, &mut |_, _| Ok(()), &mut std::io::stdout()).unwrap();
 
-    // Populate `cfg_edge`
+    // Populate `cfg_edge`.  Blocks that can't be reached from the entry get no edges.  Built MIR
+    // keeps the dead code after a `return`, `break`, or `continue`, which is common in transpiled
+    // `goto`s, and a loan issued there would otherwise flow into live code.
+    let reachable = traversal::reachable_as_bitset(mir);
     for (bb, bb_data) in mir.basic_blocks().iter_enumerated() {
-        eprintln!("{:?}:", bb);
+        if !reachable.contains(bb) {
+            continue;
+        }
+        log::trace!("{:?}:", bb);
 
         for idx in 0..bb_data.statements.len() {
-            eprintln!("  {}: {:?}", idx, bb_data.statements[idx]);
+            log::trace!("  {}: {:?}", idx, bb_data.statements[idx]);
             let start = maps.point(bb, idx, SubPoint::Start);
             let mid = maps.point(bb, idx, SubPoint::Mid);
             let next_start = maps.point(bb, idx + 1, SubPoint::Start);
@@ -229,7 +248,7 @@ fn run_polonius<'tcx>(
         }
 
         let term_idx = bb_data.statements.len();
-        eprintln!("  {}: {:?}", term_idx, bb_data.terminator());
+        log::trace!("  {}: {:?}", term_idx, bb_data.terminator());
         let term_start = maps.point(bb, term_idx, SubPoint::Start);
         let term_mid = maps.point(bb, term_idx, SubPoint::Mid);
         facts.cfg_edge.push((term_start, term_mid));
@@ -286,16 +305,21 @@ fn run_polonius<'tcx>(
         local_ltys.push(lty);
     }
 
-    // Gather field permissions
+    // Gather field permissions.  Every pointer within a field's type has its own `PointerId`, so
+    // we record one `PermissionSet` per label, in the order `LabeledTyCtxt::relabel` visits them.
     let field_permissions = field_tys
         .iter()
         .map(|(did, lty)| {
-            let perm = if lty.label.is_none() {
-                PermissionSet::empty()
-            } else {
-                hypothesis[lty.label]
-            };
-            (*did, perm)
+            let mut perms = Vec::new();
+            lty.for_each_label_postorder(&mut |ptr| {
+                let perm = if ptr.is_none() {
+                    PermissionSet::empty()
+                } else {
+                    hypothesis[ptr]
+                };
+                perms.push(perm);
+            });
+            (*did, perms)
         })
         .collect::<HashMap<_, _>>();
 
@@ -356,7 +380,7 @@ fn assign_origins<'tcx>(
                         .iter()
                         .map(|o| {
                             let pairing = (*o, amaps.origin());
-                            eprintln!("pairing lifetime parameter with origin: {pairing:?}");
+                            log::debug!("pairing lifetime parameter with origin: {pairing:?}");
                             pairing
                         })
                         .collect()
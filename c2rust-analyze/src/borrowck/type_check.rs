@@ -2,7 +2,8 @@ use crate::borrowck::atoms::{AllFacts, AtomMaps, Loan, Origin, Path, Point, SubP
 use crate::borrowck::{LTy, LTyCtxt, Label, OriginParam};
 use crate::c_void_casts::CVoidCasts;
 use crate::context::PermissionSet;
-use crate::util::{self, ty_callee, Callee};
+use crate::keep_going;
+use crate::util::{self, ty_callee, AtomicOp, Callee};
 use crate::AdtMetadataTable;
 use assert_matches::assert_matches;
 use indexmap::IndexMap;
@@ -22,7 +23,7 @@ struct TypeChecker<'tcx, 'a> {
     maps: &'a mut AtomMaps<'tcx>,
     loans: &'a mut HashMap<Local, Vec<(Path, Loan, BorrowKind)>>,
     local_ltys: &'a [LTy<'tcx>],
-    field_permissions: &'a HashMap<DefId, PermissionSet>,
+    field_permissions: &'a HashMap<DefId, Vec<PermissionSet>>,
     local_decls: &'a IndexVec<Local, LocalDecl<'tcx>>,
     current_location: Location,
     adt_metadata: &'a AdtMetadataTable<'tcx>,
@@ -44,109 +45,114 @@ impl<'tcx> TypeChecker<'tcx, '_> {
             let base_origin_param_map: IndexMap<OriginParam, Origin> =
                 IndexMap::from_iter(base_lty.label.origin_params.to_vec());
             let field_def: &FieldDef = &base_adt_def.non_enum_variant().fields[field.index()];
-            let perm = self.field_permissions[&field_def.did];
+            let mut field_perms = self.field_permissions[&field_def.did].iter().copied();
             let base_metadata = &self.adt_metadata.table[&base_adt_def.did()];
             let field_metadata = &base_metadata.field_info[&field_def.did];
 
             self.ltcx.relabel(
                 field_metadata.origin_args,
-                &mut |flty| match flty.kind() {
-                    TyKind::Ref(..) | TyKind::RawPtr(..) => {
-                        let origin = {
-                            assert!(flty.label.len() == 1);
-                            Some(flty.label[0])
-                        }
-                        .map(|oa| {
-                            OriginParam::try_from(&oa)
-                                .expect("'static lifetimes not yet supported")
-                        })
-                        .and_then(|o| {
-                            eprintln!(
-                                "finding {o:?} in {base_adt_def:?} {base_origin_param_map:?}"
-                            );
-                            base_origin_param_map.get(&o)
-                        })
-                        .cloned();
-
-                        Label {
-                            origin,
-                            perm,
-                            origin_params: &[],
-                        }
-                    }
-                    TyKind::Adt(fadt_def, _) => {
-                        /*
-                            If we're in this block, it means the current field is an ADT.
-
-                            That field's type may have its own lifetime parameters. In the following:
-
-                            ```
-                            struct Foo<'a> {
-                                a: &'a i32
+                &mut |flty| {
+                    let perm = field_perms
+                        .next()
+                        .expect("field type and field metadata have different shapes");
+                    match flty.kind() {
+                        TyKind::Ref(..) | TyKind::RawPtr(..) => {
+                            let origin = {
+                                assert!(flty.label.len() == 1);
+                                Some(flty.label[0])
                             }
-
-                            struct Bar<'b> {
-                                foo: Foo<'b>
+                            .map(|oa| {
+                                OriginParam::try_from(&oa)
+                                    .expect("'static lifetimes not yet supported")
+                            })
+                            .and_then(|o| {
+                                log::trace!(
+                                    "finding {o:?} in {base_adt_def:?} {base_origin_param_map:?}"
+                                );
+                                base_origin_param_map.get(&o)
+                            })
+                            .cloned();
+
+                            Label {
+                                origin,
+                                perm,
+                                origin_params: &[],
                             }
+                        }
+                        TyKind::Adt(fadt_def, _) => {
+                            /*
+                                If we're in this block, it means the current field is an ADT.
+
+                                That field's type may have its own lifetime parameters. In the following:
+
+                                ```
+                                struct Foo<'a> {
+                                    a: &'a i32
+                                }
+
+                                struct Bar<'b> {
+                                    foo: Foo<'b>
+                                }
+
+                                fn some_func() {
+                                    let bar: Bar<'0> = ...;
+                                    bar.foo.a: &'? i32 = ...;
+                                }
+                                ```
+
+                                We want to know that the lifetime `'?` gets resolved to the concrete
+                                origin `'0`. To do this, a mapping needs to be made between `bar.foo`
+                                lifetime argument `'b` (which is already paired with concrete lifetime
+                                `'0`) and `Foo` lifetime parameter `'a`. This mapping is created below.
+                            */
+                            let mut field_origin_param_map = vec![];
+                            log::trace!("{:?}", fadt_def.did());
+                            let field_adt_metadata = if let Some(field_adt_metadata) = self.adt_metadata.table.get(&fadt_def.did()) {
+                                field_adt_metadata
+                            } else {
+                                return Label {
+                                    origin: None,
+                                    origin_params: &[],
+                                    perm
+                                };
+                            };
 
-                            fn some_func() {
-                                let bar: Bar<'0> = ...;
-                                bar.foo.a: &'? i32 = ...;
+                            for (field_lifetime_arg, field_struct_lifetime_param) in flty
+                                .label
+                                .iter().zip(field_adt_metadata.lifetime_params.iter())
+                            {
+                                let field_lifetime_param =
+                                    if let Ok(param) = OriginParam::try_from(field_lifetime_arg) {
+                                        param
+                                    } else {
+                                        panic!("'static lifetimes are not yet supported")
+                                    };
+
+                                if let Some((base_lifetime_param, og)) =
+                                    base_origin_param_map.get_key_value(&field_lifetime_param)
+                                {
+                                    log::trace!(
+                                            "mapping {base_adt_def:?} lifetime parameter {base_lifetime_param:?} to \
+                                            {base_adt_def:?}.{:} struct definition lifetime parameter {field_struct_lifetime_param:?}, \
+                                            corresponding to its lifetime parameter {field_lifetime_param:?} within {base_adt_def:?}",
+                                            field_def.name
+                                        );
+                                    field_origin_param_map.push((*field_struct_lifetime_param, *og));
+                                }
                             }
-                            ```
-
-                            We want to know that the lifetime `'?` gets resolved to the concrete
-                            origin `'0`. To do this, a mapping needs to be made between `bar.foo`
-                            lifetime argument `'b` (which is already paired with concrete lifetime
-                            `'0`) and `Foo` lifetime parameter `'a`. This mapping is created below.
-                        */
-                        let mut field_origin_param_map = vec![];
-                        eprintln!("{:?}", fadt_def.did());
-                        let field_adt_metadata = if let Some(field_adt_metadata) = self.adt_metadata.table.get(&fadt_def.did()) {
-                            field_adt_metadata
-                        } else {
-                            return Label {
+                            let origin_params= self.ltcx.arena().alloc_from_iter(field_origin_param_map.into_iter());
+                            Label {
                                 origin: None,
-                                origin_params: &[],
+                                origin_params,
                                 perm
-                            };
-                        };
-
-                        for (field_lifetime_arg, field_struct_lifetime_param) in flty
-                            .label
-                            .iter().zip(field_adt_metadata.lifetime_params.iter())
-                        {
-                            let field_lifetime_param =
-                                if let Ok(param) = OriginParam::try_from(field_lifetime_arg) {
-                                    param
-                                } else {
-                                    panic!("'static lifetimes are not yet supported")
-                                };
-
-                            if let Some((base_lifetime_param, og)) =
-                                base_origin_param_map.get_key_value(&field_lifetime_param)
-                            {
-                                eprintln!(
-                                        "mapping {base_adt_def:?} lifetime parameter {base_lifetime_param:?} to \
-                                        {base_adt_def:?}.{:} struct definition lifetime parameter {field_struct_lifetime_param:?}, \
-                                        corresponding to its lifetime parameter {field_lifetime_param:?} within {base_adt_def:?}",
-                                        field_def.name
-                                    );
-                                field_origin_param_map.push((*field_struct_lifetime_param, *og));
                             }
                         }
-                        let origin_params= self.ltcx.arena().alloc_from_iter(field_origin_param_map.into_iter());
-                        Label {
-                            origin: None,
-                            origin_params,
-                            perm
-                        }
-                    }
-                    _ => {
-                        Label {
-                            origin: None,
-                            origin_params: &[],
-                            perm
+                        _ => {
+                            Label {
+                                origin: None,
+                                origin_params: &[],
+                                perm
+                            }
                         }
                     }
                 },
@@ -156,7 +162,7 @@ impl<'tcx> TypeChecker<'tcx, '_> {
         for proj in pl.projection {
             lty = util::lty_project(lty, &proj, &mut adt_func);
         }
-        eprintln!("final label for {pl:?}: {:?}", lty);
+        log::trace!("final label for {pl:?}: {:?}", lty);
         lty
     }
 
@@ -183,7 +189,7 @@ impl<'tcx> TypeChecker<'tcx, '_> {
             .push((path, loan, borrow_kind));
         let point = self.current_point(SubPoint::Mid);
         self.facts.loan_issued_at.push((origin, loan, point));
-        eprintln!("issued loan {:?} = {:?} ({:?})", loan, pl, borrow_kind);
+        log::trace!("issued loan {:?} = {:?} ({:?})", loan, pl, borrow_kind);
         origin
     }
 
@@ -289,6 +295,18 @@ impl<'tcx> TypeChecker<'tcx, '_> {
                     // relations between the regions of the array and the regions of its elements
                     self.ltcx.label(ty, &mut |_ty| Label::default())
                 }
+                AggregateKind::Adt(..) => {
+                    let ty = rv.ty(self.local_decls, *self.ltcx);
+                    // TODO: create fresh origins for all pointers in `ty`, and generate subset
+                    // relations between the regions of the fields and the operands
+                    self.ltcx.label(ty, &mut |_ty| Label::default())
+                }
+                AggregateKind::Closure(..) => {
+                    let ty = rv.ty(self.local_decls, *self.ltcx);
+                    // TODO: create fresh origins for the captured pointers, and generate subset
+                    // relations between them and the closure's operands
+                    self.ltcx.label(ty, &mut |_ty| Label::default())
+                }
                 _ => panic!("unsupported rvalue AggregateKind {:?}", kind),
             },
 
@@ -308,12 +326,19 @@ impl<'tcx> TypeChecker<'tcx, '_> {
                 // relations between the regions of the array and the regions of its elements
                 self.ltcx.label(ty, &mut |_ty| Label::default())
             }
+            Rvalue::ThreadLocalRef(..) => {
+                // The address of a thread-local lives as long as the thread, so it carries no
+                // loan, like the address of any other static.
+                let ty = rv.ty(self.local_decls, *self.ltcx);
+                self.ltcx.label(ty, &mut |_| Label::default())
+            }
+
             ref rv => panic!("unsupported rvalue {:?}", rv),
         }
     }
 
     fn do_assign(&mut self, pl_lty: LTy<'tcx>, rv_lty: LTy<'tcx>) {
-        eprintln!("assign {:?} = {:?}", pl_lty, rv_lty);
+        log::trace!("assign {:?} = {:?}", pl_lty, rv_lty);
 
         let mut add_subset_base = |pl: Origin, rv: Origin| {
             let point = self.current_point(SubPoint::Mid);
@@ -339,6 +364,7 @@ impl<'tcx> TypeChecker<'tcx, '_> {
     }
 
     pub fn visit_statement(&mut self, stmt: &Statement<'tcx>) {
+        keep_going::set_current_span(stmt.source_info.span);
         // TODO(spernsteiner): other `StatementKind`s will be handled in the future
         #[allow(clippy::single_match)]
         match stmt.kind {
@@ -354,7 +380,8 @@ impl<'tcx> TypeChecker<'tcx, '_> {
     }
 
     pub fn visit_terminator(&mut self, term: &Terminator<'tcx>) {
-        eprintln!("borrowck: visit_terminator({:?})", term.kind);
+        keep_going::set_current_span(term.source_info.span);
+        log::trace!("borrowck: visit_terminator({:?})", term.kind);
         // TODO(spernsteiner): other `TerminatorKind`s will be handled in the future
         #[allow(clippy::single_match)]
         match term.kind {
@@ -367,7 +394,7 @@ impl<'tcx> TypeChecker<'tcx, '_> {
             } => {
                 let func_ty = func.ty(self.local_decls, *self.ltcx);
                 let callee = ty_callee(*self.ltcx, func_ty);
-                eprintln!("callee = {callee:?}");
+                log::trace!("callee = {callee:?}");
                 match callee {
                     Callee::Trivial => {}
                     Callee::UnknownDef { .. } => {
@@ -376,6 +403,9 @@ impl<'tcx> TypeChecker<'tcx, '_> {
                     Callee::LocalDef { .. } => {
                         // TODO
                     }
+                    Callee::LocalClosure { .. } | Callee::Virtual { .. } => {
+                        // TODO
+                    }
                     Callee::PtrOffset { .. } => {
                         // We handle this like a pointer assignment.
                         let pl_lty = self.visit_place(destination);
@@ -386,6 +416,45 @@ impl<'tcx> TypeChecker<'tcx, '_> {
                     Callee::SliceAsPtr { .. } => {
                         // TODO: handle this like a cast
                     }
+                    Callee::MaybeUninitAsPtr { .. } => {
+                        // The result is always raw, so it can't hold a loan.
+                    }
+                    Callee::VecAsPtr { .. }
+                    | Callee::BoxIntoRaw
+                    | Callee::BoxFromRaw
+                    | Callee::CStringIntoRaw
+                    | Callee::CStringFromRaw => {
+                        // A `Vec`, `Box`, or `CString` owns its memory, so the pointers it gives
+                        // out and takes in don't hold loans.
+                        let _pl_lty = self.visit_place(destination);
+                        let _rv_lty = assert_matches!(&args[..], [p] => {
+                            self.visit_operand(p)
+                        });
+                    }
+                    Callee::CStrFromPtr | Callee::CStrAsPtr => {
+                        // TODO: the result borrows from the argument
+                        let _pl_lty = self.visit_place(destination);
+                        let _rv_lty = assert_matches!(&args[..], [p] => {
+                            self.visit_operand(p)
+                        });
+                    }
+                    Callee::MaybeUninitNew => {
+                        let pl_lty = self.visit_place(destination);
+                        let rv_lty = assert_matches!(&args[..], [v] => {
+                            self.visit_operand(v)
+                        });
+                        self.do_assign(pl_lty.args[0], rv_lty);
+                    }
+                    Callee::MaybeUninitUninit | Callee::MaybeUninitZeroed | Callee::MemZeroed => {
+                        let _pl_lty = self.visit_place(destination);
+                    }
+                    Callee::MaybeUninitAssumeInit => {
+                        let pl_lty = self.visit_place(destination);
+                        let rv_lty = assert_matches!(&args[..], [v] => {
+                            self.visit_operand(v)
+                        });
+                        self.do_assign(pl_lty, rv_lty.args[0]);
+                    }
                     Callee::Malloc => {
                         // TODO
                     }
@@ -407,11 +476,82 @@ impl<'tcx> TypeChecker<'tcx, '_> {
                             self.visit_operand(p)
                         });
                     }
+                    Callee::AlignedAlloc | Callee::PosixMemalign | Callee::Mmap => {
+                        // TODO
+                    }
+                    Callee::Munmap => {
+                        let _pl_lty = self.visit_place(destination);
+                        let _rv_lty = assert_matches!(&args[..], [p, _] => {
+                            self.visit_operand(p)
+                        });
+                    }
+                    Callee::Qsort | Callee::Bsearch => {
+                        // TODO: the result of `bsearch` borrows from its array argument
+                        let _pl_lty = self.visit_place(destination);
+                        for arg in args {
+                            let _rv_lty = self.visit_operand(arg);
+                        }
+                    }
                     Callee::IsNull => {
                         let _rv_lty = assert_matches!(&args[..], [p] => {
                             self.visit_operand(p)
                         });
                     }
+                    Callee::Null => {
+                        let _pl_lty = self.visit_place(destination);
+                    }
+                    Callee::FmtArgument => {
+                        // The argument only borrows the value until it's formatted, within the
+                        // same statement.
+                        let _pl_lty = self.visit_place(destination);
+                        for arg in args {
+                            let _rv_lty = self.visit_operand(arg);
+                        }
+                    }
+                    Callee::FnPtrUnwrap => {
+                        // Function pointers don't hold loans.
+                        let _pl_lty = self.visit_place(destination);
+                        let _rv_lty = assert_matches!(&args[..], [p, ..] => {
+                            self.visit_operand(p)
+                        });
+                    }
+                    Callee::Transmute => {
+                        // TODO: a transmute between pointers with matching layouts is like an
+                        // assignment
+                        let _pl_lty = self.visit_place(destination);
+                        let _rv_lty = assert_matches!(&args[..], [p] => {
+                            self.visit_operand(p)
+                        });
+                    }
+                    Callee::AtomicIntrinsic { op } => {
+                        // We handle these like a load from or store to `*args[0]`.
+                        let pl_lty = self.visit_place(destination);
+                        let arg_ltys = args
+                            .iter()
+                            .map(|arg| self.visit_operand(arg))
+                            .collect::<Vec<_>>();
+                        let pointee_lty = arg_ltys[0].args[0];
+                        match op {
+                            AtomicOp::Load => self.do_assign(pl_lty, pointee_lty),
+                            AtomicOp::Store => self.do_assign(pointee_lty, arg_ltys[1]),
+                            AtomicOp::Modify => {
+                                self.do_assign(pointee_lty, arg_ltys[1]);
+                                self.do_assign(pl_lty, pointee_lty);
+                            }
+                            AtomicOp::CompareExchange => {
+                                self.do_assign(pointee_lty, arg_ltys[2]);
+                                self.do_assign(pl_lty.args[0], pointee_lty);
+                            }
+                        }
+                    }
+                    Callee::AtomicPtr => {
+                        // The pointers it takes and returns are always raw, so they can't hold
+                        // loans.
+                        let _pl_lty = self.visit_place(destination);
+                        for arg in args {
+                            let _rv_lty = self.visit_operand(arg);
+                        }
+                    }
                 }
             }
             // TODO(spernsteiner): handle other `TerminatorKind`s
@@ -428,7 +568,7 @@ pub fn visit_body<'tcx>(
     maps: &mut AtomMaps<'tcx>,
     loans: &mut HashMap<Local, Vec<(Path, Loan, BorrowKind)>>,
     local_ltys: &[LTy<'tcx>],
-    field_permissions: &HashMap<DefId, PermissionSet>,
+    field_permissions: &HashMap<DefId, Vec<PermissionSet>>,
     mir: &Body<'tcx>,
     adt_metadata: &AdtMetadataTable<'tcx>,
     c_void_casts: &CVoidCasts<'tcx>,
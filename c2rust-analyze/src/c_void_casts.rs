@@ -19,13 +19,16 @@ pub enum CVoidCastDirection {
     /// From [`*c_void`](core::ffi::c_void) to another pointer type.
     ///
     /// This is used immediately after allocating,
-    /// after both [`Callee::Malloc`], [`Callee::Calloc`], and [`Callee::Realloc`].
+    /// after [`Callee::Malloc`], [`Callee::Calloc`], [`Callee::Realloc`],
+    /// [`Callee::AlignedAlloc`], and [`Callee::Mmap`],
+    /// and on the result of [`Callee::Bsearch`].
     From,
 
     /// To [`*c_void`](core::ffi::c_void) from another pointer type.
     ///
     /// This is used immediately before freeing,
-    /// before both [`Callee::Free`] and [`Callee::Realloc`].
+    /// before [`Callee::Free`], [`Callee::Realloc`], and [`Callee::Munmap`],
+    /// and on the array and key arguments of [`Callee::Qsort`] and [`Callee::Bsearch`].
     To,
 }
 
@@ -37,17 +40,35 @@ impl CVoidCastDirection {
     /// * [`Callee::Malloc`]
     /// * [`Callee::Calloc`]
     /// * [`Callee::Realloc`]
+    /// * [`Callee::AlignedAlloc`]
+    /// * [`Callee::Mmap`]
+    /// * [`Callee::Bsearch`]
     ///
     /// And these [`Callee`]s are [`CVoidCastDirection::To`]:
     /// * [`Callee::Free`]
     /// * [`Callee::Realloc`]
-    pub fn from_callee(callee: Callee) -> &'static [Self] {
+    /// * [`Callee::Munmap`]
+    /// * [`Callee::Qsort`]
+    /// * [`Callee::Bsearch`]
+    pub fn from_callee(callee: &Callee) -> &'static [Self] {
         use CVoidCastDirection::*;
         use Callee::*;
         match callee {
-            Malloc | Calloc => &[From][..],
-            Realloc => &[To, From][..],
-            Free => &[To][..],
+            Malloc | Calloc | AlignedAlloc | Mmap => &[From][..],
+            Realloc | Bsearch => &[To, From][..],
+            Free | Munmap | Qsort => &[To][..],
+            _ => &[],
+        }
+    }
+
+    /// For [`Callee`]s that take a [`*c_void`](core::ffi::c_void), return the indices of the
+    /// arguments that are [`CVoidCastDirection::To`] casts.
+    pub fn to_args(callee: &Callee) -> &'static [usize] {
+        use Callee::*;
+        match callee {
+            Realloc | Free | Munmap | Qsort => &[0],
+            // `bsearch(key, base, ...)`
+            Bsearch => &[0, 1],
             _ => &[],
         }
     }
@@ -161,15 +182,15 @@ pub struct CVoidCast<'tcx> {
 #[derive(Default, Clone, Debug)]
 pub struct CVoidCastsUniDirectional<'tcx> {
     /// Mapping from location of a call that either
-    /// produces or consumes a [`CVoidPtr`] to its
-    /// succeeding or preceding [`CVoidCast`]
-    calls: HashMap<Location, CVoidCast<'tcx>>,
+    /// produces or consumes [`CVoidPtr`]s to their
+    /// succeeding or preceding [`CVoidCast`]s
+    calls: HashMap<Location, Vec<CVoidCast<'tcx>>>,
     /// Set of locations where [`CVoidCast`]s occur.
     casts: HashSet<Location>,
 }
 
 impl<'tcx> CVoidCastsUniDirectional<'tcx> {
-    /// Get the adjusted [`Place`] from a cast at a particulat [`Location`].
+    /// Get the adjusted [`Place`] from a cast of `place` at a particulat [`Location`].
     ///
     /// Otherwise, the same `place` is returned, as no adjustments are necessary.
     pub fn get_adjusted_place_or_default_to(
@@ -177,20 +198,19 @@ impl<'tcx> CVoidCastsUniDirectional<'tcx> {
         loc: Location,
         place: Place<'tcx>,
     ) -> Place<'tcx> {
-        *self
-            .calls
+        self.calls
             .get(&loc)
-            .map(|cast| {
-                assert_eq!(cast.c_void_ptr.place, place);
-                &cast.other_ptr
-            })
-            .unwrap_or(&place)
+            .and_then(|casts| casts.iter().find(|cast| cast.c_void_ptr.place == place))
+            .map_or(place, |cast| cast.other_ptr)
     }
 
     /// Tracks the [Location] of the use of a casted pointer in a [TerminatorKind::Call]
     pub fn insert_call(&mut self, loc: Location, cast: CVoidCast<'tcx>) {
-        assert!(!self.calls.contains_key(&loc));
-        self.calls.insert(loc, cast);
+        let casts = self.calls.entry(loc).or_default();
+        assert!(casts
+            .iter()
+            .all(|other| other.c_void_ptr.place != cast.c_void_ptr.place));
+        casts.push(cast);
     }
 
     /// Tracks the [Location] of void pointer [Rvalue::Cast]
@@ -394,6 +414,8 @@ impl<'tcx> CVoidCasts<'tcx> {
     /// * `calloc`
     /// * `realloc`
     /// * `free`
+    /// * `qsort`
+    /// * `bsearch`
     ///
     /// and insert their casts to and from [`*c_void`].
     ///
@@ -418,27 +440,41 @@ impl<'tcx> CVoidCasts<'tcx> {
                 _ => continue,
             };
             let func_ty = func.ty(&body.local_decls, tcx);
+            let callee = ty_callee(tcx, func_ty);
 
-            for direction in CVoidCastDirection::from_callee(ty_callee(tcx, func_ty))
-                .iter()
-                .copied()
-            {
+            let directions = CVoidCastDirection::from_callee(&callee).iter().copied();
+            let c_void_ptrs = directions.flat_map(|direction| {
                 use CVoidCastDirection::*;
-                let c_void_ptr = match direction {
-                    From => destination,
-                    To => args[0]
-                        .place()
-                        .expect("Casts to/from null pointer are not yet supported"),
+                let places = match direction {
+                    From => vec![destination],
+                    To => CVoidCastDirection::to_args(&callee)
+                        .iter()
+                        .map(|&i| {
+                            args[i]
+                                .place()
+                                .expect("Casts to/from null pointer are not yet supported")
+                        })
+                        .collect(),
                 };
+                places.into_iter().map(move |place| (direction, place))
+            });
+            for (direction, c_void_ptr) in c_void_ptrs {
+                use CVoidCastDirection::*;
                 let c_void_ptr = CVoidPtr::checked(c_void_ptr, &body.local_decls, tcx);
                 let cast = match direction {
                     // For [`CVoidCastDirection::From`], we only count
                     // a cast from `*c_void` to an arbitrary type in the subsequent block,
-                    // searching forward.
-                    From => Self::find_first_cast(
-                        &body.basic_blocks()[target.unwrap()].statements,
-                        c_void_ptr,
-                    ),
+                    // searching forward.  If that block can also be entered some other way, as
+                    // at a `goto` label, the cast doesn't always follow the call.
+                    From => match target {
+                        Some(target) if body.basic_blocks.predecessors()[target].len() == 1 => {
+                            Self::find_first_cast(
+                                &body.basic_blocks()[target].statements,
+                                c_void_ptr,
+                            )
+                        }
+                        _ => None,
+                    },
                     // For [`CVoidCastDirection::To`], we only count
                     // a cast to `*c_void` from an arbitrary type in the same block,
                     // searching backwards.
@@ -0,0 +1,349 @@
+//! Incremental analysis cache, for `--cache-dir DIR`.
+//!
+//! Solving the permission constraints, and running Polonius in particular, is the slowest part of
+//! the analysis.  The cache records the final permissions of the pointers in each function, keyed
+//! by a hash of the function's MIR, so that re-running the analysis after editing a few functions
+//! only has to re-solve the functions that changed and the ones they interact with.
+//!
+//! A function's results also depend on the global pointers its constraints mention, such as
+//! those in its own signature, the signatures of its callees, and struct fields, which other
+//! functions constrain as well.  So each cache entry also records the final permissions of those
+//! pointers, identified by their position in the signature or field type.  While solving, an
+//! unchanged function is skipped whenever its global pointers have the same permissions as in its
+//! cache entry, since its cached results are a fixpoint of its constraints for exactly those
+//! permissions.  Global pointers start out with their cached permissions if every function
+//! connected to them, directly or through other global pointers, is unchanged, so untouched parts
+//! of the crate are skipped from the start.  Other global pointers start out as usual, and the
+//! unchanged functions that mention them are skipped only once they settle on their old values.
+//!
+//! The results also depend on inputs other than the source: the `--extern-models`, `--pdg`,
+//! `--rewrite-plan`, and `--rewrite-styles` files, the `--assume` and `--function` options, and the
+//! `#[c2rust::*]` annotations.  These are hashed together into a configuration key, and a cache
+//! written with a different one is ignored.  Imported summaries only affect global pointers, whose
+//! cached permissions are checked as above.
+use crate::annotations::Annotations;
+use crate::context::{GlobalAnalysisCtxt, GlobalAssignment, LocalAssignment, PermissionSet};
+use crate::dataflow::DataflowConstraints;
+use crate::options::Options;
+use crate::pointer_id::{LocalPointerTable, PointerId};
+use crate::rewrite_plan;
+use crate::util;
+use rustc_hash::FxHasher;
+use rustc_hir::def_id::{LocalDefId, LOCAL_CRATE};
+use rustc_middle::mir::Body;
+use rustc_middle::ty::{TyCtxt, WithOptConstParam};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::hash::Hasher;
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+
+/// The contents of a cache file.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct CacheFile {
+    /// The version of `c2rust-analyze` that wrote the cache.  Caches from other versions are
+    /// ignored.
+    version: String,
+    /// Hash of the options, input files, and annotations the results depend on.  Caches with a
+    /// different key are ignored.
+    config_key: u64,
+    /// Entries by the path of each function.
+    functions: BTreeMap<String, FnEntry>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct FnEntry {
+    /// Hash of the function's MIR and signature.
+    key: u64,
+    /// Final permissions of the function's local pointers, in order.
+    local_perms: Vec<u16>,
+    /// Final permissions of the global pointers mentioned by the function, by name.
+    global_perms: BTreeMap<String, u16>,
+}
+
+/// What we know about a function in the current run.
+struct FnState {
+    path: String,
+    key: u64,
+    globals: Vec<PointerId>,
+    /// Whether the cache entry for this function can be used.
+    hit: bool,
+}
+
+#[derive(Default)]
+pub struct Cache {
+    /// The cache file, or `None` if caching is disabled.
+    path: Option<PathBuf>,
+    old: CacheFile,
+    new: CacheFile,
+    /// Stable names for global pointers, of the form `<item> <position> <index>`.
+    global_names: HashMap<PointerId, String>,
+    fns: HashMap<LocalDefId, FnState>,
+}
+
+/// Hash the parts of `mir` that affect the analysis.  Spans are left out, so that editing one
+/// function doesn't invalidate the functions that follow it in the same file.
+fn mir_key(tcx: TyCtxt, mir: &Body) -> u64 {
+    let mut hasher = FxHasher::default();
+    let mut write = |s: String| hasher.write(s.as_bytes());
+    write(format!("{:?}", util::body_fn_sig(tcx, mir.source.def_id())));
+    for decl in &mir.local_decls {
+        write(format!("{:?}", decl.ty));
+    }
+    for bb_data in mir.basic_blocks() {
+        for stmt in &bb_data.statements {
+            write(format!("{:?}", stmt.kind));
+        }
+        write(format!("{:?}", bb_data.terminator().kind));
+    }
+    hasher.finish()
+}
+
+/// Hash the inputs other than the source that affect the analysis of every function.
+fn config_key(tcx: TyCtxt, options: &Options, annotations: &Annotations) -> u64 {
+    let mut hasher = FxHasher::default();
+    let files = [
+        &options.extern_models,
+        &options.pdg,
+        &options.rewrite_plan,
+        &options.rewrite_styles,
+    ];
+    for path in files {
+        // A missing file makes the analysis fail anyway.
+        let contents = path.as_ref().map(|path| fs::read(path).unwrap_or_default());
+        hasher.write_usize(contents.as_ref().map_or(0, |c| c.len() + 1));
+        hasher.write(contents.as_deref().unwrap_or_default());
+    }
+    let assume = options.assume.iter().map(|a| a.name()).collect::<Vec<_>>();
+    hasher.write(format!("{:?} {:?}", assume, options.functions).as_bytes());
+    for line in annotations.describe(tcx) {
+        hasher.write_usize(line.len());
+        hasher.write(line.as_bytes());
+    }
+    hasher.finish()
+}
+
+impl Cache {
+    pub fn load(tcx: TyCtxt, options: &Options, annotations: &Annotations) -> Cache {
+        let dir = match options.cache_dir {
+            Some(ref x) => x,
+            None => return Cache::default(),
+        };
+        let path = dir.join(format!("{}.json", tcx.crate_name(LOCAL_CRATE)));
+        let version = env!("CARGO_PKG_VERSION").to_owned();
+        let config_key = config_key(tcx, options, annotations);
+        let old = File::open(&path)
+            .ok()
+            .and_then(|f| serde_json::from_reader::<_, CacheFile>(BufReader::new(f)).ok())
+            .filter(|old| old.version == version && old.config_key == config_key)
+            .unwrap_or_default();
+        Cache {
+            path: Some(path),
+            old,
+            new: CacheFile {
+                version,
+                config_key,
+                functions: BTreeMap::new(),
+            },
+            ..Cache::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Check which functions are unchanged since the cache was written, and initialize the
+    /// global pointers that only unchanged functions depend on.  `fns` lists each function with
+    /// its constraints and its number of local pointers.  This must be called after the
+    /// pointers are renumbered by the equivalence analysis.
+    pub fn prepare<'a>(
+        &mut self,
+        gacx: &GlobalAnalysisCtxt,
+        fns: impl IntoIterator<Item = (LocalDefId, &'a DataflowConstraints, usize)>,
+        gasn: &mut GlobalAssignment,
+    ) {
+        if !self.is_enabled() {
+            return;
+        }
+        let tcx = gacx.tcx;
+
+        // Several names can refer to the same pointer after renumbering.  Use the smallest, so
+        // the choice doesn't depend on `HashMap` order.
+        for site in rewrite_plan::sites(gacx) {
+            let item = tcx.def_path_str(site.item);
+            let labels = site.lty.iter().map(|lty| lty.label);
+            for (i, ptr) in labels.filter(|ptr| !ptr.is_none()).enumerate() {
+                let name = format!("{} {} {}", item, site.position, i);
+                let old = self.global_names.entry(ptr).or_insert_with(|| name.clone());
+                if name < *old {
+                    *old = name;
+                }
+            }
+        }
+
+        for (ldid, dataflow, num_local_pointers) in fns {
+            let mir = tcx.mir_built(WithOptConstParam::unknown(ldid));
+            let mir = mir.borrow();
+            let path = tcx.def_path_str(ldid.to_def_id());
+            let key = mir_key(tcx, &mir);
+
+            let lsig = &gacx.fn_sigs[&ldid.to_def_id()];
+            let sig_ptrs = lsig
+                .inputs
+                .iter()
+                .chain(Some(&lsig.output))
+                .flat_map(|lty| lty.iter().map(|lty| lty.label));
+            let mut globals = dataflow
+                .pointers()
+                .chain(sig_ptrs)
+                .filter(|ptr| ptr.is_global())
+                .collect::<Vec<_>>();
+            globals.sort();
+            globals.dedup();
+
+            let hit = match self.old.functions.get(&path) {
+                Some(entry) => {
+                    entry.key == key
+                        && entry.local_perms.len() == num_local_pointers
+                        && globals.iter().all(|ptr| {
+                            self.global_names
+                                .get(ptr)
+                                .map_or(false, |name| entry.global_perms.contains_key(name))
+                        })
+                }
+                None => false,
+            };
+            self.fns.insert(
+                ldid,
+                FnState {
+                    path,
+                    key,
+                    globals,
+                    hit,
+                },
+            );
+        }
+
+        // Find the global pointers that are connected only to unchanged functions, and set them
+        // to their cached permissions.
+        let mut fns_of_global = HashMap::<PointerId, Vec<LocalDefId>>::new();
+        for (&ldid, state) in &self.fns {
+            for &ptr in &state.globals {
+                fns_of_global.entry(ptr).or_default().push(ldid);
+            }
+        }
+        let mut visited = HashSet::new();
+        for &start in self.fns.keys() {
+            if visited.contains(&start) {
+                continue;
+            }
+            let mut component = vec![start];
+            let mut component_globals = HashSet::new();
+            visited.insert(start);
+            let mut i = 0;
+            while i < component.len() {
+                for &ptr in &self.fns[&component[i]].globals {
+                    if !component_globals.insert(ptr) {
+                        continue;
+                    }
+                    for &ldid in &fns_of_global[&ptr] {
+                        if visited.insert(ldid) {
+                            component.push(ldid);
+                        }
+                    }
+                }
+                i += 1;
+            }
+
+            if !component.iter().all(|ldid| self.fns[ldid].hit) {
+                continue;
+            }
+            for &ldid in &component {
+                let state = &self.fns[&ldid];
+                let entry = &self.old.functions[&state.path];
+                for &ptr in &state.globals {
+                    let name = &self.global_names[&ptr];
+                    gasn.perms[ptr] = PermissionSet::from_bits_truncate(entry.global_perms[name]);
+                }
+            }
+        }
+
+        let num_hits = self.fns.values().filter(|state| state.hit).count();
+        log::debug!(
+            "cache: {} of {} functions unchanged",
+            num_hits,
+            self.fns.len()
+        );
+    }
+
+    /// If the cached results for `ldid` apply to the current permissions of its global pointers,
+    /// copy them into `lasn` and return `true`.  Otherwise, the function must be solved as usual.
+    pub fn try_restore(
+        &self,
+        ldid: LocalDefId,
+        gasn: &GlobalAssignment,
+        lasn: &mut LocalAssignment,
+    ) -> bool {
+        let state = match self.fns.get(&ldid) {
+            Some(x) if x.hit => x,
+            _ => return false,
+        };
+        let entry = &self.old.functions[&state.path];
+        let globals_match = state
+            .globals
+            .iter()
+            .all(|&ptr| gasn.perms[ptr].bits() == entry.global_perms[&self.global_names[&ptr]]);
+        if !globals_match {
+            return false;
+        }
+        let perms = entry
+            .local_perms
+            .iter()
+            .map(|&bits| PermissionSet::from_bits_truncate(bits))
+            .collect();
+        lasn.perms = LocalPointerTable::from_raw(perms);
+        true
+    }
+
+    /// Record the final results for `ldid` in the new cache.
+    pub fn record(&mut self, ldid: LocalDefId, gasn: &GlobalAssignment, lasn: &LocalAssignment) {
+        let state = match self.fns.get(&ldid) {
+            Some(x) => x,
+            None => return,
+        };
+        // Functions with unnamed global pointers can't be matched up in the next run.
+        if !state
+            .globals
+            .iter()
+            .all(|ptr| self.global_names.contains_key(ptr))
+        {
+            return;
+        }
+        let entry = FnEntry {
+            key: state.key,
+            local_perms: lasn.perms.iter().map(|(_, p)| p.bits()).collect(),
+            global_perms: state
+                .globals
+                .iter()
+                .map(|&ptr| (self.global_names[&ptr].clone(), gasn.perms[ptr].bits()))
+                .collect(),
+        };
+        self.new.functions.insert(state.path.clone(), entry);
+    }
+
+    /// Write the new cache.
+    pub fn save(&self) {
+        let path = match self.path {
+            Some(ref x) => x,
+            None => return,
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).unwrap();
+        }
+        let file = File::create(path)
+            .unwrap_or_else(|e| panic!("failed to create cache {}: {}", path.display(), e));
+        serde_json::to_writer(BufWriter::new(file), &self.new).unwrap();
+    }
+}
@@ -0,0 +1,103 @@
+//! Support for analyzing every crate in a `cargo` workspace in one invocation.
+//!
+//! Running `c2rust-analyze cargo [ARGS...]` (or `cargo c2rust-analyze [ARGS...]`, if the binary is
+//! installed as `cargo-c2rust-analyze`) runs `cargo check [ARGS...]` with `$RUSTC_WRAPPER` set to
+//! this binary.  `cargo` then invokes us in place of `rustc` for every crate it builds.  Crates
+//! belonging to the workspace (those `cargo` marks with `$CARGO_PRIMARY_PACKAGE`) are analyzed as
+//! they're compiled; dependencies and build scripts are compiled normally.
+//!
+//! `cargo` compiles crates in dependency order, so each workspace crate is analyzed after all the
+//! workspace crates it depends on.  Unless another `--summary-dir` is given, each crate exports a
+//! [`summary`](crate::summary) of its functions to `summaries` in the [`TARGET_DIR`] and imports
+//! those of the crates analyzed before it, so calls into other workspace crates are analyzed
+//! using the callees' results instead of being treated as calls to unknown functions.
+//!
+//! `cargo` skips crates it considers up to date, so a crate is
+//! only re-analyzed when it changes.  Remove the [`TARGET_DIR`] to force a full re-analysis.
+use crate::annotations;
+use crate::driver::AnalysisCallbacks;
+use crate::options::Options;
+use rustc_driver::{RunCompiler, TimePassesCallbacks};
+use std::env;
+use std::ffi::OsString;
+use std::path::Path;
+use std::process::{self, Command};
+
+const RUSTC_WRAPPER_VAR: &str = "RUSTC_WRAPPER";
+
+/// The `cargo` target directory used for the analysis build, relative to the current directory.
+/// This is kept separate from the normal `target` directory so that `cargo` doesn't consider the
+/// normal build artifacts up to date and skip running the analysis.
+pub const TARGET_DIR: &str = "target/c2rust-analyze";
+
+/// Check whether `cargo` invoked this process as a `$RUSTC_WRAPPER`.
+pub fn is_rustc_wrapper(own_exe: &Path) -> bool {
+    env::var_os(RUSTC_WRAPPER_VAR).as_deref() == Some(own_exe.as_os_str())
+}
+
+/// Check whether `args` request the `cargo` wrapper mode.  This is the case when the first
+/// argument is `cargo`, or `c2rust-analyze` when run as a `cargo` subcommand.
+pub fn is_cargo_wrapper(args: &[String]) -> bool {
+    matches!(
+        args.get(1).map(|s| s.as_str()),
+        Some("cargo") | Some("c2rust-analyze")
+    )
+}
+
+/// Run `cargo check` on the workspace, with this binary as the `$RUSTC_WRAPPER`.  `args` are the
+/// arguments to this process; everything after the `cargo` argument is forwarded to `cargo`.
+pub fn run_cargo_wrapper(own_exe: &Path, args: &[String], options: &Options) -> ! {
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| OsString::from("cargo"));
+    let target_dir = env::var_os("CARGO_TARGET_DIR").unwrap_or_else(|| TARGET_DIR.into());
+    let mut options = options.clone();
+    if options.summary_dir.is_none() {
+        // `cargo` doesn't run `rustc` in the current directory, so this must be absolute.
+        let cwd = env::current_dir().unwrap();
+        options.summary_dir = Some(cwd.join(&target_dir).join("summaries"));
+    }
+    let (options_var, options_value) = options.to_env();
+    let mut cmd = Command::new(cargo);
+    cmd.arg("check")
+        .args(&args[2..])
+        .env(RUSTC_WRAPPER_VAR, own_exe)
+        .env(options_var, options_value)
+        .env("CARGO_TARGET_DIR", target_dir);
+    let status = cmd
+        .status()
+        .unwrap_or_else(|e| panic!("failed to run {cmd:?}: {e}"));
+    if !status.success() {
+        eprintln!("error ({status}) running: {cmd:?}");
+    }
+    process::exit(status.code().unwrap_or(1));
+}
+
+/// Check whether the current `$RUSTC_WRAPPER` invocation is for a crate that should be analyzed.
+/// We analyze crates from the workspace, but not their build scripts.
+fn should_analyze(rustc_args: &[String]) -> bool {
+    if env::var_os("CARGO_PRIMARY_PACKAGE").is_none() {
+        return false;
+    }
+    let is_build_script = rustc_args
+        .windows(2)
+        .any(|w| w[0] == "--crate-name" && w[1] == "build_script_build");
+    !is_build_script
+}
+
+/// Run as a `$RUSTC_WRAPPER`.  `cargo` passes the path to the real `rustc` as the first argument,
+/// followed by the arguments for `rustc`.
+pub fn run_rustc_wrapper(
+    args: &[String],
+    options: Options,
+) -> rustc_interface::interface::Result<()> {
+    // Drop our own path, so the `rustc` path takes the place of the program name.
+    let rustc_args = &args[1..];
+    if should_analyze(rustc_args) {
+        let mut rustc_args = rustc_args.to_owned();
+        rustc_args.extend(annotations::RUSTC_ARGS.iter().map(|&s| s.to_owned()));
+        RunCompiler::new(&rustc_args, &mut AnalysisCallbacks::new(options)).run()
+    } else {
+        // Compile everything else with the same `rustc_driver` that performs the analysis, so the
+        // metadata of dependencies is always compatible with the crates being analyzed.
+        RunCompiler::new(rustc_args, &mut TimePassesCallbacks::default()).run()
+    }
+}
@@ -0,0 +1,151 @@
+//! Analysis of a crate under several `cfg` configurations, for `--cfg-set`.
+//!
+//! `rustc` only sees the code enabled by one configuration, so the analysis only sees that code
+//! too.  A rewrite that's right for it can be wrong for another configuration: a pointer that's
+//! only read under `unix` may be written in a `#[cfg(windows)]` block, and so can't become `&T`.
+//! With `--cfg-set SET`, given once for each configuration of interest, the crate is analyzed
+//! once under each set, where `SET` is a comma-separated list of cfgs in `--cfg` syntax, such as
+//! `unix,feature="foo"`.  The usual report is printed for every run.
+//!
+//! Every run analyzes the same source files, so a rewrite is identified by the bytes it replaces
+//! and its new text.  By default (`--cfg-rewrites=common`), only the rewrites that every run makes
+//! are applied, and the rest are listed along with the sets that make them.  With
+//! `--cfg-rewrites=per-cfg`, the rewrites of each set are output separately instead:
+//! `--rewrite-diff` prints one diff per set, and `--rewrite-output-dir DIR` writes the files of
+//! set `N` under `DIR/cfg-N`.  Rewriting in place isn't possible in this mode.
+//!
+//! Outputs other than the rewritten files, such as the rewrite plan and the JSON results, are
+//! written by each run in turn.  `--cfg-set` isn't supported in `cargo` wrapper mode, where the
+//! cfgs come from `cargo`.
+use crate::driver::{self, AnalysisError};
+use crate::options::{CfgRewrites, Options};
+use crate::rewrite::{self, SourceEdit};
+use std::collections::HashMap;
+use std::fs;
+
+/// Split a `--cfg-set` value into its cfgs, ignoring commas inside quotes.
+fn parse(set: &str) -> Vec<String> {
+    let mut cfgs = Vec::new();
+    let mut cur = String::new();
+    let mut in_quotes = false;
+    for c in set.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                cfgs.push(cur.trim().to_owned());
+                cur.clear();
+                continue;
+            }
+            _ => {}
+        }
+        cur.push(c);
+    }
+    cfgs.push(cur.trim().to_owned());
+    cfgs.retain(|cfg| !cfg.is_empty());
+    cfgs
+}
+
+/// The line number of the start of `edit`, for messages.
+fn line_of(edit: &SourceEdit) -> usize {
+    match fs::read_to_string(&edit.path) {
+        Ok(src) => src[..edit.lo.min(src.len())].matches('\n').count() + 1,
+        Err(_) => 0,
+    }
+}
+
+/// Each distinct edit made by the runs `runs`, with the indices of the runs that make it, in the
+/// order they're first made.
+fn group_edits(runs: &[Vec<SourceEdit>]) -> Vec<(&SourceEdit, Vec<usize>)> {
+    let mut index = HashMap::<&SourceEdit, usize>::new();
+    let mut groups = Vec::<(&SourceEdit, Vec<usize>)>::new();
+    for (i, edits) in runs.iter().enumerate() {
+        for edit in edits {
+            let j = *index.entry(edit).or_insert_with(|| {
+                groups.push((edit, Vec::new()));
+                groups.len() - 1
+            });
+            groups[j].1.push(i);
+        }
+    }
+    groups
+}
+
+/// Analyze the crate given by the `rustc` command line `args` under each of the cfg sets in
+/// `options`, and output the rewrites as `options` requests.
+pub fn run(args: &[String], options: &Options) -> Result<(), AnalysisError> {
+    let mode = options.rewrite_mode();
+    if options.cfg_rewrites == CfgRewrites::PerCfg && mode.in_place {
+        panic!("--rewrite-in-place can't be used with --cfg-rewrites=per-cfg");
+    }
+
+    // Each run returns its edits instead of applying them.
+    let mut run_options = options.clone();
+    run_options.rewrite_in_place = false;
+    run_options.rewrite_diff = false;
+    run_options.rewrite_output_dir = None;
+    run_options.cfg_sets.clear();
+
+    let mut runs = Vec::new();
+    for (i, set) in options.cfg_sets.iter().enumerate() {
+        eprintln!("=== cfg set {}: {} ===", i, set);
+        let mut set_args = args.to_owned();
+        for cfg in parse(set) {
+            set_args.push("--cfg".to_owned());
+            set_args.push(cfg);
+        }
+        runs.push(driver::analyze(&set_args, run_options.clone())?.edits);
+    }
+
+    eprintln!("\n=== cfg coverage ===");
+    match options.cfg_rewrites {
+        CfgRewrites::Common => {
+            let mut common = Vec::new();
+            let mut partial = Vec::new();
+            for (edit, sets) in group_edits(&runs) {
+                if sets.len() == runs.len() {
+                    common.push(edit.clone());
+                } else {
+                    partial.push((edit, sets));
+                }
+            }
+            partial.sort_by_key(|&(edit, _)| (&edit.path, edit.lo));
+            for (edit, sets) in &partial {
+                let sets = sets.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+                eprintln!(
+                    "skipped rewrite at {}:{}: only made under cfg sets {}: {:?}",
+                    edit.path.display(),
+                    line_of(edit),
+                    sets.join(", "),
+                    edit.text
+                );
+            }
+            eprintln!(
+                "{} rewrites made under all {} cfg sets, {} skipped",
+                common.len(),
+                runs.len(),
+                partial.len()
+            );
+            if mode.is_enabled() {
+                rewrite::apply_source_edits(&common, &mode);
+            }
+        }
+        CfgRewrites::PerCfg => {
+            for (i, edits) in runs.iter().enumerate() {
+                eprintln!("cfg set {}: {} rewrites", i, edits.len());
+                if !mode.is_enabled() {
+                    continue;
+                }
+                if mode.diff {
+                    println!("# cfg set {}: {}", i, options.cfg_sets[i]);
+                }
+                let mut set_mode = mode.clone();
+                set_mode.output_dir = mode
+                    .output_dir
+                    .as_ref()
+                    .map(|dir| dir.join(format!("cfg-{}", i)));
+                rewrite::apply_source_edits(edits, &set_mode);
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,4 +1,7 @@
+use crate::annotations::Annotations;
 use crate::c_void_casts::CVoidCasts;
+use crate::extern_models::ExternModels;
+use crate::fn_ptr_fields::FnPtrFields;
 use crate::labeled_ty::{LabeledTy, LabeledTyCtxt};
 use crate::pointer_id::{
     GlobalPointerTable, LocalPointerTable, NextGlobalPointerId, NextLocalPointerId, PointerTable,
@@ -62,9 +65,76 @@ bitflags! {
         /// way, and it can't be freely discarded (or its inverse freely added) as is the case for
         /// everything in `PermissionSet`.
         const CELL = 0x0001;
+        /// The pointer must be left as a raw pointer, because the user asked for it with
+        /// `#[c2rust::keep_raw]`.
+        const FIXED = 0x0002;
+        /// The pointer may be null, so it's rewritten to an `Option` type such as `Option<&T>`.
+        /// This is inferred separately from the `PermissionSet`: a pointer is `NULLABLE` if it's
+        /// ever assigned a null constant or checked with `is_null`, or if it's derived from
+        /// another `NULLABLE` pointer.
+        const NULLABLE = 0x0004;
+        /// The pointer is the unique owner of a heap allocation, and is rewritten to `Box<T>`.
+        /// This is set on `UNIQUE` pointers that flow to `free`, as long as ownership is never
+        /// split between two owning pointers or taken from a borrowed pointer.
+        const OWNED = 0x0008;
+        /// The pointer is an `OWNED` pointer to a buffer that's resized with `realloc`, and is
+        /// rewritten to `Vec<T>` instead of `Box<T>`.
+        const VEC = 0x0010;
+        /// The pointer points to a heap allocation that has several owners and is never freed,
+        /// such as an object that's reference-counted by hand or simply leaked.
+        const SHARED = 0x0020;
+        /// The user asked for `SHARED` pointers to be rewritten to `Rc<RefCell<T>>` with
+        /// `#[c2rust::rc]`.  This has no effect on pointers that aren't `SHARED`.
+        const RC = 0x0040;
+        /// The pointer is never null.  This is a "must" property, unlike `NULLABLE`: it's set on
+        /// pointers produced by taking the address of a place, and on pointers that only ever
+        /// receive values from other `NON_NULL` pointers.  `FIXED` pointers with this flag are
+        /// rewritten to `NonNull<T>` instead of being left unchanged.
+        const NON_NULL = 0x0080;
+        /// The pointer points to `c_void`.  If it's accessed as bytes, through casts to `*mut u8`
+        /// or `*mut i8`, it's rewritten to `&[u8]` or `&mut [u8]`.  Otherwise it's a type-erased
+        /// context pointer, which stays raw.
+        const CVOID = 0x0100;
+        /// The pointer may point to heap memory, from `malloc` or a similar allocator.  Only heap
+        /// pointers can become `Box` or `Vec`.
+        const HEAP = 0x0200;
+        /// The pointer may point to a local variable.  Stack pointers can only become references,
+        /// never owning pointers.
+        const STACK = 0x0400;
+        /// The pointer may point to a static, or to other static memory such as a string literal.
+        /// Pointers to statics can't be owned either, and a safe pointer to a `static mut` needs
+        /// `&'static` or interior mutability.
+        const STATIC = 0x0800;
+        /// The pointer may point into a `MaybeUninit<T>` whose contents aren't initialized yet,
+        /// as the result of `MaybeUninit::as_ptr` or `as_mut_ptr` does.  A reference to such
+        /// memory would let safe code read it before it's initialized, so these pointers stay
+        /// raw.
+        const UNINIT = 0x1000;
+        /// The pointer's address is used directly, by comparing it with another pointer or
+        /// casting it to an integer, as for `p == q` or `p as usize`.  These uses don't constrain
+        /// the pointer's permissions, and the flag doesn't propagate to other pointers.  If the
+        /// pointer becomes a safe type, the uses are rewritten to go through a raw pointer, since
+        /// comparing two references compares what they point to.
+        const COMPARE = 0x2000;
+        /// The pointer is used for atomic accesses, which other threads may make at the same
+        /// time, so it can't be `UNIQUE` and never becomes `&mut`.  Written atomic pointers
+        /// become `&Cell<T>` like other non-unique pointers that are written through.
+        const ATOMIC = 0x4000;
+        /// The pointer points to a NUL-terminated C string.  This is set on the results of
+        /// `CStr::as_ptr` and `CString::into_raw`, and on byte pointers that only ever receive
+        /// values from other `NUL_TERMINATED` pointers.  Such pointers that are never accessed
+        /// directly become `&CStr`, or `CString` if they own the string.
+        const NUL_TERMINATED = 0x8000;
     }
 }
 
+/// The flags that record where a pointer's memory may come from, and whether it may be
+/// uninitialized.  Unlike the other flags, a pointer can have several of these at once.
+pub const PROVENANCE_FLAGS: FlagSet = FlagSet::HEAP
+    .union(FlagSet::STACK)
+    .union(FlagSet::STATIC)
+    .union(FlagSet::UNINIT);
+
 pub use crate::pointer_id::PointerId;
 
 pub type LTy<'tcx> = LabeledTy<'tcx, PointerId>;
@@ -84,6 +154,22 @@ pub struct GlobalAnalysisCtxt<'tcx> {
 
     pub field_tys: HashMap<DefId, LTy<'tcx>>,
 
+    /// The labeled type of each static, which is shared by all the functions that use it.
+    pub static_tys: HashMap<DefId, LTy<'tcx>>,
+
+    /// User-supplied models for calls to extern functions.
+    pub extern_models: ExternModels,
+
+    /// `#[c2rust::...]` attributes found in the crate.
+    pub annotations: Annotations,
+
+    /// For each function, the indices of the parameters that will be turned into return values.
+    /// See [`crate::out_params`].
+    pub out_params: HashMap<DefId, Vec<usize>>,
+
+    /// The functions stored into each function pointer field.  See [`crate::fn_ptr_fields`].
+    pub fn_ptr_fields: FnPtrFields,
+
     next_ptr_id: NextGlobalPointerId,
 }
 
@@ -115,6 +201,11 @@ impl<'tcx> GlobalAnalysisCtxt<'tcx> {
             lcx: LabeledTyCtxt::new(tcx),
             fn_sigs: HashMap::new(),
             field_tys: HashMap::new(),
+            static_tys: HashMap::new(),
+            extern_models: ExternModels::default(),
+            annotations: Annotations::collect(tcx),
+            out_params: HashMap::new(),
+            fn_ptr_fields: FnPtrFields::default(),
             next_ptr_id: NextGlobalPointerId::new(),
         }
     }
@@ -152,6 +243,11 @@ impl<'tcx> GlobalAnalysisCtxt<'tcx> {
             lcx,
             ref mut fn_sigs,
             ref mut field_tys,
+            ref mut static_tys,
+            extern_models: _,
+            annotations: _,
+            out_params: _,
+            fn_ptr_fields: _,
             ref mut next_ptr_id,
         } = *self;
 
@@ -169,6 +265,10 @@ impl<'tcx> GlobalAnalysisCtxt<'tcx> {
             *labeled_field = remap_lty_pointers(lcx, map, labeled_field);
         }
 
+        for lty in static_tys.values_mut() {
+            *lty = remap_lty_pointers(lcx, map, lty);
+        }
+
         *next_ptr_id = counter;
     }
 
@@ -182,6 +282,58 @@ impl<'tcx> GlobalAnalysisCtxt<'tcx> {
             self.assign_pointer_to_field(field);
         }
     }
+
+    pub fn assign_pointer_to_static(&mut self, did: DefId) {
+        let lty = self.assign_pointer_ids(self.tcx.type_of(did));
+        self.static_tys.insert(did, lty);
+    }
+
+    /// Compute equivalence constraints for the union `did`.  All fields of a union share the same
+    /// underlying storage, so a pointer written through one field can be read back through any
+    /// other.  To account for this, the pointers in the same position of fields with the same
+    /// layout, like `*mut *mut i32` and `*mut *mut u32`, are placed in the same equivalence class,
+    /// as are the outermost pointers of all pointer-typed fields.  Pointers at different depths
+    /// stay distinct.
+    pub fn union_field_equiv_constraints(&self, did: DefId) -> Vec<(PointerId, PointerId)> {
+        let adt_def = self.tcx.adt_def(did);
+        assert!(adt_def.is_union(), "{adt_def:?} is not a union");
+
+        // Which positions of a type are pointers, and how many arguments each position has.
+        fn layout(lty: LTy) -> Vec<(bool, usize)> {
+            lty.iter()
+                .map(|lty| (!lty.label.is_none(), lty.args.len()))
+                .collect()
+        }
+
+        let mut equiv = Vec::new();
+        let mut first_ptr = None;
+        let mut first_of_layout = Vec::<(Vec<(bool, usize)>, LTy<'tcx>)>::new();
+        for field in adt_def.all_fields() {
+            let lty = self.field_tys[&field.did];
+            let field_layout = layout(lty);
+            match first_of_layout.iter().find(|(l, _)| *l == field_layout) {
+                Some(&(_, first)) => {
+                    equiv.extend(
+                        first
+                            .iter()
+                            .zip(lty.iter())
+                            .map(|(a, b)| (a.label, b.label))
+                            .filter(|(a, _)| !a.is_none()),
+                    );
+                }
+                None => {
+                    if !lty.label.is_none() {
+                        match first_ptr {
+                            Some(first) => equiv.push((first, lty.label)),
+                            None => first_ptr = Some(lty.label),
+                        }
+                    }
+                    first_of_layout.push((field_layout, lty));
+                }
+            }
+        }
+        equiv
+    }
 }
 
 impl<'a, 'tcx> AnalysisCtxt<'a, 'tcx> {
@@ -272,9 +424,11 @@ impl<'a, 'tcx> AnalysisCtxt<'a, 'tcx> {
                 let (pointee_lty, proj, ptr) = match desc {
                     RvalueDesc::Project { base, proj } => {
                         let base_lty = self.type_of(base);
-                        eprintln!(
+                        log::trace!(
                             "rvalue = {:?}, desc = {:?}, base_lty = {:?}",
-                            rv, desc, base_lty
+                            rv,
+                            desc,
+                            base_lty
                         );
                         (
                             self.project(base_lty, &PlaceElem::Deref),
@@ -324,7 +478,17 @@ impl<'a, 'tcx> AnalysisCtxt<'a, 'tcx> {
             Rvalue::Ref(..) | Rvalue::AddressOf(..) => {
                 unreachable!("should be handled by describe_rvalue case above")
             }
-            Rvalue::ThreadLocalRef(..) => todo!("type_of ThreadLocalRef"),
+            Rvalue::ThreadLocalRef(did) => {
+                // Normally labeled in `rvalue_tys`; this is the unlabeled fallback.
+                let ty = rv.ty(self, self.tcx());
+                match self.gacx.static_tys.get(&did) {
+                    Some(&static_lty) => {
+                        let args = self.lcx().mk_slice(&[static_lty]);
+                        self.lcx().mk(ty, args, PointerId::NONE)
+                    }
+                    None => self.lcx().label(ty, &mut |_| PointerId::NONE),
+                }
+            }
             Rvalue::Cast(CastKind::Pointer(PointerCast::Unsize), ref op, ty) => {
                 let pointee_ty = match *ty.kind() {
                     TyKind::Ref(_, ty, _) => ty,
@@ -354,6 +518,13 @@ impl<'a, 'tcx> AnalysisCtxt<'a, 'tcx> {
                         let args = self.lcx().mk_slice(&[pointee_lty]);
                         self.lcx().mk(ty, args, op_lty.label)
                     }
+                    TyKind::Dynamic(..) => {
+                        // The pointer itself keeps its `PointerId`, but the trait object is
+                        // opaque, so nothing inside it is labeled.
+                        let pointee_lty = self.lcx().mk(pointee_ty, &[], PointerId::NONE);
+                        let args = self.lcx().mk_slice(&[pointee_lty]);
+                        self.lcx().mk(ty, args, op_lty.label)
+                    }
                     _ => label_no_pointers(self, ty),
                 }
             }
@@ -375,6 +546,14 @@ impl<'a, 'tcx> AnalysisCtxt<'a, 'tcx> {
                     // same structure.
                     return self.lcx().mk(ty, op_lty.args, op_lty.label);
                 }
+                if util::is_byte_cast(self.tcx(), op_lty.ty, ty) {
+                    // Casts between `*mut c_void` and `*mut u8` keep the same pointer, so the
+                    // byte accesses are counted as uses of the `c_void` pointer.  Neither pointee
+                    // contains pointers.
+                    let pointee_lty = label_no_pointers(self, ty_pointee.unwrap());
+                    let args = self.lcx().mk_slice(&[pointee_lty]);
+                    return self.lcx().mk(ty, args, op_lty.label);
+                }
 
                 label_no_pointers(self, ty)
             }
@@ -396,7 +575,7 @@ impl<'a, 'tcx> AnalysisCtxt<'a, 'tcx> {
         let adt_func = |_lty: LTy, adt_def: AdtDef, field: Field| {
             let field_def = &adt_def.non_enum_variant().fields[field.index()];
             let field_def_name = field_def.name;
-            eprintln!("projecting into {adt_def:?}.{field_def_name:}");
+            log::trace!("projecting into {adt_def:?}.{field_def_name:}");
             let res = *self.gacx.field_tys.get(&field_def.did).unwrap_or_else(|| {
                 panic!("Could not find {adt_def:?}.{field_def_name:?} in field type map")
             });
@@ -582,8 +761,7 @@ impl Assignment<'_> {
         self.global.flags.and(&self.local.flags)
     }
 
-    #[allow(dead_code)]
-    pub fn _flags_mut(&mut self) -> PointerTableMut<FlagSet> {
+    pub fn flags_mut(&mut self) -> PointerTableMut<FlagSet> {
         self.global.flags.and_mut(&mut self.local.flags)
     }
 
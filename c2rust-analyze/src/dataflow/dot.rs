@@ -0,0 +1,240 @@
+//! Graphviz DOT dump of the dataflow constraints, for `--debug-constraints=dot`.
+//!
+//! Each pointer is a node, labeled with the local it belongs to, its final permissions, and the
+//! `AllPerms` and `NoPerms` constraints placed on it directly.  Local pointers are grouped in a
+//! cluster for their function, and global pointers are drawn outside of any cluster.  A
+//! `Subset(a, b)` constraint is drawn as an edge from `b` to `a`, in the direction the value
+//! flows, labeled with the reason for the constraint and its line.
+//!
+//! The dump is taken after pointers are renumbered by equivalence class, so each node stands for
+//! a whole union-find class.  Its label lists the original `PointerId`s that were merged into
+//! it, along with the reasons they were unified.
+use super::{Constraint, DataflowConstraints, Origin};
+use crate::context::{AnalysisCtxt, Assignment, PermissionSet, PointerId};
+use rustc_hir::def_id::LocalDefId;
+use rustc_middle::mir::Body;
+use rustc_middle::ty::TyCtxt;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+
+/// A pointer, qualified by its function if it's local.
+type Node = (Option<LocalDefId>, PointerId);
+
+fn node(ldid: LocalDefId, ptr: PointerId) -> Node {
+    if ptr.is_global() {
+        (None, ptr)
+    } else {
+        (Some(ldid), ptr)
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[derive(Default)]
+struct NodeInfo {
+    /// The local or field the pointer appears in.
+    what: Option<String>,
+    perms: PermissionSet,
+    /// Extra lines in the label, for constraints and unifications.
+    notes: Vec<String>,
+    /// The original pointers in this equivalence class.
+    members: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct ConstraintGraph {
+    nodes: BTreeMap<(Option<String>, PointerId), NodeInfo>,
+    /// The function name used in the node IDs of each function's local pointers.
+    fn_names: HashMap<LocalDefId, String>,
+    edges: Vec<(Node, Node, String)>,
+}
+
+impl ConstraintGraph {
+    fn key(&mut self, tcx: TyCtxt, (ldid, ptr): Node) -> (Option<String>, PointerId) {
+        let name = ldid.map(|ldid| {
+            self.fn_names
+                .entry(ldid)
+                .or_insert_with(|| tcx.def_path_str(ldid.to_def_id()))
+                .clone()
+        });
+        (name, ptr)
+    }
+
+    fn node_mut(&mut self, tcx: TyCtxt, n: Node) -> &mut NodeInfo {
+        let key = self.key(tcx, n);
+        self.nodes.entry(key).or_default()
+    }
+
+    /// Record the equivalence classes found by `crate::equiv`.  `map` gives the new `PointerId`
+    /// for each original pointer of `ldid`, or for each original global pointer if `ldid` is
+    /// `None`.  This must be called with the maps used for renumbering.
+    pub fn add_classes(
+        &mut self,
+        tcx: TyCtxt,
+        ldid: Option<LocalDefId>,
+        map: impl Iterator<Item = (PointerId, PointerId)>,
+    ) {
+        for (old, new) in map {
+            if new.is_none() {
+                continue;
+            }
+            let n = match ldid {
+                Some(ldid) => node(ldid, new),
+                None => (None, new),
+            };
+            // Local pointers merged into a global class are qualified by their function.
+            let member = match ldid {
+                Some(ldid) if new.is_global() => {
+                    format!("{}:{}", tcx.def_path_str(ldid.to_def_id()), old)
+                }
+                _ => old.to_string(),
+            };
+            self.node_mut(tcx, n).members.push(member);
+        }
+    }
+
+    /// Add the pointers and constraints of the function `mir`, with their final permissions.
+    pub fn add_fn<'tcx>(
+        &mut self,
+        acx: &AnalysisCtxt<'_, 'tcx>,
+        asn: &Assignment,
+        mir: &Body<'tcx>,
+        dataflow: &DataflowConstraints,
+    ) {
+        let tcx = acx.tcx();
+        let ldid = mir.source.def_id().expect_local();
+        let perms = asn.perms();
+        let line = |origin: &Origin| tcx.sess.source_map().lookup_char_pos(origin.span.lo()).line;
+
+        for (local, decl) in mir.local_decls.iter_enumerated() {
+            let desc = crate::describe_local(tcx, decl);
+            for (i, lty) in acx.local_tys[local].iter().enumerate() {
+                if !lty.label.is_local() {
+                    continue;
+                }
+                let what = if i == 0 {
+                    format!("{:?} ({})", local, desc)
+                } else {
+                    format!("in {:?} ({})", local, desc)
+                };
+                let info = self.node_mut(tcx, node(ldid, lty.label));
+                info.what.get_or_insert(what);
+                info.perms = perms[lty.label];
+            }
+        }
+
+        for ptr in dataflow.pointers() {
+            self.node_mut(tcx, node(ldid, ptr)).perms = perms[ptr];
+        }
+
+        for (c, origin) in dataflow.constraints.iter().zip(&dataflow.origins) {
+            match *c {
+                Constraint::Subset(a, b) => {
+                    let label = format!("{} (line {})", origin.reason, line(origin));
+                    self.edges.push((node(ldid, b), node(ldid, a), label));
+                }
+                Constraint::AllPerms(ptr, ps) => {
+                    let note = format!(
+                        "requires {:?}: {} (line {})",
+                        ps,
+                        origin.reason,
+                        line(origin)
+                    );
+                    self.node_mut(tcx, node(ldid, ptr)).notes.push(note);
+                }
+                Constraint::NoPerms(ptr, ps) => {
+                    let note = format!(
+                        "forbids {:?}: {} (line {})",
+                        ps,
+                        origin.reason,
+                        line(origin)
+                    );
+                    self.node_mut(tcx, node(ldid, ptr)).notes.push(note);
+                }
+                Constraint::Implies(a, a_ps, b, b_ps) => {
+                    let label = format!(
+                        "if {:?} then {:?}: {} (line {})",
+                        a_ps,
+                        b_ps,
+                        origin.reason,
+                        line(origin)
+                    );
+                    self.edges.push((node(ldid, a), node(ldid, b), label));
+                }
+            }
+        }
+
+        for &(a, _, ref origin) in &dataflow.equivs {
+            let note = format!("unified: {} (line {})", origin.reason, line(origin));
+            self.node_mut(tcx, node(ldid, a)).notes.push(note);
+        }
+    }
+
+    fn node_id(&self, (ldid, ptr): Node) -> String {
+        match ldid {
+            Some(ldid) => format!("\"{}:{}\"", escape(&self.fn_names[&ldid]), ptr),
+            None => format!("\"{}\"", ptr),
+        }
+    }
+
+    fn write_node(out: &mut String, id: &str, ptr: PointerId, info: &NodeInfo) {
+        let mut lines = vec![ptr.to_string()];
+        lines.extend(info.what.clone());
+        lines.push(format!("{:?}", info.perms));
+        if info.members.len() > 1 {
+            lines.push(format!("class: {}", info.members.join(", ")));
+        }
+        lines.extend(info.notes.iter().cloned());
+        let label = lines
+            .iter()
+            .map(|l| escape(l))
+            .collect::<Vec<_>>()
+            .join("\\l");
+        writeln!(out, "    {} [label=\"{}\\l\"];", id, label).unwrap();
+    }
+
+    /// Render the graph in DOT format.
+    pub fn render(&self, crate_name: &str) -> String {
+        let mut out = String::new();
+        writeln!(out, "digraph \"{}\" {{", escape(crate_name)).unwrap();
+        writeln!(out, "    node [shape=box, fontname=monospace];").unwrap();
+        writeln!(out, "    edge [fontname=monospace, fontsize=10];").unwrap();
+
+        let mut cluster = None;
+        for (i, ((fn_name, ptr), info)) in self.nodes.iter().enumerate() {
+            if fn_name.as_ref() != cluster {
+                if cluster.is_some() {
+                    writeln!(out, "    }}").unwrap();
+                }
+                if let Some(fn_name) = fn_name {
+                    writeln!(out, "    subgraph \"cluster_{}\" {{", i).unwrap();
+                    writeln!(out, "    label=\"{}\";", escape(fn_name)).unwrap();
+                }
+                cluster = fn_name.as_ref();
+            }
+            let id = match fn_name {
+                Some(fn_name) => format!("\"{}:{}\"", escape(fn_name), ptr),
+                None => format!("\"{}\"", ptr),
+            };
+            Self::write_node(&mut out, &id, *ptr, info);
+        }
+        if cluster.is_some() {
+            writeln!(out, "    }}").unwrap();
+        }
+
+        for (from, to, label) in &self.edges {
+            writeln!(
+                out,
+                "    {} -> {} [label=\"{}\"];",
+                self.node_id(*from),
+                self.node_id(*to),
+                escape(label)
+            )
+            .unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+        out
+    }
+}
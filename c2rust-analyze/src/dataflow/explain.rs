@@ -0,0 +1,399 @@
+//! Explanations of the final permissions of a pointer, for `--explain`.
+//!
+//! Each constraint records the statement that produced it (see [`Origin`]).  To explain why a
+//! pointer has a permission that propagates "up" along subset edges, such as `WRITE`, we search
+//! backward from the pointer along `Subset` constraints for a pointer that was directly required
+//! to have the permission, and print the chain of constraints found.  To explain why a pointer
+//! lacks `UNIQUE`, which propagates "down", we search forward instead, to a constraint or to a
+//! borrow the borrow checker rejected.  Global pointers can be constrained by any function, so the
+//! search covers the constraints of all functions.
+//!
+//! The same searches find the pointers whose constraints contradict each other (see
+//! [`conflicts`]), like a pointer that is freed but also shares its memory with another pointer.
+//! No safe type allows both, so the solver quietly leaves such a pointer raw.
+use super::{Constraint, DataflowConstraints, Origin};
+use crate::context::{PermissionSet, PointerId};
+use crate::pointer_id::PointerTable;
+use crate::pointer_names;
+use rustc_hir::def_id::LocalDefId;
+use rustc_middle::ty::TyCtxt;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Write as _;
+
+/// The constraints and final permissions of a single function.
+pub struct FnConstraints<'a> {
+    pub ldid: LocalDefId,
+    pub dataflow: &'a DataflowConstraints,
+    pub perms: PointerTable<'a, PermissionSet>,
+    /// The pointers the borrow checker removed `UNIQUE` from, with the conflicting borrow.
+    pub lost_unique: &'a [(PointerId, Origin)],
+}
+
+/// A pointer, qualified by its function if it's local.
+type Node = (Option<LocalDefId>, PointerId);
+
+fn node(ldid: LocalDefId, ptr: PointerId) -> Node {
+    if ptr.is_global() {
+        (None, ptr)
+    } else {
+        (Some(ldid), ptr)
+    }
+}
+
+/// Parse a pointer named on the command line, like `l3` or `g5`.
+pub fn parse_pointer(s: &str) -> Option<PointerId> {
+    let (kind, index) = s.split_at(1.min(s.len()));
+    let index = index.parse::<u32>().ok()?;
+    match kind {
+        "l" => Some(PointerId::local(index)),
+        "g" => Some(PointerId::global(index)),
+        _ => None,
+    }
+}
+
+/// The root cause of a permission, and the chain of constraints that carried it to a pointer.
+struct Trace<'a> {
+    ldid: LocalDefId,
+    origin: &'a Origin,
+    /// The lines of the explanation, from the root cause to the pointer.
+    lines: Vec<String>,
+}
+
+/// A pointer that must be freed, but isn't the unique owner of its allocation.
+pub struct Conflict {
+    /// The function the pointer belongs to, if it's local.
+    pub ldid: Option<LocalDefId>,
+    pub ptr: PointerId,
+    /// The constraint that requires the pointer to be freed.
+    pub freed: Origin,
+    /// The constraint or borrow that made it lose `UNIQUE`, if known.  The borrow checker's
+    /// reasons aren't known for functions restored from `--cache`.
+    pub shared: Option<Origin>,
+    /// A description of the pointer and both causes, for printing.
+    pub description: String,
+}
+
+struct Explainer<'a, 'tcx> {
+    tcx: TyCtxt<'tcx>,
+    fns: &'a [FnConstraints<'a>],
+}
+
+impl<'a, 'tcx> Explainer<'a, 'tcx> {
+    fn perms(&self, (ldid, ptr): Node) -> PermissionSet {
+        let f = match ldid {
+            Some(ldid) => self.fns.iter().find(|f| f.ldid == ldid),
+            None => self.fns.first(),
+        };
+        f.map_or(PermissionSet::empty(), |f| f.perms[ptr])
+    }
+
+    fn describe_node(&self, (ldid, ptr): Node) -> String {
+        pointer_names::set_current_fn(ldid);
+        match ldid {
+            Some(ldid) => format!("{} in {}", ptr, self.tcx.def_path_str(ldid.to_def_id())),
+            None => format!("{}", ptr),
+        }
+    }
+
+    fn describe_origin(&self, ldid: LocalDefId, origin: &Origin) -> String {
+        format!(
+            "{} at {} ({}, {:?})",
+            origin.reason,
+            self.tcx
+                .sess
+                .source_map()
+                .span_to_diagnostic_string(origin.span),
+            self.tcx.def_path_str(ldid.to_def_id()),
+            origin.loc,
+        )
+    }
+
+    /// Iterate over all constraints of all functions, with their origins.
+    fn constraints(&self) -> impl Iterator<Item = (LocalDefId, &'a Constraint, &'a Origin)> + 'a {
+        self.fns.iter().flat_map(|f| {
+            f.dataflow
+                .constraints
+                .iter()
+                .zip(&f.dataflow.origins)
+                .map(move |(c, o)| (f.ldid, c, o))
+        })
+    }
+
+    /// Find a constraint that `found` identifies as a root cause at `cur`.
+    fn find_root(
+        &self,
+        cur: Node,
+        found: impl Fn(LocalDefId, &Constraint) -> Option<PointerId>,
+    ) -> Option<(LocalDefId, &'a Origin)> {
+        self.constraints()
+            .find(|&(ldid, c, _)| found(ldid, c).map_or(false, |ptr| node(ldid, ptr) == cur))
+            .map(|(ldid, _, origin)| (ldid, origin))
+    }
+
+    /// Search from `start` for a root cause that `root` finds, following the `Subset` edges that
+    /// `step` returns.  Returns `None` if nothing was found.
+    fn search(
+        &self,
+        start: Node,
+        root: impl Fn(Node) -> Option<(LocalDefId, &'a Origin)>,
+        step: impl Fn(LocalDefId, &Constraint) -> Option<(Node, Node)>,
+    ) -> Option<Trace<'a>> {
+        let mut prev = HashMap::<Node, (Node, String)>::new();
+        let mut queue = VecDeque::from([start]);
+        prev.insert(start, (start, String::new()));
+        while let Some(cur) = queue.pop_front() {
+            if let Some((ldid, origin)) = root(cur) {
+                let mut lines = vec![format!(
+                    "{}: {}",
+                    self.describe_node(cur),
+                    self.describe_origin(ldid, origin)
+                )];
+                let mut n = cur;
+                while n != start {
+                    let (p, ref line) = prev[&n];
+                    lines.push(line.clone());
+                    n = p;
+                }
+                return Some(Trace {
+                    ldid,
+                    origin,
+                    lines,
+                });
+            }
+
+            for (ldid, c, origin) in self.constraints() {
+                let (from, to) = match step(ldid, c) {
+                    Some(x) => x,
+                    None => continue,
+                };
+                if from != cur || prev.contains_key(&to) {
+                    continue;
+                }
+                let (a, b) = match *c {
+                    Constraint::Subset(a, b) => (node(ldid, a), node(ldid, b)),
+                    _ => continue,
+                };
+                let line = format!(
+                    "{} receives its value from {}: {}",
+                    self.describe_node(a),
+                    self.describe_node(b),
+                    self.describe_origin(ldid, origin)
+                );
+                prev.insert(to, (cur, line));
+                queue.push_back(to);
+            }
+        }
+        None
+    }
+
+    fn trace_perm(&self, start: Node, perm: PermissionSet) -> Option<Trace<'a>> {
+        let has_perm = |n: Node| self.perms(n).contains(perm);
+        self.search(
+            start,
+            |cur| {
+                self.find_root(cur, |ldid, c| match *c {
+                    Constraint::AllPerms(ptr, perms) if perms.contains(perm) => Some(ptr),
+                    Constraint::Implies(a, a_perms, b, b_perms)
+                        if b_perms.contains(perm)
+                            && self.perms(node(ldid, a)).contains(a_perms) =>
+                    {
+                        Some(b)
+                    }
+                    _ => None,
+                })
+            },
+            // `Subset(a, b)` means `a` receives its value from `b`, so `b` gets the permissions
+            // of `a`.  Walk from `b` back to an `a` that has the permission.
+            |ldid, c| match *c {
+                Constraint::Subset(a, b) if has_perm(node(ldid, a)) => {
+                    Some((node(ldid, b), node(ldid, a)))
+                }
+                _ => None,
+            },
+        )
+    }
+
+    fn explain_perm(&self, start: Node, perm: PermissionSet) -> Vec<String> {
+        self.trace_perm(start, perm).map_or_else(
+            || vec!["no constraint requires this permission".to_owned()],
+            |t| t.lines,
+        )
+    }
+
+    fn trace_not_unique(&self, start: Node) -> Option<Trace<'a>> {
+        let lacks_unique = |n: Node| !self.perms(n).contains(PermissionSet::UNIQUE);
+        self.search(
+            start,
+            |cur| {
+                let root = self.find_root(cur, |_, c| match *c {
+                    Constraint::NoPerms(ptr, perms) if perms.contains(PermissionSet::UNIQUE) => {
+                        Some(ptr)
+                    }
+                    _ => None,
+                });
+                root.or_else(|| {
+                    self.fns.iter().find_map(|f| {
+                        f.lost_unique
+                            .iter()
+                            .find(|&&(ptr, _)| node(f.ldid, ptr) == cur)
+                            .map(|(_, origin)| (f.ldid, origin))
+                    })
+                })
+            },
+            // `a` loses `UNIQUE` if `b`, which it receives its value from, lacks it.
+            |ldid, c| match *c {
+                Constraint::Subset(a, b) if lacks_unique(node(ldid, b)) => {
+                    Some((node(ldid, a), node(ldid, b)))
+                }
+                _ => None,
+            },
+        )
+    }
+
+    fn explain_not_unique(&self, start: Node) -> Vec<String> {
+        self.trace_not_unique(start).map_or_else(
+            || vec!["removed by the borrow checker, because of a conflicting access".to_owned()],
+            |t| t.lines,
+        )
+    }
+
+    /// Check whether `n` is freed but shares its allocation, and if so, find both causes.
+    fn conflict(&self, n: Node) -> Option<Conflict> {
+        let perms = self.perms(n);
+        if !perms.contains(PermissionSet::FREE) || perms.contains(PermissionSet::UNIQUE) {
+            return None;
+        }
+        let freed = self.trace_perm(n, PermissionSet::FREE)?;
+        let shared = self.trace_not_unique(n);
+        let mut description = String::new();
+        writeln!(
+            description,
+            "{}: needs manual attention: it is freed, but isn't the unique owner of its allocation",
+            self.describe_node(n)
+        )
+        .unwrap();
+        writeln!(
+            description,
+            "  freed: {}",
+            self.describe_origin(freed.ldid, freed.origin)
+        )
+        .unwrap();
+        match shared {
+            Some(ref t) => writeln!(
+                description,
+                "  shared: {}",
+                self.describe_origin(t.ldid, t.origin)
+            )
+            .unwrap(),
+            None => writeln!(
+                description,
+                "  shared: by a borrow the borrow checker rejected"
+            )
+            .unwrap(),
+        }
+        Some(Conflict {
+            ldid: n.0,
+            ptr: n.1,
+            freed: *freed.origin,
+            shared: shared.map(|t| *t.origin),
+            description,
+        })
+    }
+
+    fn explain(&self, start: Node) -> String {
+        let mut out = String::new();
+        let perms = self.perms(start);
+        writeln!(
+            out,
+            "explanation for {}: {:?}",
+            self.describe_node(start),
+            perms
+        )
+        .unwrap();
+
+        for (ldid, f) in self.fns.iter().map(|f| (f.ldid, f)) {
+            for &(a, _, ref origin) in &f.dataflow.equivs {
+                // After renumbering, both pointers of an equivalence have the same ID.
+                if node(ldid, a) == start {
+                    writeln!(
+                        out,
+                        "  unified with another pointer: {}",
+                        self.describe_origin(ldid, origin)
+                    )
+                    .unwrap();
+                }
+            }
+        }
+
+        for perm in [
+            PermissionSet::READ,
+            PermissionSet::WRITE,
+            PermissionSet::OFFSET_ADD,
+            PermissionSet::OFFSET_SUB,
+            PermissionSet::FREE,
+        ] {
+            if !perms.contains(perm) {
+                continue;
+            }
+            writeln!(out, "  {:?}:", perm).unwrap();
+            for line in self.explain_perm(start, perm) {
+                writeln!(out, "    {}", line).unwrap();
+            }
+        }
+        if !perms.contains(PermissionSet::UNIQUE) {
+            writeln!(out, "  not UNIQUE:").unwrap();
+            for line in self.explain_not_unique(start) {
+                writeln!(out, "    {}", line).unwrap();
+            }
+        }
+        out
+    }
+}
+
+/// Explain the final permissions of `ptr`.  `ldid` is the function containing `ptr`, which is
+/// required if `ptr` is local.
+pub fn explain(
+    tcx: TyCtxt,
+    fns: &[FnConstraints],
+    ldid: Option<LocalDefId>,
+    ptr: PointerId,
+) -> String {
+    let start = match ldid {
+        Some(ldid) => node(ldid, ptr),
+        None if ptr.is_global() => (None, ptr),
+        None => {
+            return format!(
+                "local pointer {} needs a function name, as in `f:{}`\n",
+                ptr, ptr
+            )
+        }
+    };
+    Explainer { tcx, fns }.explain(start)
+}
+
+/// Find the pointers that must be freed, but lose `UNIQUE` because their memory is shared with
+/// another pointer.  Pointers whose conflicts have the same pair of causes, like the pointers
+/// along a chain of copies, are reported once.
+pub fn conflicts(tcx: TyCtxt, fns: &[FnConstraints]) -> Vec<Conflict> {
+    let explainer = Explainer { tcx, fns };
+    let mut seen_nodes = HashSet::new();
+    let mut seen_causes = HashSet::new();
+    let mut conflicts = Vec::new();
+    for f in fns {
+        for (ptr, _) in f.perms.iter() {
+            let n = node(f.ldid, ptr);
+            if !seen_nodes.insert(n) {
+                continue;
+            }
+            let conflict = match explainer.conflict(n) {
+                Some(x) => x,
+                None => continue,
+            };
+            let causes = (conflict.freed.span, conflict.shared.map(|o| o.span));
+            if seen_causes.insert(causes) {
+                conflicts.push(conflict);
+            }
+        }
+    }
+    conflicts
+}
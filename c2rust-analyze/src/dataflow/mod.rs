@@ -1,9 +1,20 @@
-use std::mem;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
 
-use crate::context::{AnalysisCtxt, Assignment, FlagSet, PermissionSet, PointerId};
+use crate::context::{
+    AnalysisCtxt, Assignment, FlagSet, GlobalAssignment, PermissionSet, PointerId, PROVENANCE_FLAGS,
+};
+use crate::options::Assumption;
 use crate::pointer_id::{OwnedPointerTable, PointerTable, PointerTableMut};
-use rustc_middle::mir::Body;
+use crate::use_after_free::{self, Move, UseAfterFree};
+use crate::util;
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::{Body, Local, Location};
+use rustc_span::Span;
 
+pub mod dot;
+pub mod explain;
 mod type_check;
 
 #[derive(Clone, Debug)]
@@ -14,37 +25,324 @@ enum Constraint {
     AllPerms(PointerId, PermissionSet),
     /// Pointer `.0` must not have any of the permissions in `.1`.
     NoPerms(PointerId, PermissionSet),
+    /// If pointer `.0` has all the permissions in `.1`, pointer `.2` must have all the
+    /// permissions in `.3`.  The condition may only use permissions that propagation adds, not
+    /// `UNIQUE`, which it removes, so a constraint that has taken effect never has to be undone.
+    Implies(PointerId, PermissionSet, PointerId, PermissionSet),
+}
+
+/// The statement that produced a constraint, and why.  This is used to explain the final
+/// permissions of a pointer; see [`explain`].
+#[derive(Clone, Copy, Debug)]
+pub struct Origin {
+    pub loc: Location,
+    pub span: Span,
+    pub reason: &'static str,
+}
+
+/// The number of elements in an allocation, recovered from the size passed to the allocator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ElemCount {
+    /// The value of a local variable or argument.
+    Local(Local),
+    Const(u128),
+    Mul(Box<ElemCount>, Box<ElemCount>),
+}
+
+impl fmt::Display for ElemCount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ElemCount::Local(local) => write!(f, "{:?}", local),
+            ElemCount::Const(n) => write!(f, "{}", n),
+            ElemCount::Mul(ref a, ref b) => write!(f, "{} * {}", a, b),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct DataflowConstraints {
     constraints: Vec<Constraint>,
+    /// The origin of each entry in `constraints`.
+    origins: Vec<Origin>,
+    /// Pointers that may be null, because they're assigned a null constant or checked with
+    /// `is_null`.  These are the starting points for `NULLABLE` propagation.
+    nullable: Vec<PointerId>,
+    /// Input and output pointers of each `realloc` call.  These are the starting points for `VEC`
+    /// propagation.
+    reallocs: Vec<(PointerId, PointerId)>,
+    /// Pointers returned by `malloc`, `calloc`, and `realloc`.  These are the starting points for
+    /// `SHARED` propagation.
+    allocs: Vec<PointerId>,
+    /// The number of elements in each allocation from `malloc`, `calloc`, or `realloc` whose size
+    /// is a multiple of `size_of::<T>()` for the pointee type `T`, by the pointer it's assigned
+    /// to.  These are the lengths the `Vec` and slice rewrites need.
+    alloc_counts: Vec<(PointerId, ElemCount)>,
+    /// Pointers returned by allocators whose memory can't be managed by `Box`, such as `mmap` and
+    /// `aligned_alloc`.  These are never `OWNED`, so the pointers derived from them aren't either.
+    raw_allocs: Vec<PointerId>,
+    /// Pointers the user chose to rewrite to references, which are never `OWNED` either.
+    borrowed: Vec<PointerId>,
+    /// Pointers produced by taking the address of a place, which are never null.  These are the
+    /// starting points for `NON_NULL` propagation.
+    non_null: Vec<PointerId>,
+    /// The `Subset(a, b)` constraints of copies of `b` that only run after a null check found `b`
+    /// isn't null (see [`crate::null_checks`]).  These don't make `a` nullable.
+    checked_copies: Vec<(PointerId, PointerId)>,
+    /// Pointers that can't be given a safe type, because they're created from an integer or by a
+    /// transmute the analysis can't see through, and why.  These are the starting points for
+    /// `FIXED` propagation.
+    stuck_raw: Vec<(PointerId, Origin)>,
+    /// Pointers that must stay raw unless the user makes an assumption that rules out the
+    /// problem, and why.  [`DataflowConstraints::apply_assumptions`] adds them to `stuck_raw`.
+    conditional_stuck: Vec<(Assumption, PointerId, Origin)>,
+    /// Whether this function creates a pointer from an integer.
+    int_to_ptr: bool,
+    /// Whether this function contains inline assembly, which could break any of the
+    /// assumptions, so none of them apply to it.
+    inline_asm: bool,
+    /// Structs passed by value to extern functions, directly or nested in another struct passed
+    /// that way.  Their layout is part of the C ABI, so their fields keep their types.
+    ffi_structs: Vec<DefId>,
+    /// Pointers to local variables, static memory, or uninitialized memory, with the `STACK`,
+    /// `STATIC`, or `UNINIT` flag for each.
+    /// Along with `allocs` and `raw_allocs`, which get `HEAP`, these are the starting points for
+    /// provenance propagation.
+    provenance: Vec<(PointerId, FlagSet)>,
+    /// Pointers to `c_void`, which get the `CVOID` flag.
+    c_void: Vec<PointerId>,
+    /// Pointers that are compared or cast to an integer, which get the `COMPARE` flag.
+    compared: Vec<PointerId>,
+    /// Pointers used for atomic accesses, which get the `ATOMIC` flag.
+    atomic: Vec<PointerId>,
+    /// Pointers to NUL-terminated strings, such as the results of `CStr::as_ptr` and
+    /// `CString::into_raw`.  These are the starting points for `NUL_TERMINATED` propagation.
+    nul_terminated: Vec<PointerId>,
+    /// Local pointers to bytes, which are the only local pointers that can be `NUL_TERMINATED`.
+    byte_ptrs: Vec<PointerId>,
+    /// Pairs of pointers that were unified by the equivalence analysis, and why.  This is used
+    /// only for explanations; the unification itself is done by `crate::equiv`.
+    equivs: Vec<(PointerId, PointerId, Origin)>,
+    /// Accesses to a pointer stored in memory that another pointer points to, as `(outer, inner,
+    /// unique, origin)`.  `unique` is set if the access needs unique access to the inner pointer,
+    /// because the inner pointer is copied out or written through.  These are checked by
+    /// [`DataflowConstraints::add_nested_constraints`].
+    nested: Vec<(PointerId, PointerId, bool, Origin)>,
+    /// Pointers moved out of a local by a call that takes ownership of their memory, such as
+    /// `free` or `Box::from_raw`.
+    moves: Vec<Move>,
+    /// Uses of a local after a pointer was moved out of it, found by
+    /// [`crate::use_after_free::find`].  The moved pointers are never `OWNED`.
+    uses_after_free: Vec<UseAfterFree>,
 }
 
 impl DataflowConstraints {
-    fn add_subset(&mut self, a: PointerId, b: PointerId) {
+    fn add_subset(&mut self, a: PointerId, b: PointerId, origin: Origin) {
         self.constraints.push(Constraint::Subset(a, b));
+        self.origins.push(origin);
     }
 
-    fn add_all_perms(&mut self, ptr: PointerId, perms: PermissionSet) {
+    fn add_all_perms(&mut self, ptr: PointerId, perms: PermissionSet, origin: Origin) {
         self.constraints.push(Constraint::AllPerms(ptr, perms));
+        self.origins.push(origin);
     }
 
-    #[allow(dead_code)]
-    fn _add_no_perms(&mut self, ptr: PointerId, perms: PermissionSet) {
+    fn add_no_perms(&mut self, ptr: PointerId, perms: PermissionSet, origin: Origin) {
         self.constraints.push(Constraint::NoPerms(ptr, perms));
+        self.origins.push(origin);
+    }
+
+    fn add_implies(
+        &mut self,
+        a: PointerId,
+        a_perms: PermissionSet,
+        b: PointerId,
+        b_perms: PermissionSet,
+        origin: Origin,
+    ) {
+        assert!(!a_perms.contains(PermissionSet::UNIQUE));
+        self.constraints
+            .push(Constraint::Implies(a, a_perms, b, b_perms));
+        self.origins.push(origin);
+    }
+
+    fn add_equiv(&mut self, a: PointerId, b: PointerId, origin: Origin) {
+        self.equivs.push((a, b, origin));
+    }
+
+    fn add_nested(&mut self, outer: PointerId, inner: PointerId, unique: bool, origin: Origin) {
+        self.nested.push((outer, inner, unique, origin));
+    }
+
+    fn add_compared(&mut self, ptr: PointerId) {
+        self.compared.push(ptr);
+    }
+
+    fn add_atomic(&mut self, ptr: PointerId, origin: Origin) {
+        self.atomic.push(ptr);
+        self.add_no_perms(ptr, PermissionSet::UNIQUE, origin);
+    }
+
+    fn add_nullable(&mut self, ptr: PointerId) {
+        self.nullable.push(ptr);
+    }
+
+    fn add_realloc(&mut self, in_ptr: PointerId, out_ptr: PointerId) {
+        self.reallocs.push((in_ptr, out_ptr));
+    }
+
+    fn add_alloc(&mut self, ptr: PointerId) {
+        self.allocs.push(ptr);
+    }
+
+    fn add_alloc_count(&mut self, ptr: PointerId, count: ElemCount) {
+        self.alloc_counts.push((ptr, count));
+    }
+
+    fn add_raw_alloc(&mut self, ptr: PointerId) {
+        self.raw_allocs.push(ptr);
+    }
+
+    fn add_non_null(&mut self, ptr: PointerId) {
+        self.non_null.push(ptr);
+    }
+
+    fn add_checked_copy(&mut self, a: PointerId, b: PointerId) {
+        self.checked_copies.push((a, b));
+    }
+
+    fn add_nul_terminated(&mut self, ptr: PointerId) {
+        self.nul_terminated.push(ptr);
+    }
+
+    fn add_provenance(&mut self, ptr: PointerId, flags: FlagSet) {
+        self.provenance.push((ptr, flags));
+    }
+
+    fn add_move(&mut self, local: Local, ptr: PointerId, origin: Origin) {
+        self.moves.push(Move { local, ptr, origin });
+    }
+
+    fn add_stuck_raw(&mut self, ptr: PointerId, origin: Origin) {
+        self.stuck_raw.push((ptr, origin));
+    }
+
+    fn add_conditional_stuck(&mut self, a: Assumption, ptr: PointerId, origin: Origin) {
+        self.conditional_stuck.push((a, ptr, origin));
+    }
+
+    fn add_ffi_struct(&mut self, did: DefId) {
+        self.ffi_structs.push(did);
+    }
+
+    /// Whether this function creates a pointer from an integer.
+    pub fn creates_ptr_from_int(&self) -> bool {
+        self.int_to_ptr
+    }
+
+    /// Keep `ptr` from owning its target, because the user chose to rewrite it to a reference.
+    pub fn pin_borrowed(&mut self, ptr: PointerId) {
+        self.borrowed.push(ptr);
+    }
+
+    /// Add the pointers that must stay raw because the user didn't make some assumption to
+    /// `stuck_raw`.  Pointers cast to integers only matter if `int_to_ptr` is set, meaning some
+    /// function in the crate creates a pointer from an integer.  A pointer passed to an extern
+    /// function that may keep it can't be a reference, and neither can the pointers it's copied
+    /// from, since a copy of them outlives the call.  In a function with inline assembly, the
+    /// assumptions are ignored.
+    pub fn apply_assumptions(&mut self, assume: &[Assumption], int_to_ptr: bool) {
+        let mut srcs = HashMap::<PointerId, Vec<PointerId>>::new();
+        for (a, b) in self.subsets() {
+            srcs.entry(a).or_default().push(b);
+        }
+        for &(a, ptr, origin) in &self.conditional_stuck {
+            if assume.contains(&a) && !self.inline_asm {
+                continue;
+            }
+            match a {
+                Assumption::NoIntPtrRoundTrips if !int_to_ptr => {}
+                Assumption::ExternNoRetain => {
+                    let mut seen = HashSet::from([ptr]);
+                    let mut queue = VecDeque::from([ptr]);
+                    while let Some(cur) = queue.pop_front() {
+                        for &src in srcs.get(&cur).into_iter().flatten() {
+                            if seen.insert(src) {
+                                queue.push_back(src);
+                            }
+                        }
+                    }
+                    self.stuck_raw
+                        .extend(seen.into_iter().map(|ptr| (ptr, origin)));
+                }
+                _ => self.stuck_raw.push((ptr, origin)),
+            }
+        }
+    }
+
+    /// Add constraints to keep the types of nested pointers, like `**T`, consistent with each
+    /// other, and return whether any were added.
+    ///
+    /// Permissions are inferred for each pointer on its own, which can give an inner pointer a
+    /// type that can't be used through the outer one.  A `&mut T` can't be copied out of, or
+    /// written through, a place behind a shared `&` or `&Cell`, and can't be read through a
+    /// `&Cell` at all, since `Cell::get` only works on `Copy` types.  When that happens, the inner
+    /// pointer loses `UNIQUE`, so it becomes a `&Cell<T>` instead.  Callers should re-solve the
+    /// constraints after any are added, and repeat until nothing changes.
+    pub fn add_nested_constraints(&mut self, perms: PointerTable<PermissionSet>) -> bool {
+        let mut_perms = PermissionSet::UNIQUE | PermissionSet::WRITE;
+        let mut new = Vec::new();
+        for &(outer, inner, unique, origin) in &self.nested {
+            if !perms[inner].contains(mut_perms) || perms[outer].contains(mut_perms) {
+                continue;
+            }
+            let outer_cell = perms[outer].contains(PermissionSet::WRITE);
+            if unique || outer_cell {
+                let reason = if outer_cell {
+                    "pointer is accessed through a `Cell`, so it can't be `&mut`"
+                } else {
+                    "pointer is accessed through a shared pointer, so it can't be `&mut`"
+                };
+                new.push((inner, Origin { reason, ..origin }));
+            }
+        }
+        let changed = !new.is_empty();
+        for (ptr, origin) in new {
+            self.add_no_perms(ptr, PermissionSet::UNIQUE, origin);
+        }
+        changed
+    }
+
+    /// The pointers that must stay raw, and why.
+    pub fn stuck_raw(&self) -> &[(PointerId, Origin)] {
+        &self.stuck_raw
+    }
+
+    /// The structs passed by value to extern functions.
+    pub fn ffi_structs(&self) -> &[DefId] {
+        &self.ffi_structs
+    }
+
+    pub fn alloc_counts(&self) -> &[(PointerId, ElemCount)] {
+        &self.alloc_counts
+    }
+
+    pub fn uses_after_free(&self) -> &[UseAfterFree] {
+        &self.uses_after_free
     }
 
     /// Update the pointer permissions in `hypothesis` to satisfy these constraints.
     pub fn propagate(&self, hypothesis: &mut PointerTableMut<PermissionSet>) -> bool {
-        eprintln!("=== propagating ===");
-        eprintln!("constraints:");
-        for c in &self.constraints {
-            eprintln!("  {:?}", c);
-        }
-        eprintln!("hypothesis:");
-        for (id, p) in hypothesis.iter() {
-            eprintln!("  {}: {:?}", id, p);
+        // Dumping every constraint is expensive, so only do it when someone will see it.
+        if log::log_enabled!(log::Level::Trace) {
+            log::trace!("=== propagating ===");
+            log::trace!("constraints:");
+            for c in &self.constraints {
+                log::trace!("  {:?}", c);
+            }
+            log::trace!("hypothesis:");
+            for (id, p) in hypothesis.iter() {
+                log::trace!("  {}: {:?}", id, p);
+            }
         }
 
         struct PropagatePerms;
@@ -98,6 +396,18 @@ impl DataflowConstraints {
             ) -> PermissionSet {
                 *val & !perms
             }
+
+            fn implies(
+                &mut self,
+                _a_ptr: PointerId,
+                a_val: &PermissionSet,
+                a_perms: PermissionSet,
+                _b_ptr: PointerId,
+                b_perms: PermissionSet,
+                b_val: &PermissionSet,
+            ) -> Option<PermissionSet> {
+                a_val.contains(a_perms).then_some(*b_val | b_perms)
+            }
         }
 
         match self.propagate_inner(hypothesis, &mut PropagatePerms) {
@@ -108,6 +418,13 @@ impl DataflowConstraints {
         }
     }
 
+    /// Apply `rules` to the constraints until `xs` stops changing.  Returns whether anything
+    /// changed.
+    ///
+    /// This uses a worklist of constraints: initially every constraint is applied once, and after
+    /// that a constraint is only revisited when one of the pointers it mentions changes.  The cost
+    /// is proportional to the number of updates times the number of constraints mentioning each
+    /// updated pointer, rather than to the number of constraints for every round of propagation.
     fn propagate_inner<T, R>(
         &self,
         xs: &mut PointerTableMut<T>,
@@ -117,57 +434,67 @@ impl DataflowConstraints {
         T: PartialEq,
         R: PropagateRules<T>,
     {
-        let mut xs = TrackedPointerTable::new(xs.borrow_mut());
+        let graph = ConstraintGraph::new(&self.constraints, &xs.borrow());
+        let mut worklist = (0..self.constraints.len()).collect::<VecDeque<_>>();
+        let mut queued = vec![true; self.constraints.len()];
+
+        // Each pass of the old round-based solver visited every constraint, and it gave up after
+        // `xs.len() + constraints.len()` passes.  Keep the same bound on the total work.
+        let limit = (xs.len() + self.constraints.len() + 1) * self.constraints.len();
+        let mut steps = 0;
 
         let mut changed = false;
-        let mut i = 0;
-        loop {
-            if i > xs.len() + self.constraints.len() {
+        while let Some(i) = worklist.pop_front() {
+            queued[i] = false;
+            steps += 1;
+            if steps > limit {
                 return Err("infinite loop in dataflow edges".to_string());
             }
-            i += 1;
-
-            for c in &self.constraints {
-                match *c {
-                    Constraint::Subset(a, b) => {
-                        if !xs.dirty(a) && !xs.dirty(b) {
-                            continue;
-                        }
 
-                        let old_a = xs.get(a);
-                        let old_b = xs.get(b);
-                        let (new_a, new_b) = rules.subset(a, old_a, b, old_b);
-                        xs.set(a, new_a);
-                        xs.set(b, new_b);
+            let mut updated = [None, None];
+            match self.constraints[i] {
+                Constraint::Subset(a, b) => {
+                    let (new_a, new_b) = rules.subset(a, &xs[a], b, &xs[b]);
+                    if update(xs, a, new_a) {
+                        updated[0] = Some(a);
                     }
+                    if update(xs, b, new_b) {
+                        updated[1] = Some(b);
+                    }
+                }
 
-                    Constraint::AllPerms(ptr, perms) => {
-                        if !xs.dirty(ptr) {
-                            continue;
-                        }
+                Constraint::AllPerms(ptr, perms) => {
+                    let new = rules.all_perms(ptr, perms, &xs[ptr]);
+                    if update(xs, ptr, new) {
+                        updated[0] = Some(ptr);
+                    }
+                }
 
-                        let old = xs.get(ptr);
-                        let new = rules.all_perms(ptr, perms, old);
-                        xs.set(ptr, new);
+                Constraint::NoPerms(ptr, perms) => {
+                    let new = rules.no_perms(ptr, perms, &xs[ptr]);
+                    if update(xs, ptr, new) {
+                        updated[0] = Some(ptr);
                     }
+                }
 
-                    Constraint::NoPerms(ptr, perms) => {
-                        if !xs.dirty(ptr) {
-                            continue;
+                Constraint::Implies(a, a_perms, b, b_perms) => {
+                    if let Some(new) = rules.implies(a, &xs[a], a_perms, b, b_perms, &xs[b]) {
+                        if update(xs, b, new) {
+                            updated[0] = Some(b);
                         }
-
-                        let old = xs.get(ptr);
-                        let new = rules.no_perms(ptr, perms, old);
-                        xs.set(ptr, new);
                     }
                 }
             }
 
-            if !xs.any_new_dirty() {
-                break;
+            for ptr in updated.into_iter().flatten() {
+                changed = true;
+                for &j in &graph.uses[ptr] {
+                    if !queued[j] {
+                        queued[j] = true;
+                        worklist.push_back(j);
+                    }
+                }
             }
-            xs.swap_dirty();
-            changed = true;
         }
 
         Ok(changed)
@@ -242,6 +569,669 @@ impl DataflowConstraints {
             }
         }
     }
+
+    /// Make `roots`, and the pointers in this function whose values may be stored into them,
+    /// owners, where they're unique and not fixed.  This is used by `--box-lists` to turn singly
+    /// linked lists into `Box`es; see [`crate::linked_lists`].  It must run before
+    /// `propagate_ownership`, which removes the flag again from pointers with several owners.
+    pub fn seed_owned(&self, asn: &mut Assignment, roots: &HashSet<PointerId>) {
+        let (perms, mut flags) = asn.all_mut();
+        let perms = perms.borrow();
+        let mut srcs = HashMap::<PointerId, Vec<PointerId>>::new();
+        for (a, b) in self.subsets() {
+            srcs.entry(a).or_default().push(b);
+        }
+        let mut seen = self
+            .pointers()
+            .filter(|ptr| roots.contains(ptr))
+            .collect::<HashSet<_>>();
+        let mut queue = seen.iter().copied().collect::<VecDeque<_>>();
+        while let Some(ptr) = queue.pop_front() {
+            if !perms[ptr].contains(PermissionSet::UNIQUE) || flags[ptr].contains(FlagSet::FIXED) {
+                continue;
+            }
+            flags[ptr].insert(FlagSet::OWNED);
+            for &src in srcs.get(&ptr).into_iter().flatten() {
+                if seen.insert(src) {
+                    queue.push_back(src);
+                }
+            }
+        }
+    }
+
+    /// Set the `OWNED` flag on pointers that can be rewritten to `Box<T>`.
+    ///
+    /// Ownership is inferred from the final permissions: a pointer is a candidate owner if it has
+    /// both `FREE` and `UNIQUE`.  Only this function's local pointers are made candidates here;
+    /// global pointers are made candidates once for the whole crate by [`seed_ownership`], so that
+    /// one function's rejections aren't undone by the next.  Since `FREE` propagates backward along assignments, this covers
+    /// the whole path from the allocation to the `free` call, while pointers that are copied off
+    /// that path and not freed become borrows.  Pointers that may point to the stack or to a
+    /// static are never candidates.  Candidates are then rejected if ownership would be
+    /// duplicated (one pointer is copied into two distinct owners) or if an owner would be
+    /// initialized from a pointer that's not itself an owner.  Finally, owners that are resized
+    /// with `realloc` are marked `VEC`.
+    pub fn propagate_ownership(&self, asn: &mut Assignment) {
+        let (perms, mut flags) = asn.all_mut();
+        let perms = perms.borrow();
+
+        for ((ptr, p), (_, f)) in perms.iter().zip(flags.iter_mut()) {
+            if !ptr.is_global() && is_owner_candidate(*p, *f) {
+                f.insert(FlagSet::OWNED);
+            }
+        }
+        for &ptr in self.raw_allocs.iter().chain(&self.borrowed) {
+            flags[ptr].remove(FlagSet::OWNED);
+        }
+        // A pointer used after it's freed can't be a `Box`, since the `drop` that replaces the
+        // `free` would move it.  Neither can the pointers it's copied from, which would have to
+        // move into it.
+        if !self.uses_after_free.is_empty() {
+            let mut srcs = HashMap::<PointerId, Vec<PointerId>>::new();
+            for (a, b) in self.subsets() {
+                srcs.entry(a).or_default().push(b);
+            }
+            let mut seen = self
+                .uses_after_free
+                .iter()
+                .map(|u| u.moved.ptr)
+                .collect::<HashSet<_>>();
+            let mut queue = seen.iter().copied().collect::<VecDeque<_>>();
+            while let Some(ptr) = queue.pop_front() {
+                flags[ptr].remove(FlagSet::OWNED);
+                for &src in srcs.get(&ptr).into_iter().flatten() {
+                    if seen.insert(src) {
+                        queue.push_back(src);
+                    }
+                }
+            }
+        }
+        // Memory on the stack or in a static can't be managed by `Box` either.
+        for (_, f) in flags.iter_mut() {
+            if f.intersects(FlagSet::STACK | FlagSet::STATIC) {
+                f.remove(FlagSet::OWNED);
+            }
+        }
+
+        // Find pointers that are moved into more than one owner.  `Box` can't be copied, so
+        // neither the source nor any of the destinations can be owners.
+        let mut owned_dests = HashMap::<PointerId, HashSet<PointerId>>::new();
+        for c in &self.constraints {
+            if let Constraint::Subset(a, b) = *c {
+                if flags[a].contains(FlagSet::OWNED) {
+                    owned_dests.entry(b).or_default().insert(a);
+                }
+            }
+        }
+        for (src, dests) in owned_dests {
+            if dests.len() > 1 {
+                flags[src].remove(FlagSet::OWNED);
+                for dest in dests {
+                    flags[dest].remove(FlagSet::OWNED);
+                }
+            }
+        }
+
+        struct Rules;
+        impl PropagateRules<FlagSet> for Rules {
+            fn subset(
+                &mut self,
+                _a_ptr: PointerId,
+                a_val: &FlagSet,
+                _b_ptr: PointerId,
+                b_val: &FlagSet,
+            ) -> (FlagSet, FlagSet) {
+                // An owner can only be initialized by moving from another owner.  If `b` is not
+                // an owner, then `a` is derived from a borrow and can't be one either.
+                let mut a_flags = *a_val;
+                if !b_val.contains(FlagSet::OWNED) {
+                    a_flags.remove(FlagSet::OWNED);
+                }
+                (a_flags, *b_val)
+            }
+
+            fn all_perms(
+                &mut self,
+                _ptr: PointerId,
+                _perms: PermissionSet,
+                val: &FlagSet,
+            ) -> FlagSet {
+                *val
+            }
+
+            fn no_perms(
+                &mut self,
+                _ptr: PointerId,
+                _perms: PermissionSet,
+                val: &FlagSet,
+            ) -> FlagSet {
+                *val
+            }
+        }
+
+        match self.propagate_inner(&mut flags, &mut Rules) {
+            Ok(_changed) => {}
+            Err(msg) => {
+                panic!("{}", msg);
+            }
+        }
+
+        // Owners of buffers that are passed to `realloc` become `Vec`s.  Moving between owners
+        // requires them to have the same type, so `VEC` is propagated in both directions along
+        // assignments from one owner to another.
+        for &(in_ptr, out_ptr) in &self.reallocs {
+            let owned = FlagSet::OWNED;
+            if flags[in_ptr].contains(owned) && flags[out_ptr].contains(owned) {
+                flags[in_ptr].insert(FlagSet::VEC);
+                flags[out_ptr].insert(FlagSet::VEC);
+            }
+        }
+
+        struct VecRules;
+        impl PropagateRules<FlagSet> for VecRules {
+            fn subset(
+                &mut self,
+                _a_ptr: PointerId,
+                a_val: &FlagSet,
+                _b_ptr: PointerId,
+                b_val: &FlagSet,
+            ) -> (FlagSet, FlagSet) {
+                let mut a_flags = *a_val;
+                let mut b_flags = *b_val;
+                if a_flags.contains(FlagSet::OWNED)
+                    && b_flags.contains(FlagSet::OWNED)
+                    && (a_flags | b_flags).contains(FlagSet::VEC)
+                {
+                    a_flags.insert(FlagSet::VEC);
+                    b_flags.insert(FlagSet::VEC);
+                }
+                (a_flags, b_flags)
+            }
+
+            fn all_perms(
+                &mut self,
+                _ptr: PointerId,
+                _perms: PermissionSet,
+                val: &FlagSet,
+            ) -> FlagSet {
+                *val
+            }
+
+            fn no_perms(
+                &mut self,
+                _ptr: PointerId,
+                _perms: PermissionSet,
+                val: &FlagSet,
+            ) -> FlagSet {
+                *val
+            }
+        }
+
+        match self.propagate_inner(&mut flags, &mut VecRules) {
+            Ok(_changed) => {}
+            Err(msg) => {
+                panic!("{}", msg);
+            }
+        }
+
+        // Allocations that are never freed and are copied into two or more owners are shared
+        // between them, such as objects that are reference-counted by hand or simply leaked.  An
+        // allocation that's only ever moved from one pointer to the next has a single owner at a
+        // time and isn't shared.
+        let mut dests = HashMap::<PointerId, HashSet<PointerId>>::new();
+        for (a, b) in self.subsets() {
+            if a != b {
+                dests.entry(b).or_default().insert(a);
+            }
+        }
+        for &ptr in &self.allocs {
+            if flags[ptr].contains(FlagSet::OWNED) || perms[ptr].contains(PermissionSet::FREE) {
+                continue;
+            }
+            let mut seen = HashSet::from([ptr]);
+            let mut queue = VecDeque::from([ptr]);
+            while let Some(src) = queue.pop_front() {
+                let src_dests = dests.get(&src).into_iter().flatten();
+                if src_dests.clone().count() > 1 {
+                    flags[ptr].insert(FlagSet::SHARED);
+                    break;
+                }
+                queue.extend(src_dests.filter(|&&dest| seen.insert(dest)));
+            }
+        }
+
+        // All pointers to a shared object must have the same type, so `SHARED` is propagated in
+        // both directions, except to pointers that may point to the stack or to a static, which
+        // can't be shared this way.  `RC`, which selects `Rc<RefCell<T>>` for the pointers, is
+        // propagated the same way within a function, since choosing it for one pointer to the
+        // object requires it for all of them.  It isn't propagated to signatures and fields, which
+        // only have it if they're marked `rc` themselves.
+        fn shareable(flags: FlagSet) -> bool {
+            !flags.intersects(FlagSet::STACK | FlagSet::STATIC)
+        }
+
+        struct SharedRules;
+        impl PropagateRules<FlagSet> for SharedRules {
+            fn subset(
+                &mut self,
+                a_ptr: PointerId,
+                a_val: &FlagSet,
+                b_ptr: PointerId,
+                b_val: &FlagSet,
+            ) -> (FlagSet, FlagSet) {
+                let mut a_flags = *a_val;
+                let mut b_flags = *b_val;
+                if (a_flags | b_flags).contains(FlagSet::SHARED)
+                    && shareable(a_flags)
+                    && shareable(b_flags)
+                {
+                    a_flags.insert(FlagSet::SHARED);
+                    b_flags.insert(FlagSet::SHARED);
+                    if !a_ptr.is_global() && !b_ptr.is_global() {
+                        let rc = (a_flags | b_flags) & FlagSet::RC;
+                        a_flags.insert(rc);
+                        b_flags.insert(rc);
+                    }
+                }
+                (a_flags, b_flags)
+            }
+
+            fn all_perms(
+                &mut self,
+                _ptr: PointerId,
+                _perms: PermissionSet,
+                val: &FlagSet,
+            ) -> FlagSet {
+                *val
+            }
+
+            fn no_perms(
+                &mut self,
+                _ptr: PointerId,
+                _perms: PermissionSet,
+                val: &FlagSet,
+            ) -> FlagSet {
+                *val
+            }
+        }
+
+        match self.propagate_inner(&mut flags, &mut SharedRules) {
+            Ok(_changed) => {}
+            Err(msg) => {
+                panic!("{}", msg);
+            }
+        }
+
+        // If any pointer to a shared object didn't get `RC`, such as a parameter of a function
+        // that isn't marked `rc`, none of them can have it.
+        struct RcRules;
+        impl PropagateRules<FlagSet> for RcRules {
+            fn subset(
+                &mut self,
+                _a_ptr: PointerId,
+                a_val: &FlagSet,
+                _b_ptr: PointerId,
+                b_val: &FlagSet,
+            ) -> (FlagSet, FlagSet) {
+                let mut a_flags = *a_val;
+                let mut b_flags = *b_val;
+                if (a_flags & b_flags).contains(FlagSet::SHARED)
+                    && !(a_flags & b_flags).contains(FlagSet::RC)
+                {
+                    a_flags.remove(FlagSet::RC);
+                    b_flags.remove(FlagSet::RC);
+                }
+                (a_flags, b_flags)
+            }
+
+            fn all_perms(
+                &mut self,
+                _ptr: PointerId,
+                _perms: PermissionSet,
+                val: &FlagSet,
+            ) -> FlagSet {
+                *val
+            }
+
+            fn no_perms(
+                &mut self,
+                _ptr: PointerId,
+                _perms: PermissionSet,
+                val: &FlagSet,
+            ) -> FlagSet {
+                *val
+            }
+        }
+
+        match self.propagate_inner(&mut flags, &mut RcRules) {
+            Ok(_changed) => {}
+            Err(msg) => {
+                panic!("{}", msg);
+            }
+        }
+    }
+
+    /// Iterate over the `Subset(a, b)` constraints, which mean that `a` receives its value from
+    /// `b`.
+    pub fn subsets(&self) -> impl Iterator<Item = (PointerId, PointerId)> + '_ {
+        self.constraints.iter().filter_map(|c| match *c {
+            Constraint::Subset(a, b) => Some((a, b)),
+            _ => None,
+        })
+    }
+
+    pub fn num_constraints(&self) -> usize {
+        self.constraints.len()
+    }
+
+    /// Iterate over all the pointers mentioned in the constraints.
+    pub fn pointers(&self) -> impl Iterator<Item = PointerId> + '_ {
+        self.constraints
+            .iter()
+            .flat_map(|c| match *c {
+                Constraint::Subset(a, b) | Constraint::Implies(a, _, b, _) => [Some(a), Some(b)],
+                Constraint::AllPerms(ptr, _) | Constraint::NoPerms(ptr, _) => [Some(ptr), None],
+            })
+            .flatten()
+    }
+
+    /// The `Subset(a, b)` pairs that are only copies of `b` made after checking it isn't null.  A
+    /// pair that's also copied without a check isn't included.
+    fn checked_subsets(&self) -> HashSet<(PointerId, PointerId)> {
+        let mut unchecked = HashMap::<(PointerId, PointerId), usize>::new();
+        for pair in self.subsets() {
+            *unchecked.entry(pair).or_default() += 1;
+        }
+        for pair in &self.checked_copies {
+            *unchecked.get_mut(pair).unwrap() -= 1;
+        }
+        self.checked_copies
+            .iter()
+            .copied()
+            .filter(|pair| unchecked[pair] == 0)
+            .collect()
+    }
+
+    /// Add the global pointers that receive a value in this function to `ptrs`.
+    pub fn assigned_global_pointers(&self, ptrs: &mut HashSet<PointerId>) {
+        let dests = self.subsets().map(|(a, _)| a);
+        ptrs.extend(
+            dests
+                .chain(self.non_null.iter().copied())
+                .filter(|ptr| ptr.is_global()),
+        );
+    }
+
+    /// Update the `NON_NULL` flags of the pointers in this function.  The flags of local pointers
+    /// are recomputed from scratch, while global pointers only lose the flag, if this function
+    /// assigns them a value that may be null.  Callers should set `NON_NULL` on all global
+    /// pointers that are assigned somewhere, then call this on every function until it stops
+    /// returning `true`, which indicates that a global pointer lost the flag.
+    pub fn propagate_non_null(&self, asn: &mut Assignment) -> bool {
+        let mut flags = asn.flags_mut();
+        let seeds = self.non_null.iter().copied().collect::<HashSet<_>>();
+        let checked = self.checked_subsets();
+
+        for (ptr, f) in flags.iter_mut() {
+            if ptr.is_local() {
+                f.remove(FlagSet::NON_NULL);
+            }
+        }
+        let dests = self.subsets().map(|(a, _)| a);
+        for ptr in dests.chain(seeds.iter().copied()) {
+            if ptr.is_local() && !flags[ptr].contains(FlagSet::NULLABLE) {
+                flags[ptr].insert(FlagSet::NON_NULL);
+            }
+        }
+
+        // `a` receives its value from `b`, so it's only non-null if `b` is.
+        let mut global_changed = false;
+        loop {
+            let mut changed = false;
+            for c in &self.constraints {
+                if let Constraint::Subset(a, b) = *c {
+                    if flags[a].contains(FlagSet::NON_NULL)
+                        && !flags[b].contains(FlagSet::NON_NULL)
+                        && !seeds.contains(&a)
+                        && !checked.contains(&(a, b))
+                    {
+                        flags[a].remove(FlagSet::NON_NULL);
+                        changed = true;
+                        global_changed |= a.is_global();
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        global_changed
+    }
+
+    /// Update the `NUL_TERMINATED` flags of the pointers in this function, the same way
+    /// [`DataflowConstraints::propagate_non_null`] updates `NON_NULL`: a local byte pointer has
+    /// the flag if it's a starting point or only receives values from pointers that have it, and a
+    /// global pointer loses it if this function assigns it a value that doesn't.  Callers should
+    /// set `NUL_TERMINATED` on all global byte pointers that are assigned somewhere, then call this
+    /// on every function until it stops returning `true`.
+    pub fn propagate_nul_terminated(&self, asn: &mut Assignment) -> bool {
+        let mut flags = asn.flags_mut();
+        let seeds = self.nul_terminated.iter().copied().collect::<HashSet<_>>();
+        let byte_ptrs = self.byte_ptrs.iter().copied().collect::<HashSet<_>>();
+
+        for (ptr, f) in flags.iter_mut() {
+            if ptr.is_local() {
+                f.remove(FlagSet::NUL_TERMINATED);
+            }
+        }
+        let dests = self.subsets().map(|(a, _)| a);
+        for ptr in dests.chain(seeds.iter().copied()) {
+            if ptr.is_local() && byte_ptrs.contains(&ptr) {
+                flags[ptr].insert(FlagSet::NUL_TERMINATED);
+            }
+        }
+
+        let mut global_changed = false;
+        loop {
+            let mut changed = false;
+            for c in &self.constraints {
+                if let Constraint::Subset(a, b) = *c {
+                    if flags[a].contains(FlagSet::NUL_TERMINATED)
+                        && !flags[b].contains(FlagSet::NUL_TERMINATED)
+                        && !seeds.contains(&a)
+                    {
+                        flags[a].remove(FlagSet::NUL_TERMINATED);
+                        changed = true;
+                        global_changed |= a.is_global();
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        global_changed
+    }
+
+    /// Set the `CVOID` flag on pointers to `c_void`.
+    pub fn mark_c_void(&self, asn: &mut Assignment) {
+        let mut flags = asn.flags_mut();
+        for &ptr in &self.c_void {
+            flags[ptr].insert(FlagSet::CVOID);
+        }
+    }
+
+    /// Set the `COMPARE` flag on pointers whose address is compared or cast to an integer.
+    pub fn mark_compared(&self, asn: &mut Assignment) {
+        let mut flags = asn.flags_mut();
+        for &ptr in &self.compared {
+            flags[ptr].insert(FlagSet::COMPARE);
+        }
+    }
+
+    /// Set the `ATOMIC` flag on pointers used for atomic accesses.
+    pub fn mark_atomic(&self, asn: &mut Assignment) {
+        let mut flags = asn.flags_mut();
+        for &ptr in &self.atomic {
+            flags[ptr].insert(FlagSet::ATOMIC);
+        }
+    }
+
+    /// Set the `FIXED` flag on pointers that must stay raw, and on every pointer that receives its
+    /// value from one of them, since a value derived from an integer can't become a reference.
+    /// `globals` holds the global pointers found to be stuck so far, which may come from other
+    /// functions; any global pointers this function reaches are added to it.  Callers should run
+    /// this on every function until `globals` stops growing.
+    pub fn propagate_stuck_raw(&self, asn: &mut Assignment, globals: &mut HashSet<PointerId>) {
+        let mut flags = asn.flags_mut();
+        let mut stuck = self
+            .stuck_raw
+            .iter()
+            .map(|&(ptr, _)| ptr)
+            .chain(self.pointers().filter(|ptr| globals.contains(ptr)))
+            .collect::<HashSet<_>>();
+        let mut queue = stuck.iter().copied().collect::<VecDeque<_>>();
+        let mut dests = HashMap::<PointerId, Vec<PointerId>>::new();
+        for (a, b) in self.subsets() {
+            dests.entry(b).or_default().push(a);
+        }
+        while let Some(ptr) = queue.pop_front() {
+            for &dest in dests.get(&ptr).into_iter().flatten() {
+                if stuck.insert(dest) {
+                    queue.push_back(dest);
+                }
+            }
+        }
+        for ptr in stuck {
+            flags[ptr].insert(FlagSet::FIXED);
+            if ptr.is_global() {
+                globals.insert(ptr);
+            }
+        }
+    }
+
+    /// Find why each pointer reached by `FIXED` propagation in this function must stay raw.  A
+    /// pointer reached from several `stuck_raw` entries gets the reason of the first one.
+    pub fn stuck_reasons(&self) -> HashMap<PointerId, &'static str> {
+        let mut dests = HashMap::<PointerId, Vec<PointerId>>::new();
+        for (a, b) in self.subsets() {
+            dests.entry(b).or_default().push(a);
+        }
+        let mut reasons = HashMap::new();
+        for &(ptr, ref origin) in &self.stuck_raw {
+            if reasons.contains_key(&ptr) {
+                continue;
+            }
+            reasons.insert(ptr, origin.reason);
+            let mut queue = VecDeque::from([ptr]);
+            while let Some(cur) = queue.pop_front() {
+                for &dest in dests.get(&cur).into_iter().flatten() {
+                    if let Entry::Vacant(e) = reasons.entry(dest) {
+                        e.insert(origin.reason);
+                        queue.push_back(dest);
+                    }
+                }
+            }
+        }
+        reasons
+    }
+
+    /// Set the provenance flags, `HEAP`, `STACK`, `STATIC`, and `UNINIT`, on the pointers in this
+    /// function.
+    /// Provenance flows forward along assignments, so each pointer gets the flags of every value
+    /// it may receive.  Global pointers only ever gain flags, so callers should run this on every
+    /// function until it stops returning `true`, which indicates that a global pointer changed.
+    pub fn propagate_provenance(&self, asn: &mut Assignment) -> bool {
+        let mut flags = asn.flags_mut();
+        let mut global_changed = false;
+        let heap = self
+            .allocs
+            .iter()
+            .chain(&self.raw_allocs)
+            .map(|&ptr| (ptr, FlagSet::HEAP));
+        for (ptr, f) in heap.chain(self.provenance.iter().copied()) {
+            if !flags[ptr].contains(f) {
+                flags[ptr].insert(f);
+                global_changed |= ptr.is_global();
+            }
+        }
+
+        // `a` receives its value from `b`, so it may point wherever `b` does.
+        loop {
+            let mut changed = false;
+            for (a, b) in self.subsets() {
+                let f = flags[b] & PROVENANCE_FLAGS;
+                if !flags[a].contains(f) {
+                    flags[a].insert(f);
+                    changed = true;
+                    global_changed |= a.is_global();
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        global_changed
+    }
+
+    /// Set the `NULLABLE` flag on pointers that may be null.  Nullability flows forward along
+    /// assignments: copying a nullable pointer makes the destination nullable too, unless the copy
+    /// is only made after checking that the pointer isn't null.
+    pub fn propagate_nullable(&self, asn: &mut Assignment) {
+        let mut flags = asn.flags_mut();
+        for &ptr in &self.nullable {
+            flags[ptr].insert(FlagSet::NULLABLE);
+        }
+
+        struct Rules {
+            checked: HashSet<(PointerId, PointerId)>,
+        }
+        impl PropagateRules<FlagSet> for Rules {
+            fn subset(
+                &mut self,
+                a_ptr: PointerId,
+                a_val: &FlagSet,
+                b_ptr: PointerId,
+                b_val: &FlagSet,
+            ) -> (FlagSet, FlagSet) {
+                // `a` receives its value from `b`, so it may be null if `b` may be null, unless
+                // `b` was checked first.
+                let mut a_flags = *a_val;
+                if b_val.contains(FlagSet::NULLABLE) && !self.checked.contains(&(a_ptr, b_ptr)) {
+                    a_flags.insert(FlagSet::NULLABLE);
+                }
+                (a_flags, *b_val)
+            }
+
+            fn all_perms(
+                &mut self,
+                _ptr: PointerId,
+                _perms: PermissionSet,
+                val: &FlagSet,
+            ) -> FlagSet {
+                *val
+            }
+
+            fn no_perms(
+                &mut self,
+                _ptr: PointerId,
+                _perms: PermissionSet,
+                val: &FlagSet,
+            ) -> FlagSet {
+                *val
+            }
+        }
+
+        let mut rules = Rules {
+            checked: self.checked_subsets(),
+        };
+        match self.propagate_inner(&mut flags, &mut rules) {
+            Ok(_changed) => {}
+            Err(msg) => {
+                panic!("{}", msg);
+            }
+        }
+    }
 }
 
 impl Constraint {
@@ -250,6 +1240,9 @@ impl Constraint {
             Constraint::Subset(a, b) => Constraint::Subset(map[a], map[b]),
             Constraint::AllPerms(ptr, perms) => Constraint::AllPerms(map[ptr], perms),
             Constraint::NoPerms(ptr, perms) => Constraint::NoPerms(map[ptr], perms),
+            Constraint::Implies(a, a_perms, b, b_perms) => {
+                Constraint::Implies(map[a], a_perms, map[b], b_perms)
+            }
         };
     }
 }
@@ -259,58 +1252,122 @@ impl DataflowConstraints {
         for c in &mut self.constraints {
             c.remap_pointers(map.borrow());
         }
+        for ptr in &mut self.nullable {
+            *ptr = map[*ptr];
+        }
+        for (in_ptr, out_ptr) in &mut self.reallocs {
+            *in_ptr = map[*in_ptr];
+            *out_ptr = map[*out_ptr];
+        }
+        for ptr in &mut self.allocs {
+            *ptr = map[*ptr];
+        }
+        for (ptr, _) in &mut self.alloc_counts {
+            *ptr = map[*ptr];
+        }
+        for ptr in &mut self.raw_allocs {
+            *ptr = map[*ptr];
+        }
+        for ptr in &mut self.borrowed {
+            *ptr = map[*ptr];
+        }
+        for ptr in &mut self.non_null {
+            *ptr = map[*ptr];
+        }
+        for (a, b) in &mut self.checked_copies {
+            *a = map[*a];
+            *b = map[*b];
+        }
+        for (ptr, _) in &mut self.stuck_raw {
+            *ptr = map[*ptr];
+        }
+        for (_, ptr, _) in &mut self.conditional_stuck {
+            *ptr = map[*ptr];
+        }
+        for (ptr, _) in &mut self.provenance {
+            *ptr = map[*ptr];
+        }
+        for ptr in &mut self.c_void {
+            *ptr = map[*ptr];
+        }
+        for ptr in &mut self.compared {
+            *ptr = map[*ptr];
+        }
+        for ptr in &mut self.atomic {
+            *ptr = map[*ptr];
+        }
+        for ptr in &mut self.nul_terminated {
+            *ptr = map[*ptr];
+        }
+        for ptr in &mut self.byte_ptrs {
+            *ptr = map[*ptr];
+        }
+        for (a, b, _) in &mut self.equivs {
+            *a = map[*a];
+            *b = map[*b];
+        }
+        for (outer, inner, _, _) in &mut self.nested {
+            *outer = map[*outer];
+            *inner = map[*inner];
+        }
+        for m in &mut self.moves {
+            m.ptr = map[m.ptr];
+        }
+        for u in &mut self.uses_after_free {
+            u.moved.ptr = map[u.moved.ptr];
+        }
     }
 }
 
-struct TrackedPointerTable<'a, T> {
-    xs: PointerTableMut<'a, T>,
-    dirty: OwnedPointerTable<bool>,
-    new_dirty: OwnedPointerTable<bool>,
-    any_new_dirty: bool,
+/// Whether a pointer with these permissions and flags may be rewritten to `Box<T>`.
+fn is_owner_candidate(perms: PermissionSet, flags: FlagSet) -> bool {
+    perms.contains(PermissionSet::FREE | PermissionSet::UNIQUE) && !flags.contains(FlagSet::FIXED)
 }
 
-impl<'a, T: PartialEq> TrackedPointerTable<'a, T> {
-    pub fn new(xs: PointerTableMut<'a, T>) -> TrackedPointerTable<'a, T> {
-        let mut dirty = OwnedPointerTable::with_len_of(&xs.borrow());
-        let mut new_dirty = OwnedPointerTable::with_len_of(&xs.borrow());
-        dirty.fill(true);
-        new_dirty.fill(false);
-        TrackedPointerTable {
-            xs,
-            dirty,
-            new_dirty,
-            any_new_dirty: false,
+/// Set the `OWNED` flag on the global pointers that may be rewritten to `Box<T>`.  This must run
+/// once, before [`DataflowConstraints::propagate_ownership`] is run on each function, which only
+/// removes the flag from global pointers.
+pub fn seed_ownership(gasn: &mut GlobalAssignment) {
+    for ((_, &p), (_, f)) in gasn.perms.iter().zip(gasn.flags.iter_mut()) {
+        if is_owner_candidate(p, *f) {
+            f.insert(FlagSet::OWNED);
         }
     }
+}
 
-    pub fn len(&self) -> usize {
-        self.xs.len()
-    }
-
-    pub fn get(&self, id: PointerId) -> &T {
-        &self.xs[id]
-    }
-
-    pub fn dirty(&self, id: PointerId) -> bool {
-        self.dirty[id]
+/// Set `xs[ptr]` to `x`, and return whether that changed it.
+fn update<T: PartialEq>(xs: &mut PointerTableMut<T>, ptr: PointerId, x: T) -> bool {
+    if xs[ptr] == x {
+        return false;
     }
+    xs[ptr] = x;
+    true
+}
 
-    pub fn any_new_dirty(&self) -> bool {
-        self.any_new_dirty
-    }
+/// An index of the constraints that mention each pointer, so propagation only revisits the
+/// constraints affected by an update.
+struct ConstraintGraph {
+    /// The indices of the constraints that mention each pointer.
+    uses: OwnedPointerTable<Vec<usize>>,
+}
 
-    pub fn set(&mut self, id: PointerId, x: T) {
-        if x != self.xs[id] {
-            self.xs[id] = x;
-            self.new_dirty[id] = true;
-            self.any_new_dirty = true;
+impl ConstraintGraph {
+    fn new<T>(constraints: &[Constraint], xs: &PointerTable<T>) -> ConstraintGraph {
+        let mut uses = OwnedPointerTable::<Vec<usize>>::with_len_of(xs);
+        for (i, c) in constraints.iter().enumerate() {
+            match *c {
+                Constraint::Subset(a, b) => {
+                    uses[a].push(i);
+                    if b != a {
+                        uses[b].push(i);
+                    }
+                }
+                Constraint::AllPerms(ptr, _) | Constraint::NoPerms(ptr, _) => uses[ptr].push(i),
+                // Only a change to the condition's pointer can make the constraint take effect.
+                Constraint::Implies(a, _, _, _) => uses[a].push(i),
+            }
         }
-    }
-
-    pub fn swap_dirty(&mut self) {
-        mem::swap(&mut self.dirty, &mut self.new_dirty);
-        self.new_dirty.fill(false);
-        self.any_new_dirty = false;
+        ConstraintGraph { uses }
     }
 }
 
@@ -318,11 +1375,104 @@ trait PropagateRules<T> {
     fn subset(&mut self, a_ptr: PointerId, a_val: &T, b_ptr: PointerId, b_val: &T) -> (T, T);
     fn all_perms(&mut self, ptr: PointerId, perms: PermissionSet, val: &T) -> T;
     fn no_perms(&mut self, ptr: PointerId, perms: PermissionSet, val: &T) -> T;
+    /// The new value of `b_ptr` for an `Implies` constraint, or `None` if it doesn't apply.  Only
+    /// permission propagation uses these constraints.
+    fn implies(
+        &mut self,
+        _a_ptr: PointerId,
+        _a_val: &T,
+        _a_perms: PermissionSet,
+        _b_ptr: PointerId,
+        _b_perms: PermissionSet,
+        _b_val: &T,
+    ) -> Option<T> {
+        None
+    }
 }
 
 pub fn generate_constraints<'tcx>(
     acx: &AnalysisCtxt<'_, 'tcx>,
     mir: &Body<'tcx>,
 ) -> (DataflowConstraints, Vec<(PointerId, PointerId)>) {
-    self::type_check::visit(acx, mir)
+    let (mut constraints, equiv_constraints) = self::type_check::visit(acx, mir);
+    constraints.uses_after_free = use_after_free::find(mir, &constraints.moves);
+
+    let tcx = acx.tcx();
+    for lty in acx.local_tys.iter().chain(acx.rvalue_tys.values()) {
+        for lty in lty.iter() {
+            if !lty.label.is_none() && util::is_c_void_ptr(tcx, lty.ty) {
+                constraints.c_void.push(lty.label);
+            }
+            if lty.label.is_local() && util::is_byte_ptr(lty.ty) {
+                constraints.byte_ptrs.push(lty.label);
+            }
+        }
+    }
+
+    // Apply `#[c2rust::assume]` annotations on the parameters.  `UNIQUE` is handled specially:
+    // it's normally present unless the analysis removes it, so not listing it means it should be
+    // removed, and listing it means it should be left alone.
+    let annotations = &acx.gacx.annotations;
+    for &(i, perms) in annotations.assumed_perms(mir.source.def_id()) {
+        let ptr = acx.local_tys[Local::from_usize(i + 1)].label;
+        if ptr.is_none() {
+            continue;
+        }
+        let origin = Origin {
+            loc: Location::START,
+            span: mir.local_decls[Local::from_usize(i + 1)].source_info.span,
+            reason: "required by a `#[c2rust::assume]` annotation",
+        };
+        constraints.add_all_perms(ptr, perms - PermissionSet::UNIQUE, origin);
+        if !perms.contains(PermissionSet::UNIQUE) {
+            constraints.add_no_perms(ptr, PermissionSet::UNIQUE, origin);
+        }
+    }
+
+    (constraints, equiv_constraints)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pointer_id::{GlobalPointerTable, LocalPointerTable};
+    use rustc_span::DUMMY_SP;
+
+    /// Times [`DataflowConstraints::propagate`] on a million pointers: chains of copies of one
+    /// pointer, the last of each written through, so `WRITE` flows up every chain to the root and
+    /// the root's lack of `UNIQUE` flows back down.  Run it with
+    /// `cargo test --release -- --ignored --nocapture propagate_benchmark`.
+    #[test]
+    #[ignore]
+    fn propagate_benchmark() {
+        const N: u32 = 1_000_000;
+        let origin = Origin {
+            loc: Location::START,
+            span: DUMMY_SP,
+            reason: "benchmark",
+        };
+        let mut constraints = DataflowConstraints::default();
+        let root = PointerId::local(0);
+        let mut prev = root;
+        for i in 1..N {
+            if i % 16 == 1 {
+                prev = root;
+            }
+            let copy = PointerId::local(i);
+            constraints.add_subset(copy, prev, origin);
+            if i % 16 == 0 {
+                constraints.add_all_perms(copy, PermissionSet::WRITE, origin);
+            }
+            prev = copy;
+        }
+        constraints.add_no_perms(root, PermissionSet::UNIQUE, origin);
+
+        let mut global = GlobalPointerTable::from_raw(vec![]);
+        let mut local = LocalPointerTable::from_raw(vec![PermissionSet::UNIQUE; N as usize]);
+        let start = std::time::Instant::now();
+        assert!(constraints.propagate(&mut global.and_mut(&mut local)));
+        eprintln!("propagate on {} pointers: {:?}", N, start.elapsed());
+        assert_eq!(local[root], PermissionSet::WRITE);
+        assert_eq!(local[PointerId::local(N - 1)], PermissionSet::empty());
+    }
 }
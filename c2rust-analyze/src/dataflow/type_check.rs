@@ -1,14 +1,19 @@
-use super::DataflowConstraints;
+use super::{DataflowConstraints, ElemCount, Origin};
 use crate::c_void_casts::CVoidCastDirection;
-use crate::context::{AnalysisCtxt, LTy, PermissionSet, PointerId};
-use crate::util::{describe_rvalue, ty_callee, Callee, RvalueDesc};
+use crate::context::{AnalysisCtxt, FlagSet, LTy, PermissionSet, PointerId};
+use crate::keep_going;
+use crate::null_checks::{self, NullChecks};
+use crate::options::Assumption;
+use crate::util::{self, describe_rvalue, ty_callee, AtomicOp, Callee, RvalueDesc, SafePtr};
 use assert_matches::assert_matches;
 use rustc_hir::def_id::DefId;
 use rustc_middle::mir::{
-    AggregateKind, BinOp, Body, Location, Mutability, Operand, Place, PlaceRef, ProjectionElem,
-    Rvalue, Statement, StatementKind, Terminator, TerminatorKind,
+    AggregateKind, BinOp, Body, InlineAsmOperand, LocalKind, Location, Mutability, Operand, Place,
+    PlaceRef, ProjectionElem, Rvalue, Statement, StatementKind, Terminator, TerminatorKind,
 };
-use rustc_middle::ty::{SubstsRef, Ty, TyKind};
+use rustc_middle::ty::{FnSig, List, ParamEnv, SubstsRef, Ty, TyKind};
+use rustc_span::{sym, Span};
+use std::collections::HashSet;
 
 /// Visitor that walks over the MIR, computing types of rvalues/operands/places and generating
 /// constraints as a side effect.
@@ -39,30 +44,427 @@ struct TypeChecker<'tcx, 'a> {
     /// structure defined in `crate::equiv`, so adding a constraint here has the effect of unifying
     /// the equivalence classes of the two `PointerId`s.
     equiv_constraints: Vec<(PointerId, PointerId)>,
+    /// The pointer locals known to be non-null in each block, because of an earlier null check.
+    null_checks: NullChecks,
+    /// The location and span of the statement or terminator being visited, for recording the
+    /// origins of constraints.
+    loc: Location,
+    span: Span,
 }
 
+/// A factor of the size passed to an allocator.
+#[derive(Clone, Debug)]
+enum SizeFactor<'tcx> {
+    Count(ElemCount),
+    SizeOf(Ty<'tcx>),
+}
+
+/// How many temporaries [`TypeChecker::size_factors`] follows back before giving up.
+const MAX_SIZE_DEPTH: usize = 16;
+
 impl<'tcx> TypeChecker<'tcx, '_> {
+    fn origin(&self, reason: &'static str) -> Origin {
+        Origin {
+            loc: self.loc,
+            span: self.span,
+            reason,
+        }
+    }
+
+    /// Record that the call being visited takes ownership of the pointer `ptr` in `place`, so the
+    /// local holding it shouldn't be used again until it's reassigned.
+    fn add_move(&mut self, place: Option<Place>, ptr: PointerId, reason: &'static str) {
+        if let Some(local) = place.and_then(|pl| pl.as_local()) {
+            let origin = self.origin(reason);
+            self.constraints.add_move(local, ptr, origin);
+        }
+    }
+
+    /// Break the integer operand `op` into factors, following temporaries back to the
+    /// multiplications, casts, and `size_of` calls that compute them.  Returns `None` if the value
+    /// isn't a product of locals, constants, and `size_of::<T>()`.
+    fn size_factors(&self, op: &Operand<'tcx>, depth: usize) -> Option<Vec<SizeFactor<'tcx>>> {
+        let tcx = self.acx.tcx();
+        if depth > MAX_SIZE_DEPTH {
+            return None;
+        }
+        let place = match *op {
+            Operand::Constant(ref c) => {
+                let n = c
+                    .literal
+                    .try_eval_bits(tcx, ParamEnv::reveal_all(), c.ty())?;
+                return Some(vec![SizeFactor::Count(ElemCount::Const(n))]);
+            }
+            Operand::Copy(pl) | Operand::Move(pl) => pl,
+        };
+        // The result of a `CheckedBinaryOp` is a `(value, overflowed)` pair, and the value is read
+        // from its first field.
+        let (local, checked) = match (place.as_local(), &place.projection[..]) {
+            (Some(local), _) => (local, false),
+            (None, &[ProjectionElem::Field(f, _)]) if f.index() == 0 => (place.local, true),
+            _ => return None,
+        };
+        if !checked && self.mir.local_kind(local) != LocalKind::Temp {
+            return Some(vec![SizeFactor::Count(ElemCount::Local(local))]);
+        }
+
+        // Only temporaries with a single assignment are followed.  One assigned in several places,
+        // as at a join point of `goto`-style control flow, may hold different sizes on different
+        // paths.
+        let mut def = None;
+        for data in self.mir.basic_blocks() {
+            for stmt in &data.statements {
+                let (pl, rv) = match stmt.kind {
+                    StatementKind::Assign(ref x) => (&x.0, &x.1),
+                    _ => continue,
+                };
+                if pl.as_local() != Some(local) {
+                    continue;
+                }
+                if def.is_some() {
+                    return None;
+                }
+                def = Some(match (rv, checked) {
+                    (&Rvalue::Use(ref op), false) | (&Rvalue::Cast(_, ref op, _), false) => {
+                        self.size_factors(op, depth + 1)
+                    }
+                    (&Rvalue::BinaryOp(BinOp::Mul, ref ops), false)
+                    | (&Rvalue::CheckedBinaryOp(BinOp::Mul, ref ops), true) => {
+                        let mut factors = self.size_factors(&ops.0, depth + 1)?;
+                        factors.extend(self.size_factors(&ops.1, depth + 1)?);
+                        Some(factors)
+                    }
+                    _ => None,
+                });
+            }
+            if let TerminatorKind::Call {
+                ref func,
+                destination,
+                ..
+            } = data.terminator().kind
+            {
+                if checked || destination.as_local() != Some(local) {
+                    continue;
+                }
+                if def.is_some() {
+                    return None;
+                }
+                def = Some(match *func.ty(self.mir, tcx).kind() {
+                    TyKind::FnDef(did, substs) if tcx.is_diagnostic_item(sym::mem_size_of, did) => {
+                        Some(vec![SizeFactor::SizeOf(substs.type_at(0))])
+                    }
+                    _ => None,
+                });
+            }
+        }
+        def.flatten()
+    }
+
+    /// Find the function pointer field that the callee `func` of an indirect call was loaded from,
+    /// as in `(*ops).read.unwrap()(p)`.
+    fn fn_ptr_field(&self, func: &Operand<'tcx>) -> Option<DefId> {
+        let tcx = self.acx.tcx();
+        let place = *util::copy_sources(self.mir, func.place()?).last()?;
+
+        // The field may be an `Option`, which is matched with `Some(f)` or unwrapped with
+        // `unwrap` or `expect` before the call.
+        if let Some(opt_place) = self.option_payload_of(place) {
+            let opt_place = *util::copy_sources(self.mir, opt_place).last()?;
+            return self.struct_field(opt_place);
+        }
+        if !place.projection.is_empty() {
+            return self.struct_field(place);
+        }
+        let local = place.as_local()?;
+        for data in self.mir.basic_blocks() {
+            let (func, args, destination) = match data.terminator().kind {
+                TerminatorKind::Call {
+                    ref func,
+                    ref args,
+                    destination,
+                    ..
+                } => (func, args, destination),
+                _ => continue,
+            };
+            if destination.as_local() != Some(local) {
+                continue;
+            }
+            if !matches!(ty_callee(tcx, func.ty(self.mir, tcx)), Callee::FnPtrUnwrap) {
+                return None;
+            }
+            let opt_place = *util::copy_sources(self.mir, args[0].place()?).last()?;
+            return self.struct_field(opt_place);
+        }
+        None
+    }
+
+    /// The `Option` that `place` is the payload of, if `place` is `(opt as Some).0`.
+    fn option_payload_of(&self, place: Place<'tcx>) -> Option<Place<'tcx>> {
+        let tcx = self.acx.tcx();
+        let (base, last2) = place
+            .projection
+            .split_at(place.projection.len().checked_sub(2)?);
+        if !matches!(
+            *last2,
+            [ProjectionElem::Downcast(_, _), ProjectionElem::Field(f, _)] if f.index() == 0
+        ) {
+            return None;
+        }
+        match *Place::ty_from(place.local, base, self.mir, tcx).ty.kind() {
+            TyKind::Adt(adt, _) if tcx.is_diagnostic_item(sym::Option, adt.did()) => Some(Place {
+                local: place.local,
+                projection: tcx.intern_place_elems(base),
+            }),
+            _ => None,
+        }
+    }
+
+    /// The struct field that `place` refers to, if its last projection is a field of a struct.
+    fn struct_field(&self, place: Place<'tcx>) -> Option<DefId> {
+        let (&elem, base) = place.projection.split_last()?;
+        let f = match elem {
+            ProjectionElem::Field(f, _) => f,
+            _ => return None,
+        };
+        let base_ty = Place::ty_from(place.local, base, self.mir, self.acx.tcx()).ty;
+        match *base_ty.kind() {
+            TyKind::Adt(adt, _) if adt.is_struct() => {
+                Some(adt.non_enum_variant().fields[f.index()].did)
+            }
+            _ => None,
+        }
+    }
+
+    /// Record the number of elements in the allocation assigned to `lty`, if the product of
+    /// `size_args` is a count times the size of its pointee type.
+    fn add_alloc_count(&mut self, lty: LTy<'tcx>, size_args: &[Operand<'tcx>]) {
+        if !matches!(lty.kind(), TyKind::RawPtr(..)) {
+            return;
+        }
+        let pointee_ty = lty.args[0].ty;
+        let mut factors = Vec::new();
+        for op in size_args {
+            match self.size_factors(op, 0) {
+                Some(fs) => factors.extend(fs),
+                None => return,
+            }
+        }
+        let i = match factors
+            .iter()
+            .position(|f| matches!(*f, SizeFactor::SizeOf(ty) if ty == pointee_ty))
+        {
+            Some(i) => i,
+            None => return,
+        };
+        factors.remove(i);
+        let mut count: Option<ElemCount> = None;
+        for f in factors {
+            let f = match f {
+                SizeFactor::Count(c) => c,
+                SizeFactor::SizeOf(_) => return,
+            };
+            count = Some(match count {
+                None => f,
+                Some(c) => ElemCount::Mul(Box::new(c), Box::new(f)),
+            });
+        }
+        let count = count.unwrap_or(ElemCount::Const(1));
+        self.constraints.add_alloc_count(lty.label, count);
+    }
+
     fn add_edge(&mut self, src: PointerId, dest: PointerId) {
         // Copying `src` to `dest` can discard permissions, but can't add new ones.
-        self.constraints.add_subset(dest, src);
+        let origin = self.origin("pointer is copied");
+        self.constraints.add_subset(dest, src, origin);
+        let checked = self.null_checks.non_null_in(self.loc.block);
+        if checked.iter().any(|&l| self.acx.local_tys[l].label == src) {
+            self.constraints.add_checked_copy(dest, src);
+        }
     }
 
     fn add_equiv(&mut self, a: PointerId, b: PointerId) {
         self.equiv_constraints.push((a, b));
+        let origin = self.origin("pointee types must match");
+        self.constraints.add_equiv(a, b, origin);
+    }
+
+    fn add_all_perms(&mut self, ptr: PointerId, perms: PermissionSet, reason: &'static str) {
+        let origin = self.origin(reason);
+        self.constraints.add_all_perms(ptr, perms, origin);
+    }
+
+    /// Record an access to `inner`, which is stored in memory that `outer` points to.
+    fn add_nested(&mut self, outer: PointerId, inner: PointerId, unique: bool) {
+        if outer != PointerId::NONE && inner != PointerId::NONE {
+            let origin = self.origin("pointer is stored behind another pointer");
+            self.constraints.add_nested(outer, inner, unique, origin);
+        }
+    }
+
+    /// Add the facts that hold for every safe pointer in `lty`: a `Box`, `Vec`, or `NonNull` is
+    /// never null, and a `Box` or `Vec` points to the heap.
+    fn visit_safe_ptrs(&mut self, lty: LTy<'tcx>) {
+        for sub in lty.iter() {
+            let kind = match SafePtr::of(self.acx.tcx(), sub.ty) {
+                Some(kind) if sub.label != PointerId::NONE => kind,
+                _ => continue,
+            };
+            self.constraints.add_non_null(sub.label);
+            if kind != SafePtr::NonNull {
+                self.constraints.add_provenance(sub.label, FlagSet::HEAP);
+            }
+        }
+    }
+
+    /// Record that `ptr` is used for atomic accesses, so it can't be unique.
+    fn add_atomic(&mut self, ptr: PointerId) {
+        if ptr != PointerId::NONE {
+            let origin = self.origin("pointer is accessed atomically");
+            self.constraints.add_atomic(ptr, origin);
+        }
+    }
+
+    fn add_stuck_raw(&mut self, ptr: PointerId, reason: &'static str) {
+        let origin = self.origin(reason);
+        self.constraints.add_stuck_raw(ptr, origin);
+    }
+
+    /// Keep `ptr` raw unless the user makes the assumption `a`.
+    fn add_stuck_raw_unless(&mut self, a: Assumption, ptr: PointerId, reason: &'static str) {
+        let origin = self.origin(reason);
+        self.constraints.add_conditional_stuck(a, ptr, origin);
+    }
+
+    /// Record that the pointers in `args` are passed to an extern function we can't see, which
+    /// may keep a copy of them.
+    fn visit_extern_args(&mut self, args: &[Operand<'tcx>]) {
+        for arg_op in args {
+            let arg_lty = self.acx.type_of(arg_op);
+            for lty in arg_lty.iter() {
+                if lty.label != PointerId::NONE {
+                    self.add_stuck_raw_unless(
+                        Assumption::ExternNoRetain,
+                        lty.label,
+                        "pointer is passed to an extern function that may keep it",
+                    );
+                }
+            }
+            self.visit_extern_struct(arg_lty.ty, &mut HashSet::new());
+        }
+    }
+
+    /// Record that the value of type `lty` is filled with zero bytes, so every pointer in it is
+    /// null.  That includes the pointers in the fields of structs, which the `LTy` of the value
+    /// doesn't include, so they're found through the labeled field types.  The pointees of the
+    /// pointers aren't touched.
+    fn add_zeroed(&mut self, lty: LTy<'tcx>, seen: &mut HashSet<DefId>) {
+        match *lty.kind() {
+            TyKind::Ref(..) | TyKind::RawPtr(..) => {
+                if lty.label != PointerId::NONE {
+                    self.constraints.add_nullable(lty.label);
+                }
+            }
+            TyKind::Array(..) | TyKind::Tuple(..) => {
+                for &arg_lty in lty.args {
+                    self.add_zeroed(arg_lty, seen);
+                }
+            }
+            TyKind::Adt(adt, _) if adt.is_struct() || adt.is_union() => {
+                if !seen.insert(adt.did()) {
+                    return;
+                }
+                for field in adt.all_fields() {
+                    if let Some(&field_lty) = self.acx.gacx.field_tys.get(&field.did) {
+                        self.add_zeroed(field_lty, seen);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Handle a call to `memset(p, 0, n)`, which zeroes what `p` points to.  `p` is usually cast
+    /// to `*mut c_void` from a pointer to the real type, as in `memset(&mut s as *mut S as *mut
+    /// c_void, 0, size_of::<S>())`.
+    fn visit_memset_zero(&mut self, args: &[Operand<'tcx>]) {
+        let (dest, value) = match args {
+            [dest, value, _] => (dest, value),
+            _ => return,
+        };
+        let is_zero = |op: &Operand<'tcx>| match op.place().and_then(|pl| pl.as_local()) {
+            Some(local) => match util::temp_assignment(self.mir, local) {
+                Some(Rvalue::Cast(_, op, _)) => util::is_null_const(op),
+                _ => false,
+            },
+            None => util::is_null_const(op),
+        };
+        if !is_zero(value) {
+            return;
+        }
+        // Look through the casts for the pointer to the real type.
+        let mut ptr_lty = self.acx.type_of(dest);
+        let mut place = dest.place();
+        while let Some(local) =
+            place.and_then(|pl| util::copy_sources(self.mir, pl).last()?.as_local())
+        {
+            match util::temp_assignment(self.mir, local) {
+                Some(Rvalue::Cast(_, op, _)) => {
+                    ptr_lty = self.acx.type_of(op);
+                    place = op.place();
+                }
+                _ => break,
+            }
+        }
+        if matches!(ptr_lty.kind(), TyKind::Ref(..) | TyKind::RawPtr(..)) {
+            self.add_zeroed(ptr_lty.args[0], &mut HashSet::new());
+        }
+    }
+
+    /// Record that a value of type `ty` is passed by value to an extern function.  If it's a
+    /// struct, the pointers in its fields are passed too, but the `LTy` of the value doesn't
+    /// include them, so they're found through the labeled field types, along with those of the
+    /// structs and arrays nested in it.  Their types are part of the C ABI, so the struct must
+    /// keep its layout.
+    fn visit_extern_struct(&mut self, ty: Ty<'tcx>, seen: &mut HashSet<DefId>) {
+        let adt = match *ty.kind() {
+            TyKind::Adt(adt, _) if adt.is_struct() || adt.is_union() => adt,
+            TyKind::Array(elem_ty, _) => return self.visit_extern_struct(elem_ty, seen),
+            _ => return,
+        };
+        if !seen.insert(adt.did()) {
+            return;
+        }
+        self.constraints.add_ffi_struct(adt.did());
+        for field in adt.all_fields() {
+            let field_lty = match self.acx.gacx.field_tys.get(&field.did) {
+                Some(&lty) => lty,
+                None => continue,
+            };
+            for lty in field_lty.iter() {
+                if lty.label != PointerId::NONE {
+                    self.add_stuck_raw_unless(
+                        Assumption::ExternNoRetain,
+                        lty.label,
+                        "pointer is in a struct passed to an extern function that may keep it",
+                    );
+                }
+            }
+            self.visit_extern_struct(field_lty.ty, seen);
+        }
     }
 
     fn record_access(&mut self, ptr: PointerId, mutbl: Mutability) {
-        eprintln!("record_access({:?}, {:?})", ptr, mutbl);
+        log::trace!("record_access({:?}, {:?})", ptr, mutbl);
         if ptr == PointerId::NONE {
             return;
         }
         match mutbl {
             Mutability::Mut => {
-                self.constraints
-                    .add_all_perms(ptr, PermissionSet::READ | PermissionSet::WRITE);
+                let perms = PermissionSet::READ | PermissionSet::WRITE;
+                self.add_all_perms(ptr, perms, "pointer is written through");
             }
             Mutability::Not => {
-                self.constraints.add_all_perms(ptr, PermissionSet::READ);
+                self.add_all_perms(ptr, PermissionSet::READ, "pointer is read through");
             }
         }
     }
@@ -84,6 +486,7 @@ impl<'tcx> TypeChecker<'tcx, '_> {
                 // access the memory at `*p`.
                 if let Some(ptr) = prev_deref_ptr.take() {
                     self.record_access(ptr, mutbl);
+                    self.add_nested(ptr, lty.label, mutbl == Mutability::Mut);
                 }
                 prev_deref_ptr = Some(lty.label);
             }
@@ -97,21 +500,51 @@ impl<'tcx> TypeChecker<'tcx, '_> {
 
     pub fn visit_rvalue(&mut self, rv: &Rvalue<'tcx>, lty: LTy<'tcx>) {
         let rv_desc = describe_rvalue(rv);
-        eprintln!("visit_rvalue({rv:?}), desc = {rv_desc:?}");
+        log::trace!("visit_rvalue({rv:?}), desc = {rv_desc:?}");
 
         if let Some(desc) = rv_desc {
-            match desc {
+            // Taking the address of a place never produces a null pointer.
+            if lty.label != PointerId::NONE {
+                self.constraints.add_non_null(lty.label);
+            }
+            let base_ptr = match desc {
                 RvalueDesc::Project { base, proj: _ } => {
                     // TODO: mutability should probably depend on mutability of the output ref/ptr
                     self.visit_place_ref(base, Mutability::Not);
+                    self.acx.type_of(base).label
+                }
+                RvalueDesc::AddrOfLocal { local, .. } => {
+                    if lty.label != PointerId::NONE {
+                        self.constraints.add_provenance(lty.label, FlagSet::STACK);
+                    }
+                    self.acx.addr_of_local[local]
                 }
-                RvalueDesc::AddrOfLocal { .. } => {}
+            };
+            // The address of a field of a packed struct has its own pointer, derived from the
+            // one it's projected from, and must stay raw since it may be unaligned.
+            if util::is_packed_field_addr(self.acx.tcx(), self.mir, rv) {
+                if base_ptr != PointerId::NONE {
+                    self.add_edge(base_ptr, lty.label);
+                }
+                self.add_stuck_raw(lty.label, "pointer is to a field of a packed struct");
             }
             return;
         }
 
         match *rv {
-            Rvalue::Use(ref op) => self.visit_operand(op),
+            Rvalue::Use(ref op) => {
+                self.visit_operand(op);
+                // Constant pointers, other than null, point to statics or to other static memory,
+                // such as string literals.
+                if matches!(op, Operand::Constant(..)) && lty.label != PointerId::NONE {
+                    if util::is_null_const(op) {
+                        self.constraints.add_nullable(lty.label);
+                    } else {
+                        self.constraints.add_non_null(lty.label);
+                        self.constraints.add_provenance(lty.label, FlagSet::STATIC);
+                    }
+                }
+            }
             Rvalue::Repeat(ref op, _) => {
                 assert!(lty.ty.is_array());
                 assert_matches!(lty.args, [elem_lty] => {
@@ -135,23 +568,69 @@ impl<'tcx> TypeChecker<'tcx, '_> {
             Rvalue::Ref(..) => {
                 unreachable!("Rvalue::Ref should be handled by describe_rvalue instead")
             }
-            Rvalue::ThreadLocalRef(..) => todo!("visit_rvalue ThreadLocalRef"),
+            Rvalue::ThreadLocalRef(..) => {
+                // Like the address of any other static, this is never null.
+                if lty.label != PointerId::NONE {
+                    self.constraints.add_non_null(lty.label);
+                    self.constraints.add_provenance(lty.label, FlagSet::STATIC);
+                }
+            }
             Rvalue::AddressOf(..) => {
                 unreachable!("Rvalue::AddressOf should be handled by describe_rvalue instead")
             }
             Rvalue::Len(pl) => {
                 self.visit_place(pl, Mutability::Not);
             }
-            Rvalue::Cast(_, ref op, _) => self.visit_operand(op),
-            Rvalue::BinaryOp(BinOp::Offset, _) => todo!("visit_rvalue BinOp::Offset"),
-            Rvalue::BinaryOp(_, ref ops) => {
-                self.visit_operand(&ops.0);
-                self.visit_operand(&ops.1);
+            Rvalue::Cast(_, ref op, ty) => {
+                self.visit_operand(op);
+                let op_ty = op.ty(self.mir, self.acx.tcx());
+                let op_ptr = self.acx.type_of(op).label;
+                if lty.label != PointerId::NONE {
+                    if util::is_null_const(op) {
+                        // `0 as *mut T` produces a null pointer.
+                        self.constraints.add_nullable(lty.label);
+                    } else if op_ty.is_integral() {
+                        // Any other integer could point anywhere, so the result can't become a
+                        // reference.
+                        self.add_stuck_raw(lty.label, "pointer is created from an integer");
+                        self.constraints.int_to_ptr = true;
+                    } else if op_ptr != PointerId::NONE
+                        && op_ptr != lty.label
+                        && (util::is_c_void_ptr(self.acx.tcx(), op_ty)
+                            || util::is_c_void_ptr(self.acx.tcx(), ty))
+                    {
+                        // Casts between `c_void` and bytes keep the same pointer.  Any other
+                        // cast to or from `c_void` changes the pointee type in a way we can't
+                        // check, as when a context pointer is cast back to its real type.
+                        let reason = "pointer is cast to or from `c_void`";
+                        self.add_stuck_raw(op_ptr, reason);
+                        self.add_stuck_raw(lty.label, reason);
+                    }
+                } else if op_ptr != PointerId::NONE && ty.is_integral() {
+                    // Casting a pointer to an integer, as for hashing or printing it, only uses
+                    // its address.  But if the integer is cast back later, the new pointer may
+                    // alias this one.
+                    self.constraints.add_compared(op_ptr);
+                    self.add_stuck_raw_unless(
+                        Assumption::NoIntPtrRoundTrips,
+                        op_ptr,
+                        "pointer is cast to an integer, which may become a pointer again",
+                    );
+                }
             }
-            Rvalue::CheckedBinaryOp(BinOp::Offset, _) => todo!("visit_rvalue BinOp::Offset"),
-            Rvalue::CheckedBinaryOp(_, ref ops) => {
-                self.visit_operand(&ops.0);
-                self.visit_operand(&ops.1);
+            Rvalue::BinaryOp(BinOp::Offset, _) | Rvalue::CheckedBinaryOp(BinOp::Offset, _) => {
+                todo!("visit_rvalue BinOp::Offset")
+            }
+            Rvalue::BinaryOp(_, ref ops) | Rvalue::CheckedBinaryOp(_, ref ops) => {
+                // Comparing two pointers only looks at their addresses, so it places no
+                // constraints on either of them.
+                for op in [&ops.0, &ops.1] {
+                    self.visit_operand(op);
+                    let ptr = self.acx.type_of(op).label;
+                    if ptr != PointerId::NONE {
+                        self.constraints.add_compared(ptr);
+                    }
+                }
             }
             Rvalue::NullaryOp(..) => {}
             Rvalue::UnaryOp(_, ref op) => {
@@ -173,6 +652,35 @@ impl<'tcx> TypeChecker<'tcx, '_> {
                             self.do_assign(elem_lty, op_lty);
                         }
                     }
+                    AggregateKind::Adt(did, variant_idx, _, _, active_field) => {
+                        // Pseudo-assign from each operand to the corresponding field.  Fields of
+                        // local types have their own labeled types; fields whose type is a type
+                        // parameter, like that of `Some(x)`, use the corresponding argument of
+                        // `lty`.
+                        let tcx = self.acx.tcx();
+                        let variant = tcx.adt_def(did).variant(variant_idx);
+                        for (i, op) in ops.iter().enumerate() {
+                            let field = &variant.fields[active_field.unwrap_or(i)];
+                            let field_lty = match *tcx.type_of(field.did).kind() {
+                                TyKind::Param(param) => lty.args[param.index as usize],
+                                _ => match self.acx.gacx.field_tys.get(&field.did) {
+                                    Some(&field_lty) => field_lty,
+                                    None => continue,
+                                },
+                            };
+                            let op_lty = self.acx.type_of(op);
+                            self.do_assign(field_lty, op_lty);
+                        }
+                    }
+                    AggregateKind::Closure(..) => {
+                        assert!(matches!(lty.kind(), TyKind::Closure(..)));
+                        assert_eq!(lty.args.len(), ops.len());
+                        // Pseudo-assign from each captured value to the corresponding upvar.
+                        for (op, &upvar_lty) in ops.iter().zip(lty.args) {
+                            let op_lty = self.acx.type_of(op);
+                            self.do_assign(upvar_lty, op_lty);
+                        }
+                    }
                     ref kind => todo!("Rvalue::Aggregate({:?})", kind),
                 }
             }
@@ -185,6 +693,21 @@ impl<'tcx> TypeChecker<'tcx, '_> {
         match *op {
             Operand::Copy(pl) | Operand::Move(pl) => {
                 self.visit_place(pl, Mutability::Not);
+                // Copying a pointer out of a place behind another pointer needs unique access to
+                // it, if it's going to be `&mut`.
+                let inner = self.acx.type_of(pl).label;
+                if let Some(i) = pl
+                    .projection
+                    .iter()
+                    .rposition(|p| matches!(p, ProjectionElem::Deref))
+                {
+                    let base = PlaceRef {
+                        local: pl.local,
+                        projection: &pl.projection[..i],
+                    };
+                    let outer = self.acx.type_of(base).label;
+                    self.add_nested(outer, inner, true);
+                }
             }
             Operand::Constant(ref _c) => {
                 // TODO: addr of static may show up as `Operand::Constant`
@@ -233,7 +756,7 @@ impl<'tcx> TypeChecker<'tcx, '_> {
             self.acx.tcx().erase_regions(lty2.ty)
         );
         for (sub_lty1, sub_lty2) in lty1.iter().zip(lty2.iter()) {
-            eprintln!("equate {:?} = {:?}", sub_lty1, sub_lty2);
+            log::trace!("equate {:?} = {:?}", sub_lty1, sub_lty2);
             if sub_lty1.label != PointerId::NONE || sub_lty2.label != PointerId::NONE {
                 assert!(sub_lty1.label != PointerId::NONE);
                 assert!(sub_lty2.label != PointerId::NONE);
@@ -243,7 +766,10 @@ impl<'tcx> TypeChecker<'tcx, '_> {
     }
 
     pub fn visit_statement(&mut self, stmt: &Statement<'tcx>, loc: Location) {
-        eprintln!("visit_statement({:?})", stmt);
+        keep_going::set_current_span(stmt.source_info.span);
+        self.loc = loc;
+        self.span = stmt.source_info.span;
+        log::trace!("visit_statement({:?})", stmt);
 
         if self.acx.c_void_casts.should_skip_stmt(loc) {
             return;
@@ -267,10 +793,12 @@ impl<'tcx> TypeChecker<'tcx, '_> {
     }
 
     pub fn visit_terminator(&mut self, term: &Terminator<'tcx>, loc: Location) {
-        eprintln!("visit_terminator({:?})", term.kind);
+        keep_going::set_current_span(term.source_info.span);
+        self.loc = loc;
+        self.span = term.source_info.span;
+        log::trace!("visit_terminator({:?})", term.kind);
         let tcx = self.acx.tcx();
         // TODO(spernsteiner): other `TerminatorKind`s will be handled in the future
-        #[allow(clippy::single_match)]
         match term.kind {
             TerminatorKind::Call {
                 ref func,
@@ -279,14 +807,81 @@ impl<'tcx> TypeChecker<'tcx, '_> {
                 target: _,
                 ..
             } => {
-                let func = func.ty(self.mir, tcx);
-                self.visit_call(loc, func, args, destination);
+                let acx = self.acx;
+                let func_ty = func.ty(self.mir, tcx);
+                let targets = match *func_ty.kind() {
+                    TyKind::FnPtr(_) => self
+                        .fn_ptr_field(func)
+                        .and_then(|field| acx.gacx.fn_ptr_fields.targets(field))
+                        .filter(|targets| {
+                            !targets.is_empty()
+                                && targets.iter().all(|t| acx.gacx.fn_sigs.contains_key(t))
+                        }),
+                    _ => None,
+                };
+                match targets {
+                    // A call through a function pointer field that only ever holds known
+                    // functions is a call to each of them.  See [`crate::fn_ptr_fields`].
+                    Some(targets) => {
+                        for &target in targets {
+                            self.visit_local_call(target, List::empty(), args, destination);
+                        }
+                    }
+                    None => self.visit_call(loc, func_ty, args, destination),
+                }
+            }
+            TerminatorKind::InlineAsm { ref operands, .. } => {
+                self.visit_inline_asm(operands);
             }
             // TODO(spernsteiner): handle other `TerminatorKind`s
             _ => (),
         }
     }
 
+    /// Handle an `asm!` block.  The assembly may read through its input pointers and write
+    /// through its output pointers, and may keep any of them or produce new ones, as from an
+    /// integer, so all of them must stay raw.
+    fn visit_inline_asm(&mut self, operands: &[InlineAsmOperand<'tcx>]) {
+        self.constraints.inline_asm = true;
+        self.constraints.int_to_ptr = true;
+        for op in operands {
+            match *op {
+                InlineAsmOperand::In { ref value, .. } => {
+                    self.visit_operand(value);
+                    self.visit_asm_ptrs(self.acx.type_of(value), Mutability::Not);
+                }
+                InlineAsmOperand::Out {
+                    place: Some(place), ..
+                } => {
+                    self.visit_place(place, Mutability::Mut);
+                    self.visit_asm_ptrs(self.acx.type_of(place), Mutability::Mut);
+                }
+                InlineAsmOperand::InOut {
+                    ref in_value,
+                    out_place,
+                    ..
+                } => {
+                    self.visit_operand(in_value);
+                    self.visit_asm_ptrs(self.acx.type_of(in_value), Mutability::Mut);
+                    if let Some(place) = out_place {
+                        self.visit_place(place, Mutability::Mut);
+                        self.visit_asm_ptrs(self.acx.type_of(place), Mutability::Mut);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn visit_asm_ptrs(&mut self, lty: LTy<'tcx>, mutbl: Mutability) {
+        for sub in lty.iter() {
+            if sub.label != PointerId::NONE {
+                self.record_access(sub.label, mutbl);
+                self.add_stuck_raw(sub.label, "pointer is used by inline assembly");
+            }
+        }
+    }
+
     pub fn visit_call(
         &mut self,
         loc: Location,
@@ -295,18 +890,78 @@ impl<'tcx> TypeChecker<'tcx, '_> {
         destination: Place<'tcx>,
     ) {
         let tcx = self.acx.tcx();
-        let callee = ty_callee(tcx, func);
-        eprintln!("callee = {callee:?}");
+        let callee = match ty_callee(tcx, func) {
+            // Skipped functions have no signature in `fn_sigs`, so calls to them are handled like
+            // calls to an unknown function.
+            Callee::LocalDef { def_id, .. } if self.acx.gacx.annotations.skip(def_id) => {
+                Callee::UnknownDef { ty: func }
+            }
+            callee => callee,
+        };
+        log::trace!("callee = {callee:?}");
+        if let TyKind::FnDef(def_id, _) = *func.kind() {
+            if tcx.is_foreign_item(def_id) && tcx.item_name(def_id).as_str() == "memset" {
+                self.visit_memset_zero(args);
+            }
+        }
         match callee {
             Callee::Trivial => {}
-            Callee::UnknownDef { .. } => {
-                log::error!("TODO: visit Callee::{callee:?}");
-            }
+            Callee::UnknownDef { ty } => match *ty.kind() {
+                // Functions from other crates have a signature in `fn_sigs` if a summary was
+                // imported for them.
+                TyKind::FnDef(def_id, substs) if self.acx.gacx.fn_sigs.contains_key(&def_id) => {
+                    self.visit_local_call(def_id, substs, args, destination);
+                }
+                TyKind::FnPtr(sig) => {
+                    let sig = tcx.erase_late_bound_regions(sig);
+                    let reason = "required by the signature of an indirect call";
+                    self.visit_sig_call(sig, args, destination, reason);
+                }
+                TyKind::FnDef(def_id, _)
+                    if tcx.is_foreign_item(def_id)
+                        && self
+                            .acx
+                            .gacx
+                            .extern_models
+                            .get(tcx.item_name(def_id).as_str())
+                            .is_some() =>
+                {
+                    self.visit_modeled_call(def_id, args, destination);
+                }
+                // Variadic externs like `printf` are handled using their declared signature.
+                TyKind::FnDef(def_id, _) if ty.fn_sig(tcx).c_variadic() => {
+                    let sig = tcx.erase_late_bound_regions(ty.fn_sig(tcx));
+                    let reason = "required by the signature of an extern function";
+                    self.visit_sig_call(sig, args, destination, reason);
+                    if tcx.is_foreign_item(def_id) {
+                        self.visit_extern_args(args);
+                    }
+                }
+                TyKind::FnDef(def_id, _) if tcx.is_foreign_item(def_id) => {
+                    log::error!("TODO: visit Callee::{callee:?}");
+                    self.visit_extern_args(args);
+                }
+                _ => {
+                    log::error!("TODO: visit Callee::{callee:?}");
+                }
+            },
 
             Callee::LocalDef { def_id, substs } => {
                 self.visit_local_call(def_id, substs, args, destination);
             }
 
+            Callee::LocalClosure { def_id } if self.acx.gacx.fn_sigs.contains_key(&def_id) => {
+                self.visit_closure_call(def_id, args, destination);
+            }
+
+            // Skipped closures are handled like virtual calls, using the signature of the `Fn`
+            // trait method.
+            Callee::LocalClosure { .. } | Callee::Virtual { .. } => {
+                let sig = tcx.erase_late_bound_regions(func.fn_sig(tcx));
+                let reason = "required by the signature of an indirect call";
+                self.visit_sig_call(sig, args, destination, reason);
+            }
+
             Callee::PtrOffset { .. } => {
                 // We handle this like a pointer assignment.
                 self.visit_place(destination, Mutability::Mut);
@@ -316,7 +971,7 @@ impl<'tcx> TypeChecker<'tcx, '_> {
                 let rv_lty = self.acx.type_of(&args[0]);
                 self.do_assign(pl_lty, rv_lty);
                 let perms = PermissionSet::OFFSET_ADD | PermissionSet::OFFSET_SUB;
-                self.constraints.add_all_perms(rv_lty.label, perms);
+                self.add_all_perms(rv_lty.label, perms, "pointer is offset");
             }
 
             Callee::SliceAsPtr { elem_ty, .. } => {
@@ -343,6 +998,151 @@ impl<'tcx> TypeChecker<'tcx, '_> {
                 self.do_assign_pointer_ids(pl_lty.label, rv_lty.label);
             }
 
+            Callee::MaybeUninitAsPtr { .. } => {
+                // Like `SliceAsPtr`, this is an assignment from `&MaybeUninit<T>` to `*const T`.
+                // The contents may not be initialized yet, so the result must stay raw.
+                self.visit_place(destination, Mutability::Mut);
+                let pl_lty = self.acx.type_of(destination);
+                assert!(args.len() == 1);
+                self.visit_operand(&args[0]);
+                let rv_lty = self.acx.type_of(&args[0]);
+                self.do_unify(pl_lty.args[0], rv_lty.args[0].args[0]);
+                self.do_assign_pointer_ids(pl_lty.label, rv_lty.label);
+                self.constraints
+                    .add_provenance(pl_lty.label, FlagSet::UNINIT);
+                self.add_stuck_raw(pl_lty.label, "pointer is into a `MaybeUninit`");
+            }
+
+            Callee::VecAsPtr { .. } => {
+                // This is an assignment from the `Vec<T>` inside `&Vec<T>` to `*const T`.  The
+                // pointer is into the vector's buffer, so it can be offset within it.
+                self.visit_place(destination, Mutability::Mut);
+                let pl_lty = self.acx.type_of(destination);
+                assert!(args.len() == 1);
+                self.visit_operand(&args[0]);
+                let vec_lty = self.acx.type_of(&args[0]).args[0];
+                self.do_unify(pl_lty.args[0], vec_lty.args[0]);
+                self.do_assign_pointer_ids(pl_lty.label, vec_lty.label);
+            }
+
+            Callee::BoxIntoRaw => {
+                // The result owns the allocation, as if it came from `malloc`.
+                self.visit_place(destination, Mutability::Mut);
+                let pl_lty = self.acx.type_of(destination);
+                assert!(args.len() == 1);
+                self.visit_operand(&args[0]);
+                let rv_lty = self.acx.type_of(&args[0]);
+                self.do_unify(pl_lty.args[0], rv_lty.args[0]);
+                self.do_assign_pointer_ids(pl_lty.label, rv_lty.label);
+                self.constraints.add_alloc(pl_lty.label);
+            }
+
+            Callee::BoxFromRaw => {
+                // The `Box` will free the allocation, as `free` would.
+                self.visit_place(destination, Mutability::Mut);
+                let pl_lty = self.acx.type_of(destination);
+                assert!(args.len() == 1);
+                self.visit_operand(&args[0]);
+                let rv_lty = self.acx.type_of(&args[0]);
+                self.do_unify(pl_lty.args[0], rv_lty.args[0]);
+                self.do_assign_pointer_ids(pl_lty.label, rv_lty.label);
+                let perms = PermissionSet::FREE;
+                self.add_all_perms(rv_lty.label, perms, "pointer is passed to `Box::from_raw`");
+                self.add_move(
+                    args[0].place(),
+                    rv_lty.label,
+                    "being passed to `Box::from_raw`",
+                );
+            }
+
+            Callee::CStrFromPtr => {
+                // This is an assignment from `*const c_char` to `&CStr`.  The string isn't
+                // accessed through the raw pointer, so it needs no permissions.
+                self.visit_place(destination, Mutability::Mut);
+                let pl_lty = self.acx.type_of(destination);
+                assert!(args.len() == 1);
+                self.visit_operand(&args[0]);
+                let rv_lty = self.acx.type_of(&args[0]);
+                self.do_assign_pointer_ids(pl_lty.label, rv_lty.label);
+            }
+
+            Callee::CStrAsPtr => {
+                // This is an assignment from `&CStr` to `*const c_char`.
+                self.visit_place(destination, Mutability::Mut);
+                let pl_lty = self.acx.type_of(destination);
+                assert!(args.len() == 1);
+                self.visit_operand(&args[0]);
+                let rv_lty = self.acx.type_of(&args[0]);
+                self.do_assign_pointer_ids(pl_lty.label, rv_lty.label);
+                self.constraints.add_non_null(pl_lty.label);
+                self.constraints.add_nul_terminated(pl_lty.label);
+            }
+
+            Callee::CStringIntoRaw => {
+                // The result owns the string, as if it came from `malloc`.
+                self.visit_place(destination, Mutability::Mut);
+                let pl_lty = self.acx.type_of(destination);
+                assert!(args.len() == 1);
+                self.visit_operand(&args[0]);
+                self.constraints.add_alloc(pl_lty.label);
+                self.constraints.add_non_null(pl_lty.label);
+                self.constraints.add_nul_terminated(pl_lty.label);
+            }
+
+            Callee::CStringFromRaw => {
+                // The `CString` will free the string, as `free` would.
+                self.visit_place(destination, Mutability::Mut);
+                assert!(args.len() == 1);
+                self.visit_operand(&args[0]);
+                let rv_lty = self.acx.type_of(&args[0]);
+                let perms = PermissionSet::FREE;
+                self.add_all_perms(
+                    rv_lty.label,
+                    perms,
+                    "pointer is passed to `CString::from_raw`",
+                );
+                self.add_move(
+                    args[0].place(),
+                    rv_lty.label,
+                    "being passed to `CString::from_raw`",
+                );
+            }
+
+            Callee::MaybeUninitNew => {
+                // `MaybeUninit<T>` has the same pointers as the `T` it wraps.
+                self.visit_place(destination, Mutability::Mut);
+                let pl_lty = self.acx.type_of(destination);
+                assert!(args.len() == 1);
+                self.visit_operand(&args[0]);
+                let rv_lty = self.acx.type_of(&args[0]);
+                self.do_assign(pl_lty.args[0], rv_lty);
+            }
+
+            Callee::MaybeUninitUninit => {
+                self.visit_place(destination, Mutability::Mut);
+            }
+
+            Callee::MaybeUninitZeroed => {
+                self.visit_place(destination, Mutability::Mut);
+                let pl_lty = self.acx.type_of(destination);
+                self.add_zeroed(pl_lty.args[0], &mut HashSet::new());
+            }
+
+            Callee::MemZeroed => {
+                self.visit_place(destination, Mutability::Mut);
+                let pl_lty = self.acx.type_of(destination);
+                self.add_zeroed(pl_lty, &mut HashSet::new());
+            }
+
+            Callee::MaybeUninitAssumeInit => {
+                self.visit_place(destination, Mutability::Mut);
+                let pl_lty = self.acx.type_of(destination);
+                assert!(args.len() == 1);
+                self.visit_operand(&args[0]);
+                let rv_lty = self.acx.type_of(&args[0]);
+                self.do_assign(pl_lty, rv_lty.args[0]);
+            }
+
             Callee::Malloc | Callee::Calloc => {
                 let out_ptr = self.acx.c_void_casts.get_adjusted_place_or_default_to(
                     loc,
@@ -350,6 +1150,13 @@ impl<'tcx> TypeChecker<'tcx, '_> {
                     destination,
                 );
                 self.visit_place(out_ptr, Mutability::Mut);
+                let out_lty = self.acx.type_of(out_ptr);
+                self.constraints.add_alloc(out_lty.label);
+                self.add_alloc_count(out_lty, args);
+                // `calloc` zeroes the memory, so the pointers in it start out null.
+                if matches!(callee, Callee::Calloc) && out_lty.label != PointerId::NONE {
+                    self.add_zeroed(out_lty.args[0], &mut HashSet::new());
+                }
             }
             Callee::Realloc => {
                 let out_ptr = self.acx.c_void_casts.get_adjusted_place_or_default_to(
@@ -373,10 +1180,15 @@ impl<'tcx> TypeChecker<'tcx, '_> {
 
                 // input needs FREE permission
                 let perms = PermissionSet::FREE;
-                self.constraints.add_all_perms(rv_lty.label, perms);
+                self.add_all_perms(rv_lty.label, perms, "pointer is passed to `realloc`");
+                self.add_move(Some(in_ptr), rv_lty.label, "being passed to `realloc`");
 
                 // unify inner-most pointer types
                 self.do_equivalence_nested(pl_lty, rv_lty);
+
+                self.constraints.add_realloc(rv_lty.label, pl_lty.label);
+                self.constraints.add_alloc(pl_lty.label);
+                self.add_alloc_count(pl_lty, &args[1..]);
             }
             Callee::Free => {
                 let in_ptr = args[0]
@@ -392,16 +1204,471 @@ impl<'tcx> TypeChecker<'tcx, '_> {
 
                 let rv_lty = self.acx.type_of(in_ptr);
                 let perms = PermissionSet::FREE;
-                self.constraints.add_all_perms(rv_lty.label, perms);
+                self.add_all_perms(rv_lty.label, perms, "pointer is passed to `free`");
+                self.add_move(Some(in_ptr), rv_lty.label, "being passed to `free`");
+            }
+
+            Callee::AlignedAlloc | Callee::Mmap => {
+                let out_ptr = self.acx.c_void_casts.get_adjusted_place_or_default_to(
+                    loc,
+                    CVoidCastDirection::From,
+                    destination,
+                );
+                self.visit_place(out_ptr, Mutability::Mut);
+                let out_lty = self.acx.type_of(out_ptr);
+                self.constraints.add_raw_alloc(out_lty.label);
+            }
+            Callee::PosixMemalign => {
+                // `posix_memalign(&mut p, align, size)` stores the new allocation in `p`.
+                assert!(args.len() == 3);
+                self.visit_place(destination, Mutability::Mut);
+                self.visit_operand(&args[0]);
+                let memptr_lty = self.acx.type_of(&args[0]);
+                self.record_access(memptr_lty.label, Mutability::Mut);
+                if let Some(&out_lty) = memptr_lty.args.first() {
+                    if !out_lty.label.is_none() {
+                        self.constraints.add_raw_alloc(out_lty.label);
+                    }
+                }
+            }
+            Callee::Munmap => {
+                let in_ptr = args[0]
+                    .place()
+                    .expect("Casts to/from null pointer are not yet supported");
+                let in_ptr = self.acx.c_void_casts.get_adjusted_place_or_default_to(
+                    loc,
+                    CVoidCastDirection::To,
+                    in_ptr,
+                );
+                self.visit_place(destination, Mutability::Mut);
+                assert!(args.len() == 2);
+
+                let rv_lty = self.acx.type_of(in_ptr);
+                let perms = PermissionSet::FREE;
+                self.add_all_perms(rv_lty.label, perms, "pointer is passed to `munmap`");
+            }
+
+            Callee::Qsort => {
+                // `qsort(base, nmemb, size, compar)` reads, writes, and offsets `base`, and calls
+                // `compar` with pointers to elements of `base`.
+                assert!(args.len() == 4);
+                let base = args[0]
+                    .place()
+                    .expect("Casts to/from null pointer are not yet supported");
+                let base = self.acx.c_void_casts.get_adjusted_place_or_default_to(
+                    loc,
+                    CVoidCastDirection::To,
+                    base,
+                );
+                self.visit_place(base, Mutability::Not);
+                let base_lty = self.acx.type_of(base);
+                let perms = PermissionSet::READ | PermissionSet::WRITE | PermissionSet::OFFSET_ADD;
+                self.add_all_perms(base_lty.label, perms, "pointer is passed to `qsort`");
+
+                self.visit_callback(&args[3], &[base_lty.label, base_lty.label]);
+                self.visit_place(destination, Mutability::Mut);
+            }
+
+            Callee::Bsearch => {
+                // `bsearch(key, base, nmemb, size, compar)` reads `key`, reads and offsets `base`,
+                // and calls `compar` with `key` and pointers to elements of `base`.  The result
+                // points into `base`, or is null if the key wasn't found.
+                assert!(args.len() == 5);
+                let mut in_ptrs = [PointerId::NONE; 2];
+                let arg_perms = [
+                    PermissionSet::READ,
+                    PermissionSet::READ | PermissionSet::OFFSET_ADD,
+                ];
+                for (i, perms) in arg_perms.into_iter().enumerate() {
+                    let in_ptr = args[i]
+                        .place()
+                        .expect("Casts to/from null pointer are not yet supported");
+                    let in_ptr = self.acx.c_void_casts.get_adjusted_place_or_default_to(
+                        loc,
+                        CVoidCastDirection::To,
+                        in_ptr,
+                    );
+                    self.visit_place(in_ptr, Mutability::Not);
+                    let in_lty = self.acx.type_of(in_ptr);
+                    self.add_all_perms(in_lty.label, perms, "pointer is passed to `bsearch`");
+                    in_ptrs[i] = in_lty.label;
+                }
+                self.visit_callback(&args[4], &in_ptrs);
+
+                let out_ptr = self.acx.c_void_casts.get_adjusted_place_or_default_to(
+                    loc,
+                    CVoidCastDirection::From,
+                    destination,
+                );
+                self.visit_place(out_ptr, Mutability::Mut);
+                let out_lty = self.acx.type_of(out_ptr);
+                self.do_assign_pointer_ids(out_lty.label, in_ptrs[1]);
+                self.constraints.add_nullable(out_lty.label);
             }
 
             Callee::IsNull => {
                 assert!(args.len() == 1);
                 self.visit_operand(&args[0]);
+                // Checking `p.is_null()` means `p` may be null.  The argument is usually a
+                // temporary copy of `p`, so the places it was copied from are marked as well.
+                if let Some(pl) = args[0].place() {
+                    for pl in util::copy_sources(self.mir, pl) {
+                        if let Some(ptr) = self.acx.ptr_of(pl) {
+                            self.constraints.add_nullable(ptr);
+                        }
+                    }
+                }
+            }
+
+            Callee::FmtArgument => {
+                // Formatting only reads the value, through the reference passed in.  A pointer
+                // in the value is printed as an address, if it's printed at all, so it needs no
+                // permissions and doesn't escape.
+                self.visit_place(destination, Mutability::Mut);
+                for arg in args {
+                    self.visit_operand(arg);
+                }
+                let rv_lty = self.acx.type_of(&args[0]);
+                self.add_all_perms(rv_lty.label, PermissionSet::READ, "value is formatted");
+            }
+
+            Callee::Null => {
+                self.visit_place(destination, Mutability::Mut);
+                let pl_lty = self.acx.type_of(destination);
+                self.constraints.add_nullable(pl_lty.label);
+            }
+
+            Callee::FnPtrUnwrap => {
+                // We handle this like an assignment of the function pointer in the `Option`, so
+                // the pointers in its signature are those of the function stored in it.
+                self.visit_place(destination, Mutability::Mut);
+                let pl_lty = self.acx.type_of(destination);
+                self.visit_operand(&args[0]);
+                let rv_lty = self.acx.type_of(&args[0]);
+                self.do_assign(pl_lty, rv_lty.args[0]);
+            }
+
+            Callee::Transmute => {
+                self.visit_place(destination, Mutability::Mut);
+                let pl_lty = self.acx.type_of(destination);
+                assert!(args.len() == 1);
+                self.visit_operand(&args[0]);
+                let rv_lty = self.acx.type_of(&args[0]);
+                self.do_transmute(pl_lty, rv_lty);
+            }
+
+            Callee::AtomicIntrinsic { op } => {
+                // We handle these like a load from or store to `*args[0]`, or both.
+                self.visit_place(destination, Mutability::Mut);
+                let pl_lty = self.acx.type_of(destination);
+                for arg in args {
+                    self.visit_operand(arg);
+                }
+                let ptr_lty = self.acx.type_of(&args[0]);
+                let pointee_lty = ptr_lty.args[0];
+                let mutbl = match op {
+                    AtomicOp::Load => Mutability::Not,
+                    _ => Mutability::Mut,
+                };
+                self.record_access(ptr_lty.label, mutbl);
+                self.add_atomic(ptr_lty.label);
+                match op {
+                    AtomicOp::Load => self.do_assign(pl_lty, pointee_lty),
+                    AtomicOp::Store => {
+                        assert!(args.len() == 2);
+                        self.do_assign(pointee_lty, self.acx.type_of(&args[1]));
+                    }
+                    AtomicOp::Modify => {
+                        assert!(args.len() == 2);
+                        self.do_assign(pointee_lty, self.acx.type_of(&args[1]));
+                        self.do_assign(pl_lty, pointee_lty);
+                    }
+                    AtomicOp::CompareExchange => {
+                        // The result is the old value and whether it was replaced.
+                        assert!(args.len() == 3);
+                        self.do_assign(pointee_lty, self.acx.type_of(&args[2]));
+                        self.do_assign(pl_lty.args[0], pointee_lty);
+                    }
+                }
+            }
+
+            Callee::AtomicPtr => {
+                // The pointer inside an `AtomicPtr<T>` has no label of its own, so the pointers
+                // stored in one and loaded from one can't be connected, and they may be shared
+                // with other threads.
+                self.visit_place(destination, Mutability::Mut);
+                for arg in args {
+                    self.visit_operand(arg);
+                }
+                let ltys = args
+                    .iter()
+                    .map(|arg| self.acx.type_of(arg))
+                    .chain(Some(self.acx.type_of(destination)))
+                    .collect::<Vec<_>>();
+                for lty in ltys {
+                    for sub in lty.iter() {
+                        if sub.label != PointerId::NONE && matches!(sub.kind(), TyKind::RawPtr(..))
+                        {
+                            self.add_atomic(sub.label);
+                            self.add_stuck_raw(sub.label, "pointer is stored in an `AtomicPtr`");
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Handle `pl = transmute(rv)`.  A transmute to the same type is an assignment.  A transmute
+    /// between pointers is an assignment of the outer pointer, and if the pointee types have the
+    /// same layout and pointers in the same places, the pointee pointers are unified as though the
+    /// types were the same.  In every other case, the bits of the pointers involved are
+    /// reinterpreted in ways we can't track, so all of them must stay raw.
+    fn do_transmute(&mut self, pl_lty: LTy<'tcx>, rv_lty: LTy<'tcx>) {
+        let tcx = self.acx.tcx();
+        if tcx.erase_regions(pl_lty.ty) == tcx.erase_regions(rv_lty.ty) {
+            self.do_assign(pl_lty, rv_lty);
+            return;
+        }
+
+        let is_ptr = |lty: LTy| matches!(lty.kind(), TyKind::Ref(..) | TyKind::RawPtr(..));
+        if is_ptr(pl_lty) && is_ptr(rv_lty) {
+            self.add_edge(rv_lty.label, pl_lty.label);
+            let (pl_pointee, rv_pointee) = (pl_lty.args[0], rv_lty.args[0]);
+            if self.same_pointer_layout(pl_pointee, rv_pointee) {
+                for (pl_sub, rv_sub) in pl_pointee.iter().zip(rv_pointee.iter()) {
+                    if pl_sub.label != PointerId::NONE && rv_sub.label != PointerId::NONE {
+                        self.add_equiv(pl_sub.label, rv_sub.label);
+                    }
+                }
+                return;
+            }
+            for lty in pl_pointee.iter().chain(rv_pointee.iter()) {
+                if lty.label != PointerId::NONE {
+                    self.add_stuck_raw(lty.label, "pointer is transmuted to a different layout");
+                }
+            }
+        }
+
+        for lty in pl_lty.iter().chain(rv_lty.iter()) {
+            if lty.label != PointerId::NONE {
+                self.add_stuck_raw(lty.label, "pointer is transmuted to or from a non-pointer");
             }
         }
     }
 
+    /// Check whether `lty1` and `lty2` have the same size and alignment, and pointers in the same
+    /// positions, so that a pointer to one can be treated as a pointer to the other.
+    fn same_pointer_layout(&self, lty1: LTy<'tcx>, lty2: LTy<'tcx>) -> bool {
+        let tcx = self.acx.tcx();
+        let layout = |lty: LTy<'tcx>| {
+            let layout = tcx.layout_of(ParamEnv::reveal_all().and(lty.ty)).ok()?;
+            Some((layout.size, layout.align))
+        };
+        let pointer_positions = |lty: LTy<'tcx>| {
+            lty.iter()
+                .map(|lty| lty.label != PointerId::NONE)
+                .collect::<Vec<_>>()
+        };
+        layout(lty1).is_some()
+            && layout(lty1) == layout(lty2)
+            && pointer_positions(lty1) == pointer_positions(lty2)
+    }
+
+    /// Visit a callback passed to a library function, such as the comparison function of `qsort`,
+    /// as though it were called with the pointers `ptrs`.  The callback is a function pointer or
+    /// an `Option` of one.  When it refers to a known function, the pointers in its labeled type
+    /// are those of the function's signature, so the arguments flow into the function's
+    /// parameters.
+    fn visit_callback(&mut self, callback: &Operand<'tcx>, ptrs: &[PointerId]) {
+        self.visit_operand(callback);
+        let mut fn_lty = self.acx.type_of(callback);
+        if let TyKind::Adt(adt_def, _) = *fn_lty.kind() {
+            if self
+                .acx
+                .tcx()
+                .is_diagnostic_item(sym::Option, adt_def.did())
+            {
+                fn_lty = fn_lty.args[0];
+            }
+        }
+        if !matches!(fn_lty.kind(), TyKind::FnPtr(..)) {
+            return;
+        }
+        for (&ptr, input_lty) in ptrs.iter().zip(fn_lty.args) {
+            if ptr != PointerId::NONE && input_lty.label != PointerId::NONE {
+                self.add_edge(ptr, input_lty.label);
+            }
+        }
+    }
+
+    /// Visit a call whose callee isn't known statically, such as a call through a function pointer
+    /// or a trait object, or whose body we can't see, like a variadic extern, using only the
+    /// callee's signature.  Each pointer argument must allow
+    /// the accesses its declared type permits, so a pointer passed as `&mut T` or `*mut T` needs
+    /// `WRITE` as well as `READ`.
+    fn visit_sig_call(
+        &mut self,
+        sig: FnSig<'tcx>,
+        args: &[Operand<'tcx>],
+        dest: Place<'tcx>,
+        reason: &'static str,
+    ) {
+        for (arg_op, &input_ty) in args.iter().zip(sig.inputs()) {
+            self.visit_operand(arg_op);
+            let arg_lty = self.acx.type_of(arg_op);
+            self.add_sig_perms(arg_lty, input_ty, reason);
+        }
+        self.visit_variadic_args(&args[sig.inputs().len().min(args.len())..]);
+        self.visit_place(dest, Mutability::Mut);
+    }
+
+    /// Visit the arguments passed in the `...` of a variadic call.  The callee reads them with
+    /// `va_arg` at whatever type it likes, so each pointer gets all the permissions its own type
+    /// allows: `READ` for `*const T` and `READ | WRITE` for `*mut T`.
+    fn visit_variadic_args(&mut self, args: &[Operand<'tcx>]) {
+        for arg_op in args {
+            self.visit_operand(arg_op);
+            let arg_lty = self.acx.type_of(arg_op);
+            self.add_sig_perms(
+                arg_lty,
+                arg_lty.ty,
+                "pointer is passed as a variadic argument",
+            );
+        }
+    }
+
+    /// Require the pointers in `lty` to have the permissions allowed by the corresponding
+    /// pointer types in `ty`.  Trait objects are opaque, so the pointers inside them, if any, are
+    /// left alone.
+    fn add_sig_perms(&mut self, lty: LTy<'tcx>, ty: Ty<'tcx>, reason: &'static str) {
+        let (pointee_ty, mutbl) = match *ty.kind() {
+            TyKind::Ref(_, pointee_ty, mutbl) => (pointee_ty, mutbl),
+            TyKind::RawPtr(tm) => (tm.ty, tm.mutbl),
+            // Closures called through `Fn` traits take their arguments as a tuple.
+            TyKind::Tuple(tys) => {
+                for (&arg_lty, ty) in lty.args.iter().zip(tys) {
+                    self.add_sig_perms(arg_lty, ty, reason);
+                }
+                return;
+            }
+            _ => return,
+        };
+        if lty.label.is_none() {
+            return;
+        }
+        let perms = match mutbl {
+            Mutability::Not => PermissionSet::READ,
+            Mutability::Mut => PermissionSet::READ | PermissionSet::WRITE,
+        };
+        self.add_all_perms(lty.label, perms, reason);
+        if let Some(&pointee_lty) = lty.args.first() {
+            self.add_sig_perms(pointee_lty, pointee_ty, reason);
+        }
+    }
+
+    /// Visit a call to a local closure through one of the `Fn` traits.  The call passes the
+    /// closure (or a reference to it) and a tuple of arguments, while the closure body takes its
+    /// environment and the untupled arguments, so the arguments are matched up one at a time.
+    ///
+    /// See [`Callee::LocalClosure`].
+    fn visit_closure_call(&mut self, def_id: DefId, args: &[Operand<'tcx>], dest: Place<'tcx>) {
+        let sig = self.acx.gacx.fn_sigs[&def_id];
+        assert_eq!(args.len(), 2);
+        for op in args {
+            self.visit_operand(op);
+        }
+
+        // Calling a closure that implements `Fn` through `FnOnce` passes it by value, while its
+        // body takes a reference, so the environments can only be matched up if the types agree.
+        let env_lty = self.acx.type_of(&args[0]);
+        if env_lty.ty == sig.inputs[0].ty {
+            self.do_assign(sig.inputs[0], env_lty);
+        }
+
+        let tuple_lty = self.acx.type_of(&args[1]);
+        for (&arg_lty, &input_lty) in tuple_lty.args.iter().zip(&sig.inputs[1..]) {
+            self.do_assign(input_lty, arg_lty);
+        }
+
+        self.visit_place(dest, Mutability::Mut);
+        let dest_lty = self.acx.type_of(dest);
+        self.do_assign(dest_lty, sig.output);
+    }
+
+    /// Visit a call to an extern function that has a user-supplied model.
+    ///
+    /// See [`crate::extern_models`].
+    fn visit_modeled_call(&mut self, def_id: DefId, args: &[Operand<'tcx>], dest: Place<'tcx>) {
+        let tcx = self.acx.tcx();
+        let model = self
+            .acx
+            .gacx
+            .extern_models
+            .get(tcx.item_name(def_id).as_str())
+            .unwrap();
+
+        let mut arg_ltys = Vec::with_capacity(args.len());
+        for (i, arg_op) in args.iter().enumerate() {
+            self.visit_operand(arg_op);
+            let arg_lty = self.acx.type_of(arg_op);
+            arg_ltys.push(arg_lty);
+
+            // Apply the required permissions to each level of pointer described in the model.
+            let mut lty = arg_lty;
+            let mut arg_model = model.args.get(i);
+            while let Some(m) = arg_model {
+                if lty.label.is_none() {
+                    log::warn!("model for {def_id:?} arg {i} describes non-pointer type {lty:?}");
+                    break;
+                }
+                self.add_all_perms(lty.label, m.perms(), "required by the extern model");
+                arg_model = m.pointee.as_deref();
+                lty = lty.args[0];
+            }
+        }
+
+        for implies in &model.implies {
+            let lty = |i| {
+                *arg_ltys.get(i).unwrap_or_else(|| {
+                    panic!("model for {def_id:?} has implication for nonexistent arg {i}")
+                })
+            };
+            let (if_lty, then_lty) = (lty(implies.if_arg), lty(implies.arg));
+            if if_lty.label.is_none() || then_lty.label.is_none() {
+                log::warn!("model for {def_id:?} has implication for a non-pointer arg");
+                continue;
+            }
+            // The argument is usually a temporary copy of a pointer that gets its permissions
+            // from the rest of the function, so the condition applies to the places it was
+            // copied from as well.
+            let mut if_ptrs = vec![if_lty.label];
+            if let Some(pl) = args[implies.if_arg].place() {
+                for pl in util::copy_sources(self.mir, pl) {
+                    if_ptrs.extend(self.acx.ptr_of(pl).filter(|ptr| !if_ptrs.contains(ptr)));
+                }
+            }
+            for if_ptr in if_ptrs {
+                let origin = self.origin("required by the extern model");
+                self.constraints.add_implies(
+                    if_ptr,
+                    implies.if_perms(),
+                    then_lty.label,
+                    implies.perms(),
+                    origin,
+                );
+            }
+        }
+
+        self.visit_place(dest, Mutability::Mut);
+        if let Some(i) = model.returns_alias_of {
+            let dest_lty = self.acx.type_of(dest);
+            let arg_lty = *arg_ltys.get(i).unwrap_or_else(|| {
+                panic!("model for {def_id:?} returns alias of nonexistent arg {i}")
+            });
+            self.do_assign(dest_lty, arg_lty);
+        }
+    }
+
     /// Visit a local call, where local means
     /// local to the current crate with a static, known definition.
     ///
@@ -419,12 +1686,14 @@ impl<'tcx> TypeChecker<'tcx, '_> {
             todo!("call to generic function {def_id:?} {substs:?}");
         }
 
-        // Process pseudo-assignments from `args` to the types declared in `sig`.
+        // Process pseudo-assignments from `args` to the types declared in `sig`.  Any extra
+        // arguments are passed to a variadic function.
         for (arg_op, &input_lty) in args.iter().zip(sig.inputs.iter()) {
             self.visit_operand(arg_op);
             let arg_lty = self.acx.type_of(arg_op);
             self.do_assign(input_lty, arg_lty);
         }
+        self.visit_variadic_args(&args[sig.inputs.len().min(args.len())..]);
 
         // Process a pseudo-assignment from the return type declared in `sig` to `dest`.
         self.visit_place(dest, Mutability::Mut);
@@ -443,8 +1712,15 @@ pub fn visit<'tcx>(
         mir,
         constraints: DataflowConstraints::default(),
         equiv_constraints: Vec::new(),
+        null_checks: null_checks::find(acx.tcx(), mir),
+        loc: Location::START,
+        span: mir.span,
     };
 
+    for lty in &acx.local_tys {
+        tc.visit_safe_ptrs(lty);
+    }
+
     for (bb, bb_data) in mir.basic_blocks().iter_enumerated() {
         for (i, stmt) in bb_data.statements.iter().enumerate() {
             tc.visit_statement(
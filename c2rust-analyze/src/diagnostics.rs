@@ -0,0 +1,376 @@
+//! Collection of analysis findings, and output as SARIF for `--output-format=sarif`.
+//!
+//! Findings are recorded as [`Diagnostic`]s with a span and an explanation, rather than printed
+//! directly, so they can be output in different formats.  SARIF (the Static Analysis Results
+//! Interchange Format) is understood by GitHub code scanning and by many IDEs.
+use crate::context::{FlagSet, PermissionSet, PointerId};
+use crate::dataflow::explain::Conflict;
+use crate::dataflow::Origin;
+use crate::keep_going::UnsupportedReport;
+use crate::pointer_names;
+use crate::type_desc::{self, Ownership};
+use crate::use_after_free::UseAfterFree;
+use rustc_middle::ty::TyCtxt;
+use rustc_span::{FileName, Span};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    Note,
+    Warning,
+    Error,
+}
+
+impl Level {
+    fn sarif_name(self) -> &'static str {
+        match self {
+            Level::Note => "note",
+            Level::Warning => "warning",
+            Level::Error => "error",
+        }
+    }
+}
+
+/// A kind of finding.  Each rule is listed in the SARIF output with its description.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rule {
+    /// The analysis gave up on a function because of a construct it doesn't support.
+    UnsupportedConstruct,
+    /// A pointer is kept as a raw pointer.
+    RawPointer,
+    /// A pointer has constraints that no safe type can satisfy.
+    ConflictingConstraints,
+    /// A pointer's static permissions don't match its behavior in a PDG.  See [`crate::pdg`].
+    DynamicContradiction,
+    /// A pointer may be used after a call like `free` consumed it.
+    UseAfterFree,
+}
+
+impl Rule {
+    const ALL: [Rule; 5] = [
+        Rule::UnsupportedConstruct,
+        Rule::RawPointer,
+        Rule::ConflictingConstraints,
+        Rule::DynamicContradiction,
+        Rule::UseAfterFree,
+    ];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Rule::UnsupportedConstruct => "unsupported-construct",
+            Rule::RawPointer => "raw-pointer",
+            Rule::ConflictingConstraints => "conflicting-constraints",
+            Rule::DynamicContradiction => "dynamic-contradiction",
+            Rule::UseAfterFree => "use-after-free",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Rule::UnsupportedConstruct => {
+                "The function contains a construct the analysis doesn't support"
+            }
+            Rule::RawPointer => "The pointer can't be rewritten to a safe type",
+            Rule::ConflictingConstraints => "The pointer has uses that no single safe type allows",
+            Rule::DynamicContradiction => {
+                "The pointer's permissions don't match its behavior at run time"
+            }
+            Rule::UseAfterFree => "The pointer may be used after its memory is freed",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub rule: Rule,
+    pub level: Level,
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Diagnostics {
+    diags: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn add(&mut self, rule: Rule, level: Level, span: Span, message: impl Into<String>) {
+        self.diags.push(Diagnostic {
+            rule,
+            level,
+            span,
+            message: message.into(),
+        });
+    }
+
+    /// Record the functions the analysis gave up on.
+    pub fn add_unsupported(&mut self, report: &UnsupportedReport) {
+        for entry in report.entries() {
+            self.add(
+                Rule::UnsupportedConstruct,
+                Level::Error,
+                entry.source_span,
+                format!("giving up on {}: {}", entry.def_path, entry.reason),
+            );
+        }
+    }
+
+    /// Record why the top-level pointer of `what`, declared at `span`, stays raw, if it does.
+    pub fn add_raw_pointer(
+        &mut self,
+        span: Span,
+        what: &str,
+        perms: PermissionSet,
+        flags: FlagSet,
+    ) {
+        let (own, _) = type_desc::perms_to_desc(perms, flags);
+        if !matches!(own, Ownership::Raw | Ownership::RawMut | Ownership::NonNull) {
+            return;
+        }
+        if flags.contains(FlagSet::CVOID) && !perms.intersects(type_desc::BYTE_BUFFER_PERMS) {
+            self.add(
+                Rule::RawPointer,
+                Level::Note,
+                span,
+                format!(
+                    "{} is kept raw because it is an untyped `c_void` pointer that is never \
+                     accessed as bytes; consider a generic parameter or a trait object instead",
+                    what
+                ),
+            );
+        } else if flags.contains(FlagSet::FIXED) {
+            self.add(
+                Rule::RawPointer,
+                Level::Note,
+                span,
+                format!(
+                    "{} is kept raw because it is fixed by an annotation, the rewrite plan, \
+                     an unsupported construct, or a conversion from a non-pointer",
+                    what
+                ),
+            );
+        } else if perms.contains(PermissionSet::FREE) {
+            self.add(
+                Rule::ConflictingConstraints,
+                Level::Warning,
+                span,
+                format!(
+                    "{} is kept raw because it is freed, but isn't the unique owner of its \
+                     allocation",
+                    what
+                ),
+            );
+        }
+    }
+
+    /// Record the pointers that must stay raw because of how they're created, such as by casting
+    /// an integer to a pointer.
+    pub fn add_stuck_raw(&mut self, stuck: &[(PointerId, Origin)]) {
+        for &(ptr, ref origin) in stuck {
+            self.add(
+                Rule::RawPointer,
+                Level::Warning,
+                origin.span,
+                format!("{} must stay raw: {}", ptr, origin.reason),
+            );
+        }
+    }
+
+    /// Record the pointers that are freed, but share their allocation with another pointer.
+    pub fn add_conflicts(&mut self, conflicts: &[Conflict]) {
+        for c in conflicts {
+            pointer_names::set_current_fn(c.ldid);
+            let message = match c.shared {
+                Some(ref shared) => format!(
+                    "{} is freed ({}), but it can't be the unique owner of its allocation ({}); \
+                     it needs manual attention",
+                    c.ptr, c.freed.reason, shared.reason
+                ),
+                None => format!(
+                    "{} is freed ({}), but the borrow checker found it isn't the unique owner of \
+                     its allocation; it needs manual attention",
+                    c.ptr, c.freed.reason
+                ),
+            };
+            self.add(
+                Rule::ConflictingConstraints,
+                Level::Warning,
+                c.freed.span,
+                message,
+            );
+            if let Some(ref shared) = c.shared {
+                self.add(
+                    Rule::ConflictingConstraints,
+                    Level::Note,
+                    shared.span,
+                    format!("{} shares its allocation here: {}", c.ptr, shared.reason),
+                );
+            }
+        }
+    }
+
+    /// Record the uses of pointers after a call that consumes them, such as `free`.
+    pub fn add_uses_after_free(&mut self, uses: &[UseAfterFree]) {
+        for u in uses {
+            self.add(
+                Rule::UseAfterFree,
+                Level::Warning,
+                u.span,
+                format!(
+                    "{} may be used after {}",
+                    u.moved.ptr, u.moved.origin.reason
+                ),
+            );
+        }
+    }
+
+    /// Convert the diagnostics to a SARIF log.
+    pub fn to_sarif(&self, tcx: TyCtxt) -> sarif::Log {
+        let sm = tcx.sess.source_map();
+        let rules = Rule::ALL
+            .iter()
+            .map(|rule| sarif::ReportingDescriptor {
+                id: rule.id(),
+                short_description: sarif::Message::new(rule.description()),
+            })
+            .collect();
+        let results = self
+            .diags
+            .iter()
+            .map(|diag| {
+                let lo = sm.lookup_char_pos(diag.span.lo());
+                let hi = sm.lookup_char_pos(diag.span.hi());
+                let uri = match lo.file.name {
+                    FileName::Real(ref name) => name
+                        .local_path()
+                        .map_or_else(String::new, |p| p.display().to_string()),
+                    ref name => name.prefer_local().to_string(),
+                };
+                sarif::Result {
+                    rule_id: diag.rule.id(),
+                    level: diag.level.sarif_name(),
+                    message: sarif::Message::new(diag.message.clone()),
+                    locations: vec![sarif::Location {
+                        physical_location: sarif::PhysicalLocation {
+                            artifact_location: sarif::ArtifactLocation { uri },
+                            region: sarif::Region {
+                                start_line: lo.line,
+                                start_column: lo.col.0 + 1,
+                                end_line: hi.line,
+                                end_column: hi.col.0 + 1,
+                            },
+                        },
+                    }],
+                }
+            })
+            .collect();
+        sarif::Log {
+            schema: sarif::SCHEMA,
+            version: "2.1.0",
+            runs: vec![sarif::Run {
+                tool: sarif::Tool {
+                    driver: sarif::ToolComponent {
+                        name: "c2rust-analyze",
+                        information_uri: env!("CARGO_PKG_REPOSITORY"),
+                        rules,
+                    },
+                },
+                results,
+            }],
+        }
+    }
+
+    pub fn print_sarif(&self, tcx: TyCtxt) {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&self.to_sarif(tcx)).unwrap()
+        );
+    }
+}
+
+/// The subset of the SARIF 2.1.0 object model that we produce.
+pub mod sarif {
+    use serde::Serialize;
+
+    pub const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+    #[derive(Clone, Debug, Serialize)]
+    pub struct Log {
+        #[serde(rename = "$schema")]
+        pub schema: &'static str,
+        pub version: &'static str,
+        pub runs: Vec<Run>,
+    }
+
+    #[derive(Clone, Debug, Serialize)]
+    pub struct Run {
+        pub tool: Tool,
+        pub results: Vec<Result>,
+    }
+
+    #[derive(Clone, Debug, Serialize)]
+    pub struct Tool {
+        pub driver: ToolComponent,
+    }
+
+    #[derive(Clone, Debug, Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ToolComponent {
+        pub name: &'static str,
+        pub information_uri: &'static str,
+        pub rules: Vec<ReportingDescriptor>,
+    }
+
+    #[derive(Clone, Debug, Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ReportingDescriptor {
+        pub id: &'static str,
+        pub short_description: Message,
+    }
+
+    #[derive(Clone, Debug, Serialize)]
+    pub struct Message {
+        pub text: String,
+    }
+
+    impl Message {
+        pub fn new(text: impl Into<String>) -> Message {
+            Message { text: text.into() }
+        }
+    }
+
+    #[derive(Clone, Debug, Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Result {
+        pub rule_id: &'static str,
+        pub level: &'static str,
+        pub message: Message,
+        pub locations: Vec<Location>,
+    }
+
+    #[derive(Clone, Debug, Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Location {
+        pub physical_location: PhysicalLocation,
+    }
+
+    #[derive(Clone, Debug, Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct PhysicalLocation {
+        pub artifact_location: ArtifactLocation,
+        pub region: Region,
+    }
+
+    #[derive(Clone, Debug, Serialize)]
+    pub struct ArtifactLocation {
+        pub uri: String,
+    }
+
+    #[derive(Clone, Debug, Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Region {
+        pub start_line: usize,
+        pub start_column: usize,
+        pub end_line: usize,
+        pub end_column: usize,
+    }
+}
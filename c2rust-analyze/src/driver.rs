@@ -0,0 +1,156 @@
+//! Entry points for running the analysis: the `c2rust-analyze` command line, and [`run_analysis`]
+//! for tools that embed the analysis, such as IDE plugins and CI bots.
+//!
+//! [`run_analysis`] compiles one crate in the current process and returns the results, rather
+//! than printing them.  The [`AnalysisConfig`] gives the crate root and the other `rustc`
+//! arguments for the crate; for a crate built by `cargo`, these are the arguments `cargo` would
+//! pass to `rustc`, such as `--edition`, `--extern`, and `-L`.  The results hold the final
+//! permissions and flags of every pointer, as in `--output-format=json`, along with the proposed
+//! signature and field rewrites, as in `--rewrite-plan-out`, the source edits that implement all
+//! the rewrites, and an index of the results by source location, which
+//! [`AnalysisResults::permissions_of`] looks up.  The analysis still prints its usual text report
+//! to stderr, and the rewrite outputs requested in [`AnalysisConfig::options`] still take effect.
+//!
+//! Each call runs a full `rustc` session, so the embedding program must be able to load the
+//! `rustc` libraries, and may need `--sysroot` among the arguments.  A whole `cargo` workspace is
+//! analyzed by the command line's `cargo` mode instead, which runs the analysis in a separate
+//! process for each crate; see [`crate::cargo_wrapper`].
+use crate::annotations;
+use crate::cargo_wrapper;
+use crate::cfg_sets;
+use crate::json_output::CrateResults;
+use crate::log::init_logger;
+use crate::options::Options;
+use crate::query::{QueryIndex, SpanResults};
+use crate::rewrite::SourceEdit;
+use crate::rewrite_plan::PlanEntry;
+use rustc_driver::RunCompiler;
+use serde::Serialize;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// What to analyze, and how.
+#[derive(Clone, Debug, Default)]
+pub struct AnalysisConfig {
+    /// The root source file of the crate.
+    pub crate_root: PathBuf,
+    /// Other arguments for `rustc`.
+    pub rustc_args: Vec<String>,
+    /// The analysis options, as for the command line.
+    pub options: Options,
+}
+
+/// The results of analyzing a crate.
+#[derive(Clone, Debug, Serialize)]
+pub struct AnalysisResults {
+    /// The final permissions and flags of each pointer, and the summaries of the whole crate.
+    pub crate_results: CrateResults,
+    /// The proposed rewrites of signature and field types.
+    pub plan: Vec<PlanEntry>,
+    /// The edits to the source files that implement the rewrites.
+    pub edits: Vec<SourceEdit>,
+    /// The results of each variable and expression, by source location.
+    pub query: QueryIndex,
+}
+
+impl AnalysisResults {
+    /// The results of the innermost variable or expression in the file `path` that contains the
+    /// bytes `lo..hi`.  See [`QueryIndex::permissions_of`].
+    pub fn permissions_of(&self, path: &Path, lo: usize, hi: usize) -> Option<&SpanResults> {
+        self.query.permissions_of(path, lo, hi)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnalysisError {
+    /// `rustc` reported errors in the crate, so it couldn't be analyzed.
+    Compile,
+}
+
+impl fmt::Display for AnalysisError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AnalysisError::Compile => write!(f, "the crate failed to compile"),
+        }
+    }
+}
+
+impl Error for AnalysisError {}
+
+pub(crate) struct AnalysisCallbacks {
+    options: Options,
+    collect_edits: bool,
+    results: Option<AnalysisResults>,
+}
+
+impl AnalysisCallbacks {
+    pub fn new(options: Options) -> AnalysisCallbacks {
+        AnalysisCallbacks {
+            options,
+            collect_edits: false,
+            results: None,
+        }
+    }
+}
+
+impl rustc_driver::Callbacks for AnalysisCallbacks {
+    fn after_expansion<'tcx>(
+        &mut self,
+        _compiler: &rustc_interface::interface::Compiler,
+        queries: &'tcx rustc_interface::Queries<'tcx>,
+    ) -> rustc_driver::Compilation {
+        queries.global_ctxt().unwrap().peek_mut().enter(|tcx| {
+            self.results = Some(crate::run(tcx, &self.options, self.collect_edits));
+        });
+        rustc_driver::Compilation::Continue
+    }
+}
+
+/// Analyze the crate described by `config`.
+pub fn run_analysis(config: &AnalysisConfig) -> Result<AnalysisResults, AnalysisError> {
+    let mut args = vec![
+        "rustc".to_owned(),
+        config.crate_root.to_string_lossy().into_owned(),
+    ];
+    args.extend(config.rustc_args.iter().cloned());
+    args.extend(annotations::RUSTC_ARGS.iter().map(|&s| s.to_owned()));
+    analyze(&args, config.options.clone())
+}
+
+/// Compile and analyze the crate given by the full `rustc` command line `args`, and return the
+/// results.
+pub(crate) fn analyze(args: &[String], options: Options) -> Result<AnalysisResults, AnalysisError> {
+    let mut callbacks = AnalysisCallbacks::new(options);
+    callbacks.collect_edits = true;
+    RunCompiler::new(args, &mut callbacks)
+        .run()
+        .map_err(|_| AnalysisError::Compile)?;
+    callbacks.results.ok_or(AnalysisError::Compile)
+}
+
+/// Run the `c2rust-analyze` command, with the arguments of the current process.
+pub fn main() -> Result<(), AnalysisError> {
+    let mut args = env::args().collect::<Vec<_>>();
+    let own_exe = env::current_exe().unwrap();
+    let result = if cargo_wrapper::is_rustc_wrapper(&own_exe) {
+        // The arguments come from `cargo` and are meant for `rustc`, so our own options are
+        // passed through the environment instead.
+        let options = Options::from_env();
+        init_logger(options.log.as_deref());
+        cargo_wrapper::run_rustc_wrapper(&args, options)
+    } else {
+        let options = Options::take_from_args(&mut args);
+        init_logger(options.log.as_deref());
+        if cargo_wrapper::is_cargo_wrapper(&args) {
+            cargo_wrapper::run_cargo_wrapper(&own_exe, &args, &options);
+        }
+        args.extend(annotations::RUSTC_ARGS.iter().map(|&s| s.to_owned()));
+        if !options.cfg_sets.is_empty() {
+            return cfg_sets::run(&args, &options);
+        }
+        RunCompiler::new(&args, &mut AnalysisCallbacks::new(options)).run()
+    };
+    result.map_err(|_| AnalysisError::Compile)
+}
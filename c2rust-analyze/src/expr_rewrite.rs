@@ -1,12 +1,18 @@
+use crate::c_void_casts::{CVoidCastDirection, CVoidCasts};
 use crate::context::{AnalysisCtxt, Assignment, FlagSet, LTy, PermissionSet, PointerId};
+use crate::keep_going;
+use crate::out_params;
 use crate::pointer_id::PointerTable;
+use crate::reborrow::Liveness;
 use crate::type_desc::{self, Ownership, Quantity};
-use crate::util::{ty_callee, Callee};
+use crate::util::{self, ty_callee, Callee};
 use rustc_middle::mir::{
-    BasicBlock, Body, Location, Operand, Place, Rvalue, Statement, StatementKind, Terminator,
-    TerminatorKind,
+    BasicBlock, Body, Local, Location, Operand, Place, ProjectionElem, Rvalue, Statement,
+    StatementKind, Terminator, TerminatorKind,
 };
+use rustc_middle::ty::TyKind;
 use rustc_span::{Span, DUMMY_SP};
+use std::collections::HashSet;
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct ExprLoc {
@@ -35,12 +41,92 @@ pub enum SubLoc {
 pub enum RewriteKind {
     /// Replace `ptr.offset(i)` with something like `&ptr[i..]`.
     OffsetSlice { mutbl: bool },
+    /// Replace `ptr.offset(i)` with something like `&ptr[i]`.  This is used instead of
+    /// `OffsetSlice` when the result points to a single element.
+    OffsetIndex { mutbl: bool },
     /// Replace `slice` with `&slice[0]`.
     SliceFirst { mutbl: bool },
     /// Replace `ptr` with `&*ptr`, converting `&mut T` to `&T`.
     MutToImm,
+    /// Replace `ptr` with `&mut *ptr`, reborrowing a `&mut T` that's used again later instead of
+    /// moving it.  See [`crate::reborrow`].
+    Reborrow,
     /// Remove a call to `as_ptr` or `as_mut_ptr`.
     RemoveAsPtr,
+    /// Replace `CStr::from_ptr(s)`, `CString::from_raw(s)`, or `CString::into_raw(s)` with `s`,
+    /// which already has the type of the result.
+    RemoveCStrConversion,
+    /// Replace `s.as_ptr()` or `CStr::from_ptr(s)` with `&*s`, producing a `&CStr` from a `&CStr`
+    /// or a `CString`.
+    CStrBorrow,
+    /// Remove a cast like `ptr as *mut u8` between a `c_void` byte buffer and a byte pointer,
+    /// which both become `&[u8]` or `&mut [u8]`.
+    RemoveCast,
+    /// Replace `ptr.is_null()` with `ptr.is_none()`.
+    IsNullToIsNone,
+    /// Replace a null pointer, such as `ptr::null()` or `0 as *const T`, with `None`.
+    NullToNone,
+    /// Replace `ptr` with `ptr.unwrap()`, converting `Option<&T>` to `&T`.
+    Unwrap,
+    /// Replace `ptr` with `ptr.as_deref_mut().unwrap()` or `ptr.as_deref().unwrap()`, converting
+    /// an `Option<&mut T>` or `Option<Box<T>>` that's stored behind another pointer, and so can't
+    /// be moved out by `unwrap()`.
+    UnwrapRef { mutbl: bool },
+    /// Replace `ptr` with `Some(ptr)`, converting `&T` to `Option<&T>`.
+    WrapSome,
+    /// Replace `*ptr` with `ptr.get()`, reading through a `&Cell<T>`.
+    CellGet,
+    /// Replace `*ptr = x` with `ptr.set(x)`, writing through a `&Cell<T>`.
+    CellSet,
+    /// Replace `*ptr` with `*ptr.borrow()` or `*ptr.borrow_mut()`, accessing a `&RefCell<T>` or
+    /// `Rc<RefCell<T>>`.
+    RefCellBorrow { mutbl: bool },
+    /// Replace `ptr` with `Rc::clone(&ptr)`, copying a shared pointer.
+    CloneRc,
+    /// Replace `ptr` with `ptr.as_ptr()`, converting `NonNull<T>` to `*mut T`.
+    NonNullAsPtr,
+    /// Replace `ptr` with a raw pointer to the same address, like `&*ptr as *const T`, in a
+    /// comparison or a cast to an integer.  Comparing two safe pointers would compare their
+    /// pointees rather than their addresses.
+    AsRawPtr,
+    /// Replace `&mut x` or `addr_of_mut!(x)` with `NonNull::from(&mut x)`, or similarly for an
+    /// immutable place.
+    NonNullFrom,
+    /// Replace `addr_of_mut!(x)` with `NonNull::new_unchecked(addr_of_mut!(x))`, or similarly for
+    /// `addr_of!(x)`.  This is used instead of `NonNullFrom` when `x` may be unaligned, as a field
+    /// of a packed struct may be, since `NonNull::from` would need a reference to it.
+    NonNullNewUnchecked,
+    /// Replace `malloc(size) as *mut T` or `calloc(n, size) as *mut T` with
+    /// `Box::new(T::default())`.
+    AllocToBox,
+    /// Replace `malloc(size) as *mut T` with
+    /// `vec![T::default(); size / size_of::<T>()].into_boxed_slice()`, or `calloc(n, size) as
+    /// *mut T` with `vec![T::default(); n].into_boxed_slice()`.
+    AllocToBoxSlice,
+    /// Like `AllocToBoxSlice`, but produces a `Vec<T>`, without the `into_boxed_slice()`.
+    AllocToVec,
+    /// Replace `malloc(size) as *mut T` or `calloc(n, size) as *mut T` with
+    /// `Rc::new(RefCell::new(T::default()))`.
+    AllocToRc,
+    /// Replace `ptr = realloc(ptr as *mut c_void, size) as *mut T` with
+    /// `ptr.resize(size / size_of::<T>(), T::default())`.  This uses `resize` rather than
+    /// `reserve` because the new elements are accessed by indexing, which requires them to be
+    /// initialized.
+    ReallocToResize,
+    /// Replace `free(ptr as *mut c_void)` with `drop(ptr)`.
+    FreeToDrop,
+    /// Replace `ptr` with `&*ptr` or `&mut *ptr`, borrowing a `Box<T>`, `Vec<T>`, or `CString`.
+    BorrowOwned { mutbl: bool },
+    /// Replace `*out = value` with `out = value`, where `out` is an out-parameter that is now a
+    /// local variable.  See [`crate::out_params`].
+    OutParamWrite,
+    /// Replace `return x` with `return (x, out1, out2, ...)`, adding the values of the
+    /// out-parameters at indices `params`.  If the function originally returned `()` and has a
+    /// single out-parameter, this is just `return out1`.
+    ReturnOutParams { params: Vec<usize> },
+    /// Replace `f(a, &mut x)` with `(ret, x) = f(a)`, removing the out-parameter arguments at
+    /// indices `params` and assigning their values from the result instead.
+    OutParamCall { params: Vec<usize> },
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -55,6 +141,10 @@ struct ExprRewriteVisitor<'a, 'tcx> {
     flags: PointerTable<'a, FlagSet>,
     rewrites: &'a mut Vec<ExprRewrite>,
     mir: &'a Body<'tcx>,
+    c_void_casts: CVoidCasts<'tcx>,
+    /// Locals holding a copy of one of this function's out-parameters.
+    out_param_locals: HashSet<Local>,
+    liveness: Liveness<'a, 'tcx>,
     loc: ExprLoc,
 }
 
@@ -67,12 +157,21 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
     ) -> ExprRewriteVisitor<'a, 'tcx> {
         let perms = asn.perms();
         let flags = asn.flags();
+        let out_params = acx.gacx.out_params.get(&mir.source.def_id());
+        let out_param_locals = out_params
+            .into_iter()
+            .flatten()
+            .flat_map(|&i| out_params::param_copies(mir, Local::from_usize(i + 1)))
+            .collect();
         ExprRewriteVisitor {
             acx,
             perms,
             flags,
             rewrites,
             mir,
+            c_void_casts: CVoidCasts::new(mir, acx.tcx()),
+            out_param_locals,
+            liveness: Liveness::new(mir),
             loc: ExprLoc {
                 stmt: Location {
                     block: BasicBlock::from_usize(0),
@@ -91,8 +190,7 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
         r
     }
 
-    #[allow(dead_code)]
-    fn _enter_dest<F: FnOnce(&mut Self) -> R, R>(&mut self, f: F) -> R {
+    fn enter_dest<F: FnOnce(&mut Self) -> R, R>(&mut self, f: F) -> R {
         self.enter(SubLoc::Dest, f)
     }
 
@@ -113,24 +211,34 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
         self.enter(SubLoc::OperandPlace, f)
     }
 
-    #[allow(dead_code)]
-    fn _enter_place_pointer<F: FnOnce(&mut Self) -> R, R>(&mut self, i: usize, f: F) -> R {
+    fn enter_place_pointer<F: FnOnce(&mut Self) -> R, R>(&mut self, i: usize, f: F) -> R {
         self.enter(SubLoc::PlacePointer(i), f)
     }
 
     fn visit_statement(&mut self, stmt: &Statement<'tcx>, loc: Location) {
+        keep_going::set_current_span(stmt.source_info.span);
         self.loc = ExprLoc {
             stmt: loc,
             span: stmt.source_info.span,
             sub: Vec::new(),
         };
 
+        // Casts to and from `*mut c_void` around allocation calls are handled as part of the
+        // call.
+        if self.c_void_casts.should_skip_stmt(loc) {
+            return;
+        }
+
         match stmt.kind {
             StatementKind::Assign(ref x) => {
                 let (pl, ref rv) = **x;
                 let pl_ty = self.acx.type_of(pl);
                 self.enter_assign_rvalue(|v| v.visit_rvalue(rv, pl_ty));
-                // TODO: visit place
+                if self.out_param_locals.contains(&pl.local) && !pl.projection.is_empty() {
+                    self.enter_dest(|v| v.emit(RewriteKind::OutParamWrite));
+                } else {
+                    self.enter_dest(|v| v.visit_place_derefs(pl, true));
+                }
             }
             StatementKind::FakeRead(..) => {}
             StatementKind::SetDiscriminant { .. } => todo!("statement {:?}", stmt),
@@ -146,6 +254,7 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
     }
 
     fn visit_terminator(&mut self, term: &Terminator<'tcx>, loc: Location) {
+        keep_going::set_current_span(term.source_info.span);
         let tcx = self.acx.tcx();
         self.loc = ExprLoc {
             stmt: loc,
@@ -158,7 +267,13 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
             TerminatorKind::SwitchInt { .. } => {}
             TerminatorKind::Resume => {}
             TerminatorKind::Abort => {}
-            TerminatorKind::Return => {}
+            TerminatorKind::Return => {
+                if let Some(params) = self.acx.gacx.out_params.get(&self.mir.source.def_id()) {
+                    self.emit(RewriteKind::ReturnOutParams {
+                        params: params.clone(),
+                    });
+                }
+            }
             TerminatorKind::Unreachable => {}
             TerminatorKind::Drop { .. } => {}
             TerminatorKind::DropAndReplace { .. } => {}
@@ -182,6 +297,131 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
                         self.visit_slice_as_ptr(&args[0], pl_ty);
                         return;
                     }
+                    Callee::LocalDef { def_id, .. } => {
+                        if let Some(params) = self.acx.gacx.out_params.get(&def_id) {
+                            self.emit(RewriteKind::OutParamCall {
+                                params: params.clone(),
+                            });
+                        }
+                    }
+                    Callee::CStrFromPtr => {
+                        let arg_lty = self.acx.type_of(&args[0]);
+                        if !self.is_nullable(arg_lty.label) {
+                            match self.ownership(arg_lty) {
+                                Some(Ownership::CStr) => {
+                                    self.emit(RewriteKind::RemoveCStrConversion)
+                                }
+                                Some(Ownership::CString) => self.emit(RewriteKind::CStrBorrow),
+                                _ => {}
+                            }
+                        }
+                        return;
+                    }
+                    Callee::CStringFromRaw => {
+                        let arg_lty = self.acx.type_of(&args[0]);
+                        if self.ownership(arg_lty) == Some(Ownership::CString)
+                            && !self.is_nullable(arg_lty.label)
+                        {
+                            self.emit(RewriteKind::RemoveCStrConversion);
+                        }
+                        return;
+                    }
+                    Callee::CStringIntoRaw => {
+                        if self.ownership(pl_ty) == Some(Ownership::CString)
+                            && !self.is_nullable(pl_ty.label)
+                        {
+                            self.emit(RewriteKind::RemoveCStrConversion);
+                        }
+                        return;
+                    }
+                    Callee::CStrAsPtr => {
+                        if self.ownership(pl_ty) == Some(Ownership::CStr)
+                            && !self.is_nullable(pl_ty.label)
+                        {
+                            self.emit(RewriteKind::CStrBorrow);
+                        }
+                        return;
+                    }
+                    Callee::IsNull => {
+                        if let Some(ptr) = self.acx.ptr_of(&args[0]) {
+                            if self.is_nullable(ptr) {
+                                self.emit(RewriteKind::IsNullToIsNone);
+                            }
+                        }
+                        return;
+                    }
+                    Callee::Null => {
+                        if self.is_nullable(pl_ty.label) {
+                            self.emit(RewriteKind::NullToNone);
+                        }
+                        return;
+                    }
+                    Callee::Malloc | Callee::Calloc => {
+                        let out_ptr = self.c_void_casts.get_adjusted_place_or_default_to(
+                            loc,
+                            CVoidCastDirection::From,
+                            destination,
+                        );
+                        if self.ownership(self.acx.type_of(out_ptr)) == Some(Ownership::Rc) {
+                            self.emit(RewriteKind::AllocToRc);
+                            return;
+                        }
+                        let out_ptr = match self.acx.ptr_of(out_ptr) {
+                            Some(p) if self.is_owned(p) => p,
+                            _ => return,
+                        };
+                        let (_, qty) =
+                            type_desc::perms_to_desc(self.perms[out_ptr], self.flags[out_ptr]);
+                        if self.flags[out_ptr].contains(FlagSet::VEC) {
+                            self.emit(RewriteKind::AllocToVec);
+                            return;
+                        }
+                        match qty {
+                            Quantity::Single => self.emit(RewriteKind::AllocToBox),
+                            // `OffsetPtr` isn't implemented yet, so an owned buffer that's offset
+                            // backward becomes a `Box<[T]>`, like one that's only offset forward.
+                            Quantity::Slice | Quantity::OffsetPtr => {
+                                self.emit(RewriteKind::AllocToBoxSlice)
+                            }
+                        }
+                        return;
+                    }
+                    Callee::Realloc => {
+                        let out_ptr = self.c_void_casts.get_adjusted_place_or_default_to(
+                            loc,
+                            CVoidCastDirection::From,
+                            destination,
+                        );
+                        let in_ptr = args[0].place().map(|pl| {
+                            self.c_void_casts.get_adjusted_place_or_default_to(
+                                loc,
+                                CVoidCastDirection::To,
+                                pl,
+                            )
+                        });
+                        let is_vec = |pl| {
+                            self.acx
+                                .ptr_of(pl)
+                                .map_or(false, |p| self.flags[p].contains(FlagSet::VEC))
+                        };
+                        if is_vec(out_ptr) && in_ptr.map_or(false, is_vec) {
+                            self.emit(RewriteKind::ReallocToResize);
+                        }
+                        return;
+                    }
+                    Callee::Free => {
+                        if let Some(pl) = args[0].place() {
+                            let in_ptr = self.c_void_casts.get_adjusted_place_or_default_to(
+                                loc,
+                                CVoidCastDirection::To,
+                                pl,
+                            );
+                            if self.acx.ptr_of(in_ptr).map_or(false, |p| self.is_owned(p)) {
+                                self.emit(RewriteKind::FreeToDrop);
+                            }
+                        }
+                        return;
+                    }
                     _ => {}
                 }
 
@@ -208,7 +448,8 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
             TerminatorKind::GeneratorDrop => {}
             TerminatorKind::FalseEdge { .. } => {}
             TerminatorKind::FalseUnwind { .. } => {}
-            TerminatorKind::InlineAsm { .. } => todo!("terminator {:?}", term),
+            // The pointers used by inline assembly stay raw, so they need no rewrites.
+            TerminatorKind::InlineAsm { .. } => {}
         }
     }
 
@@ -222,25 +463,49 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
                 // TODO
             }
             Rvalue::Ref(_rg, _kind, _pl) => {
+                if self.ownership(expect_ty) == Some(Ownership::NonNull) {
+                    self.emit(RewriteKind::NonNullFrom);
+                }
                 // TODO
             }
             Rvalue::ThreadLocalRef(_def_id) => {
                 // TODO
             }
             Rvalue::AddressOf(_mutbl, _pl) => {
+                if self.ownership(expect_ty) == Some(Ownership::NonNull) {
+                    if util::is_packed_field_addr(self.acx.tcx(), self.mir, rv) {
+                        self.emit(RewriteKind::NonNullNewUnchecked);
+                    } else {
+                        self.emit(RewriteKind::NonNullFrom);
+                    }
+                }
                 // TODO
             }
             Rvalue::Len(_pl) => {
                 // TODO
             }
-            Rvalue::Cast(_kind, ref _op, _ty) => {
-                // TODO
-            }
-            Rvalue::BinaryOp(_bop, ref _ops) => {
+            Rvalue::Cast(_kind, ref op, ty) => {
+                if util::is_null_const(op)
+                    && !expect_ty.label.is_none()
+                    && self.is_nullable(expect_ty.label)
+                {
+                    self.emit(RewriteKind::NullToNone);
+                }
+                let op_lty = self.acx.type_of(op);
+                if util::is_byte_cast(self.acx.tcx(), op_lty.ty, ty)
+                    && !self.flags[op_lty.label].contains(FlagSet::FIXED)
+                {
+                    self.emit(RewriteKind::RemoveCast);
+                    self.enter_rvalue_operand(0, |v| v.visit_operand(op, expect_ty));
+                }
+                if ty.is_integral() {
+                    self.enter_rvalue_operand(0, |v| v.emit_as_raw_ptr(op, true));
+                }
                 // TODO
             }
-            Rvalue::CheckedBinaryOp(_bop, ref _ops) => {
-                // TODO
+            Rvalue::BinaryOp(_bop, ref ops) | Rvalue::CheckedBinaryOp(_bop, ref ops) => {
+                self.enter_rvalue_operand(0, |v| v.emit_as_raw_ptr(&ops.0, false));
+                self.enter_rvalue_operand(1, |v| v.emit_as_raw_ptr(&ops.1, false));
             }
             Rvalue::NullaryOp(..) => {}
             Rvalue::UnaryOp(_uop, ref _op) => {
@@ -264,6 +529,13 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
     fn visit_operand(&mut self, op: &Operand<'tcx>, expect_ty: LTy<'tcx>) {
         match *op {
             Operand::Copy(pl) | Operand::Move(pl) => {
+                // Raw pointers are always copied, but `Rc` has to be cloned.
+                if self.ownership(self.acx.type_of(pl)) == Some(Ownership::Rc) {
+                    self.emit(RewriteKind::CloneRc);
+                }
+                if self.needs_reborrow(pl, expect_ty) {
+                    self.emit(RewriteKind::Reborrow);
+                }
                 self.visit_place(pl, expect_ty);
             }
             Operand::Constant(..) => {}
@@ -275,7 +547,62 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
             let expect_ptr = expect_ty.label;
             self.emit_ptr_cast(ptr, expect_ptr);
         }
-        // TODO: walk over `pl` to handle all derefs (casts, `*x` -> `(*x).get()`)
+        self.visit_place_derefs(pl, false);
+    }
+
+    /// Emit rewrites for the derefs in `pl`.  `write` is set if `pl` is being assigned to, rather
+    /// than read.
+    fn visit_place_derefs(&mut self, pl: Place<'tcx>, write: bool) {
+        // TODO: handle other kinds of derefs (casts)
+        let tcx = self.acx.tcx();
+        let mut lty = self.acx.type_of(pl.local);
+        let mut i = 0;
+        for (j, proj) in pl.projection.iter().enumerate() {
+            if let ProjectionElem::Deref = proj {
+                // A nullable pointer must be unwrapped before it can be dereferenced.  One that
+                // was loaded by an earlier deref is borrowed rather than moved, unless it's
+                // `Copy`.
+                let own = self.ownership(lty);
+                if self.is_nullable(lty.label) {
+                    let rw = match own {
+                        Some(Ownership::Mut | Ownership::Box | Ownership::Vec) if i > 0 => {
+                            RewriteKind::UnwrapRef { mutbl: write }
+                        }
+                        _ => RewriteKind::Unwrap,
+                    };
+                    self.enter_place_pointer(i, |v| v.emit(rw));
+                }
+                if own == Some(Ownership::NonNull) {
+                    self.enter_place_pointer(i, |v| v.emit(RewriteKind::NonNullAsPtr));
+                }
+                if matches!(own, Some(Ownership::Cell | Ownership::Rc)) {
+                    // Only the last deref accesses the place being written.  Earlier derefs just
+                    // read the pointers leading to it.
+                    let is_last = !pl.projection[j + 1..]
+                        .iter()
+                        .any(|p| matches!(p, ProjectionElem::Deref));
+                    let is_write = write && is_last;
+                    let rw = if own == Some(Ownership::Rc)
+                        || type_desc::needs_ref_cell(tcx, lty.args[0], &self.perms, &self.flags)
+                    {
+                        Some(RewriteKind::RefCellBorrow { mutbl: is_write })
+                    } else if !is_write {
+                        Some(RewriteKind::CellGet)
+                    } else if j + 1 == pl.projection.len() {
+                        Some(RewriteKind::CellSet)
+                    } else {
+                        // `Cell` has no way to write to only part of its contents.
+                        eprintln!("unsupported write to part of a `Cell`: {:?}", pl);
+                        None
+                    };
+                    if let Some(rw) = rw {
+                        self.enter_place_pointer(i, |v| v.emit(rw));
+                    }
+                }
+                i += 1;
+            }
+            lty = self.acx.project(lty, &proj);
+        }
     }
 
     fn visit_operand_desc(
@@ -299,6 +626,10 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
     fn visit_ptr_offset(&mut self, op: &Operand<'tcx>, result_ty: LTy<'tcx>) {
         // Compute the expected type for the argument, and emit a cast if needed.
         let result_ptr = result_ty.label;
+        if self.flags[result_ptr].contains(FlagSet::FIXED) {
+            // The result stays a raw pointer, so the `offset` call is kept as is.
+            return;
+        }
         let (result_own, result_qty) =
             type_desc::perms_to_desc(self.perms[result_ptr], self.flags[result_ptr]);
 
@@ -306,22 +637,21 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
         // TODO: infer `arg_expect_qty` based on the type of offset this is (positive / unknown)
         let arg_expect_qty = match result_qty {
             Quantity::Single => Quantity::Slice,
-            Quantity::Slice => Quantity::Slice,
-            Quantity::OffsetPtr => todo!("OffsetPtr"),
+            Quantity::Slice | Quantity::OffsetPtr => Quantity::Slice,
         };
 
         self.enter_call_arg(0, |v| {
             v.visit_operand_desc(op, arg_expect_own, arg_expect_qty)
         });
 
-        // Emit `OffsetSlice` for the offset itself.
+        // Emit `OffsetSlice` or `OffsetIndex` for the offset itself.
         let mutbl = matches!(result_own, Ownership::Mut);
 
-        self.emit(RewriteKind::OffsetSlice { mutbl });
-
-        // If the result is `Single`, also insert an upcast.
-        if result_qty == Quantity::Single {
-            self.emit(RewriteKind::SliceFirst { mutbl });
+        // If the result is `Single`, index to get the element directly, rather than taking a
+        // subslice and then its first element.
+        match result_qty {
+            Quantity::Single => self.emit(RewriteKind::OffsetIndex { mutbl }),
+            _ => self.emit(RewriteKind::OffsetSlice { mutbl }),
         }
     }
 
@@ -355,12 +685,88 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
         });
     }
 
+    /// Check whether `ptr` will be rewritten to `Box<T>`.
+    fn is_owned(&self, ptr: PointerId) -> bool {
+        self.flags[ptr].contains(FlagSet::OWNED)
+    }
+
+    /// Get the ownership the raw pointer type `lty` will be rewritten to.  Returns `None` if
+    /// `lty` isn't a raw pointer.
+    fn ownership(&self, lty: LTy<'tcx>) -> Option<Ownership> {
+        let ptr = lty.label;
+        if ptr == PointerId::NONE || !matches!(lty.ty.kind(), TyKind::RawPtr(..)) {
+            return None;
+        }
+        let (own, _) = type_desc::perms_to_desc(self.perms[ptr], self.flags[ptr]);
+        Some(own)
+    }
+
+    /// Emit a rewrite that turns `op` back into a raw pointer, if it's a pointer that becomes a
+    /// safe type and whose address is compared or, if `to_int` is set, cast to an integer.
+    /// `NonNull` compares by address already, but needs `as_ptr` for an integer cast.
+    fn emit_as_raw_ptr(&mut self, op: &Operand<'tcx>, to_int: bool) {
+        let lty = self.acx.type_of(op);
+        if lty.label.is_none() || !self.flags[lty.label].contains(FlagSet::COMPARE) {
+            return;
+        }
+        match self.ownership(lty) {
+            None | Some(Ownership::Raw | Ownership::RawMut) => {}
+            Some(Ownership::NonNull) => {
+                if to_int {
+                    self.emit(RewriteKind::NonNullAsPtr);
+                }
+            }
+            Some(_) => self.emit(RewriteKind::AsRawPtr),
+        }
+    }
+
+    /// Check whether copying the pointer in `pl` into a pointer of type `expect_ty` needs an
+    /// explicit `&mut` reborrow.  This is the case when both become the same kind of `&mut`, and
+    /// `pl` is used again afterward or can't be moved out of.
+    fn needs_reborrow(&self, pl: Place<'tcx>, expect_ty: LTy<'tcx>) -> bool {
+        let (ptr, expect_ptr) = (self.acx.type_of(pl).label, expect_ty.label);
+        if self.ownership(self.acx.type_of(pl)) != Some(Ownership::Mut)
+            || expect_ptr == PointerId::NONE
+            || self.is_nullable(ptr)
+            || self.is_nullable(expect_ptr)
+            || self.flags[expect_ptr].contains(FlagSet::FIXED)
+        {
+            return false;
+        }
+        let desc = type_desc::perms_to_desc(self.perms[ptr], self.flags[ptr]);
+        let expect_desc = type_desc::perms_to_desc(self.perms[expect_ptr], self.flags[expect_ptr]);
+        if desc != expect_desc {
+            return false;
+        }
+        match pl.as_local() {
+            Some(local) => self.liveness.live_after(local, self.loc.stmt),
+            None => true,
+        }
+    }
+
+    /// Check whether `ptr` will be rewritten to an `Option` type.
+    fn is_nullable(&self, ptr: PointerId) -> bool {
+        if ptr == PointerId::NONE {
+            return false;
+        }
+        let flags = self.flags[ptr];
+        flags.contains(FlagSet::NULLABLE) && !flags.contains(FlagSet::FIXED)
+    }
+
     fn emit_ptr_cast(&mut self, ptr: PointerId, expect_ptr: PointerId) {
         assert!(expect_ptr != PointerId::NONE);
 
         let (own2, qty2) = type_desc::perms_to_desc(self.perms[expect_ptr], self.flags[expect_ptr]);
 
+        let nullable1 = self.is_nullable(ptr);
+        let nullable2 = self.is_nullable(expect_ptr);
+        if nullable1 && !nullable2 {
+            self.emit(RewriteKind::Unwrap);
+        }
         self.emit_cast(ptr, own2, qty2);
+        if !nullable1 && nullable2 {
+            self.emit(RewriteKind::WrapSome);
+        }
     }
 
     fn emit_cast(&mut self, ptr: PointerId, expect_own: Ownership, expect_qty: Quantity) {
@@ -373,11 +779,41 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
             return;
         }
 
+        if own1 == Ownership::NonNull && matches!(own2, Ownership::Raw | Ownership::RawMut) {
+            self.emit(RewriteKind::NonNullAsPtr);
+            return;
+        }
+
         if qty1 == qty2 && (own1, own2) == (Ownership::Mut, Ownership::Imm) {
             self.emit(RewriteKind::MutToImm);
             return;
         }
 
+        if own1 == own2 && (qty1, qty2) == (Quantity::Slice, Quantity::Single) {
+            let mutbl = matches!(own1, Ownership::Mut);
+            self.emit(RewriteKind::SliceFirst { mutbl });
+            return;
+        }
+
+        if (own1, own2) == (Ownership::CString, Ownership::CStr) {
+            self.emit(RewriteKind::BorrowOwned { mutbl: false });
+            return;
+        }
+
+        if qty1 == qty2 && matches!(own1, Ownership::Box | Ownership::Vec) {
+            match own2 {
+                Ownership::Imm => {
+                    self.emit(RewriteKind::BorrowOwned { mutbl: false });
+                    return;
+                }
+                Ownership::Mut => {
+                    self.emit(RewriteKind::BorrowOwned { mutbl: true });
+                    return;
+                }
+                _ => {}
+            }
+        }
+
         eprintln!(
             "unsupported cast kind: {:?} {:?} -> {:?}",
             self.perms[ptr],
@@ -0,0 +1,149 @@
+//! User-supplied models of extern functions.
+//!
+//! Calls to foreign functions such as `fopen` or `qsort` have no body for us to analyze, so by
+//! default they're treated as [`Callee::UnknownDef`](crate::util::Callee::UnknownDef).  A models
+//! file, passed with `--extern-models`, describes the permissions each such function requires of
+//! its pointer arguments, so calls to it can be handled precisely instead.  The file is a JSON
+//! object mapping function names to models:
+//!
+//! ```json
+//! {
+//!     "fopen": {
+//!         "args": [{ "perms": ["READ"] }, { "perms": ["READ"] }]
+//!     },
+//!     "strchr": {
+//!         "args": [{ "perms": ["READ", "OFFSET_ADD"] }, {}],
+//!         "returns_alias_of": 0
+//!     },
+//!     "strtol": {
+//!         "args": [{ "perms": ["READ"] }, { "perms": ["WRITE"], "pointee": {} }, {}]
+//!     },
+//!     "list_swap": {
+//!         "args": [{ "perms": ["READ"] }, { "perms": ["READ"] }],
+//!         "implies": [{ "if_arg": 1, "if_perms": ["WRITE"], "arg": 0, "perms": ["WRITE"] }]
+//!     }
+//! }
+//! ```
+//!
+//! `implies` lists permissions an argument needs only if another argument ends up with some
+//! permissions.  Above, the first argument of `list_swap` must be writable only if the second one
+//! is written through too, rather than in every call.
+use crate::context::PermissionSet;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ExternFnModel {
+    /// Model for each argument, in order.  Arguments beyond the end of the list (such as variadic
+    /// arguments) have no requirements.
+    #[serde(default)]
+    pub args: Vec<ArgModel>,
+    /// If set, the returned pointer may be derived from the pointer passed as this argument, so
+    /// the call is treated like a copy from that argument to the return value.
+    #[serde(default)]
+    pub returns_alias_of: Option<usize>,
+    /// Permissions arguments need only when other arguments have some permissions.
+    #[serde(default)]
+    pub implies: Vec<ImpliesModel>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ArgModel {
+    /// Names of the permissions the callee requires of this pointer, such as `"READ"`.
+    #[serde(default)]
+    pub perms: Vec<String>,
+    /// Requirements on the pointer this pointer points to, for arguments like `char **endptr`.
+    #[serde(default)]
+    pub pointee: Option<Box<ArgModel>>,
+}
+
+impl ArgModel {
+    pub fn perms(&self) -> PermissionSet {
+        parse_perms(&self.perms)
+    }
+}
+
+/// A conditional requirement: if argument `if_arg` has all of `if_perms`, argument `arg` needs
+/// all of `perms`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ImpliesModel {
+    pub if_arg: usize,
+    pub if_perms: Vec<String>,
+    pub arg: usize,
+    pub perms: Vec<String>,
+}
+
+impl ImpliesModel {
+    pub fn if_perms(&self) -> PermissionSet {
+        parse_perms(&self.if_perms)
+    }
+
+    pub fn perms(&self) -> PermissionSet {
+        parse_perms(&self.perms)
+    }
+}
+
+fn parse_perms(names: &[String]) -> PermissionSet {
+    names
+        .iter()
+        .map(|name| {
+            parse_perm(name).unwrap_or_else(|| panic!("unknown permission {name:?} in model"))
+        })
+        .collect()
+}
+
+pub fn parse_perm(name: &str) -> Option<PermissionSet> {
+    Some(match name {
+        "READ" => PermissionSet::READ,
+        "WRITE" => PermissionSet::WRITE,
+        "UNIQUE" => PermissionSet::UNIQUE,
+        "LINEAR" => PermissionSet::LINEAR,
+        "OFFSET_ADD" => PermissionSet::OFFSET_ADD,
+        "OFFSET_SUB" => PermissionSet::OFFSET_SUB,
+        "FREE" => PermissionSet::FREE,
+        _ => return None,
+    })
+}
+
+/// Models for extern functions, indexed by function name.
+#[derive(Clone, Debug, Default)]
+pub struct ExternModels {
+    models: HashMap<String, ExternFnModel>,
+}
+
+impl ExternModels {
+    pub fn load(path: &Path) -> ExternModels {
+        let file =
+            File::open(path).unwrap_or_else(|e| panic!("failed to open models {path:?}: {e}"));
+        let models: HashMap<String, ExternFnModel> = serde_json::from_reader(BufReader::new(file))
+            .unwrap_or_else(|e| panic!("failed to parse models {path:?}: {e}"));
+        // Check all permission names up front, rather than when the function is first called.
+        for model in models.values() {
+            let mut stack = model.args.iter().collect::<Vec<_>>();
+            while let Some(arg) = stack.pop() {
+                arg.perms();
+                stack.extend(arg.pointee.as_deref());
+            }
+            for implies in &model.implies {
+                implies.perms();
+                if implies.if_perms().contains(PermissionSet::UNIQUE) {
+                    panic!(
+                        "`implies` conditions can't use UNIQUE, which the analysis only removes"
+                    );
+                }
+            }
+        }
+        eprintln!(
+            "loaded {} extern function models from {path:?}",
+            models.len()
+        );
+        ExternModels { models }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ExternFnModel> {
+        self.models.get(name)
+    }
+}
@@ -0,0 +1,200 @@
+//! Calls through function pointers stored in struct fields.
+//!
+//! C code often dispatches through "vtable" structs of function pointers, like
+//! `struct ops { int (*read)(void *); }`.  A call through a function pointer normally gets only
+//! the constraints its signature implies, so every pointer passed to it needs all the permissions
+//! its declared type allows.  [`collect`] finds the functions stored into each function pointer
+//! field, in assignments and struct literals anywhere in the crate, including the initializers of
+//! statics.  When every value stored into a field is a known local function (or `None`), a call
+//! through a value loaded from the field is treated as a call to each of those functions, so the
+//! arguments only need the permissions the functions actually use.  Nullable fields are an
+//! `Option` of a function pointer, and the value may be unwrapped with `expect` or `unwrap`, as
+//! c2rust does, or matched with `Some(f)`.
+//!
+//! A field that's also assigned some other value, like a function pointer passed in as an
+//! argument or an extern function, may hold a function the analysis can't see, and calls through
+//! it keep using only the signature.  Values that reach a field without being stored to it by
+//! name, such as through `memcpy` or from C code, aren't seen.
+use rustc_hir as hir;
+use rustc_hir::def::{CtorOf, DefKind, Res};
+use rustc_hir::def_id::DefId;
+use rustc_hir::intravisit::{self, Visitor};
+use rustc_middle::hir::nested_filter;
+use rustc_middle::ty::{self, DefIdTree, Ty, TyCtxt, TyKind, TypeckResults};
+use rustc_span::sym;
+use std::collections::{HashMap, HashSet};
+
+/// The functions stored into each function pointer field.
+#[derive(Clone, Debug, Default)]
+pub struct FnPtrFields {
+    targets: HashMap<DefId, Vec<DefId>>,
+    /// Fields that may hold a function other than the ones in `targets`.
+    open: HashSet<DefId>,
+}
+
+impl FnPtrFields {
+    /// The functions that may be called through field `field`, or `None` if it may also hold a
+    /// function the analysis can't see.
+    pub fn targets(&self, field: DefId) -> Option<&[DefId]> {
+        if self.open.contains(&field) {
+            return None;
+        }
+        self.targets.get(&field).map(|v| &v[..])
+    }
+
+    /// Every field that has a function stored into it, with its targets, or `None` if it's open.
+    pub fn iter(&self) -> impl Iterator<Item = (DefId, Option<&[DefId]>)> + '_ {
+        self.targets
+            .keys()
+            .chain(self.open.iter().filter(|f| !self.targets.contains_key(f)))
+            .map(|&f| (f, self.targets(f)))
+    }
+}
+
+/// The function pointer type of a field of type `ty`, which may be wrapped in an `Option`.
+pub fn fn_ptr_ty<'tcx>(tcx: TyCtxt<'tcx>, ty: Ty<'tcx>) -> Option<Ty<'tcx>> {
+    match *ty.kind() {
+        TyKind::FnPtr(_) => Some(ty),
+        TyKind::Adt(adt, substs) if tcx.is_diagnostic_item(sym::Option, adt.did()) => {
+            let inner = substs.type_at(0);
+            matches!(inner.kind(), TyKind::FnPtr(_)).then_some(inner)
+        }
+        _ => None,
+    }
+}
+
+/// A value stored into a function pointer field.
+enum FnValue {
+    Null,
+    Fn(DefId),
+    Unknown,
+}
+
+struct Collector<'tcx> {
+    tcx: TyCtxt<'tcx>,
+    typeck: Option<&'tcx TypeckResults<'tcx>>,
+    fields: FnPtrFields,
+}
+
+impl<'tcx> Collector<'tcx> {
+    /// Classify the value `ex`, stored into a field of function pointer type `fn_ty`.
+    fn classify(&self, ex: &hir::Expr, fn_ty: Ty<'tcx>) -> FnValue {
+        let tcx = self.tcx;
+        let typeck = self.typeck.unwrap();
+        match ex.kind {
+            hir::ExprKind::Call(callee, [arg]) => match callee.kind {
+                hir::ExprKind::Path(ref qpath) => match typeck.qpath_res(qpath, callee.hir_id) {
+                    Res::Def(DefKind::Ctor(CtorOf::Variant, _), did)
+                        if Some(tcx.parent(did)) == tcx.lang_items().option_some_variant() =>
+                    {
+                        self.classify(arg, fn_ty)
+                    }
+                    _ => FnValue::Unknown,
+                },
+                _ => FnValue::Unknown,
+            },
+            hir::ExprKind::Cast(inner, _) => self.classify(inner, fn_ty),
+            hir::ExprKind::Path(ref qpath) => match typeck.qpath_res(qpath, ex.hir_id) {
+                Res::Def(DefKind::Fn | DefKind::AssocFn, did) if self.is_target(did, fn_ty) => {
+                    FnValue::Fn(did)
+                }
+                Res::Def(DefKind::Ctor(CtorOf::Variant, _), did)
+                    if Some(tcx.parent(did)) == tcx.lang_items().option_none_variant() =>
+                {
+                    FnValue::Null
+                }
+                _ => FnValue::Unknown,
+            },
+            _ => FnValue::Unknown,
+        }
+    }
+
+    /// Check whether `did` is a non-generic local function whose signature is exactly `fn_ty`,
+    /// so a call through the pointer can be treated as a call to it.
+    fn is_target(&self, did: DefId, fn_ty: Ty<'tcx>) -> bool {
+        let tcx = self.tcx;
+        if !did.is_local() || tcx.is_foreign_item(did) || tcx.generics_of(did).count() != 0 {
+            return false;
+        }
+        let fn_sig = tcx.erase_late_bound_regions(tcx.fn_sig(did));
+        let ptr_sig = tcx.erase_late_bound_regions(fn_ty.fn_sig(tcx));
+        tcx.erase_regions(fn_sig) == tcx.erase_regions(ptr_sig)
+    }
+
+    /// Record that `value` is stored into the field `field` of `adt`.
+    fn record(&mut self, adt: ty::AdtDef<'tcx>, field: usize, value: &hir::Expr) {
+        let field_did = adt.non_enum_variant().fields[field].did;
+        let fn_ty = match fn_ptr_ty(self.tcx, self.tcx.type_of(field_did)) {
+            Some(ty) => ty,
+            None => return,
+        };
+        match self.classify(value, fn_ty) {
+            FnValue::Null => {}
+            FnValue::Fn(did) => {
+                let targets = self.fields.targets.entry(field_did).or_default();
+                if !targets.contains(&did) {
+                    targets.push(did);
+                }
+            }
+            FnValue::Unknown => {
+                self.fields.open.insert(field_did);
+            }
+        }
+    }
+
+    /// The struct whose fields `ex` has, if it's a struct.
+    fn struct_of(&self, ex: &hir::Expr) -> Option<ty::AdtDef<'tcx>> {
+        match *self.typeck?.expr_ty_adjusted(ex).peel_refs().kind() {
+            TyKind::Adt(adt, _) if adt.is_struct() => Some(adt),
+            _ => None,
+        }
+    }
+}
+
+impl<'tcx> Visitor<'tcx> for Collector<'tcx> {
+    type NestedFilter = nested_filter::OnlyBodies;
+
+    fn nested_visit_map(&mut self) -> Self::Map {
+        self.tcx.hir()
+    }
+
+    fn visit_nested_body(&mut self, id: hir::BodyId) {
+        let old = self.typeck.replace(self.tcx.typeck_body(id));
+        intravisit::walk_body(self, self.tcx.hir().body(id));
+        self.typeck = old;
+    }
+
+    fn visit_expr(&mut self, ex: &'tcx hir::Expr<'tcx>) {
+        match ex.kind {
+            hir::ExprKind::Assign(lhs, rhs, _) => {
+                if let hir::ExprKind::Field(base, _) = lhs.kind {
+                    if let Some(adt) = self.struct_of(base) {
+                        let field = self.tcx.field_index(lhs.hir_id, self.typeck.unwrap());
+                        self.record(adt, field, rhs);
+                    }
+                }
+            }
+            hir::ExprKind::Struct(_, fields, _) => {
+                if let Some(adt) = self.struct_of(ex) {
+                    for f in fields {
+                        let field = self.tcx.field_index(f.hir_id, self.typeck.unwrap());
+                        self.record(adt, field, f.expr);
+                    }
+                }
+            }
+            _ => {}
+        }
+        intravisit::walk_expr(self, ex);
+    }
+}
+
+/// Find the functions stored into each function pointer field in the crate.
+pub fn collect(tcx: TyCtxt) -> FnPtrFields {
+    let mut collector = Collector {
+        tcx,
+        typeck: None,
+        fields: FnPtrFields::default(),
+    };
+    tcx.hir().visit_all_item_likes_in_crate(&mut collector);
+    collector.fields
+}
@@ -0,0 +1,274 @@
+//! A static HTML report of the analysis results, for `--html-report FILE`.
+//!
+//! The report shows the source of each analyzed function, with every variable of pointer type
+//! highlighted according to what it will be rewritten to: green for safe references and owned
+//! pointers, yellow for pointers that need `Cell`, and red for pointers that are stuck as raw
+//! pointers.  The rewritten type and the inferred permissions are shown after each variable, and
+//! in a tooltip.  An index at the top lists the functions with the number of pointers in each
+//! category.
+use crate::context::{AnalysisCtxt, Assignment};
+use crate::options::Assumption;
+use crate::type_desc::{self, Ownership};
+use rustc_middle::mir::{BindingForm, Body, ClearCrossCrate, LocalInfo};
+use rustc_span::Span;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+const STYLE: &str = "
+body { font-family: sans-serif; }
+pre { background: #f8f8f8; padding: 0.5em; border: 1px solid #ddd; }
+.safe { background: #c8f0c8; }
+.cell { background: #f8f0a0; }
+.raw { background: #f8c0c0; }
+.ty { color: #777; font-style: italic; }
+td { padding: 0 0.5em; }
+";
+
+/// How a pointer will be rewritten, for color-coding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Category {
+    /// A safe reference or owned pointer.
+    Safe,
+    /// A reference to a `Cell` or `RefCell`.
+    Cell,
+    /// A raw pointer, or `NonNull`.
+    Raw,
+}
+
+impl Category {
+    const ALL: [Category; 3] = [Category::Safe, Category::Cell, Category::Raw];
+
+    fn class(self) -> &'static str {
+        match self {
+            Category::Safe => "safe",
+            Category::Cell => "cell",
+            Category::Raw => "raw",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Category::Safe => "rewritable",
+            Category::Cell => "needs Cell",
+            Category::Raw => "stuck raw",
+        }
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// The span of the pattern that binds a user variable.
+fn user_pat_span(decl: &rustc_middle::mir::LocalDecl) -> Option<Span> {
+    match decl.local_info.as_deref() {
+        Some(LocalInfo::User(ClearCrossCrate::Set(BindingForm::Var(v)))) => Some(v.pat_span),
+        _ => None,
+    }
+}
+
+/// A highlighted region of a function's source.
+struct Annotation {
+    /// Byte range within the function's source text.
+    lo: usize,
+    hi: usize,
+    category: Category,
+    label: String,
+}
+
+struct FnReport {
+    name: String,
+    location: String,
+    source: String,
+    annotations: Vec<Annotation>,
+}
+
+impl FnReport {
+    fn count(&self, category: Category) -> usize {
+        self.annotations
+            .iter()
+            .filter(|ann| ann.category == category)
+            .count()
+    }
+
+    fn render_source(&self) -> String {
+        let mut out = String::new();
+        let mut pos = 0;
+        for ann in &self.annotations {
+            out.push_str(&escape(&self.source[pos..ann.lo]));
+            let label = escape(&ann.label);
+            write!(
+                out,
+                "<span class=\"{}\" title=\"{}\">{}</span><span class=\"ty\"> /* {} */</span>",
+                ann.category.class(),
+                label,
+                escape(&self.source[ann.lo..ann.hi]),
+                label,
+            )
+            .unwrap();
+            pos = ann.hi;
+        }
+        out.push_str(&escape(&self.source[pos..]));
+        out
+    }
+}
+
+#[derive(Default)]
+pub struct HtmlReport {
+    fns: Vec<FnReport>,
+    /// The names of the `--assume` assumptions the results depend on.
+    assumptions: Vec<&'static str>,
+}
+
+impl HtmlReport {
+    pub fn new(assume: &[Assumption]) -> HtmlReport {
+        HtmlReport {
+            fns: Vec::new(),
+            assumptions: assume.iter().map(|a| a.name()).collect(),
+        }
+    }
+
+    /// Record the results for the function `mir`.
+    pub fn add_fn<'tcx>(
+        &mut self,
+        acx: &AnalysisCtxt<'_, 'tcx>,
+        asn: &Assignment,
+        mir: &Body<'tcx>,
+    ) {
+        let tcx = acx.tcx();
+        let sm = tcx.sess.source_map();
+        let def_id = mir.source.def_id();
+        let fn_span = match def_id.as_local() {
+            Some(ldid) => tcx
+                .hir()
+                .span_with_body(tcx.hir().local_def_id_to_hir_id(ldid)),
+            None => return,
+        };
+        let source = match sm.span_to_snippet(fn_span) {
+            Ok(s) => s,
+            Err(_) => return,
+        };
+        let base = sm.lookup_byte_offset(fn_span.lo()).pos.0 as usize;
+
+        let mut annotations = Vec::new();
+        for (local, decl) in mir.local_decls.iter_enumerated() {
+            let lty = acx.local_tys[local];
+            let span = match user_pat_span(decl) {
+                Some(span) if !lty.label.is_none() && fn_span.contains(span) => span,
+                _ => continue,
+            };
+            if span.from_expansion() {
+                continue;
+            }
+            let perms = asn.perms()[lty.label];
+            let flags = asn.flags()[lty.label];
+            let category = match type_desc::perms_to_desc(perms, flags).0 {
+                Ownership::Raw | Ownership::RawMut | Ownership::NonNull => Category::Raw,
+                Ownership::Cell => Category::Cell,
+                _ => Category::Safe,
+            };
+            let ty = type_desc::convert_type(acx, lty, asn);
+            annotations.push(Annotation {
+                lo: sm.lookup_byte_offset(span.lo()).pos.0 as usize - base,
+                hi: sm.lookup_byte_offset(span.hi()).pos.0 as usize - base,
+                category,
+                label: format!("{:?}, perms = {:?}, flags = {:?}", ty, perms, flags),
+            });
+        }
+        annotations.sort_by_key(|ann| (ann.lo, ann.hi));
+        let mut end = 0;
+        annotations.retain(|ann| {
+            let keep = ann.lo >= end;
+            end = end.max(ann.hi);
+            keep
+        });
+
+        self.fns.push(FnReport {
+            name: tcx.def_path_str(def_id),
+            location: sm.span_to_diagnostic_string(fn_span.shrink_to_lo()),
+            source,
+            annotations,
+        });
+    }
+
+    pub fn render(&self, crate_name: &str) -> String {
+        let mut out = String::new();
+        let title = format!("c2rust-analyze report for {}", escape(crate_name));
+        write!(
+            out,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+             <style>{}</style>\n</head>\n<body>\n<h1>{}</h1>\n",
+            title, STYLE, title
+        )
+        .unwrap();
+
+        if !self.assumptions.is_empty() {
+            writeln!(
+                out,
+                "<p>Assuming: {}</p>",
+                escape(&self.assumptions.join(", "))
+            )
+            .unwrap();
+        }
+
+        out.push_str("<p>");
+        for category in Category::ALL {
+            write!(
+                out,
+                "<span class=\"{}\">{}</span> ",
+                category.class(),
+                category.description()
+            )
+            .unwrap();
+        }
+        out.push_str("</p>\n<table>\n<tr><th>function</th>");
+        for category in Category::ALL {
+            write!(out, "<th>{}</th>", category.description()).unwrap();
+        }
+        out.push_str("</tr>\n");
+        for (i, f) in self.fns.iter().enumerate() {
+            write!(
+                out,
+                "<tr><td><a href=\"#fn{}\">{}</a></td>",
+                i,
+                escape(&f.name)
+            )
+            .unwrap();
+            for category in Category::ALL {
+                write!(out, "<td>{}</td>", f.count(category)).unwrap();
+            }
+            out.push_str("</tr>\n");
+        }
+        out.push_str("</table>\n");
+
+        for (i, f) in self.fns.iter().enumerate() {
+            write!(
+                out,
+                "<h2 id=\"fn{}\">{}</h2>\n<p>{}</p>\n<pre>{}</pre>\n",
+                i,
+                escape(&f.name),
+                escape(&f.location),
+                f.render_source()
+            )
+            .unwrap();
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+
+    pub fn write(&self, path: &Path, crate_name: &str) {
+        fs::write(path, self.render(crate_name))
+            .unwrap_or_else(|e| panic!("failed to write {}: {}", path.display(), e));
+    }
+}
@@ -0,0 +1,131 @@
+//! Machine-readable output of the analysis results, for `--output-format=json`.
+//!
+//! For each function, this lists every pointer that appears in the types of its locals, with its
+//! final [`PermissionSet`] and [`FlagSet`].  Pointers that were unified by the equivalence
+//! analysis share a single [`PointerId`], so the equivalence class of a pointer is given as the
+//! list of places whose types contain it.  The results for the whole crate are printed to stdout
+//! as a single JSON object once the analysis is done.
+//!
+//! [`PermissionSet`]: crate::context::PermissionSet
+//! [`FlagSet`]: crate::context::FlagSet
+use crate::context::{AnalysisCtxt, Assignment};
+use crate::linked_lists::LinkedStruct;
+use crate::metrics::Summary;
+use crate::options::Assumption;
+use crate::pointer_id::PointerId;
+use indexmap::IndexMap;
+use rustc_hir::def_id::LOCAL_CRATE;
+use rustc_middle::mir::Body;
+use rustc_middle::ty::TyCtxt;
+use serde::Serialize;
+use std::fmt::Debug;
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CrateResults {
+    pub crate_name: String,
+    /// The names of the `--assume` assumptions the results depend on.
+    pub assumptions: Vec<String>,
+    pub functions: Vec<FnResults>,
+    /// The counts of pointers by outcome.  See [`crate::metrics`].
+    pub summary: Summary,
+    /// The self-referential structs, with suggested rewrites.  See [`crate::linked_lists`].
+    pub linked_structs: Vec<LinkedStruct>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FnResults {
+    pub def_path: String,
+    pub span: String,
+    pub pointers: Vec<PointerResults>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PointerResults {
+    /// The pointer's ID, such as `l3` or `g12`.
+    pub id: String,
+    pub perms: Vec<String>,
+    pub flags: Vec<String>,
+    /// The places whose types contain this pointer.  A place is written as its MIR local and
+    /// source text, and `&` indicates the pointer is the address of the local.
+    pub equiv: Vec<String>,
+    /// The location of the first place in `equiv`.
+    pub span: String,
+}
+
+/// Split the `Debug` output of a set of bitflags, like `READ | WRITE`, into the names of the
+/// individual flags.
+pub(crate) fn flag_names(flags: impl Debug) -> Vec<String> {
+    let s = format!("{:?}", flags);
+    s.split(" | ")
+        .filter(|&name| name != "(empty)")
+        .map(|name| name.to_owned())
+        .collect()
+}
+
+impl CrateResults {
+    pub fn new(tcx: TyCtxt, assume: &[Assumption]) -> CrateResults {
+        CrateResults {
+            crate_name: tcx.crate_name(LOCAL_CRATE).to_string(),
+            assumptions: assume.iter().map(|a| a.name().to_owned()).collect(),
+            functions: Vec::new(),
+            summary: Summary::default(),
+            linked_structs: Vec::new(),
+        }
+    }
+
+    /// Record the results for the function `mir`.
+    pub fn add_fn<'tcx>(
+        &mut self,
+        acx: &AnalysisCtxt<'_, 'tcx>,
+        asn: &Assignment,
+        mir: &Body<'tcx>,
+    ) {
+        let tcx = acx.tcx();
+        let sm = tcx.sess.source_map();
+        let perms = asn.perms();
+        let flags = asn.flags();
+
+        let mut places = IndexMap::<PointerId, Vec<(String, String)>>::new();
+        for (local, decl) in mir.local_decls.iter_enumerated() {
+            let desc = format!("{:?} ({})", local, crate::describe_local(tcx, decl));
+            let span = sm.span_to_diagnostic_string(decl.source_info.span);
+            let addr_of = acx.addr_of_local[local];
+            if !addr_of.is_none() {
+                places
+                    .entry(addr_of)
+                    .or_default()
+                    .push((format!("&{}", desc), span.clone()));
+            }
+            for ptr in acx.local_tys[local].iter().map(|lty| lty.label) {
+                if !ptr.is_none() {
+                    places
+                        .entry(ptr)
+                        .or_default()
+                        .push((desc.clone(), span.clone()));
+                }
+            }
+        }
+
+        let pointers = places
+            .into_iter()
+            .map(|(ptr, places)| PointerResults {
+                id: format!("{:?}", ptr),
+                perms: flag_names(perms[ptr]),
+                flags: flag_names(flags[ptr]),
+                span: places[0].1.clone(),
+                equiv: places.into_iter().map(|(desc, _)| desc).collect(),
+            })
+            .collect();
+
+        let def_id = mir.source.def_id();
+        self.functions.push(FnResults {
+            def_path: tcx.def_path_str(def_id),
+            span: sm.span_to_diagnostic_string(tcx.def_span(def_id)),
+            pointers,
+        });
+    }
+
+    pub fn print(&self) {
+        println!("{}", serde_json::to_string_pretty(self).unwrap());
+    }
+}
@@ -0,0 +1,125 @@
+//! Support for `--keep-going`, which continues past unsupported constructs instead of aborting.
+//!
+//! Many parts of the analysis still `panic!` or `todo!` on MIR constructs they don't handle.
+//! Normally this aborts the whole run.  With `--keep-going`, each per-function step is run under
+//! [`panic::catch_unwind`], and a panic marks the function as unanalyzed and records it in a
+//! report.  The analysis continues with the remaining functions, and the report is printed at the
+//! end.  The signatures of unanalyzed functions are marked [`FlagSet::FIXED`], so they keep their
+//! original raw pointer types, and their pointers get every permission their types allow before
+//! their callers are solved, since nothing is known about what their bodies do.
+//!
+//! [`FlagSet::FIXED`]: crate::context::FlagSet::FIXED
+use rustc_hir::def_id::LocalDefId;
+use rustc_middle::ty::TyCtxt;
+use rustc_span::Span;
+use std::any::Any;
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::panic::{self, AssertUnwindSafe};
+
+thread_local! {
+    /// The span of the statement or terminator currently being processed, if any.
+    static CURRENT_SPAN: Cell<Option<Span>> = Cell::new(None);
+}
+
+/// Record that the analysis is now processing the MIR statement or terminator at `span`.  If the
+/// analysis panics, this span is reported as the location of the unsupported construct.
+pub fn set_current_span(span: Span) {
+    CURRENT_SPAN.with(|c| c.set(Some(span)));
+}
+
+/// A function the analysis gave up on.
+#[derive(Clone, Debug)]
+pub struct Unsupported {
+    pub def_path: String,
+    /// The location of the construct that caused the failure, or of the whole function if the
+    /// failure happened outside any particular statement.
+    pub span: String,
+    /// The span of `span`, for [`crate::diagnostics`].
+    pub source_span: Span,
+    pub reason: String,
+}
+
+#[derive(Debug, Default)]
+pub struct UnsupportedReport {
+    keep_going: bool,
+    entries: Vec<Unsupported>,
+    failed: HashSet<LocalDefId>,
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(&s) = payload.downcast_ref::<&str>() {
+        s.to_owned()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".to_owned()
+    }
+}
+
+impl UnsupportedReport {
+    pub fn new(keep_going: bool) -> UnsupportedReport {
+        UnsupportedReport {
+            keep_going,
+            ..UnsupportedReport::default()
+        }
+    }
+
+    /// Run `f`, which processes function `ldid`.  In `--keep-going` mode, a panic in `f` is
+    /// recorded, `ldid` is marked as failed, and `None` is returned.  Otherwise, panics propagate
+    /// as usual.  Returns `None` without running `f` if `ldid` has already failed.
+    pub fn run<R>(&mut self, tcx: TyCtxt, ldid: LocalDefId, f: impl FnOnce() -> R) -> Option<R> {
+        if self.failed.contains(&ldid) {
+            return None;
+        }
+        if !self.keep_going {
+            return Some(f());
+        }
+
+        CURRENT_SPAN.with(|c| c.set(None));
+        let payload = match panic::catch_unwind(AssertUnwindSafe(f)) {
+            Ok(x) => return Some(x),
+            Err(payload) => payload,
+        };
+
+        let span = CURRENT_SPAN
+            .with(|c| c.take())
+            .unwrap_or_else(|| tcx.def_span(ldid));
+        let entry = Unsupported {
+            def_path: tcx.def_path_str(ldid.to_def_id()),
+            span: tcx.sess.source_map().span_to_diagnostic_string(span),
+            source_span: span,
+            reason: panic_message(&*payload),
+        };
+        eprintln!(
+            "error: giving up on {} at {}: {}",
+            entry.def_path, entry.span, entry.reason
+        );
+        self.entries.push(entry);
+        self.failed.insert(ldid);
+        None
+    }
+
+    /// Iterate over the functions whose analysis has failed.
+    pub fn failed_fns(&self) -> impl Iterator<Item = LocalDefId> + '_ {
+        self.failed.iter().copied()
+    }
+
+    /// Iterate over the functions the analysis gave up on, in the order they failed.
+    pub fn entries(&self) -> impl Iterator<Item = &Unsupported> {
+        self.entries.iter()
+    }
+
+    pub fn print(&self) {
+        if !self.keep_going {
+            return;
+        }
+        eprintln!(
+            "\n=== unsupported constructs: {} functions not analyzed ===",
+            self.entries.len()
+        );
+        for entry in &self.entries {
+            eprintln!("{} at {}: {}", entry.def_path, entry.span, entry.reason);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! A dump of a function's MIR with the labeled type of every place and rvalue, for
+//! `--dump-labeled-mir`.
+//!
+//! This is like rustc's `-Zdump-mir`, but each statement and terminator is followed by the `LTy`
+//! of the places it mentions and of its rvalue, so the `PointerId`s in the analysis output can be
+//! matched up with the MIR they come from.
+use crate::context::{AnalysisCtxt, LTy};
+use rustc_middle::mir::visit::{PlaceContext, Visitor};
+use rustc_middle::mir::{Body, Location, Place};
+use std::fmt::Write as _;
+
+/// Collects the distinct places mentioned by a statement or terminator, in order.
+#[derive(Default)]
+struct Places<'tcx> {
+    places: Vec<Place<'tcx>>,
+}
+
+impl<'tcx> Visitor<'tcx> for Places<'tcx> {
+    fn visit_place(&mut self, place: &Place<'tcx>, context: PlaceContext, location: Location) {
+        if !self.places.contains(place) {
+            self.places.push(*place);
+        }
+        self.super_place(place, context, location);
+    }
+}
+
+fn write_tys<'tcx>(
+    s: &mut String,
+    acx: &AnalysisCtxt<'_, 'tcx>,
+    places: &[Place<'tcx>],
+    rvalue_ty: Option<LTy<'tcx>>,
+) {
+    for &place in places {
+        writeln!(s, "        // {:?}: {:?}", place, acx.type_of(place)).unwrap();
+    }
+    if let Some(lty) = rvalue_ty {
+        writeln!(s, "        // rvalue: {:?}", lty).unwrap();
+    }
+}
+
+/// Print `mir` with the labeled types from `acx`.
+pub fn dump<'tcx>(acx: &AnalysisCtxt<'_, 'tcx>, mir: &Body<'tcx>) -> String {
+    let mut s = String::new();
+    for (local, decl) in mir.local_decls.iter_enumerated() {
+        writeln!(
+            s,
+            "let {:?}: {:?}; // {:?}",
+            local, acx.local_tys[local], decl.ty
+        )
+        .unwrap();
+    }
+    for (bb, data) in mir.basic_blocks().iter_enumerated() {
+        let cleanup = if data.is_cleanup { " (cleanup)" } else { "" };
+        writeln!(s, "\n{:?}{}: {{", bb, cleanup).unwrap();
+        for (statement_index, stmt) in data.statements.iter().enumerate() {
+            let loc = Location {
+                block: bb,
+                statement_index,
+            };
+            writeln!(s, "    {:?};", stmt).unwrap();
+            let mut places = Places::default();
+            places.visit_statement(stmt, loc);
+            write_tys(
+                &mut s,
+                acx,
+                &places.places,
+                acx.rvalue_tys.get(&loc).copied(),
+            );
+        }
+        let loc = mir.terminator_loc(bb);
+        writeln!(s, "    {:?};", data.terminator().kind).unwrap();
+        let mut places = Places::default();
+        places.visit_terminator(data.terminator(), loc);
+        write_tys(
+            &mut s,
+            acx,
+            &places.places,
+            acx.rvalue_tys.get(&loc).copied(),
+        );
+        writeln!(s, "}}").unwrap();
+    }
+    s
+}
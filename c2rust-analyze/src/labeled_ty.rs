@@ -54,6 +54,16 @@ impl<'tcx, L: Copy> LabeledTyS<'tcx, L> {
             arg.for_each_label(callback);
         }
     }
+
+    /// Like [`for_each_label`](Self::for_each_label), but visits the labels of a type's arguments
+    /// before the label of the type itself.  This is the same order in which
+    /// [`LabeledTyCtxt::relabel`] invokes its callback.
+    pub fn for_each_label_postorder<F: FnMut(L)>(&'tcx self, callback: &mut F) {
+        for &arg in self.args {
+            arg.for_each_label_postorder(callback);
+        }
+        callback(self.label);
+    }
 }
 
 impl<'tcx, L> LabeledTyS<'tcx, L> {
@@ -127,6 +137,10 @@ impl<'tcx, L: Copy> LabeledTyCtxt<'tcx, L> {
         }
     }
 
+    pub fn tcx(&self) -> TyCtxt<'tcx> {
+        self.tcx
+    }
+
     pub fn arena(&self) -> &'tcx DroplessArena {
         &self.tcx.arena.dropless
     }
@@ -202,10 +216,19 @@ impl<'tcx, L: Copy> LabeledTyCtxt<'tcx, L> {
                 let args = elems.iter().map(|ty| self.label(ty, f)).collect::<Vec<_>>();
                 self.mk(ty, self.mk_slice(&args), label)
             }
+            // A closure is labeled like a tuple of its captured variables.
+            Closure(_, substs) => {
+                let args = substs
+                    .as_closure()
+                    .upvar_tys()
+                    .map(|ty| self.label(ty, f))
+                    .collect::<Vec<_>>();
+                self.mk(ty, self.mk_slice(&args), label)
+            }
 
             // Types that aren't actually supported by this code yet
-            Dynamic(..) | Closure(..) | Generator(..) | GeneratorWitness(..) | Projection(..)
-            | Opaque(..) | Param(..) | Bound(..) | Placeholder(..) | Infer(..) | Error(..) => {
+            Dynamic(..) | Generator(..) | GeneratorWitness(..) | Projection(..) | Opaque(..)
+            | Param(..) | Bound(..) | Placeholder(..) | Infer(..) | Error(..) => {
                 self.mk(ty, &[], label)
             }
         }
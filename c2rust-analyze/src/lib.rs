@@ -0,0 +1,1983 @@
+//! Analysis and rewriting of unsafe pointer code, as used by the `c2rust-analyze` command.  Tools
+//! that embed the analysis can call [`run_analysis`]; see [`driver`] for details.
+#![feature(rustc_private)]
+extern crate either;
+extern crate rustc_arena;
+extern crate rustc_ast;
+extern crate rustc_const_eval;
+extern crate rustc_data_structures;
+extern crate rustc_driver;
+extern crate rustc_hir;
+extern crate rustc_index;
+extern crate rustc_interface;
+extern crate rustc_middle;
+extern crate rustc_mir_build;
+extern crate rustc_session;
+extern crate rustc_span;
+extern crate rustc_target;
+extern crate rustc_type_ir;
+
+use crate::alternatives::Alternatives;
+use crate::annotations::Annotations;
+use crate::borrowck::{AdtMetadata, FieldMetadata, OriginArg, OriginParam};
+use crate::cache::Cache;
+use crate::context::{
+    AnalysisCtxt, AnalysisCtxtData, FlagSet, GlobalAnalysisCtxt, GlobalAssignment, LFnSig, LTy,
+    LTyCtxt, LocalAssignment, PermissionSet, PointerId,
+};
+use crate::dataflow::dot::ConstraintGraph;
+use crate::dataflow::{explain, DataflowConstraints, Origin};
+use crate::diagnostics::Diagnostics;
+use crate::equiv::{GlobalEquivSet, LocalEquivSet};
+use crate::expr_rewrite::RewriteKind;
+use crate::extern_models::ExternModels;
+use crate::html_report::HtmlReport;
+use crate::keep_going::UnsupportedReport;
+use crate::labeled_ty::LabeledTyCtxt;
+use crate::lifetimes::TypePrinter;
+use crate::metrics::Outcome;
+use crate::pdg::PdgFacts;
+use crate::pointer_names::Namer;
+use crate::rewrite_plan::Planner;
+use crate::rewrite_styles::{RewriteStyles, Style};
+use crate::stats::{Phase, Stats};
+use crate::summary::ImportedSummaries;
+use crate::unsafety::UnsafeReduction;
+use crate::util::{Callee, SafePtr};
+use assert_matches::assert_matches;
+use indexmap::IndexSet;
+use labeled_ty::LabeledTy;
+use rustc_ast::Mutability;
+use rustc_hir::def::DefKind;
+use rustc_hir::def_id::{DefId, LocalDefId, LOCAL_CRATE};
+use rustc_index::vec::IndexVec;
+use rustc_middle::mir::visit::Visitor;
+use rustc_middle::mir::{
+    AggregateKind, BindingForm, Body, CastKind, Local, LocalDecl, LocalInfo, LocalKind, Location,
+    Operand, Rvalue, StatementKind,
+};
+use rustc_middle::ty::adjustment::PointerCast;
+use rustc_middle::ty::tls;
+use rustc_middle::ty::{GenericArgKind, Ty, TyCtxt, TyKind, WithOptConstParam};
+use rustc_span::Span;
+use rustc_type_ir::RegionKind::{ReEarlyBound, ReStatic};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::fs;
+use std::ops::{Deref, DerefMut};
+use std::time::Instant;
+
+mod abi_shims;
+mod alternatives;
+mod annotations;
+mod borrowck;
+mod c_void_casts;
+mod cache;
+mod cargo_wrapper;
+mod cfg_sets;
+mod context;
+mod dataflow;
+mod diagnostics;
+pub mod driver;
+mod equiv;
+mod expr_rewrite;
+mod extern_models;
+mod fn_ptr_fields;
+mod html_report;
+mod json_output;
+mod keep_going;
+mod labeled_mir;
+mod labeled_ty;
+mod lifetimes;
+mod linked_lists;
+mod log;
+mod metrics;
+mod null_checks;
+mod options;
+mod out_params;
+mod pdg;
+mod pointer_id;
+mod pointer_names;
+mod ptr_loops;
+mod query;
+mod reborrow;
+mod rewrite;
+mod rewrite_plan;
+mod rewrite_styles;
+mod statics;
+mod stats;
+mod summary;
+mod trivial;
+mod type_desc;
+mod unsafety;
+mod use_after_free;
+mod util;
+mod write_phase;
+
+pub use crate::alternatives::Alternative;
+pub use crate::driver::{run_analysis, AnalysisConfig, AnalysisError, AnalysisResults};
+pub use crate::json_output::{CrateResults, FnResults, PointerResults};
+pub use crate::linked_lists::{LinkedStruct, Shape, Suggestion};
+pub use crate::metrics::{Counts, Summary};
+pub use crate::options::{Assumption, CfgRewrites, ConstraintDump, Options, OutputFormat};
+pub use crate::query::{QueryIndex, SpanResults};
+pub use crate::rewrite::SourceEdit;
+pub use crate::rewrite_plan::PlanEntry;
+
+/// A wrapper around `T` that dynamically tracks whether it's initialized or not.
+/// [`RefCell`][std::cell::RefCell] dynamically tracks borrowing and panics if the rules are
+/// violated at run time; `MaybeUnset` dynamically tracks initialization and similarly panics if
+/// the value is accessed while unset.
+#[derive(Clone, Copy, Debug)]
+struct MaybeUnset<T>(Option<T>);
+
+impl<T> Default for MaybeUnset<T> {
+    fn default() -> MaybeUnset<T> {
+        MaybeUnset(None)
+    }
+}
+
+impl<T> MaybeUnset<T> {
+    pub fn set(&mut self, x: T) {
+        if self.0.is_some() {
+            panic!("value is already set");
+        }
+        self.0 = Some(x);
+    }
+
+    pub fn clear(&mut self) {
+        if self.0.is_none() {
+            panic!("value is already cleared");
+        }
+        self.0 = None;
+    }
+
+    pub fn get(&self) -> &T {
+        self.0.as_ref().expect("value is not set")
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.0.as_mut().expect("value is not set")
+    }
+
+    pub fn take(&mut self) -> T {
+        self.0.take().expect("value is not set")
+    }
+}
+
+impl<T> Deref for MaybeUnset<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.get()
+    }
+}
+
+impl<T> DerefMut for MaybeUnset<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.get_mut()
+    }
+}
+
+fn construct_adt_metadata<'tcx>(tcx: TyCtxt<'tcx>) -> AdtMetadataTable {
+    let struct_dids: Vec<_> = tcx
+        .hir_crate_items(())
+        .definitions()
+        .filter_map(|ldid: LocalDefId| {
+            use DefKind::*;
+            let did = ldid.to_def_id();
+            if matches!(tcx.def_kind(did), Struct | Enum | Union) {
+                return Some(did);
+            }
+
+            None
+        })
+        .collect();
+
+    let mut adt_metadata_table = AdtMetadataTable {
+        table: HashMap::new(),
+        struct_dids,
+    };
+
+    // Gather known lifetime parameters for each struct
+    for struct_did in &adt_metadata_table.struct_dids {
+        let struct_ty = tcx.type_of(struct_did);
+        if let TyKind::Adt(adt_def, substs) = struct_ty.kind() {
+            adt_metadata_table
+                .table
+                .insert(adt_def.did(), AdtMetadata::default());
+            ::log::debug!("gathering known lifetimes for {adt_def:?}");
+            for sub in substs.iter() {
+                if let GenericArgKind::Lifetime(r) = sub.unpack() {
+                    ::log::debug!("\tfound lifetime {r:?} in {adt_def:?}");
+                    assert_matches!(r.kind(), ReEarlyBound(eb) => {
+                        let _ = adt_metadata_table
+                        .table
+                        .entry(adt_def.did())
+                        .and_modify(|metadata| {
+                            metadata.lifetime_params.insert(OriginParam::Actual(eb));
+                        });
+                    });
+                }
+            }
+        } else {
+            panic!("{struct_ty:?} is not a struct");
+        }
+    }
+
+    let ltcx = LabeledTyCtxt::<'tcx, &[OriginArg<'tcx>]>::new(tcx);
+    let mut loop_count = 0;
+    loop {
+        /*
+            This loop iterates over all structs and gathers metadata for each.
+            If there were no recursive or mutually-recursive data structures,
+            this loop would only need one iteration to complete. To support
+            recursive and mutually-recursive structs, the loop iterates until
+            the metadata gathered for each struct reaches a fixed point.
+        */
+        loop_count += 1;
+        assert!(loop_count < 1000);
+
+        ::log::debug!(
+            "---- running fixed point struct field analysis iteration #{loop_count:?} ----"
+        );
+        let old_adt_metadata = adt_metadata_table.table.clone();
+        let mut next_hypo_origin_id = 0;
+
+        // for each struct, gather lifetime information (actual and hypothetical)
+        for struct_did in &adt_metadata_table.struct_dids {
+            let adt_def = tcx.adt_def(struct_did);
+            ::log::debug!("gathering lifetimes and lifetime parameters for {adt_def:?}");
+            for field in adt_def.all_fields() {
+                let field_ty: Ty = tcx.type_of(field.did);
+                ::log::debug!("\t{adt_def:?}.{:}", field.name);
+                let field_origin_args = ltcx.label(field_ty, &mut |ty| {
+                    let mut field_origin_args = IndexSet::new();
+                    match ty.kind() {
+                        TyKind::RawPtr(ty) => {
+                            ::log::debug!(
+                                "\t\tfound pointer that requires hypothetical lifetime: *{:}",
+                                if let Mutability::Mut = ty.mutbl {
+                                    "mut"
+                                } else {
+                                    "const"
+                                }
+                            );
+                            adt_metadata_table
+                                .table
+                                .entry(*struct_did)
+                                .and_modify(|adt| {
+                                    let origin_arg = OriginArg::Hypothetical(next_hypo_origin_id);
+                                    let origin_param =
+                                        OriginParam::Hypothetical(next_hypo_origin_id);
+                                    ::log::debug!(
+                                        "\t\t\tinserting origin {origin_param:?} into {adt_def:?}"
+                                    );
+
+                                    adt.lifetime_params.insert(origin_param);
+                                    next_hypo_origin_id += 1;
+                                    field_origin_args.insert(origin_arg);
+                                });
+                        }
+                        TyKind::Ref(reg, _ty, _mutability) => {
+                            ::log::debug!("\t\tfound reference field lifetime: {reg:}");
+                            assert_matches!(reg.kind(), ReEarlyBound(..) | ReStatic);
+                            let origin_arg = OriginArg::Actual(*reg);
+                            adt_metadata_table
+                                .table
+                                .entry(*struct_did)
+                                .and_modify(|adt| {
+                                    if let ReEarlyBound(eb) = reg.kind() {
+                                        ::log::debug!("\t\t\tinserting origin {eb:?} into {adt_def:?}");
+                                        adt.lifetime_params.insert(OriginParam::Actual(eb));
+                                    }
+
+                                    field_origin_args.insert(origin_arg);
+                                });
+                        }
+                        TyKind::Adt(adt_field, substs) => {
+                            ::log::debug!("\t\tfound ADT field base type: {adt_field:?}");
+                            for sub in substs.iter() {
+                                if let GenericArgKind::Lifetime(r) = sub.unpack() {
+                                    ::log::debug!("\tfound field lifetime {r:?} in {adt_def:?}.{adt_field:?}");
+                                    ::log::debug!("\t\t\tinserting {adt_field:?} lifetime param {r:?} into {adt_def:?}.{:} lifetime parameters", field.name);
+                                    assert_matches!(r.kind(), ReEarlyBound(..) | ReStatic);
+                                    field_origin_args.insert(OriginArg::Actual(r));
+                                }
+                            }
+                            if let Some(adt_field_metadata) =
+                                adt_metadata_table.table.get(&adt_field.did()).cloned()
+                            {
+                                // add a metadata entry for the struct field matching the metadata entry
+                                // for the struct definition of said field
+                                adt_metadata_table
+                                    .table
+                                    .insert(field.did, adt_field_metadata.clone());
+
+                                for adt_field_lifetime_param in adt_field_metadata.lifetime_params.iter() {
+                                    adt_metadata_table.table.entry(*struct_did).and_modify(|adt| {
+                                        if let OriginParam::Hypothetical(h) = adt_field_lifetime_param {
+                                            ::log::debug!("\t\t\tbubbling {adt_field:?} origin {adt_field_lifetime_param:?} up into {adt_def:?} origins");
+                                            field_origin_args.insert(OriginArg::Hypothetical(*h));
+                                            adt.lifetime_params.insert(*adt_field_lifetime_param);
+                                        }
+                                    });
+                                }
+                            }
+                        }
+                        _ => (),
+                    }
+
+                    if field_origin_args.is_empty() {
+                        return &[];
+                    }
+                    let field_origin_args: Vec<_> = field_origin_args.into_iter().collect();
+                    ltcx.arena().alloc_slice(&field_origin_args[..])
+                });
+
+                adt_metadata_table
+                    .table
+                    .entry(*struct_did)
+                    .and_modify(|adt| {
+                        adt.field_info.insert(
+                            field.did,
+                            FieldMetadata {
+                                origin_args: field_origin_args,
+                            },
+                        );
+                    });
+            }
+        }
+
+        if adt_metadata_table.table == old_adt_metadata {
+            ::log::debug!("reached a fixed point in struct lifetime reconciliation");
+            break;
+        }
+    }
+
+    adt_metadata_table
+}
+
+pub struct AdtMetadataTable<'tcx> {
+    pub table: HashMap<DefId, AdtMetadata<'tcx>>,
+    pub struct_dids: Vec<DefId>,
+}
+
+impl<'tcx> Debug for AdtMetadataTable<'tcx> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn fmt_string(lty: LabeledTy<'_, &[OriginArg]>) -> String {
+            let args: Vec<String> = lty.args.iter().map(|t| fmt_string(t)).collect();
+            use rustc_type_ir::TyKind::*;
+            match lty.kind() {
+                Ref(..) | RawPtr(..) => {
+                    format!("&{:?} {:}", lty.label[0], args[0])
+                }
+                Adt(adt, _) => {
+                    let mut s = format!("{adt:?}");
+                    let params = lty
+                        .label
+                        .iter()
+                        .map(|p| format!("{:?}", p))
+                        .into_iter()
+                        .chain(args.into_iter())
+                        .collect::<Vec<_>>()
+                        .join(",");
+
+                    if !params.is_empty() {
+                        s.push('<');
+                        s.push_str(&params);
+                        s.push('>');
+                    }
+                    s
+                }
+                Tuple(_) => {
+                    format!("({:})", args.join(","))
+                }
+                _ => format!("{:?}", lty.ty),
+            }
+        }
+
+        tls::with_opt(|tcx| {
+            let tcx = tcx.unwrap();
+            for k in &self.struct_dids {
+                let adt = &self.table[k];
+                write!(f, "struct {:}", tcx.item_name(*k))?;
+                write!(f, "<")?;
+                let lifetime_params_str = adt
+                    .lifetime_params
+                    .iter()
+                    .map(|p| format!("{:?}", p))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                write!(f, "{lifetime_params_str:}")?;
+                writeln!(f, "> {{")?;
+                for (fdid, fmeta) in &adt.field_info {
+                    write!(f, "\t{:}: ", tcx.item_name(*fdid))?;
+                    let field_string_lty = fmt_string(fmeta.origin_args);
+
+                    write!(f, "{field_string_lty:}")?;
+
+                    writeln!(f)?;
+                }
+
+                writeln!(f, "}}\n")?;
+            }
+            writeln!(f)
+        })
+    }
+}
+
+/// Run the analysis on the crate `tcx`.  `collect_edits` requests the source edits for the
+/// rewrites in the results, even if `options` doesn't ask for the rewrites to be output.
+fn run(tcx: TyCtxt, options: &Options, collect_edits: bool) -> AnalysisResults {
+    let mut gacx = GlobalAnalysisCtxt::new(tcx);
+    gacx.annotations.select_fns(tcx, &options.functions);
+    let mut report = UnsupportedReport::new(options.keep_going);
+    let rewrite_mode = options.rewrite_mode();
+    let mut planner = Planner::load(options.rewrite_plan.as_deref());
+    let gen_edits =
+        rewrite_mode.is_enabled() || options.rewrite_plan_out.is_some() || collect_edits;
+    let mut results = CrateResults::new(tcx, &options.assume);
+    let mut diagnostics = Diagnostics::default();
+    let mut html_report = HtmlReport::new(&options.assume);
+    let mut query_index = QueryIndex::default();
+    let mut imported_summaries = ImportedSummaries::load(tcx, options);
+    let mut cache = Cache::load(tcx, options, &gacx.annotations);
+    let mut stats = Stats::new(options.stats);
+    if let Some(ref path) = options.extern_models {
+        gacx.extern_models = ExternModels::load(path);
+    }
+    gacx.fn_ptr_fields = fn_ptr_fields::collect(tcx);
+    let mut func_info = HashMap::new();
+
+    /// Local information, specific to a single function.  Many of the data structures we use for
+    /// the pointer analysis have a "global" part that's shared between all functions and a "local"
+    /// part that's specific to the function being analyzed; this struct contains only the local
+    /// parts.  The different fields are set, used, and cleared at various points below.
+    #[derive(Default)]
+    struct FuncInfo<'tcx> {
+        /// Local analysis context data, such as [`LTy`]s for all MIR locals.  Combine with the
+        /// [`GlobalAnalysisCtxt`] to get a complete [`AnalysisCtxt`] for use within this function.
+        acx_data: MaybeUnset<AnalysisCtxtData<'tcx>>,
+        /// Dataflow constraints gathered from the body of this function.  These are used for
+        /// propagating `READ`/`WRITE`/`OFFSET_ADD` and similar permissions.
+        dataflow: MaybeUnset<DataflowConstraints>,
+        /// Local equivalence-class information.  Combine with the [`GlobalEquivSet`] to get a
+        /// complete [`EquivSet`], which assigns an equivalence class to each [`PointerId`] that
+        /// appears in the function.  Used for renumbering [`PointerId`]s.
+        local_equiv: MaybeUnset<LocalEquivSet>,
+        /// Local part of the permission/flag assignment.  Combine with the [`GlobalAssignment`] to
+        /// get a complete [`Assignment`] for this function, which maps every [`PointerId`] in this
+        /// function to a [`PermissionSet`] and [`FlagSet`].
+        lasn: MaybeUnset<LocalAssignment>,
+        /// Pointers the borrow checker removed `UNIQUE` from, with the conflicting borrow.
+        lost_unique: Vec<(PointerId, Origin)>,
+    }
+
+    // Follow a postorder traversal of the call graph's SCCs, so that callers are visited after
+    // their callees.  This means callee signatures will usually be up to date when we visit the
+    // call site.
+    let fn_sccs = fn_body_owners_sccs(tcx, &gacx.annotations);
+    let all_fn_ldids = fn_sccs.iter().flatten().copied().collect::<Vec<_>>();
+    ::log::debug!("callgraph traversal order:");
+    for scc in &fn_sccs {
+        ::log::debug!("  {:?}", scc);
+    }
+
+    // Assign global `PointerId`s for all pointers that appear in function signatures.
+    let start = Instant::now();
+    for &ldid in &all_fn_ldids {
+        let sig = util::body_fn_sig(tcx, ldid.to_def_id());
+
+        let inputs = sig
+            .inputs()
+            .iter()
+            .map(|&ty| gacx.assign_pointer_ids(ty))
+            .collect::<Vec<_>>();
+        let inputs = gacx.lcx.mk_slice(&inputs);
+        let output = gacx.assign_pointer_ids(sig.output());
+
+        let lsig = LFnSig { inputs, output };
+        gacx.fn_sigs.insert(ldid.to_def_id(), lsig);
+    }
+
+    // Assign global `PointerId`s for the signatures of external functions that have summaries.
+    imported_summaries.assign_pointer_ids(&mut gacx, &all_fn_ldids);
+
+    // Label the field types of each struct, and the type of each static.
+    for ldid in tcx.hir_crate_items(()).definitions() {
+        let did = ldid.to_def_id();
+        use DefKind::*;
+        match tcx.def_kind(did) {
+            Struct | Enum | Union => gacx.assign_pointer_to_fields(did),
+            Static(_) => gacx.assign_pointer_to_static(did),
+            _ => {}
+        }
+    }
+    stats.record(Phase::Labeling, start);
+
+    // Initial pass to assign local `PointerId`s and gather equivalence constraints, which state
+    // that two pointer types must be converted to the same reference type.  Some additional data
+    // computed during this the process is kept around for use in later passes.
+    let start = Instant::now();
+    let mut global_equiv = GlobalEquivSet::new(gacx.num_pointers());
+
+    // Fields of a union alias each other, so their pointers must all be converted the same way.
+    for ldid in tcx.hir_crate_items(()).definitions() {
+        let did = ldid.to_def_id();
+        if !matches!(tcx.def_kind(did), DefKind::Union) {
+            continue;
+        }
+        let mut no_locals = LocalEquivSet::new(0);
+        let mut equiv = global_equiv.and_mut(&mut no_locals);
+        for (a, b) in gacx.union_field_equiv_constraints(did) {
+            equiv.unify(a, b);
+        }
+    }
+    stats.record(Phase::Equivalence, start);
+
+    for &ldid in &all_fn_ldids {
+        let info = report.run(tcx, ldid, || {
+            let ldid_const = WithOptConstParam::unknown(ldid);
+            let mir = tcx.mir_built(ldid_const);
+            let mir = mir.borrow();
+            let lsig = *gacx.fn_sigs.get(&ldid.to_def_id()).unwrap();
+
+            let mut acx = gacx.function_context(&mir);
+
+            // Assign PointerIds to local types
+            let start = Instant::now();
+            assert!(acx.local_tys.is_empty());
+            acx.local_tys = IndexVec::with_capacity(mir.local_decls.len());
+            for (local, decl) in mir.local_decls.iter_enumerated() {
+                let lty = match mir.local_kind(local) {
+                    LocalKind::Var | LocalKind::Temp => acx.assign_pointer_ids(decl.ty),
+                    LocalKind::Arg => {
+                        debug_assert!(local.as_usize() >= 1 && local.as_usize() <= mir.arg_count);
+                        // The `VaListImpl` of a variadic function is an extra argument that
+                        // isn't part of the signature.
+                        match lsig.inputs.get(local.as_usize() - 1) {
+                            Some(&lty) => lty,
+                            None => acx.assign_pointer_ids(decl.ty),
+                        }
+                    }
+                    LocalKind::ReturnPointer => lsig.output,
+                };
+                let l = acx.local_tys.push(lty);
+                assert_eq!(local, l);
+
+                let ptr = acx.new_pointer();
+                let l = acx.addr_of_local.push(ptr);
+                assert_eq!(local, l);
+            }
+
+            for (bb, bb_data) in mir.basic_blocks().iter_enumerated() {
+                for (i, stmt) in bb_data.statements.iter().enumerate() {
+                    let (_, rv) = match &stmt.kind {
+                        StatementKind::Assign(x) => *x.clone(),
+                        _ => continue,
+                    };
+                    let lty = match rv {
+                        Rvalue::Aggregate(ref kind, ref _ops) => match **kind {
+                            AggregateKind::Array(elem_ty) => {
+                                let elem_lty = acx.assign_pointer_ids(elem_ty);
+                                let array_ty = rv.ty(&acx, acx.tcx());
+                                let args = acx.lcx().mk_slice(&[elem_lty]);
+                                acx.lcx().mk(array_ty, args, PointerId::NONE)
+                            }
+                            AggregateKind::Adt(..) => {
+                                acx.assign_pointer_ids(rv.ty(&acx, acx.tcx()))
+                            }
+                            // Use the closure type from the closure's own signature, so the
+                            // captured pointers are shared between this function and the
+                            // closure body.
+                            AggregateKind::Closure(def_id, _) => {
+                                match acx.gacx.fn_sigs.get(&def_id.to_def_id()) {
+                                    Some(lsig) => closure_lty(lsig),
+                                    None => acx.assign_pointer_ids(rv.ty(&acx, acx.tcx())),
+                                }
+                            }
+                            _ => continue,
+                        },
+                        // Converting a function to a function pointer uses the function's
+                        // signature, so calls through the pointer can be connected to it.
+                        Rvalue::Cast(
+                            CastKind::Pointer(PointerCast::ReifyFnPointer),
+                            ref op,
+                            ty,
+                        ) => match *op.ty(&acx, acx.tcx()).kind() {
+                            TyKind::FnDef(def_id, _) if acx.gacx.fn_sigs.contains_key(&def_id) => {
+                                let lsig = acx.gacx.fn_sigs[&def_id];
+                                let args = lsig
+                                    .inputs
+                                    .iter()
+                                    .copied()
+                                    .chain(Some(lsig.output))
+                                    .collect::<Vec<_>>();
+                                let args = acx.lcx().mk_slice(&args);
+                                acx.lcx().mk(ty, args, PointerId::NONE)
+                            }
+                            _ => acx.assign_pointer_ids(ty),
+                        },
+                        // Casts between `c_void` and typed pointers, other than byte pointers,
+                        // produce a new pointer that isn't connected to the old one.
+                        Rvalue::Cast(_, ref op, ty)
+                            if matches!(ty.kind(), TyKind::RawPtr(..))
+                                && op.ty(&acx, acx.tcx()).is_any_ptr()
+                                && (util::is_c_void_ptr(acx.tcx(), op.ty(&acx, acx.tcx()))
+                                    || util::is_c_void_ptr(acx.tcx(), ty))
+                                && !util::is_byte_cast(acx.tcx(), op.ty(&acx, acx.tcx()), ty) =>
+                        {
+                            acx.assign_pointer_ids(ty)
+                        }
+                        // Constant pointers, such as the address of a static or a string
+                        // literal, get fresh labels.  The address of a static points to the
+                        // static's own labeled type, so every function that uses the static
+                        // shares its pointers.
+                        Rvalue::Use(Operand::Constant(ref c)) if c.ty().is_any_ptr() => {
+                            let static_lty = c
+                                .check_static_ptr(acx.tcx())
+                                .and_then(|did| acx.gacx.static_tys.get(&did).copied());
+                            match static_lty {
+                                Some(static_lty) => {
+                                    let args = acx.lcx().mk_slice(&[static_lty]);
+                                    let ptr = acx.new_pointer();
+                                    acx.lcx().mk(c.ty(), args, ptr)
+                                }
+                                None => acx.assign_pointer_ids(c.ty()),
+                            }
+                        }
+                        // The address of a thread-local static likewise points to the static's
+                        // labeled type.
+                        Rvalue::ThreadLocalRef(did) => {
+                            let ty = rv.ty(&acx, acx.tcx());
+                            match acx.gacx.static_tys.get(&did).copied() {
+                                Some(static_lty) => {
+                                    let args = acx.lcx().mk_slice(&[static_lty]);
+                                    let ptr = acx.new_pointer();
+                                    acx.lcx().mk(ty, args, ptr)
+                                }
+                                None => acx.assign_pointer_ids(ty),
+                            }
+                        }
+                        // The address of a field of a packed struct may be unaligned, so it gets
+                        // its own pointer instead of sharing the pointer it's projected from.
+                        Rvalue::AddressOf(..)
+                            if util::is_packed_field_addr(acx.tcx(), &mir, &rv) =>
+                        {
+                            let loc = Location {
+                                block: bb,
+                                statement_index: i,
+                            };
+                            let lty = acx.type_of_rvalue(&rv, loc);
+                            let ptr = acx.new_pointer();
+                            acx.lcx().mk(lty.ty, lty.args, ptr)
+                        }
+                        // Integer-to-pointer casts, such as `0 as *mut T`, produce a fresh
+                        // pointer, which may be null.
+                        Rvalue::Cast(_, ref op, ty)
+                            if matches!(ty.kind(), TyKind::RawPtr(..))
+                                && op.ty(&acx, acx.tcx()).is_integral() =>
+                        {
+                            acx.assign_pointer_ids(ty)
+                        }
+                        _ => continue,
+                    };
+                    let loc = Location {
+                        block: bb,
+                        statement_index: i,
+                    };
+                    acx.rvalue_tys.insert(loc, lty);
+                }
+            }
+
+            stats.record(Phase::Labeling, start);
+
+            // Compute local equivalence classes and dataflow constraints.
+            let start = Instant::now();
+            let (dataflow, equiv_constraints) = dataflow::generate_constraints(&acx, &mir);
+            stats.record(Phase::Constraints, start);
+            let start = Instant::now();
+            let mut local_equiv = LocalEquivSet::new(acx.num_pointers());
+            let mut equiv = global_equiv.and_mut(&mut local_equiv);
+            for (a, b) in equiv_constraints {
+                equiv.unify(a, b);
+            }
+            stats.record(Phase::Equivalence, start);
+            stats.add_fn(
+                tcx.def_path_str(ldid.to_def_id()),
+                acx.num_pointers(),
+                dataflow.num_constraints(),
+            );
+
+            let mut info = FuncInfo::default();
+            info.acx_data.set(acx.into_data());
+            info.dataflow.set(dataflow);
+            info.local_equiv.set(local_equiv);
+            info
+        });
+        if let Some(info) = info {
+            func_info.insert(ldid, info);
+        }
+    }
+
+    // Remap pointers based on equivalence classes, so all members of an equivalence class now use
+    // the same `PointerId`.
+    let start = Instant::now();
+    let (global_counter, global_equiv_map) = global_equiv.renumber();
+    ::log::debug!("global_equiv_map = {global_equiv_map:?}");
+    let dump_constraints = options.debug_constraints == Some(ConstraintDump::Dot);
+    let mut constraint_graph = ConstraintGraph::default();
+    if dump_constraints {
+        let map = global_equiv_map.iter().map(|(old, &new)| (old, new));
+        constraint_graph.add_classes(tcx, None, map);
+    }
+    gacx.remap_pointers(&global_equiv_map, global_counter);
+
+    for &ldid in &all_fn_ldids {
+        let info = match func_info.get_mut(&ldid) {
+            Some(x) => x,
+            None => continue,
+        };
+        let (local_counter, local_equiv_map) = info.local_equiv.renumber(&global_equiv_map);
+        ::log::debug!("local_equiv_map = {local_equiv_map:?}");
+        if dump_constraints {
+            let map = local_equiv_map.iter().map(|(old, &new)| (old, new));
+            constraint_graph.add_classes(tcx, Some(ldid), map);
+        }
+        info.acx_data.remap_pointers(
+            gacx.lcx,
+            global_equiv_map.and(&local_equiv_map),
+            local_counter,
+        );
+        info.dataflow
+            .remap_pointers(global_equiv_map.and(&local_equiv_map));
+        info.local_equiv.clear();
+    }
+    stats.record(Phase::Equivalence, start);
+
+    // Name the pointers by where they appear in the source, now that each equivalence class has a
+    // single `PointerId`.
+    if options.stable_pointer_names {
+        let mut namer = Namer::new(&gacx);
+        for &ldid in &all_fn_ldids {
+            let info = match func_info.get_mut(&ldid) {
+                Some(x) => x,
+                None => continue,
+            };
+            let mir = tcx.mir_built(WithOptConstParam::unknown(ldid));
+            let mir = mir.borrow();
+            let acx = gacx.function_context_with_data(&mir, info.acx_data.take());
+            namer.add_fn(ldid, &mir, &acx);
+            info.acx_data.set(acx.into_data());
+        }
+        namer.install();
+    }
+
+    // Compute permission and flag assignments.
+    let start = Instant::now();
+
+    let mut gasn =
+        GlobalAssignment::new(gacx.num_pointers(), PermissionSet::UNIQUE, FlagSet::empty());
+    imported_summaries.apply(&gacx, &mut gasn);
+    for (ldid, info) in func_info.iter_mut() {
+        let num_pointers = info.acx_data.num_pointers();
+        let flags = gacx.annotations.flags(ldid.to_def_id());
+        let lasn = LocalAssignment::new(num_pointers, PermissionSet::UNIQUE, flags);
+        info.lasn.set(lasn);
+        info.dataflow.mark_c_void(&mut gasn.and(&mut info.lasn));
+        info.dataflow.mark_compared(&mut gasn.and(&mut info.lasn));
+        info.dataflow.mark_atomic(&mut gasn.and(&mut info.lasn));
+    }
+
+    // Apply the flags requested by annotations on signatures and fields, such as `FIXED` for
+    // `#[c2rust::keep_raw]`, and mark the `c_void` pointers in them, and the `Box` and `Vec`
+    // pointers, which point to the heap.
+    let sig_ltys = gacx.fn_sigs.iter().flat_map(|(&did, lsig)| {
+        let ltys = lsig.inputs.iter().copied().chain(Some(lsig.output));
+        ltys.map(move |lty| (did, lty))
+    });
+    let field_ltys = gacx.field_tys.iter().map(|(&did, &lty)| (did, lty));
+    // The global byte pointers, which may point to NUL-terminated strings.
+    let mut global_byte_ptrs = HashSet::new();
+    for (did, lty) in sig_ltys.chain(field_ltys) {
+        for lty in lty.iter() {
+            if !lty.label.is_none() && util::is_c_void_ptr(tcx, lty.ty) {
+                gasn.flags[lty.label].insert(FlagSet::CVOID);
+            }
+            if !lty.label.is_none() && util::is_byte_ptr(lty.ty) {
+                global_byte_ptrs.insert(lty.label);
+            }
+            if !lty.label.is_none()
+                && matches!(SafePtr::of(tcx, lty.ty), Some(SafePtr::Box | SafePtr::Vec))
+            {
+                gasn.flags[lty.label].insert(FlagSet::HEAP);
+            }
+        }
+        let flags = gacx.annotations.flags(did);
+        if flags.is_empty() {
+            continue;
+        }
+        for ptr in lty.iter().map(|lty| lty.label) {
+            if !ptr.is_none() {
+                gasn.flags[ptr].insert(flags);
+            }
+        }
+    }
+
+    // Keep the pointers whose rewrites were rejected in the rewrite plan.
+    planner.apply_rejections(&gacx, &mut gasn);
+
+    // Apply the rewrite styles the user chose for particular pointers.
+    let rewrite_styles = RewriteStyles::load(options.rewrite_styles.as_deref());
+    if !rewrite_styles.is_empty() {
+        let mut resolver = rewrite_styles.resolver(&gacx);
+        for &ldid in &all_fn_ldids {
+            let info = match func_info.get_mut(&ldid) {
+                Some(x) => x,
+                None => continue,
+            };
+            let mir = tcx.mir_built(WithOptConstParam::unknown(ldid));
+            let mir = mir.borrow();
+            let acx = gacx.function_context_with_data(&mir, info.acx_data.take());
+            resolver.add_fn(ldid, &mir, &acx.local_tys);
+            info.acx_data.set(acx.into_data());
+        }
+        eprintln!("\n=== rewrite styles ===");
+        for pinned in resolver.finish() {
+            pointer_names::set_current_fn(pinned.func);
+            eprintln!("{}: {} pinned to {}", pinned.name, pinned.ptr, pinned.style);
+            let flags = match pinned.func {
+                Some(ldid) => &mut func_info.get_mut(&ldid).unwrap().lasn.flags[pinned.ptr],
+                None => &mut gasn.flags[pinned.ptr],
+            };
+            match pinned.style {
+                Style::Raw | Style::Indices => flags.insert(FlagSet::FIXED),
+                Style::Rc => flags.insert(FlagSet::RC),
+                Style::References => flags.remove(FlagSet::RC),
+            }
+            if pinned.style == Style::References {
+                for (&ldid, info) in func_info.iter_mut() {
+                    if pinned.func.map_or(true, |func| func == ldid) {
+                        info.dataflow.pin_borrowed(pinned.ptr);
+                    }
+                }
+            }
+        }
+    }
+
+    // Start from the cached results of the functions that haven't changed since the last run.
+    cache.prepare(
+        &gacx,
+        func_info
+            .iter()
+            .map(|(&ldid, info)| (ldid, &*info.dataflow, info.acx_data.num_pointers())),
+        &mut gasn,
+    );
+
+    let adt_metadata = construct_adt_metadata(tcx);
+    ::log::debug!("=== ADT Metadata ===\n{adt_metadata:?}");
+
+    // Callers of the functions we gave up on are solved against their original signatures.
+    for ldid in report.failed_fns() {
+        fix_failed_sig(&gacx, &mut gasn, ldid);
+    }
+
+    let mut loop_count = 0;
+    loop {
+        // Loop until the global assignment reaches a fixpoint.  Functions are processed one SCC of
+        // the call graph at a time, in postorder, so permissions required by callees are mostly
+        // settled before their callers are visited.  Within an SCC, we iterate until the
+        // `GlobalAssignment` converges, since mutually recursive functions constrain each other's
+        // signatures.  Processing a caller can still remove permissions from a callee's signature,
+        // so the outer loop runs until a full pass over all SCCs leaves the assignment unchanged.
+        loop_count += 1;
+        let old_gasn = gasn.clone();
+        for scc in &fn_sccs {
+            let mut scc_loop_count = 0;
+            loop {
+                scc_loop_count += 1;
+                let old_scc_gasn = gasn.clone();
+                for &ldid in scc {
+                    let solved = report.run(tcx, ldid, || {
+                        let info = func_info.get_mut(&ldid).unwrap();
+                        if cache.try_restore(ldid, &gasn, &mut info.lasn) {
+                            let mut asn = gasn.and(&mut info.lasn);
+                            info.dataflow.propagate_nullable(&mut asn);
+                            return;
+                        }
+                        let ldid_const = WithOptConstParam::unknown(ldid);
+                        let name = util::fn_name(tcx, ldid.to_def_id());
+                        let mir = tcx.mir_built(ldid_const);
+                        let mir = mir.borrow();
+
+                        let field_tys = gacx.field_tys.clone();
+                        let acx = gacx.function_context_with_data(&mir, info.acx_data.take());
+                        let mut asn = gasn.and(&mut info.lasn);
+
+                        // `dataflow.propagate` and `borrowck_mir` both run until the assignment
+                        // converges on a fixpoint, so there's no need to do multiple iterations here.
+                        info.dataflow.propagate(&mut asn.perms_mut());
+                        info.dataflow.propagate_nullable(&mut asn);
+
+                        borrowck::borrowck_mir(
+                            &acx,
+                            &info.dataflow,
+                            &mut asn.perms_mut(),
+                            &mut info.lost_unique,
+                            name.as_str(),
+                            &mir,
+                            &adt_metadata,
+                            field_tys,
+                        );
+
+                        info.acx_data.set(acx.into_data());
+                    });
+                    if solved.is_none() {
+                        fix_failed_sig(&gacx, &mut gasn, ldid);
+                    }
+                }
+
+                // For a single-function SCC, the calls above already solve its constraints to a
+                // fixpoint, including those from any recursive calls to itself.
+                if scc.len() == 1 || gasn == old_scc_gasn {
+                    break;
+                }
+            }
+            ::log::debug!(
+                "scc {:?} reached fixpoint in {} iterations",
+                scc,
+                scc_loop_count
+            );
+        }
+
+        if gasn == old_gasn {
+            // Nested pointers may need weaker types than their own permissions allow.  Any new
+            // constraints call for another pass.
+            let mut changed = false;
+            for info in func_info.values_mut() {
+                let asn = gasn.and(&mut info.lasn);
+                changed |= info.dataflow.add_nested_constraints(asn.perms());
+            }
+            if !changed {
+                break;
+            }
+        }
+    }
+    eprintln!("reached fixpoint in {} iterations", loop_count);
+
+    if cache.is_enabled() {
+        let failed = report.failed_fns().collect::<HashSet<_>>();
+        for (&ldid, info) in &func_info {
+            if !failed.contains(&ldid) {
+                cache.record(ldid, &gasn, &info.lasn);
+            }
+        }
+        cache.save();
+    }
+
+    // Settle the uniqueness of pointers that the static analysis couldn't prove unique using the
+    // behavior observed at run time, and report static results that it contradicts.
+    let pdg_facts = PdgFacts::load(tcx, options.pdg.as_deref(), &all_fn_ldids);
+    if !pdg_facts.is_empty() {
+        eprintln!("\n=== pdg facts ===");
+        for &ldid in &all_fn_ldids {
+            let info = func_info.get_mut(&ldid).unwrap();
+            let mir = tcx.mir_built(WithOptConstParam::unknown(ldid));
+            let mir = mir.borrow();
+            let acx = gacx.function_context_with_data(&mir, info.acx_data.take());
+            let mut asn = gasn.and(&mut info.lasn);
+            pdg_facts.refine(&acx, &mut asn, &mir, &mut diagnostics);
+            info.acx_data.set(acx.into_data());
+        }
+    }
+
+    // Functions we gave up on after solving keep their original signatures too.
+    for ldid in report.failed_fns() {
+        fix_failed_sig(&gacx, &mut gasn, ldid);
+    }
+
+    // Structs passed by value to extern functions keep their layout, so every pointer in their
+    // fields stays raw, even if the extern functions are assumed not to keep the pointers.
+    let ffi_structs = func_info
+        .values()
+        .flat_map(|info| info.dataflow.ffi_structs().iter().copied())
+        .collect::<HashSet<_>>();
+    for &did in &ffi_structs {
+        for field in tcx.adt_def(did).all_fields() {
+            let lty = match gacx.field_tys.get(&field.did) {
+                Some(&lty) => lty,
+                None => continue,
+            };
+            for ptr in lty.iter().map(|lty| lty.label) {
+                if !ptr.is_none() {
+                    gasn.flags[ptr].insert(FlagSet::FIXED);
+                }
+            }
+        }
+    }
+
+    // Exported functions keep their ABI through a shim, unless some pointer in their signature
+    // can't be converted at the boundary, in which case the signature stays as it is.
+    let kept_abis = abi_shims::fix_unconvertible(&gacx, &mut gasn, &all_fn_ldids);
+
+    // `c_void` pointers that are never accessed as bytes are type-erased context pointers, which
+    // keep their type.
+    let is_context = |perms: PermissionSet, flags: FlagSet| {
+        flags.contains(FlagSet::CVOID) && !perms.intersects(type_desc::BYTE_BUFFER_PERMS)
+    };
+    for (ptr, flags) in gasn.flags.iter_mut() {
+        if is_context(gasn.perms[ptr], *flags) {
+            flags.insert(FlagSet::FIXED);
+        }
+    }
+    for info in func_info.values_mut() {
+        let lasn = &mut *info.lasn;
+        for (ptr, flags) in lasn.flags.iter_mut() {
+            if is_context(lasn.perms[ptr], *flags) {
+                flags.insert(FlagSet::FIXED);
+            }
+        }
+    }
+
+    // Pointers created from integers, and everything they flow into, must stay raw.  So must the
+    // pointers that are only safe to rewrite under an assumption the user didn't make.  Stuck
+    // global pointers are passed on to the other functions that use them.
+    let int_to_ptr = func_info
+        .values()
+        .any(|info| info.dataflow.creates_ptr_from_int());
+    for info in func_info.values_mut() {
+        info.dataflow.apply_assumptions(&options.assume, int_to_ptr);
+    }
+    let mut stuck_globals = HashSet::new();
+    loop {
+        let num_stuck = stuck_globals.len();
+        for &ldid in &all_fn_ldids {
+            let info = func_info.get_mut(&ldid).unwrap();
+            let mut asn = gasn.and(&mut info.lasn);
+            info.dataflow
+                .propagate_stuck_raw(&mut asn, &mut stuck_globals);
+        }
+        if stuck_globals.len() == num_stuck {
+            break;
+        }
+    }
+    for &ldid in &all_fn_ldids {
+        pointer_names::set_current_fn(Some(ldid));
+        diagnostics.add_stuck_raw(func_info[&ldid].dataflow.stuck_raw());
+        diagnostics.add_uses_after_free(func_info[&ldid].dataflow.uses_after_free());
+    }
+
+    // Find pointers that are never null.  Global pointers start out `NON_NULL` if any function
+    // assigns them a value, and lose the flag once some function assigns them a value that may be
+    // null.  The signatures of exported functions are left out, since C code can pass or expect
+    // null pointers there.
+    let mut assigned = HashSet::new();
+    for info in func_info.values() {
+        info.dataflow.assigned_global_pointers(&mut assigned);
+    }
+    let mut exported_sig_ptrs = HashSet::new();
+    for &ldid in &all_fn_ldids {
+        if abi_shims::is_exported(tcx, ldid.to_def_id()) {
+            let lsig = &gacx.fn_sigs[&ldid.to_def_id()];
+            for lty in lsig.inputs.iter().copied().chain(Some(lsig.output)) {
+                exported_sig_ptrs.extend(lty.iter().map(|lty| lty.label));
+            }
+        }
+    }
+    for &ptr in &assigned {
+        if !gasn.flags[ptr].contains(FlagSet::NULLABLE) && !exported_sig_ptrs.contains(&ptr) {
+            gasn.flags[ptr].insert(FlagSet::NON_NULL);
+        }
+    }
+    loop {
+        let mut changed = false;
+        for &ldid in &all_fn_ldids {
+            let info = func_info.get_mut(&ldid).unwrap();
+            let mut asn = gasn.and(&mut info.lasn);
+            changed |= info.dataflow.propagate_non_null(&mut asn);
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    // Find pointers to NUL-terminated strings the same way, starting from the global byte
+    // pointers that are assigned somewhere.
+    for &ptr in &assigned {
+        if global_byte_ptrs.contains(&ptr) {
+            gasn.flags[ptr].insert(FlagSet::NUL_TERMINATED);
+        }
+    }
+    loop {
+        let mut changed = false;
+        for &ldid in &all_fn_ldids {
+            let info = func_info.get_mut(&ldid).unwrap();
+            let mut asn = gasn.and(&mut info.lasn);
+            changed |= info.dataflow.propagate_nul_terminated(&mut asn);
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    // Find where the memory each pointer points to may come from.  Global pointers collect the
+    // provenance of every value that any function assigns them.
+    loop {
+        let mut changed = false;
+        for &ldid in &all_fn_ldids {
+            let info = func_info.get_mut(&ldid).unwrap();
+            let mut asn = gasn.and(&mut info.lasn);
+            changed |= info.dataflow.propagate_provenance(&mut asn);
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    // Find out-parameters that can be turned into return values.  This must be done before
+    // generating rewrites, since call sites in other functions are rewritten as well.
+    let mut out_params = HashMap::new();
+    let address_taken = out_params::address_taken(tcx);
+    for &ldid in &all_fn_ldids {
+        // Closures are called from code we can't rewrite, such as `sort_by`, so their signatures
+        // must stay the same.
+        if tcx.is_closure(ldid.to_def_id()) {
+            continue;
+        }
+        let params = report.run(tcx, ldid, || {
+            let info = func_info.get_mut(&ldid).unwrap();
+            let ldid_const = WithOptConstParam::unknown(ldid);
+            let mir = tcx.mir_built(ldid_const);
+            let mir = mir.borrow();
+            let acx = gacx.function_context_with_data(&mir, info.acx_data.take());
+            let asn = gasn.and(&mut info.lasn);
+            let params = out_params::find(&acx, &mir, &asn, &address_taken);
+            info.acx_data.set(acx.into_data());
+            params
+        });
+        if let Some(params) = params.filter(|p| !p.is_empty()) {
+            out_params.insert(ldid.to_def_id(), params);
+        }
+    }
+    gacx.out_params = out_params;
+
+    summary::export(&gacx, &gasn, &all_fn_ldids, options);
+
+    // With `--box-lists`, singly linked structs own their next node, where the analysis allows.
+    let mut linked_structs = linked_lists::find(&gacx);
+    if options.box_lists {
+        let roots = linked_structs
+            .iter()
+            .flat_map(|s| s.box_list_roots().iter().copied())
+            .collect::<HashSet<_>>();
+        for &ldid in &all_fn_ldids {
+            let info = func_info.get_mut(&ldid).unwrap();
+            info.dataflow
+                .seed_owned(&mut gasn.and(&mut info.lasn), &roots);
+        }
+    }
+
+    // Compute the remaining flags for every function before printing anything, since the
+    // rewritten types of signatures and fields depend on the flags from all functions.
+    for &ldid in &all_fn_ldids {
+        let info = func_info.get_mut(&ldid).unwrap();
+        report.run(tcx, ldid, || {
+            let mut asn = gasn.and(&mut info.lasn);
+            info.dataflow.propagate_cell(&mut asn);
+        });
+    }
+    // Global pointers are made candidate owners once, and each function can only reject them.  A
+    // rejection affects the locals of functions that were already processed, so every function is
+    // processed again, starting from its flags before ownership, until the global flags settle.
+    dataflow::seed_ownership(&mut gasn);
+    let local_flags = func_info
+        .iter()
+        .map(|(&ldid, info)| (ldid, info.lasn.flags.clone()))
+        .collect::<HashMap<_, _>>();
+    loop {
+        let old_flags = gasn.flags.clone();
+        for &ldid in &all_fn_ldids {
+            let info = func_info.get_mut(&ldid).unwrap();
+            info.lasn.flags = local_flags[&ldid].clone();
+            report.run(tcx, ldid, || {
+                let mut asn = gasn.and(&mut info.lasn);
+                info.dataflow.propagate_ownership(&mut asn);
+            });
+        }
+        if gasn.flags == old_flags {
+            break;
+        }
+    }
+    stats.record(Phase::Solving, start);
+
+    let fns = all_fn_ldids
+        .iter()
+        .map(|ldid| {
+            let info = &func_info[ldid];
+            explain::FnConstraints {
+                ldid: *ldid,
+                dataflow: &info.dataflow,
+                perms: gasn.perms.and(&info.lasn.perms),
+                lost_unique: &info.lost_unique,
+            }
+        })
+        .collect::<Vec<_>>();
+    if !options.explain.is_empty() {
+        for query in &options.explain {
+            let (fn_name, ptr_name) = match query.rsplit_once(':') {
+                Some((f, p)) => (Some(f), p),
+                None => (None, query.as_str()),
+            };
+            let ptr = explain::parse_pointer(ptr_name)
+                .unwrap_or_else(|| panic!("bad pointer {ptr_name:?} in --explain {query}"));
+            let ldid = fn_name.map(|name| {
+                *all_fn_ldids
+                    .iter()
+                    .find(|ldid| tcx.def_path_str(ldid.to_def_id()) == name)
+                    .unwrap_or_else(|| panic!("unknown function {name:?} in --explain {query}"))
+            });
+            eprintln!("\n{}", explain::explain(tcx, &fns, ldid, ptr));
+        }
+    }
+
+    // Report the pointers whose constraints contradict each other.  The solver leaves them raw,
+    // which is rarely what the user wants, so each needs a decision by hand.
+    let conflicts = explain::conflicts(tcx, &fns);
+    if !conflicts.is_empty() {
+        eprintln!("\n=== conflicting constraints ===");
+        for conflict in &conflicts {
+            eprint!("{}", conflict.description);
+        }
+        diagnostics.add_conflicts(&conflicts);
+    }
+
+    let start = Instant::now();
+    let empty_lasn = LocalAssignment::new(0, PermissionSet::UNIQUE, FlagSet::empty());
+    let struct_lifetimes = lifetimes::infer_struct_lifetimes(
+        &gacx,
+        gasn.perms.and(&empty_lasn.perms),
+        gasn.flags.and(&empty_lasn.flags),
+    );
+    let mut pointer_summary = metrics::Summary::default();
+    let alternatives = options.alternatives.then(|| {
+        Alternatives::new(
+            &gacx,
+            &gasn,
+            all_fn_ldids.iter().map(|ldid| {
+                let info = &func_info[ldid];
+                (*ldid, &*info.dataflow, &*info.lasn)
+            }),
+        )
+    });
+    eprintln!("\n=== rewritten struct definitions ===");
+    let mut edits = Vec::new();
+    {
+        let printer = TypePrinter::new(
+            &gacx,
+            gasn.perms.and(&empty_lasn.perms),
+            gasn.flags.and(&empty_lasn.flags),
+            &struct_lifetimes,
+        );
+        for ldid in tcx.hir_crate_items(()).definitions() {
+            if tcx.def_kind(ldid) == DefKind::Struct {
+                eprintln!("{}", lifetimes::print_struct(&printer, ldid.to_def_id()));
+                for field in tcx.adt_def(ldid).all_fields() {
+                    let lty = gacx.field_tys[&field.did];
+                    if let Some(ref alternatives) = alternatives {
+                        let new_lt = struct_lifetimes.get(&ldid.to_def_id()).map(|s| s.as_str());
+                        let name = |_| new_lt.map(|s| s.to_owned());
+                        let alts = alternatives.for_type(&printer, lty, &name, &name, new_lt);
+                        alternatives::print(&tcx.def_path_str(field.did), "field", &alts);
+                    }
+                    if !lty.label.is_none() && SafePtr::of(tcx, lty.ty).is_none() {
+                        let (perms, flags) = (gasn.perms[lty.label], gasn.flags[lty.label]);
+                        diagnostics.add_raw_pointer(
+                            tcx.def_span(field.did),
+                            &format!("field `{}`", field.name),
+                            perms,
+                            flags,
+                        );
+                        let module = metrics::module_name(tcx, field.did.expect_local());
+                        pointer_summary.add(&module, None, Outcome::of(perms, flags, None));
+                    }
+                }
+                if gen_edits {
+                    edits.extend(rewrite::struct_edits(
+                        &gacx,
+                        &printer,
+                        &struct_lifetimes,
+                        &mut planner,
+                        alternatives.as_ref(),
+                        ldid,
+                    ));
+                }
+            }
+        }
+    }
+
+    eprintln!("\n=== linked structures ===");
+    linked_lists::mark_applied(&mut linked_structs, &gasn);
+    for s in &linked_structs {
+        eprintln!("{}", s);
+    }
+    results.linked_structs = linked_structs;
+
+    eprintln!("\n=== function pointer fields ===");
+    let mut fn_ptr_fields = gacx
+        .fn_ptr_fields
+        .iter()
+        .map(|(field, targets)| {
+            let targets = match targets {
+                Some(targets) => targets
+                    .iter()
+                    .map(|&did| tcx.def_path_str(did))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                None => "unknown functions".to_owned(),
+            };
+            (tcx.def_path_str(field), targets)
+        })
+        .collect::<Vec<_>>();
+    fn_ptr_fields.sort();
+    for (field, targets) in fn_ptr_fields {
+        eprintln!("field {}: calls {}", field, targets);
+    }
+
+    let static_rewrites = statics::analyze(tcx, options.assumes(Assumption::SingleThreaded));
+    let rewritten_statics = static_rewrites
+        .iter()
+        .map(|rw| rw.did.to_def_id())
+        .collect::<HashSet<_>>();
+    eprintln!("\n=== rewritten statics ===");
+    for rw in &static_rewrites {
+        let new_ty = rw.new_type(tcx).unwrap_or_default();
+        let name = tcx.def_path_str(rw.did.to_def_id());
+        eprintln!("static {}: {} ({})", name, new_ty, rw.kind);
+        if gen_edits {
+            edits.extend(rw.edits(tcx).into_iter().flatten());
+        }
+    }
+
+    let mut unsafe_reduction = UnsafeReduction::default();
+    unsafe_reduction.add_fields(&gacx, &gasn);
+
+    // Callees come before their callers in `all_fn_ldids`, so the sources of their outputs are
+    // known when their callers' are computed.
+    let mut output_sources = lifetimes::OutputSources::default();
+    for ldid in &all_fn_ldids {
+        if let Some(info) = func_info.get(ldid) {
+            output_sources.add_fn(gacx.fn_sigs[&ldid.to_def_id()], &info.dataflow);
+        }
+    }
+
+    // Print results for each function in `all_fn_ldids`, going in declaration order.  Concretely,
+    // we iterate over `body_owners()`, which is a superset of `all_fn_ldids`, and filter based on
+    // membership in `func_info`, which contains an entry for each ID in `all_fn_ldids`.
+    for ldid in tcx.hir().body_owners() {
+        // Skip any body owners that aren't present in `func_info`, and also get the info itself.
+        let info = match func_info.get_mut(&ldid) {
+            Some(x) => x,
+            None => continue,
+        };
+        report.run(tcx, ldid, || {
+            pointer_names::set_current_fn(Some(ldid));
+            let ldid_const = WithOptConstParam::unknown(ldid);
+            let name = util::fn_name(tcx, ldid.to_def_id());
+            let mir = tcx.mir_built(ldid_const);
+            let mir = mir.borrow();
+            let acx = gacx.function_context_with_data(&mir, info.acx_data.take());
+            let asn = gasn.and(&mut info.lasn);
+
+            // Print labeling and rewrites for the current function.
+
+            let fn_path = tcx.def_path_str(ldid.to_def_id());
+            if options.dump_labeled_mir.contains(&fn_path) {
+                eprintln!("\nlabeled MIR for {:?}:", name);
+                eprint!("{}", labeled_mir::dump(&acx, &mir));
+            }
+
+            eprintln!("\nfinal labeling for {:?}:", name);
+            let lcx1 = crate::labeled_ty::LabeledTyCtxt::new(tcx);
+            let lcx2 = crate::labeled_ty::LabeledTyCtxt::new(tcx);
+            for (local, decl) in mir.local_decls.iter_enumerated() {
+                let addr_of1 = asn.perms()[acx.addr_of_local[local]];
+                let ty1 = lcx1.relabel(acx.local_tys[local], &mut |lty| {
+                    if lty.label == PointerId::NONE {
+                        PermissionSet::empty()
+                    } else {
+                        asn.perms()[lty.label]
+                    }
+                });
+                eprintln!(
+                    "{:?} ({}): addr_of = {:?}, type = {:?}",
+                    local,
+                    describe_local(tcx, decl),
+                    addr_of1,
+                    ty1,
+                );
+
+                let addr_of2 = asn.flags()[acx.addr_of_local[local]];
+                let ty2 = lcx2.relabel(acx.local_tys[local], &mut |lty| {
+                    if lty.label == PointerId::NONE {
+                        FlagSet::empty()
+                    } else {
+                        asn.flags()[lty.label]
+                    }
+                });
+                eprintln!(
+                    "{:?} ({}): addr_of flags = {:?}, type flags = {:?}",
+                    local,
+                    describe_local(tcx, decl),
+                    addr_of2,
+                    ty2,
+                );
+
+                let addr_of3 = acx.addr_of_local[local];
+                let ty3 = acx.local_tys[local];
+                eprintln!(
+                    "{:?} ({}): addr_of = {:?}, type = {:?}",
+                    local,
+                    describe_local(tcx, decl),
+                    addr_of3,
+                    ty3,
+                );
+            }
+
+            if options.output_format == OutputFormat::Json {
+                results.add_fn(&acx, &asn, &mir);
+            }
+            if options.html_report.is_some() {
+                html_report.add_fn(&acx, &asn, &mir);
+            }
+            let module = metrics::module_name(tcx, ldid);
+            let stuck_reasons = info.dataflow.stuck_reasons();
+            for (local, decl) in mir.local_decls.iter_enumerated() {
+                let ptr = acx.local_tys[local].label;
+                let is_user = mir.local_kind(local) == LocalKind::Arg || decl.is_user_variable();
+                if is_user && !ptr.is_none() && SafePtr::of(tcx, decl.ty).is_none() {
+                    let (perms, flags) = (asn.perms()[ptr], asn.flags()[ptr]);
+                    diagnostics.add_raw_pointer(
+                        decl.source_info.span,
+                        &format!("`{}`", describe_local(tcx, decl)),
+                        perms,
+                        flags,
+                    );
+                    let outcome = Outcome::of(perms, flags, stuck_reasons.get(&ptr).copied());
+                    pointer_summary.add(&module, Some(&fn_path), outcome);
+                }
+            }
+
+            eprintln!("\ntype assignment for {:?}:", name);
+            for (local, decl) in mir.local_decls.iter_enumerated() {
+                // TODO: apply `Cell` if `addr_of_local` indicates it's needed
+                let ty = type_desc::convert_type(&acx, acx.local_tys[local], &asn);
+                eprintln!("{:?} ({}): {:?}", local, describe_local(tcx, decl), ty,);
+            }
+
+            let alloc_counts = info.dataflow.alloc_counts();
+            if !alloc_counts.is_empty() {
+                eprintln!("\nallocation counts for {:?}:", name);
+            }
+            for (ptr, count) in alloc_counts {
+                eprintln!("{}: {} elements", ptr, count);
+            }
+
+            let uses_after_free = info.dataflow.uses_after_free();
+            if !uses_after_free.is_empty() {
+                eprintln!("\nuses after free in {:?}:", name);
+            }
+            for u in uses_after_free {
+                eprintln!(
+                    "{} ({}): may be used after {}",
+                    u.moved.ptr,
+                    describe_span(tcx, u.span),
+                    u.moved.origin.reason
+                );
+            }
+
+            if let Some(params) = acx.gacx.out_params.get(&ldid.to_def_id()) {
+                let lsig = &acx.gacx.fn_sigs[&ldid.to_def_id()];
+                let param_tys = params
+                    .iter()
+                    .map(|&i| type_desc::convert_type(&acx, lsig.inputs[i].args[0], &asn))
+                    .collect::<Vec<_>>();
+                let output = type_desc::convert_type(&acx, lsig.output, &asn);
+                eprintln!("\nout-params for {:?}: {:?}", name, params);
+                eprintln!(
+                    "  new return type: {:?}",
+                    out_params::rewritten_output(tcx, output, &param_tys)
+                );
+            }
+
+            let did = ldid.to_def_id();
+            let printer = TypePrinter::new(acx.gacx, asn.perms(), asn.flags(), &struct_lifetimes);
+            query_index.add_fn(&acx, &asn, &mir, &printer);
+            let lsig = acx.gacx.fn_sigs[&did];
+            let lifetimes =
+                lifetimes::infer_fn_lifetimes(&printer, did, lsig, &info.dataflow, &output_sources);
+            let param_names = (1..=mir.arg_count)
+                .map(|i| describe_local(tcx, &mir.local_decls[Local::from_usize(i)]))
+                .collect::<Vec<_>>();
+            // Closure signatures are inferred from their uses, so they're never rewritten.
+            let is_closure = tcx.is_closure(did);
+            if !is_closure {
+                eprintln!("\nrewritten signature for {:?}:", name);
+                eprintln!(
+                    "  {}",
+                    lifetimes::print_fn_sig(&printer, did, lsig, &lifetimes, &param_names)
+                );
+                if let Some(ref alternatives) = alternatives {
+                    let path = tcx.def_path_str(did);
+                    let lt_name = |ptr| lifetimes.name(ptr);
+                    let behind = |ptr| lifetimes.behind_name(ptr);
+                    for (i, &lty) in lsig.inputs.iter().enumerate() {
+                        let alts = alternatives.for_type(&printer, lty, &lt_name, &behind, None);
+                        alternatives::print(&path, &format!("input {}", i), &alts);
+                    }
+                    let adt_name = lifetimes.output_adt_name();
+                    let alts =
+                        alternatives.for_type(&printer, lsig.output, &lt_name, &behind, adt_name);
+                    alternatives::print(&path, "output", &alts);
+                }
+            }
+            let shim = if abi_shims::is_exported(tcx, did) {
+                abi_shims::build(tcx, ldid, lsig, &asn.perms(), &asn.flags())
+            } else {
+                None
+            };
+            if let Some(ref shim) = shim {
+                eprintln!("\nABI shim for {:?}:\n{}", name, shim.text.trim());
+            } else if kept_abis.contains(&ldid) {
+                eprintln!(
+                    "\nexported signature kept for {:?}: a pointer can't be converted at the ABI \
+                     boundary",
+                    name
+                );
+            }
+
+            let ptr_loops = ptr_loops::find(&acx, &asn, &mir);
+            if !ptr_loops.is_empty() {
+                eprintln!("\npointer loops for {:?}:", name);
+            }
+            for l in &ptr_loops {
+                let line = tcx.sess.source_map().lookup_char_pos(l.span.lo()).line;
+                eprintln!("{} (line {}): {}", l.name, line, l.header);
+            }
+
+            eprintln!();
+            let rewrites = expr_rewrite::gen_expr_rewrites(&acx, &asn, &mir);
+            let exprs = rewrite::BodyExprs::new(tcx, ldid);
+            // Code that a pointer loop replaces doesn't need its rewrites.
+            let outside_loops = rewrites
+                .iter()
+                .filter(|rw| !ptr_loops.iter().any(|l| l.conflicts(rw.loc.span)));
+            let expr_edits = match exprs.render_all(outside_loops) {
+                Ok(expr_edits) => Some(expr_edits),
+                Err(rw) => {
+                    if gen_edits {
+                        eprintln!(
+                            "warning: leaving {} unchanged: can't apply {:?} at {}",
+                            tcx.def_path_str(did),
+                            rw.kinds,
+                            tcx.sess.source_map().span_to_diagnostic_string(rw.loc.span),
+                        );
+                    }
+                    None
+                }
+            };
+            if let Some(expr_edits) = expr_edits.as_ref().filter(|_| gen_edits) {
+                let mut fn_edits = Vec::new();
+                if !is_closure {
+                    fn_edits.extend(rewrite::fn_sig_edits(
+                        tcx,
+                        &printer,
+                        ldid,
+                        lsig,
+                        &lifetimes,
+                        &mut planner,
+                        alternatives.as_ref(),
+                        acx.gacx
+                            .out_params
+                            .get(&did)
+                            .map_or(&[], |params| &params[..]),
+                    ));
+                }
+                fn_edits.extend(shim.into_iter().flat_map(|shim| shim.edits));
+                fn_edits.extend(rewrite::let_ty_edits(&exprs, &acx, &asn, &mir));
+                fn_edits.extend(expr_edits.iter().cloned());
+                // A rewritten pointer loop replaces the code that these edits were planned for.
+                fn_edits.retain(|e| !ptr_loops.iter().any(|l| l.conflicts(e.span)));
+                fn_edits.extend(ptr_loops.iter().flat_map(|l| l.edits().iter().cloned()));
+                edits.extend(fn_edits);
+            }
+            for rw in &rewrites {
+                eprintln!(
+                    "at {:?} ({}, {:?}):",
+                    rw.loc.stmt,
+                    describe_span(tcx, rw.loc.span),
+                    rw.loc.sub,
+                );
+                for kind in &rw.kinds {
+                    eprintln!("  {:?}", kind);
+                }
+            }
+            let splits = write_phase::find_splits(&acx, &asn, &mir);
+            if !splits.is_empty() {
+                eprintln!("\nwrite phases for {:?}:", name);
+            }
+            for split in &splits {
+                let line = tcx.sess.source_map().lookup_char_pos(split.after.hi()).line;
+                eprintln!(
+                    "{:?} ({}): &mut until line {}, then &",
+                    split.local, split.name, line
+                );
+                if gen_edits && expr_edits.is_some() {
+                    edits.push(split.edit(tcx));
+                }
+            }
+            let rewritten_locs = expr_edits.as_ref().map(|_| {
+                rewrites
+                    .iter()
+                    .filter(|rw| rw.kinds.iter().any(|k| *k != RewriteKind::MutToImm))
+                    .map(|rw| rw.loc.stmt)
+                    .collect::<HashSet<_>>()
+            });
+            unsafe_reduction.add_fn(
+                &acx,
+                &asn,
+                &mir,
+                &rewritten_statics,
+                rewritten_locs.as_ref(),
+            );
+            if dump_constraints {
+                constraint_graph.add_fn(&acx, &asn, &mir, &info.dataflow);
+            }
+        });
+    }
+
+    let unsafe_block_edits = unsafe_reduction.finish(tcx);
+    if gen_edits {
+        edits.extend(unsafe_block_edits);
+    }
+    query::print(&query_index, &options.query);
+
+    eprintln!("\n=== pointer summary ===");
+    eprint!("{}", pointer_summary);
+    results.summary = pointer_summary;
+
+    eprintln!("\n=== assumptions ===");
+    if options.assume.is_empty() {
+        eprintln!("none");
+    }
+    for a in &options.assume {
+        eprintln!("{}", a.name());
+    }
+
+    eprintln!("\n=== unsafe reduction ===");
+    eprint!("{}", unsafe_reduction);
+
+    diagnostics.add_unsupported(&report);
+    match options.output_format {
+        OutputFormat::Text => {}
+        OutputFormat::Json => results.print(),
+        OutputFormat::Sarif => diagnostics.print_sarif(tcx),
+    }
+    if let Some(ref path) = options.html_report {
+        html_report.write(path, tcx.crate_name(LOCAL_CRATE).as_str());
+    }
+    if dump_constraints {
+        let crate_name = tcx.crate_name(LOCAL_CRATE);
+        let path = format!("{}.constraints.dot", crate_name);
+        fs::write(&path, constraint_graph.render(crate_name.as_str()))
+            .unwrap_or_else(|e| panic!("failed to write {}: {}", path, e));
+        eprintln!("wrote constraint graph to {}", path);
+    }
+    if let Some(ref path) = options.rewrite_plan_out {
+        planner.save(path);
+    }
+    if rewrite_mode.is_enabled() {
+        rewrite::apply(tcx, &edits, &rewrite_mode);
+    }
+    stats.record(Phase::Rewriting, start);
+
+    report.print();
+    stats.print();
+
+    AnalysisResults {
+        edits: rewrite::source_edits(tcx, &edits),
+        plan: planner.into_entries(),
+        crate_results: results,
+        query: query_index,
+    }
+}
+
+trait AssignPointerIds<'tcx> {
+    fn lcx(&self) -> LTyCtxt<'tcx>;
+
+    fn new_pointer(&mut self) -> PointerId;
+
+    fn assign_pointer_ids(&mut self, ty: Ty<'tcx>) -> LTy<'tcx> {
+        let tcx = self.lcx().tcx();
+        self.lcx().label(ty, &mut |ty| match ty.kind() {
+            TyKind::Ref(_, _, _) | TyKind::RawPtr(_) => self.new_pointer(),
+            TyKind::Adt(..) if SafePtr::of(tcx, ty).is_some() => self.new_pointer(),
+            _ => PointerId::NONE,
+        })
+    }
+}
+
+impl<'tcx> AssignPointerIds<'tcx> for GlobalAnalysisCtxt<'tcx> {
+    fn lcx(&self) -> LTyCtxt<'tcx> {
+        self.lcx
+    }
+
+    fn new_pointer(&mut self) -> PointerId {
+        self.new_pointer()
+    }
+}
+
+impl<'tcx> AssignPointerIds<'tcx> for AnalysisCtxt<'_, 'tcx> {
+    fn lcx(&self) -> LTyCtxt<'tcx> {
+        self.lcx()
+    }
+
+    fn new_pointer(&mut self) -> PointerId {
+        self.new_pointer()
+    }
+}
+
+fn describe_local(tcx: TyCtxt, decl: &LocalDecl) -> String {
+    let mut span = decl.source_info.span;
+    if let Some(ref info) = decl.local_info {
+        if let LocalInfo::User(ref binding_form) = **info {
+            let binding_form = binding_form.as_ref().assert_crate_local();
+            if let BindingForm::Var(ref v) = *binding_form {
+                span = v.pat_span;
+            }
+        }
+    }
+    describe_span(tcx, span)
+}
+
+fn describe_span(tcx: TyCtxt, span: Span) -> String {
+    let s = tcx.sess.source_map().span_to_snippet(span).unwrap();
+    let s = {
+        let mut s2 = String::new();
+        for word in s.split_ascii_whitespace() {
+            if !s2.is_empty() {
+                s2.push(' ');
+            }
+            s2.push_str(word);
+        }
+        s2
+    };
+
+    let (src1, src2, src3) = if s.len() > 20 {
+        (&s[..15], " ... ", &s[s.len() - 5..])
+    } else {
+        (&s[..], "", "")
+    };
+    let line = tcx.sess.source_map().lookup_char_pos(span.lo()).line;
+    format!("{}: {}{}{}", line, src1, src2, src3)
+}
+
+/// Compute the strongly connected components of the call graph of all local functions that have
+/// bodies.  The components are returned in postorder: every SCC appears after all the SCCs
+/// containing its callees.  Functions that are mutually recursive end up in the same SCC.
+/// Functions marked `#[c2rust::skip]` or excluded by `--function` are left out.
+fn fn_body_owners_sccs(tcx: TyCtxt, annotations: &Annotations) -> Vec<Vec<LocalDefId>> {
+    let mut roots = Vec::new();
+    let mut callees = HashMap::new();
+    for ldid in tcx.hir().body_owners() {
+        match tcx.def_kind(ldid) {
+            DefKind::Fn | DefKind::AssocFn | DefKind::Closure => {}
+            DefKind::AnonConst | DefKind::Const => continue,
+            dk => panic!("unexpected def_kind {:?} for body_owner {:?}", dk, ldid),
+        }
+        if annotations.deselected(ldid.to_def_id()) {
+            ::log::debug!("skipping {:?} due to --function", ldid);
+            continue;
+        }
+        if annotations.skip(ldid.to_def_id()) {
+            eprintln!("skipping {:?} due to #[c2rust::skip]", ldid);
+            continue;
+        }
+        roots.push(ldid);
+    }
+    // Callees may include functions that aren't `body_owners`, so gather edges on demand.
+    let mut get_callees = |ldid: LocalDefId| -> Vec<LocalDefId> {
+        callees
+            .entry(ldid)
+            .or_insert_with(|| {
+                let mut v = Vec::new();
+                for_each_callee(tcx, ldid, |callee_ldid| {
+                    if !annotations.skip(callee_ldid.to_def_id()) {
+                        v.push(callee_ldid);
+                    }
+                });
+                v
+            })
+            .clone()
+    };
+
+    // Iterative version of Tarjan's SCC algorithm.
+    struct NodeState {
+        index: usize,
+        lowlink: usize,
+        on_stack: bool,
+    }
+    let mut state: HashMap<LocalDefId, NodeState> = HashMap::new();
+    let mut scc_stack = Vec::new();
+    let mut sccs = Vec::new();
+    // Each entry is a function and the list of callees that remain to be visited.
+    let mut work: Vec<(LocalDefId, std::vec::IntoIter<LocalDefId>)> = Vec::new();
+
+    for root in roots {
+        if state.contains_key(&root) {
+            continue;
+        }
+
+        let index = state.len();
+        state.insert(
+            root,
+            NodeState {
+                index,
+                lowlink: index,
+                on_stack: true,
+            },
+        );
+        scc_stack.push(root);
+        work.push((root, get_callees(root).into_iter()));
+
+        while let Some((ldid, remaining)) = work.last_mut() {
+            let ldid = *ldid;
+            if let Some(callee) = remaining.next() {
+                match state.get(&callee) {
+                    None => {
+                        let index = state.len();
+                        state.insert(
+                            callee,
+                            NodeState {
+                                index,
+                                lowlink: index,
+                                on_stack: true,
+                            },
+                        );
+                        scc_stack.push(callee);
+                        work.push((callee, get_callees(callee).into_iter()));
+                    }
+                    Some(callee_state) if callee_state.on_stack => {
+                        let callee_index = callee_state.index;
+                        let s = state.get_mut(&ldid).unwrap();
+                        s.lowlink = s.lowlink.min(callee_index);
+                    }
+                    Some(_) => {}
+                }
+                continue;
+            }
+
+            // All callees of `ldid` have been visited.
+            work.pop();
+            let NodeState { index, lowlink, .. } = state[&ldid];
+            if let Some(&(caller, _)) = work.last() {
+                let s = state.get_mut(&caller).unwrap();
+                s.lowlink = s.lowlink.min(lowlink);
+            }
+            if lowlink == index {
+                let mut scc = Vec::new();
+                loop {
+                    let member = scc_stack.pop().unwrap();
+                    state.get_mut(&member).unwrap().on_stack = false;
+                    scc.push(member);
+                    if member == ldid {
+                        break;
+                    }
+                }
+                scc.reverse();
+                sccs.push(scc);
+            }
+        }
+    }
+
+    sccs
+}
+
+fn for_each_callee(tcx: TyCtxt, ldid: LocalDefId, f: impl FnMut(LocalDefId)) {
+    let ldid_const = WithOptConstParam::unknown(ldid);
+    let mir = tcx.mir_built(ldid_const);
+    let mir = mir.borrow();
+    let mir: &Body = &mir;
+
+    struct CalleeVisitor<'a, 'tcx, F> {
+        tcx: TyCtxt<'tcx>,
+        mir: &'a Body<'tcx>,
+        f: F,
+    }
+
+    impl<'tcx, F: FnMut(LocalDefId)> Visitor<'tcx> for CalleeVisitor<'_, 'tcx, F> {
+        fn visit_operand(&mut self, operand: &Operand<'tcx>, _location: Location) {
+            let ty = operand.ty(self.mir, self.tcx);
+            let def_id = match util::ty_callee(self.tcx, ty) {
+                Callee::LocalDef { def_id, .. } => def_id,
+                _ => return,
+            };
+            let ldid = match def_id.as_local() {
+                Some(x) => x,
+                None => return,
+            };
+            if self.tcx.is_foreign_item(def_id) {
+                return;
+            }
+            if !matches!(self.tcx.def_kind(def_id), DefKind::Fn | DefKind::AssocFn) {
+                return;
+            }
+            (self.f)(ldid);
+        }
+
+        // Closures defined in this function are treated as callees, so that the requirements of
+        // the closure body on its captured pointers are known before this function is visited.
+        fn visit_rvalue(&mut self, rvalue: &Rvalue<'tcx>, location: Location) {
+            if let Rvalue::Aggregate(ref kind, _) = *rvalue {
+                if let AggregateKind::Closure(ldid, _) = **kind {
+                    (self.f)(ldid);
+                }
+            }
+            self.super_rvalue(rvalue, location);
+        }
+    }
+
+    CalleeVisitor { tcx, mir, f }.visit_body(mir);
+}
+
+/// Get the labeled type of a closure from the signature of its body, whose first input is the
+/// closure environment: the closure itself, or a reference to it.
+fn closure_lty<'tcx>(lsig: &LFnSig<'tcx>) -> LTy<'tcx> {
+    let env_lty = lsig.inputs[0];
+    match env_lty.kind() {
+        TyKind::Ref(..) => env_lty.args[0],
+        _ => env_lty,
+    }
+}
+
+/// Keep the signature of `ldid`, a function the analysis gave up on, as it is.  Its body adds no
+/// constraints, so, as for a call through a function pointer, its pointers get every permission
+/// their types allow, which its callers then need as well.
+fn fix_failed_sig(gacx: &GlobalAnalysisCtxt, gasn: &mut GlobalAssignment, ldid: LocalDefId) {
+    let lsig = &gacx.fn_sigs[&ldid.to_def_id()];
+    for lty in lsig.inputs.iter().copied().chain(Some(lsig.output)) {
+        for lty in lty.iter() {
+            if lty.label.is_none() {
+                continue;
+            }
+            let perms = match *lty.ty.kind() {
+                TyKind::RawPtr(tm) if tm.mutbl == Mutability::Mut => {
+                    PermissionSet::READ | PermissionSet::WRITE
+                }
+                TyKind::Ref(_, _, Mutability::Mut) => PermissionSet::READ | PermissionSet::WRITE,
+                _ => PermissionSet::READ,
+            };
+            gasn.perms[lty.label].insert(perms);
+            gasn.flags[lty.label].insert(FlagSet::FIXED);
+        }
+    }
+}
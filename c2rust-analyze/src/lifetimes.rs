@@ -0,0 +1,697 @@
+//! Lifetime inference for rewritten signatures and struct definitions.
+//!
+//! Rewriting `*mut T` to `&mut T` requires choosing a lifetime for the new reference.  For a
+//! function signature, each output reference gets the lifetime of the input references it may be
+//! derived from, according to the function's dataflow constraints.  If it may come from several
+//! inputs, their lifetimes are merged into one.  Input references that no output depends on each
+//! get their own lifetime, and outputs that don't depend on any input are `'static`.  When Rust's
+//! lifetime elision rules would produce the same signature, the lifetimes are left out.
+//!
+//! For structs, all reference fields share a single new lifetime parameter, which is also passed
+//! to any other struct that contains one by value or through a reference.  This adds at most one
+//! parameter to each struct.  References to such structs use the reference's own lifetime for the
+//! struct's parameter, as in `&'a S<'a>`, except for a `&mut` in a function signature, which gets a
+//! separate lifetime for the struct, as in `&'a mut S<'b>`: `&'a mut S<'a>` would borrow the struct
+//! for as long as the references in it live.  An output loaded from a field of such a struct, as
+//! in `(*s).p`, gets the struct's lifetime.
+//!
+//! An output returned from a call, as in `g(p)`, is derived from whatever the callee's output is
+//! derived from, so the global pointers each function's outputs may come from are collected first,
+//! callees before callers, in [`OutputSources`].
+use crate::context::{FlagSet, GlobalAnalysisCtxt, LFnSig, LTy, PermissionSet, PointerId};
+use crate::dataflow::DataflowConstraints;
+use crate::pointer_id::PointerTable;
+use crate::type_desc::{self, Ownership, Quantity};
+use rustc_hir::def::DefKind;
+use rustc_hir::def_id::DefId;
+use rustc_middle::ty::{DefIdTree, GenericParamDefKind, ParamEnv, TyCtxt, TyKind};
+use std::collections::{HashMap, HashSet};
+
+/// Generate a lifetime name that isn't in `used`, and add it to `used`.
+fn fresh_name(used: &mut HashSet<String>) -> String {
+    let name = (0..)
+        .map(|i| {
+            let c = (b'a' + (i % 26) as u8) as char;
+            match i / 26 {
+                0 => format!("'{}", c),
+                n => format!("'{}{}", c, n),
+            }
+        })
+        .find(|name| !used.contains(name))
+        .unwrap();
+    used.insert(name.clone());
+    name
+}
+
+/// The names of the lifetime parameters of item `did`.
+fn lifetime_param_names(tcx: TyCtxt, did: DefId) -> Vec<String> {
+    tcx.generics_of(did)
+        .params
+        .iter()
+        .filter(|param| matches!(param.kind, GenericParamDefKind::Lifetime))
+        .map(|param| param.name.to_string())
+        .collect()
+}
+
+/// Prints rewritten types, with lifetimes chosen by a callback.
+pub struct TypePrinter<'a, 'tcx> {
+    gacx: &'a GlobalAnalysisCtxt<'tcx>,
+    perms: PointerTable<'a, PermissionSet>,
+    flags: PointerTable<'a, FlagSet>,
+    /// The lifetime parameter added to each struct that needs one.
+    struct_lifetimes: &'a HashMap<DefId, String>,
+}
+
+impl<'a, 'tcx> TypePrinter<'a, 'tcx> {
+    pub fn new(
+        gacx: &'a GlobalAnalysisCtxt<'tcx>,
+        perms: PointerTable<'a, PermissionSet>,
+        flags: PointerTable<'a, FlagSet>,
+        struct_lifetimes: &'a HashMap<DefId, String>,
+    ) -> TypePrinter<'a, 'tcx> {
+        TypePrinter {
+            gacx,
+            perms,
+            flags,
+            struct_lifetimes,
+        }
+    }
+
+    /// A printer for the same types with different permissions and flags, as for
+    /// [`crate::alternatives`].
+    pub fn with_tables<'b>(
+        &'b self,
+        perms: PointerTable<'b, PermissionSet>,
+        flags: PointerTable<'b, FlagSet>,
+    ) -> TypePrinter<'b, 'tcx> {
+        TypePrinter::new(self.gacx, perms, flags, self.struct_lifetimes)
+    }
+
+    /// Get the ownership of the pointer `lty` after rewriting, or `None` if `lty` isn't a raw
+    /// pointer.
+    fn ownership(&self, lty: LTy<'tcx>) -> Option<(Ownership, Quantity)> {
+        if lty.label == PointerId::NONE || !matches!(lty.ty.kind(), TyKind::RawPtr(..)) {
+            return None;
+        }
+        let ptr = lty.label;
+        Some(type_desc::perms_to_desc(self.perms[ptr], self.flags[ptr]))
+    }
+
+    /// Check whether the raw pointer `lty` will be rewritten to a reference, which needs a
+    /// lifetime.
+    fn is_ref(&self, lty: LTy<'tcx>) -> bool {
+        matches!(
+            self.ownership(lty),
+            Some((
+                Ownership::Imm | Ownership::Cell | Ownership::Mut | Ownership::CStr,
+                _
+            ))
+        )
+    }
+
+    /// Check whether `lty` is a struct that needs an extra lifetime parameter.
+    fn adt_lifetime(&self, lty: LTy<'tcx>) -> bool {
+        match *lty.ty.kind() {
+            TyKind::Adt(adt_def, _) => self.struct_lifetimes.contains_key(&adt_def.did()),
+            _ => false,
+        }
+    }
+
+    /// Print the rewritten form of `lty`.  `name` gives the lifetime to use for each new
+    /// reference, and `adt_name` gives the lifetime to pass to a struct that isn't behind one of
+    /// the new references.  A name of `None` leaves the lifetime out.
+    pub fn print(
+        &self,
+        lty: LTy<'tcx>,
+        name: &dyn Fn(PointerId) -> Option<String>,
+        adt_name: Option<&str>,
+    ) -> String {
+        self.print_with(lty, name, name, adt_name)
+    }
+
+    /// Like [`TypePrinter::print`], but `behind` gives the lifetime to pass to a struct behind
+    /// each new reference, instead of the reference's own lifetime.
+    pub fn print_with(
+        &self,
+        lty: LTy<'tcx>,
+        name: &dyn Fn(PointerId) -> Option<String>,
+        behind: &dyn Fn(PointerId) -> Option<String>,
+        adt_name: Option<&str>,
+    ) -> String {
+        let tcx = self.gacx.tcx;
+        let with_lifetime = |lt: Option<&str>| lt.map_or(String::new(), |lt| format!("{} ", lt));
+
+        if let Some((own, qty)) = self.ownership(lty) {
+            let ptr = lty.label;
+            let flags = self.flags[ptr];
+            if own == Ownership::NonNull {
+                return format!(
+                    "std::ptr::NonNull<{}>",
+                    self.print_with(lty.args[0], name, behind, adt_name)
+                );
+            }
+            if flags.contains(FlagSet::FIXED) {
+                return format!("{:?}", lty.ty);
+            }
+
+            // Structs behind a new reference get the lifetime `behind` chooses for them.
+            let lt = if self.is_ref(lty) { name(ptr) } else { None };
+            let behind_lt = if self.is_ref(lty) { behind(ptr) } else { None };
+            let inner_adt_name = if self.is_ref(lty) {
+                behind_lt.as_deref().or(Some("'_"))
+            } else {
+                adt_name
+            };
+            let mut s = if flags.contains(FlagSet::CVOID) {
+                "u8".to_owned()
+            } else {
+                self.print_with(lty.args[0], name, behind, inner_adt_name)
+            };
+            if own == Ownership::Cell {
+                let pointee = lty.args[0];
+                s = if type_desc::needs_ref_cell(tcx, pointee, &self.perms, &self.flags) {
+                    format!("std::cell::RefCell<{}>", s)
+                } else {
+                    format!("std::cell::Cell<{}>", s)
+                };
+            }
+            if own != Ownership::Vec && qty != Quantity::Single {
+                s = format!("[{}]", s);
+            }
+            let lt = lt.as_deref();
+            s = match own {
+                Ownership::Raw => format!("*const {}", s),
+                Ownership::RawMut => format!("*mut {}", s),
+                Ownership::Imm | Ownership::Cell => format!("&{}{}", with_lifetime(lt), s),
+                Ownership::Mut => format!("&{}mut {}", with_lifetime(lt), s),
+                Ownership::Rc => format!("std::rc::Rc<std::cell::RefCell<{}>>", s),
+                Ownership::Box => format!("std::boxed::Box<{}>", s),
+                Ownership::Vec => format!("std::vec::Vec<{}>", s),
+                Ownership::CStr => format!("&{}std::ffi::CStr", with_lifetime(lt)),
+                Ownership::CString => "std::ffi::CString".to_owned(),
+                Ownership::NonNull => unreachable!(),
+            };
+            if flags.contains(FlagSet::NULLABLE) {
+                s = format!("std::option::Option<{}>", s);
+            }
+            return s;
+        }
+
+        let args = || {
+            lty.args
+                .iter()
+                .map(|&arg| self.print_with(arg, name, behind, adt_name))
+        };
+        match *lty.ty.kind() {
+            TyKind::Ref(r, _, mutbl) => {
+                let r = r.to_string();
+                let r = (!r.is_empty()).then_some(r);
+                format!(
+                    "&{}{}{}",
+                    with_lifetime(r.as_deref()),
+                    mutbl.prefix_str(),
+                    self.print_with(lty.args[0], name, behind, adt_name)
+                )
+            }
+            TyKind::Adt(adt_def, substs)
+                if lty.args.iter().any(|&arg| self.has_rewrites(arg)) || self.adt_lifetime(lty) =>
+            {
+                let mut params = substs
+                    .regions()
+                    .map(|r| r.to_string())
+                    .filter(|r| !r.is_empty())
+                    .collect::<Vec<_>>();
+                if self.adt_lifetime(lty) {
+                    params.push(adt_name.unwrap_or("'_").to_owned());
+                }
+                params.extend(args());
+                format!("{}<{}>", tcx.def_path_str(adt_def.did()), params.join(", "))
+            }
+            TyKind::Array(_, len) if self.has_rewrites(lty) => {
+                let len = len.try_eval_usize(tcx, ParamEnv::reveal_all());
+                let len = len.map_or_else(|| "_".to_owned(), |n| n.to_string());
+                let elem = self.print_with(lty.args[0], name, behind, adt_name);
+                format!("[{}; {}]", elem, len)
+            }
+            TyKind::Slice(_) if self.has_rewrites(lty) => {
+                format!("[{}]", self.print_with(lty.args[0], name, behind, adt_name))
+            }
+            TyKind::Tuple(_) if self.has_rewrites(lty) => {
+                let args = args().collect::<Vec<_>>();
+                if args.len() == 1 {
+                    format!("({},)", args[0])
+                } else {
+                    format!("({})", args.join(", "))
+                }
+            }
+            _ => format!("{:?}", lty.ty),
+        }
+    }
+
+    /// Check whether printing `lty` differs from printing its original type.
+    fn has_rewrites(&self, lty: LTy<'tcx>) -> bool {
+        lty.iter()
+            .any(|lty| self.ownership(lty).is_some() || self.adt_lifetime(lty))
+    }
+}
+
+/// Find the structs that need a new lifetime parameter, and choose a name for it.
+pub fn infer_struct_lifetimes<'tcx>(
+    gacx: &GlobalAnalysisCtxt<'tcx>,
+    perms: PointerTable<PermissionSet>,
+    flags: PointerTable<FlagSet>,
+) -> HashMap<DefId, String> {
+    let tcx = gacx.tcx;
+    let mut struct_fields = HashMap::<DefId, Vec<LTy>>::new();
+    for (&field_did, &lty) in &gacx.field_tys {
+        let parent = tcx.parent(field_did);
+        if tcx.def_kind(parent) != DefKind::Struct {
+            continue;
+        }
+        struct_fields.entry(parent).or_default().push(lty);
+    }
+
+    // A struct needs a lifetime if it has a reference field, or contains another struct that
+    // needs one.  Iterate to a fixpoint to handle recursive structs.
+    let mut struct_lifetimes = HashMap::new();
+    loop {
+        let printer = TypePrinter::new(gacx, perms.borrow(), flags.borrow(), &struct_lifetimes);
+        let new = struct_fields
+            .iter()
+            .filter(|&(did, _)| !struct_lifetimes.contains_key(did))
+            .filter(|(_, fields)| {
+                fields.iter().any(|lty| {
+                    lty.iter()
+                        .any(|lty| printer.is_ref(lty) || printer.adt_lifetime(lty))
+                })
+            })
+            .map(|(&did, _)| did)
+            .collect::<Vec<_>>();
+        if new.is_empty() {
+            break;
+        }
+        for did in new {
+            let mut used = lifetime_param_names(tcx, did).into_iter().collect();
+            struct_lifetimes.insert(did, fresh_name(&mut used));
+        }
+    }
+    struct_lifetimes
+}
+
+/// Print the rewritten definition of struct `did`, including its new lifetime parameter.
+pub fn print_struct(printer: &TypePrinter, did: DefId) -> String {
+    let tcx = printer.gacx.tcx;
+    let new_lt = printer.struct_lifetimes.get(&did).map(|s| s.as_str());
+    let generics = tcx.generics_of(did);
+    let mut params = lifetime_param_names(tcx, did);
+    params.extend(new_lt.map(|s| s.to_owned()));
+    params.extend(
+        generics
+            .params
+            .iter()
+            .filter(|param| !matches!(param.kind, GenericParamDefKind::Lifetime))
+            .map(|param| param.name.to_string()),
+    );
+
+    let mut s = format!("struct {}", tcx.def_path_str(did));
+    if !params.is_empty() {
+        s.push_str(&format!("<{}>", params.join(", ")));
+    }
+    s.push_str(" {\n");
+    for field in tcx.adt_def(did).all_fields() {
+        let lty = printer.gacx.field_tys[&field.did];
+        let field_ty = printer.print(lty, &|_| new_lt.map(|s| s.to_owned()), new_lt);
+        s.push_str(&format!("    {}: {},\n", field.name, field_ty));
+    }
+    s.push('}');
+    s
+}
+
+/// Lifetimes chosen for the new references in a function signature.
+#[derive(Debug)]
+pub struct FnLifetimes {
+    /// The lifetime parameters to add to the function.  This is empty if all lifetimes can be
+    /// elided.
+    pub params: Vec<String>,
+    /// The lifetime of each new reference, indexed by its `PointerId`.  If lifetimes can be
+    /// elided, only `'static` outputs are listed.
+    names: HashMap<PointerId, String>,
+    /// The lifetime of the structs behind each new `&mut` input, indexed by the `&mut`'s
+    /// `PointerId`.  Structs behind other new references use the reference's lifetime.
+    behind_names: HashMap<PointerId, String>,
+    /// The lifetime to pass to structs that are returned by value.
+    output_adt_name: Option<String>,
+}
+
+impl FnLifetimes {
+    pub fn name(&self, ptr: PointerId) -> Option<String> {
+        self.names.get(&ptr).cloned()
+    }
+
+    /// The lifetime to pass to structs behind the new reference `ptr`.
+    pub fn behind_name(&self, ptr: PointerId) -> Option<String> {
+        self.behind_names
+            .get(&ptr)
+            .or_else(|| self.names.get(&ptr))
+            .cloned()
+    }
+
+    pub fn output_adt_name(&self) -> Option<&str> {
+        self.output_adt_name.as_deref()
+    }
+}
+
+/// A lifetime in a function signature: that of a new reference, or that of the structs behind a
+/// new `&mut`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Lifetime {
+    Ref(PointerId),
+    Behind(PointerId),
+}
+
+fn find(parent: &mut HashMap<Lifetime, Lifetime>, lt: Lifetime) -> Lifetime {
+    let p = *parent.entry(lt).or_insert(lt);
+    if p == lt {
+        return lt;
+    }
+    let root = find(parent, p);
+    parent.insert(lt, root);
+    root
+}
+
+/// Merge the sets containing `lts`, and return the root of the merged set.  Returns `None` if
+/// `lts` is empty.
+fn merge(parent: &mut HashMap<Lifetime, Lifetime>, lts: &[Lifetime]) -> Option<Lifetime> {
+    let (&first, rest) = lts.split_first()?;
+    let root = find(parent, first);
+    for &lt in rest {
+        let r = find(parent, lt);
+        parent.insert(r, root);
+    }
+    Some(root)
+}
+
+/// Collect into `ptrs` the pointers in `lty` and in the fields of the structs it mentions, and of
+/// the structs those mention in turn.  In a struct that needs a lifetime, they all share it.
+fn field_ptrs(
+    gacx: &GlobalAnalysisCtxt,
+    lty: LTy,
+    seen: &mut HashSet<DefId>,
+    ptrs: &mut HashSet<PointerId>,
+) {
+    for lty in lty.iter() {
+        if !lty.label.is_none() {
+            ptrs.insert(lty.label);
+        }
+        if let TyKind::Adt(adt_def, _) = *lty.ty.kind() {
+            if !seen.insert(adt_def.did()) {
+                continue;
+            }
+            for field in adt_def.all_fields() {
+                if let Some(&field_lty) = gacx.field_tys.get(&field.did) {
+                    field_ptrs(gacx, field_lty, seen, ptrs);
+                }
+            }
+        }
+    }
+}
+
+/// The global pointers that each pointer in the output of a function may be derived from.
+#[derive(Debug, Default)]
+pub struct OutputSources {
+    sources: HashMap<PointerId, Vec<PointerId>>,
+}
+
+impl OutputSources {
+    /// Record the sources of the output pointers of a function with signature `lsig`, whose body
+    /// has the dataflow constraints `dataflow`.  The functions it calls should be added first.
+    pub fn add_fn(&mut self, lsig: LFnSig, dataflow: &DataflowConstraints) {
+        let sources = subset_sources(dataflow);
+        for ptr in lsig.output.iter().map(|lty| lty.label) {
+            if ptr.is_none() {
+                continue;
+            }
+            let globals = self
+                .reachable(&sources, ptr)
+                .into_iter()
+                .filter(|&p| p != ptr && p.is_global())
+                .collect();
+            self.sources.insert(ptr, globals);
+        }
+    }
+
+    /// The pointers `ptr` may receive its value from, following the `Subset` edges in `sources`,
+    /// and through calls, the sources of the callees' outputs.
+    fn reachable(
+        &self,
+        sources: &HashMap<PointerId, Vec<PointerId>>,
+        ptr: PointerId,
+    ) -> HashSet<PointerId> {
+        let mut seen = HashSet::new();
+        let mut stack = vec![ptr];
+        while let Some(p) = stack.pop() {
+            if seen.insert(p) {
+                stack.extend(sources.get(&p).into_iter().flatten().copied());
+                stack.extend(self.sources.get(&p).into_iter().flatten().copied());
+            }
+        }
+        seen
+    }
+}
+
+/// Map each pointer to the pointers it receives its value from.  `Subset(a, b)` means `a`
+/// receives its value from `b`.
+fn subset_sources(dataflow: &DataflowConstraints) -> HashMap<PointerId, Vec<PointerId>> {
+    let mut sources = HashMap::<PointerId, Vec<PointerId>>::new();
+    for (a, b) in dataflow.subsets() {
+        sources.entry(a).or_default().push(b);
+    }
+    sources
+}
+
+/// Infer lifetimes for the new references in the signature `lsig` of function `did`, whose body
+/// has the dataflow constraints `dataflow`.  `output_sources` gives the sources of the outputs of
+/// the functions it calls.
+pub fn infer_fn_lifetimes<'tcx>(
+    printer: &TypePrinter<'_, 'tcx>,
+    did: DefId,
+    lsig: LFnSig<'tcx>,
+    dataflow: &DataflowConstraints,
+    output_sources: &OutputSources,
+) -> FnLifetimes {
+    /// Collect the new references in `lty`, in order, and the structs that need a lifetime, with
+    /// the new reference each one is behind, if any.
+    fn walk<'tcx>(
+        printer: &TypePrinter<'_, 'tcx>,
+        lty: LTy<'tcx>,
+        behind: Option<LTy<'tcx>>,
+        refs: &mut Vec<PointerId>,
+        adts: &mut Vec<(Option<LTy<'tcx>>, LTy<'tcx>)>,
+    ) {
+        let is_ref = printer.is_ref(lty);
+        if is_ref {
+            refs.push(lty.label);
+        } else if printer.adt_lifetime(lty) {
+            adts.push((behind, lty));
+        }
+        let behind = if is_ref { Some(lty) } else { behind };
+        for &arg in lty.args {
+            walk(printer, arg, behind, refs, adts);
+        }
+    }
+
+    // Each struct's lifetime parameter counts as an input lifetime position for elision, even
+    // when it's printed with the lifetime of the reference it's behind.
+    let mut input_refs = Vec::new();
+    let mut input_adts = Vec::new();
+    for &input in lsig.inputs {
+        walk(printer, input, None, &mut input_refs, &mut input_adts);
+    }
+    let num_input_positions = input_refs.len() + input_adts.len();
+    let mut inputs = Vec::new();
+    for ptr in input_refs {
+        if !inputs.contains(&ptr) {
+            inputs.push(ptr);
+        }
+    }
+
+    // The lifetime of the structs behind each input reference, with the pointers in their fields.
+    // Structs passed by value keep an anonymous lifetime, so they're left out.
+    let mut behind_inputs = Vec::new();
+    for &(behind, adt) in &input_adts {
+        let r = match behind {
+            Some(r) => r,
+            None => continue,
+        };
+        let lt = if matches!(printer.ownership(r), Some((Ownership::Mut, _))) {
+            Lifetime::Behind(r.label)
+        } else {
+            Lifetime::Ref(r.label)
+        };
+        let mut ptrs = HashSet::new();
+        field_ptrs(printer.gacx, adt, &mut HashSet::new(), &mut ptrs);
+        behind_inputs.push((lt, ptrs));
+    }
+
+    // For each output reference, find the input lifetimes it may be derived from: those of the
+    // input references it may be copied from, and those of the structs whose fields it may be
+    // loaded from, directly or through the calls it's returned from.
+    let sources = subset_sources(dataflow);
+    let reachable_inputs = |ptr: PointerId| {
+        let seen = output_sources.reachable(&sources, ptr);
+        let mut lts = inputs
+            .iter()
+            .copied()
+            .filter(|p| seen.contains(p))
+            .map(Lifetime::Ref)
+            .collect::<Vec<_>>();
+        lts.extend(
+            behind_inputs
+                .iter()
+                .filter(|(_, ptrs)| ptrs.iter().any(|p| seen.contains(p)))
+                .map(|&(lt, _)| lt),
+        );
+        lts
+    };
+
+    let mut parent = HashMap::new();
+    let mut static_outputs = HashSet::new();
+    let mut outputs = Vec::new();
+    let mut output_adts = Vec::new();
+    walk(printer, lsig.output, None, &mut outputs, &mut output_adts);
+    let has_output_adt = output_adts.iter().any(|(behind, _)| behind.is_none());
+    for &ptr in &outputs {
+        match merge(&mut parent, &reachable_inputs(ptr)) {
+            Some(root) => {
+                merge(&mut parent, &[root, Lifetime::Ref(ptr)]);
+            }
+            None => {
+                static_outputs.insert(ptr);
+            }
+        }
+    }
+
+    // Each input reference's lifetime, followed by that of the structs behind it if they have
+    // their own.
+    let mut input_lts = Vec::new();
+    for &ptr in &inputs {
+        input_lts.push(Lifetime::Ref(ptr));
+        let behind = Lifetime::Behind(ptr);
+        if behind_inputs.iter().any(|&(lt, _)| lt == behind) {
+            input_lts.push(behind);
+        }
+    }
+    // A struct returned by value may hold references derived from any input.
+    let output_adt_root = if has_output_adt {
+        merge(&mut parent, &input_lts)
+    } else {
+        None
+    };
+
+    // Lifetimes can be elided if there's only one input lifetime and every output that isn't
+    // `'static` uses it.
+    let no_output_lifetimes =
+        outputs.iter().all(|ptr| static_outputs.contains(ptr)) && !has_output_adt;
+    let elided = no_output_lifetimes || (num_input_positions == 1 && inputs.len() == 1);
+
+    let mut names = HashMap::new();
+    for &ptr in &static_outputs {
+        names.insert(ptr, "'static".to_owned());
+    }
+    if elided {
+        return FnLifetimes {
+            params: Vec::new(),
+            names,
+            behind_names: HashMap::new(),
+            output_adt_name: None,
+        };
+    }
+
+    let mut used = lifetime_param_names(printer.gacx.tcx, did)
+        .into_iter()
+        .collect::<HashSet<_>>();
+    let mut root_names = HashMap::new();
+    let mut params = Vec::new();
+    let mut behind_names = HashMap::new();
+    let output_lts = outputs.iter().map(|&ptr| Lifetime::Ref(ptr));
+    for lt in input_lts.iter().copied().chain(output_lts) {
+        if let Lifetime::Ref(ptr) = lt {
+            if static_outputs.contains(&ptr) {
+                continue;
+            }
+        }
+        let root = find(&mut parent, lt);
+        let name = root_names
+            .entry(root)
+            .or_insert_with(|| {
+                let name = fresh_name(&mut used);
+                params.push(name.clone());
+                name
+            })
+            .clone();
+        match lt {
+            Lifetime::Ref(ptr) => names.insert(ptr, name),
+            Lifetime::Behind(ptr) => behind_names.insert(ptr, name),
+        };
+    }
+    let output_adt_name = if has_output_adt {
+        Some(
+            output_adt_root
+                .map(|root| root_names[&find(&mut parent, root)].clone())
+                .unwrap_or_else(|| "'static".to_owned()),
+        )
+    } else {
+        None
+    };
+    FnLifetimes {
+        params,
+        names,
+        behind_names,
+        output_adt_name,
+    }
+}
+
+/// Print the rewritten signature of function `did`, with the lifetimes in `lifetimes`.
+/// `param_names` gives the names of the parameters.
+pub fn print_fn_sig<'tcx>(
+    printer: &TypePrinter<'_, 'tcx>,
+    did: DefId,
+    lsig: LFnSig<'tcx>,
+    lifetimes: &FnLifetimes,
+    param_names: &[String],
+) -> String {
+    let tcx = printer.gacx.tcx;
+    let mut params = lifetime_param_names(tcx, did);
+    params.extend(lifetimes.params.iter().cloned());
+
+    let name = |ptr| lifetimes.name(ptr);
+    let behind = |ptr| lifetimes.behind_name(ptr);
+    let mut inputs = lsig
+        .inputs
+        .iter()
+        .zip(param_names)
+        .map(|(&lty, param)| {
+            let ty = printer.print_with(lty, &name, &behind, None);
+            format!("{}: {}", param, ty)
+        })
+        .collect::<Vec<_>>();
+    // The `...` of a variadic function is the parameter after the ones in the signature.
+    if tcx.fn_sig(did).c_variadic() {
+        match param_names.get(lsig.inputs.len()) {
+            Some(param) => inputs.push(format!("{}: ...", param)),
+            None => inputs.push("...".to_owned()),
+        }
+    }
+
+    let mut s = format!("fn {}", tcx.item_name(did));
+    if !params.is_empty() {
+        s.push_str(&format!("<{}>", params.join(", ")));
+    }
+    s.push_str(&format!("({})", inputs.join(", ")));
+    if !lsig.output.ty.is_unit() {
+        let adt_name = lifetimes.output_adt_name();
+        let output = printer.print_with(lsig.output, &name, &behind, adt_name);
+        s.push_str(&format!(" -> {}", output));
+    }
+    s
+}
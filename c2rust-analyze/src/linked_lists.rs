@@ -0,0 +1,222 @@
+//! Detection of self-referential structs, such as linked lists and trees.
+//!
+//! A struct that points to itself, directly or through other structs, is usually a linked data
+//! structure like `struct node { struct node *next; }`.  Its pointers rarely become references:
+//! the nodes live on the heap, are reached through several pointers at once, and are freed one at
+//! a time, so their pointers are neither unique nor borrowed from anything.  [`find`] lists the
+//! pointer fields on such cycles, and suggests how the structure could be rewritten: as a list of
+//! nodes that each own the next one through `Option<Box<T>>`, as indices into an arena, or as
+//! shared `Rc<RefCell<T>>` nodes.  The suggestions are printed after the rewritten structs, and
+//! included in the JSON output.
+//!
+//! With `--box-lists`, the owned-list rewrite is also applied to singly linked structs, which have
+//! one field pointing to the struct itself.  Before ownership is inferred, the field and the
+//! pointers stored into it are made owners, where they're unique; see
+//! [`crate::dataflow::DataflowConstraints::seed_owned`].  The usual ownership rules then undo this
+//! if a node has more than one owner, as when a list also keeps a pointer to its tail.
+use crate::context::{FlagSet, GlobalAnalysisCtxt, GlobalAssignment};
+use crate::pointer_id::PointerId;
+use rustc_hir::def::DefKind;
+use rustc_hir::def_id::DefId;
+use rustc_middle::ty::TyKind;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+/// How a struct refers to itself.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Shape {
+    /// One field points to the struct itself, as in a singly linked list.
+    SinglyLinked,
+    /// Several fields point to the struct itself, as in a tree or a doubly linked list.
+    MultiplyLinked,
+    /// The struct is reached again through another struct.
+    Mutual,
+}
+
+/// A way to rewrite a linked structure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Suggestion {
+    /// Each node owns the next one through `Option<Box<T>>`.
+    BoxList,
+    /// The nodes are kept in a `Vec<T>`, and the pointers become indices into it.
+    Arena,
+    /// The nodes are shared through `Rc<RefCell<T>>`, with `Weak` for back pointers.
+    RcRefCell,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct LinkedStruct {
+    pub def_path: String,
+    pub shape: Shape,
+    /// The pointer fields on a cycle back to the struct.
+    pub fields: Vec<String>,
+    /// The possible rewrites, best first.
+    pub suggestions: Vec<Suggestion>,
+    /// Whether the `BoxList` rewrite was applied, with `--box-lists`.
+    pub applied: bool,
+    #[serde(skip)]
+    name: String,
+    #[serde(skip)]
+    ptrs: Vec<PointerId>,
+}
+
+impl LinkedStruct {
+    /// The pointers in the fields that `--box-lists` makes into owners, if this is a singly
+    /// linked struct.
+    pub fn box_list_roots(&self) -> &[PointerId] {
+        match self.shape {
+            Shape::SinglyLinked => &self.ptrs,
+            _ => &[],
+        }
+    }
+}
+
+/// A pointer field, with the struct it points to.
+struct Edge {
+    field: String,
+    ptr: PointerId,
+    target: DefId,
+}
+
+/// Find the structs in the crate that point to themselves.
+pub fn find(gacx: &GlobalAnalysisCtxt) -> Vec<LinkedStruct> {
+    let tcx = gacx.tcx;
+    let mut structs = Vec::new();
+    let mut edges = HashMap::<DefId, Vec<Edge>>::new();
+    for ldid in tcx.hir_crate_items(()).definitions() {
+        if tcx.def_kind(ldid) != DefKind::Struct {
+            continue;
+        }
+        let did = ldid.to_def_id();
+        structs.push(did);
+        for field in tcx.adt_def(did).all_fields() {
+            let lty = match gacx.field_tys.get(&field.did) {
+                Some(&lty) => lty,
+                None => continue,
+            };
+            for sub in lty.iter() {
+                if sub.label.is_none() || !matches!(sub.ty.kind(), TyKind::RawPtr(..)) {
+                    continue;
+                }
+                if let TyKind::Adt(adt, _) = sub.args[0].ty.kind() {
+                    if adt.is_struct() && adt.did().is_local() {
+                        edges.entry(did).or_default().push(Edge {
+                            field: field.name.to_string(),
+                            ptr: sub.label,
+                            target: adt.did(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    // Whether `to` can be reached from `from` by following pointer fields.
+    let reaches = |from: DefId, to: DefId| {
+        let mut seen = HashSet::from([from]);
+        let mut queue = VecDeque::from([from]);
+        while let Some(cur) = queue.pop_front() {
+            if cur == to {
+                return true;
+            }
+            for e in edges.get(&cur).into_iter().flatten() {
+                if seen.insert(e.target) {
+                    queue.push_back(e.target);
+                }
+            }
+        }
+        false
+    };
+
+    let mut linked = Vec::new();
+    for did in structs {
+        let cycle = edges
+            .get(&did)
+            .into_iter()
+            .flatten()
+            .filter(|e| reaches(e.target, did))
+            .collect::<Vec<_>>();
+        if cycle.is_empty() {
+            continue;
+        }
+        let shape = if cycle.iter().any(|e| e.target != did) {
+            Shape::Mutual
+        } else if cycle.len() == 1 {
+            Shape::SinglyLinked
+        } else {
+            Shape::MultiplyLinked
+        };
+        let suggestions = match shape {
+            Shape::SinglyLinked => vec![Suggestion::BoxList, Suggestion::Arena],
+            Shape::MultiplyLinked => vec![Suggestion::RcRefCell, Suggestion::Arena],
+            Shape::Mutual => vec![Suggestion::Arena, Suggestion::RcRefCell],
+        };
+        let mut fields = cycle.iter().map(|e| e.field.clone()).collect::<Vec<_>>();
+        fields.dedup();
+        linked.push(LinkedStruct {
+            def_path: tcx.def_path_str(did),
+            shape,
+            fields,
+            suggestions,
+            applied: false,
+            name: tcx.item_name(did).to_string(),
+            ptrs: cycle.iter().map(|e| e.ptr).collect(),
+        });
+    }
+    linked
+}
+
+/// Record which of the `BoxList` rewrites requested by `--box-lists` took effect, meaning the
+/// fields ended up as owners.
+pub fn mark_applied(linked: &mut [LinkedStruct], gasn: &GlobalAssignment) {
+    for s in linked {
+        let roots = s.box_list_roots();
+        s.applied = !roots.is_empty()
+            && roots
+                .iter()
+                .all(|&ptr| gasn.flags[ptr].contains(FlagSet::OWNED));
+    }
+}
+
+impl fmt::Display for LinkedStruct {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let fields = self
+            .fields
+            .iter()
+            .map(|field| format!("`{}`", field))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let shape = match self.shape {
+            Shape::SinglyLinked => "singly linked",
+            Shape::MultiplyLinked => "multiply linked",
+            Shape::Mutual => "mutually linked",
+        };
+        write!(f, "struct {}: {} through {}", self.def_path, shape, fields)?;
+        for &sugg in &self.suggestions {
+            let desc = match sugg {
+                Suggestion::BoxList => format!(
+                    "make {} an `Option<Box<{}>>` that owns the rest of the list",
+                    fields, self.name
+                ),
+                Suggestion::Arena => format!(
+                    "keep the nodes in a `Vec<{}>` arena and make {} `Option<usize>` indices",
+                    self.name, fields
+                ),
+                Suggestion::RcRefCell => format!(
+                    "share the nodes as `Rc<RefCell<{}>>`, using `Weak` for back pointers",
+                    self.name
+                ),
+            };
+            let applied = if sugg == Suggestion::BoxList && self.applied {
+                " (applied)"
+            } else {
+                ""
+            };
+            write!(f, "\n  suggestion: {}{}", desc, applied)?;
+        }
+        Ok(())
+    }
+}
@@ -24,23 +24,35 @@ impl Display for DisplayRecord<'_> {
 /// Anything that matches `$RUST_LOG_PANIC` will panic instead of being logged.
 ///
 /// The defaults for these are:
-/// * `RUST_LOG=debug`
+/// * `RUST_LOG=warn`
 /// * `RUST_LOG_PANIC=error`
 ///
 /// so by default, `log::error!` panics,
 /// but setting `RUST_LOG_PANIC=off` turns them into just being logged.
-pub fn init_logger() {
-    let log_env = Env::default().default_filter_or(LevelFilter::Debug.as_str());
+///
+/// `spec`, from `--log SPEC`, overrides `$RUST_LOG`.
+/// Both use the `env_logger` filter syntax, so verbosity can be set per module,
+/// as in `--log warn,c2rust_analyze::dataflow=trace`.
+/// `--log error` logs only errors, and `--quiet` (`--log off`) logs nothing.
+pub fn init_logger(spec: Option<&str>) {
+    let log_env = Env::default().default_filter_or(LevelFilter::Warn.as_str());
     let panic_env = Env::default().filter_or("RUST_LOG_PANIC", LevelFilter::Error.as_str());
 
-    let log_logger = env_logger::builder().parse_env(log_env).build();
+    let mut log_builder = env_logger::builder();
+    log_builder.parse_env(log_env);
+    if let Some(spec) = spec {
+        log_builder.parse_filters(spec);
+    }
+    let log_logger = log_builder.build();
     let panic_logger = env_logger::builder().parse_env(panic_env).build();
 
-    // Create the actual [`Logger`] to log everything ([`LevelFilter::max()]`),
+    // Create the actual [`Logger`] to log everything either of them wants,
     // and then we can do the specific matching for `log_logger` and `panic_logger` inside the formatter,
     // as we don't want them to conflict.
+    // The `log` macros reject records above that level before formatting their arguments,
+    // so disabled tracing costs next to nothing.
     env_logger::builder()
-        .filter_level(LevelFilter::max())
+        .filter_level(log_logger.filter().max(panic_logger.filter()))
         .format(move |f, record| {
             let record = DisplayRecord(record);
             if log_logger.matches(record.0) {
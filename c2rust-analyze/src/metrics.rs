@@ -0,0 +1,168 @@
+//! A summary of what the rewrites do to the crate's pointers, printed at the end of every run.
+//!
+//! Each pointer variable and parameter, and each pointer field, is counted by what it becomes:
+//! a reference, an `Option` of a safe pointer, an owning pointer (`Box`, `Vec`, or `Rc`), a
+//! reference to a `Cell`, or a raw pointer.  Raw pointers are also counted by why they stay raw.
+//! The counts are broken down by module and by function, to show where the remaining manual work
+//! is.  With `--output-format=json`, the same summary is included in the JSON output.
+use crate::context::{FlagSet, PermissionSet};
+use crate::type_desc::{self, Ownership};
+use rustc_hir::def_id::LocalDefId;
+use rustc_middle::ty::TyCtxt;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// What a pointer is rewritten to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// `&T` or `&mut T`, a slice reference, or `&CStr`.
+    Ref,
+    /// `Option` of any of the safe pointer types.
+    Option,
+    /// `Box<T>`, `Vec<T>`, `Rc<RefCell<T>>`, or `CString`.
+    Owned,
+    /// `&Cell<T>` or `&RefCell<T>`.
+    Cell,
+    /// A raw pointer or `NonNull`, and why.
+    Raw(&'static str),
+}
+
+impl Outcome {
+    /// Classify a pointer with the final `perms` and `flags`.  `stuck_reason` is the reason the
+    /// pointer must stay raw, if it's `FIXED` because of how its value was created.
+    pub fn of(perms: PermissionSet, flags: FlagSet, stuck_reason: Option<&'static str>) -> Outcome {
+        let (own, _) = type_desc::perms_to_desc(perms, flags);
+        match own {
+            Ownership::Raw | Ownership::RawMut | Ownership::NonNull => {
+                let reason = if flags.contains(FlagSet::CVOID)
+                    && !perms.intersects(type_desc::BYTE_BUFFER_PERMS)
+                {
+                    "pointer is an untyped `c_void` pointer"
+                } else if let Some(reason) = stuck_reason {
+                    reason
+                } else if flags.contains(FlagSet::FIXED) {
+                    "pointer is fixed by an annotation, the rewrite plan, or an unsupported \
+                     construct"
+                } else {
+                    "pointer is freed, but isn't the unique owner of its allocation"
+                };
+                Outcome::Raw(reason)
+            }
+            _ if flags.contains(FlagSet::NULLABLE) => Outcome::Option,
+            Ownership::Imm | Ownership::Mut | Ownership::CStr => Outcome::Ref,
+            Ownership::Cell => Outcome::Cell,
+            Ownership::Rc | Ownership::Box | Ownership::Vec | Ownership::CString => Outcome::Owned,
+        }
+    }
+}
+
+/// The number of pointers with each outcome.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Counts {
+    #[serde(rename = "ref")]
+    pub ref_: usize,
+    pub option: usize,
+    pub owned: usize,
+    pub cell: usize,
+    pub raw: usize,
+    /// The raw pointers, by reason.
+    pub raw_reasons: BTreeMap<&'static str, usize>,
+}
+
+impl Counts {
+    fn add(&mut self, outcome: Outcome) {
+        match outcome {
+            Outcome::Ref => self.ref_ += 1,
+            Outcome::Option => self.option += 1,
+            Outcome::Owned => self.owned += 1,
+            Outcome::Cell => self.cell += 1,
+            Outcome::Raw(reason) => {
+                self.raw += 1;
+                *self.raw_reasons.entry(reason).or_default() += 1;
+            }
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.ref_ + self.option + self.owned + self.cell + self.raw
+    }
+
+    pub fn safe(&self) -> usize {
+        self.total() - self.raw
+    }
+}
+
+impl fmt::Display for Counts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total = self.total();
+        let percent = if total == 0 {
+            100
+        } else {
+            self.safe() * 100 / total
+        };
+        write!(
+            f,
+            "{} pointers, {} safe ({}%): ref {}, option {}, owned {}, cell {}, raw {}",
+            total,
+            self.safe(),
+            percent,
+            self.ref_,
+            self.option,
+            self.owned,
+            self.cell,
+            self.raw
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Summary {
+    pub total: Counts,
+    /// Counts for each module, by path.  The crate root is `crate`.
+    pub modules: BTreeMap<String, Counts>,
+    /// Counts for each function, by path.  Struct fields are counted only in their module.
+    pub functions: BTreeMap<String, Counts>,
+}
+
+impl Summary {
+    /// Record the outcome of a pointer in `module`, and in `function` if it's not a field.
+    pub fn add(&mut self, module: &str, function: Option<&str>, outcome: Outcome) {
+        self.total.add(outcome);
+        self.modules
+            .entry(module.to_owned())
+            .or_default()
+            .add(outcome);
+        if let Some(function) = function {
+            self.functions
+                .entry(function.to_owned())
+                .or_default()
+                .add(outcome);
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "total: {}", self.total)?;
+        for (reason, n) in &self.total.raw_reasons {
+            writeln!(f, "  raw because {}: {}", reason, n)?;
+        }
+        for (name, counts) in &self.modules {
+            writeln!(f, "module {}: {}", name, counts)?;
+        }
+        for (name, counts) in &self.functions {
+            writeln!(f, "fn {}: {}", name, counts)?;
+        }
+        Ok(())
+    }
+}
+
+/// The path of the module containing `ldid`, for grouping.
+pub fn module_name(tcx: TyCtxt, ldid: LocalDefId) -> String {
+    let module = tcx.parent_module_from_def_id(ldid);
+    match tcx.def_path_str(module.to_def_id()) {
+        s if s.is_empty() => "crate".to_owned(),
+        s => s,
+    }
+}
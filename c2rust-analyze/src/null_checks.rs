@@ -0,0 +1,165 @@
+//! Null checks whose results decide a later branch.
+//!
+//! C code often saves the result of a null check in a variable and branches on it later, as in
+//! `let null = p.is_null(); ...; if !null { q = p; }`, and even an immediate `if !p.is_null()`
+//! reaches the branch through a few temporaries.  [`find`] follows the result of each `is_null`
+//! call through copies and negations of boolean locals to the `SwitchInt` that branches on it.
+//! The blocks dominated by the branch taken when the pointer isn't null can rely on it being
+//! non-null, so a copy of it made there doesn't make the destination nullable.
+//!
+//! This is sound only if neither the pointer nor the boolean changes between the check and the
+//! branch, so only locals that are assigned once and never borrowed mutably are followed.  Such a
+//! check may run many times in a loop, but it always checks the same value.
+use crate::util::{self, Callee};
+use rustc_middle::mir::visit::{MutatingUseContext, PlaceContext, Visitor};
+use rustc_middle::mir::{
+    BasicBlock, Body, Local, Location, Operand, Place, Rvalue, StatementKind, TerminatorKind, UnOp,
+};
+use rustc_middle::ty::TyCtxt;
+use std::collections::HashMap;
+
+/// The pointer locals known to be non-null in each block.
+#[derive(Clone, Debug, Default)]
+pub struct NullChecks {
+    non_null: HashMap<BasicBlock, Vec<Local>>,
+}
+
+impl NullChecks {
+    /// The pointer locals that were checked to be non-null before block `bb` runs.
+    pub fn non_null_in(&self, bb: BasicBlock) -> &[Local] {
+        self.non_null.get(&bb).map_or(&[], |v| v)
+    }
+}
+
+/// Count the assignments to each local.  A local that's borrowed mutably, or whose address is
+/// taken mutably, may be assigned through the pointer, and counts as assigned many times.
+struct AssignCounter {
+    counts: Vec<usize>,
+}
+
+impl<'tcx> Visitor<'tcx> for AssignCounter {
+    fn visit_place(&mut self, place: &Place<'tcx>, context: PlaceContext, location: Location) {
+        let count = &mut self.counts[place.local.index()];
+        match context {
+            PlaceContext::MutatingUse(
+                MutatingUseContext::Store
+                | MutatingUseContext::Call
+                | MutatingUseContext::AsmOutput,
+            ) if place.projection.is_empty() => *count += 1,
+            PlaceContext::MutatingUse(
+                MutatingUseContext::Borrow | MutatingUseContext::AddressOf,
+            ) if !place.is_indirect() => *count += 2,
+            _ => {}
+        }
+        self.super_place(place, context, location);
+    }
+}
+
+/// The local that `op` reads, if it reads a whole local.
+fn operand_local(op: &Operand) -> Option<Local> {
+    op.place().and_then(|pl| pl.as_local())
+}
+
+/// Find the blocks where a pointer local is known to be non-null, because they only run after a
+/// check of the pointer with `is_null` found it isn't.
+pub fn find<'tcx>(tcx: TyCtxt<'tcx>, mir: &Body<'tcx>) -> NullChecks {
+    let mut counter = AssignCounter {
+        counts: vec![0; mir.local_decls.len()],
+    };
+    for arg in mir.args_iter() {
+        counter.counts[arg.index()] += 1;
+    }
+    counter.visit_body(mir);
+    let assigned_once = |l: Local| counter.counts[l.index()] == 1;
+
+    // The pointer each boolean local holds the null check of, and whether `true` means null.
+    let mut checks = HashMap::<Local, (Local, bool)>::new();
+    for bb_data in mir.basic_blocks() {
+        let (func, args, destination) = match bb_data.terminator().kind {
+            TerminatorKind::Call {
+                ref func,
+                ref args,
+                destination,
+                ..
+            } => (func, args, destination),
+            _ => continue,
+        };
+        if !matches!(util::ty_callee(tcx, func.ty(mir, tcx)), Callee::IsNull) {
+            continue;
+        }
+        let dest = match destination.as_local() {
+            Some(l) if assigned_once(l) => l,
+            _ => continue,
+        };
+        let ptr = args[0]
+            .place()
+            .and_then(|pl| util::copy_sources(mir, pl).last().copied())
+            .and_then(|pl| pl.as_local());
+        if let Some(ptr) = ptr.filter(|&l| assigned_once(l)) {
+            checks.insert(dest, (ptr, true));
+        }
+    }
+
+    // Follow the results through copies and negations.
+    loop {
+        let mut changed = false;
+        for bb_data in mir.basic_blocks() {
+            for stmt in &bb_data.statements {
+                let (pl, rv) = match stmt.kind {
+                    StatementKind::Assign(ref x) => (x.0, &x.1),
+                    _ => continue,
+                };
+                let dest = match pl.as_local() {
+                    Some(l) if assigned_once(l) && !checks.contains_key(&l) => l,
+                    _ => continue,
+                };
+                let (src, negate) = match *rv {
+                    Rvalue::Use(ref op) => (operand_local(op), false),
+                    Rvalue::UnaryOp(UnOp::Not, ref op) => (operand_local(op), true),
+                    _ => continue,
+                };
+                if let Some(&(ptr, is_null)) = src.and_then(|l| checks.get(&l)) {
+                    checks.insert(dest, (ptr, is_null != negate));
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    // Mark the blocks dominated by the non-null side of each branch on a check.
+    let dominators = mir.basic_blocks.dominators();
+    let predecessors = mir.basic_blocks.predecessors();
+    let mut non_null = HashMap::<BasicBlock, Vec<Local>>::new();
+    for bb_data in mir.basic_blocks() {
+        let (discr, targets) = match bb_data.terminator().kind {
+            TerminatorKind::SwitchInt {
+                ref discr,
+                ref targets,
+                ..
+            } => (discr, targets),
+            _ => continue,
+        };
+        let &(ptr, is_null) = match operand_local(discr).and_then(|l| checks.get(&l)) {
+            Some(x) => x,
+            None => continue,
+        };
+        // A `bool` switch goes to the target for 0 if it's false, and otherwise if it's true.
+        let target = if is_null {
+            targets.target_for_value(0)
+        } else {
+            targets.otherwise()
+        };
+        if predecessors[target].len() != 1 {
+            continue;
+        }
+        for bb in mir.basic_blocks().indices() {
+            if dominators.is_reachable(bb) && dominators.is_dominated_by(bb, target) {
+                non_null.entry(bb).or_default().push(ptr);
+            }
+        }
+    }
+    NullChecks { non_null }
+}
@@ -0,0 +1,283 @@
+//! Command-line options for `c2rust-analyze` itself.
+//!
+//! These are mixed in with the `rustc` arguments on the command line.  [`Options::take_from_args`]
+//! removes the ones it recognizes, and everything else is passed through to `rustc` unchanged.
+//! All arguments after a `--` are passed through as well, which allows passing `rustc` (or, in
+//! `cargo` wrapper mode, `cargo`) flags that have the same name as one of ours.
+use crate::rewrite;
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::mem;
+use std::path::PathBuf;
+
+/// Environment variable used to pass [`Options`] from the `cargo` wrapper to each `rustc`
+/// wrapper invocation.
+const OPTIONS_VAR: &str = "C2RUST_ANALYZE_OPTIONS";
+
+/// The format of the `--debug-constraints` dump.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConstraintDump {
+    /// Graphviz DOT.  See [`crate::dataflow::dot`].
+    Dot,
+}
+
+/// The format of the analysis results.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutputFormat {
+    /// Only the human-readable dump on stderr.
+    #[default]
+    Text,
+    /// Also print the results as JSON on stdout.  See [`crate::json_output`].
+    Json,
+    /// Also print the findings as SARIF on stdout.  See [`crate::diagnostics`].
+    Sarif,
+}
+
+/// An assumption about the program that lets the analysis be less conservative, for
+/// `--assume`.  Each one removes a source of `FIXED` pointers (or, for `SingleThreaded`, of
+/// strong atomic orderings) that's needed for soundness only if the program does something the
+/// user knows it doesn't.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Assumption {
+    /// Integers created from pointers are never cast back to pointers.  Without this, when the
+    /// crate creates a pointer from an integer, every pointer cast to an integer stays raw, since
+    /// the new pointer may alias it.
+    NoIntPtrRoundTrips,
+    /// Extern functions don't keep the pointers passed to them after they return.  Without this,
+    /// pointers passed to extern functions that have no model stay raw, along with the pointers
+    /// they're copied from.
+    ExternNoRetain,
+    /// The program has only one thread.  Statics rewritten to atomics use `Relaxed` instead of
+    /// `SeqCst`.  See [`crate::statics`].
+    SingleThreaded,
+}
+
+impl Assumption {
+    /// The name of the assumption on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Assumption::NoIntPtrRoundTrips => "no-int-ptr-round-trips",
+            Assumption::ExternNoRetain => "extern-no-retain",
+            Assumption::SingleThreaded => "single-threaded",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Assumption> {
+        [
+            Assumption::NoIntPtrRoundTrips,
+            Assumption::ExternNoRetain,
+            Assumption::SingleThreaded,
+        ]
+        .into_iter()
+        .find(|a| a.name() == name)
+    }
+}
+
+/// How rewrites are output when the crate is analyzed under several `--cfg-set`s.  See
+/// [`crate::cfg_sets`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CfgRewrites {
+    /// Apply only the rewrites made under every set.
+    #[default]
+    Common,
+    /// Output the rewrites made under each set separately.
+    PerCfg,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Options {
+    /// Write a summary of the analysis results for this crate's functions to this file.
+    pub export_summary: Option<PathBuf>,
+    /// Read summaries of external functions from these files.
+    pub import_summaries: Vec<PathBuf>,
+    /// Export this crate's summary to `<summary_dir>/<crate_name>.json`, and import all other
+    /// summaries found in the directory.  This is mainly useful in `cargo` wrapper mode, where
+    /// each crate in the workspace can see the summaries of the workspace crates it depends on.
+    pub summary_dir: Option<PathBuf>,
+    /// Read models of extern functions from this file.  See [`crate::extern_models`].
+    pub extern_models: Option<PathBuf>,
+    /// Read dynamic facts about pointers from this file, as written by `c2rust-pdg --facts`.  See
+    /// [`crate::pdg`].
+    pub pdg: Option<PathBuf>,
+    /// Record functions containing unsupported constructs and continue with the rest of the
+    /// crate, instead of aborting.  See [`crate::keep_going`].
+    pub keep_going: bool,
+    /// Apply the rewrites to the original source files.  See [`crate::rewrite`].
+    pub rewrite_in_place: bool,
+    /// Print the rewrites as a unified diff, without changing any files.
+    pub rewrite_diff: bool,
+    /// Write the rewritten source files to this directory.
+    pub rewrite_output_dir: Option<PathBuf>,
+    /// Write the proposed signature and field rewrites to this file, for review.  See
+    /// [`crate::rewrite_plan`].
+    pub rewrite_plan_out: Option<PathBuf>,
+    /// Read overrides for individual rewrites from this file, in the format written by
+    /// `rewrite_plan_out`.
+    pub rewrite_plan: Option<PathBuf>,
+    /// Read the rewrite styles chosen for particular pointers from this file.  See
+    /// [`crate::rewrite_styles`].
+    pub rewrite_styles: Option<PathBuf>,
+    pub output_format: OutputFormat,
+    /// Write an HTML report of the results to this file.  See [`crate::html_report`].
+    pub html_report: Option<PathBuf>,
+    /// Explain the final permissions of these pointers, given as `l3` or `g5`, with local
+    /// pointers qualified by their function, as in `f:l3`.  See [`crate::dataflow::explain`].
+    pub explain: Vec<String>,
+    /// Log filter, in `$RUST_LOG` syntax, such as `c2rust_analyze::dataflow=trace`.  `--quiet` sets
+    /// it to `off`.  See [`crate::log::init_logger`].
+    pub log: Option<String>,
+    /// Only analyze the functions whose def paths match one of these glob patterns, such as
+    /// `parser::*`.  Calls to other functions are treated as calls to unknown functions.  See
+    /// [`crate::annotations`].
+    pub functions: Vec<String>,
+    /// Cache the results for each function in this directory, and reuse them for functions that
+    /// haven't changed.  See [`crate::cache`].
+    pub cache_dir: Option<PathBuf>,
+    /// Print the time and peak memory use of each phase, and the size of each function.  See
+    /// [`crate::stats`].
+    pub stats: bool,
+    /// Write the dataflow constraints and equivalence classes to `<crate_name>.constraints.dot`,
+    /// for debugging.
+    pub debug_constraints: Option<ConstraintDump>,
+    /// Print the MIR of these functions, given by def path, with the labeled type of each place
+    /// and rvalue.  See [`crate::labeled_mir`].
+    pub dump_labeled_mir: Vec<String>,
+    /// Print the other types each signature and field pointer could be rewritten to, and include
+    /// them in the rewrite plan.  See [`crate::alternatives`].
+    pub alternatives: bool,
+    /// Rewrite singly linked structs so that each node owns the next one through `Box`, where
+    /// the analysis allows.  See [`crate::linked_lists`].
+    pub box_lists: bool,
+    /// Print pointers with names derived from where they appear in the source, instead of
+    /// `PointerId`s, so the output of different runs can be compared.  See
+    /// [`crate::pointer_names`].
+    pub stable_pointer_names: bool,
+    /// Print the results of the variable or expression at each of these locations, given as
+    /// `FILE:LINE:COL`.  See [`crate::query`].
+    pub query: Vec<String>,
+    /// Assumptions about the program that relax the analysis.  These are echoed in the results so
+    /// that readers know what the rewrites depend on.
+    pub assume: Vec<Assumption>,
+    /// Analyze the crate once under each of these sets of cfgs, each a comma-separated list in
+    /// `--cfg` syntax, such as `unix,feature="foo"`.  See [`crate::cfg_sets`].
+    pub cfg_sets: Vec<String>,
+    pub cfg_rewrites: CfgRewrites,
+}
+
+impl Options {
+    /// Remove all `c2rust-analyze` options from `args` and parse them.  `args[0]` is the program
+    /// name, and is always kept.
+    pub fn take_from_args(args: &mut Vec<String>) -> Options {
+        let mut opts = Options::default();
+        let mut it = mem::take(args).into_iter();
+        args.extend(it.next());
+
+        while let Some(arg) = it.next() {
+            if arg == "--" {
+                args.extend(it);
+                break;
+            }
+
+            let (name, inline_value) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_owned(), Some(value.to_owned())),
+                None => (arg.clone(), None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| it.next())
+                    .unwrap_or_else(|| panic!("missing value for {name}"))
+            };
+
+            match name.as_str() {
+                "--export-summary" => opts.export_summary = Some(value().into()),
+                "--import-summary" => opts.import_summaries.push(value().into()),
+                "--summary-dir" => opts.summary_dir = Some(value().into()),
+                "--extern-models" => opts.extern_models = Some(value().into()),
+                "--pdg" => opts.pdg = Some(value().into()),
+                "--keep-going" => opts.keep_going = true,
+                "--rewrite-in-place" => opts.rewrite_in_place = true,
+                "--rewrite-diff" => opts.rewrite_diff = true,
+                "--rewrite-output-dir" => opts.rewrite_output_dir = Some(value().into()),
+                "--rewrite-plan-out" => opts.rewrite_plan_out = Some(value().into()),
+                "--rewrite-plan" => opts.rewrite_plan = Some(value().into()),
+                "--rewrite-styles" => opts.rewrite_styles = Some(value().into()),
+                "--explain" => opts.explain.push(value()),
+                "--query" => opts.query.push(value()),
+                "--cache-dir" => opts.cache_dir = Some(value().into()),
+                "--stats" => opts.stats = true,
+                "--alternatives" => opts.alternatives = true,
+                "--box-lists" => opts.box_lists = true,
+                "--stable-pointer-names" => opts.stable_pointer_names = true,
+                "--function" => opts.functions.push(value()),
+                "--cfg-set" => opts.cfg_sets.push(value()),
+                "--dump-labeled-mir" => opts.dump_labeled_mir.push(value()),
+                "--cfg-rewrites" => {
+                    opts.cfg_rewrites = match value().as_str() {
+                        "common" => CfgRewrites::Common,
+                        "per-cfg" => CfgRewrites::PerCfg,
+                        other => panic!("unknown cfg rewrite mode {other:?}"),
+                    }
+                }
+                "--log" => opts.log = Some(value()),
+                "--quiet" => opts.log = Some(LevelFilter::Off.as_str().to_owned()),
+                "--html-report" => opts.html_report = Some(value().into()),
+                "--debug-constraints" => {
+                    opts.debug_constraints = match value().as_str() {
+                        "dot" => Some(ConstraintDump::Dot),
+                        other => panic!("unknown constraint dump format {other:?}"),
+                    }
+                }
+                "--assume" => {
+                    let v = value();
+                    let a = Assumption::from_name(&v)
+                        .unwrap_or_else(|| panic!("unknown assumption {v:?}"));
+                    if !opts.assume.contains(&a) {
+                        opts.assume.push(a);
+                    }
+                }
+                "--output-format" => {
+                    opts.output_format = match value().as_str() {
+                        "text" => OutputFormat::Text,
+                        "json" => OutputFormat::Json,
+                        "sarif" => OutputFormat::Sarif,
+                        other => panic!("unknown output format {other:?}"),
+                    }
+                }
+                _ => args.push(arg),
+            }
+        }
+
+        opts
+    }
+
+    /// Whether the user made the assumption `a`.
+    pub fn assumes(&self, a: Assumption) -> bool {
+        self.assume.contains(&a)
+    }
+
+    /// How to output the rewritten source files.
+    pub fn rewrite_mode(&self) -> rewrite::OutputMode {
+        rewrite::OutputMode {
+            in_place: self.rewrite_in_place,
+            diff: self.rewrite_diff,
+            output_dir: self.rewrite_output_dir.clone(),
+        }
+    }
+
+    /// Read the options passed to us by the `cargo` wrapper.
+    pub fn from_env() -> Options {
+        match env::var(OPTIONS_VAR) {
+            Ok(s) => serde_json::from_str(&s)
+                .unwrap_or_else(|e| panic!("failed to parse ${OPTIONS_VAR}: {e}")),
+            Err(_) => Options::default(),
+        }
+    }
+
+    /// Return the environment variable assignment that passes these options to a `rustc`
+    /// wrapper, for use with [`Options::from_env`].
+    pub fn to_env(&self) -> (&'static str, String) {
+        (OPTIONS_VAR, serde_json::to_string(self).unwrap())
+    }
+}
@@ -0,0 +1,223 @@
+//! Detection of out-parameters that can be turned into return values.
+//!
+//! C functions often return results by writing through a pointer argument, as in `int parse(int
+//! x, int *out)`.  When such a parameter is only ever written through, and is written before
+//! every return, it can be removed from the signature and its value returned instead: `parse`
+//! becomes `fn parse(x: i32) -> (i32, i32)`, or `fn parse(x: i32) -> i32` if it originally
+//! returned nothing.  Call sites change from `parse(x, &mut v)` to `(status, v) = parse(x)`.
+//!
+//! Functions whose address is taken, such as callbacks and the functions stored in function
+//! pointer fields, keep their signatures, since the calls through the pointer can't be rewritten.
+//!
+//! Turning a status code into `Result<T, E>` additionally requires knowing which status values
+//! mean success, so that's left to the user.
+use crate::abi_shims;
+use crate::context::{AnalysisCtxt, Assignment, FlagSet};
+use crate::util;
+use rustc_hir as hir;
+use rustc_hir::def::{DefKind, Res};
+use rustc_hir::def_id::DefId;
+use rustc_hir::intravisit::{self, Visitor as HirVisitor};
+use rustc_middle::hir::nested_filter;
+use rustc_middle::mir::visit::{MutatingUseContext, PlaceContext, Visitor};
+use rustc_middle::mir::{
+    Body, Local, LocalKind, Location, Operand, Place, ProjectionElem, Rvalue, TerminatorKind,
+};
+use rustc_middle::ty::{Ty, TyCtxt, TyKind, TypeckResults};
+use std::collections::HashSet;
+
+/// Checks that the pointers in `locals` are used only to write through, and records where the
+/// writes happen.
+struct UseChecker<'a> {
+    locals: &'a HashSet<Local>,
+    ok: bool,
+    writes: Vec<Location>,
+}
+
+impl<'tcx> Visitor<'tcx> for UseChecker<'_> {
+    fn visit_assign(&mut self, place: &Place<'tcx>, rvalue: &Rvalue<'tcx>, location: Location) {
+        // Copying the pointer between the tracked locals is allowed.
+        let src = match *rvalue {
+            Rvalue::Use(Operand::Copy(pl) | Operand::Move(pl)) => Some(pl),
+            Rvalue::CopyForDeref(pl) => Some(pl),
+            _ => None,
+        };
+        let is_tracked = |pl: Place| pl.as_local().map_or(false, |l| self.locals.contains(&l));
+        if is_tracked(*place) && src.map_or(false, is_tracked) {
+            return;
+        }
+        self.super_assign(place, rvalue, location);
+    }
+
+    fn visit_place(&mut self, place: &Place<'tcx>, context: PlaceContext, location: Location) {
+        if !self.locals.contains(&place.local) {
+            self.super_place(place, context, location);
+            return;
+        }
+        match (&place.projection[..], context) {
+            (
+                [ProjectionElem::Deref],
+                PlaceContext::MutatingUse(MutatingUseContext::Store | MutatingUseContext::Call),
+            ) => self.writes.push(location),
+            ([], PlaceContext::NonUse(_)) => {}
+            _ => self.ok = false,
+        }
+    }
+}
+
+/// Collect `param` and the temporaries that hold copies of it.
+pub fn param_copies(mir: &Body, param: Local) -> HashSet<Local> {
+    let param_place = Place::from(param);
+    mir.local_decls
+        .indices()
+        .filter(|&l| {
+            l == param
+                || (mir.local_kind(l) == LocalKind::Temp
+                    && util::copy_sources(mir, Place::from(l)).contains(&param_place))
+        })
+        .collect()
+}
+
+/// Finds the local functions that are used other than by calling them directly.
+struct AddressTaken<'tcx> {
+    tcx: TyCtxt<'tcx>,
+    typeck: Option<&'tcx TypeckResults<'tcx>>,
+    fns: HashSet<DefId>,
+}
+
+impl<'tcx> HirVisitor<'tcx> for AddressTaken<'tcx> {
+    type NestedFilter = nested_filter::OnlyBodies;
+
+    fn nested_visit_map(&mut self) -> Self::Map {
+        self.tcx.hir()
+    }
+
+    fn visit_nested_body(&mut self, id: hir::BodyId) {
+        let old = self.typeck.replace(self.tcx.typeck_body(id));
+        intravisit::walk_body(self, self.tcx.hir().body(id));
+        self.typeck = old;
+    }
+
+    fn visit_expr(&mut self, ex: &'tcx hir::Expr<'tcx>) {
+        match ex.kind {
+            // The callee of a direct call doesn't take the function's address.
+            hir::ExprKind::Call(callee, args) if matches!(callee.kind, hir::ExprKind::Path(..)) => {
+                for arg in args {
+                    self.visit_expr(arg);
+                }
+                return;
+            }
+            hir::ExprKind::Path(ref qpath) => {
+                let typeck = self.typeck.unwrap();
+                if let Res::Def(DefKind::Fn | DefKind::AssocFn, did) =
+                    typeck.qpath_res(qpath, ex.hir_id)
+                {
+                    if did.is_local() {
+                        self.fns.insert(did);
+                    }
+                }
+            }
+            _ => {}
+        }
+        intravisit::walk_expr(self, ex);
+    }
+}
+
+/// Find the local functions whose address is taken anywhere in the crate, including in the
+/// initializers of statics.
+pub fn address_taken(tcx: TyCtxt) -> HashSet<DefId> {
+    let mut collector = AddressTaken {
+        tcx,
+        typeck: None,
+        fns: HashSet::new(),
+    };
+    tcx.hir().visit_all_item_likes_in_crate(&mut collector);
+    collector.fns
+}
+
+/// Find the parameters of the function `mir` that can be turned into return values.  Returns the
+/// indices of the parameters, in order.  Functions in `address_taken` have none.
+pub fn find<'tcx>(
+    acx: &AnalysisCtxt<'_, 'tcx>,
+    mir: &Body<'tcx>,
+    asn: &Assignment,
+    address_taken: &HashSet<DefId>,
+) -> Vec<usize> {
+    let tcx = acx.tcx();
+    // The signatures of `#[no_mangle]` and `extern "C"` functions are part of the ABI.
+    if abi_shims::is_exported(tcx, mir.source.def_id()) {
+        return Vec::new();
+    }
+    // Calls through a function pointer can't be rewritten.
+    if address_taken.contains(&mir.source.def_id()) {
+        return Vec::new();
+    }
+
+    let flags = asn.flags();
+    let dominators = mir.basic_blocks.dominators();
+    let returns = mir
+        .basic_blocks()
+        .iter_enumerated()
+        .filter(|&(bb, bb_data)| {
+            dominators.is_reachable(bb)
+                && matches!(bb_data.terminator().kind, TerminatorKind::Return)
+        })
+        .map(|(bb, bb_data)| util::terminator_location(bb, bb_data))
+        .collect::<Vec<_>>();
+
+    let mut out = Vec::new();
+    for i in 0..mir.arg_count {
+        let param = Local::from_usize(i + 1);
+        let lty = acx.local_tys[param];
+        if !matches!(lty.ty.kind(), TyKind::RawPtr(..)) {
+            continue;
+        }
+        // Callers that pass null to skip the output, and pointers the user wants kept raw,
+        // can't be removed.
+        if flags[lty.label].intersects(FlagSet::NULLABLE | FlagSet::FIXED) {
+            continue;
+        }
+
+        let locals = param_copies(mir, param);
+        let mut checker = UseChecker {
+            locals: &locals,
+            ok: true,
+            writes: Vec::new(),
+        };
+        checker.visit_body(mir);
+        if !checker.ok {
+            continue;
+        }
+
+        // Every return must be preceded by a write, so the returned value is always initialized.
+        let always_written = returns.iter().all(|&ret| {
+            checker
+                .writes
+                .iter()
+                .any(|&w| w.dominates(ret, &dominators))
+        });
+        if !always_written {
+            continue;
+        }
+
+        out.push(i);
+    }
+    out
+}
+
+/// Compute the return type of a function after turning the out-parameters with types
+/// `param_tys` into return values.
+pub fn rewritten_output<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    output: Ty<'tcx>,
+    param_tys: &[Ty<'tcx>],
+) -> Ty<'tcx> {
+    if output.is_unit() && param_tys.len() == 1 {
+        return param_tys[0];
+    }
+    let tys = (!output.is_unit())
+        .then_some(output)
+        .into_iter()
+        .chain(param_tys.iter().copied());
+    tcx.mk_tup(tys)
+}
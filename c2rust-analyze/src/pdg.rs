@@ -0,0 +1,185 @@
+//! Dynamic facts from the pointer derivation graph (PDG) of an instrumented run of the program.
+//!
+//! `c2rust-pdg --facts <file>` records what the program did at run time with each pointer it
+//! assigned to a MIR local: whether the pointer (or anything derived from it) was loaded from,
+//! stored to, or offset, and whether it was always used uniquely.  With `--pdg <file>`, these facts
+//! are merged per local and compared against the static results once the assignment has reached
+//! its fixpoint:
+//!
+//! * A local pointer that's written through but lost `UNIQUE` in the static analysis would become
+//!   a `Cell`.  If every observed use of it was unique, we take the dynamic result and restore
+//!   `UNIQUE`, so it becomes `&mut` instead.  Global pointers are left alone, since their uses in
+//!   other functions may not have been exercised by the run.
+//! * A static result that lacks a permission the program was seen to use (`READ` for a load,
+//!   `WRITE` for a store, `OFFSET_ADD` or `OFFSET_SUB` for an offset), or that would make a `&mut`
+//!   of a pointer that was seen to be aliased, is reported as a contradiction.  These indicate a bug
+//!   in the static analysis or a mismatch between the source and the instrumented binary.
+//!
+//! Facts are only as complete as the run that produced them, so a pointer that never executed
+//! has no facts and keeps its static result.
+use crate::context::{AnalysisCtxt, Assignment, PermissionSet};
+use crate::diagnostics::{Diagnostics, Level, Rule};
+use crate::util;
+use rustc_hir::def_id::LocalDefId;
+use rustc_middle::mir::{Body, Local};
+use rustc_middle::ty::TyCtxt;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// One record of the facts file, as written by `c2rust-pdg`.
+#[derive(Clone, Debug, Deserialize)]
+struct NodeFacts {
+    /// The [`DefPathHash`](rustc_span::def_id::DefPathHash) of the function, as returned by
+    /// `Fingerprint::as_value`.
+    def_path_hash: (u64, u64),
+    function: String,
+    block: usize,
+    statement_idx: usize,
+    local: u32,
+    load: bool,
+    store: bool,
+    offset: bool,
+    unique: bool,
+}
+
+/// The facts of all the nodes that assigned one local.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LocalFacts {
+    pub load: bool,
+    pub store: bool,
+    pub offset: bool,
+    /// Every assignment of the local was used uniquely.
+    pub unique: bool,
+}
+
+#[derive(Default)]
+pub struct PdgFacts {
+    fns: HashMap<LocalDefId, BTreeMap<Local, LocalFacts>>,
+}
+
+impl PdgFacts {
+    /// Load the facts from `path`, if one was given, and map them to the functions in `ldids`.
+    pub fn load(tcx: TyCtxt, path: Option<&Path>, ldids: &[LocalDefId]) -> PdgFacts {
+        let mut facts = PdgFacts::default();
+        let path = match path {
+            Some(x) => x,
+            None => return facts,
+        };
+        let file =
+            File::open(path).unwrap_or_else(|e| panic!("failed to open PDG facts {path:?}: {e}"));
+        let nodes: Vec<NodeFacts> = serde_json::from_reader(BufReader::new(file))
+            .unwrap_or_else(|e| panic!("failed to parse PDG facts {path:?}: {e}"));
+
+        let by_hash = ldids
+            .iter()
+            .map(|&ldid| (tcx.def_path_hash(ldid.to_def_id()).0.as_value(), ldid))
+            .collect::<HashMap<_, _>>();
+        let mut num_used = 0;
+        for node in &nodes {
+            let ldid = match by_hash.get(&node.def_path_hash) {
+                Some(&x) => x,
+                None => {
+                    ::log::debug!("no function matches PDG node {node:?}");
+                    continue;
+                }
+            };
+            let local = Local::from_u32(node.local);
+            ::log::debug!(
+                "PDG node in {} at bb{}[{}] assigns {local:?}",
+                node.function,
+                node.block,
+                node.statement_idx
+            );
+            let locals = facts.fns.entry(ldid).or_default();
+            let lf = locals.entry(local).or_insert(LocalFacts {
+                unique: true,
+                ..LocalFacts::default()
+            });
+            lf.load |= node.load;
+            lf.store |= node.store;
+            lf.offset |= node.offset;
+            lf.unique &= node.unique;
+            num_used += 1;
+        }
+        eprintln!(
+            "loaded facts from {} of {} PDG nodes in {:?}",
+            num_used,
+            nodes.len(),
+            path
+        );
+        facts
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fns.is_empty()
+    }
+
+    /// Restore `UNIQUE` on the local pointers of `mir` that were always unique at run time, and
+    /// report the static results that contradict the observed behavior.
+    pub fn refine(
+        &self,
+        acx: &AnalysisCtxt,
+        asn: &mut Assignment,
+        mir: &Body,
+        diagnostics: &mut Diagnostics,
+    ) {
+        let ldid = mir.source.def_id().expect_local();
+        let locals = match self.fns.get(&ldid) {
+            Some(x) => x,
+            None => return,
+        };
+        let name = util::fn_name(acx.tcx(), ldid.to_def_id());
+        for (&local, lf) in locals {
+            let decl = match mir.local_decls.get(local) {
+                Some(x) => x,
+                None => {
+                    ::log::debug!("{name}: PDG local {local:?} is out of range");
+                    continue;
+                }
+            };
+            let ptr = acx.local_tys[local].label;
+            if ptr.is_none() {
+                continue;
+            }
+            let mut perms = asn.perms_mut();
+            let perm = &mut perms[ptr];
+
+            if ptr.is_local()
+                && lf.unique
+                && perm.contains(PermissionSet::WRITE)
+                && !perm.contains(PermissionSet::UNIQUE)
+            {
+                perm.insert(PermissionSet::UNIQUE);
+                eprintln!("{name}: {local:?} ({ptr}): unique at run time, now {perm:?}");
+            }
+
+            let mut missing = Vec::new();
+            if lf.load && !perm.contains(PermissionSet::READ) {
+                missing.push("loaded from, but lacks READ");
+            }
+            if lf.store && !perm.contains(PermissionSet::WRITE) {
+                missing.push("stored to, but lacks WRITE");
+            }
+            if lf.offset && !perm.intersects(PermissionSet::OFFSET_ADD | PermissionSet::OFFSET_SUB)
+            {
+                missing.push("offset, but lacks OFFSET_ADD and OFFSET_SUB");
+            }
+            if !lf.unique && perm.contains(PermissionSet::WRITE | PermissionSet::UNIQUE) {
+                missing.push("aliased, but has WRITE and UNIQUE");
+            }
+            for what in missing {
+                let message = format!("{name}: {local:?} ({ptr}) was {what} at run time");
+                eprintln!("{message}");
+                diagnostics.add(
+                    Rule::DynamicContradiction,
+                    Level::Warning,
+                    decl.source_info.span,
+                    message,
+                );
+            }
+        }
+    }
+}
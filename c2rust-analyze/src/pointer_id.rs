@@ -43,6 +43,9 @@ impl PointerId {
 
 impl fmt::Display for PointerId {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(result) = crate::pointer_names::write_name(*self, fmt) {
+            return result;
+        }
         if self.is_none() {
             write!(fmt, "NONE")
         } else if self.is_local() {
@@ -60,7 +63,7 @@ impl fmt::Debug for PointerId {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct NextLocalPointerId(u32);
 
 impl NextLocalPointerId {
@@ -79,7 +82,7 @@ impl NextLocalPointerId {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct NextGlobalPointerId(u32);
 
 impl NextGlobalPointerId {
@@ -0,0 +1,166 @@
+//! Stable names for pointers, for `--stable-pointer-names`.
+//!
+//! A [`PointerId`] is an index in the order pointers were created, so adding a local variable or
+//! a field shifts the IDs of everything after it, and a diff of the output between two runs is
+//! mostly renumbering.  With this option, each pointer is printed instead with a name derived from
+//! where it appears in the source: the def path of the item it belongs to, and a hash of its
+//! position within the item, as in `parse_header@1f03a2c4`.  Signature and field pointers are
+//! named by their position in the item's type, and local pointers by the span of the local or
+//! rvalue they label, relative to the start of the function, so editing one function doesn't
+//! rename the pointers of the functions after it.
+//!
+//! After renumbering, one `PointerId` stands for a whole equivalence class, which can appear in
+//! several places.  The class takes the smallest of their names, so the choice doesn't depend on
+//! `HashMap` order.  Pointers that still end up with the same name, such as two temporaries
+//! expanded from one macro call, get the suffixes `.1`, `.2` and so on, in `PointerId` order.
+//!
+//! The names are installed in a thread-local table that the `Display` impl of `PointerId`
+//! consults, so they appear in all of the text and JSON output.  Local pointers are named for the
+//! function set with [`set_current_fn`].  Pointers that don't appear anywhere with a span are
+//! still printed as `l3` or `g5`.
+use crate::context::{AnalysisCtxt, GlobalAnalysisCtxt, LTy, PointerId};
+use crate::rewrite_plan;
+use rustc_hash::FxHasher;
+use rustc_hir::def_id::LocalDefId;
+use rustc_middle::mir::Body;
+use rustc_middle::ty::TyCtxt;
+use rustc_span::Span;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hasher;
+
+/// The names of the global pointers, and of the local pointers of each function.
+#[derive(Debug, Default)]
+struct Names {
+    global: HashMap<PointerId, String>,
+    local: HashMap<LocalDefId, HashMap<PointerId, String>>,
+}
+
+thread_local! {
+    static NAMES: RefCell<Option<Names>> = RefCell::new(None);
+    /// The function whose local pointers are being printed, if any.
+    static CURRENT_FN: Cell<Option<LocalDefId>> = Cell::new(None);
+}
+
+/// Record that the local pointers printed from now on belong to `ldid`.
+pub fn set_current_fn(ldid: Option<LocalDefId>) {
+    CURRENT_FN.with(|c| c.set(ldid));
+}
+
+/// Write the stable name of `ptr`, if it has one.  Returns `None` if it doesn't, or if stable
+/// names aren't enabled.
+pub fn write_name(ptr: PointerId, f: &mut fmt::Formatter) -> Option<fmt::Result> {
+    NAMES.with(|names| {
+        let names = names.borrow();
+        let names = names.as_ref()?;
+        let name = if ptr.is_global() {
+            names.global.get(&ptr)?
+        } else {
+            let ldid = CURRENT_FN.with(|c| c.get())?;
+            names.local.get(&ldid)?.get(&ptr)?
+        };
+        Some(f.write_str(name))
+    })
+}
+
+/// Collects the names of the pointers in the crate.
+pub struct Namer<'tcx> {
+    tcx: TyCtxt<'tcx>,
+    names: Names,
+}
+
+/// Hash the position of a pointer within its item into the suffix of its name.
+fn hashed(item: &str, parts: &[&dyn fmt::Display]) -> String {
+    let mut hasher = FxHasher::default();
+    for part in parts {
+        hasher.write(part.to_string().as_bytes());
+        hasher.write_u8(0);
+    }
+    format!("{}@{:08x}", item, hasher.finish() as u32)
+}
+
+/// The labels of `lty` and its arguments, in order.
+fn labels(lty: LTy) -> impl Iterator<Item = PointerId> + '_ {
+    lty.iter().map(|lty| lty.label)
+}
+
+/// Record `name` for `ptr`, unless it already has a smaller one.
+fn add_name(names: &mut HashMap<PointerId, String>, ptr: PointerId, name: String) {
+    let old = names.entry(ptr).or_insert_with(|| name.clone());
+    if name < *old {
+        *old = name;
+    }
+}
+
+/// Add the suffixes `.1`, `.2` and so on to pointers that share a name with an earlier pointer.
+fn disambiguate(names: &mut HashMap<PointerId, String>) {
+    let mut ptrs = names.keys().copied().collect::<Vec<_>>();
+    ptrs.sort();
+    let mut seen = HashMap::<String, usize>::new();
+    for ptr in ptrs {
+        let name = names.get_mut(&ptr).unwrap();
+        let count = seen.entry(name.clone()).or_insert(0);
+        if *count > 0 {
+            *name = format!("{}.{}", name, count);
+        }
+        *count += 1;
+    }
+}
+
+impl<'tcx> Namer<'tcx> {
+    /// Name the signature, field, and static pointers.  The local pointers are named by passing
+    /// each function to [`Namer::add_fn`].
+    pub fn new(gacx: &GlobalAnalysisCtxt<'tcx>) -> Namer<'tcx> {
+        let tcx = gacx.tcx;
+        let mut names = Names::default();
+        let sites = rewrite_plan::sites(gacx).map(|site| (site.item, site.position, site.lty));
+        let statics = gacx
+            .static_tys
+            .iter()
+            .map(|(&item, &lty)| (item, "static".to_owned(), lty));
+        for (item, position, lty) in sites.chain(statics) {
+            let item = tcx.def_path_str(item);
+            for (i, ptr) in labels(lty).enumerate().filter(|&(_, ptr)| ptr.is_global()) {
+                add_name(&mut names.global, ptr, hashed(&item, &[&position, &i]));
+            }
+        }
+        Namer { tcx, names }
+    }
+
+    /// Name the local pointers of `ldid`, by the spans of the locals and rvalues they label.
+    pub fn add_fn(&mut self, ldid: LocalDefId, mir: &Body<'tcx>, acx: &AnalysisCtxt<'_, 'tcx>) {
+        let tcx = self.tcx;
+        let path = tcx.def_path_str(ldid.to_def_id());
+        let base = tcx.def_span(ldid).lo();
+        let names = self.names.local.entry(ldid).or_default();
+        let mut add = |kind: &str, span: Span, labels: &mut dyn Iterator<Item = PointerId>| {
+            let lo = span.lo().0.wrapping_sub(base.0);
+            let hi = span.hi().0.wrapping_sub(base.0);
+            for (i, ptr) in labels.enumerate().filter(|&(_, ptr)| ptr.is_local()) {
+                add_name(names, ptr, hashed(&path, &[&kind, &lo, &hi, &i]));
+            }
+        };
+        for (local, decl) in mir.local_decls.iter_enumerated() {
+            let span = decl.source_info.span;
+            add("local", span, &mut labels(acx.local_tys[local]));
+            add(
+                "addr_of",
+                span,
+                &mut Some(acx.addr_of_local[local]).into_iter(),
+            );
+        }
+        for (&loc, &lty) in &acx.rvalue_tys {
+            add("rvalue", mir.source_info(loc).span, &mut labels(lty));
+        }
+    }
+
+    /// Use the names in all output from now on.
+    pub fn install(mut self) {
+        disambiguate(&mut self.names.global);
+        for names in self.names.local.values_mut() {
+            disambiguate(names);
+        }
+        NAMES.with(|names| *names.borrow_mut() = Some(self.names));
+    }
+}
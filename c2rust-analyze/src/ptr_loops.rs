@@ -0,0 +1,472 @@
+//! Rewriting of loops over a buffer with a pointer into loops over a slice.
+//!
+//! c2rust transpiles a C loop like `for (p = buf; p < end; p++)` into
+//!
+//! ```ignore
+//! p = buf;
+//! while p < end {
+//!     *p = 0;
+//!     p = p.offset(1);
+//! }
+//! ```
+//!
+//! where `end` is `buf.offset(n)`, either written in the condition or computed by an earlier
+//! `let`.  The offsets make `buf` a slice, and `p` one too, so the loop would still step through
+//! the buffer one subslice at a time.  [`find`] recognizes these loops and rewrites them to
+//! `for p in buf[..usize::try_from(n).unwrap_or(0)].iter_mut()`, or `iter()` if the elements are
+//! only read, which makes `p` a reference to each element in turn.  A negative `n` runs the loop
+//! zero times, as `p < end` would, rather than panicking.  The initialization of `p` and its
+//! increment are removed, and so is the `let` of `end` if the condition is its only use.
+//!
+//! A loop is only rewritten if it keeps its meaning:
+//!
+//! * `p` is stepped by exactly one element at the end of the body, and the body uses it only by
+//!   dereferencing it, doesn't mention `buf`, and doesn't `continue` the loop, which would skip
+//!   the increment;
+//! * `buf` and the locals in `n` are never reassigned, so the bound is the same where the loop
+//!   starts as where `end` was computed;
+//! * `p` is dead when the loop exits, according to [`Liveness`], since it no longer exists
+//!   afterward.
+use crate::context::{AnalysisCtxt, Assignment, FlagSet, PermissionSet, PointerId};
+use crate::reborrow::Liveness;
+use crate::rewrite::TextEdit;
+use crate::type_desc::{self, Ownership, Quantity};
+use crate::util::{self, Callee};
+use rustc_hir as hir;
+use rustc_hir::def::Res;
+use rustc_hir::intravisit::{self, Visitor};
+use rustc_hir::HirId;
+use rustc_middle::mir::{
+    BindingForm, Body, Local, LocalInfo, Rvalue, StatementKind, TerminatorKind,
+};
+use rustc_middle::ty::{TyCtxt, TypeckResults};
+use rustc_span::{BytePos, Span, Symbol};
+use std::collections::HashSet;
+
+/// A loop over a buffer that can be rewritten to iterate over a slice.
+pub struct PtrLoop {
+    /// The name of the pointer that steps through the buffer.
+    pub name: Symbol,
+    /// The span of the `while` loop.
+    pub span: Span,
+    /// The new loop header, like `for p in buf[..usize::try_from(n).unwrap_or(0)].iter_mut()`.
+    pub header: String,
+    edits: Vec<TextEdit>,
+}
+
+impl PtrLoop {
+    /// The edits that rewrite the loop.
+    pub fn edits(&self) -> &[TextEdit] {
+        &self.edits
+    }
+
+    /// Whether an edit of `span` conflicts with the rewrite of the loop, because it changes code
+    /// that the rewrite replaces or removes.  Such edits were planned for the pointer types the
+    /// loop no longer uses, and should be dropped.
+    pub fn conflicts(&self, span: Span) -> bool {
+        self.edits.iter().any(|e| e.span.overlaps(span))
+    }
+}
+
+/// The local that `ex` names, if it's a path to one.
+fn path_local(ex: &hir::Expr) -> Option<HirId> {
+    match ex.kind {
+        hir::ExprKind::Path(hir::QPath::Resolved(None, path)) => match path.res {
+            Res::Local(id) => Some(id),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The expression of a statement, if it's an expression statement.
+fn stmt_expr<'tcx>(stmt: &hir::Stmt<'tcx>) -> Option<&'tcx hir::Expr<'tcx>> {
+    match stmt.kind {
+        hir::StmtKind::Expr(ex) | hir::StmtKind::Semi(ex) => Some(ex),
+        _ => None,
+    }
+}
+
+/// The local and initial value of a statement that sets a local, `let p = x;` or `p = x;`.
+fn stmt_init<'tcx>(stmt: &hir::Stmt<'tcx>) -> Option<(HirId, &'tcx hir::Expr<'tcx>)> {
+    match stmt.kind {
+        hir::StmtKind::Local(local) => match local.pat.kind {
+            hir::PatKind::Binding(_, id, _, None) => Some((id, local.init?)),
+            _ => None,
+        },
+        hir::StmtKind::Semi(ex) => match ex.kind {
+            hir::ExprKind::Assign(lhs, rhs, _) => Some((path_local(lhs)?, rhs)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether `ex` is the literal `1`, possibly cast.
+fn is_one(ex: &hir::Expr) -> bool {
+    match ex.kind {
+        hir::ExprKind::Lit(ref lit) => matches!(lit.node, rustc_ast::LitKind::Int(1, _)),
+        hir::ExprKind::Cast(inner, _) => is_one(inner),
+        _ => false,
+    }
+}
+
+/// An edit that removes the statement at `span`, along with its line if nothing else is on it.
+fn remove_stmt(tcx: TyCtxt, span: Span) -> TextEdit {
+    let sm = tcx.sess.source_map();
+    let before = sm.span_to_prev_source(span).unwrap_or_default();
+    let after = sm.span_to_next_source(span).unwrap_or_default();
+    let indent = before.len() - before.trim_end_matches([' ', '\t']).len();
+    let trailing = after.len() - after.trim_start_matches([' ', '\t']).len();
+    let own_line =
+        before[..before.len() - indent].ends_with('\n') && after[trailing..].starts_with('\n');
+    if own_line {
+        let lo = span.lo() - BytePos(indent as u32);
+        let hi = span.hi() + BytePos(trailing as u32 + 1);
+        TextEdit::new(span.with_lo(lo).with_hi(hi), "")
+    } else {
+        TextEdit::new(span, "")
+    }
+}
+
+/// Collects the locals that are assigned or mutably borrowed anywhere in a body, and counts the
+/// uses of each local.
+#[derive(Default)]
+struct LocalUses {
+    assigned: HashSet<HirId>,
+    uses: Vec<HirId>,
+}
+
+impl<'tcx> Visitor<'tcx> for LocalUses {
+    fn visit_expr(&mut self, ex: &'tcx hir::Expr<'tcx>) {
+        match ex.kind {
+            hir::ExprKind::Assign(lhs, _, _)
+            | hir::ExprKind::AssignOp(_, lhs, _)
+            | hir::ExprKind::AddrOf(_, hir::Mutability::Mut, lhs) => {
+                self.assigned.extend(path_local(lhs));
+            }
+            _ => {}
+        }
+        self.uses.extend(path_local(ex));
+        intravisit::walk_expr(self, ex);
+    }
+}
+
+/// Checks that a loop body uses the pointer `ptr` only by dereferencing it, never mentions the
+/// buffer `buf`, and never continues the loop `loop_id`.
+struct BodyChecker {
+    ptr: HirId,
+    buf: HirId,
+    loop_id: HirId,
+    ok: bool,
+}
+
+impl<'tcx> Visitor<'tcx> for BodyChecker {
+    fn visit_expr(&mut self, ex: &'tcx hir::Expr<'tcx>) {
+        match ex.kind {
+            hir::ExprKind::Unary(hir::UnOp::Deref, inner)
+                if path_local(inner) == Some(self.ptr) =>
+            {
+                return;
+            }
+            hir::ExprKind::Continue(dest) if dest.target_id.ok() == Some(self.loop_id) => {
+                self.ok = false;
+            }
+            _ => {}
+        }
+        if let Some(id) = path_local(ex) {
+            if id == self.ptr || id == self.buf {
+                self.ok = false;
+            }
+        }
+        intravisit::walk_expr(self, ex);
+    }
+}
+
+struct Finder<'a, 'tcx> {
+    acx: &'a AnalysisCtxt<'a, 'tcx>,
+    asn: &'a Assignment<'a>,
+    mir: &'a Body<'tcx>,
+    typeck: &'tcx TypeckResults<'tcx>,
+    liveness: Liveness<'a, 'tcx>,
+    locals: LocalUses,
+    loops: Vec<PtrLoop>,
+}
+
+impl<'a, 'tcx> Finder<'a, 'tcx> {
+    fn snippet(&self, ex: &hir::Expr) -> Option<String> {
+        let sm = self.acx.tcx().sess.source_map();
+        sm.span_to_snippet(ex.span).ok()
+    }
+
+    /// The base and offset of a call to `<*mut T>::offset` or `<*const T>::offset`.
+    fn ptr_offset<'e>(
+        &self,
+        ex: &'e hir::Expr<'e>,
+    ) -> Option<(&'e hir::Expr<'e>, &'e hir::Expr<'e>)> {
+        let tcx = self.acx.tcx();
+        let args = match ex.kind {
+            hir::ExprKind::MethodCall(_, [base, offset], _) => (base, offset),
+            _ => return None,
+        };
+        let did = self.typeck.type_dependent_def_id(ex.hir_id)?;
+        let fn_ty = tcx.mk_fn_def(did, self.typeck.node_substs(ex.hir_id));
+        match util::ty_callee(tcx, fn_ty) {
+            Callee::PtrOffset { .. } => Some(args),
+            _ => None,
+        }
+    }
+
+    /// Whether `ex` has the same value wherever it's evaluated: it only reads locals that are
+    /// never reassigned.
+    fn is_stable(&self, ex: &hir::Expr) -> bool {
+        match ex.kind {
+            hir::ExprKind::Lit(_) => true,
+            hir::ExprKind::Path(hir::QPath::Resolved(None, path)) => match path.res {
+                Res::Local(id) => !self.locals.assigned.contains(&id),
+                Res::Def(..) => true,
+                _ => false,
+            },
+            hir::ExprKind::Cast(inner, _) | hir::ExprKind::Unary(_, inner) => self.is_stable(inner),
+            hir::ExprKind::Binary(_, a, b) => self.is_stable(a) && self.is_stable(b),
+            _ => false,
+        }
+    }
+
+    /// The MIR local for the variable bound at `id`.
+    fn mir_local(&self, id: HirId) -> Option<Local> {
+        let span = self.acx.tcx().hir().span(id);
+        self.mir
+            .local_decls
+            .iter_enumerated()
+            .find_map(|(local, decl)| match **decl.local_info.as_ref()? {
+                LocalInfo::User(ref binding_form) => {
+                    match *binding_form.as_ref().assert_crate_local() {
+                        BindingForm::Var(ref v) if v.pat_span == span => Some(local),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            })
+    }
+
+    fn ptr_of(&self, id: HirId) -> Option<(Local, PointerId)> {
+        let local = self.mir_local(id)?;
+        let ptr = self.acx.local_tys[local].label;
+        (!ptr.is_none()).then_some((local, ptr))
+    }
+
+    /// Whether `ptr` is dead when the loop whose condition is at `cond_span` exits.
+    fn dead_on_exit(&self, ptr: Local, cond_span: Span) -> bool {
+        for data in self.mir.basic_blocks().iter() {
+            let is_cond = data.statements.iter().any(|stmt| match stmt.kind {
+                StatementKind::Assign(ref x) => {
+                    stmt.source_info.span == cond_span && matches!(x.1, Rvalue::BinaryOp(..))
+                }
+                _ => false,
+            });
+            if !is_cond {
+                continue;
+            }
+            let exit = match data.terminator().kind {
+                TerminatorKind::SwitchInt { ref targets, .. } => targets
+                    .iter()
+                    .find_map(|(v, target)| (v == 0).then_some(target)),
+                _ => None,
+            };
+            return match exit {
+                Some(exit) => !self.liveness.live_on_entry(ptr, exit),
+                None => false,
+            };
+        }
+        false
+    }
+
+    /// Check whether `init` followed by the loop `ex` is a pointer loop, and record it if so.
+    /// `stmts` are the statements of the enclosing block.
+    fn check_loop(
+        &mut self,
+        stmts: &'tcx [hir::Stmt<'tcx>],
+        init: &'tcx hir::Stmt<'tcx>,
+        ex: &'tcx hir::Expr<'tcx>,
+    ) -> Option<()> {
+        let tcx = self.acx.tcx();
+        let (block, label, header_span) = match ex.kind {
+            hir::ExprKind::Loop(block, label, hir::LoopSource::While, span) => (block, label, span),
+            _ => return None,
+        };
+        let (cond, body) = match block.expr?.kind {
+            hir::ExprKind::If(cond, body, Some(_)) => (cond, body),
+            _ => return None,
+        };
+        let cond = match cond.kind {
+            hir::ExprKind::DropTemps(cond) => cond,
+            _ => cond,
+        };
+        let (ptr, bound) = match cond.kind {
+            hir::ExprKind::Binary(op, lhs, rhs)
+                if matches!(op.node, hir::BinOpKind::Lt | hir::BinOpKind::Ne) =>
+            {
+                (path_local(lhs)?, rhs)
+            }
+            _ => return None,
+        };
+
+        // `p = buf;`
+        let (init_ptr, start) = stmt_init(init)?;
+        let buf = path_local(start)?;
+        if init_ptr != ptr || buf == ptr {
+            return None;
+        }
+
+        // `buf.offset(n)`, in the condition or in `let end = buf.offset(n);`.
+        let mut end_let = None;
+        let end = match path_local(bound) {
+            Some(end) => {
+                let stmt = stmts.iter().find(|s| match stmt_init(s) {
+                    Some((id, _)) => id == end && matches!(s.kind, hir::StmtKind::Local(_)),
+                    None => false,
+                })?;
+                let uses = self.locals.uses.iter().filter(|&&id| id == end).count();
+                if uses == 1 && !self.locals.assigned.contains(&end) {
+                    end_let = Some(stmt);
+                }
+                stmt_init(stmt)?.1
+            }
+            None => bound,
+        };
+        let (end_base, count) = self.ptr_offset(end)?;
+        if path_local(end_base) != Some(buf)
+            || self.locals.assigned.contains(&buf)
+            || !self.is_stable(count)
+        {
+            return None;
+        }
+
+        // The body ends with `p = p.offset(1);`.
+        let body_block = match body.kind {
+            hir::ExprKind::Block(block, None) if block.expr.is_none() => block,
+            _ => return None,
+        };
+        let (step, rest) = body_block.stmts.split_last()?;
+        let (step_ptr, step_val) = stmt_init(step)?;
+        let (step_base, step_offset) = self.ptr_offset(step_val)?;
+        if step_ptr != ptr
+            || !matches!(step.kind, hir::StmtKind::Semi(_))
+            || path_local(step_base) != Some(ptr)
+            || !is_one(step_offset)
+        {
+            return None;
+        }
+        let mut checker = BodyChecker {
+            ptr,
+            buf,
+            loop_id: ex.hir_id,
+            ok: true,
+        };
+        for stmt in rest {
+            checker.visit_stmt(stmt);
+        }
+        if !checker.ok {
+            return None;
+        }
+
+        // The buffer must become a slice, and `p` must be dead after the loop.
+        let (_, buf_ptr) = self.ptr_of(buf)?;
+        let (ptr_local, ptr_ptr) = self.ptr_of(ptr)?;
+        let perms = self.asn.perms();
+        let flags = self.asn.flags();
+        if flags[ptr_ptr].contains(FlagSet::FIXED) || flags[buf_ptr].contains(FlagSet::NULLABLE) {
+            return None;
+        }
+        let (own, qty) = type_desc::perms_to_desc(perms[buf_ptr], flags[buf_ptr]);
+        let write = perms[ptr_ptr].contains(PermissionSet::WRITE);
+        let iter = match own {
+            Ownership::Mut | Ownership::Box | Ownership::Vec if write => "iter_mut",
+            Ownership::Mut | Ownership::Box | Ownership::Vec | Ownership::Imm if !write => "iter",
+            _ => return None,
+        };
+        if qty != Quantity::Slice || !self.dead_on_exit(ptr_local, cond.span) {
+            return None;
+        }
+
+        let spans = [init.span, header_span, step.span];
+        if spans
+            .iter()
+            .chain(end_let.map(|s| &s.span))
+            .any(|s| s.from_expansion())
+        {
+            return None;
+        }
+        let name = tcx.hir().name(ptr);
+        let label = label.map_or_else(String::new, |l| format!("{}: ", l.ident));
+        let header = format!(
+            "{}for {} in {}[..usize::try_from({}).unwrap_or(0)].{}()",
+            label,
+            name,
+            self.snippet(start)?,
+            self.snippet(count)?,
+            iter
+        );
+        let mut edits = vec![
+            remove_stmt(tcx, init.span),
+            TextEdit::new(header_span, header.clone()),
+            remove_stmt(tcx, step.span),
+        ];
+        if let Some(stmt) = end_let {
+            edits.push(remove_stmt(tcx, stmt.span));
+        }
+        self.loops.push(PtrLoop {
+            name,
+            span: ex.span,
+            header,
+            edits,
+        });
+        Some(())
+    }
+}
+
+impl<'a, 'tcx> Visitor<'tcx> for Finder<'a, 'tcx> {
+    fn visit_block(&mut self, block: &'tcx hir::Block<'tcx>) {
+        let stmts = block.stmts;
+        let loops = stmts
+            .iter()
+            .skip(1)
+            .map(stmt_expr)
+            .chain(Some(block.expr))
+            .enumerate();
+        for (i, ex) in loops {
+            if let Some(ex) = ex {
+                if let Some(init) = stmts.get(i) {
+                    self.check_loop(stmts, init, ex);
+                }
+            }
+        }
+        intravisit::walk_block(self, block);
+    }
+}
+
+/// Find the loops of `mir` that step a pointer through a buffer and can iterate over a slice
+/// instead.
+pub fn find<'tcx>(
+    acx: &AnalysisCtxt<'_, 'tcx>,
+    asn: &Assignment,
+    mir: &Body<'tcx>,
+) -> Vec<PtrLoop> {
+    let tcx = acx.tcx();
+    let ldid = mir.source.def_id().expect_local();
+    let body = tcx.hir().body(tcx.hir().body_owned_by(ldid));
+    let mut locals = LocalUses::default();
+    locals.visit_body(body);
+    let mut finder = Finder {
+        acx,
+        asn,
+        mir,
+        typeck: tcx.typeck(ldid),
+        liveness: Liveness::new(mir),
+        locals,
+        loops: Vec::new(),
+    };
+    finder.visit_body(body);
+    finder.loops
+}
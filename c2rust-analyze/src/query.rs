@@ -0,0 +1,156 @@
+//! Queries over the analysis results by source location, for `--query FILE:LINE:COL` and
+//! [`AnalysisResults::permissions_of`](crate::AnalysisResults::permissions_of).
+//!
+//! Tools built on the analysis, such as an editor showing the inferred safe type of the
+//! expression under the cursor, need the results by source location rather than by
+//! [`PointerId`](crate::context::PointerId).  [`QueryIndex`] records, for the span of each
+//! pointer-typed variable, parameter, and rvalue in the analyzed functions, the final permissions
+//! and flags of its outermost pointer and the type it will be rewritten to.  A query for a
+//! location returns the innermost recorded span containing it, so a query inside `&(*p).x` finds
+//! the address-of expression, and a query on the name of a variable finds the variable.
+//!
+//! Spans in macro expansions aren't recorded, since they don't correspond to anything the user
+//! can point at.
+use crate::context::{AnalysisCtxt, Assignment};
+use crate::json_output::flag_names;
+use crate::lifetimes::TypePrinter;
+use rustc_middle::mir::{Body, LocalKind};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// The results for one variable or expression.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SpanResults {
+    /// The source file containing the span.
+    pub path: PathBuf,
+    /// The byte range of the span within the file.
+    pub lo: usize,
+    pub hi: usize,
+    /// The 1-based line and column where the span starts and ends.
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    /// The def path of the function containing the span.
+    pub def_path: String,
+    /// The source text of the span.
+    pub text: String,
+    pub perms: Vec<String>,
+    pub flags: Vec<String>,
+    /// The type the variable or expression will have after rewriting.
+    pub ty: String,
+}
+
+/// The results of every variable and expression with a pointer type, by span.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct QueryIndex {
+    entries: Vec<SpanResults>,
+}
+
+impl QueryIndex {
+    /// Record the variables and rvalues of the function `mir`.
+    pub fn add_fn<'tcx>(
+        &mut self,
+        acx: &AnalysisCtxt<'_, 'tcx>,
+        asn: &Assignment,
+        mir: &Body<'tcx>,
+        printer: &TypePrinter<'_, 'tcx>,
+    ) {
+        let tcx = acx.tcx();
+        let sm = tcx.sess.source_map();
+        let def_path = tcx.def_path_str(mir.source.def_id());
+        let mut spans = Vec::new();
+        for (local, decl) in mir.local_decls.iter_enumerated() {
+            if mir.local_kind(local) == LocalKind::Arg || decl.is_user_variable() {
+                spans.push((decl.source_info.span, acx.local_tys[local]));
+            }
+        }
+        let mut rvalues = acx.rvalue_tys.iter().collect::<Vec<_>>();
+        rvalues.sort_by_key(|&(&loc, _)| loc);
+        for (&loc, &lty) in rvalues {
+            spans.push((mir.source_info(loc).span, lty));
+        }
+
+        for (span, lty) in spans {
+            if lty.label.is_none() || span.from_expansion() || span.is_dummy() {
+                continue;
+            }
+            let text = match sm.span_to_snippet(span) {
+                Ok(s) => s,
+                Err(_) => continue,
+            };
+            let (start, end) = (sm.lookup_char_pos(span.lo()), sm.lookup_char_pos(span.hi()));
+            self.entries.push(SpanResults {
+                path: PathBuf::from(start.file.name.prefer_local().to_string()),
+                lo: sm.lookup_byte_offset(span.lo()).pos.0 as usize,
+                hi: sm.lookup_byte_offset(span.hi()).pos.0 as usize,
+                start: (start.line, start.col.0 + 1),
+                end: (end.line, end.col.0 + 1),
+                def_path: def_path.clone(),
+                text,
+                perms: flag_names(asn.perms()[lty.label]),
+                flags: flag_names(asn.flags()[lty.label]),
+                ty: printer.print(lty, &|_| None, None),
+            });
+        }
+    }
+
+    /// All the recorded spans, in the order their functions were analyzed.
+    pub fn entries(&self) -> &[SpanResults] {
+        &self.entries
+    }
+
+    /// The innermost recorded span in the file `path` that contains the bytes `lo..hi`.  `path`
+    /// may be a suffix of the recorded path, such as just the file name.
+    pub fn permissions_of(&self, path: &Path, lo: usize, hi: usize) -> Option<&SpanResults> {
+        self.innermost(path, |e| e.lo <= lo && hi <= e.hi)
+    }
+
+    /// The innermost recorded span in the file `path` that contains the character at the 1-based
+    /// `line` and `col`.
+    pub fn permissions_at(&self, path: &Path, line: usize, col: usize) -> Option<&SpanResults> {
+        self.innermost(path, |e| e.start <= (line, col) && (line, col) < e.end)
+    }
+
+    fn innermost(
+        &self,
+        path: &Path,
+        contains: impl Fn(&SpanResults) -> bool,
+    ) -> Option<&SpanResults> {
+        self.entries
+            .iter()
+            .filter(|e| e.path.ends_with(path) && contains(e))
+            .min_by_key(|e| e.hi - e.lo)
+    }
+}
+
+/// Parse a `--query` location, `FILE:LINE:COL`.
+pub fn parse_location(s: &str) -> Option<(PathBuf, usize, usize)> {
+    let mut parts = s.rsplitn(3, ':');
+    let col = parts.next()?.parse().ok()?;
+    let line = parts.next()?.parse().ok()?;
+    let file = parts.next()?;
+    Some((PathBuf::from(file), line, col))
+}
+
+/// Print the results for each of the locations given with `--query`.
+pub fn print(index: &QueryIndex, locations: &[String]) {
+    if locations.is_empty() {
+        return;
+    }
+    eprintln!("\n=== query results ===");
+    for loc in locations {
+        let (path, line, col) = parse_location(loc)
+            .unwrap_or_else(|| panic!("bad --query location {:?}: expected FILE:LINE:COL", loc));
+        match index.permissions_at(&path, line, col) {
+            Some(e) => eprintln!(
+                "{}: `{}` in {}: {}, perms = {}, flags = {}",
+                loc,
+                e.text,
+                e.def_path,
+                e.ty,
+                e.perms.join(" | "),
+                e.flags.join(" | "),
+            ),
+            None => eprintln!("{}: no pointer here", loc),
+        }
+    }
+}
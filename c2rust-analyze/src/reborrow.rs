@@ -0,0 +1,141 @@
+//! Planning of the reborrows needed once raw pointers become references.
+//!
+//! Raw pointers are `Copy`, but `&mut T` isn't: using a `&mut` as an operand moves it, unless the
+//! compiler can see that the destination is a `&mut` too and inserts an implicit reborrow.  That
+//! only happens at coercion sites with a known type, so rewritten code like `let q = p;` or
+//! `s.f = p;` moves `p`, and any later use of `p` fails to borrow-check.  To avoid this,
+//! [`crate::expr_rewrite`] emits an explicit `&mut *p` reborrow for every copy of a pointer that
+//! becomes `&mut`, except where [`Liveness`] shows that the copy is the last use of the local, so
+//! moving it is fine.  Copies out of a place behind another pointer, like `(*s).f`, are always
+//! reborrowed, since moving out of them isn't allowed at all.
+//!
+//! Borrows end at their last use, so the rewritten code doesn't need extra scopes to end a
+//! reborrow before the original reference is used again.
+use rustc_index::bit_set::BitSet;
+use rustc_index::vec::IndexVec;
+use rustc_middle::mir::visit::{MutatingUseContext, PlaceContext, Visitor};
+use rustc_middle::mir::{BasicBlock, Body, Local, Location};
+
+/// The locals each statement or terminator uses and defines.  A local that's both used and
+/// defined, as in `p = p.offset(1)`, counts as used.
+#[derive(Default)]
+struct UseDef {
+    uses: Vec<Local>,
+    defs: Vec<Local>,
+}
+
+impl<'tcx> Visitor<'tcx> for UseDef {
+    fn visit_local(&mut self, local: Local, context: PlaceContext, _loc: Location) {
+        match context {
+            PlaceContext::NonUse(_) => {}
+            PlaceContext::MutatingUse(
+                MutatingUseContext::Store
+                | MutatingUseContext::Call
+                | MutatingUseContext::AsmOutput
+                | MutatingUseContext::Yield
+                | MutatingUseContext::Deinit,
+            ) => self.defs.push(local),
+            _ => self.uses.push(local),
+        }
+    }
+}
+
+fn use_def(mir: &Body, loc: Location) -> UseDef {
+    let mut ud = UseDef::default();
+    let bb = &mir.basic_blocks()[loc.block];
+    match bb.statements.get(loc.statement_index) {
+        Some(stmt) => ud.visit_statement(stmt, loc),
+        None => ud.visit_terminator(bb.terminator(), loc),
+    }
+    ud
+}
+
+/// Which locals are live, meaning they may be used before being reassigned, after each point of
+/// the function.
+pub struct Liveness<'a, 'tcx> {
+    mir: &'a Body<'tcx>,
+    live_out: IndexVec<BasicBlock, BitSet<Local>>,
+}
+
+impl<'a, 'tcx> Liveness<'a, 'tcx> {
+    pub fn new(mir: &'a Body<'tcx>) -> Liveness<'a, 'tcx> {
+        let num_locals = mir.local_decls.len();
+        let blocks = mir.basic_blocks();
+
+        // For each block, the locals used before being defined, and the locals defined.
+        let mut gen = IndexVec::from_elem_n(BitSet::new_empty(num_locals), blocks.len());
+        let mut kill = gen.clone();
+        for (bb, data) in blocks.iter_enumerated() {
+            for i in (0..=data.statements.len()).rev() {
+                let ud = use_def(
+                    mir,
+                    Location {
+                        block: bb,
+                        statement_index: i,
+                    },
+                );
+                for &l in &ud.defs {
+                    gen[bb].remove(l);
+                    kill[bb].insert(l);
+                }
+                for &l in &ud.uses {
+                    gen[bb].insert(l);
+                    kill[bb].remove(l);
+                }
+            }
+        }
+
+        let mut live_in = IndexVec::from_elem_n(BitSet::new_empty(num_locals), blocks.len());
+        let mut live_out = live_in.clone();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (bb, data) in blocks.iter_enumerated().rev() {
+                let mut out = BitSet::new_empty(num_locals);
+                for succ in data.terminator().successors() {
+                    out.union(&live_in[succ]);
+                }
+                let mut inp = out.clone();
+                inp.subtract(&kill[bb]);
+                inp.union(&gen[bb]);
+                changed |= live_in[bb] != inp;
+                live_in[bb] = inp;
+                live_out[bb] = out;
+            }
+        }
+
+        Liveness { mir, live_out }
+    }
+
+    /// Whether `local` may be used again after the statement or terminator at `loc`.
+    pub fn live_after(&self, local: Local, loc: Location) -> bool {
+        self.live_from(local, loc.block, loc.statement_index + 1)
+    }
+
+    /// Whether `local` may be used before being reassigned once control reaches block `bb`.
+    pub fn live_on_entry(&self, local: Local, bb: BasicBlock) -> bool {
+        self.live_from(local, bb, 0)
+    }
+
+    /// Whether `local` may be used before being reassigned, starting from statement `start` of
+    /// block `bb`.
+    fn live_from(&self, local: Local, bb: BasicBlock, start: usize) -> bool {
+        let num_stmts = self.mir.basic_blocks()[bb].statements.len();
+        for i in start..=num_stmts {
+            let ud = use_def(
+                self.mir,
+                Location {
+                    block: bb,
+                    statement_index: i,
+                },
+            );
+            if ud.uses.contains(&local) {
+                return true;
+            }
+            if ud.defs.contains(&local) {
+                return false;
+            }
+        }
+        self.live_out[bb].contains(local)
+    }
+}
@@ -0,0 +1,967 @@
+//! Applying the computed rewrites to the source files.
+//!
+//! Rewrites are turned into [`TextEdit`]s, each of which replaces the source text of a span.
+//! Types in function signatures, struct fields, and `let` statements with an explicit type are
+//! replaced with their rewritten forms, and lifetime parameters are added where needed.  Rewritten
+//! expressions are rendered from the source text of their subexpressions, which is found through
+//! the HIR.  Expression rewrites that apply to only part of a MIR statement (those with a
+//! non-empty [`ExprLoc::sub`]) have no exact span, and aren't applied yet.  A function with a
+//! rewrite that can't be applied is left unchanged, with a warning, rather than given new types
+//! that its body doesn't match.
+//!
+//! The edited files can be written back in place (`--rewrite-in-place`), written to another
+//! directory (`--rewrite-output-dir`), or printed as a unified diff without changing anything
+//! (`--rewrite-diff`).  Signature and field rewrites can be reviewed individually through a
+//! rewrite plan; see [`crate::rewrite_plan`].
+//!
+//! [`ExprLoc::sub`]: crate::expr_rewrite::ExprLoc::sub
+use crate::alternatives::Alternatives;
+use crate::context::{AnalysisCtxt, Assignment, GlobalAnalysisCtxt, LFnSig, LTy, PointerId};
+use crate::expr_rewrite::{ExprRewrite, RewriteKind, SubLoc};
+use crate::lifetimes::{FnLifetimes, TypePrinter};
+use crate::rewrite_plan::{Planner, Site};
+use crate::type_desc;
+use rustc_hir as hir;
+use rustc_hir::def_id::{DefId, LocalDefId};
+use rustc_hir::intravisit::{self, Visitor};
+use rustc_middle::hir::nested_filter;
+use rustc_middle::mir::{BindingForm, Body, ClearCrossCrate, LocalInfo};
+use rustc_middle::ty::{Ty, TyCtxt};
+use rustc_span::{BytePos, FileName, Span};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Number of lines of context to show around each change in a diff.
+const DIFF_CONTEXT: usize = 3;
+
+/// A replacement of the source text of `span` with `text`.
+#[derive(Clone, Debug)]
+pub struct TextEdit {
+    pub span: Span,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn new(span: Span, text: impl Into<String>) -> TextEdit {
+        TextEdit {
+            span,
+            text: text.into(),
+        }
+    }
+}
+
+/// The HIR expressions and `let` statements of a function body, indexed by span.
+pub struct BodyExprs<'tcx> {
+    tcx: TyCtxt<'tcx>,
+    ldid: LocalDefId,
+    body: &'tcx hir::Body<'tcx>,
+    exprs: HashMap<Span, &'tcx hir::Expr<'tcx>>,
+    /// The parent of each expression, by the expression's span.
+    parents: HashMap<Span, &'tcx hir::Expr<'tcx>>,
+    /// The span of the type annotation of each `let`, by the span of its pattern.
+    let_tys: HashMap<Span, Span>,
+}
+
+impl<'tcx> Visitor<'tcx> for BodyExprs<'tcx> {
+    type NestedFilter = nested_filter::OnlyBodies;
+
+    fn nested_visit_map(&mut self) -> Self::Map {
+        self.tcx.hir()
+    }
+
+    fn visit_expr(&mut self, ex: &'tcx hir::Expr<'tcx>) {
+        self.exprs.entry(ex.span).or_insert(ex);
+        let mut record_parent = |child: &hir::Expr| {
+            self.parents.entry(child.span).or_insert(ex);
+        };
+        match ex.kind {
+            hir::ExprKind::Cast(inner, _) => record_parent(inner),
+            hir::ExprKind::Assign(lhs, rhs, _) => {
+                record_parent(lhs);
+                record_parent(rhs);
+            }
+            _ => {}
+        }
+        intravisit::walk_expr(self, ex);
+    }
+
+    fn visit_local(&mut self, local: &'tcx hir::Local<'tcx>) {
+        if let Some(ty) = local.ty {
+            self.let_tys.insert(local.pat.span, ty.span);
+        }
+        intravisit::walk_local(self, local);
+    }
+}
+
+impl<'tcx> BodyExprs<'tcx> {
+    pub fn new(tcx: TyCtxt<'tcx>, ldid: LocalDefId) -> BodyExprs<'tcx> {
+        let body = tcx.hir().body(tcx.hir().body_owned_by(ldid));
+        let mut exprs = BodyExprs {
+            tcx,
+            ldid,
+            body,
+            exprs: HashMap::new(),
+            parents: HashMap::new(),
+            let_tys: HashMap::new(),
+        };
+        exprs.visit_body(body);
+        exprs
+    }
+
+    /// The span of the type annotation of the `let` whose pattern is at `pat_span`, if it has one.
+    pub fn let_ty_span(&self, pat_span: Span) -> Option<Span> {
+        self.let_tys.get(&pat_span).copied()
+    }
+
+    fn snippet(&self, ex: &hir::Expr) -> Option<String> {
+        self.tcx.sess.source_map().span_to_snippet(ex.span).ok()
+    }
+
+    /// Get the receiver and arguments of a method call like `a.f(b)` or a function call like
+    /// `f(a, b)`.
+    fn call_args(&self, ex: &'tcx hir::Expr<'tcx>) -> Option<&'tcx [hir::Expr<'tcx>]> {
+        match ex.kind {
+            hir::ExprKind::MethodCall(_, args, _) => Some(args),
+            hir::ExprKind::Call(_, args) => Some(args),
+            _ => None,
+        }
+    }
+
+    /// Remove a cast like `p as *mut c_void` from `ex`.
+    fn strip_cast<'a>(&self, ex: &'a hir::Expr<'a>) -> &'a hir::Expr<'a> {
+        match ex.kind {
+            hir::ExprKind::Cast(inner, _) => inner,
+            _ => ex,
+        }
+    }
+
+    /// Find the cast of an allocation call at `call_span` to `*mut T`, and return the cast
+    /// expression and the source text of `T`.
+    fn alloc_cast(&self, call_span: Span) -> Option<(&'tcx hir::Expr<'tcx>, String)> {
+        let cast = self.parents.get(&call_span)?;
+        let ty = match cast.kind {
+            hir::ExprKind::Cast(_, ty) => ty,
+            _ => return None,
+        };
+        let pointee = match ty.kind {
+            hir::TyKind::Ptr(ref mt) => mt.ty,
+            _ => return None,
+        };
+        let pointee = self
+            .tcx
+            .sess
+            .source_map()
+            .span_to_snippet(pointee.span)
+            .ok()?;
+        Some((cast, pointee))
+    }
+
+    /// Render a `Reborrow` of the operand of an assignment like `_2 = copy _1`.  The statement's
+    /// span covers either the operand itself, as in `let q = p` or `f(p)`, or the whole `q = p`.
+    fn render_reborrow(&self, rw: &ExprRewrite, ex: &'tcx hir::Expr<'tcx>) -> Option<TextEdit> {
+        if rw.loc.sub != [SubLoc::AssignRvalue, SubLoc::RvalueOperand(0)] {
+            return None;
+        }
+        let operand = match ex.kind {
+            hir::ExprKind::Assign(_, rhs, _) => rhs,
+            _ => ex,
+        };
+        let text = format!("&mut *{}", self.snippet(operand)?);
+        Some(TextEdit::new(operand.span, text))
+    }
+
+    /// Render an `OutParamWrite` of the statement `ex`, which must be an assignment like
+    /// `*out = value`.
+    fn render_out_param_write(&self, rw: &ExprRewrite, ex: &hir::Expr) -> Option<TextEdit> {
+        if rw.loc.sub != [SubLoc::Dest] {
+            return None;
+        }
+        let lhs = match ex.kind {
+            hir::ExprKind::Assign(lhs, _, _) => lhs,
+            _ => return None,
+        };
+        match lhs.kind {
+            hir::ExprKind::Unary(hir::UnOp::Deref, out) => {
+                Some(TextEdit::new(lhs.span, self.snippet(out)?))
+            }
+            _ => None,
+        }
+    }
+
+    /// Render an `OutParamCall` of the call `ex`.  The out-parameter arguments at indices `params`
+    /// are removed, and their values are stored through the pointers that were passed for them.
+    fn render_out_param_call(&self, ex: &hir::Expr, params: &[usize]) -> Option<TextEdit> {
+        let (callee, args) = match ex.kind {
+            hir::ExprKind::Call(callee, args) => (callee, args),
+            _ => return None,
+        };
+        let kept = args
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !params.contains(i))
+            .map(|(_, arg)| self.snippet(arg))
+            .collect::<Option<Vec<_>>>()?;
+        let outs = params
+            .iter()
+            .map(|i| format!("__out{}", i))
+            .collect::<Vec<_>>();
+        let mut stores = String::new();
+        for (&i, out) in params.iter().zip(&outs) {
+            let arg = args.get(i)?;
+            let store = match self.strip_cast(arg).kind {
+                hir::ExprKind::AddrOf(hir::BorrowKind::Ref, hir::Mutability::Mut, place) => {
+                    format!("{} = {};", self.snippet(place)?, out)
+                }
+                hir::ExprKind::Path(..) => format!("*{} = {};", self.snippet(arg)?, out),
+                _ => format!("*({}) = {};", self.snippet(arg)?, out),
+            };
+            stores.push_str(&store);
+            stores.push(' ');
+        }
+        let returns_unit = self.tcx.typeck(self.ldid).expr_ty(ex).is_unit();
+        let ret = (!returns_unit).then(|| "__ret".to_owned());
+        let text = format!(
+            "{{ let {} = {}({}); {}{}}}",
+            out_params_value(ret.clone(), &outs),
+            self.snippet(callee)?,
+            kept.join(", "),
+            stores,
+            ret.map_or_else(String::new, |ret| ret + " "),
+        );
+        Some(TextEdit::new(ex.span, text))
+    }
+
+    /// Render a `ReturnOutParams`, which returns the values of the out-parameters at indices
+    /// `params` along with the function's result.  The parameters become locals declared at the
+    /// start of the body, and each `return` and the body's final value are extended with them.
+    fn render_return_out_params(&self, params: &[usize]) -> Option<Vec<TextEdit>> {
+        let names = params
+            .iter()
+            .map(|&i| match self.body.params.get(i)?.pat.kind {
+                hir::PatKind::Binding(_, _, ident, None) => Some(ident.to_string()),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let block = match self.body.value.kind {
+            hir::ExprKind::Block(block, _) => block,
+            _ => return None,
+        };
+        let sm = self.tcx.sess.source_map();
+        let indent = |span: Span| " ".repeat(sm.lookup_char_pos(span.lo()).col.0);
+        let first = block
+            .stmts
+            .first()
+            .map(|s| s.span)
+            .or_else(|| block.expr.map(|ex| ex.span))?;
+        let returns_unit = self
+            .tcx
+            .fn_sig(self.ldid.to_def_id())
+            .skip_binder()
+            .output()
+            .is_unit();
+
+        let mut edits = Vec::new();
+        let decls = names
+            .iter()
+            .map(|name| format!("\n{}let mut {};", indent(first), name))
+            .collect::<String>();
+        let open = block.span.lo() + BytePos(1);
+        edits.push(TextEdit::new(block.span.with_lo(open).with_hi(open), decls));
+
+        let mut returns = Returns { rets: Vec::new() };
+        intravisit::walk_body(&mut returns, self.body);
+        for ex in returns.rets {
+            let edit = match ex.kind {
+                hir::ExprKind::Ret(Some(val)) if returns_unit => TextEdit::new(
+                    ex.span,
+                    format!(
+                        "return {{ {}; {} }}",
+                        self.snippet(val)?,
+                        out_params_value(None, &names)
+                    ),
+                ),
+                hir::ExprKind::Ret(Some(val)) => {
+                    let text = out_params_value(Some(self.snippet(val)?), &names);
+                    TextEdit::new(val.span, text)
+                }
+                _ => TextEdit::new(
+                    ex.span,
+                    format!("return {}", out_params_value(None, &names)),
+                ),
+            };
+            edits.push(edit);
+        }
+
+        let is_ret = |ex: &hir::Expr| matches!(ex.kind, hir::ExprKind::Ret(..));
+        match block.expr {
+            Some(tail) if is_ret(tail) => {}
+            Some(tail) if returns_unit => {
+                let text = format!(";\n{}{}", indent(tail.span), out_params_value(None, &names));
+                edits.push(TextEdit::new(tail.span.shrink_to_hi(), text));
+            }
+            Some(tail) => {
+                let text = out_params_value(Some(self.snippet(tail)?), &names);
+                edits.push(TextEdit::new(tail.span, text));
+            }
+            // Without a final value, the end of a function that returns something is unreachable.
+            None if !returns_unit => {}
+            None => {
+                let last = block.stmts.last()?;
+                let ends_in_return = match last.kind {
+                    hir::StmtKind::Expr(ex) | hir::StmtKind::Semi(ex) => is_ret(ex),
+                    _ => false,
+                };
+                if !ends_in_return {
+                    let text = format!("\n{}{}", indent(last.span), out_params_value(None, &names));
+                    edits.push(TextEdit::new(last.span.shrink_to_hi(), text));
+                }
+            }
+        }
+        Some(edits)
+    }
+
+    /// Render a rewrite of a whole MIR statement or terminator as an edit of the source.  Returns
+    /// `None` if `rw` can't be applied to the source text.
+    pub fn render(&self, rw: &ExprRewrite) -> Option<TextEdit> {
+        let kind = match *rw.kinds {
+            [ref kind] => kind,
+            _ => return None,
+        };
+        let span = rw.loc.span;
+        let ex = *self.exprs.get(&span)?;
+        match *kind {
+            RewriteKind::Reborrow => return self.render_reborrow(rw, ex),
+            RewriteKind::OutParamWrite => return self.render_out_param_write(rw, ex),
+            _ => {}
+        }
+        if !rw.loc.sub.is_empty() {
+            return None;
+        }
+        let mkmut = |mutbl: bool| if mutbl { "&mut " } else { "&" };
+
+        let edit = match *kind {
+            RewriteKind::OffsetSlice { mutbl } | RewriteKind::OffsetIndex { mutbl } => {
+                let args = self.call_args(ex)?;
+                let (base, offset) = (self.snippet(&args[0])?, self.snippet(&args[1])?);
+                let range = if matches!(kind, RewriteKind::OffsetSlice { .. }) {
+                    ".."
+                } else {
+                    ""
+                };
+                let text = format!("{}{}[({}) as usize{}]", mkmut(mutbl), base, offset, range);
+                TextEdit::new(span, text)
+            }
+            RewriteKind::RemoveAsPtr | RewriteKind::RemoveCStrConversion => {
+                let args = self.call_args(ex)?;
+                TextEdit::new(span, self.snippet(&args[0])?)
+            }
+            RewriteKind::CStrBorrow => {
+                let args = self.call_args(ex)?;
+                TextEdit::new(span, format!("&*{}", self.snippet(&args[0])?))
+            }
+            RewriteKind::RemoveCast => TextEdit::new(span, self.snippet(self.strip_cast(ex))?),
+            RewriteKind::IsNullToIsNone => {
+                let args = self.call_args(ex)?;
+                TextEdit::new(span, format!("{}.is_none()", self.snippet(&args[0])?))
+            }
+            RewriteKind::NullToNone => TextEdit::new(span, "None"),
+            RewriteKind::FreeToDrop => {
+                let args = self.call_args(ex)?;
+                let ptr = self.snippet(self.strip_cast(&args[0]))?;
+                TextEdit::new(span, format!("drop({})", ptr))
+            }
+            RewriteKind::AllocToBox => {
+                let (cast, ty) = self.alloc_cast(span)?;
+                TextEdit::new(cast.span, format!("Box::new(<{}>::default())", ty))
+            }
+            RewriteKind::AllocToBoxSlice | RewriteKind::AllocToVec => {
+                let (cast, ty) = self.alloc_cast(span)?;
+                let args = self.call_args(ex)?;
+                let len = match *args {
+                    // `calloc(n, size)`
+                    [ref n, _] => format!("({}) as usize", self.snippet(n)?),
+                    // `malloc(size)`
+                    [ref size] => format!(
+                        "({}) as usize / std::mem::size_of::<{}>()",
+                        self.snippet(size)?,
+                        ty
+                    ),
+                    _ => return None,
+                };
+                let mut text = format!("vec![<{}>::default(); {}]", ty, len);
+                if matches!(kind, RewriteKind::AllocToBoxSlice) {
+                    text.push_str(".into_boxed_slice()");
+                }
+                TextEdit::new(cast.span, text)
+            }
+            RewriteKind::AllocToRc => {
+                let (cast, ty) = self.alloc_cast(span)?;
+                let text = format!(
+                    "std::rc::Rc::new(std::cell::RefCell::new(<{}>::default()))",
+                    ty
+                );
+                TextEdit::new(cast.span, text)
+            }
+            RewriteKind::ReallocToResize => {
+                let (cast, ty) = self.alloc_cast(span)?;
+                let assign = self.parents.get(&cast.span)?;
+                let lhs = match assign.kind {
+                    hir::ExprKind::Assign(lhs, _, _) => lhs,
+                    _ => return None,
+                };
+                let args = self.call_args(ex)?;
+                let text = format!(
+                    "{}.resize(({}) as usize / std::mem::size_of::<{}>(), <{}>::default())",
+                    self.snippet(lhs)?,
+                    self.snippet(args.get(1)?)?,
+                    ty,
+                    ty
+                );
+                TextEdit::new(assign.span, text)
+            }
+            RewriteKind::OutParamCall { ref params } => {
+                return self.render_out_param_call(ex, params);
+            }
+            _ => return None,
+        };
+        Some(edit)
+    }
+
+    /// Render all of `rws`, the rewrites of one function body.  If one of them can't be applied
+    /// to the source text, it's returned instead, since the function's other edits wouldn't
+    /// compile without it.  Conversions Rust makes implicitly, like `&mut T` to `&T`, can be left
+    /// out.
+    pub fn render_all<'a>(
+        &self,
+        rws: impl IntoIterator<Item = &'a ExprRewrite>,
+    ) -> Result<Vec<TextEdit>, &'a ExprRewrite> {
+        let mut edits = Vec::new();
+        for rw in rws {
+            // `ReturnOutParams` changes every `return` in the body, so it's rendered separately.
+            if let [RewriteKind::ReturnOutParams { ref params }] = *rw.kinds {
+                edits.extend(self.render_return_out_params(params).ok_or(rw)?);
+                continue;
+            }
+            match self.render(rw) {
+                Some(edit) => edits.push(edit),
+                None if rw.kinds.iter().all(|k| *k == RewriteKind::MutToImm) => {}
+                None => return Err(rw),
+            }
+        }
+        Ok(edits)
+    }
+}
+
+/// Collects the `return` expressions of a body, not including those of closures inside it.
+struct Returns<'tcx> {
+    rets: Vec<&'tcx hir::Expr<'tcx>>,
+}
+
+impl<'tcx> Visitor<'tcx> for Returns<'tcx> {
+    fn visit_expr(&mut self, ex: &'tcx hir::Expr<'tcx>) {
+        if let hir::ExprKind::Ret(..) = ex.kind {
+            self.rets.push(ex);
+        }
+        intravisit::walk_expr(self, ex);
+    }
+}
+
+/// Build the value a function with out-parameters returns: its original result `ret`, if it has
+/// one, followed by the values of the out-parameters `outs`.  This has the type
+/// [`crate::out_params::rewritten_output`] computes.
+fn out_params_value(ret: Option<String>, outs: &[String]) -> String {
+    let vals = ret
+        .into_iter()
+        .chain(outs.iter().cloned())
+        .collect::<Vec<_>>();
+    match *vals {
+        [ref val] => val.clone(),
+        _ => format!("({})", vals.join(", ")),
+    }
+}
+
+/// Print the types of the values stored through the out-parameters at indices `out_params`.
+fn out_param_tys<'tcx>(
+    printer: &TypePrinter<'_, 'tcx>,
+    lsig: LFnSig<'tcx>,
+    out_params: &[usize],
+    name: &dyn Fn(PointerId) -> Option<String>,
+    behind: &dyn Fn(PointerId) -> Option<String>,
+) -> Vec<String> {
+    out_params
+        .iter()
+        .map(|&i| printer.print_with(lsig.inputs[i].args[0], name, behind, None))
+        .collect()
+}
+
+/// Build the edits that remove the parameters at indices `removed` from `params`, along with the
+/// commas that separate them from the rest.
+fn remove_params(params: &[hir::Param], removed: &[usize]) -> Vec<TextEdit> {
+    let first_kept = (0..params.len()).find(|i| !removed.contains(i));
+    let mut edits = Vec::new();
+    for &i in removed {
+        let span = match first_kept {
+            // Leading parameters are removed together, up to the first one that's kept.
+            Some(k) if i < k => match i {
+                0 => params[0].span.with_hi(params[k].span.lo()),
+                _ => continue,
+            },
+            Some(_) => params[i].span.with_lo(params[i - 1].span.hi()),
+            None => match i {
+                0 => params[0].span.to(params[params.len() - 1].span),
+                _ => continue,
+            },
+        };
+        edits.push(TextEdit::new(span, ""));
+    }
+    edits
+}
+
+/// Build an edit that adds the lifetime parameters `lifetimes` to `generics`.
+pub fn add_lifetime_params(generics: &hir::Generics, lifetimes: &[String]) -> Option<TextEdit> {
+    if lifetimes.is_empty() {
+        return None;
+    }
+    let span = generics.span;
+    let has_params = generics.params.iter().any(|p| span.contains(p.span));
+    let edit = if has_params {
+        // Lifetime parameters must come first, so insert them right after the `<`.
+        let pos = span.lo() + BytePos(1);
+        TextEdit::new(
+            span.with_lo(pos).with_hi(pos),
+            format!("{}, ", lifetimes.join(", ")),
+        )
+    } else {
+        TextEdit::new(span.shrink_to_hi(), format!("<{}>", lifetimes.join(", ")))
+    };
+    Some(edit)
+}
+
+/// Build an edit that replaces the type at `span` with `new_ty`, unless the rewritten type is the
+/// same as the original type `old_ty`.
+fn type_edit(span: Span, old_ty: Ty, new_ty: String) -> Option<TextEdit> {
+    (new_ty != format!("{:?}", old_ty)).then(|| TextEdit::new(span, new_ty))
+}
+
+/// Build the edits that rewrite the field types of struct `ldid` and add its new lifetime
+/// parameter.
+pub fn struct_edits<'tcx>(
+    gacx: &GlobalAnalysisCtxt<'tcx>,
+    printer: &TypePrinter<'_, 'tcx>,
+    struct_lifetimes: &HashMap<DefId, String>,
+    planner: &mut Planner,
+    alternatives: Option<&Alternatives<'tcx>>,
+    ldid: LocalDefId,
+) -> Vec<TextEdit> {
+    let tcx = gacx.tcx;
+    let item = tcx.hir().expect_item(ldid);
+    let (variant, generics) = match item.kind {
+        hir::ItemKind::Struct(ref variant, ref generics) => (variant, generics),
+        _ => return Vec::new(),
+    };
+    let new_lt = struct_lifetimes.get(&ldid.to_def_id()).map(|s| s.as_str());
+
+    let mut edits = Vec::new();
+    for field in variant.fields() {
+        let field_did = tcx.hir().local_def_id(field.hir_id).to_def_id();
+        let lty = match gacx.field_tys.get(&field_did) {
+            Some(&lty) => lty,
+            None => continue,
+        };
+        let name = |_| new_lt.map(|s| s.to_owned());
+        let new_ty = printer.print(lty, &name, new_lt);
+        let alts =
+            alternatives.map_or_else(Vec::new, |a| a.for_type(printer, lty, &name, &name, new_lt));
+        let site = Site {
+            item: field_did,
+            position: "field".to_owned(),
+            lty,
+        };
+        let new_ty = planner.propose(tcx, site, field.ty.span, new_ty, alts);
+        edits.extend(new_ty.map(|ty| TextEdit::new(field.ty.span, ty)));
+    }
+    edits.extend(add_lifetime_params(
+        generics,
+        &new_lt.map(|s| s.to_owned()).into_iter().collect::<Vec<_>>(),
+    ));
+    edits
+}
+
+/// Build the edits that rewrite the signature of function `ldid` and add its new lifetime
+/// parameters.  The out-parameters at indices `out_params` are removed, and their values added to
+/// the return type.
+#[allow(clippy::too_many_arguments)]
+pub fn fn_sig_edits<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    printer: &TypePrinter<'_, 'tcx>,
+    ldid: LocalDefId,
+    lsig: LFnSig<'tcx>,
+    lifetimes: &FnLifetimes,
+    planner: &mut Planner,
+    alternatives: Option<&Alternatives<'tcx>>,
+    out_params: &[usize],
+) -> Vec<TextEdit> {
+    let hir_id = tcx.hir().local_def_id_to_hir_id(ldid);
+    let decl = match tcx.hir().fn_decl_by_hir_id(hir_id) {
+        Some(decl) => decl,
+        None => return Vec::new(),
+    };
+    let name = |ptr| lifetimes.name(ptr);
+    let behind = |ptr| lifetimes.behind_name(ptr);
+
+    let item = ldid.to_def_id();
+    let mut edits = Vec::new();
+    let mut propose = |position: String, lty: LTy<'tcx>, span: Span, adt_name: Option<&str>| {
+        let new_ty = printer.print_with(lty, &name, &behind, adt_name);
+        let alts = alternatives.map_or_else(Vec::new, |a| {
+            a.for_type(printer, lty, &name, &behind, adt_name)
+        });
+        let site = Site {
+            item,
+            position,
+            lty,
+        };
+        let new_ty = planner.propose(tcx, site, span, new_ty, alts);
+        edits.extend(new_ty.map(|ty| TextEdit::new(span, ty)));
+    };
+    for (i, (hir_ty, &lty)) in decl.inputs.iter().zip(lsig.inputs).enumerate() {
+        if !out_params.contains(&i) {
+            propose(format!("input {}", i), lty, hir_ty.span, None);
+        }
+    }
+    let adt_name = lifetimes.output_adt_name();
+    match decl.output {
+        hir::FnRetTy::Return(hir_ty) if out_params.is_empty() => {
+            propose("output".to_owned(), lsig.output, hir_ty.span, adt_name);
+        }
+        _ if out_params.is_empty() => {}
+        hir::FnRetTy::Return(hir_ty) => {
+            let ret = printer.print_with(lsig.output, &name, &behind, adt_name);
+            let outs = out_param_tys(printer, lsig, out_params, &name, &behind);
+            edits.push(TextEdit::new(
+                hir_ty.span,
+                out_params_value(Some(ret), &outs),
+            ));
+        }
+        hir::FnRetTy::DefaultReturn(span) => {
+            let outs = out_param_tys(printer, lsig, out_params, &name, &behind);
+            let text = format!("-> {} ", out_params_value(None, &outs));
+            edits.push(TextEdit::new(span, text));
+        }
+    }
+    let body = tcx.hir().body(tcx.hir().body_owned_by(ldid));
+    edits.extend(remove_params(body.params, out_params));
+    if let Some(generics) = tcx.hir().get_generics(ldid) {
+        edits.extend(add_lifetime_params(generics, &lifetimes.params));
+    }
+    edits
+}
+
+/// Build the edits that rewrite the explicit types of the `let` statements in function `ldid`.
+pub fn let_ty_edits<'tcx>(
+    exprs: &BodyExprs<'tcx>,
+    acx: &AnalysisCtxt<'_, 'tcx>,
+    asn: &Assignment,
+    mir: &Body<'tcx>,
+) -> Vec<TextEdit> {
+    let mut edits = Vec::new();
+    for (local, decl) in mir.local_decls.iter_enumerated() {
+        let pat_span = match decl.local_info.as_deref() {
+            Some(LocalInfo::User(ClearCrossCrate::Set(BindingForm::Var(v)))) => v.pat_span,
+            _ => continue,
+        };
+        let ty_span = match exprs.let_ty_span(pat_span) {
+            Some(span) => span,
+            None => continue,
+        };
+        let new_ty = type_desc::convert_type(acx, acx.local_tys[local], asn);
+        edits.extend(type_edit(ty_span, decl.ty, format!("{:?}", new_ty)));
+    }
+    edits
+}
+
+/// An edit as a byte range in a file's text and its replacement.
+type ByteEdit = (usize, usize, String);
+
+/// The edits for one file, and its original text.
+struct FileEdits {
+    src: String,
+    /// Edits as byte ranges in `src`, sorted by start position.
+    edits: Vec<ByteEdit>,
+}
+
+/// Group `edits` by file.  Edits in macro expansions, in files that aren't on disk, and edits that
+/// overlap an earlier edit are dropped.
+fn group_by_file(tcx: TyCtxt, edits: &[TextEdit]) -> BTreeMap<PathBuf, FileEdits> {
+    let sm = tcx.sess.source_map();
+    let mut files = BTreeMap::<PathBuf, FileEdits>::new();
+    for edit in edits {
+        if edit.span.from_expansion() {
+            continue;
+        }
+        let lo = sm.lookup_byte_offset(edit.span.lo());
+        let hi = sm.lookup_byte_offset(edit.span.hi());
+        let sf = lo.sf;
+        let path = match sf.name {
+            FileName::Real(ref name) => match name.local_path() {
+                Some(path) => path.to_owned(),
+                None => continue,
+            },
+            _ => continue,
+        };
+        let src = match sf.src {
+            Some(ref src) => src,
+            None => continue,
+        };
+        let file = files.entry(path).or_insert_with(|| FileEdits {
+            src: String::clone(src),
+            edits: Vec::new(),
+        });
+        file.edits
+            .push((lo.pos.0 as usize, hi.pos.0 as usize, edit.text.clone()));
+    }
+
+    for (path, file) in &mut files {
+        file.edits.sort_by_key(|&(lo, hi, _)| (lo, hi));
+        let mut end = 0;
+        file.edits.retain(|&(lo, hi, ref text)| {
+            if lo < end {
+                eprintln!(
+                    "warning: dropping overlapping rewrite at {}:{}: {:?}",
+                    path.display(),
+                    lo,
+                    text
+                );
+                return false;
+            }
+            end = hi;
+            true
+        });
+    }
+    files
+}
+
+fn apply_edits(src: &str, edits: &[ByteEdit]) -> String {
+    let mut out = String::new();
+    let mut pos = 0;
+    for &(lo, hi, ref text) in edits {
+        out.push_str(&src[pos..lo]);
+        out.push_str(text);
+        pos = hi;
+    }
+    out.push_str(&src[pos..]);
+    out
+}
+
+/// A replacement of the lines `first..=last` of a file with `new_lines`.
+struct LineChange<'a> {
+    first: usize,
+    last: usize,
+    new_lines: Vec<String>,
+    edits: Vec<&'a ByteEdit>,
+}
+
+/// Produce a unified diff of the changes `edits` make to `src`.
+fn unified_diff(path: &Path, src: &str, edits: &[ByteEdit]) -> String {
+    // The start offset of each line, plus the end of the file.
+    let mut line_starts = vec![0];
+    line_starts.extend(src.match_indices('\n').map(|(i, _)| i + 1));
+    if *line_starts.last().unwrap() != src.len() {
+        line_starts.push(src.len());
+    }
+    let num_lines = line_starts.len() - 1;
+    let line_of = |pos: usize| match line_starts.binary_search(&pos) {
+        Ok(i) => i.min(num_lines.saturating_sub(1)),
+        Err(i) => i - 1,
+    };
+    let line = |i: usize| &src[line_starts[i]..line_starts[i + 1]];
+
+    // Group the edits by the lines they touch.  Edits that touch the same line are combined into
+    // a single change.
+    let mut changes: Vec<LineChange> = Vec::new();
+    for edit in edits {
+        let first = line_of(edit.0);
+        let last = line_of(edit.1.max(edit.0 + 1) - 1).max(first);
+        match changes.last_mut() {
+            Some(c) if first <= c.last => {
+                c.last = c.last.max(last);
+                c.edits.push(edit);
+            }
+            _ => changes.push(LineChange {
+                first,
+                last,
+                new_lines: Vec::new(),
+                edits: vec![edit],
+            }),
+        }
+    }
+    for c in &mut changes {
+        let base = line_starts[c.first];
+        let old_text = &src[base..line_starts[c.last + 1]];
+        let rel_edits = c
+            .edits
+            .iter()
+            .map(|&&(lo, hi, ref text)| (lo - base, hi - base, text.clone()))
+            .collect::<Vec<_>>();
+        let new_text = apply_edits(old_text, &rel_edits);
+        c.new_lines = new_text
+            .split_inclusive('\n')
+            .map(|l| l.to_owned())
+            .collect();
+    }
+
+    // Group the changes into hunks, merging changes whose context would overlap.
+    let mut hunks: Vec<Vec<LineChange>> = Vec::new();
+    for c in changes {
+        match hunks.last_mut() {
+            Some(h) if c.first <= h.last().unwrap().last + 2 * DIFF_CONTEXT + 1 => h.push(c),
+            _ => hunks.push(vec![c]),
+        }
+    }
+
+    let mut out = format!("--- a/{}\n+++ b/{}\n", path.display(), path.display());
+    let push_line = |out: &mut String, prefix: char, l: &str| {
+        out.push(prefix);
+        out.push_str(l);
+        if !l.ends_with('\n') {
+            out.push_str("\n\\ No newline at end of file\n");
+        }
+    };
+    let mut line_delta: isize = 0;
+    for hunk in hunks {
+        let start = hunk[0].first.saturating_sub(DIFF_CONTEXT);
+        let end = (hunk.last().unwrap().last + 1 + DIFF_CONTEXT).min(num_lines);
+        let old_len = end - start;
+        let hunk_delta = hunk
+            .iter()
+            .map(|c| c.new_lines.len() as isize - (c.last + 1 - c.first) as isize)
+            .sum::<isize>();
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            start + 1,
+            old_len,
+            start as isize + line_delta + 1,
+            old_len as isize + hunk_delta,
+        ));
+
+        let mut pos = start;
+        for c in &hunk {
+            for i in pos..c.first {
+                push_line(&mut out, ' ', line(i));
+            }
+            for i in c.first..=c.last {
+                push_line(&mut out, '-', line(i));
+            }
+            for l in &c.new_lines {
+                push_line(&mut out, '+', l);
+            }
+            pos = c.last + 1;
+        }
+        for i in pos..end {
+            push_line(&mut out, ' ', line(i));
+        }
+        line_delta += hunk_delta;
+    }
+    out
+}
+
+/// How to output the rewritten source files.
+#[derive(Clone, Debug, Default)]
+pub struct OutputMode {
+    /// Overwrite the original files.
+    pub in_place: bool,
+    /// Print a unified diff of the changes to stdout.
+    pub diff: bool,
+    /// Write the rewritten files to this directory, keeping their paths relative to the current
+    /// directory.
+    pub output_dir: Option<PathBuf>,
+}
+
+impl OutputMode {
+    pub fn is_enabled(&self) -> bool {
+        self.in_place || self.diff || self.output_dir.is_some()
+    }
+}
+
+/// An edit to a source file, replacing the bytes from `lo` to `hi` with `text`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct SourceEdit {
+    pub path: PathBuf,
+    pub lo: usize,
+    pub hi: usize,
+    pub text: String,
+}
+
+/// Resolve `edits` to byte offsets in their files, dropping overlapping edits and edits in macro
+/// expansions, as [`apply`] does.
+pub fn source_edits(tcx: TyCtxt, edits: &[TextEdit]) -> Vec<SourceEdit> {
+    let mut out = Vec::new();
+    for (path, file) in group_by_file(tcx, edits) {
+        for (lo, hi, text) in file.edits {
+            out.push(SourceEdit {
+                path: path.clone(),
+                lo,
+                hi,
+                text,
+            });
+        }
+    }
+    out
+}
+
+/// Apply `edits` to the source files, and output the results as requested by `mode`.
+pub fn apply(tcx: TyCtxt, edits: &[TextEdit], mode: &OutputMode) {
+    write_files(&group_by_file(tcx, edits), mode);
+}
+
+/// Apply `edits`, which were resolved by [`source_edits`], to the source files as they are on
+/// disk, and output the results as requested by `mode`.
+pub fn apply_source_edits(edits: &[SourceEdit], mode: &OutputMode) {
+    let mut files = BTreeMap::<PathBuf, FileEdits>::new();
+    for edit in edits {
+        let file = files.entry(edit.path.clone()).or_insert_with(|| FileEdits {
+            src: fs::read_to_string(&edit.path)
+                .unwrap_or_else(|e| panic!("failed to read {}: {}", edit.path.display(), e)),
+            edits: Vec::new(),
+        });
+        file.edits.push((edit.lo, edit.hi, edit.text.clone()));
+    }
+    for file in files.values_mut() {
+        file.edits.sort_by_key(|&(lo, hi, _)| (lo, hi));
+    }
+    write_files(&files, mode);
+}
+
+fn write_files(files: &BTreeMap<PathBuf, FileEdits>, mode: &OutputMode) {
+    for (path, file) in files {
+        if mode.diff {
+            print!("{}", unified_diff(path, &file.src, &file.edits));
+        }
+
+        let new_src = apply_edits(&file.src, &file.edits);
+        if mode.in_place {
+            fs::write(path, &new_src)
+                .unwrap_or_else(|e| panic!("failed to write {}: {}", path.display(), e));
+        }
+        if let Some(ref dir) = mode.output_dir {
+            let rel_path = path
+                .strip_prefix(std::env::current_dir().unwrap())
+                .unwrap_or(path);
+            let rel_path = rel_path.strip_prefix("/").unwrap_or(rel_path);
+            let out_path = dir.join(rel_path);
+            if let Some(parent) = out_path.parent() {
+                fs::create_dir_all(parent)
+                    .unwrap_or_else(|e| panic!("failed to create {}: {}", parent.display(), e));
+            }
+            fs::write(&out_path, &new_src)
+                .unwrap_or_else(|e| panic!("failed to write {}: {}", out_path.display(), e));
+        }
+    }
+    eprintln!(
+        "applied {} rewrites to {} files",
+        files.values().map(|f| f.edits.len()).sum::<usize>(),
+        files.len()
+    );
+}
@@ -0,0 +1,209 @@
+//! Rewrite plans, for reviewing type rewrites one at a time.
+//!
+//! `--rewrite-plan-out FILE` writes a JSON list of the proposed rewrites of the pointer types in
+//! function signatures and struct fields, giving the pointers involved, the span of the type, and
+//! the old and new types.  The user can then edit the file and pass it back with
+//! `--rewrite-plan FILE`.  Setting an entry's `accept` to `false` keeps its pointers raw, as if
+//! the function or field were marked `#[c2rust::keep_raw]`, so the rest of the analysis adapts
+//! to the vetoed rewrite.  Changing an entry's `new_type` replaces the proposed type with the
+//! given text, which is useful for picking a different but compatible type; the rest of the
+//! rewrites are not adjusted to match.
+//!
+//! Entries are matched to signatures and fields by item path and position.  If the pointers listed
+//! in an entry don't match the current ones, usually because the source has changed since the plan
+//! was written, the entry is ignored with a warning.
+use crate::alternatives::Alternative;
+use crate::context::{FlagSet, GlobalAnalysisCtxt, GlobalAssignment, LTy};
+use rustc_hir::def_id::DefId;
+use rustc_middle::ty::TyCtxt;
+use rustc_span::Span;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlanEntry {
+    /// The path of the function or field.
+    pub item: String,
+    /// The position of the type within the item: `input N`, `output`, or `field`.
+    pub position: String,
+    /// The pointers in the type, in the order they're visited by
+    /// [`LabeledTyS::iter`](crate::labeled_ty::LabeledTyS::iter).  This is used to detect stale
+    /// entries, and may be left empty in hand-written entries to skip the check.
+    #[serde(default)]
+    pub pointers: Vec<String>,
+    /// The location of the type in the source, for human readers.
+    #[serde(default)]
+    pub span: String,
+    /// The original type, for human readers.
+    #[serde(default)]
+    pub old_type: String,
+    /// The type to rewrite to.
+    pub new_type: String,
+    /// Whether to apply the rewrite.
+    pub accept: bool,
+    /// Other types the rewrite could use, best first, for `--alternatives`.  See
+    /// [`crate::alternatives`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alternatives: Vec<Alternative>,
+}
+
+/// A pointer type in a signature or field that can be rewritten.
+pub struct Site<'tcx> {
+    pub item: DefId,
+    pub position: String,
+    pub lty: LTy<'tcx>,
+}
+
+/// Iterate over all the signature and field types that can have plan entries.
+pub fn sites<'a, 'tcx>(
+    gacx: &'a GlobalAnalysisCtxt<'tcx>,
+) -> impl Iterator<Item = Site<'tcx>> + 'a {
+    let sig_sites = gacx.fn_sigs.iter().flat_map(|(&item, lsig)| {
+        let inputs = lsig.inputs.iter().enumerate().map(move |(i, &lty)| Site {
+            item,
+            position: format!("input {}", i),
+            lty,
+        });
+        inputs.chain(Some(Site {
+            item,
+            position: "output".to_owned(),
+            lty: lsig.output,
+        }))
+    });
+    let field_sites = gacx.field_tys.iter().map(|(&item, &lty)| Site {
+        item,
+        position: "field".to_owned(),
+        lty,
+    });
+    sig_sites.chain(field_sites)
+}
+
+fn pointer_names(lty: LTy) -> Vec<String> {
+    lty.iter()
+        .map(|lty| lty.label)
+        .filter(|ptr| !ptr.is_none())
+        .map(|ptr| format!("{:?}", ptr))
+        .collect()
+}
+
+/// Reads the overrides from an edited plan, and collects the entries of a new one.
+#[derive(Debug, Default)]
+pub struct Planner {
+    /// Entries of the plan passed with `--rewrite-plan`, by item path and position.
+    overrides: HashMap<(String, String), PlanEntry>,
+    /// Entries of the plan being built.
+    entries: Vec<PlanEntry>,
+}
+
+impl Planner {
+    pub fn load(path: Option<&Path>) -> Planner {
+        let mut planner = Planner::default();
+        let path = match path {
+            Some(x) => x,
+            None => return planner,
+        };
+        let file =
+            File::open(path).unwrap_or_else(|e| panic!("failed to open {}: {}", path.display(), e));
+        let entries: Vec<PlanEntry> = serde_json::from_reader(BufReader::new(file))
+            .unwrap_or_else(|e| panic!("failed to parse {}: {}", path.display(), e));
+        for entry in entries {
+            let key = (entry.item.clone(), entry.position.clone());
+            planner.overrides.insert(key, entry);
+        }
+        planner
+    }
+
+    /// Get the override for the type `lty` at `position` in `item`, if there is one and it's
+    /// up to date.
+    fn get_override(
+        &self,
+        tcx: TyCtxt,
+        item: DefId,
+        position: &str,
+        lty: LTy,
+    ) -> Option<&PlanEntry> {
+        let key = (tcx.def_path_str(item), position.to_owned());
+        let entry = self.overrides.get(&key)?;
+        if !entry.pointers.is_empty() && entry.pointers != pointer_names(lty) {
+            eprintln!(
+                "warning: ignoring stale rewrite plan entry for {} {}",
+                key.0, key.1
+            );
+            return None;
+        }
+        Some(entry)
+    }
+
+    /// Mark the pointers of all the rejected plan entries as `FIXED`.
+    pub fn apply_rejections(&self, gacx: &GlobalAnalysisCtxt, gasn: &mut GlobalAssignment) {
+        if self.overrides.is_empty() {
+            return;
+        }
+        for site in sites(gacx) {
+            let entry = match self.get_override(gacx.tcx, site.item, &site.position, site.lty) {
+                Some(x) => x,
+                None => continue,
+            };
+            if entry.accept {
+                continue;
+            }
+            for ptr in site.lty.iter().map(|lty| lty.label) {
+                if !ptr.is_none() {
+                    gasn.flags[ptr].insert(FlagSet::FIXED);
+                }
+            }
+        }
+    }
+
+    /// Record the proposed rewrite of the type `lty` at `span` to `new_type`, along with its
+    /// `alternatives`, and return the type to actually rewrite it to, or `None` to leave it
+    /// unchanged.
+    pub fn propose(
+        &mut self,
+        tcx: TyCtxt,
+        site: Site,
+        span: Span,
+        new_type: String,
+        alternatives: Vec<Alternative>,
+    ) -> Option<String> {
+        let old_type = format!("{:?}", site.lty.ty);
+        if let Some(entry) = self.get_override(tcx, site.item, &site.position, site.lty) {
+            // Keep the user's decision in the new plan.
+            let mut entry = entry.clone();
+            entry.alternatives = alternatives;
+            let accepted =
+                (entry.accept && entry.new_type != old_type).then(|| entry.new_type.clone());
+            self.entries.push(entry);
+            return accepted;
+        }
+        if new_type == old_type {
+            return None;
+        }
+        self.entries.push(PlanEntry {
+            item: tcx.def_path_str(site.item),
+            position: site.position,
+            pointers: pointer_names(site.lty),
+            span: tcx.sess.source_map().span_to_diagnostic_string(span),
+            old_type,
+            new_type: new_type.clone(),
+            accept: true,
+            alternatives,
+        });
+        Some(new_type)
+    }
+
+    /// Write the entries collected so far to `path`.
+    /// The entries of the plan being built.
+    pub fn into_entries(self) -> Vec<PlanEntry> {
+        self.entries
+    }
+
+    pub fn save(&self, path: &Path) {
+        let json = serde_json::to_string_pretty(&self.entries).unwrap();
+        fs::write(path, json)
+            .unwrap_or_else(|e| panic!("failed to write {}: {}", path.display(), e));
+    }
+}
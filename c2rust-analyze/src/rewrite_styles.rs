@@ -0,0 +1,220 @@
+//! Rewrite styles chosen by the user for particular pointers, for `--rewrite-styles FILE`.
+//!
+//! Every pointer normally gets the safest type its permissions allow, but a team migrating a
+//! crate often has its own plans for some data structures: arena indices rather than references
+//! for the nodes of a graph, say, or raw pointers for an intrusive list they'll restructure by
+//! hand.  The styles file is a JSON list of pins, each naming a pointer and the style of type its
+//! equivalence class must get:
+//!
+//! ```json
+//! [
+//!     { "item": "Node::next", "style": "indices" },
+//!     { "item": "push", "position": "input 0", "style": "references" },
+//!     { "span": "src/list.rs:12:13", "style": "raw" }
+//! ]
+//! ```
+//!
+//! A pointer is named either by the `item` and `position` of a signature or field type, as in the
+//! rewrite plan (see [`crate::rewrite_plan`]), with the position defaulting to `field`, or by the
+//! `file:line:column` where a local variable or parameter is declared.  The pin applies to the
+//! outermost pointer of the type, and so to every pointer in its equivalence class, since they
+//! all share one `PointerId` by the time the styles are applied.
+//!
+//! * `references`: the pointers are borrowed, and never become `Box` or `Vec`, even where the
+//!   analysis finds they own their target.  Any `#[c2rust::rc]` on them is dropped.  A pointer
+//!   the code frees stays raw, since a borrow can't free its target.
+//! * `rc`: shared heap pointers become `Rc<RefCell<T>>`, as with `#[c2rust::rc]`.
+//! * `raw`: the pointers stay raw, as with `#[c2rust::keep_raw]`.
+//! * `indices`: the pointers will become indices into an arena.  The analysis doesn't rewrite
+//!   pointers into indices itself, so they stay raw, leaving the data structure for the user to
+//!   convert instead of turning it into references that would have to be undone.
+//!
+//! Pins that don't match any pointer are ignored with a warning.
+use crate::context::{LTy, PointerId};
+use crate::rewrite_plan;
+use crate::GlobalAnalysisCtxt;
+use rustc_hir::def_id::LocalDefId;
+use rustc_index::vec::IndexVec;
+use rustc_middle::mir::{BindingForm, Body, Local, LocalInfo};
+use rustc_middle::ty::TyCtxt;
+use rustc_span::Span;
+use serde::Deserialize;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// A family of types to rewrite a pointer to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Style {
+    References,
+    Rc,
+    Raw,
+    Indices,
+}
+
+impl fmt::Display for Style {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            Style::References => "references",
+            Style::Rc => "rc",
+            Style::Raw => "raw",
+            Style::Indices => "indices",
+        };
+        f.write_str(s)
+    }
+}
+
+/// An entry of the styles file.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Pin {
+    /// The path of the function or field whose type contains the pointer.
+    #[serde(default)]
+    pub item: Option<String>,
+    /// The position of the type within the item: `input N`, `output`, or `field`.
+    #[serde(default)]
+    pub position: Option<String>,
+    /// The declaration of a local variable or parameter, as `file:line:column`.
+    #[serde(default)]
+    pub span: Option<String>,
+    pub style: Style,
+}
+
+impl Pin {
+    fn describe(&self) -> String {
+        match (&self.item, &self.span) {
+            (Some(item), _) => format!("{} {}", item, self.position()),
+            (None, Some(span)) => span.clone(),
+            (None, None) => "(no pointer)".to_owned(),
+        }
+    }
+
+    fn position(&self) -> &str {
+        self.position.as_deref().unwrap_or("field")
+    }
+
+    /// Check whether the declaration at `span` is the one named by this pin.
+    fn matches_span(&self, tcx: TyCtxt, span: Span) -> bool {
+        let pin = match self.span {
+            Some(ref x) => x,
+            None => return false,
+        };
+        let mut parts = pin.rsplitn(3, ':');
+        let (col, line, file) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(col), Some(line), Some(file)) => (col, line, file),
+            _ => return false,
+        };
+        let loc = tcx.sess.source_map().lookup_char_pos(span.lo());
+        let name = loc.file.name.prefer_local().to_string();
+        name.ends_with(file) && line == loc.line.to_string() && col == (loc.col.0 + 1).to_string()
+    }
+}
+
+/// A pointer whose equivalence class was pinned to a style.
+pub struct PinnedClass {
+    /// The function the pointer belongs to, if it's local.
+    pub func: Option<LocalDefId>,
+    pub ptr: PointerId,
+    pub style: Style,
+    /// The pin's name for the pointer.
+    pub name: String,
+}
+
+/// The pins read from `--rewrite-styles`.
+#[derive(Debug, Default)]
+pub struct RewriteStyles {
+    pins: Vec<Pin>,
+}
+
+impl RewriteStyles {
+    pub fn load(path: Option<&Path>) -> RewriteStyles {
+        let path = match path {
+            Some(x) => x,
+            None => return RewriteStyles::default(),
+        };
+        let file =
+            File::open(path).unwrap_or_else(|e| panic!("failed to open {}: {}", path.display(), e));
+        let pins = serde_json::from_reader(BufReader::new(file))
+            .unwrap_or_else(|e| panic!("failed to parse {}: {}", path.display(), e));
+        RewriteStyles { pins }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pins.is_empty()
+    }
+
+    /// Find the signature and field pointers named by the pins.  The pins naming local variables
+    /// are found by passing each function to [`Resolver::add_fn`].
+    pub fn resolver<'a, 'tcx>(&'a self, gacx: &GlobalAnalysisCtxt<'tcx>) -> Resolver<'a, 'tcx> {
+        let tcx = gacx.tcx;
+        let mut found = vec![None; self.pins.len()];
+        for site in rewrite_plan::sites(gacx) {
+            let item = tcx.def_path_str(site.item);
+            for (pin, found) in self.pins.iter().zip(&mut found) {
+                if pin.item.as_deref() == Some(&item) && pin.position() == site.position {
+                    *found = Some((None, site.lty.label));
+                }
+            }
+        }
+        Resolver {
+            tcx,
+            pins: &self.pins,
+            found,
+        }
+    }
+}
+
+/// The pointers found so far for each pin.
+pub struct Resolver<'a, 'tcx> {
+    tcx: TyCtxt<'tcx>,
+    pins: &'a [Pin],
+    found: Vec<Option<(Option<LocalDefId>, PointerId)>>,
+}
+
+impl<'a, 'tcx> Resolver<'a, 'tcx> {
+    /// Find the local variables of `ldid` named by the pins.
+    pub fn add_fn(
+        &mut self,
+        ldid: LocalDefId,
+        mir: &Body<'tcx>,
+        local_tys: &IndexVec<Local, LTy<'tcx>>,
+    ) {
+        for (local, decl) in mir.local_decls.iter_enumerated() {
+            let span = match decl.local_info.as_deref() {
+                Some(LocalInfo::User(binding_form)) => {
+                    match *binding_form.as_ref().assert_crate_local() {
+                        BindingForm::Var(ref v) => v.pat_span,
+                        _ => continue,
+                    }
+                }
+                _ => continue,
+            };
+            for (pin, found) in self.pins.iter().zip(&mut self.found) {
+                if pin.matches_span(self.tcx, span) {
+                    *found = Some((Some(ldid), local_tys[local].label));
+                }
+            }
+        }
+    }
+
+    /// The pointer named by each pin, warning about the pins that didn't match one.
+    pub fn finish(self) -> Vec<PinnedClass> {
+        let mut pinned = Vec::new();
+        for (pin, found) in self.pins.iter().zip(self.found) {
+            match found {
+                Some((func, ptr)) if !ptr.is_none() => pinned.push(PinnedClass {
+                    func: func.filter(|_| !ptr.is_global()),
+                    ptr,
+                    style: pin.style,
+                    name: pin.describe(),
+                }),
+                _ => eprintln!(
+                    "warning: rewrite style for {} doesn't match any pointer",
+                    pin.describe()
+                ),
+            }
+        }
+        pinned
+    }
+}
@@ -0,0 +1,472 @@
+//! Rewriting `static mut` items to safe equivalents.
+//!
+//! Every access to a `static mut` is unsafe, so removing them is a prerequisite for removing most
+//! `unsafe` blocks in transpiled code.  Each `static mut` is classified by how it's used across
+//! the crate, and rewritten to the simplest safe type that supports those uses:
+//!
+//! * A static that's never written becomes a plain `static`.
+//! * A static of a `Copy` type that's assigned in exactly one place, directly in `main` and
+//!   outside any loop, becomes a `once_cell::sync::OnceCell<T>`.  That write runs at most once, so
+//!   it becomes `set`, and reads copy the value out, falling back to the original initializer
+//!   until then.  A write anywhere else may run again, which would make `set` fail.
+//! * A static of integer or `bool` type that's written more than once becomes the corresponding
+//!   atomic type, such as `AtomicI32`.  Compound assignments in statement position become atomic
+//!   read-modify-write operations like `fetch_add`.
+//! * Any other static that's written more than once becomes a `Mutex<T>`, as long as no single
+//!   statement or tail expression accesses it twice, which would deadlock.
+//!
+//! Statics whose address is taken, whose types contain raw pointers (which aren't `Sync`), or
+//! that are visible to C code through `#[no_mangle]` stay `static mut`.  All atomic operations use
+//! `SeqCst` ordering, or `Relaxed` with `--assume=single-threaded`, since a single thread always
+//! sees its own writes in order.
+use crate::rewrite::TextEdit;
+use rustc_hir as hir;
+use rustc_hir::def::{DefKind, Res};
+use rustc_hir::def_id::{DefId, LocalDefId};
+use rustc_hir::intravisit::{self, Visitor};
+use rustc_hir::Mutability;
+use rustc_middle::hir::nested_filter;
+use rustc_middle::ty::{GenericArgKind, IntTy, ParamEnv, Ty, TyCtxt, TyKind, UintTy};
+use rustc_span::{sym, Span, DUMMY_SP};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+const SEQ_CST: &str = "std::sync::atomic::Ordering::SeqCst";
+const RELAXED: &str = "std::sync::atomic::Ordering::Relaxed";
+
+/// The safe replacement chosen for a `static mut`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StaticKind {
+    /// A plain `static`.
+    Immutable,
+    /// A `OnceCell<T>`, set by the static's only write.
+    OnceCell,
+    /// An atomic type, such as `AtomicI32`.
+    Atomic(&'static str),
+    /// A `Mutex<T>`.
+    Mutex,
+}
+
+/// A use of a `static mut` in some function body.
+#[derive(Clone, Copy, Debug)]
+enum Access {
+    /// A read of the static, or of a field or element of it, at the span of the path.
+    Read(Span),
+    /// An assignment `X = v`, with the spans of `X` and `v`.
+    Write(Span, Span),
+    /// A compound assignment `X op= v`, with the operator, the spans of `X` and `v`, and whether
+    /// it's a statement on its own.
+    Compound(hir::BinOpKind, Span, Span, bool),
+    /// An assignment to a field or element of the static.  The path itself is recorded as a
+    /// separate `Read`.
+    PartialWrite,
+}
+
+#[derive(Default)]
+struct StaticUses {
+    accesses: Vec<Access>,
+    /// Whether any write may run more than once, because it's inside a loop or in a function
+    /// other than `main`.
+    written_repeatedly: bool,
+    /// Whether the static's address is taken, or it's the receiver of a method call, which may
+    /// take its address implicitly.
+    addr_taken: bool,
+    /// Whether some statement or tail expression accesses the static more than once.
+    repeated_in_stmt: bool,
+}
+
+impl StaticUses {
+    fn writes(&self) -> Vec<Access> {
+        self.accesses
+            .iter()
+            .copied()
+            .filter(|a| !matches!(a, Access::Read(..)))
+            .collect()
+    }
+}
+
+/// The rewrite of one `static mut` item.
+pub struct StaticRewrite {
+    pub did: LocalDefId,
+    pub kind: StaticKind,
+    uses: StaticUses,
+    /// The ordering of atomic operations on the static.
+    ordering: &'static str,
+}
+
+struct UseCollector<'tcx> {
+    tcx: TyCtxt<'tcx>,
+    uses: HashMap<DefId, StaticUses>,
+    /// The crate's `main` function, if it has one.
+    entry_fn: Option<DefId>,
+    /// Whether the body being visited is that of `main`, rather than another function or a
+    /// closure.
+    in_entry_fn: bool,
+    loop_depth: usize,
+    /// The number of accesses to each static in the current statement.
+    stmt_accesses: Option<HashMap<DefId, usize>>,
+    /// Expressions that are statements on their own, like `x += 1;`.
+    stmt_exprs: HashSet<hir::HirId>,
+}
+
+impl<'tcx> UseCollector<'tcx> {
+    /// If `ex` is a path to a `static mut`, return its `DefId`.
+    fn static_mut(&self, ex: &hir::Expr) -> Option<DefId> {
+        match ex.kind {
+            hir::ExprKind::Path(hir::QPath::Resolved(None, path)) => match path.res {
+                Res::Def(DefKind::Static(Mutability::Mut), did) => Some(did),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// If `ex` is a field or element of a `static mut`, like `X.a[i]`, return the static's
+    /// `DefId`.
+    fn static_mut_base(&self, ex: &hir::Expr) -> Option<DefId> {
+        match ex.kind {
+            hir::ExprKind::Field(base, _) | hir::ExprKind::Index(base, _) => {
+                self.static_mut(base).or_else(|| self.static_mut_base(base))
+            }
+            _ => None,
+        }
+    }
+
+    fn record(&mut self, did: DefId, access: Access) {
+        let uses = self.uses.entry(did).or_default();
+        // A partial write's path is counted by its `Read`.
+        if let Some(ref mut counts) = self.stmt_accesses {
+            if !matches!(access, Access::PartialWrite) {
+                let count = counts.entry(did).or_default();
+                *count += 1;
+                uses.repeated_in_stmt |= *count > 1;
+            }
+        }
+        if !matches!(access, Access::Read(..)) && (self.loop_depth > 0 || !self.in_entry_fn) {
+            uses.written_repeatedly = true;
+        }
+        uses.accesses.push(access);
+    }
+}
+
+impl<'tcx> Visitor<'tcx> for UseCollector<'tcx> {
+    type NestedFilter = nested_filter::OnlyBodies;
+
+    fn nested_visit_map(&mut self) -> Self::Map {
+        self.tcx.hir()
+    }
+
+    fn visit_body(&mut self, body: &'tcx hir::Body<'tcx>) {
+        let owner = self.tcx.hir().body_owner_def_id(body.id()).to_def_id();
+        let old = self.in_entry_fn;
+        self.in_entry_fn = Some(owner) == self.entry_fn;
+        intravisit::walk_body(self, body);
+        self.in_entry_fn = old;
+    }
+
+    fn visit_stmt(&mut self, stmt: &'tcx hir::Stmt<'tcx>) {
+        if let hir::StmtKind::Semi(ex) = stmt.kind {
+            self.stmt_exprs.insert(ex.hir_id);
+        }
+        let old = self.stmt_accesses.replace(HashMap::new());
+        intravisit::walk_stmt(self, stmt);
+        self.stmt_accesses = old;
+    }
+
+    fn visit_block(&mut self, block: &'tcx hir::Block<'tcx>) {
+        for stmt in block.stmts {
+            self.visit_stmt(stmt);
+        }
+        // A block's tail expression is evaluated as a whole, just like a statement.
+        if let Some(ex) = block.expr {
+            let old = self.stmt_accesses.replace(HashMap::new());
+            self.visit_expr(ex);
+            self.stmt_accesses = old;
+        }
+    }
+
+    fn visit_expr(&mut self, ex: &'tcx hir::Expr<'tcx>) {
+        match ex.kind {
+            hir::ExprKind::Assign(lhs, rhs, _) => {
+                if let Some(did) = self.static_mut(lhs) {
+                    self.record(did, Access::Write(lhs.span, rhs.span));
+                    self.visit_expr(rhs);
+                    return;
+                }
+                if let Some(did) = self.static_mut_base(lhs) {
+                    self.record(did, Access::PartialWrite);
+                }
+            }
+            hir::ExprKind::AssignOp(op, lhs, rhs) => {
+                if let Some(did) = self.static_mut(lhs) {
+                    let is_stmt = self.stmt_exprs.contains(&ex.hir_id);
+                    let access = Access::Compound(op.node, lhs.span, rhs.span, is_stmt);
+                    self.record(did, access);
+                    self.visit_expr(rhs);
+                    return;
+                }
+                if let Some(did) = self.static_mut_base(lhs) {
+                    self.record(did, Access::PartialWrite);
+                }
+            }
+            hir::ExprKind::AddrOf(_, _, inner) => {
+                if let Some(did) = self
+                    .static_mut(inner)
+                    .or_else(|| self.static_mut_base(inner))
+                {
+                    self.uses.entry(did).or_default().addr_taken = true;
+                }
+            }
+            hir::ExprKind::MethodCall(_, args, _) => {
+                if let Some(did) = self.static_mut(&args[0]) {
+                    self.uses.entry(did).or_default().addr_taken = true;
+                }
+            }
+            hir::ExprKind::Loop(..) => {
+                self.loop_depth += 1;
+                intravisit::walk_expr(self, ex);
+                self.loop_depth -= 1;
+                return;
+            }
+            hir::ExprKind::Path(..) => {
+                if let Some(did) = self.static_mut(ex) {
+                    self.record(did, Access::Read(ex.span));
+                }
+            }
+            _ => {}
+        }
+        intravisit::walk_expr(self, ex);
+    }
+}
+
+/// The atomic type that can replace a static of type `ty`, if there is one.
+fn atomic_type(ty: Ty) -> Option<&'static str> {
+    let name = match *ty.kind() {
+        TyKind::Bool => "AtomicBool",
+        TyKind::Int(IntTy::I8) => "AtomicI8",
+        TyKind::Int(IntTy::I16) => "AtomicI16",
+        TyKind::Int(IntTy::I32) => "AtomicI32",
+        TyKind::Int(IntTy::I64) => "AtomicI64",
+        TyKind::Int(IntTy::Isize) => "AtomicIsize",
+        TyKind::Uint(UintTy::U8) => "AtomicU8",
+        TyKind::Uint(UintTy::U16) => "AtomicU16",
+        TyKind::Uint(UintTy::U32) => "AtomicU32",
+        TyKind::Uint(UintTy::U64) => "AtomicU64",
+        TyKind::Uint(UintTy::Usize) => "AtomicUsize",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// The atomic read-modify-write method for the compound assignment `op`, if there is one.
+fn atomic_rmw_method(op: hir::BinOpKind, is_bool: bool) -> Option<&'static str> {
+    use hir::BinOpKind::*;
+    let name = match op {
+        Add if !is_bool => "fetch_add",
+        Sub if !is_bool => "fetch_sub",
+        BitAnd => "fetch_and",
+        BitOr => "fetch_or",
+        BitXor => "fetch_xor",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Decide how a `static mut` with the given uses should be rewritten, or return `None` to leave it
+/// alone.
+fn classify(tcx: TyCtxt, did: DefId, uses: &StaticUses) -> Option<StaticKind> {
+    let ty = tcx.type_of(did);
+    let has_raw_ptr = ty.walk().any(|arg| match arg.unpack() {
+        GenericArgKind::Type(ty) => matches!(ty.kind(), TyKind::RawPtr(..)),
+        _ => false,
+    });
+    if uses.addr_taken
+        || has_raw_ptr
+        || tcx.has_attr(did, sym::no_mangle)
+        || tcx.is_thread_local_static(did)
+    {
+        return None;
+    }
+
+    // Reads of a `OnceCell` copy the value out of the reference `get` returns.
+    let is_copy = ty.is_copy_modulo_regions(tcx.at(DUMMY_SP), ParamEnv::reveal_all());
+    match uses.writes()[..] {
+        [] => return Some(StaticKind::Immutable),
+        [Access::Write(..)] if !uses.written_repeatedly && is_copy => {
+            return Some(StaticKind::OnceCell)
+        }
+        _ => {}
+    }
+    if let Some(name) = atomic_type(ty) {
+        return Some(StaticKind::Atomic(name));
+    }
+    if uses.repeated_in_stmt {
+        return None;
+    }
+    Some(StaticKind::Mutex)
+}
+
+/// Find the `static mut` items in the crate and decide how to rewrite each one.  Atomics use
+/// `Relaxed` ordering if `single_threaded` is set.
+pub fn analyze(tcx: TyCtxt, single_threaded: bool) -> Vec<StaticRewrite> {
+    let ordering = if single_threaded { RELAXED } else { SEQ_CST };
+    let mut collector = UseCollector {
+        tcx,
+        uses: HashMap::new(),
+        entry_fn: tcx.entry_fn(()).map(|(did, _)| did),
+        in_entry_fn: false,
+        loop_depth: 0,
+        stmt_accesses: None,
+        stmt_exprs: HashSet::new(),
+    };
+    tcx.hir().visit_all_item_likes_in_crate(&mut collector);
+
+    let mut rewrites = Vec::new();
+    for ldid in tcx.hir_crate_items(()).definitions() {
+        let did = ldid.to_def_id();
+        if tcx.def_kind(did) != DefKind::Static(Mutability::Mut) {
+            continue;
+        }
+        // Foreign statics are defined elsewhere.
+        if !matches!(tcx.hir().get_by_def_id(ldid), hir::Node::Item(..)) {
+            continue;
+        }
+        let uses = collector.uses.remove(&did).unwrap_or_default();
+        if let Some(kind) = classify(tcx, did, &uses) {
+            rewrites.push(StaticRewrite {
+                did: ldid,
+                kind,
+                uses,
+                ordering,
+            });
+        }
+    }
+    rewrites
+}
+
+impl StaticRewrite {
+    /// The spans of the static's type and initializer.
+    fn ty_and_init_spans(&self, tcx: TyCtxt) -> (Span, Span) {
+        match tcx.hir().expect_item(self.did).kind {
+            hir::ItemKind::Static(ty, _, body_id) => (ty.span, tcx.hir().body(body_id).value.span),
+            ref kind => panic!("expected a static, but got {:?}", kind),
+        }
+    }
+
+    /// The rewritten type of the static.
+    pub fn new_type(&self, tcx: TyCtxt) -> Option<String> {
+        let (ty_span, _) = self.ty_and_init_spans(tcx);
+        let ty = tcx.sess.source_map().span_to_snippet(ty_span).ok()?;
+        Some(match self.kind {
+            StaticKind::Immutable => ty,
+            StaticKind::OnceCell => format!("once_cell::sync::OnceCell<{}>", ty),
+            StaticKind::Atomic(name) => format!("std::sync::atomic::{}", name),
+            StaticKind::Mutex => format!("std::sync::Mutex<{}>", ty),
+        })
+    }
+
+    /// Build the edits that rewrite the static's definition and all of its uses.  Returns `None`
+    /// if the source of the static or one of its uses isn't available.
+    pub fn edits(&self, tcx: TyCtxt) -> Option<Vec<TextEdit>> {
+        let snippet = |span| tcx.sess.source_map().span_to_snippet(span).ok();
+        let item = tcx.hir().expect_item(self.did);
+        let (ty_span, init_span) = self.ty_and_init_spans(tcx);
+        let init = snippet(init_span)?;
+
+        let mut edits = Vec::new();
+        let prefix_span = item.span.with_hi(ty_span.lo());
+        let prefix = snippet(prefix_span)?.replacen("static mut", "static", 1);
+        edits.push(TextEdit::new(prefix_span, prefix));
+        let new_ty = self.new_type(tcx)?;
+        match self.kind {
+            StaticKind::Immutable => {}
+            // The generic types need the qualified path syntax to call `new`.
+            StaticKind::OnceCell => {
+                edits.push(TextEdit::new(init_span, format!("<{}>::new()", new_ty)));
+                edits.push(TextEdit::new(ty_span, new_ty));
+            }
+            StaticKind::Mutex => {
+                edits.push(TextEdit::new(
+                    init_span,
+                    format!("<{}>::new({})", new_ty, init),
+                ));
+                edits.push(TextEdit::new(ty_span, new_ty));
+            }
+            StaticKind::Atomic(_) => {
+                edits.push(TextEdit::new(
+                    init_span,
+                    format!("{}::new({})", new_ty, init),
+                ));
+                edits.push(TextEdit::new(ty_span, new_ty));
+            }
+        }
+
+        // Each use refers to the static by the path at its own site, which may be qualified
+        // differently from one module to the next.
+        for &access in &self.uses.accesses {
+            match (self.kind, access) {
+                (StaticKind::Immutable, _) | (_, Access::PartialWrite) => {}
+                (StaticKind::OnceCell, Access::Read(span)) => {
+                    let text = format!("(*{}.get().unwrap_or(&({})))", snippet(span)?, init);
+                    edits.push(TextEdit::new(span, text));
+                }
+                (StaticKind::OnceCell, Access::Write(lhs, rhs)) => {
+                    let text = format!("assert!({}.set(", snippet(lhs)?);
+                    edits.push(TextEdit::new(lhs.to(rhs.shrink_to_lo()), text));
+                    edits.push(TextEdit::new(rhs.shrink_to_hi(), ").is_ok())"));
+                }
+                (StaticKind::Atomic(_), Access::Read(span)) => {
+                    let text = format!("{}.load({})", snippet(span)?, self.ordering);
+                    edits.push(TextEdit::new(span, text));
+                }
+                (StaticKind::Atomic(_), Access::Write(lhs, rhs)) => {
+                    let text = format!("{}.store(", snippet(lhs)?);
+                    edits.push(TextEdit::new(lhs.to(rhs.shrink_to_lo()), text));
+                    edits.push(TextEdit::new(
+                        rhs.shrink_to_hi(),
+                        format!(", {})", self.ordering),
+                    ));
+                }
+                (StaticKind::Atomic(atomic), Access::Compound(op, lhs, rhs, is_stmt)) => {
+                    let path = snippet(lhs)?;
+                    let is_bool = atomic == "AtomicBool";
+                    // The read-modify-write methods return the old value, so they can only
+                    // replace a compound assignment whose `()` value is discarded.
+                    let (text, end) = match atomic_rmw_method(op, is_bool).filter(|_| is_stmt) {
+                        Some(method) => (format!("{}.{}(", path, method), ""),
+                        None => {
+                            let load = format!("{}.load({})", path, self.ordering);
+                            (format!("{}.store({} {} (", path, load, op.as_str()), ")")
+                        }
+                    };
+                    edits.push(TextEdit::new(lhs.to(rhs.shrink_to_lo()), text));
+                    let text = format!("{}, {})", end, self.ordering);
+                    edits.push(TextEdit::new(rhs.shrink_to_hi(), text));
+                }
+                (StaticKind::Mutex, Access::Read(span)) => {
+                    let text = format!("(*{}.lock().unwrap())", snippet(span)?);
+                    edits.push(TextEdit::new(span, text));
+                }
+                (StaticKind::Mutex, Access::Write(lhs, _) | Access::Compound(_, lhs, _, _)) => {
+                    let text = format!("*{}.lock().unwrap()", snippet(lhs)?);
+                    edits.push(TextEdit::new(lhs, text));
+                }
+                (kind, access) => {
+                    unreachable!("{:?} static can't have access {:?}", kind, access)
+                }
+            }
+        }
+        Some(edits)
+    }
+}
+
+impl fmt::Display for StaticKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StaticKind::Immutable => write!(f, "immutable"),
+            StaticKind::OnceCell => write!(f, "write-once"),
+            StaticKind::Atomic(_) => write!(f, "atomic"),
+            StaticKind::Mutex => write!(f, "mutex"),
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Timing and size statistics, for `--stats`.
+//!
+//! The time spent in each phase of the analysis is accumulated across all functions, and the
+//! peak memory use of the process is sampled at the end of each phase.  Together with the number
+//! of pointers and constraints in each function, this is meant for finding out which phase, and
+//! which functions, to look at when the analysis is slow on a large crate.
+use std::fs;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Assigning `PointerId`s to signatures, fields, and locals.
+    Labeling,
+    /// Generating dataflow and equivalence constraints.
+    Constraints,
+    /// Unifying equivalent pointers and renumbering them.
+    Equivalence,
+    /// Solving for the final permissions and flags.
+    Solving,
+    /// Generating and applying rewrites, and printing the results.
+    Rewriting,
+}
+
+impl Phase {
+    const ALL: [Phase; 5] = [
+        Phase::Labeling,
+        Phase::Constraints,
+        Phase::Equivalence,
+        Phase::Solving,
+        Phase::Rewriting,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Phase::Labeling => "labeling",
+            Phase::Constraints => "constraint generation",
+            Phase::Equivalence => "equivalence unification",
+            Phase::Solving => "solving",
+            Phase::Rewriting => "rewriting",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct PhaseStats {
+    time: Duration,
+    /// Peak resident memory of the process at the end of the phase, in kilobytes.
+    peak_kb: Option<u64>,
+}
+
+struct FnStats {
+    name: String,
+    pointers: usize,
+    constraints: usize,
+}
+
+/// Read the peak resident memory of the process, in kilobytes.  This is only available on Linux.
+fn peak_memory_kb() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+#[derive(Default)]
+pub struct Stats {
+    enabled: bool,
+    phases: [PhaseStats; Phase::ALL.len()],
+    fns: Vec<FnStats>,
+}
+
+impl Stats {
+    pub fn new(enabled: bool) -> Stats {
+        Stats {
+            enabled,
+            ..Stats::default()
+        }
+    }
+
+    /// Add the time since `start` to `phase`.
+    pub fn record(&mut self, phase: Phase, start: Instant) {
+        if !self.enabled {
+            return;
+        }
+        let stats = &mut self.phases[phase as usize];
+        stats.time += start.elapsed();
+        stats.peak_kb = peak_memory_kb();
+    }
+
+    /// Record the size of the function `name`.
+    pub fn add_fn(&mut self, name: String, pointers: usize, constraints: usize) {
+        if !self.enabled {
+            return;
+        }
+        self.fns.push(FnStats {
+            name,
+            pointers,
+            constraints,
+        });
+    }
+
+    pub fn print(&self) {
+        if !self.enabled {
+            return;
+        }
+        eprintln!("\n=== statistics ===");
+        for phase in Phase::ALL {
+            let stats = &self.phases[phase as usize];
+            let peak = match stats.peak_kb {
+                Some(kb) => format!("{} KiB", kb),
+                None => "unknown".to_owned(),
+            };
+            eprintln!(
+                "{:<24} {:>10.3}s, peak memory {}",
+                phase.name(),
+                stats.time.as_secs_f64(),
+                peak
+            );
+        }
+
+        // List the largest functions first.
+        let mut fns = self.fns.iter().collect::<Vec<_>>();
+        fns.sort_by(|a, b| b.constraints.cmp(&a.constraints).then(a.name.cmp(&b.name)));
+        eprintln!("\n{:>10} {:>12}  function", "pointers", "constraints");
+        for f in fns {
+            eprintln!("{:>10} {:>12}  {}", f.pointers, f.constraints, f.name);
+        }
+    }
+}
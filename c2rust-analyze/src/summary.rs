@@ -0,0 +1,275 @@
+//! Function summaries for cross-crate analysis.
+//!
+//! When a transpiled project is split into several crates, calls from one crate into another
+//! would normally be treated as [`Callee::UnknownDef`].  To avoid this, an analysis run can export
+//! a summary of the signatures of the functions it analyzed, including the final
+//! [`PermissionSet`] and [`FlagSet`] of each pointer, and a later run on a dependent crate can
+//! import it.  Functions are identified by their [`DefPathHash`], which is stable across crates
+//! and compilation sessions.
+//!
+//! For each imported function that's called from the current crate, we assign global
+//! [`PointerId`]s to its signature just as for local functions, then initialize those pointers
+//! with the summarized permissions.  Calls to the function are then handled like calls to a local
+//! function.
+use crate::context::{FlagSet, GlobalAnalysisCtxt, GlobalAssignment, LFnSig, LTy, PermissionSet};
+use crate::options::Options;
+use crate::pointer_id::PointerId;
+use crate::util::{self, Callee};
+use crate::AssignPointerIds;
+use rustc_hir::def_id::{DefId, LocalDefId, LOCAL_CRATE};
+use rustc_middle::mir::visit::Visitor;
+use rustc_middle::mir::{Body, Location, Operand};
+use rustc_middle::ty::{TyCtxt, TyKind, WithOptConstParam};
+use rustc_span::def_id::DefPathHash;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+/// The summary of a whole crate, as written to a summary file.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CrateSummary {
+    pub crate_name: String,
+    pub functions: Vec<FnSummary>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FnSummary {
+    /// The [`DefPathHash`] of the function, as returned by `Fingerprint::as_value`.
+    pub def_path_hash: (u64, u64),
+    /// The path of the function, for human readers.  This isn't used when importing.
+    pub def_path: String,
+    pub inputs: Vec<TySummary>,
+    pub output: TySummary,
+}
+
+/// The permissions and flags of each pointer in a type.  Pointers are listed in the order they're
+/// visited by [`LabeledTyS::iter`](crate::labeled_ty::LabeledTyS::iter).  Non-pointer
+/// components of the type are omitted.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TySummary {
+    /// The (unlabeled) type, for human readers.  This isn't used when importing.
+    pub ty: String,
+    pub perms: Vec<u16>,
+    pub flags: Vec<u16>,
+}
+
+impl TySummary {
+    fn new(lty: LTy, gasn: &GlobalAssignment) -> TySummary {
+        let ptrs = pointers(lty);
+        TySummary {
+            ty: format!("{}", lty.ty),
+            perms: ptrs.iter().map(|&p| gasn.perms[p].bits()).collect(),
+            flags: ptrs.iter().map(|&p| gasn.flags[p].bits()).collect(),
+        }
+    }
+}
+
+fn pointers(lty: LTy) -> Vec<PointerId> {
+    lty.iter()
+        .map(|lty| lty.label)
+        .filter(|ptr| !ptr.is_none())
+        .collect()
+}
+
+fn hash_key(hash: DefPathHash) -> (u64, u64) {
+    hash.0.as_value()
+}
+
+/// Summaries imported from other crates, indexed by [`DefPathHash`].
+#[derive(Default)]
+pub struct ImportedSummaries {
+    by_hash: HashMap<(u64, u64), FnSummary>,
+    /// Imported functions that are actually called from this crate, and thus have entries in
+    /// [`GlobalAnalysisCtxt::fn_sigs`].
+    used: HashMap<DefId, FnSummary>,
+}
+
+fn summary_dir_path(dir: &Path, crate_name: &str) -> PathBuf {
+    dir.join(format!("{crate_name}.json"))
+}
+
+impl ImportedSummaries {
+    /// Load the summaries requested in `options`.  If a `--summary-dir` is set, all summaries in
+    /// it are loaded, except the one for the current crate.
+    pub fn load(tcx: TyCtxt, options: &Options) -> ImportedSummaries {
+        let mut paths = options.import_summaries.clone();
+        if let Some(ref dir) = options.summary_dir {
+            let own_path = summary_dir_path(dir, tcx.crate_name(LOCAL_CRATE).as_str());
+            if let Ok(entries) = fs::read_dir(dir) {
+                for entry in entries {
+                    let path = entry.unwrap().path();
+                    if path.extension().map_or(false, |ext| ext == "json") && path != own_path {
+                        paths.push(path);
+                    }
+                }
+            }
+        }
+
+        let mut summaries = ImportedSummaries::default();
+        for path in paths {
+            let file = File::open(&path)
+                .unwrap_or_else(|e| panic!("failed to open summary {path:?}: {e}"));
+            let crate_summary: CrateSummary = serde_json::from_reader(BufReader::new(file))
+                .unwrap_or_else(|e| panic!("failed to parse summary {path:?}: {e}"));
+            eprintln!(
+                "imported {} function summaries for crate {} from {:?}",
+                crate_summary.functions.len(),
+                crate_summary.crate_name,
+                path,
+            );
+            for f in crate_summary.functions {
+                summaries.by_hash.insert(f.def_path_hash, f);
+            }
+        }
+        summaries
+    }
+
+    /// Assign global `PointerId`s for the signature of every imported function called from one
+    /// of `ldids`, and add the signature to `gacx.fn_sigs`.  This must run while global
+    /// `PointerId`s are being assigned, before any local analysis begins.
+    pub fn assign_pointer_ids(&mut self, gacx: &mut GlobalAnalysisCtxt, ldids: &[LocalDefId]) {
+        if self.by_hash.is_empty() {
+            return;
+        }
+        let tcx = gacx.tcx;
+
+        let mut callees = Vec::new();
+        for &ldid in ldids {
+            let mir = tcx.mir_built(WithOptConstParam::unknown(ldid));
+            let mir = mir.borrow();
+            for_each_extern_callee(tcx, &mir, |did| callees.push(did));
+        }
+
+        for did in callees {
+            if gacx.fn_sigs.contains_key(&did) {
+                continue;
+            }
+            let summary = match self.by_hash.get(&hash_key(tcx.def_path_hash(did))) {
+                Some(x) => x,
+                None => continue,
+            };
+
+            let sig = tcx.erase_late_bound_regions(tcx.fn_sig(did));
+            let inputs = sig
+                .inputs()
+                .iter()
+                .map(|&ty| gacx.assign_pointer_ids(ty))
+                .collect::<Vec<_>>();
+            let output = gacx.assign_pointer_ids(sig.output());
+
+            let matches = |lty: LTy, ty_summary: &TySummary| {
+                let n = pointers(lty).len();
+                n == ty_summary.perms.len() && n == ty_summary.flags.len()
+            };
+            if inputs.len() != summary.inputs.len()
+                || !inputs
+                    .iter()
+                    .zip(&summary.inputs)
+                    .all(|(&l, s)| matches(l, s))
+                || !matches(output, &summary.output)
+            {
+                eprintln!(
+                    "warning: summary for {} doesn't match its signature {:?}; ignoring it",
+                    summary.def_path, sig,
+                );
+                // The `PointerId`s we just assigned are left unused, which is harmless.
+                continue;
+            }
+
+            let inputs = gacx.lcx.mk_slice(&inputs);
+            gacx.fn_sigs.insert(did, LFnSig { inputs, output });
+            self.used.insert(did, summary.clone());
+        }
+    }
+
+    /// Initialize the permissions and flags of each imported signature in `gasn`.  This must run
+    /// after `PointerId`s have been remapped.
+    pub fn apply(&self, gacx: &GlobalAnalysisCtxt, gasn: &mut GlobalAssignment) {
+        for (did, summary) in &self.used {
+            let lsig = &gacx.fn_sigs[did];
+            let ltys = lsig.inputs.iter().copied().chain(Some(lsig.output));
+            let ty_summaries = summary.inputs.iter().chain(Some(&summary.output));
+            for (lty, ty_summary) in ltys.zip(ty_summaries) {
+                let ptrs = pointers(lty);
+                for ((ptr, &perms), &flags) in ptrs
+                    .into_iter()
+                    .zip(&ty_summary.perms)
+                    .zip(&ty_summary.flags)
+                {
+                    gasn.perms[ptr] = PermissionSet::from_bits_truncate(perms);
+                    gasn.flags[ptr] = FlagSet::from_bits_truncate(flags);
+                }
+            }
+        }
+    }
+}
+
+/// Call `f` on the `DefId` of each non-local function called in `mir`.
+fn for_each_extern_callee<'tcx>(tcx: TyCtxt<'tcx>, mir: &Body<'tcx>, f: impl FnMut(DefId)) {
+    struct CalleeVisitor<'a, 'tcx, F> {
+        tcx: TyCtxt<'tcx>,
+        mir: &'a Body<'tcx>,
+        f: F,
+    }
+
+    impl<'tcx, F: FnMut(DefId)> Visitor<'tcx> for CalleeVisitor<'_, 'tcx, F> {
+        fn visit_operand(&mut self, operand: &Operand<'tcx>, _location: Location) {
+            let ty = operand.ty(self.mir, self.tcx);
+            if let Callee::UnknownDef { ty } = util::ty_callee(self.tcx, ty) {
+                if let TyKind::FnDef(did, _) = *ty.kind() {
+                    (self.f)(did);
+                }
+            }
+        }
+    }
+
+    CalleeVisitor { tcx, mir, f }.visit_body(mir);
+}
+
+/// Write a summary of all the functions in `ldids` to the file requested in `options`, if any.
+pub fn export(
+    gacx: &GlobalAnalysisCtxt,
+    gasn: &GlobalAssignment,
+    ldids: &[LocalDefId],
+    options: &Options,
+) {
+    let tcx = gacx.tcx;
+    let crate_name = tcx.crate_name(LOCAL_CRATE).to_string();
+    let path = match (&options.export_summary, &options.summary_dir) {
+        (Some(path), _) => path.clone(),
+        (None, Some(dir)) => {
+            fs::create_dir_all(dir).unwrap();
+            summary_dir_path(dir, &crate_name)
+        }
+        (None, None) => return,
+    };
+
+    let functions = ldids
+        .iter()
+        .map(|&ldid| {
+            let did = ldid.to_def_id();
+            let lsig = &gacx.fn_sigs[&did];
+            FnSummary {
+                def_path_hash: hash_key(tcx.def_path_hash(did)),
+                def_path: tcx.def_path_str(did),
+                inputs: lsig
+                    .inputs
+                    .iter()
+                    .map(|&lty| TySummary::new(lty, gasn))
+                    .collect(),
+                output: TySummary::new(lsig.output, gasn),
+            }
+        })
+        .collect();
+
+    let crate_summary = CrateSummary {
+        crate_name,
+        functions,
+    };
+    let file =
+        File::create(&path).unwrap_or_else(|e| panic!("failed to create summary {path:?}: {e}"));
+    serde_json::to_writer_pretty(BufWriter::new(file), &crate_summary).unwrap();
+    eprintln!("wrote function summaries to {path:?}");
+}
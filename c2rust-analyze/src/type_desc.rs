@@ -1,7 +1,11 @@
 use crate::context::{AnalysisCtxt, Assignment, FlagSet, LTy, PermissionSet, PointerId};
+use crate::pointer_id::PointerTable;
 use rustc_hir::def::{DefKind, Res};
-use rustc_middle::ty::subst::GenericArg;
-use rustc_middle::ty::{ReErased, Ty, TyCtxt, TyKind};
+use rustc_hir::def_id::DefId;
+use rustc_middle::ty::subst::{GenericArg, InternalSubsts};
+use rustc_middle::ty::{ParamEnv, ReErased, Ty, TyCtxt, TyKind};
+use rustc_span::{sym, DUMMY_SP};
+use std::collections::HashSet;
 
 #[allow(dead_code)]
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
@@ -10,16 +14,24 @@ pub enum Ownership {
     Raw,
     /// E.g. `*mut T`
     RawMut,
+    /// E.g. `NonNull<T>`.  This is used for raw pointers that are never null.
+    NonNull,
     /// E.g. `&T`
     Imm,
-    /// E.g. `&Cell<T>`
+    /// E.g. `&Cell<T>`, or `&RefCell<T>` if `T` isn't `Copy`
     Cell,
     /// E.g. `&mut T`
     Mut,
-    /// E.g. `Rc<T>`
+    /// E.g. `Rc<RefCell<T>>`
     Rc,
     /// E.g. `Box<T>`
     Box,
+    /// E.g. `Vec<T>`
+    Vec,
+    /// `&CStr`, for a pointer to a NUL-terminated string
+    CStr,
+    /// `CString`, for a pointer that owns a NUL-terminated string
+    CString,
 }
 
 #[allow(dead_code)]
@@ -33,8 +45,53 @@ pub enum Quantity {
     OffsetPtr,
 }
 
+/// Permissions that show a `CVOID` pointer is used as a byte buffer, rather than as an opaque
+/// context pointer.
+pub const BYTE_BUFFER_PERMS: PermissionSet = PermissionSet::READ
+    .union(PermissionSet::WRITE)
+    .union(PermissionSet::OFFSET_ADD)
+    .union(PermissionSet::OFFSET_SUB);
+
 pub fn perms_to_desc(perms: PermissionSet, flags: FlagSet) -> (Ownership, Quantity) {
-    let own = if perms.contains(PermissionSet::UNIQUE | PermissionSet::WRITE) {
+    if flags.contains(FlagSet::FIXED) {
+        let own = if flags.contains(FlagSet::NON_NULL) && !flags.contains(FlagSet::NULLABLE) {
+            Ownership::NonNull
+        } else if perms.contains(PermissionSet::WRITE) {
+            Ownership::RawMut
+        } else {
+            Ownership::Raw
+        };
+        return (own, Quantity::Single);
+    }
+
+    // `&CStr` and `CString` don't give access to the string's bytes through the pointer, so a
+    // string that's read, written, or offset directly keeps its usual type.
+    if flags.contains(FlagSet::NUL_TERMINATED) && !perms.intersects(BYTE_BUFFER_PERMS) {
+        if flags.contains(FlagSet::OWNED) {
+            return (Ownership::CString, Quantity::Single);
+        } else if !perms.contains(PermissionSet::FREE) {
+            return (Ownership::CStr, Quantity::Single);
+        }
+    }
+
+    let own = if flags.contains(FlagSet::SHARED | FlagSet::RC)
+        && !perms.intersects(PermissionSet::OFFSET_ADD | PermissionSet::OFFSET_SUB)
+    {
+        // `Rc<RefCell<[T]>>` isn't useful, so only pointers to single objects are shared this
+        // way.
+        Ownership::Rc
+    } else if flags.contains(FlagSet::OWNED | FlagSet::VEC) {
+        Ownership::Vec
+    } else if flags.contains(FlagSet::OWNED) {
+        Ownership::Box
+    } else if perms.contains(PermissionSet::FREE) {
+        // Freed pointers that aren't unique owners can't be expressed safely.
+        if perms.contains(PermissionSet::WRITE) {
+            Ownership::RawMut
+        } else {
+            Ownership::Raw
+        }
+    } else if perms.contains(PermissionSet::UNIQUE | PermissionSet::WRITE) {
         Ownership::Mut
     } else if flags.contains(FlagSet::CELL) {
         Ownership::Cell
@@ -46,7 +103,13 @@ pub fn perms_to_desc(perms: PermissionSet, flags: FlagSet) -> (Ownership, Quanti
 
     // TODO(spernsteiner): will not remain identical branches
     #[allow(clippy::if_same_then_else)]
-    let qty = if perms.contains(PermissionSet::OFFSET_SUB) {
+    let qty = if own == Ownership::Vec {
+        // A `Vec` always holds a slice of elements, even if it's never offset.
+        Quantity::Slice
+    } else if flags.contains(FlagSet::CVOID) {
+        // A `c_void` byte buffer has no fixed size.
+        Quantity::Slice
+    } else if perms.contains(PermissionSet::OFFSET_SUB) {
         // TODO(spernsteiner): should be [`Quantity::OffsetPtr`], but that's not implemented yet
         Quantity::Slice
     } else if perms.contains(PermissionSet::OFFSET_ADD) {
@@ -58,7 +121,8 @@ pub fn perms_to_desc(perms: PermissionSet, flags: FlagSet) -> (Ownership, Quanti
     (own, qty)
 }
 
-fn mk_cell<'tcx>(tcx: TyCtxt<'tcx>, ty: Ty<'tcx>) -> Ty<'tcx> {
+/// Find the item `core::<mod_name>::<item_name>`, which must have kind `kind`.
+fn core_item(tcx: TyCtxt, mod_name: &str, item_name: &str, kind: DefKind) -> DefId {
     let core_crate = tcx
         .crates(())
         .iter()
@@ -66,29 +130,134 @@ fn mk_cell<'tcx>(tcx: TyCtxt<'tcx>, ty: Ty<'tcx>) -> Ty<'tcx> {
         .find(|&krate| tcx.crate_name(krate).as_str() == "core")
         .expect("failed to find crate `core`");
 
-    let cell_mod_child = tcx
+    let mod_child = tcx
         .module_children(core_crate.as_def_id())
         .iter()
-        .find(|child| child.ident.as_str() == "cell")
-        .expect("failed to find module `core::cell`");
-    let cell_mod = match cell_mod_child.res {
+        .find(|child| child.ident.as_str() == mod_name)
+        .unwrap_or_else(|| panic!("failed to find module `core::{}`", mod_name));
+    let mod_did = match mod_child.res {
         Res::Def(DefKind::Mod, did) => did,
-        ref r => panic!("unexpected resolution {:?} for `core::cell`", r),
+        ref r => panic!("unexpected resolution {:?} for `core::{}`", r, mod_name),
     };
 
-    let cell_struct_child = tcx
-        .module_children(cell_mod)
+    let item_child = tcx
+        .module_children(mod_did)
         .iter()
-        .find(|child| child.ident.as_str() == "Cell")
-        .expect("failed to find struct `core::cell::Cell`");
-    let cell_struct = match cell_struct_child.res {
-        Res::Def(DefKind::Struct, did) => did,
-        ref r => panic!("unexpected resolution {:?} for `core::cell::Cell`", r),
-    };
+        .find(|child| child.ident.as_str() == item_name)
+        .unwrap_or_else(|| panic!("failed to find `core::{}::{}`", mod_name, item_name));
+    match item_child.res {
+        Res::Def(k, did) if k == kind => did,
+        ref r => panic!(
+            "unexpected resolution {:?} for `core::{}::{}`",
+            r, mod_name, item_name
+        ),
+    }
+}
 
-    let cell_adt = tcx.adt_def(cell_struct);
+/// Build the type `core::<mod_name>::<item_name><ty>`.
+fn mk_core_adt<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    mod_name: &str,
+    item_name: &str,
+    kind: DefKind,
+    ty: Ty<'tcx>,
+) -> Ty<'tcx> {
+    let adt = tcx.adt_def(core_item(tcx, mod_name, item_name, kind));
     let substs = tcx.mk_substs([GenericArg::from(ty)].into_iter());
-    tcx.mk_adt(cell_adt, substs)
+    tcx.mk_adt(adt, substs)
+}
+
+fn mk_cell<'tcx>(tcx: TyCtxt<'tcx>, ty: Ty<'tcx>) -> Ty<'tcx> {
+    mk_core_adt(tcx, "cell", "Cell", DefKind::Struct, ty)
+}
+
+fn mk_non_null<'tcx>(tcx: TyCtxt<'tcx>, ty: Ty<'tcx>) -> Ty<'tcx> {
+    mk_core_adt(tcx, "ptr", "NonNull", DefKind::Struct, ty)
+}
+
+fn mk_ref_cell<'tcx>(tcx: TyCtxt<'tcx>, ty: Ty<'tcx>) -> Ty<'tcx> {
+    mk_core_adt(tcx, "cell", "RefCell", DefKind::Struct, ty)
+}
+
+/// Check whether a [`Ownership::Cell`] pointer to `pointee` must use `RefCell<T>` instead of
+/// `Cell<T>`.  Reading a `Cell<T>` copies the value out, so it only works for `Copy` types.  This
+/// depends on the rewritten pointee type: a `*mut *mut T` whose inner pointer becomes `&mut T` needs
+/// a `RefCell`.
+pub fn needs_ref_cell<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    pointee: LTy<'tcx>,
+    perms: &PointerTable<PermissionSet>,
+    flags: &PointerTable<FlagSet>,
+) -> bool {
+    !is_copy_after_rewrite(tcx, pointee, perms, flags)
+}
+
+fn is_copy_after_rewrite<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    lty: LTy<'tcx>,
+    perms: &PointerTable<PermissionSet>,
+    flags: &PointerTable<FlagSet>,
+) -> bool {
+    let ptr = lty.label;
+    if !ptr.is_none() && lty.ty.is_unsafe_ptr() {
+        if flags[ptr].contains(FlagSet::FIXED) {
+            return true;
+        }
+        let (own, _) = perms_to_desc(perms[ptr], flags[ptr]);
+        return matches!(
+            own,
+            Ownership::Raw
+                | Ownership::RawMut
+                | Ownership::NonNull
+                | Ownership::Imm
+                | Ownership::Cell
+                | Ownership::CStr
+        );
+    }
+    lty.ty
+        .is_copy_modulo_regions(tcx.at(DUMMY_SP), ParamEnv::reveal_all())
+        && lty
+            .args
+            .iter()
+            .all(|&arg| is_copy_after_rewrite(tcx, arg, perms, flags))
+}
+
+fn mk_rc<'tcx>(tcx: TyCtxt<'tcx>, ty: Ty<'tcx>) -> Ty<'tcx> {
+    let rc_did = tcx
+        .get_diagnostic_item(sym::Rc)
+        .expect("failed to find `Rc`");
+    let substs = tcx.mk_substs([GenericArg::from(ty)].into_iter());
+    tcx.mk_adt(tcx.adt_def(rc_did), substs)
+}
+
+fn mk_vec<'tcx>(tcx: TyCtxt<'tcx>, ty: Ty<'tcx>) -> Ty<'tcx> {
+    let vec_did = tcx
+        .get_diagnostic_item(sym::Vec)
+        .expect("failed to find `Vec`");
+    // Use the default for the remaining parameters, which is just the allocator.
+    let substs = InternalSubsts::for_item(tcx, vec_did, |param, _| {
+        if param.index == 0 {
+            ty.into()
+        } else {
+            tcx.type_of(param.def_id).into()
+        }
+    });
+    tcx.mk_adt(tcx.adt_def(vec_did), substs)
+}
+
+fn mk_c_str(tcx: TyCtxt) -> Ty {
+    tcx.type_of(core_item(tcx, "ffi", "CStr", DefKind::Struct))
+}
+
+fn mk_c_string(tcx: TyCtxt) -> Ty {
+    let c_string_did = tcx
+        .get_diagnostic_item(sym::cstring_type)
+        .expect("failed to find `CString`");
+    tcx.type_of(c_string_did)
+}
+
+fn mk_option<'tcx>(tcx: TyCtxt<'tcx>, ty: Ty<'tcx>) -> Ty<'tcx> {
+    mk_core_adt(tcx, "option", "Option", DefKind::Enum, ty)
 }
 
 pub fn convert_type<'tcx>(
@@ -99,37 +268,72 @@ pub fn convert_type<'tcx>(
     let tcx = acx.tcx();
     let perms = asn.perms();
     let flags = asn.flags();
+    let ref_cells = lty
+        .iter()
+        .filter(|lty| !lty.label.is_none() && lty.ty.is_unsafe_ptr())
+        .filter(|lty| needs_ref_cell(tcx, lty.args[0], &perms, &flags))
+        .map(|lty| lty.label)
+        .collect::<HashSet<_>>();
     acx.lcx().rewrite_unlabeled(lty, &mut |ty, args, label| {
         if label == PointerId::NONE || !matches!(ty.kind(), TyKind::RawPtr(..)) {
             return ty;
         }
         let ptr = label;
-
         let (own, qty) = perms_to_desc(perms[ptr], flags[ptr]);
 
+        if flags[ptr].contains(FlagSet::FIXED) {
+            // Keep the original type, including any pointers inside it.
+            if own == Ownership::NonNull {
+                return mk_non_null(tcx, ty.builtin_deref(true).unwrap().ty);
+            }
+            return ty;
+        }
+
         assert_eq!(args.len(), 1);
-        let mut ty = args[0];
+        let mut ty = if flags[ptr].contains(FlagSet::CVOID) {
+            tcx.types.u8
+        } else {
+            args[0]
+        };
 
         if own == Ownership::Cell {
-            ty = mk_cell(tcx, ty);
+            ty = if ref_cells.contains(&ptr) {
+                mk_ref_cell(tcx, ty)
+            } else {
+                mk_cell(tcx, ty)
+            };
         }
 
-        ty = match qty {
-            Quantity::Single => ty,
-            Quantity::Slice => tcx.mk_slice(ty),
-            Quantity::OffsetPtr => todo!(),
-        };
+        // `Vec<T>` always holds a slice of elements, so `qty` doesn't change its type.
+        if own != Ownership::Vec {
+            ty = match qty {
+                Quantity::Single => ty,
+                // `OffsetPtr` isn't implemented yet, so it's rewritten as a slice.
+                Quantity::Slice | Quantity::OffsetPtr => tcx.mk_slice(ty),
+            };
+        }
 
         ty = match own {
             Ownership::Raw => tcx.mk_imm_ptr(ty),
             Ownership::RawMut => tcx.mk_mut_ptr(ty),
+            Ownership::NonNull => unreachable!("only `FIXED` pointers become `NonNull`"),
             Ownership::Imm => tcx.mk_imm_ref(tcx.mk_region(ReErased), ty),
             Ownership::Cell => tcx.mk_imm_ref(tcx.mk_region(ReErased), ty),
             Ownership::Mut => tcx.mk_mut_ref(tcx.mk_region(ReErased), ty),
-            Ownership::Rc => todo!(),
-            Ownership::Box => todo!(),
+            Ownership::Rc => mk_rc(tcx, mk_ref_cell(tcx, ty)),
+            Ownership::Box => tcx.mk_box(ty),
+            Ownership::Vec => mk_vec(tcx, ty),
+            // These don't mention the pointee, which is always `c_char`.
+            Ownership::CStr => tcx.mk_imm_ref(tcx.mk_region(ReErased), mk_c_str(tcx)),
+            Ownership::CString => mk_c_string(tcx),
         };
 
+        // Nullable pointers become `Option<&T>`, `Option<&mut T>`, and so on, which have the
+        // same representation as the raw pointer, with `None` standing in for null.
+        if flags[ptr].contains(FlagSet::NULLABLE) {
+            ty = mk_option(tcx, ty);
+        }
+
         ty
     })
 }
@@ -0,0 +1,467 @@
+//! Removal of `unsafe` blocks that the rewrites make unnecessary, and a measure of how much unsafe
+//! code is left.
+//!
+//! Each function's MIR is scanned for unsafe operations, and each one is checked against the
+//! final assignment to see whether it survives the rewrites.  These operations go away:
+//!
+//! * Dereferencing a raw pointer that's rewritten to a reference, `Box`, `Vec`, or `Rc`.
+//! * Calling `offset` on such a pointer, which becomes indexing or slicing.
+//! * Using a `static mut` that's rewritten by [`crate::statics`].
+//!
+//! A pointer only counts as rewritten if its new type actually appears in the rewritten source.
+//! Arguments, fields, and locals with a type annotation get it from an edit to their declaration.
+//! A local whose type is inferred gets it only from the expressions assigned to it, so it stays raw
+//! unless each of those is rewritten, is a call to a function whose signature is rewritten, or
+//! copies another rewritten pointer.  Nothing in a function whose rewrites weren't applied counts
+//! as rewritten.
+//!
+//! Everything else stays unsafe, including calls to the crate's own `unsafe fn`s, whose
+//! signatures keep their `unsafe`.  An `unsafe` block is turned into a plain block if none of the
+//! remaining operations fall inside it, unless it belongs to a function whose rewrites weren't
+//! computed.  Blocks are only removed, not shrunk around the operations that are left.
+//!
+//! The counts of unsafe blocks, raw pointer types, and raw pointer dereferences before and after
+//! rewriting are printed as a measure of progress.
+use crate::context::{AnalysisCtxt, Assignment, FlagSet, GlobalAnalysisCtxt, GlobalAssignment};
+use crate::context::{PermissionSet, PointerId};
+use crate::pointer_id::PointerTable;
+use crate::rewrite::TextEdit;
+use crate::type_desc::{self, Ownership};
+use crate::util::{self, Callee};
+use rustc_hir as hir;
+use rustc_hir::def_id::{DefId, LocalDefId};
+use rustc_hir::intravisit::{self, Visitor};
+use rustc_index::vec::IndexVec;
+use rustc_middle::mir::visit::{PlaceContext, Visitor as MirVisitor};
+use rustc_middle::mir::{
+    AggregateKind, BindingForm, Body, ClearCrossCrate, Constant, Local, LocalInfo, LocalKind,
+    Location, Operand, Place, ProjectionElem, Rvalue, StatementKind, TerminatorKind,
+};
+use rustc_middle::ty::{GenericArgKind, Ty, TyCtxt, TyKind};
+use rustc_span::{BytePos, Span};
+use std::collections::HashSet;
+use std::fmt;
+
+/// Whether a pointer with these permissions and flags is rewritten to a safe type.
+fn is_safe(perms: PermissionSet, flags: FlagSet) -> bool {
+    let (own, _) = type_desc::perms_to_desc(perms, flags);
+    !matches!(own, Ownership::Raw | Ownership::RawMut | Ownership::NonNull)
+}
+
+/// Whether a type is safe in the rewritten source: `None` if it stays as written, or else the
+/// other functions whose rewritten signatures it relies on.
+type Rewritten = Option<HashSet<LocalDefId>>;
+
+fn has_raw_ptr(ty: Ty) -> bool {
+    ty.walk()
+        .any(|arg| matches!(arg.unpack(), GenericArgKind::Type(ty) if ty.is_unsafe_ptr()))
+}
+
+/// A count before and after rewriting.
+#[derive(Clone, Copy, Debug, Default)]
+struct Count {
+    before: usize,
+    after: usize,
+}
+
+impl Count {
+    fn add(&mut self, remains: bool) {
+        self.before += 1;
+        if remains {
+            self.after += 1;
+        }
+    }
+}
+
+/// Which locals of a function have their rewritten types in the rewritten source.
+struct SourceTypes<'a, 'tcx> {
+    acx: &'a AnalysisCtxt<'a, 'tcx>,
+    mir: &'a Body<'tcx>,
+    /// Statements and calls whose expressions were rewritten, or `None` if the function's
+    /// rewrites weren't applied.
+    rewritten: Option<&'a HashSet<Location>>,
+    locals: IndexVec<Local, Rewritten>,
+}
+
+impl<'a, 'tcx> SourceTypes<'a, 'tcx> {
+    /// Find the locals whose types are rewritten, given the locations whose expressions were
+    /// rewritten, or `None` if the function's rewrites weren't applied.
+    fn new(
+        acx: &'a AnalysisCtxt<'a, 'tcx>,
+        mir: &'a Body<'tcx>,
+        rewritten: Option<&'a HashSet<Location>>,
+    ) -> Self {
+        let is_closure = acx.tcx().is_closure(mir.source.def_id());
+        let mut inferred = HashSet::new();
+        let locals = mir
+            .local_decls
+            .iter_enumerated()
+            .map(|(local, decl)| {
+                rewritten?;
+                match (mir.local_kind(local), decl.local_info.as_deref()) {
+                    // Closure signatures are never rewritten.
+                    (LocalKind::Arg | LocalKind::ReturnPointer, _) if is_closure => return None,
+                    (LocalKind::Arg | LocalKind::ReturnPointer, _) => {}
+                    (_, Some(LocalInfo::User(ClearCrossCrate::Set(BindingForm::Var(v)))))
+                        if v.opt_ty_info.is_some() => {}
+                    // Assume the best until an assignment shows otherwise.
+                    _ if has_raw_ptr(decl.ty) => {
+                        inferred.insert(local);
+                    }
+                    _ => {}
+                }
+                Some(HashSet::new())
+            })
+            .collect();
+        let mut types = SourceTypes {
+            acx,
+            mir,
+            rewritten,
+            locals,
+        };
+
+        let mut defs = Vec::new();
+        for (block, bb_data) in mir.basic_blocks().iter_enumerated() {
+            for (statement_index, stmt) in bb_data.statements.iter().enumerate() {
+                if let StatementKind::Assign(ref x) = stmt.kind {
+                    if x.0.projection.is_empty() && inferred.contains(&x.0.local) {
+                        let loc = Location {
+                            block,
+                            statement_index,
+                        };
+                        defs.push((x.0.local, loc, Some(&x.1)));
+                    }
+                }
+            }
+            if let TerminatorKind::Call { destination, .. } = bb_data.terminator().kind {
+                if destination.projection.is_empty() && inferred.contains(&destination.local) {
+                    defs.push((destination.local, mir.terminator_loc(block), None));
+                }
+            }
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &(local, loc, rv) in &defs {
+                let new = match (&types.locals[local], types.assigned(loc, rv)) {
+                    (Some(old), Some(deps)) if deps.is_subset(old) => continue,
+                    (Some(old), Some(deps)) => Some(old.union(&deps).copied().collect()),
+                    (None, _) => continue,
+                    (Some(_), None) => None,
+                };
+                types.locals[local] = new;
+                changed = true;
+            }
+        }
+        types
+    }
+
+    /// Whether the type of `pl` is rewritten.
+    fn place(&self, pl: Place<'tcx>) -> Rewritten {
+        let mut lty = self.acx.type_of(pl.local);
+        let mut rewritten = self.locals[pl.local].clone();
+        for proj in pl.projection {
+            // Field types come from the struct definition.
+            if matches!(proj, ProjectionElem::Field(..)) && lty.ty.is_adt() {
+                rewritten = rewritten.or_else(|| Some(HashSet::new()));
+            }
+            lty = self.acx.project(lty, &proj);
+        }
+        rewritten
+    }
+
+    /// Whether the value assigned at `loc`, by `rv` or else by a call, has its rewritten type.
+    fn assigned(&self, loc: Location, rv: Option<&Rvalue<'tcx>>) -> Rewritten {
+        if self.rewritten.map_or(false, |locs| locs.contains(&loc)) {
+            return Some(HashSet::new());
+        }
+        let tcx = self.acx.tcx();
+        match rv {
+            Some(&Rvalue::Use(Operand::Copy(pl) | Operand::Move(pl)))
+            | Some(&Rvalue::CopyForDeref(pl))
+            | Some(&Rvalue::Ref(_, _, pl)) => self.place(pl),
+            // A `c_void` pointer cast to its real type, as in `malloc(n) as *mut T`.
+            Some(&Rvalue::Cast(_, Operand::Copy(pl) | Operand::Move(pl), _))
+                if util::is_c_void_ptr(tcx, pl.ty(self.mir, tcx).ty) =>
+            {
+                self.place(pl)
+            }
+            Some(Rvalue::Aggregate(kind, _)) if matches!(**kind, AggregateKind::Adt(..)) => {
+                Some(HashSet::new())
+            }
+            Some(_) => None,
+            None => match self.mir.basic_blocks()[loc.block].terminator().kind {
+                TerminatorKind::Call { ref func, .. } => {
+                    match util::ty_callee(tcx, func.ty(self.mir, tcx)) {
+                        Callee::LocalDef { def_id, .. } => {
+                            Some(def_id.as_local().into_iter().collect())
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Build an edit that turns the `unsafe` block at `span` into a plain block.
+fn remove_unsafe_keyword(tcx: TyCtxt, span: Span) -> Option<TextEdit> {
+    let snippet = tcx.sess.source_map().span_to_snippet(span).ok()?;
+    let rest = snippet.strip_prefix("unsafe")?;
+    let len = snippet.len() - rest.trim_start().len();
+    let keyword_span = span.with_hi(span.lo() + BytePos(len as u32));
+    Some(TextEdit::new(keyword_span, ""))
+}
+
+#[derive(Default)]
+pub struct UnsafeReduction {
+    /// Spans of the unsafe operations, and whether the rewrites make each one safe.
+    ops: Vec<(Span, Rewritten)>,
+    /// Functions whose unsafe operations were all found and whose rewrites were applied.
+    scanned: HashSet<LocalDefId>,
+    blocks: Count,
+    raw_ptr_types: Vec<Rewritten>,
+    raw_derefs: Vec<Rewritten>,
+}
+
+struct OpCollector<'a, 'tcx> {
+    acx: &'a AnalysisCtxt<'a, 'tcx>,
+    perms: PointerTable<'a, PermissionSet>,
+    flags: PointerTable<'a, FlagSet>,
+    mir: &'a Body<'tcx>,
+    types: &'a SourceTypes<'a, 'tcx>,
+    rewritten_statics: &'a HashSet<DefId>,
+    reduction: &'a mut UnsafeReduction,
+}
+
+impl<'a, 'tcx> OpCollector<'a, 'tcx> {
+    fn is_safe(&self, ptr: PointerId) -> bool {
+        !ptr.is_none() && is_safe(self.perms[ptr], self.flags[ptr])
+    }
+
+    fn add_op(&mut self, loc: Location, rewritten: Rewritten) {
+        let span = self.mir.source_info(loc).span.source_callsite();
+        self.reduction.ops.push((span, rewritten));
+    }
+}
+
+impl<'a, 'tcx> MirVisitor<'tcx> for OpCollector<'a, 'tcx> {
+    fn visit_place(&mut self, place: &Place<'tcx>, _context: PlaceContext, loc: Location) {
+        let mut lty = self.acx.type_of(place.local);
+        let mut place_rewritten = self.types.locals[place.local].clone();
+        for proj in place.projection {
+            match proj {
+                ProjectionElem::Deref if lty.ty.is_unsafe_ptr() => {
+                    let rewritten = place_rewritten.clone().filter(|_| self.is_safe(lty.label));
+                    self.reduction.raw_derefs.push(rewritten.clone());
+                    self.add_op(loc, rewritten);
+                }
+                ProjectionElem::Field(..) if lty.ty.is_union() => self.add_op(loc, None),
+                ProjectionElem::Field(..) if lty.ty.is_adt() => {
+                    place_rewritten = place_rewritten.or_else(|| Some(HashSet::new()));
+                }
+                _ => {}
+            }
+            lty = self.acx.project(lty, &proj);
+        }
+    }
+
+    fn visit_constant(&mut self, constant: &Constant<'tcx>, loc: Location) {
+        let tcx = self.acx.tcx();
+        if let Some(did) = constant.check_static_ptr(tcx) {
+            if tcx.is_foreign_item(did) {
+                self.add_op(loc, None);
+            } else if tcx.is_mutable_static(did) {
+                let rewritten = self.rewritten_statics.contains(&did);
+                self.add_op(loc, Some(HashSet::new()).filter(|_| rewritten));
+            }
+        }
+    }
+
+    fn visit_terminator(&mut self, term: &rustc_middle::mir::Terminator<'tcx>, loc: Location) {
+        let tcx = self.acx.tcx();
+        match term.kind {
+            TerminatorKind::Call {
+                ref func, ref args, ..
+            } => {
+                let func_ty = func.ty(self.mir, tcx);
+                let is_unsafe = match *func_ty.kind() {
+                    TyKind::FnDef(..) | TyKind::FnPtr(..) => {
+                        func_ty.fn_sig(tcx).unsafety() == hir::Unsafety::Unsafe
+                    }
+                    _ => false,
+                };
+                if is_unsafe {
+                    let rewritten = match util::ty_callee(tcx, func_ty) {
+                        Callee::PtrOffset { .. } => match args[0] {
+                            Operand::Copy(pl) | Operand::Move(pl) => self
+                                .types
+                                .place(pl)
+                                .filter(|_| self.is_safe(self.acx.type_of(pl).label)),
+                            Operand::Constant(..) => None,
+                        },
+                        _ => None,
+                    };
+                    self.add_op(loc, rewritten);
+                }
+            }
+            TerminatorKind::InlineAsm { .. } => self.add_op(loc, None),
+            _ => {}
+        }
+        self.super_terminator(term, loc);
+    }
+}
+
+/// Collects the user-written `unsafe` blocks of a body, without descending into closures.
+struct BlockCollector {
+    blocks: Vec<Span>,
+}
+
+impl<'tcx> Visitor<'tcx> for BlockCollector {
+    fn visit_block(&mut self, block: &'tcx hir::Block<'tcx>) {
+        if let hir::BlockCheckMode::UnsafeBlock(hir::UnsafeSource::UserProvided) = block.rules {
+            self.blocks.push(block.span);
+        }
+        intravisit::walk_block(self, block);
+    }
+}
+
+impl UnsafeReduction {
+    /// Record the unsafe operations and raw pointer types of the function `mir`.  Uses of the
+    /// statics in `rewritten_statics` are assumed to be safe after rewriting.  `rewritten` holds
+    /// the locations whose expressions were rewritten, or is `None` if the function was left
+    /// unchanged.
+    pub fn add_fn<'tcx>(
+        &mut self,
+        acx: &AnalysisCtxt<'_, 'tcx>,
+        asn: &Assignment,
+        mir: &Body<'tcx>,
+        rewritten_statics: &HashSet<DefId>,
+        rewritten: Option<&HashSet<Location>>,
+    ) {
+        let (perms, flags) = (asn.perms(), asn.flags());
+        let types = SourceTypes::new(acx, mir, rewritten);
+        for (local, decl) in mir.local_decls.iter_enumerated() {
+            let is_user = matches!(
+                mir.local_kind(local),
+                LocalKind::Arg | LocalKind::ReturnPointer
+            ) || decl.is_user_variable();
+            if !is_user {
+                continue;
+            }
+            for lty in acx.local_tys[local].iter() {
+                if lty.ty.is_unsafe_ptr() {
+                    let safe = !lty.label.is_none() && is_safe(perms[lty.label], flags[lty.label]);
+                    let rewritten = types.locals[local].clone().filter(|_| safe);
+                    self.raw_ptr_types.push(rewritten);
+                }
+            }
+        }
+
+        let mut collector = OpCollector {
+            acx,
+            perms,
+            flags,
+            mir,
+            types: &types,
+            rewritten_statics,
+            reduction: self,
+        };
+        collector.visit_body(mir);
+        if rewritten.is_some() {
+            let ldid = mir.source.def_id().expect_local();
+            self.scanned.insert(ldid);
+        }
+    }
+
+    /// Record the raw pointer types in struct fields.
+    pub fn add_fields(&mut self, gacx: &GlobalAnalysisCtxt, gasn: &GlobalAssignment) {
+        for lty in gacx.field_tys.values() {
+            for lty in lty.iter() {
+                if lty.ty.is_unsafe_ptr() {
+                    let safe = !lty.label.is_none()
+                        && is_safe(gasn.perms[lty.label], gasn.flags[lty.label]);
+                    self.raw_ptr_types
+                        .push(Some(HashSet::new()).filter(|_| safe));
+                }
+            }
+        }
+    }
+
+    /// Whether something the rewrites make safe, relying on the signatures of the functions in
+    /// `rewritten`, is actually made safe.
+    fn is_rewritten(&self, rewritten: &Rewritten) -> bool {
+        rewritten.as_ref().map_or(false, |deps| {
+            deps.iter().all(|ldid| self.scanned.contains(ldid))
+        })
+    }
+
+    fn count(&self, items: &[Rewritten]) -> Count {
+        let mut count = Count::default();
+        for rewritten in items {
+            count.add(!self.is_rewritten(rewritten));
+        }
+        count
+    }
+
+    /// Find the `unsafe` blocks that are no longer needed, and build the edits that remove them.
+    /// This must be called after every function has been added.
+    pub fn finish(&mut self, tcx: TyCtxt) -> Vec<TextEdit> {
+        let mut remaining = self
+            .ops
+            .iter()
+            .filter(|(_, rewritten)| !self.is_rewritten(rewritten))
+            .map(|&(span, _)| span)
+            .collect::<Vec<_>>();
+        let mut blocks = Vec::new();
+        for ldid in tcx.hir().body_owners() {
+            let body = tcx.hir().body(tcx.hir().body_owned_by(ldid));
+            let mut collector = BlockCollector { blocks: Vec::new() };
+            collector.visit_body(body);
+            let scanned = self.scanned.contains(&ldid);
+            blocks.extend(collector.blocks.into_iter().map(|span| (span, scanned)));
+            // Anything in a function we didn't scan may need an enclosing `unsafe` block.
+            if !scanned {
+                remaining.push(body.value.span);
+            }
+        }
+
+        // Each remaining operation needs its innermost enclosing block.
+        let mut needed = HashSet::new();
+        for &op in &remaining {
+            let innermost = blocks
+                .iter()
+                .map(|&(span, _)| span)
+                .filter(|span| span.contains(op))
+                .min_by_key(|span| span.hi() - span.lo());
+            needed.extend(innermost);
+        }
+
+        let mut edits = Vec::new();
+        for &(span, scanned) in &blocks {
+            let edit = if scanned && !needed.contains(&span) {
+                remove_unsafe_keyword(tcx, span)
+            } else {
+                None
+            };
+            self.blocks.add(edit.is_none());
+            edits.extend(edit);
+        }
+        edits
+    }
+}
+
+impl fmt::Display for UnsafeReduction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let counts = [
+            ("unsafe blocks", self.blocks),
+            ("raw pointer types", self.count(&self.raw_ptr_types)),
+            ("raw pointer dereferences", self.count(&self.raw_derefs)),
+        ];
+        for (name, count) in counts {
+            writeln!(f, "{}: {} -> {}", name, count.before, count.after)?;
+        }
+        Ok(())
+    }
+}
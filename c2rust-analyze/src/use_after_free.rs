@@ -0,0 +1,135 @@
+//! Detection of pointers that may be used after they're freed.
+//!
+//! `FREE` only says that a pointer is passed to `free` somewhere, not where its ownership ends.
+//! A call that consumes a pointer, like `free(p)` or `Box::from_raw(p)`, is recorded as a move out
+//! of the local holding it.  [`find`] then looks for a later use of the same local on some path
+//! from the call that doesn't assign it a new value first.  Such a use is a potential
+//! use-after-free.  It also means the pointer can't become a `Box`: the `drop(p)` that replaces
+//! the `free` would move `p`, and the later use wouldn't compile.  So the consumed pointer, and
+//! the pointers it's copied from, are never `OWNED`, and the use is reported as a warning.
+use crate::dataflow::Origin;
+use crate::pointer_id::PointerId;
+use rustc_middle::mir::visit::{MutatingUseContext, PlaceContext, Visitor};
+use rustc_middle::mir::{BasicBlock, Body, Local, Location, Operand, Place, Rvalue, StatementKind};
+use rustc_span::Span;
+use std::collections::{HashSet, VecDeque};
+
+/// A pointer moved out of `local` at `origin`, by a call that takes ownership of its memory.
+#[derive(Clone, Copy, Debug)]
+pub struct Move {
+    pub local: Local,
+    pub ptr: PointerId,
+    pub origin: Origin,
+}
+
+/// A use of `local` after its pointer was moved out by `moved`.
+#[derive(Clone, Copy, Debug)]
+pub struct UseAfterFree {
+    pub moved: Move,
+    pub span: Span,
+}
+
+/// How a statement or terminator touches the local being tracked.
+#[derive(Default)]
+struct LocalUses {
+    local: Option<Local>,
+    used: bool,
+    assigned: bool,
+}
+
+impl<'tcx> Visitor<'tcx> for LocalUses {
+    fn visit_place(&mut self, place: &Place<'tcx>, context: PlaceContext, location: Location) {
+        if Some(place.local) == self.local {
+            match context {
+                PlaceContext::MutatingUse(
+                    MutatingUseContext::Store
+                    | MutatingUseContext::Call
+                    | MutatingUseContext::AsmOutput,
+                ) if place.projection.is_empty() => self.assigned = true,
+                PlaceContext::NonUse(_) | PlaceContext::MutatingUse(MutatingUseContext::Drop) => {}
+                _ => self.used = true,
+            }
+        }
+        self.super_place(place, context, location);
+    }
+}
+
+/// Check how the statement or terminator at `loc` touches `local`.
+fn uses_at(mir: &Body, local: Local, loc: Location) -> LocalUses {
+    let mut uses = LocalUses {
+        local: Some(local),
+        ..LocalUses::default()
+    };
+    let bb = &mir.basic_blocks()[loc.block];
+    match bb.statements.get(loc.statement_index) {
+        Some(stmt) => uses.visit_statement(stmt, loc),
+        None => uses.visit_terminator(bb.terminator(), loc),
+    }
+    uses
+}
+
+/// Find the local that `local` was copied from, if it holds a temporary copy of another local made
+/// earlier in the block of `loc`, as for the argument of a call like `free(p as *mut c_void)`.
+fn root_local(mir: &Body, mut local: Local, loc: Location) -> Local {
+    let bb = &mir.basic_blocks()[loc.block];
+    for stmt in bb.statements[..loc.statement_index].iter().rev() {
+        let (pl, rv) = match stmt.kind {
+            StatementKind::Assign(ref x) => (x.0, &x.1),
+            _ => continue,
+        };
+        if pl.as_local() != Some(local) {
+            continue;
+        }
+        let src = match *rv {
+            Rvalue::Use(Operand::Copy(src) | Operand::Move(src)) => src,
+            Rvalue::Cast(_, Operand::Copy(src) | Operand::Move(src), _) => src,
+            Rvalue::CopyForDeref(src) => src,
+            _ => break,
+        };
+        match src.as_local() {
+            Some(src) => local = src,
+            None => break,
+        }
+    }
+    local
+}
+
+/// Find the first use of the local in `m` on each path from the call that moved out of it,
+/// stopping where the local is assigned again.  Cleanup paths are ignored.
+fn find_one(mir: &Body, mut m: Move) -> Option<UseAfterFree> {
+    m.local = root_local(mir, m.local, m.origin.loc);
+    let succs = |bb: BasicBlock| mir.basic_blocks()[bb].terminator().successors();
+    let mut seen = HashSet::new();
+    let mut queue = succs(m.origin.loc.block).collect::<VecDeque<_>>();
+    while let Some(bb) = queue.pop_front() {
+        if !seen.insert(bb) || mir.basic_blocks()[bb].is_cleanup {
+            continue;
+        }
+        let data = &mir.basic_blocks()[bb];
+        let mut killed = false;
+        for statement_index in 0..=data.statements.len() {
+            let loc = Location {
+                block: bb,
+                statement_index,
+            };
+            let uses = uses_at(mir, m.local, loc);
+            if uses.used {
+                let span = mir.source_info(loc).span;
+                return Some(UseAfterFree { moved: m, span });
+            }
+            if uses.assigned {
+                killed = true;
+                break;
+            }
+        }
+        if !killed {
+            queue.extend(succs(bb));
+        }
+    }
+    None
+}
+
+/// Find the uses of pointers after the calls in `moves` consumed them.
+pub fn find(mir: &Body, moves: &[Move]) -> Vec<UseAfterFree> {
+    moves.iter().filter_map(|&m| find_one(mir, m)).collect()
+}
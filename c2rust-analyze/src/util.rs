@@ -3,11 +3,15 @@ use crate::trivial::IsTrivial;
 use rustc_hir::def::DefKind;
 use rustc_hir::def_id::DefId;
 use rustc_middle::mir::{
-    BasicBlock, BasicBlockData, Field, Local, Location, Mutability, Operand, Place, PlaceElem,
-    PlaceRef, ProjectionElem, Rvalue, Statement, StatementKind,
+    BasicBlock, BasicBlockData, Body, Field, Local, LocalKind, Location, Mutability, Operand,
+    Place, PlaceElem, PlaceRef, ProjectionElem, Rvalue, Statement, StatementKind,
 };
-use rustc_middle::ty::{self, AdtDef, DefIdTree, SubstsRef, Ty, TyCtxt, TyKind, UintTy};
+use rustc_middle::ty::{
+    self, AdtDef, ClosureKind, DefIdTree, FnSig, IntTy, SubstsRef, Ty, TyCtxt, TyKind, UintTy,
+};
+use rustc_span::{sym, Symbol};
 use std::fmt::Debug;
+use std::iter;
 
 #[derive(Debug)]
 pub enum RvalueDesc<'tcx> {
@@ -33,6 +37,19 @@ pub enum RvalueDesc<'tcx> {
     },
 }
 
+/// Check whether `rv` takes the raw address of a place inside a packed struct that may not be
+/// aligned for its type, as `addr_of!(s.field)` does for most fields of a `#[repr(packed)]`
+/// struct.  Such a pointer can't become a reference.
+pub fn is_packed_field_addr<'tcx>(tcx: TyCtxt<'tcx>, mir: &Body<'tcx>, rv: &Rvalue<'tcx>) -> bool {
+    match *rv {
+        Rvalue::AddressOf(_, pl) => {
+            let param_env = tcx.param_env(mir.source.def_id());
+            rustc_const_eval::util::is_disaligned(tcx, mir, param_env, pl)
+        }
+        _ => false,
+    }
+}
+
 pub fn describe_rvalue<'tcx>(rv: &Rvalue<'tcx>) -> Option<RvalueDesc<'tcx>> {
     Some(match *rv {
         Rvalue::Use(ref op) => match *op {
@@ -122,6 +139,17 @@ pub enum Callee<'tcx> {
         substs: SubstsRef<'tcx>,
     },
 
+    /// A call to a local closure through one of the `Fn` traits, as in `f(x)`, which MIR
+    /// represents as `<[closure] as Fn<(T,)>>::call(&f, (x,))`.  `def_id` is the closure itself.
+    LocalClosure { def_id: DefId },
+
+    /// A call to a trait method through a trait object, such as `<dyn Trait>::method(p)`.  The
+    /// function that actually runs isn't known statically, so only its signature can be used.
+    Virtual {
+        def_id: DefId,
+        substs: SubstsRef<'tcx>,
+    },
+
     /// `<*mut T>::offset` or `<*const T>::offset`.
     PtrOffset {
         pointee_ty: Ty<'tcx>,
@@ -140,6 +168,48 @@ pub enum Callee<'tcx> {
         mutbl: Mutability,
     },
 
+    /// `MaybeUninit::as_ptr` and `MaybeUninit::as_mut_ptr`, which return a pointer to the
+    /// possibly uninitialized contents.
+    MaybeUninitAsPtr { mutbl: Mutability },
+
+    /// `Vec::as_ptr` and `Vec::as_mut_ptr`, which return a pointer to the vector's buffer.
+    VecAsPtr { mutbl: Mutability },
+
+    /// `Box::into_raw`, which gives up ownership of the allocation to the returned pointer.
+    BoxIntoRaw,
+
+    /// `Box::from_raw`, which takes ownership of the allocation the pointer points to.
+    BoxFromRaw,
+
+    /// `CStr::from_ptr`, which wraps a pointer to a NUL-terminated string.
+    CStrFromPtr,
+
+    /// `CStr::as_ptr`, which returns a pointer to the NUL-terminated string.  This is also what
+    /// `CString::as_ptr` calls, through `Deref`.
+    CStrAsPtr,
+
+    /// `CString::into_raw`, which gives up ownership of the string to the returned pointer.
+    CStringIntoRaw,
+
+    /// `CString::from_raw`, which takes ownership of a string returned by `into_raw`.
+    CStringFromRaw,
+
+    /// `MaybeUninit::new`, which wraps an initialized value.
+    MaybeUninitNew,
+
+    /// `MaybeUninit::uninit`, which produces a value whose contents aren't initialized.
+    MaybeUninitUninit,
+
+    /// `MaybeUninit::zeroed`, which produces a value whose contents are zero.
+    MaybeUninitZeroed,
+
+    /// `core::mem::zeroed`, which produces a value with every byte zero, so every pointer in it
+    /// is null.
+    MemZeroed,
+
+    /// `MaybeUninit::assume_init`, which unwraps the contents.
+    MaybeUninitAssumeInit,
+
     /// libc::malloc
     Malloc,
 
@@ -152,14 +222,87 @@ pub enum Callee<'tcx> {
     /// libc::realloc
     Realloc,
 
+    /// libc::aligned_alloc
+    AlignedAlloc,
+
+    /// libc::posix_memalign
+    PosixMemalign,
+
+    /// libc::mmap
+    Mmap,
+
+    /// libc::munmap
+    Munmap,
+
+    /// libc::qsort
+    Qsort,
+
+    /// libc::bsearch
+    Bsearch,
+
     /// core::ptr::is_null
     IsNull,
+
+    /// `core::mem::transmute`
+    Transmute,
+
+    /// `core::ptr::null` or `core::ptr::null_mut`
+    Null,
+
+    /// `Option::unwrap` or `Option::expect` on an `Option` of a function pointer, which is how
+    /// c2rust calls a nullable C function pointer: `f.expect("non-null function pointer")(x)`.
+    /// The result is the function pointer in the `Option`.
+    FnPtrUnwrap,
+
+    /// One of the `core::fmt::ArgumentV1` constructors that `format_args!` expands to, and so
+    /// `println!`, `format!`, `write!`, and the logging macros.  The first argument is a
+    /// reference to the value to be formatted, which is only read, and doesn't outlive the
+    /// formatting.
+    FmtArgument,
+
+    /// One of the `core::intrinsics::atomic_*` intrinsics that access memory, which is what the
+    /// GCC `__atomic_*` and `__sync_*` builtins transpile to.  The first argument is the pointer
+    /// to the memory accessed.
+    AtomicIntrinsic { op: AtomicOp },
+
+    /// A method of `AtomicPtr<T>` that takes or returns a pointer, such as `load`, `store`, or
+    /// `compare_exchange`.
+    AtomicPtr,
+}
+
+/// What an atomic intrinsic does with the memory its first argument points to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AtomicOp {
+    /// `atomic_load(src) -> T`
+    Load,
+    /// `atomic_store(dst, val)`
+    Store,
+    /// `atomic_xchg(dst, val) -> T`, and the read-modify-write operations like `atomic_xadd`
+    Modify,
+    /// `atomic_cxchg(dst, old, new) -> (T, bool)`
+    CompareExchange,
+}
+
+impl AtomicOp {
+    /// Get the operation of the atomic intrinsic `name`, such as `atomic_load_seqcst`.  Fences
+    /// don't access memory, and return `None`.
+    fn from_intrinsic_name(name: &str) -> Option<AtomicOp> {
+        let op = name.strip_prefix("atomic_")?.split('_').next()?;
+        match op {
+            "load" => Some(AtomicOp::Load),
+            "store" => Some(AtomicOp::Store),
+            "xchg" | "xadd" | "xsub" | "and" | "nand" | "or" | "xor" | "max" | "min" | "umax"
+            | "umin" => Some(AtomicOp::Modify),
+            "cxchg" | "cxchgweak" => Some(AtomicOp::CompareExchange),
+            _ => None,
+        }
+    }
 }
 
 pub fn ty_callee<'tcx>(tcx: TyCtxt<'tcx>, ty: Ty<'tcx>) -> Callee<'tcx> {
     let is_trivial = || {
         let is_trivial = ty.fn_sig(tcx).is_trivial(tcx);
-        eprintln!("{ty:?} is trivial: {is_trivial}");
+        log::trace!("{ty:?} is trivial: {is_trivial}");
         is_trivial
     };
 
@@ -167,6 +310,19 @@ pub fn ty_callee<'tcx>(tcx: TyCtxt<'tcx>, ty: Ty<'tcx>) -> Callee<'tcx> {
         ty::FnDef(did, substs) => {
             if is_trivial() {
                 Callee::Trivial
+            } else if let Some(closure_did) = local_closure_callee(tcx, did, substs) {
+                Callee::LocalClosure {
+                    def_id: closure_did,
+                }
+            } else if is_virtual_call(tcx, did, substs) {
+                Callee::Virtual {
+                    def_id: did,
+                    substs,
+                }
+            } else if let Some(callee) = fn_ptr_option_callee(tcx, did, substs) {
+                callee
+            } else if is_fmt_argument(tcx, did) {
+                Callee::FmtArgument
             } else if let Some(callee) = builtin_callee(tcx, did) {
                 callee
             } else if !did.is_local() || tcx.def_kind(tcx.parent(did)) == DefKind::ForeignMod {
@@ -189,10 +345,104 @@ pub fn ty_callee<'tcx>(tcx: TyCtxt<'tcx>, ty: Ty<'tcx>) -> Callee<'tcx> {
     }
 }
 
+/// Check whether calling the function `did` with `substs` dispatches through a trait object, which
+/// is the case for trait methods whose `Self` type is `dyn Trait`.
+fn is_virtual_call(tcx: TyCtxt, did: DefId, substs: SubstsRef) -> bool {
+    tcx.trait_of_item(did).is_some()
+        && matches!(
+            substs.types().next().map(|ty| ty.kind()),
+            Some(TyKind::Dynamic(..))
+        )
+}
+
+/// If `did` with `substs` is a method of an `Option` of a function pointer that unwraps it or
+/// checks whether it's null, return how to handle it.  The checks are trivial, since they only
+/// look at whether the function pointer is null.
+fn fn_ptr_option_callee<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    did: DefId,
+    substs: SubstsRef<'tcx>,
+) -> Option<Callee<'tcx>> {
+    let impl_did = tcx.impl_of_method(did)?;
+    if tcx.impl_trait_ref(impl_did).is_some() {
+        return None;
+    }
+    match *tcx.type_of(impl_did).kind() {
+        TyKind::Adt(adt, _) if tcx.is_diagnostic_item(sym::Option, adt.did()) => {}
+        _ => return None,
+    }
+    if !matches!(substs.types().next()?.kind(), TyKind::FnPtr(_)) {
+        return None;
+    }
+    match tcx.item_name(did) {
+        sym::unwrap | sym::expect => Some(Callee::FnPtrUnwrap),
+        name if matches!(name.as_str(), "is_some" | "is_none") => Some(Callee::Trivial),
+        _ => None,
+    }
+}
+
+/// Check whether `did` is a constructor of `core::fmt::ArgumentV1`, like `new_display` or
+/// `new_debug`.
+fn is_fmt_argument(tcx: TyCtxt, did: DefId) -> bool {
+    tcx.impl_of_method(did).map_or(false, |impl_did| {
+        tcx.is_diagnostic_item(sym::ArgumentV1Methods, impl_did)
+    })
+}
+
+/// If calling the function `did` with `substs` calls a local closure through `Fn`, `FnMut`, or
+/// `FnOnce`, return the `DefId` of the closure.
+fn local_closure_callee(tcx: TyCtxt, did: DefId, substs: SubstsRef) -> Option<DefId> {
+    let trait_did = tcx.trait_of_item(did)?;
+    tcx.fn_trait_kind_from_lang_item(trait_did)?;
+    match *substs.types().next()?.kind() {
+        TyKind::Closure(closure_did, _) if closure_did.is_local() => Some(closure_did),
+        _ => None,
+    }
+}
+
+/// Get the name of the function `did`, for printing.  Closures have no name of their own, so
+/// they're named by their path, like `f::{closure#0}`.
+pub fn fn_name(tcx: TyCtxt, did: DefId) -> Symbol {
+    tcx.opt_item_name(did)
+        .unwrap_or_else(|| Symbol::intern(&tcx.def_path_str(did)))
+}
+
+/// Get the signature of the body of the function `did`.  For closures, this is the signature of
+/// the closure's MIR, which takes the closure environment followed by the untupled arguments,
+/// rather than the signature of its `Fn` trait method.
+pub fn body_fn_sig(tcx: TyCtxt, did: DefId) -> FnSig {
+    let substs = match *tcx.type_of(did).kind() {
+        TyKind::Closure(_, substs) => substs,
+        _ => return tcx.erase_late_bound_regions(tcx.fn_sig(did)),
+    };
+    let closure = substs.as_closure();
+    let sig = tcx.erase_late_bound_regions(closure.sig());
+    let closure_ty = tcx.type_of(did);
+    let env_ty = match closure.kind() {
+        ClosureKind::Fn => tcx.mk_imm_ref(tcx.lifetimes.re_erased, closure_ty),
+        ClosureKind::FnMut => tcx.mk_mut_ref(tcx.lifetimes.re_erased, closure_ty),
+        ClosureKind::FnOnce => closure_ty,
+    };
+    tcx.mk_fn_sig(
+        iter::once(env_ty).chain(sig.inputs()[0].tuple_fields()),
+        sig.output(),
+        sig.c_variadic,
+        sig.unsafety,
+        sig.abi,
+    )
+}
+
 fn builtin_callee(tcx: TyCtxt, did: DefId) -> Option<Callee> {
     let name = tcx.item_name(did);
 
     match name.as_str() {
+        name if name.starts_with("atomic_") && ty::util::is_intrinsic(tcx, did) => {
+            let op = AtomicOp::from_intrinsic_name(name)?;
+            Some(Callee::AtomicIntrinsic { op })
+        }
+
+        _ if is_atomic_ptr_method(tcx, did) => Some(Callee::AtomicPtr),
+
         "offset" => {
             // The `offset` inherent method of `*const T` and `*mut T`.
             let parent_did = tcx.parent(did);
@@ -220,17 +470,26 @@ fn builtin_callee(tcx: TyCtxt, did: DefId) -> Option<Callee> {
                 return None;
             }
             let parent_impl_ty = tcx.type_of(parent_did);
+            let mutbl = match name {
+                "as_ptr" => Mutability::Not,
+                "as_mut_ptr" => Mutability::Mut,
+                _ => unreachable!(),
+            };
+            if is_maybe_uninit(tcx, parent_impl_ty) {
+                return Some(Callee::MaybeUninitAsPtr { mutbl });
+            }
+            if SafePtr::of(tcx, parent_impl_ty) == Some(SafePtr::Vec) {
+                return Some(Callee::VecAsPtr { mutbl });
+            }
+            if name == "as_ptr" && is_c_str(tcx, parent_impl_ty) {
+                return Some(Callee::CStrAsPtr);
+            }
             let elem_ty = match *parent_impl_ty.kind() {
                 TyKind::Array(ty, _) => ty,
                 TyKind::Slice(ty) => ty,
                 TyKind::Str => tcx.mk_mach_uint(UintTy::U8),
                 _ => return None,
             };
-            let mutbl = match name {
-                "as_ptr" => Mutability::Not,
-                "as_mut_ptr" => Mutability::Mut,
-                _ => unreachable!(),
-            };
             Some(Callee::SliceAsPtr {
                 pointee_ty: parent_impl_ty,
                 elem_ty,
@@ -238,6 +497,61 @@ fn builtin_callee(tcx: TyCtxt, did: DefId) -> Option<Callee> {
             })
         }
 
+        name @ ("into_raw" | "from_raw") => {
+            // The associated functions of `Box<T>` and `CString`.
+            let parent_did = tcx.parent(did);
+            if tcx.def_kind(parent_did) != DefKind::Impl || tcx.impl_trait_ref(parent_did).is_some()
+            {
+                return None;
+            }
+            let parent_impl_ty = tcx.type_of(parent_did);
+            match name {
+                "into_raw" if parent_impl_ty.is_box() => Some(Callee::BoxIntoRaw),
+                "from_raw" if parent_impl_ty.is_box() => Some(Callee::BoxFromRaw),
+                "into_raw" if is_c_string(tcx, parent_impl_ty) => Some(Callee::CStringIntoRaw),
+                "from_raw" if is_c_string(tcx, parent_impl_ty) => Some(Callee::CStringFromRaw),
+                _ => None,
+            }
+        }
+
+        "from_ptr" => {
+            // The associated function of `CStr`.
+            let parent_did = tcx.parent(did);
+            if tcx.def_kind(parent_did) != DefKind::Impl
+                || tcx.impl_trait_ref(parent_did).is_some()
+                || !is_c_str(tcx, tcx.type_of(parent_did))
+            {
+                return None;
+            }
+            Some(Callee::CStrFromPtr)
+        }
+
+        "zeroed" if tcx.def_kind(tcx.parent(did)) == DefKind::Mod => {
+            // The free function `core::mem::zeroed`.
+            if tcx.item_name(tcx.parent(did)).as_str() != "mem" {
+                return None;
+            }
+            Some(Callee::MemZeroed)
+        }
+
+        name @ ("new" | "uninit" | "zeroed" | "assume_init") => {
+            // The inherent methods of `MaybeUninit<T>`.
+            let parent_did = tcx.parent(did);
+            if tcx.def_kind(parent_did) != DefKind::Impl
+                || tcx.impl_trait_ref(parent_did).is_some()
+                || !is_maybe_uninit(tcx, tcx.type_of(parent_did))
+            {
+                return None;
+            }
+            match name {
+                "new" => Some(Callee::MaybeUninitNew),
+                "uninit" => Some(Callee::MaybeUninitUninit),
+                "zeroed" => Some(Callee::MaybeUninitZeroed),
+                "assume_init" => Some(Callee::MaybeUninitAssumeInit),
+                _ => unreachable!(),
+            }
+        }
+
         "malloc" => {
             if matches!(tcx.def_kind(tcx.parent(did)), DefKind::ForeignMod) {
                 return Some(Callee::Malloc);
@@ -266,6 +580,55 @@ fn builtin_callee(tcx: TyCtxt, did: DefId) -> Option<Callee> {
             None
         }
 
+        "aligned_alloc" => {
+            if matches!(tcx.def_kind(tcx.parent(did)), DefKind::ForeignMod) {
+                return Some(Callee::AlignedAlloc);
+            }
+            None
+        }
+
+        "posix_memalign" => {
+            if matches!(tcx.def_kind(tcx.parent(did)), DefKind::ForeignMod) {
+                return Some(Callee::PosixMemalign);
+            }
+            None
+        }
+
+        "mmap" => {
+            if matches!(tcx.def_kind(tcx.parent(did)), DefKind::ForeignMod) {
+                return Some(Callee::Mmap);
+            }
+            None
+        }
+
+        "munmap" => {
+            if matches!(tcx.def_kind(tcx.parent(did)), DefKind::ForeignMod) {
+                return Some(Callee::Munmap);
+            }
+            None
+        }
+
+        "qsort" => {
+            if matches!(tcx.def_kind(tcx.parent(did)), DefKind::ForeignMod) {
+                return Some(Callee::Qsort);
+            }
+            None
+        }
+
+        "bsearch" => {
+            if matches!(tcx.def_kind(tcx.parent(did)), DefKind::ForeignMod) {
+                return Some(Callee::Bsearch);
+            }
+            None
+        }
+
+        "transmute" => {
+            if ty::util::is_intrinsic(tcx, did) {
+                return Some(Callee::Transmute);
+            }
+            None
+        }
+
         "is_null" => {
             // The `offset` inherent method of `*const T` and `*mut T`.
             let parent_did = tcx.parent(did);
@@ -283,26 +646,152 @@ fn builtin_callee(tcx: TyCtxt, did: DefId) -> Option<Callee> {
             Some(Callee::IsNull)
         }
 
+        "null" | "null_mut" => {
+            // The free functions `core::ptr::null` and `core::ptr::null_mut`.
+            let parent_did = tcx.parent(did);
+            if tcx.def_kind(parent_did) != DefKind::Mod
+                || tcx.item_name(parent_did).as_str() != "ptr"
+            {
+                return None;
+            }
+            Some(Callee::Null)
+        }
+
         _ => {
-            eprintln!("name: {name:?}");
+            log::trace!("name: {name:?}");
+            None
+        }
+    }
+}
+
+/// A safe pointer type from the standard library.  These are labeled like raw pointers, with a
+/// `PointerId` of their own, so that pointers copied into and out of code that already uses them
+/// are connected to them.  Their types are never rewritten.  `Option<&T>` needs no special case,
+/// since the reference inside it is labeled already.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SafePtr {
+    Box,
+    Vec,
+    NonNull,
+}
+
+impl SafePtr {
+    /// Get the kind of safe pointer `ty` is, if it's one.
+    pub fn of(tcx: TyCtxt, ty: Ty) -> Option<SafePtr> {
+        let adt = match *ty.kind() {
+            TyKind::Adt(adt, _) => adt,
+            _ => return None,
+        };
+        if adt.is_box() {
+            Some(SafePtr::Box)
+        } else if tcx.is_diagnostic_item(sym::Vec, adt.did()) {
+            Some(SafePtr::Vec)
+        } else if tcx.crate_name(adt.did().krate).as_str() == "core"
+            && tcx.item_name(adt.did()).as_str() == "NonNull"
+        {
+            Some(SafePtr::NonNull)
+        } else {
             None
         }
     }
 }
 
+/// Check whether `ty` is `core::ffi::CStr`.
+pub fn is_c_str(tcx: TyCtxt, ty: Ty) -> bool {
+    match *ty.kind() {
+        TyKind::Adt(adt, _) => {
+            tcx.crate_name(adt.did().krate).as_str() == "core"
+                && tcx.item_name(adt.did()).as_str() == "CStr"
+        }
+        _ => false,
+    }
+}
+
+/// Check whether `ty` is `alloc::ffi::CString`.
+pub fn is_c_string(tcx: TyCtxt, ty: Ty) -> bool {
+    match *ty.kind() {
+        TyKind::Adt(adt, _) => tcx.is_diagnostic_item(sym::cstring_type, adt.did()),
+        _ => false,
+    }
+}
+
+/// Check whether `ty` is a raw pointer to bytes (`u8` or `i8`), which is how `c_char` pointers
+/// appear after type aliases are resolved.
+pub fn is_byte_ptr(ty: Ty) -> bool {
+    match *ty.kind() {
+        TyKind::RawPtr(tm) => matches!(
+            tm.ty.kind(),
+            TyKind::Uint(UintTy::U8) | TyKind::Int(IntTy::I8)
+        ),
+        _ => false,
+    }
+}
+
+/// Check whether `did` is an inherent method of `core::sync::atomic::AtomicPtr<T>`.
+fn is_atomic_ptr_method(tcx: TyCtxt, did: DefId) -> bool {
+    let parent_did = tcx.parent(did);
+    if tcx.def_kind(parent_did) != DefKind::Impl || tcx.impl_trait_ref(parent_did).is_some() {
+        return false;
+    }
+    match *tcx.type_of(parent_did).kind() {
+        TyKind::Adt(adt, _) => tcx
+            .def_path_str(adt.did())
+            .ends_with("sync::atomic::AtomicPtr"),
+        _ => false,
+    }
+}
+
+/// Check whether `ty` is `core::mem::MaybeUninit<T>` for some `T`.
+pub fn is_maybe_uninit(tcx: TyCtxt, ty: Ty) -> bool {
+    match *ty.kind() {
+        TyKind::Adt(adt, _) => tcx.lang_items().maybe_uninit() == Some(adt.did()),
+        _ => false,
+    }
+}
+
+/// Check whether `ty` is `core::ffi::c_void`, which `libc::c_void` re-exports.
+pub fn is_c_void(tcx: TyCtxt, ty: Ty) -> bool {
+    match *ty.kind() {
+        TyKind::Adt(adt, _) => {
+            tcx.def_path(adt.did()).data[0].to_string() == "ffi"
+                && tcx.item_name(adt.did()).as_str() == "c_void"
+        }
+        _ => false,
+    }
+}
+
+/// Check whether `ty` is a raw pointer to `c_void`.
+pub fn is_c_void_ptr(tcx: TyCtxt, ty: Ty) -> bool {
+    match *ty.kind() {
+        TyKind::RawPtr(tm) => is_c_void(tcx, tm.ty),
+        _ => false,
+    }
+}
+
+/// Check whether a cast from `from` to `to` converts between a `c_void` pointer and a pointer to
+/// bytes (`u8` or `i8`), in either direction.  These casts treat the `c_void` pointer as a byte
+/// buffer.
+pub fn is_byte_cast<'tcx>(tcx: TyCtxt<'tcx>, from: Ty<'tcx>, to: Ty<'tcx>) -> bool {
+    (is_c_void_ptr(tcx, from) && is_byte_ptr(to)) || (is_byte_ptr(from) && is_c_void_ptr(tcx, to))
+}
+
 pub fn lty_project<'tcx, L: Debug>(
     lty: LabeledTy<'tcx, L>,
     proj: &PlaceElem<'tcx>,
     mut adt_func: impl FnMut(LabeledTy<'tcx, L>, AdtDef<'tcx>, Field) -> LabeledTy<'tcx, L>,
 ) -> LabeledTy<'tcx, L> {
     match *proj {
+        ProjectionElem::Deref if lty.ty.is_box() => {
+            // `Box<T, A>` also has the allocator as an argument.
+            lty.args[0]
+        }
         ProjectionElem::Deref => {
             assert!(matches!(lty.kind(), TyKind::Ref(..) | TyKind::RawPtr(..)));
             assert_eq!(lty.args.len(), 1);
             lty.args[0]
         }
         ProjectionElem::Field(f, _) => match lty.kind() {
-            TyKind::Tuple(_) => lty.args[f.index()],
+            TyKind::Tuple(_) | TyKind::Closure(..) => lty.args[f.index()],
             TyKind::Adt(def, _) => adt_func(lty, *def, f),
             _ => panic!("Field projection is unsupported on type {:?}", lty),
         },
@@ -323,6 +812,52 @@ pub fn get_cast_place<'tcx>(rv: &Rvalue<'tcx>) -> Option<Place<'tcx>> {
     }
 }
 
+/// Check whether `op` is the integer constant zero, as in the null pointer cast `0 as *mut T`.
+pub fn is_null_const(op: &Operand) -> bool {
+    match *op {
+        Operand::Constant(ref c) => c.literal.try_to_scalar_int().map_or(false, |x| x.is_null()),
+        _ => false,
+    }
+}
+
+/// Return `pl` followed by the places it was copied from.  MIR often copies a value into a
+/// temporary before using it, as in `_3 = _1; _2 = is_null(move _3)`; given `_3`, this returns `[_3,
+/// _1]`.  Only temporaries with a single assignment of the form `_t = pl` are followed.
+pub fn copy_sources<'tcx>(mir: &Body<'tcx>, pl: Place<'tcx>) -> Vec<Place<'tcx>> {
+    let mut out = vec![pl];
+    let mut cur = pl;
+    while let Some(local) = cur.as_local() {
+        if out.len() > mir.local_decls.len() {
+            break;
+        }
+        let src = match temp_assignment(mir, local) {
+            Some(&Rvalue::Use(Operand::Copy(src) | Operand::Move(src))) => src,
+            Some(&Rvalue::CopyForDeref(src)) => src,
+            _ => break,
+        };
+        out.push(src);
+        cur = src;
+    }
+    out
+}
+
+/// The value assigned to `local`, if it's a temporary that's assigned exactly once.
+pub fn temp_assignment<'a, 'tcx>(mir: &'a Body<'tcx>, local: Local) -> Option<&'a Rvalue<'tcx>> {
+    if mir.local_kind(local) != LocalKind::Temp {
+        return None;
+    }
+    let mut assigns = mir
+        .basic_blocks()
+        .iter()
+        .flat_map(|bb_data| &bb_data.statements)
+        .filter_map(get_assign_sides)
+        .filter(|&(dest, _)| dest.as_local() == Some(local));
+    match (assigns.next(), assigns.next()) {
+        (Some((_, rv)), None) => Some(rv),
+        _ => None,
+    }
+}
+
 pub fn terminator_location(block: BasicBlock, block_data: &BasicBlockData) -> Location {
     Location {
         block,
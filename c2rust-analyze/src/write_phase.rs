@@ -0,0 +1,301 @@
+//! Flow-sensitive refinement of `&mut` pointers.
+//!
+//! Permissions are assigned once per pointer for the whole function, so a pointer that's only
+//! written while its target is being initialized becomes `&mut` at every use.  This pass finds the
+//! point after which such a pointer is never written through again, and shadows it there with a
+//! shared reborrow, `let p = &*p;`.  The uses after that point see a `&T`, which is `Copy` and can
+//! be handed out any number of times without explicit reborrows.
+//!
+//! A use of the pointer needs `WRITE` if it writes through the pointer or reassigns it.  A use
+//! that copies the pointer into another local with `WRITE`, such as a `&mut` reborrow or the
+//! result of a call that returns its argument, makes every use of that local count as well, since
+//! a `&mut` derived from `p` must be dead before `p` can be reborrowed as shared.  A pointer that
+//! has its own address taken, or that's stored with `WRITE` somewhere other than a local, isn't
+//! split.  Borrowing the pointer only to format it, as `println!("{:p}", p)` does, is just a
+//! read.
+//!
+//! To keep the rewrite simple, only the statements of the function's outermost block are
+//! considered as split points: the pointer is shadowed after the last such statement that
+//! contains a use needing `WRITE`, as long as some later statement still uses the pointer.
+use crate::context::{AnalysisCtxt, Assignment, FlagSet, PermissionSet};
+use crate::rewrite::TextEdit;
+use crate::type_desc::{self, Ownership, Quantity};
+use crate::util::{self, Callee};
+use rustc_hir as hir;
+use rustc_index::bit_set::BitSet;
+use rustc_middle::mir::visit::{MutatingUseContext, NonMutatingUseContext, PlaceContext, Visitor};
+use rustc_middle::mir::{
+    Body, BorrowKind, Local, LocalKind, Location, Operand, Place, Rvalue, Statement, StatementKind,
+    Terminator, TerminatorKind, VarDebugInfoContents,
+};
+use rustc_middle::ty::TyCtxt;
+use rustc_span::{Span, Symbol};
+use std::collections::{HashMap, HashSet};
+
+/// A pointer that can be reborrowed as shared after a statement.
+pub struct Split {
+    pub local: Local,
+    pub name: Symbol,
+    /// The last statement that needs the pointer to be `&mut`.
+    pub after: Span,
+}
+
+impl Split {
+    /// Build the edit that inserts `let p = &*p;` after the split statement.
+    pub fn edit(&self, tcx: TyCtxt) -> TextEdit {
+        let sm = tcx.sess.source_map();
+        let before = sm.span_to_prev_source(self.after).unwrap_or_default();
+        let indent = before.rsplit('\n').next().unwrap_or("");
+        let indent = if indent.trim().is_empty() {
+            indent
+        } else {
+            "    "
+        };
+        let text = format!("\n{}let {} = &*{};", indent, self.name, self.name);
+        TextEdit::new(self.after.shrink_to_hi(), text)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Use {
+    span: Span,
+    write: bool,
+}
+
+struct UseCollector<'a, 'tcx> {
+    acx: &'a AnalysisCtxt<'a, 'tcx>,
+    asn: &'a Assignment<'a>,
+    mir: &'a Body<'tcx>,
+    uses: HashMap<Local, Vec<Use>>,
+    /// `(a, b)` if `b` receives a copy of the pointer in `a` and has `WRITE`.
+    edges: Vec<(Local, Local)>,
+    /// Locals whose address is taken, or that are stored with `WRITE` outside of a local.
+    escaped: BitSet<Local>,
+    /// Temporaries holding a reference to a value that's passed to `format_args!`.
+    fmt_refs: HashSet<Local>,
+}
+
+impl<'a, 'tcx> UseCollector<'a, 'tcx> {
+    /// Whether any pointer in the type of `pl` has `WRITE`.
+    fn has_write(&self, pl: Place<'tcx>) -> bool {
+        let perms = self.asn.perms();
+        self.acx
+            .type_of(pl)
+            .iter()
+            .any(|lty| !lty.label.is_none() && perms[lty.label].contains(PermissionSet::WRITE))
+    }
+
+    /// The local whose pointer value is copied by `rv`, if any.
+    fn copied_pointer(rv: &Rvalue<'tcx>) -> Option<Local> {
+        match *rv {
+            Rvalue::Use(Operand::Copy(pl) | Operand::Move(pl))
+            | Rvalue::Cast(_, Operand::Copy(pl) | Operand::Move(pl), _)
+            | Rvalue::Ref(_, _, pl)
+            | Rvalue::AddressOf(_, pl) => Some(pl.local),
+            _ => None,
+        }
+    }
+
+    /// Whether the statement at `loc` borrows a value only to format it, as in `_t = &p` for
+    /// `println!("{:p}", p)`.
+    fn is_fmt_borrow(&self, loc: Location) -> bool {
+        let stmt = match self.mir.stmt_at(loc).left() {
+            Some(x) => x,
+            None => return false,
+        };
+        match stmt.kind {
+            StatementKind::Assign(ref x) => {
+                matches!(x.1, Rvalue::Ref(_, BorrowKind::Shared, _))
+                    && x.0.as_local().map_or(false, |l| self.fmt_refs.contains(&l))
+            }
+            _ => false,
+        }
+    }
+
+    /// Record that the pointer in `src` flows into `dest`.
+    fn add_flow(&mut self, src: Local, dest: Place<'tcx>) {
+        if !self.has_write(dest) {
+            return;
+        }
+        match dest.as_local() {
+            Some(dest) => self.edges.push((src, dest)),
+            None => {
+                self.escaped.insert(src);
+            }
+        }
+    }
+}
+
+impl<'a, 'tcx> Visitor<'tcx> for UseCollector<'a, 'tcx> {
+    fn visit_statement(&mut self, stmt: &Statement<'tcx>, loc: Location) {
+        if let StatementKind::Assign(ref x) = stmt.kind {
+            let (pl, ref rv) = **x;
+            if let Some(src) = Self::copied_pointer(rv) {
+                self.add_flow(src, pl);
+            }
+        }
+        self.super_statement(stmt, loc);
+    }
+
+    fn visit_terminator(&mut self, term: &Terminator<'tcx>, loc: Location) {
+        if let TerminatorKind::Call {
+            ref args,
+            destination,
+            ..
+        } = term.kind
+        {
+            for arg in args {
+                if let Operand::Copy(pl) | Operand::Move(pl) = *arg {
+                    self.add_flow(pl.local, destination);
+                }
+            }
+        }
+        self.super_terminator(term, loc);
+    }
+
+    fn visit_place(&mut self, pl: &Place<'tcx>, context: PlaceContext, loc: Location) {
+        let write = match context {
+            PlaceContext::NonUse(_) => return,
+            PlaceContext::MutatingUse(_) => true,
+            PlaceContext::NonMutatingUse(_) => false,
+        };
+        let is_address_of = matches!(
+            context,
+            PlaceContext::NonMutatingUse(NonMutatingUseContext::AddressOf)
+                | PlaceContext::MutatingUse(MutatingUseContext::AddressOf)
+        );
+        if pl.projection.is_empty()
+            && (context.is_borrow() || is_address_of)
+            && !self.is_fmt_borrow(loc)
+        {
+            self.escaped.insert(pl.local);
+        }
+        let span = self.mir.source_info(loc).span;
+        self.uses
+            .entry(pl.local)
+            .or_default()
+            .push(Use { span, write });
+    }
+}
+
+/// Find the pointers of `mir` that can be reborrowed as shared partway through the function.
+pub fn find_splits<'tcx>(
+    acx: &AnalysisCtxt<'_, 'tcx>,
+    asn: &Assignment,
+    mir: &Body<'tcx>,
+) -> Vec<Split> {
+    let tcx = acx.tcx();
+    let ldid = mir.source.def_id().expect_local();
+    let body = tcx.hir().body(tcx.hir().body_owned_by(ldid));
+    let block = match body.value.kind {
+        hir::ExprKind::Block(block, _) => block,
+        _ => return Vec::new(),
+    };
+    let stmts = block.stmts.iter().map(|s| s.span).collect::<Vec<_>>();
+    // The index of the outermost statement containing `span`.  The tail expression comes after
+    // all the statements.
+    let stmt_index = |span: Span| {
+        let span = span.source_callsite();
+        if let Some(i) = stmts.iter().position(|s| s.contains(span)) {
+            return Some(i);
+        }
+        match block.expr {
+            Some(e) if e.span.contains(span) => Some(stmts.len()),
+            _ => None,
+        }
+    };
+
+    let mut fmt_refs = HashSet::new();
+    for bb_data in mir.basic_blocks() {
+        if let TerminatorKind::Call {
+            ref func, ref args, ..
+        } = bb_data.terminator().kind
+        {
+            if matches!(util::ty_callee(tcx, func.ty(mir, tcx)), Callee::FmtArgument) {
+                let srcs = args[0].place().map(|pl| util::copy_sources(mir, pl));
+                fmt_refs.extend(srcs.into_iter().flatten().filter_map(|pl| pl.as_local()));
+            }
+        }
+    }
+
+    let mut collector = UseCollector {
+        acx,
+        asn,
+        mir,
+        uses: HashMap::new(),
+        edges: Vec::new(),
+        escaped: BitSet::new_empty(mir.local_decls.len()),
+        fmt_refs,
+    };
+    collector.visit_body(mir);
+    let UseCollector {
+        uses,
+        edges,
+        escaped,
+        ..
+    } = collector;
+
+    let mut splits = Vec::new();
+    for (local, decl) in mir.local_decls.iter_enumerated() {
+        let is_user = mir.local_kind(local) == LocalKind::Arg || decl.is_user_variable();
+        let ptr = acx.local_tys[local].label;
+        if !is_user || ptr.is_none() || escaped.contains(local) {
+            continue;
+        }
+        let (perms, flags) = (asn.perms()[ptr], asn.flags()[ptr]);
+        if flags.contains(FlagSet::NULLABLE)
+            || type_desc::perms_to_desc(perms, flags) != (Ownership::Mut, Quantity::Single)
+        {
+            continue;
+        }
+        let name = mir.var_debug_info.iter().find_map(|vdi| match vdi.value {
+            VarDebugInfoContents::Place(pl) if pl.as_local() == Some(local) => Some(vdi.name),
+            _ => None,
+        });
+        let name = match name {
+            Some(x) => x,
+            None => continue,
+        };
+
+        // Every use of a `&mut` derived from `local` needs `local` to be `&mut` as well.
+        let mut derived = BitSet::new_empty(mir.local_decls.len());
+        let mut stack = vec![local];
+        while let Some(cur) = stack.pop() {
+            for &(a, b) in &edges {
+                if a == cur && b != local && derived.insert(b) {
+                    stack.push(b);
+                }
+            }
+        }
+        if derived.iter().any(|l| escaped.contains(l)) {
+            continue;
+        }
+        let own_uses = uses.get(&local).map_or(&[][..], |v| &v[..]);
+        let write_uses = own_uses.iter().filter(|u| u.write).chain(
+            derived
+                .iter()
+                .flat_map(|l| uses.get(&l).into_iter().flatten()),
+        );
+
+        let mut last_write = None;
+        let mut ok = true;
+        for u in write_uses {
+            match stmt_index(u.span) {
+                Some(i) => last_write = last_write.max(Some(i)),
+                None => ok = false,
+            }
+        }
+        let last_write = match last_write {
+            Some(i) if ok && i < stmts.len() => i,
+            _ => continue,
+        };
+        let used_later = own_uses
+            .iter()
+            .any(|u| stmt_index(u.span).map_or(false, |i| i > last_write));
+        let after = stmts[last_write];
+        if used_later && !after.from_expansion() {
+            splits.push(Split { local, name, after });
+        }
+    }
+    splits
+}
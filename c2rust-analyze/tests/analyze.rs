@@ -1,4 +1,5 @@
 use common::Analyze;
+use std::fs;
 
 pub mod common;
 
@@ -12,6 +13,56 @@ fn string_casts() {
     Analyze::resolve().run("tests/analyze/string_casts.rs");
 }
 
+/// Running twice with `--cache-dir` gives the same results, and the second run reuses the cached
+/// results of every function.
+#[test]
+fn cache() {
+    let path = "tests/analyze/cache.rs";
+    let _ = fs::remove_dir_all("tests/analyze/cache.rs.cache");
+    let analyze = Analyze::resolve();
+    let run = || fs::read_to_string(analyze.run(path)).unwrap();
+    let first = run();
+    let second = run();
+    assert!(first.contains("cache: 0 of 2 functions unchanged"));
+    assert!(second.contains("cache: 2 of 2 functions unchanged"));
+
+    // Apart from the cache's own log line, the output is the same.
+    let results = |output: &str| {
+        output
+            .lines()
+            .filter(|line| !line.contains("@ c2rust_analyze::cache]"))
+            .map(str::to_owned)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(results(&first), results(&second));
+}
+
+/// The permissions and flags in a summary exported from one crate constrain the callers of its
+/// functions in a crate that imports it.
+#[test]
+fn summary() {
+    let analyze = Analyze::resolve();
+    analyze.run("tests/analyze/summary_callee.rs");
+    let output = analyze.run("tests/analyze/summary_caller.rs");
+    let output = fs::read_to_string(output).unwrap();
+    assert!(output.contains("imported 2 function summaries for crate summary_callee"));
+    // `inc` writes through its parameter, and `find` reads through it.
+    assert!(output.contains("fn inc_twice(p: &mut i32)"));
+    assert!(output.contains("fn find_or_zero(p: &i32, c: bool) -> i32"));
+    // The result of `find` may be null.
+    assert!(output.contains("q): std::option::Option<&i32>"));
+}
+
+/// In `cargo` wrapper mode, each crate is analyzed with the results of the workspace crates it
+/// depends on.
+#[test]
+fn cargo_workspace() {
+    let output = Analyze::resolve().run_cargo("tests/analyze/workspace");
+    let output = fs::read_to_string(output).unwrap();
+    assert!(output.contains("imported 1 function summaries for crate a"));
+    assert!(output.contains("fn inc_twice(p: &mut i32)"));
+}
+
 #[test]
 fn lighttpd_minimal() {
     Analyze::resolve().run("../analysis/tests/lighttpd-minimal/src/main.rs");
@@ -0,0 +1,10 @@
+//! --cache-dir=tests/analyze/cache.rs.cache, --log=c2rust_analyze::cache=debug
+
+pub unsafe fn set(p: *mut i32) {
+    *p = 1;
+}
+
+pub unsafe fn call(p: *mut i32, q: *const i32) {
+    set(p);
+    let _x = *q;
+}
@@ -0,0 +1,15 @@
+//! --export-summary=tests/analyze/summary_callee.json
+
+use std::ptr;
+
+pub unsafe fn inc(p: *mut i32) {
+    *p += 1;
+}
+
+pub unsafe fn find(p: *const i32, c: bool) -> *const i32 {
+    if c {
+        p
+    } else {
+        ptr::null()
+    }
+}
@@ -0,0 +1,18 @@
+//! --import-summary=tests/analyze/summary_callee.json
+//! --extern=summary_callee=libsummary_callee.rlib
+
+extern crate summary_callee;
+
+pub unsafe fn inc_twice(p: *mut i32) {
+    summary_callee::inc(p);
+    summary_callee::inc(p);
+}
+
+pub unsafe fn find_or_zero(p: *const i32, c: bool) -> i32 {
+    let q = summary_callee::find(p, c);
+    if q.is_null() {
+        0
+    } else {
+        *q
+    }
+}
@@ -0,0 +1,3 @@
+pub unsafe fn inc(p: *mut i32) {
+    *p += 1;
+}
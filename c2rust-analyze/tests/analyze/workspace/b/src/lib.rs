@@ -0,0 +1,5 @@
+/// `p` only becomes a reference if the call is analyzed with the summary of `a::inc`.
+pub unsafe fn inc_twice(p: *mut i32) {
+    a::inc(p);
+    a::inc(p);
+}
@@ -49,14 +49,27 @@ impl Analyze {
         let output_stdout = File::create(&output_path).unwrap();
         let output_stderr = File::try_clone(&output_stdout).unwrap();
 
+        // Directives that look like flags, such as `//! --extern-models=models.json`, are passed
+        // to `c2rust-analyze` as extra arguments.
+        let mut extra_args = directives
+            .iter()
+            .filter(|directive| directive.starts_with("--"))
+            .collect::<Vec<_>>();
+        extra_args.sort();
+        // Tests are built as libraries unless they ask for another crate type, which rustc won't
+        // mix with `bin`.
+        let has_crate_type = extra_args.iter().any(|arg| arg.starts_with("--crate-type"));
+
         let mut cmd = Command::new(&self.path);
-        cmd.arg(&rs_path)
+        cmd.args(extra_args)
+            .arg(&rs_path)
             .arg("-L")
             .arg(lib_dir)
-            .arg("--crate-type")
-            .arg("rlib")
             .stdout(output_stdout)
             .stderr(output_stderr);
+        if !has_crate_type {
+            cmd.args(["--crate-type", "rlib"]);
+        }
         let status = cmd.status().unwrap();
         if !status.success() && !directives.contains("allow_crash") {
             let message = format!(
@@ -71,6 +84,41 @@ impl Analyze {
     pub fn run(&self, rs_path: impl AsRef<Path>) -> PathBuf {
         self.run_(rs_path.as_ref())
     }
+
+    fn run_cargo_(&self, workspace_dir: &Path) -> PathBuf {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let lib_dir = Path::new(env!("C2RUST_TARGET_LIB_DIR"));
+
+        let workspace_dir = dir.join(workspace_dir);
+        // Analyze every crate, not just the ones that changed since the last run.
+        let _ = fs::remove_dir_all(workspace_dir.join("target"));
+
+        let output_path = workspace_dir.with_extension("analysis.txt");
+        let output_stdout = File::create(&output_path).unwrap();
+        let output_stderr = File::try_clone(&output_stdout).unwrap();
+
+        let mut cmd = Command::new(&self.path);
+        cmd.arg("cargo")
+            .current_dir(&workspace_dir)
+            .env("RUSTFLAGS", format!("-L {}", lib_dir.display()))
+            .env_remove("CARGO_TARGET_DIR")
+            .stdout(output_stdout)
+            .stderr(output_stderr);
+        let status = cmd.status().unwrap();
+        if !status.success() {
+            let message = format!(
+                "c2rust-analyze failed with status {status}:\n> {cmd:?} > {output_path:?} 2>&1\n"
+            );
+            let output = fs::read_to_string(&output_path).unwrap();
+            panic!("\n{message}\n{output}\n{message}");
+        }
+        output_path
+    }
+
+    /// Analyze every crate of the `cargo` workspace in `workspace_dir` with `c2rust-analyze cargo`.
+    pub fn run_cargo(&self, workspace_dir: impl AsRef<Path>) -> PathBuf {
+        self.run_cargo_(workspace_dir.as_ref())
+    }
 }
 
 pub struct FileCheck {
@@ -0,0 +1,17 @@
+//! --debug-constraints=dot
+
+// CHECK: digraph "constraints" {
+
+// `p` is in the function's signature, so its pointer is global and drawn outside the cluster.
+// CHECK: "[[P:g[0-9]+]]" [label="[[P]]\l
+
+// CHECK: subgraph "cluster_{{[0-9]+}}" {
+// CHECK-NEXT: label="copy";
+// CHECK-NEXT: "copy:[[Q:l[0-9]+]]" [label="[[Q]]\l_2 (q)\lREAD | WRITE | UNIQUE\l
+
+// The value of `p` flows into `q`.
+// CHECK: "[[P]]" -> "copy:[[Q]]" [label="{{[^"]*}} (line [[@LINE+2]])"];
+pub unsafe fn copy(p: *mut i32) {
+    let q = p;
+    *q = 1;
+}
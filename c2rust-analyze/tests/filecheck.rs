@@ -1,6 +1,7 @@
 pub mod common;
 
 use std::fs;
+use std::path::Path;
 
 use common::{Analyze, FileCheck};
 
@@ -28,3 +29,20 @@ fn filecheck() {
         file_check.run(entry.path(), &output_path);
     }
 }
+
+/// `--html-report` writes to a file rather than to the output, so the fixture checks that file.
+#[test]
+fn html_report() {
+    let path = Path::new("tests/html_report/report.rs");
+    Analyze::resolve().run(path);
+    FileCheck::resolve().run(path, path.with_extension("rs.html"));
+}
+
+/// `--debug-constraints=dot` writes to a file in the current directory, so the fixture checks that
+/// file.
+#[test]
+fn constraints_dot() {
+    let path = Path::new("tests/constraints_dot/constraints.rs");
+    Analyze::resolve().run(path);
+    FileCheck::resolve().run(path, "constraints.constraints.dot");
+}
@@ -0,0 +1,37 @@
+// CHECK-LABEL: final labeling for "set_value"
+#[no_mangle]
+pub unsafe extern "C" fn set_value(p: *mut i32, v: i32) {
+    *p = v;
+}
+
+// CHECK-LABEL: ABI shim for "set_value":
+// CHECK-NEXT: #[export_name = "set_value"]
+// CHECK-NEXT: pub unsafe extern "C" fn set_value_export(arg0: *mut i32, arg1: i32) {
+// CHECK-NEXT: set_value(&mut *arg0, arg1)
+// CHECK-NEXT: }
+
+// CHECK-LABEL: final labeling for "first"
+#[export_name = "list_first"]
+pub unsafe extern "C" fn first(p: *const i32) -> *const i32 {
+    p
+}
+
+// CHECK-LABEL: ABI shim for "first":
+// CHECK-NEXT: #[export_name = "list_first"]
+// CHECK-NEXT: pub unsafe extern "C" fn first_export(arg0: *const i32) -> *const i32 {
+// CHECK-NEXT: first(&*arg0) as *const _ as *const i32
+// CHECK-NEXT: }
+
+// CHECK-LABEL: final labeling for "sum"
+#[no_mangle]
+pub unsafe extern "C" fn sum(p: *const i32, n: isize) -> i32 {
+    let mut total = 0;
+    let mut i = 0;
+    while i < n {
+        total += *p.offset(i);
+        i += 1;
+    }
+    total
+}
+
+// CHECK: exported signature kept for "sum": a pointer can't be converted at the ABI boundary
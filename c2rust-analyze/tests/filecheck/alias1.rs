@@ -19,7 +19,7 @@ pub unsafe fn alias1_bad() {
     // CHECK-DAG: ([[@LINE+1]]: p): {{.*}}type = READ | WRITE#
     let p = ptr::addr_of_mut!(x);
     // CHECK-DAG: ([[@LINE+2]]: q): {{.*}}type = (empty)#
-    // CHECK-DAG: ([[@LINE+1]]: q): {{.*}}type flags = CELL#
+    // CHECK-DAG: ([[@LINE+1]]: q): {{.*}}type flags = CELL | NON_NULL | STACK#
     let q = ptr::addr_of_mut!(x);
     *p = 1;
 }
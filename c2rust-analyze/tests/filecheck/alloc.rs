@@ -45,6 +45,7 @@ pub unsafe extern "C" fn malloc1(mut cnt: libc::c_int) -> *mut i32 {
 }
 
 // CHECK-LABEL: final labeling for "free1"
+// CHECK-DAG: ([[@LINE+1]]: mut i): std::boxed::Box<i32>
 unsafe extern "C" fn free1(mut i: *mut i32) {
     // CHECK-DAG: ([[@LINE+1]]: i{{.*}}): {{.*}}type = UNIQUE | FREE#
     free(i as *mut libc::c_void);
@@ -76,7 +77,8 @@ unsafe extern "C" fn realloc1(mut i: *mut i32, len: libc::c_ulong) {
 
 // CHECK-LABEL: final labeling for "alloc_and_free1"
 pub unsafe extern "C" fn alloc_and_free1(mut cnt: libc::c_int) {
-    // CHECK-DAG: ([[@LINE+1]]: i): addr_of = UNIQUE, type = UNIQUE | FREE#
+    // CHECK-DAG: ([[@LINE+2]]: i): addr_of = UNIQUE, type = UNIQUE | FREE#
+    // CHECK-DAG: ([[@LINE+1]]: i): std::boxed::Box<i32>
     let i = malloc(::std::mem::size_of::<i32>() as libc::c_ulong) as *mut i32;
     // CHECK-DAG: ([[@LINE+1]]: i{{.*}}): {{.*}}type = UNIQUE | FREE#
     free(i as *mut libc::c_void);
@@ -85,13 +87,138 @@ pub unsafe extern "C" fn alloc_and_free1(mut cnt: libc::c_int) {
 
 // CHECK-LABEL: final labeling for "alloc_and_free2"
 pub unsafe extern "C" fn alloc_and_free2(mut cnt: libc::c_int) {
-    // CHECK-DAG: ([[@LINE+1]]: i): addr_of = UNIQUE, type = READ | WRITE | UNIQUE | FREE#
+    // CHECK-DAG: ([[@LINE+2]]: i): addr_of = UNIQUE, type = READ | WRITE | UNIQUE | FREE#
+    // CHECK-DAG: ([[@LINE+1]]: i): std::option::Option<std::boxed::Box<i32>>
     let i = malloc(::std::mem::size_of::<i32>() as libc::c_ulong) as *mut i32;
     if !i.is_null() {
-        // CHECK-DAG: ([[@LINE+1]]: mut b): addr_of = UNIQUE, type = READ | WRITE | UNIQUE#
+        // CHECK-DAG: ([[@LINE+2]]: mut b): addr_of = UNIQUE, type = READ | WRITE | UNIQUE#
+        // CHECK-DAG: ([[@LINE+1]]: mut b): std::option::Option<&mut i32>
         let mut b = i;
         *b = 2;
         // CHECK-DAG: ([[@LINE+1]]: i): {{.*}}type = UNIQUE | FREE#
         free(i as *mut libc::c_void);
     }
 }
+
+// CHECK-LABEL: final labeling for "malloc_array"
+pub unsafe extern "C" fn malloc_array(n: libc::c_ulong) {
+    // CHECK-DAG: ([[@LINE+1]]: i): std::boxed::Box<[i32]>
+    let i = malloc(n * ::std::mem::size_of::<i32>() as libc::c_ulong) as *mut i32;
+    *i.offset(1) = 1;
+    free(i as *mut libc::c_void);
+}
+
+// CHECK-LABEL: final labeling for "calloc_array"
+pub unsafe extern "C" fn calloc_array(n: libc::c_ulong) -> i32 {
+    // CHECK-DAG: ([[@LINE+1]]: i): std::boxed::Box<[i32]>
+    let i = calloc(n, ::std::mem::size_of::<i32>() as libc::c_ulong) as *mut i32;
+    let x = *i.offset(2);
+    free(i as *mut libc::c_void);
+    x
+}
+
+// An owned buffer that's offset backward still becomes a `Box<[T]>`.
+// CHECK-LABEL: final labeling for "malloc_array_offset_back"
+pub unsafe extern "C" fn malloc_array_offset_back(n: libc::c_ulong) -> i32 {
+    // CHECK-DAG: ([[@LINE+1]]: i): std::boxed::Box<[i32]>
+    let i = malloc(n * ::std::mem::size_of::<i32>() as libc::c_ulong) as *mut i32;
+    let x = *i.offset(2).offset(-1);
+    free(i as *mut libc::c_void);
+    x
+}
+
+// CHECK-LABEL: final labeling for "realloc_grow"
+pub unsafe extern "C" fn realloc_grow(n: libc::c_int) {
+    // CHECK-DAG: ([[@LINE+1]]: mut buf): std::vec::Vec<i32>
+    let mut buf = malloc(4 as libc::c_ulong) as *mut i32;
+    let mut cap = 1;
+    let mut len = 0;
+    while len < n {
+        if len == cap {
+            cap *= 2;
+            buf = realloc(buf as *mut libc::c_void, (cap * 4) as libc::c_ulong) as *mut i32;
+        }
+        *buf.offset(len as isize) = len;
+        len += 1;
+    }
+    free(buf as *mut libc::c_void);
+}
+
+// `free_stack` passes `free_copy` a pointer that doesn't own its memory, so neither `p` nor `q`,
+// which is copied from it, can be a `Box`, even though `free_copy` is processed first.
+// CHECK-LABEL: final labeling for "free_copy"
+// CHECK-NOT: Box
+unsafe extern "C" fn free_copy(p: *mut i32) {
+    let q = p;
+    free(q as *mut libc::c_void);
+}
+
+// CHECK-LABEL: final labeling for "free_stack"
+unsafe extern "C" fn free_stack() {
+    let mut x = 1;
+    free_copy(&mut x);
+}
+
+// Allocations that are copied into several owners and never freed are shared.
+// CHECK-LABEL: final labeling for "shared_leak"
+pub unsafe extern "C" fn shared_leak() -> i32 {
+    // CHECK-DAG: ([[@LINE+1]]: p): {{.*}}type flags = SHARED | HEAP#
+    let p = malloc(4 as libc::c_ulong) as *mut i32;
+    let q = p;
+    let r = p;
+    *q = 1;
+    *r
+}
+
+// CHECK-LABEL: final labeling for "shared_rc"
+#[c2rust::rc]
+pub unsafe extern "C" fn shared_rc() -> i32 {
+    // CHECK-DAG: ([[@LINE+1]]: p): std::rc::Rc<std::cell::RefCell<i32>>
+    let p = malloc(4 as libc::c_ulong) as *mut i32;
+    // CHECK-DAG: ([[@LINE+1]]: q): std::rc::Rc<std::cell::RefCell<i32>>
+    let q = p;
+    let r = p;
+    *q = 1;
+    *r
+}
+
+// A leaked allocation that's never copied into a second owner isn't shared.
+// CHECK-LABEL: final labeling for "leak_single"
+pub unsafe extern "C" fn leak_single() -> i32 {
+    // CHECK-NOT: ([[@LINE+1]]: p): {{.*}}SHARED
+    let p = malloc(4 as libc::c_ulong) as *mut i32;
+    *p = 1;
+    *p
+}
+
+// A pointer that may point to a local isn't shared, even if it may also point to a shared
+// allocation.
+// CHECK-LABEL: final labeling for "shared_or_stack"
+#[c2rust::rc]
+pub unsafe extern "C" fn shared_or_stack(c: bool) -> i32 {
+    let mut x = 1;
+    let p = malloc(4 as libc::c_ulong) as *mut i32;
+    let q = p;
+    // CHECK-NOT: ([[@LINE+1]]: r): {{.*}}SHARED
+    let r = if c { p } else { &mut x as *mut i32 };
+    *q = 1;
+    *r
+}
+
+// CHECK-LABEL: final labeling for "read_shared"
+pub unsafe extern "C" fn read_shared(p: *mut i32) -> i32 {
+    // CHECK-NOT: Rc<
+    *p
+}
+
+// `read_shared` isn't marked `rc`, so its parameter can't be an `Rc`, and neither can the pointers
+// passed to it.
+// CHECK-LABEL: final labeling for "shared_rc_call"
+#[c2rust::rc]
+pub unsafe extern "C" fn shared_rc_call() -> i32 {
+    // CHECK-NOT: Rc<
+    let p = malloc(4 as libc::c_ulong) as *mut i32;
+    let q = p;
+    *q = 1;
+    read_shared(p)
+}
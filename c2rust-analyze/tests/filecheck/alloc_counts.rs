@@ -0,0 +1,31 @@
+extern crate libc;
+
+use std::mem::size_of;
+
+extern "C" {
+    fn malloc(_: libc::c_ulong) -> *mut libc::c_void;
+    fn calloc(_: libc::c_ulong, _: libc::c_ulong) -> *mut libc::c_void;
+    fn realloc(_: *mut libc::c_void, _: libc::c_ulong) -> *mut libc::c_void;
+}
+
+// CHECK-LABEL: allocation counts for "counted"
+// CHECK-NEXT: l{{[0-9]+}}: _1 elements
+// CHECK-NEXT: l{{[0-9]+}}: _1 elements
+// CHECK-NEXT: l{{[0-9]+}}: 4 elements
+// CHECK-NEXT: l{{[0-9]+}}: 1 elements
+// CHECK-NEXT: l{{[0-9]+}}: _1 * 2 elements
+pub unsafe fn counted(n: libc::c_ulong) {
+    let a = malloc(n * size_of::<i32>() as libc::c_ulong) as *mut i32;
+    let b = calloc(n, size_of::<u16>() as libc::c_ulong) as *mut u16;
+    let c = malloc(size_of::<i64>() as libc::c_ulong * 4) as *mut i64;
+    let d = malloc(size_of::<u8>() as libc::c_ulong) as *mut u8;
+    let e = realloc(a as *mut libc::c_void, n * 2 * size_of::<i32>() as libc::c_ulong) as *mut i32;
+}
+
+// Sizes that aren't a multiple of the pointee size don't give a count.
+// CHECK-NOT: allocation counts for "uncounted"
+pub unsafe fn uncounted(size: libc::c_ulong) {
+    let a = malloc(16) as *mut i32;
+    let b = malloc(size) as *mut i32;
+    let c = malloc(size_of::<i64>() as libc::c_ulong) as *mut i32;
+}
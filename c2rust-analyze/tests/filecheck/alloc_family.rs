@@ -0,0 +1,43 @@
+extern crate libc;
+
+extern "C" {
+    fn aligned_alloc(_: libc::size_t, _: libc::size_t) -> *mut libc::c_void;
+    fn posix_memalign(_: *mut *mut libc::c_void, _: libc::size_t, _: libc::size_t) -> libc::c_int;
+    fn mmap(
+        _: *mut libc::c_void,
+        _: libc::size_t,
+        _: libc::c_int,
+        _: libc::c_int,
+        _: libc::c_int,
+        _: libc::off_t,
+    ) -> *mut libc::c_void;
+    fn munmap(_: *mut libc::c_void, _: libc::size_t) -> libc::c_int;
+    fn free(_: *mut libc::c_void);
+}
+
+// Memory from `aligned_alloc` is freed with `free`, but can't become a `Box`, since `Box`
+// doesn't keep the requested alignment.
+// CHECK-LABEL: final labeling for "aligned"
+pub unsafe fn aligned() {
+    // CHECK-DAG: ([[@LINE+2]]: p): {{.*}}type = READ | WRITE | UNIQUE | FREE#
+    // CHECK-NOT: ([[@LINE+1]]: p): {{.*}}OWNED
+    let p = aligned_alloc(64, 4) as *mut i32;
+    *p = 1;
+    free(p as *mut libc::c_void);
+}
+
+// CHECK-LABEL: final labeling for "mapped"
+pub unsafe fn mapped(len: libc::size_t) {
+    // CHECK-DAG: ([[@LINE+1]]: p): {{.*}}type = READ | WRITE | UNIQUE | FREE#
+    let p = mmap(0 as *mut libc::c_void, len, 3, 0x22, -1, 0) as *mut u32;
+    *p = 1;
+    munmap(p as *mut libc::c_void, len);
+}
+
+// CHECK-LABEL: final labeling for "memalign"
+pub unsafe fn memalign() {
+    // CHECK-DAG: ([[@LINE+1]]: mut p): {{.*}}type = UNIQUE | FREE#
+    let mut p: *mut libc::c_void = 0 as *mut libc::c_void;
+    posix_memalign(&mut p, 64, 4);
+    free(p);
+}
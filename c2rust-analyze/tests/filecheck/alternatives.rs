@@ -0,0 +1,19 @@
+//! --alternatives
+
+// CHECK-LABEL: rewritten signature for "set"
+// CHECK: alternatives for set input 0:
+// CHECK-NEXT: {{^}}  &std::cell::Cell<i32>{{$}}
+// CHECK-NEXT: {{^}}  *mut i32{{$}}
+pub unsafe fn set(p: *mut i32) {
+    *p = 1;
+}
+
+// Weakening `p` also weakens `q`, which is copied from it.
+// CHECK-LABEL: rewritten signature for "set_copy"
+// CHECK: alternatives for set_copy input 0:
+// CHECK-NEXT: {{^}}  &std::cell::Cell<i32> (also changes set_copy:l{{[0-9]+}})
+// CHECK-NEXT: {{^}}  *mut i32 (also changes set_copy:l{{[0-9]+}})
+pub unsafe fn set_copy(p: *mut i32) {
+    let q = p;
+    *q = 1;
+}
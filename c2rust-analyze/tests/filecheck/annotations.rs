@@ -0,0 +1,74 @@
+// The rewritten struct definitions are printed before any function's labeling.  `keep_raw` on a
+// field keeps it raw, while the other fields are rewritten as usual.
+// CHECK-LABEL: === rewritten struct definitions ===
+// CHECK: struct S{{.*}} {
+// CHECK-NEXT: raw: *mut i32,
+pub struct S {
+    #[c2rust::keep_raw]
+    pub raw: *mut i32,
+    pub safe: *mut i32,
+}
+
+// The body only reads through `p`, but the annotation adds `OFFSET_ADD` and removes `UNIQUE`.
+// CHECK-LABEL: final labeling for "assume_perms"
+// CHECK-DAG: ([[@LINE+2]]: p): {{.*}}type = READ | OFFSET_ADD#
+// CHECK-DAG: ([[@LINE+1]]: p): &[u8]
+pub unsafe fn assume_perms(#[c2rust::assume(perms = "READ|OFFSET_ADD")] p: *mut u8) -> u8 {
+    *p
+}
+
+// CHECK-LABEL: final labeling for "keep_raw_fn"
+// CHECK-DAG: ([[@LINE+2]]: p): *mut i32
+#[c2rust::keep_raw]
+pub unsafe fn keep_raw_fn(p: *mut i32) {
+    *p = 1;
+}
+
+// Pointers that are kept raw but are never null become `NonNull`.
+// CHECK-LABEL: final labeling for "keep_raw_non_null"
+// CHECK-DAG: ([[@LINE+2]]: p): *mut i32
+#[c2rust::keep_raw]
+pub unsafe fn keep_raw_non_null(p: *mut i32) {
+    let mut x = 0;
+    // CHECK-DAG: ([[@LINE+1]]: q): std::ptr::NonNull<i32>
+    let q = std::ptr::addr_of_mut!(x);
+    *q = *p;
+}
+
+// `q` starts out as the address of a place, but it's also assigned `p`, which may be null, so
+// the fixpoint removes its `NON_NULL`.
+// CHECK-LABEL: final labeling for "keep_raw_maybe_null"
+#[c2rust::keep_raw]
+pub unsafe fn keep_raw_maybe_null(p: *mut i32, c: bool) {
+    let mut x = 0;
+    // CHECK-DAG: ([[@LINE+1]]: mut q): *mut i32
+    let mut q = std::ptr::addr_of_mut!(x);
+    if c {
+        q = p;
+    }
+    *q = 1;
+}
+
+// C code may call an exported function with a null pointer, so its parameter doesn't become
+// `NonNull`, even though the only caller in the crate passes an address.
+// CHECK-LABEL: final labeling for "keep_raw_exported"
+// CHECK-DAG: ([[@LINE+3]]: p): *mut i32
+#[c2rust::keep_raw]
+#[no_mangle]
+pub unsafe extern "C" fn keep_raw_exported(p: *mut i32) {
+    *p = 1;
+}
+
+pub unsafe fn call_exported() {
+    let mut x = 0;
+    keep_raw_exported(std::ptr::addr_of_mut!(x));
+}
+
+// Only the field itself is kept raw.  Locals copied from it are rewritten as usual.
+// CHECK-LABEL: final labeling for "keep_raw_field"
+pub unsafe fn keep_raw_field(s: *mut S) {
+    let raw = (*s).raw;
+    // CHECK-DAG: ([[@LINE+1]]: safe): &i32
+    let safe = (*s).safe;
+    *raw = *safe;
+}
@@ -0,0 +1,40 @@
+//! --assume=extern-no-retain, --assume=no-int-ptr-round-trips, --assume=single-threaded
+//! --rewrite-diff
+
+extern "C" {
+    fn keep(p: *mut i32);
+}
+
+static mut COUNT: i32 = 0;
+
+// CHECK-LABEL: final labeling for "register"
+// CHECK-NOT: ([[@LINE+1]]: p): {{.*}}type flags = FIXED
+pub unsafe fn register(p: *mut i32) {
+    *p = 1;
+    keep(p);
+    COUNT += 1;
+    COUNT = 0;
+}
+
+// The crate creates a pointer from an integer, but we assume it's never one of ours.
+// CHECK-LABEL: final labeling for "addr"
+// CHECK-NOT: ([[@LINE+1]]: p): {{.*}}type flags = FIXED
+pub unsafe fn addr(p: *const i32) -> usize {
+    p as usize
+}
+
+pub unsafe fn from_addr(addr: usize) -> *const i32 {
+    addr as *const i32
+}
+
+// CHECK-LABEL: === rewritten statics ===
+// CHECK: static COUNT: std::sync::atomic::AtomicI32 (atomic)
+
+// CHECK-LABEL: === assumptions ===
+// CHECK-NEXT: extern-no-retain
+// CHECK-NEXT: no-int-ptr-round-trips
+// CHECK-NEXT: single-threaded
+
+// Atomics only need `Relaxed` ordering in a single thread.
+// CHECK-NOT: SeqCst
+// CHECK: {{^}}+{{.*}}COUNT{{.*}}std::sync::atomic::Ordering::Relaxed
@@ -0,0 +1,37 @@
+#![feature(core_intrinsics)]
+
+use std::intrinsics::{atomic_load_seqcst, atomic_store_seqcst, atomic_xadd_seqcst};
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+// Atomic intrinsics read and write through their pointer argument like ordinary accesses, but the
+// pointer can't become `&mut`, since other threads may access the same memory.
+
+// CHECK-LABEL: final labeling for "incr"
+pub unsafe fn incr(x: &mut i32) -> i32 {
+    // CHECK-DAG: ([[@LINE+2]]: p): {{.*}}type flags = {{.*}}ATOMIC{{.*}}#
+    // CHECK-DAG: ([[@LINE+1]]: p): &std::cell::Cell<i32>
+    let p = x as *mut i32;
+    atomic_xadd_seqcst(p, 1)
+}
+
+// CHECK-LABEL: final labeling for "get"
+pub unsafe fn get(x: &i32) -> i32 {
+    // CHECK-DAG: ([[@LINE+1]]: p): &i32
+    let p = x as *const i32;
+    atomic_load_seqcst(p)
+}
+
+// CHECK-LABEL: final labeling for "set"
+pub unsafe fn set(x: &mut i32, v: i32) {
+    // CHECK-NOT: ([[@LINE+1]]: p): &mut i32
+    let p = x as *mut i32;
+    atomic_store_seqcst(p, v);
+}
+
+// Pointers stored in an `AtomicPtr` aren't tracked, so they stay raw.
+// CHECK-LABEL: final labeling for "publish"
+pub unsafe fn publish(slot: &AtomicPtr<i32>, x: &mut i32) {
+    // CHECK-DAG: ([[@LINE+1]]: p): *mut i32
+    let p = x as *mut i32;
+    slot.store(p, Ordering::Release);
+}
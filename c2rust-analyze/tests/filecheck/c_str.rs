@@ -0,0 +1,34 @@
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+
+// Pointers that only ever hold NUL-terminated strings become `&CStr`, or `CString` if they own
+// the string, as long as the bytes aren't accessed through them directly.
+
+// CHECK-LABEL: final labeling for "name_len"
+pub unsafe fn name_len(name: *const c_char) -> usize {
+    CStr::from_ptr(name).to_bytes().len()
+}
+
+// CHECK-LABEL: final labeling for "greet"
+pub unsafe fn greet(s: &CStr) -> usize {
+    // CHECK-DAG: ([[@LINE+2]]: p): {{.*}}type flags = {{.*}}NUL_TERMINATED{{.*}}#
+    // CHECK-DAG: ([[@LINE+1]]: p): &{{.*}}CStr
+    let p = s.as_ptr();
+    name_len(p)
+}
+
+// CHECK-LABEL: final labeling for "first_byte"
+pub unsafe fn first_byte(s: &CStr) -> c_char {
+    // Reading the bytes directly needs a byte pointer.
+    // CHECK-DAG: ([[@LINE+1]]: p): &i8
+    let p = s.as_ptr();
+    *p
+}
+
+// CHECK-LABEL: final labeling for "round_trip"
+pub unsafe fn round_trip(s: CString) -> CString {
+    // CHECK-DAG: ([[@LINE+2]]: p): {{.*}}type flags = {{.*}}OWNED{{.*}}NUL_TERMINATED{{.*}}#
+    // CHECK-DAG: ([[@LINE+1]]: p): {{.*}}CString
+    let p = CString::into_raw(s);
+    CString::from_raw(p)
+}
@@ -0,0 +1,31 @@
+extern crate libc;
+
+pub struct State {
+    count: i32,
+}
+
+// `buf` is only accessed as bytes, so it becomes a byte slice.
+// CHECK-LABEL: final labeling for "checksum"
+// CHECK-DAG: ([[@LINE+1]]: buf): {{.*}}type flags = {{.*}}CVOID{{.*}}#
+pub unsafe fn checksum(buf: *const libc::c_void, len: usize) -> u8 {
+    let bytes = buf as *const u8;
+    let mut sum = 0u8;
+    let mut i = 0;
+    while i < len {
+        sum = sum.wrapping_add(*bytes.offset(i as isize));
+        i += 1;
+    }
+    sum
+}
+// CHECK-LABEL: type assignment for "checksum"
+// CHECK-DAG: ({{.*}}: buf): &[u8]
+
+// `ctx` is a type-erased pointer to a `State`, so it stays raw.
+// CHECK-LABEL: final labeling for "callback"
+// CHECK-DAG: ([[@LINE+1]]: ctx): {{.*}}type flags = FIXED{{.*}}CVOID{{.*}}#
+pub unsafe fn callback(ctx: *mut libc::c_void) {
+    let state = ctx as *mut State;
+    (*state).count += 1;
+}
+// CHECK-LABEL: type assignment for "callback"
+// CHECK-DAG: ({{.*}}: ctx): *mut {{.*}}c_void
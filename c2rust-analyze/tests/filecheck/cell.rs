@@ -0,0 +1,42 @@
+use std::ptr;
+
+// CHECK-LABEL: final labeling for "cell_copy"
+pub unsafe fn cell_copy() -> i32 {
+    let mut x = 0;
+    // CHECK-DAG: ([[@LINE+1]]: p): &std::cell::Cell<i32>
+    let p = ptr::addr_of_mut!(x);
+    // CHECK-DAG: ([[@LINE+1]]: q): &std::cell::Cell<i32>
+    let q = ptr::addr_of_mut!(x);
+    // CHECK-DAG: CellSet
+    *p = 1;
+    // CHECK-DAG: CellGet
+    *p
+}
+
+pub struct Pair {
+    pub x: i32,
+    pub y: i32,
+}
+
+// CHECK-LABEL: final labeling for "cell_non_copy"
+pub unsafe fn cell_non_copy(mut a: Pair, b: Pair) {
+    // CHECK-DAG: ([[@LINE+1]]: p): &std::cell::RefCell<Pair>
+    let p = ptr::addr_of_mut!(a);
+    // CHECK-DAG: ([[@LINE+1]]: q): &std::cell::RefCell<Pair>
+    let q = ptr::addr_of_mut!(a);
+    // CHECK-DAG: RefCellBorrow { mutbl: true }
+    *p = b;
+}
+
+// The pointer `p` and `q` point to is rewritten to `&mut i32`, which isn't `Copy`, so they need a
+// `RefCell` even though `*mut i32` is `Copy`.
+// CHECK-LABEL: final labeling for "cell_nested"
+pub unsafe fn cell_nested(r: *mut i32) {
+    let mut x = r;
+    // CHECK-DAG: ([[@LINE+1]]: p): &std::cell::RefCell<&mut i32>
+    let p = ptr::addr_of_mut!(x);
+    // CHECK-DAG: ([[@LINE+1]]: q): &std::cell::RefCell<&mut i32>
+    let q = ptr::addr_of_mut!(x);
+    // CHECK-DAG: RefCellBorrow { mutbl: false }
+    **p = 1;
+}
@@ -0,0 +1,25 @@
+//! --cfg-set=
+//! --cfg-set=write_it
+
+// CHECK: === cfg set 0:  ===
+// CHECK: === cfg set 1: write_it ===
+// CHECK-LABEL: === cfg coverage ===
+
+// `p` is only written when `write_it` is set, so neither rewrite of it is safe for both sets.
+// CHECK-DAG: skipped rewrite at {{.*}}cfg_sets.rs:[[@LINE+2]]: only made under cfg sets 0: "&i32"
+// CHECK-DAG: skipped rewrite at {{.*}}cfg_sets.rs:[[@LINE+1]]: only made under cfg sets 1: "&mut i32"
+pub unsafe fn get(p: *mut i32) -> i32 {
+    #[cfg(write_it)]
+    {
+        *p = 1;
+    }
+    *p
+}
+
+// `q` is read-only under both sets.
+// CHECK-NOT: skipped rewrite at {{.*}}cfg_sets.rs:[[@LINE+1]]:
+pub unsafe fn read(q: *const i32) -> i32 {
+    *q
+}
+
+// CHECK: {{[0-9]+}} rewrites made under all 2 cfg sets, 2 skipped
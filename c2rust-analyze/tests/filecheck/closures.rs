@@ -0,0 +1,15 @@
+// CHECK-LABEL: final labeling for "closure_write"
+// The closure writes through the captured pointer, so `p` needs `WRITE`.
+// CHECK-DAG: ([[@LINE+1]]: p): {{.*}}type = {{.*}}WRITE{{.*}}#
+pub unsafe fn closure_write(p: *mut i32) {
+    let f = move |x: i32| *p = x;
+    f(1);
+}
+
+// CHECK-LABEL: final labeling for "closure_arg"
+pub unsafe fn closure_arg(p: *mut i32) -> i32 {
+    let f = |q: *const i32| *q;
+    // CHECK-DAG: ([[@LINE+1]]: q): {{.*}}type = READ{{.*}}#
+    let q = p as *const i32;
+    f(q)
+}
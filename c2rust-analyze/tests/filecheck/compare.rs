@@ -0,0 +1,19 @@
+// Comparing pointers or casting them to integers doesn't keep them raw, but the comparison is
+// rewritten to compare addresses.
+// CHECK-LABEL: final labeling for "same"
+// CHECK-DAG: ([[@LINE+5]]: p): &i32
+// CHECK: ([[@LINE+5]]: p == q, [AssignRvalue, RvalueOperand(0)]):
+// CHECK-NEXT: AsRawPtr
+// CHECK: ([[@LINE+3]]: p == q, [AssignRvalue, RvalueOperand(1)]):
+// CHECK-NEXT: AsRawPtr
+pub unsafe fn same(p: *const i32, q: *const i32) -> bool {
+    p == q
+}
+
+// CHECK-LABEL: final labeling for "addr"
+// CHECK-DAG: ([[@LINE+3]]: p): &i32
+// CHECK: ([[@LINE+3]]: p as usize, [AssignRvalue, RvalueOperand(0)]):
+// CHECK-NEXT: AsRawPtr
+pub unsafe fn addr(p: *const i32) -> usize {
+    p as usize
+}
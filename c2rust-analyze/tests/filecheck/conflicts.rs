@@ -0,0 +1,29 @@
+extern crate libc;
+
+extern "C" {
+    fn malloc(_: libc::c_ulong) -> *mut libc::c_void;
+    fn free(_: *mut libc::c_void);
+}
+
+// `p` is freed, so it would have to own its allocation, but `q` still uses the same memory after
+// `p` is written through.  No safe type allows both, so the pointer needs manual attention.
+// CHECK-LABEL: === conflicting constraints ===
+// CHECK-NEXT: l{{[0-9]+}} in free_aliased: needs manual attention: it is freed, but isn't the unique owner of its allocation
+// CHECK-NEXT: freed: pointer is passed to `free` at {{.*}}conflicts.rs:[[@LINE+9]]
+// CHECK-NEXT: shared: pointer is borrowed while another use of its memory is live at {{.*}}conflicts.rs:[[@LINE+5]]
+// CHECK-NOT: in free_unique
+// CHECK-LABEL: final labeling for "free_aliased"
+pub unsafe fn free_aliased() {
+    let p = malloc(4) as *mut i32;
+    let q = p;
+    *p = 1;
+    *q = 2;
+    free(p as *mut libc::c_void);
+}
+
+// A pointer that's freed without being shared has no conflict.
+pub unsafe fn free_unique() {
+    let p = malloc(4) as *mut i32;
+    *p = 1;
+    free(p as *mut libc::c_void);
+}
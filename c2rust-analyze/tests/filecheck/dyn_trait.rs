@@ -0,0 +1,33 @@
+pub trait Counter {
+    fn bump(&mut self, amount: *const i32);
+}
+
+pub struct Simple {
+    count: i32,
+}
+
+impl Counter for Simple {
+    fn bump(&mut self, amount: *const i32) {
+        unsafe {
+            self.count += *amount;
+        }
+    }
+}
+
+// CHECK-LABEL: final labeling for "bump_dyn"
+pub unsafe fn bump_dyn(c: *mut Simple, amount: *const i32) {
+    // The pointer to the trait object is labeled, but the object itself is opaque.
+    // CHECK-DAG: ([[@LINE+1]]: d): {{.*}}type = READ | WRITE{{.*}}#
+    let d: &mut dyn Counter = &mut *c;
+    // The virtual call requires the permissions allowed by the signature of `Counter::bump`.
+    // CHECK-DAG: ([[@LINE+1]]: a): {{.*}}type = READ{{.*}}#
+    let a = amount;
+    d.bump(a);
+}
+
+// CHECK-LABEL: final labeling for "call_ptr"
+pub unsafe fn call_ptr(f: unsafe fn(*mut i32), p: *mut i32) {
+    // CHECK-DAG: ([[@LINE+1]]: q): {{.*}}type = READ | WRITE{{.*}}#
+    let q = p;
+    f(q);
+}
@@ -0,0 +1,10 @@
+//! --explain=g0
+
+// CHECK-LABEL: explanation for g0: READ | WRITE | UNIQUE
+// CHECK: WRITE:
+// CHECK-NEXT: pointer is written through at {{.*}}explain.rs:[[@LINE+4]]
+// CHECK-NEXT: receives its value from g0: pointer is copied at {{.*}}explain.rs:[[@LINE+2]]
+pub unsafe fn set(p: *mut i32) {
+    let q = p;
+    *q = 1;
+}
@@ -0,0 +1,34 @@
+//! --extern-models=tests/filecheck/extern_models.json
+
+extern "C" {
+    fn memset(s: *mut u8, c: i32, n: usize) -> *mut u8;
+    fn strlen(s: *const u8) -> usize;
+    fn list_swap(a: *mut i32, b: *mut i32);
+}
+
+// CHECK-LABEL: final labeling for "modeled_memset"
+// CHECK-DAG: ([[@LINE+1]]: p): {{.*}}type = {{.*}}WRITE{{.*}}OFFSET_ADD{{.*}}#
+pub unsafe fn modeled_memset(p: *mut u8, n: usize) {
+    memset(p, 0, n);
+}
+
+// CHECK-LABEL: final labeling for "modeled_strlen"
+// CHECK-DAG: ([[@LINE+1]]: s): {{.*}}type = READ{{.*}}OFFSET_ADD{{.*}}#
+pub unsafe fn modeled_strlen(s: *const u8) -> usize {
+    strlen(s)
+}
+
+// The first argument of `list_swap` needs `WRITE` only when the second one is written through.
+// CHECK-LABEL: final labeling for "swap_read_only"
+// CHECK-DAG: ([[@LINE+1]]: a): {{.*}}type = READ | UNIQUE#
+pub unsafe fn swap_read_only(a: *mut i32, b: *mut i32) -> i32 {
+    list_swap(a, b);
+    *b
+}
+
+// CHECK-LABEL: final labeling for "swap_written"
+// CHECK-DAG: ([[@LINE+1]]: a): {{.*}}type = READ | WRITE | UNIQUE#
+pub unsafe fn swap_written(a: *mut i32, b: *mut i32) {
+    list_swap(a, b);
+    *b = 1;
+}
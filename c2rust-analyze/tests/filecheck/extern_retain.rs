@@ -0,0 +1,31 @@
+use std::ptr;
+
+extern "C" {
+    fn keep(p: *mut i32);
+}
+
+// A pointer passed to an extern function may be kept after the call returns, so it stays raw,
+// along with the pointers it's copied from.  Assuming the call doesn't keep it lifts this; see
+// `assume.rs`.
+// CHECK-LABEL: final labeling for "register"
+// CHECK-DAG: ([[@LINE+1]]: p): {{.*}}type flags = FIXED{{.*}}#
+pub unsafe fn register(p: *mut i32) {
+    *p = 1;
+    // CHECK-DAG: ([[@LINE+1]]: q): {{.*}}type flags = FIXED{{.*}}#
+    let q = p;
+    keep(q);
+}
+
+// Without an int-to-pointer cast anywhere in the crate, casting a pointer to an integer doesn't
+// keep it raw.
+// CHECK-LABEL: final labeling for "addr"
+// CHECK-NOT: ([[@LINE+1]]: p): {{.*}}type flags = FIXED
+pub unsafe fn addr(p: *const i32) -> usize {
+    p as usize
+}
+
+// CHECK-LABEL: === assumptions ===
+// CHECK-NEXT: none
+pub unsafe fn null() -> *mut i32 {
+    ptr::null_mut()
+}
@@ -0,0 +1,51 @@
+//! --assume=extern-no-retain
+
+// Structs passed by value to extern functions are part of the C ABI, so they keep their layout
+// and every pointer in them stays raw, even when extern functions are assumed not to keep the
+// pointers passed to them.
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct Inner {
+    pub data: *mut i32,
+}
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct Request {
+    pub buf: *mut u8,
+    pub inner: Inner,
+    pub len: usize,
+}
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct Local {
+    pub p: *mut i32,
+}
+
+extern "C" {
+    fn submit(req: Request) -> i32;
+}
+
+// CHECK-LABEL: === rewritten struct definitions ===
+// CHECK: struct Inner {
+// CHECK-NEXT: data: *mut i32,
+// CHECK: struct Request {
+// CHECK-NEXT: buf: *mut u8,
+// CHECK-NEXT: inner: Inner,
+// CHECK: struct Local<'a> {
+// CHECK-NEXT: p: &'a mut i32,
+
+pub unsafe fn send(buf: *mut u8, data: *mut i32) -> i32 {
+    let req = Request {
+        buf,
+        inner: Inner { data },
+        len: 1,
+    };
+    submit(req)
+}
+
+pub unsafe fn set(l: Local) {
+    *l.p = 1;
+}
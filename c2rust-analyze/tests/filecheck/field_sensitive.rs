@@ -0,0 +1,17 @@
+pub struct S {
+    pub r: *mut i32,
+    pub w: *mut i32,
+}
+
+// Each field has its own `PointerId`s, so writing through `w` doesn't force `r` to be mutable.
+// CHECK-LABEL: final labeling for "field_sensitive"
+pub unsafe fn field_sensitive(s: *mut S) {
+    // CHECK-DAG: ([[@LINE+2]]: r): {{.*}}type = READ | UNIQUE#
+    // CHECK-DAG: ([[@LINE+1]]: r): &i32
+    let r = (*s).r;
+    // CHECK-DAG: ([[@LINE+2]]: w): {{.*}}type = {{.*}}WRITE | UNIQUE#
+    // CHECK-DAG: ([[@LINE+1]]: w): &mut i32
+    let w = (*s).w;
+    let _x = *r;
+    *w = *r;
+}
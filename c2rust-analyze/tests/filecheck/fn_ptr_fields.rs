@@ -0,0 +1,71 @@
+// A "vtable" struct of function pointers, as c2rust transpiles
+//
+// struct ops {
+//     int (*read)(int *);
+//     int (*update)(int *);
+// };
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct ops {
+    pub read: Option<unsafe extern "C" fn(*mut i32) -> i32>,
+    pub update: Option<unsafe extern "C" fn(*mut i32) -> i32>,
+}
+
+pub unsafe extern "C" fn get(p: *mut i32) -> i32 {
+    *p
+}
+
+pub unsafe extern "C" fn peek(p: *mut i32) -> i32 {
+    *p + 1
+}
+
+pub unsafe fn make_ops() -> ops {
+    let mut o = ops {
+        read: Some(get as unsafe extern "C" fn(*mut i32) -> i32),
+        update: None,
+    };
+    o.read = Some(peek as unsafe extern "C" fn(*mut i32) -> i32);
+    o
+}
+
+pub unsafe fn set_update(o: *mut ops, f: unsafe extern "C" fn(*mut i32) -> i32) {
+    (*o).update = Some(f);
+}
+
+// Only `get` and `peek` are ever stored in `read`, and neither writes through its argument.
+// CHECK-LABEL: final labeling for "call_read"
+// CHECK-DAG: ([[@LINE+1]]: p): {{.*}}type = READ | UNIQUE#
+pub unsafe fn call_read(o: *mut ops, p: *mut i32) -> i32 {
+    (*o).read.expect("non-null function pointer")(p)
+}
+
+// `update` may hold any function passed to `set_update`, so the argument keeps every permission
+// its type allows.
+// CHECK-LABEL: final labeling for "call_update"
+// CHECK-DAG: ([[@LINE+1]]: p): {{.*}}type = READ | WRITE | UNIQUE#
+pub unsafe fn call_update(o: *mut ops, p: *mut i32) -> i32 {
+    (*o).update.expect("non-null function pointer")(p)
+}
+
+// Matching the field with `Some(f)` sees through the `Option` too, and a null field calls nothing.
+// CHECK-LABEL: final labeling for "call_read_if_set"
+// CHECK-DAG: ([[@LINE+1]]: p): {{.*}}type = READ | UNIQUE#
+pub unsafe fn call_read_if_set(o: *mut ops, p: *mut i32) -> i32 {
+    match (*o).read {
+        Some(f) => f(p),
+        None => 0,
+    }
+}
+
+// CHECK-LABEL: final labeling for "call_read_checked"
+// CHECK-DAG: ([[@LINE+1]]: p): {{.*}}type = READ | UNIQUE#
+pub unsafe fn call_read_checked(o: *mut ops, p: *mut i32) -> i32 {
+    if (*o).read.is_none() {
+        return 0;
+    }
+    (*o).read.unwrap()(p)
+}
+
+// CHECK-LABEL: === function pointer fields ===
+// CHECK-NEXT: field ops::read: calls get, peek
+// CHECK-NEXT: field ops::update: calls unknown functions
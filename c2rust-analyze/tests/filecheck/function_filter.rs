@@ -0,0 +1,16 @@
+//! --function=selected::*
+
+// CHECK-NOT: final labeling for "ignored"
+pub unsafe fn ignored(p: *mut i32) -> *mut i32 {
+    p.offset(1)
+}
+
+pub mod selected {
+    // The call to `ignored` is treated like a call to an unknown function.
+    // CHECK-LABEL: final labeling for "caller"
+    pub unsafe fn caller(p: *mut i32) -> i32 {
+        // CHECK-DAG: ([[@LINE+1]]: q): {{.*}}type = READ{{.*}}#
+        let q = super::ignored(p);
+        *q
+    }
+}
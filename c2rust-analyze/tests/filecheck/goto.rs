@@ -0,0 +1,124 @@
+// Control flow as c2rust transpiles C `goto`s: a `current_block` variable selects the code to run
+// next, and `continue` or `break` jumps to it, leaving dead code behind each jump.
+
+extern crate libc;
+
+extern "C" {
+    fn malloc(_: libc::c_ulong) -> *mut libc::c_void;
+    fn free(_: *mut libc::c_void);
+}
+
+// The search loop exits to one of two labels.
+//
+// int find(int *p, int n) {
+//     int i = 0;
+//     for (;;) {
+//         if (i >= n) goto not_found;
+//         if (p[i] == 0) goto found;
+//         i++;
+//     }
+// found:
+//     p[i] = 1;
+//     return i;
+// not_found:
+//     return -1;
+// }
+// CHECK-LABEL: final labeling for "find"
+// CHECK-DAG: ([[@LINE+1]]: mut p): {{.*}}type = READ | WRITE | UNIQUE | OFFSET_ADD | OFFSET_SUB#
+pub unsafe extern "C" fn find(mut p: *mut libc::c_int, mut n: libc::c_int) -> libc::c_int {
+    let mut current_block: u64;
+    let mut i: libc::c_int = 0 as libc::c_int;
+    loop {
+        if i >= n {
+            current_block = 5;
+            break;
+        }
+        if *p.offset(i as isize) == 0 as libc::c_int {
+            current_block = 3;
+            break;
+        }
+        i += 1;
+    }
+    match current_block {
+        3 => {
+            *p.offset(i as isize) = 1 as libc::c_int;
+            return i;
+        }
+        _ => return -(1 as libc::c_int),
+    };
+}
+
+// Jumps into the middle of a loop, from before it and from its end, give the loop two entries.
+//
+// int *fill(int n) {
+//     int *p = malloc(n * sizeof(int));
+//     int i = 0;
+//     if (n > 0) goto body;
+//     goto done;
+// top:
+//     i++;
+//     if (i >= n) goto done;
+// body:
+//     p[i] = i;
+//     goto top;
+// done:
+//     return p;
+// }
+// CHECK-LABEL: final labeling for "fill"
+pub unsafe extern "C" fn fill(mut n: libc::c_int) -> *mut libc::c_int {
+    let mut current_block: u64;
+    // CHECK-DAG: ([[@LINE+1]]: mut p): {{.*}}type = READ | WRITE | UNIQUE | OFFSET_ADD | OFFSET_SUB#
+    let mut p: *mut libc::c_int = malloc(
+        (n as libc::c_ulong).wrapping_mul(::std::mem::size_of::<libc::c_int>() as libc::c_ulong),
+    ) as *mut libc::c_int;
+    let mut i: libc::c_int = 0 as libc::c_int;
+    if n > 0 as libc::c_int {
+        current_block = 7;
+    } else {
+        current_block = 9;
+    }
+    'c_12: loop {
+        match current_block {
+            7 => {
+                *p.offset(i as isize) = i;
+                current_block = 11;
+                continue 'c_12;
+            }
+            11 => {
+                i += 1;
+                if i >= n {
+                    current_block = 9;
+                    continue 'c_12;
+                }
+                current_block = 7;
+            }
+            _ => return p,
+        }
+    }
+}
+
+// The cleanup label is reached from both the error path and the normal exit.
+//
+// int sum(int n) {
+//     int total = 0;
+//     int *buf = malloc(sizeof(int));
+//     if (!buf) goto out;
+//     *buf = n;
+//     total = *buf;
+//     free(buf);
+// out:
+//     return total;
+// }
+// CHECK-LABEL: final labeling for "sum"
+pub unsafe extern "C" fn sum(mut n: libc::c_int) -> libc::c_int {
+    let mut total: libc::c_int = 0 as libc::c_int;
+    // CHECK-DAG: ([[@LINE+1]]: mut buf): {{.*}}type = READ | WRITE | UNIQUE | FREE#
+    let mut buf: *mut libc::c_int =
+        malloc(::std::mem::size_of::<libc::c_int>() as libc::c_ulong) as *mut libc::c_int;
+    if !buf.is_null() {
+        *buf = n;
+        total = *buf;
+        free(buf as *mut libc::c_void);
+    }
+    return total;
+}
@@ -0,0 +1,22 @@
+use std::arch::asm;
+
+// Inline assembly may access memory through the pointers passed to it, and may keep them, so they
+// stay raw.
+
+// CHECK-LABEL: final labeling for "load"
+pub unsafe fn load(p: *const i32) -> i32 {
+    let x: i32;
+    // CHECK-DAG: ([[@LINE+1]]: q): {{.*}}type = READ{{.*}}type flags = FIXED{{.*}}#
+    let q = p;
+    asm!("mov {0:e}, [{1}]", out(reg) x, in(reg) q);
+    x
+}
+
+// CHECK-LABEL: final labeling for "not_passed"
+pub unsafe fn not_passed(p: *mut i32) {
+    // Pointers the assembly never sees are analyzed as usual.
+    // CHECK-DAG: ([[@LINE+1]]: q): &mut i32
+    let q = p;
+    *q = 1;
+    asm!("nop");
+}
@@ -0,0 +1,16 @@
+//! --output-format=json
+
+pub unsafe fn set(p: *mut i32) {
+    let q = p;
+    *q = 1;
+}
+
+// CHECK: "crate_name": "json_output",
+// CHECK: "def_path": "set",
+// CHECK: "pointers": [
+// CHECK: "perms": [
+// CHECK-NEXT: "READ",
+// CHECK-NEXT: "WRITE",
+// CHECK: "equiv": [
+// CHECK-NEXT: "_1 (p)",
+// CHECK-NEXT: "_2 (q)",
@@ -0,0 +1,27 @@
+//! --keep-going
+
+fn id<T>(x: T) -> T {
+    x
+}
+
+// Calls to generic functions aren't supported yet, so the analysis gives up on this function.
+pub unsafe fn calls_generic(p: *mut i32) {
+    *id(p) = 1;
+}
+
+// CHECK-LABEL: final labeling for "still_analyzed"
+// CHECK-DAG: ([[@LINE+1]]: p): {{.*}}type = {{.*}}WRITE | UNIQUE#
+pub unsafe fn still_analyzed(p: *mut i32) {
+    *p = 1;
+}
+
+// `calls_generic` writes through its parameter, which its caller can't see, so the caller is
+// solved as if it passed the pointer to an unknown function that takes `*mut i32`.
+// CHECK-LABEL: final labeling for "calls_failed"
+// CHECK-DAG: ([[@LINE+1]]: p): {{.*}}type = READ | WRITE | UNIQUE#
+pub unsafe fn calls_failed(p: *mut i32) {
+    calls_generic(p);
+}
+
+// CHECK-LABEL: unsupported constructs: 1 functions not analyzed
+// CHECK: calls_generic at {{.*}}keep_going.rs:9:
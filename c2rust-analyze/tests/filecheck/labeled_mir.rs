@@ -0,0 +1,21 @@
+//! --dump-labeled-mir=set
+
+// CHECK-LABEL: labeled MIR for "set":
+// CHECK: let _1: g0#*mut i32
+// CHECK: let _2: [[Q:l[0-9]+]]#*mut i32
+// CHECK: bb0: {
+// CHECK: _2 = _1;
+// CHECK-NEXT: // _2: [[Q]]#*mut i32
+// CHECK-NEXT: // _1: g0#*mut i32
+// CHECK-NEXT: // rvalue: g0#*mut i32
+// CHECK: (*_2) = const 1_i32;
+// CHECK-NEXT: // (*_2): {{.*}}#i32
+// CHECK-NOT: labeled MIR for "get"
+pub unsafe fn set(p: *mut i32) {
+    let q = p;
+    *q = 1;
+}
+
+pub unsafe fn get(p: *const i32) -> i32 {
+    *p
+}
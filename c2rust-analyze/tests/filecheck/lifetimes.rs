@@ -0,0 +1,83 @@
+// CHECK-LABEL: === rewritten struct definitions ===
+// CHECK: struct Holder<'a> {
+// CHECK-NEXT: p: &'a mut i32,
+pub struct Holder {
+    pub p: *mut i32,
+}
+
+// Structs that contain a struct with a new lifetime get one too.
+// CHECK: struct Outer<'a> {
+// CHECK-NEXT: h: Holder<'a>,
+pub struct Outer {
+    pub h: Holder,
+}
+
+// CHECK-LABEL: rewritten signature for "set"
+// CHECK-NEXT: fn set(h: &Holder<'_>)
+pub unsafe fn set(h: *mut Holder) {
+    *(*h).p = 1;
+}
+
+// CHECK-LABEL: rewritten signature for "set_outer"
+// CHECK-NEXT: fn set_outer(o: &Outer<'_>)
+pub unsafe fn set_outer(o: *mut Outer) {
+    *(*o).h.p = 1;
+}
+
+// The result is derived from `p` only, so it shares `p`'s lifetime.
+// CHECK-LABEL: rewritten signature for "pick"
+// CHECK-NEXT: fn pick<'a, 'b>(p: &'a i32, q: &'b i32) -> &'a i32
+pub unsafe fn pick(p: *const i32, q: *const i32) -> *const i32 {
+    let _x = *q;
+    p
+}
+
+// A single input lifetime is elided.
+// CHECK-LABEL: rewritten signature for "identity"
+// CHECK-NEXT: fn identity(p: &i32) -> &i32
+pub unsafe fn identity(p: *const i32) -> *const i32 {
+    p
+}
+
+pub struct Cursor {
+    pub p: *const i32,
+}
+
+// The result is loaded from a field of the struct `c` points to, so it has the struct's lifetime,
+// which is `c`'s own.
+// CHECK-LABEL: rewritten signature for "get"
+// CHECK-NEXT: fn get<'a>(c: &'a Cursor<'a>) -> &'a i32
+pub unsafe fn get(c: *const Cursor) -> *const i32 {
+    (*c).p
+}
+
+pub struct Slot {
+    pub p: *const i32,
+    pub n: i32,
+}
+
+// A struct behind a `&mut` gets its own lifetime, which the result loaded from its field shares.
+// CHECK-LABEL: rewritten signature for "take"
+// CHECK-NEXT: fn take<'a, 'b>(s: &'a mut Slot<'b>) -> &'b i32
+pub unsafe fn take(s: *mut Slot) -> *const i32 {
+    (*s).n += 1;
+    (*s).p
+}
+
+pub unsafe fn bump(p: *mut i32) -> *mut i32 {
+    *p += 1;
+    p
+}
+
+// A result returned from a call is derived from what the callee's result is derived from.
+// CHECK-LABEL: rewritten signature for "forward"
+// CHECK-NEXT: fn forward(p: &mut i32) -> &mut i32
+pub unsafe fn forward(p: *mut i32) -> *mut i32 {
+    bump(p)
+}
+
+// CHECK-LABEL: rewritten signature for "forward_pick"
+// CHECK-NEXT: fn forward_pick<'a, 'b>(p: &'a i32, q: &'b i32) -> &'a i32
+pub unsafe fn forward_pick(p: *const i32, q: *const i32) -> *const i32 {
+    pick(p, q)
+}
@@ -0,0 +1,40 @@
+//! --box-lists
+
+pub struct Node {
+    pub value: i32,
+    pub next: *mut Node,
+}
+
+pub struct Tree {
+    pub left: *mut Tree,
+    pub right: *mut Tree,
+}
+
+pub struct Outer {
+    pub inner: *mut Inner,
+}
+
+pub struct Inner {
+    pub outer: *mut Outer,
+    pub value: *mut i32,
+}
+
+pub unsafe fn sum(mut n: *mut Node) -> i32 {
+    let mut total = 0;
+    while !n.is_null() {
+        total += (*n).value;
+        n = (*n).next;
+    }
+    total
+}
+
+// CHECK-LABEL: === linked structures ===
+// CHECK: struct Node: singly linked through `next`
+// CHECK-NEXT: suggestion: make `next` an `Option<Box<Node>>` that owns the rest of the list
+// CHECK-NEXT: suggestion: keep the nodes in a `Vec<Node>` arena and make `next` `Option<usize>` indices
+// CHECK: struct Tree: multiply linked through `left`, `right`
+// CHECK-NEXT: suggestion: share the nodes as `Rc<RefCell<Tree>>`, using `Weak` for back pointers
+// CHECK: struct Outer: mutually linked through `inner`
+// CHECK-NEXT: suggestion: keep the nodes in a `Vec<Outer>` arena
+// CHECK: struct Inner: mutually linked through `outer`
+// CHECK-NOT: `value`
@@ -0,0 +1,26 @@
+use std::mem::MaybeUninit;
+
+pub unsafe fn init(p: *mut i32) {
+    *p = 1;
+}
+
+// `p` points to the uninitialized contents of `x`, so it stays raw, along with the parameter of
+// `init` that receives it.
+// CHECK-LABEL: final labeling for "init_in_place"
+pub unsafe fn init_in_place() -> i32 {
+    let mut x = MaybeUninit::<i32>::uninit();
+    // CHECK: ([[@LINE+1]]: p): *mut i32
+    let p = x.as_mut_ptr();
+    init(p);
+    x.assume_init()
+}
+
+// The pointer stored in `x` flows through `new` and `assume_init` unchanged.
+// CHECK-LABEL: final labeling for "wrap_unwrap"
+pub unsafe fn wrap_unwrap(q: *mut i32) -> i32 {
+    let x = MaybeUninit::new(q);
+    // CHECK: ([[@LINE+1]]: r): &mut i32
+    let r = x.assume_init();
+    *r = 1;
+    *r
+}
@@ -0,0 +1,18 @@
+mod inner {
+    pub unsafe fn set(p: *mut i32) {
+        *p = 1;
+    }
+}
+
+pub unsafe fn from_int(addr: usize) -> i32 {
+    let p = addr as *const i32;
+    *p
+}
+
+// CHECK-LABEL: === pointer summary ===
+// CHECK-NEXT: total: 2 pointers, 1 safe (50%): ref 1, option 0, owned 0, cell 0, raw 1
+// CHECK-NEXT: raw because pointer is created from an integer: 1
+// CHECK-NEXT: module crate: 1 pointers, 0 safe (0%): ref 0, option 0, owned 0, cell 0, raw 1
+// CHECK-NEXT: module inner: 1 pointers, 1 safe (100%): ref 1, option 0, owned 0, cell 0, raw 0
+// CHECK-NEXT: fn from_int: 1 pointers, 0 safe (0%): ref 0, option 0, owned 0, cell 0, raw 1
+// CHECK-NEXT: fn inner::set: 1 pointers, 1 safe (100%): ref 1, option 0, owned 0, cell 0, raw 0
@@ -0,0 +1,66 @@
+use std::ptr;
+
+// CHECK-LABEL: final labeling for "null_init"
+// CHECK-DAG: ([[@LINE+1]]: x): &i32
+pub unsafe fn null_init(x: *mut i32, c: bool) -> i32 {
+    // CHECK-DAG: ([[@LINE+2]]: mut p): {{.*}}type flags = NULLABLE#
+    // CHECK-DAG: ([[@LINE+1]]: mut p): std::option::Option<&i32>
+    let mut p = ptr::null_mut();
+    if c {
+        p = x;
+    }
+    if p.is_null() {
+        0
+    } else {
+        *p
+    }
+}
+
+// CHECK-LABEL: final labeling for "null_cast"
+pub unsafe fn null_cast(x: *mut i32) {
+    // CHECK-DAG: ([[@LINE+1]]: mut p): std::option::Option<&mut i32>
+    let mut p = 0 as *mut i32;
+    p = x;
+    *p = 1;
+}
+
+// CHECK-LABEL: final labeling for "checked_param"
+// CHECK-DAG: ([[@LINE+1]]: p): std::option::Option<&mut i32>
+pub unsafe fn checked_param(p: *mut i32) {
+    if !p.is_null() {
+        *p = 1;
+    }
+}
+
+// CHECK-LABEL: final labeling for "unchecked_param"
+// CHECK-DAG: ([[@LINE+1]]: p): &mut i32
+pub unsafe fn unchecked_param(p: *mut i32) {
+    *p = 1;
+}
+
+// The result of the null check is saved and branched on later.  `q` is only assigned when `p`
+// isn't null, so it doesn't need to be an `Option`.
+// CHECK-LABEL: final labeling for "checked_later"
+pub unsafe fn checked_later(p: *mut i32) -> i32 {
+    let null = p.is_null();
+    let mut x = 0;
+    if !null {
+        // CHECK-DAG: ([[@LINE+1]]: q): &i32
+        let q = p;
+        x = *q;
+    }
+    x
+}
+
+// `p` is reassigned after the check, so the check says nothing about the value copied to `q`.
+// CHECK-LABEL: final labeling for "checked_then_reassigned"
+pub unsafe fn checked_then_reassigned(mut p: *mut i32, r: *mut i32) -> i32 {
+    let null = p.is_null();
+    p = r;
+    if !null {
+        // CHECK-DAG: ([[@LINE+1]]: q): std::option::Option<&i32>
+        let q = p;
+        return *q;
+    }
+    0
+}
@@ -0,0 +1,78 @@
+//! --rewrite-diff
+
+// CHECK-LABEL: final labeling for "parse"
+// CHECK: out-params for "parse": [1]
+// CHECK-NEXT: new return type: (i32, i32)
+pub unsafe fn parse(x: i32, out: *mut i32) -> i32 {
+    *out = x * 2;
+    if x < 0 {
+        return -1;
+    }
+    0
+}
+
+// CHECK-LABEL: final labeling for "fill"
+// CHECK: out-params for "fill": [0]
+// CHECK-NEXT: new return type: i32
+pub unsafe fn fill(out: *mut i32) {
+    *out = 1;
+}
+
+// `out` is read as well as written, so it stays a parameter.
+// CHECK-LABEL: final labeling for "increment"
+// CHECK-NOT: out-params for
+pub unsafe fn increment(out: *mut i32) {
+    *out += 1;
+}
+
+// `out` is not written on every path.
+// CHECK-LABEL: final labeling for "maybe_fill"
+// CHECK-NOT: out-params for
+pub unsafe fn maybe_fill(c: bool, out: *mut i32) {
+    if c {
+        *out = 1;
+    }
+}
+
+// Calls through a function pointer can't be rewritten, so a function whose address is taken keeps
+// its signature.
+// CHECK-LABEL: final labeling for "fill_callback"
+// CHECK-NOT: out-params for
+pub unsafe fn fill_callback(out: *mut i32) {
+    *out = 2;
+}
+
+// CHECK-LABEL: final labeling for "caller"
+pub unsafe fn caller() -> i32 {
+    let mut v = 0;
+    let status = parse(3, &mut v);
+    let mut w = 0;
+    fill(&mut w);
+    let f: unsafe fn(*mut i32) = fill_callback;
+    f(&mut w);
+    status + v + w
+}
+
+// CHECK: +++ b/{{.*}}out_params.rs
+// CHECK: {{^}}-pub unsafe fn parse(x: i32, out: *mut i32) -> i32 {
+// CHECK-NEXT: {{^}}-    *out = x * 2;
+// CHECK-NEXT: {{^}}+pub unsafe fn parse(x: i32) -> (i32, i32) {
+// CHECK-NEXT: {{^}}+    let mut out;
+// CHECK-NEXT: {{^}}+    out = x * 2;
+// CHECK: {{^}}-        return -1;
+// CHECK-NEXT: {{^}}+        return (-1, out);
+// CHECK: {{^}}-    0
+// CHECK-NEXT: {{^}}+    (0, out)
+
+// CHECK: {{^}}-pub unsafe fn fill(out: *mut i32) {
+// CHECK-NEXT: {{^}}-    *out = 1;
+// CHECK-NEXT: {{^}}+pub unsafe fn fill() -> i32 {
+// CHECK-NEXT: {{^}}+    let mut out;
+// CHECK-NEXT: {{^}}+    out = 1;
+// CHECK-NEXT: {{^}}+    out
+
+// CHECK-NOT: {{^}}+pub unsafe fn fill_callback(
+// CHECK: {{^}}-    let status = parse(3, &mut v);
+// CHECK-NEXT: {{^}}+    let status = { let (__ret, __out1) = parse(3); v = __out1; __ret };
+// CHECK: {{^}}-    fill(&mut w);
+// CHECK-NEXT: {{^}}+    { let __out0 = fill(); w = __out0; };
@@ -0,0 +1,21 @@
+use std::ptr;
+
+#[repr(C, packed)]
+pub struct Packed {
+    pub a: u8,
+    pub b: u32,
+}
+
+// `b` may be unaligned, so the pointer to it stays raw, while `s` and the pointer to `a`, which
+// is always aligned, become references.
+// CHECK-LABEL: final labeling for "packed_fields"
+// CHECK-DAG: ([[@LINE+1]]: s): &mut Packed
+pub unsafe fn packed_fields(s: *mut Packed) {
+    // CHECK-DAG: ([[@LINE+1]]: p): std::ptr::NonNull<u32>
+    let p = ptr::addr_of_mut!((*s).b);
+    *p = 1;
+    // CHECK-DAG: ([[@LINE+1]]: q): &mut u8
+    let q = ptr::addr_of_mut!((*s).a);
+    *q = 2;
+}
+// CHECK-DAG: NonNullNewUnchecked
@@ -0,0 +1,19 @@
+//! --pdg=tests/filecheck/pdg_facts.json
+
+// `p` and `q` are both copied from `r`, and `p` is written through after `q` is created, so
+// statically neither `r` nor `p` is unique, and they would become `Cell`s.  `pdg_facts.json` says
+// every use of them at run time was unique, since `q` is never used, so they become `&mut`
+// instead.  The facts identify the function by its `DefPathHash`, which depends on the
+// toolchain, so they need updating when it changes.
+// CHECK-LABEL: === pdg facts ===
+// CHECK-DAG: unique_at_run_time: _2 ({{.*}}): unique at run time
+// CHECK-DAG: unique_at_run_time: _3 ({{.*}}): unique at run time
+// CHECK-LABEL: final labeling for "unique_at_run_time"
+pub unsafe fn unique_at_run_time(x: *mut i32) {
+    // CHECK-DAG: ([[@LINE+1]]: r): &mut i32
+    let r = x;
+    // CHECK-DAG: ([[@LINE+1]]: p): &mut i32
+    let p = r;
+    let q = r;
+    *p = 1;
+}
@@ -0,0 +1,41 @@
+extern crate libc;
+
+extern "C" {
+    fn malloc(_: libc::c_ulong) -> *mut libc::c_void;
+    fn free(_: *mut libc::c_void);
+}
+
+static mut COUNTER: i32 = 0;
+
+// CHECK-LABEL: final labeling for "heap"
+pub unsafe fn heap() {
+    // CHECK-DAG: ([[@LINE+2]]: p): {{.*}}type flags = {{.*}}OWNED{{.*}}HEAP{{.*}}#
+    // CHECK-DAG: ([[@LINE+1]]: p): std::boxed::Box<i32>
+    let p = malloc(4) as *mut i32;
+    *p = 1;
+    free(p as *mut libc::c_void);
+}
+
+// CHECK-LABEL: final labeling for "stack"
+pub unsafe fn stack() -> i32 {
+    let mut x = 1;
+    // CHECK-DAG: ([[@LINE+2]]: p): {{.*}}type flags = {{.*}}STACK{{.*}}#
+    // CHECK-DAG: ([[@LINE+1]]: p): &mut i32
+    let p = &mut x as *mut i32;
+    *p += 1;
+    *p
+}
+
+// A pointer to a static keeps its provenance when it's passed to another function.
+// CHECK-LABEL: final labeling for "bump"
+// CHECK-DAG: ([[@LINE+1]]: p): {{.*}}type flags = {{.*}}STATIC{{.*}}#
+pub unsafe fn bump(p: *mut i32) {
+    *p += 1;
+}
+
+// CHECK-LABEL: final labeling for "bump_counter"
+pub unsafe fn bump_counter() {
+    // CHECK-DAG: ([[@LINE+1]]: p): {{.*}}type flags = {{.*}}STATIC{{.*}}#
+    let p = &mut COUNTER as *mut i32;
+    bump(p);
+}
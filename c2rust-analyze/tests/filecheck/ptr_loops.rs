@@ -0,0 +1,66 @@
+//! --rewrite-diff
+
+// CHECK-LABEL: pointer loops for "zero"
+// CHECK-NEXT: p (line [[@LINE+5]]): for p in buf[..usize::try_from(n as isize).unwrap_or(0)].iter_mut()
+pub unsafe fn zero(buf: *mut i32, n: i32) {
+    let mut p: *mut i32 = 0 as *mut i32;
+    let end: *mut i32 = buf.offset(n as isize);
+    p = buf;
+    while p < end {
+        *p = 0;
+        p = p.offset(1);
+    }
+}
+
+// CHECK-LABEL: pointer loops for "sum"
+// CHECK-NEXT: p (line [[@LINE+4]]): for p in buf[..usize::try_from(n as isize).unwrap_or(0)].iter()
+pub unsafe fn sum(buf: *mut i32, n: i32) -> i32 {
+    let mut total = 0;
+    let mut p: *mut i32 = buf;
+    while p != buf.offset(n as isize) {
+        total += *p;
+        p = p.offset(1);
+    }
+    total
+}
+
+// `p` is used after the loop, so it can't become the loop variable.
+// CHECK-NOT: pointer loops for "find"
+pub unsafe fn find(buf: *mut i32, n: i32) -> *mut i32 {
+    let mut p: *mut i32 = buf;
+    while p < buf.offset(n as isize) {
+        if *p == 0 {
+            break;
+        }
+        p = p.offset(1);
+    }
+    p
+}
+
+// Skipping the increment with `continue` would loop forever, so the loop is left alone.
+// CHECK-NOT: pointer loops for "skip"
+pub unsafe fn skip(buf: *mut i32, n: i32) {
+    let mut p: *mut i32 = buf;
+    while p < buf.offset(n as isize) {
+        if *p == 0 {
+            continue;
+        }
+        *p = 1;
+        p = p.offset(1);
+    }
+}
+
+// CHECK: +++ b/{{.*}}ptr_loops.rs
+// CHECK: {{^}}-    let end: *mut i32 = buf.offset(n as isize);
+// CHECK-NEXT: {{^}}-    p = buf;
+// CHECK-NEXT: {{^}}-    while p < end {
+// CHECK-NEXT: {{^}}+    for p in buf[..usize::try_from(n as isize).unwrap_or(0)].iter_mut() {
+// CHECK-NEXT: {{^}}         *p = 0;
+// CHECK-NEXT: {{^}}-        p = p.offset(1);
+// CHECK-NEXT: {{^}}     }
+// CHECK: {{^}}-    let mut p: *mut i32 = buf;
+// CHECK-NEXT: {{^}}-    while p != buf.offset(n as isize) {
+// CHECK-NEXT: {{^}}+    for p in buf[..usize::try_from(n as isize).unwrap_or(0)].iter() {
+// CHECK-NEXT: {{^}}         total += *p;
+// CHECK-NEXT: {{^}}-        p = p.offset(1);
+// CHECK-NEXT: {{^}}     }
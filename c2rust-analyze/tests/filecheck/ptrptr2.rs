@@ -0,0 +1,25 @@
+
+// CHECK-LABEL: final labeling for "ptrptr2_load_shared"
+// CHECK-DAG: ([[@LINE+1]]: pp): &&std::cell::Cell<i32>
+pub unsafe fn ptrptr2_load_shared(pp: *const *mut i32) {
+    // `*pp` is behind a shared reference, so it can't be copied out as a `&mut`.
+    // CHECK-DAG: ([[@LINE+1]]: p): &std::cell::Cell<i32>
+    let p = *pp;
+    *p = 1;
+}
+
+// CHECK-LABEL: final labeling for "ptrptr2_write_through"
+// CHECK-DAG: ([[@LINE+1]]: pp): &mut &mut i32
+pub unsafe fn ptrptr2_write_through(pp: *mut *mut i32) {
+    **pp = 1;
+}
+
+// CHECK-LABEL: final labeling for "ptrptr2_nullable_inner"
+// CHECK-DAG: ([[@LINE+1]]: pp): &mut std::option::Option<&mut i32>
+pub unsafe fn ptrptr2_nullable_inner(pp: *mut *mut i32) {
+    if !(*pp).is_null() {
+        // The inner pointer is borrowed, not moved out of `*pp`.
+        // CHECK-DAG: UnwrapRef { mutbl: true }
+        **pp = 1;
+    }
+}
@@ -0,0 +1,52 @@
+extern crate libc;
+
+extern "C" {
+    fn qsort(
+        base: *mut libc::c_void,
+        nmemb: libc::size_t,
+        size: libc::size_t,
+        compar: Option<
+            unsafe extern "C" fn(*const libc::c_void, *const libc::c_void) -> libc::c_int,
+        >,
+    );
+    fn bsearch(
+        key: *const libc::c_void,
+        base: *const libc::c_void,
+        nmemb: libc::size_t,
+        size: libc::size_t,
+        compar: Option<
+            unsafe extern "C" fn(*const libc::c_void, *const libc::c_void) -> libc::c_int,
+        >,
+    ) -> *mut libc::c_void;
+}
+
+unsafe extern "C" fn cmp(a: *const libc::c_void, b: *const libc::c_void) -> libc::c_int {
+    0
+}
+
+// CHECK-LABEL: final labeling for "sort"
+pub unsafe extern "C" fn sort(p: *mut i32, n: libc::size_t) {
+    // CHECK-DAG: ([[@LINE+1]]: q): {{.*}}type = READ | WRITE | OFFSET_ADD{{.*}}#
+    let q = p;
+    qsort(
+        q as *mut libc::c_void,
+        n,
+        4,
+        Some(cmp as unsafe extern "C" fn(*const libc::c_void, *const libc::c_void) -> libc::c_int),
+    );
+}
+
+// CHECK-LABEL: final labeling for "search"
+pub unsafe extern "C" fn search(key: *const i32, p: *const i32, n: libc::size_t) -> *mut i32 {
+    // CHECK-DAG: ([[@LINE+1]]: q): {{.*}}type = READ | OFFSET_ADD{{.*}}#
+    let q = p;
+    // CHECK-DAG: ([[@LINE+1]]: r): {{.*}}type flags = NULLABLE{{.*}}#
+    let r = bsearch(
+        key as *const libc::c_void,
+        q as *const libc::c_void,
+        n,
+        4,
+        Some(cmp as unsafe extern "C" fn(*const libc::c_void, *const libc::c_void) -> libc::c_int),
+    ) as *mut i32;
+    r
+}
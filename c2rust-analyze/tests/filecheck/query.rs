@@ -0,0 +1,14 @@
+//! --query=query.rs:11:19
+//! --query=query.rs:12:9
+//! --query=query.rs:13:5
+
+// Each query finds the innermost variable or expression with a pointer type at its location.
+// CHECK-LABEL: === query results ===
+// CHECK-NEXT: query.rs:11:19: `p` in set: &mut i32, perms = {{.*}}WRITE
+// CHECK-NEXT: query.rs:12:9: `q` in set: &mut i32, perms = {{.*}}WRITE
+// CHECK-NEXT: query.rs:13:5: no pointer here
+
+pub unsafe fn set(p: *mut i32) {
+    let q = p;
+    *q = 1;
+}
@@ -0,0 +1,21 @@
+//! --rewrite-diff
+
+pub unsafe fn set(p: *mut i32, x: i32) {
+    *p += x;
+}
+
+// `p` becomes `&mut i32`, so passing it to `set` would move it.  The first call reborrows it
+// instead, since `p` is used again afterward, but the last call can move it.
+// CHECK-LABEL: final labeling for "set_twice"
+pub unsafe fn set_twice(p: *mut i32) {
+    // CHECK: ([[@LINE+2]]: p, [AssignRvalue, RvalueOperand(0)]):
+    // CHECK-NEXT: Reborrow
+    set(p, 1);
+    // CHECK-NOT: Reborrow
+    set(p, 2);
+}
+
+// CHECK: +++ b/{{.*}}reborrow.rs
+// CHECK: {{^}}-    set(p, 1);
+// CHECK-NEXT: {{^}}+    set(&mut *p, 1);
+// CHECK-NOT: &mut *p, 2
@@ -0,0 +1,20 @@
+// `mutual_a` never writes through `p` itself, but it passes `p` to `mutual_b`, which does.  The
+// two functions form a cycle in the call graph, so the `WRITE` permission must propagate around
+// the cycle into `mutual_a`'s signature.
+
+// CHECK-LABEL: final labeling for "mutual_a"
+// CHECK-DAG: ([[@LINE+1]]: p): &mut i32
+pub unsafe fn mutual_a(p: *mut i32, n: i32) {
+    if n > 0 {
+        mutual_b(p, n - 1);
+    }
+}
+
+// CHECK-LABEL: final labeling for "mutual_b"
+// CHECK-DAG: ([[@LINE+1]]: p): &mut i32
+pub unsafe fn mutual_b(p: *mut i32, n: i32) {
+    *p = n;
+    if n > 0 {
+        mutual_a(p, n - 1);
+    }
+}
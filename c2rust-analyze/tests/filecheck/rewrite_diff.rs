@@ -0,0 +1,24 @@
+//! --rewrite-diff
+
+// CHECK: +++ b/{{.*}}rewrite_diff.rs
+// CHECK: {{^}}-pub unsafe fn set(p: *mut i32, x: i32) {
+// CHECK-NEXT: {{^}}+pub unsafe fn set(p: &mut i32, x: i32) {
+pub unsafe fn set(p: *mut i32, x: i32) {
+    *p += x;
+}
+
+// CHECK: {{^}}-pub unsafe fn first(p: *const i32) -> *const i32 {
+// CHECK-NEXT: {{^}}+pub unsafe fn first(p: &i32) -> &i32 {
+pub unsafe fn first(p: *const i32) -> *const i32 {
+    p
+}
+
+// CHECK: {{^}}-pub unsafe fn check(p: *const i32) -> bool {
+// CHECK-NEXT: {{^}}+pub unsafe fn check(p: std::option::Option<&i32>) -> bool {
+// CHECK: {{^}}-    p.is_null()
+// CHECK-NEXT: {{^}}+    p.is_none()
+pub unsafe fn check(p: *const i32) -> bool {
+    p.is_null()
+}
+
+// CHECK: applied {{[0-9]+}} rewrites to 1 files
@@ -0,0 +1,21 @@
+//! --rewrite-plan=tests/filecheck/rewrite_plan.json, --rewrite-diff
+
+// A rejected rewrite keeps the pointer raw.
+// CHECK-LABEL: final labeling for "rejected"
+// CHECK-DAG: ([[@LINE+1]]: p): *mut i32
+pub unsafe fn rejected(p: *mut i32) {
+    *p = 1;
+}
+
+// CHECK-LABEL: final labeling for "accepted"
+// CHECK-DAG: ([[@LINE+1]]: p): &mut i32
+pub unsafe fn accepted(p: *mut i32) {
+    *p += 1;
+}
+
+// An altered rewrite uses the type from the plan.
+// CHECK: {{^}}-pub unsafe fn altered(p: *mut u8) {
+// CHECK-NEXT: {{^}}+pub unsafe fn altered(p: std::option::Option<&mut u8>) {
+pub unsafe fn altered(p: *mut u8) {
+    *p += 1;
+}
@@ -0,0 +1,52 @@
+//! --rewrite-styles=tests/filecheck/rewrite_styles.json
+
+extern crate libc;
+
+extern "C" {
+    fn malloc(_: libc::c_ulong) -> *mut libc::c_void;
+    fn free(_: *mut libc::c_void);
+}
+
+// CHECK-LABEL: === rewrite styles ===
+// CHECK-DAG: Node::next field: g{{[0-9]+}} pinned to indices
+// CHECK-DAG: keep input 0: g{{[0-9]+}} pinned to raw
+// CHECK-DAG: rewrite_styles.rs:37:9: l{{[0-9]+}} pinned to references
+// CHECK-DAG: warning: rewrite style for missing input 0 doesn't match any pointer
+
+// A struct pinned to indices keeps its pointers raw, for the user to convert to an arena.
+// CHECK-LABEL: === rewritten struct definitions ===
+// CHECK: struct Node {
+// CHECK-NEXT: next: *mut Node,
+pub struct Node {
+    pub next: *mut Node,
+    pub value: i32,
+}
+
+// CHECK-LABEL: final labeling for "keep"
+// CHECK-DAG: ([[@LINE+1]]: p): *mut i32
+pub unsafe fn keep(p: *mut i32) {
+    *p = 1;
+}
+
+// A pointer pinned to references never owns its target, so the freed one stays raw instead of
+// becoming a `Box`, while the unpinned one doesn't.
+// CHECK-LABEL: final labeling for "borrowed"
+pub unsafe extern "C" fn borrowed() {
+    // CHECK-DAG: ([[@LINE+2]]: i): addr_of = UNIQUE, type = READ | WRITE | UNIQUE | FREE#
+    // CHECK-DAG: ([[@LINE+1]]: i): *mut i32
+    let i = malloc(::std::mem::size_of::<i32>() as libc::c_ulong) as *mut i32;
+    *i = 1;
+    free(i as *mut libc::c_void);
+}
+
+// CHECK-LABEL: final labeling for "owned"
+pub unsafe extern "C" fn owned() {
+    // CHECK-DAG: ([[@LINE+1]]: i): std::boxed::Box<i32>
+    let i = malloc(::std::mem::size_of::<i32>() as libc::c_ulong) as *mut i32;
+    *i = 1;
+    free(i as *mut libc::c_void);
+}
+
+pub unsafe fn walk(n: *mut Node) -> i32 {
+    (*(*n).next).value
+}
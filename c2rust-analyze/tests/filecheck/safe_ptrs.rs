@@ -0,0 +1,30 @@
+// Code that was partly rewritten by hand already uses `Box`, `Vec`, and `NonNull`.  These are
+// labeled like pointers, so raw pointers taken from them stay connected to them.
+
+// CHECK-LABEL: final labeling for "from_box"
+pub unsafe fn from_box(b: &mut Box<i32>) -> i32 {
+    // CHECK-DAG: ([[@LINE+2]]: p): {{.*}}type flags = {{.*}}HEAP{{.*}}#
+    // CHECK-DAG: ([[@LINE+1]]: p): &i32
+    let p = &**b as *const i32;
+    *p
+}
+
+// `Box::into_raw` and `Box::from_raw` pass the ownership of the allocation through a raw pointer.
+// CHECK-LABEL: final labeling for "round_trip"
+pub unsafe fn round_trip(b: Box<i32>) -> Box<i32> {
+    // CHECK-DAG: ([[@LINE+1]]: p): {{.*}}type = {{.*}}FREE{{.*}}type flags = {{.*}}HEAP{{.*}}#
+    let p = Box::into_raw(b);
+    *p += 1;
+    Box::from_raw(p)
+}
+
+// CHECK-LABEL: final labeling for "vec_sum"
+pub unsafe fn vec_sum(v: &mut Vec<i32>, n: usize) -> i32 {
+    // CHECK-DAG: ([[@LINE+1]]: p): {{.*}}type flags = {{.*}}HEAP{{.*}}#
+    let p = v.as_mut_ptr();
+    let mut sum = 0;
+    for i in 0..n {
+        sum += *p.offset(i as isize);
+    }
+    sum
+}
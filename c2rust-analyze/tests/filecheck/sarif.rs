@@ -0,0 +1,26 @@
+//! --output-format=sarif, --keep-going
+
+fn id<T>(x: T) -> T {
+    x
+}
+
+pub unsafe fn calls_generic(p: *mut i32) {
+    *id(p) = 1;
+}
+
+#[c2rust::keep_raw]
+pub unsafe fn keep_raw(p: *mut i32) {
+    *p = 1;
+}
+
+// CHECK: "version": "2.1.0",
+// CHECK: "name": "c2rust-analyze",
+// CHECK: "ruleId": "raw-pointer",
+// CHECK-NEXT: "level": "note",
+// CHECK-NEXT: "message": {
+// CHECK-NEXT: "text": "`p` is kept raw because it is fixed
+// CHECK: "ruleId": "unsupported-construct",
+// CHECK-NEXT: "level": "error",
+// CHECK-NEXT: "message": {
+// CHECK-NEXT: "text": "giving up on calls_generic:
+// CHECK: "uri": "{{.*}}sarif.rs",
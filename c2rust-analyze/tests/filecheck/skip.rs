@@ -0,0 +1,15 @@
+// CHECK: skipping {{.*}}skipped{{.*}} due to #[c2rust::skip]
+// CHECK-NOT: final labeling for "skipped"
+#[c2rust::skip]
+pub unsafe fn skipped(p: *mut i32) -> *mut i32 {
+    p.offset(1)
+}
+
+// The call to `skipped` is treated like a call to an unknown function, so the rest of
+// `caller` is still analyzed.
+// CHECK-LABEL: final labeling for "caller"
+pub unsafe fn caller(p: *mut i32) -> i32 {
+    // CHECK-DAG: ([[@LINE+1]]: q): {{.*}}type = READ{{.*}}#
+    let q = skipped(p);
+    *q
+}
@@ -0,0 +1,17 @@
+//! --stable-pointer-names
+extern crate libc;
+
+extern "C" {
+    fn malloc(_: libc::c_ulong) -> *mut libc::c_void;
+}
+
+// Pointers are named by the item they belong to and a hash of where they appear in it, rather
+// than by `PointerId`.
+// CHECK-LABEL: final labeling for "fill"
+// CHECK: _1 (p): addr_of = fill@{{[0-9a-f]+}}, type = fill@{{[0-9a-f]+}}#*mut i32
+// CHECK-LABEL: allocation counts for "fill"
+// CHECK-NEXT: fill@{{[0-9a-f]+}}: 4 elements
+pub unsafe fn fill(p: *mut i32) {
+    let q = malloc(16) as *mut i32;
+    *q = *p;
+}
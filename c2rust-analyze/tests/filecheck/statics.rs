@@ -0,0 +1,126 @@
+//! --crate-type=bin, --rewrite-diff
+
+// CHECK-LABEL: === rewritten statics ===
+
+// CHECK-DAG: static LIMIT: i32 (immutable)
+static mut LIMIT: i32 = 10;
+
+// Written only once, in `main`.
+// CHECK-DAG: static CONFIG: once_cell::sync::OnceCell<Config> (write-once)
+static mut CONFIG: Config = Config { verbose: false };
+
+// Written in only one place, but `init` may be called more than once.
+// CHECK-DAG: static DEFAULTS: std::sync::Mutex<Config> (mutex)
+static mut DEFAULTS: Config = Config { verbose: false };
+
+// Written only once, in `main`, but reads couldn't copy the value out of a `OnceCell`.
+// CHECK-DAG: static LOG: std::sync::Mutex<Log> (mutex)
+static mut LOG: Log = Log { lines: 0 };
+
+// CHECK-DAG: static COUNTER: std::sync::atomic::AtomicU32 (atomic)
+static mut COUNTER: u32 = 0;
+
+// CHECK-DAG: static STATE: std::sync::Mutex<State> (mutex)
+static mut STATE: State = State { a: 0, b: 0 };
+
+// Statics whose address is taken are left alone.
+// CHECK-NOT: static BUF:
+static mut BUF: [u8; 4] = [0; 4];
+
+// Locking twice in one expression would deadlock.
+// CHECK-NOT: static PAIR:
+static mut PAIR: State = State { a: 0, b: 0 };
+
+#[derive(Clone, Copy)]
+pub struct Config {
+    verbose: bool,
+}
+
+pub struct Log {
+    lines: i32,
+}
+
+pub struct State {
+    a: i32,
+    b: i32,
+}
+
+pub fn main() {
+    unsafe {
+        CONFIG = Config { verbose: true };
+        init(false);
+        LOG = Log { lines: 1 };
+        tick();
+    }
+}
+
+pub unsafe fn init(verbose: bool) {
+    DEFAULTS = Config { verbose };
+}
+
+pub unsafe fn tick() -> u32 {
+    if CONFIG.verbose && DEFAULTS.verbose && COUNTER < LIMIT as u32 {
+        COUNTER += 1;
+    }
+    COUNTER
+}
+
+pub unsafe fn lines() -> i32 {
+    LOG.lines
+}
+
+pub unsafe fn reset() {
+    COUNTER = 0;
+    STATE.a = 0;
+    STATE.b = 0;
+}
+
+pub unsafe fn fill(c: u8) {
+    let p = BUF.as_mut_ptr();
+    *p = c;
+}
+
+pub unsafe fn set_pair(a: i32, b: i32) {
+    PAIR = State { a, b };
+}
+
+pub unsafe fn sum() -> i32 {
+    PAIR.a + PAIR.b
+}
+
+// CHECK: {{^}}-static mut LIMIT: i32 = 10;
+// CHECK-NEXT: {{^}}+static LIMIT: i32 = 10;
+// CHECK: {{^}}-static mut CONFIG: Config = Config { verbose: false };
+// CHECK-NEXT: {{^}}+static CONFIG: once_cell::sync::OnceCell<Config> = <once_cell::sync::OnceCell<Config>>::new();
+// CHECK: {{^}}-static mut DEFAULTS: Config = Config { verbose: false };
+// CHECK-NEXT: {{^}}+static DEFAULTS: std::sync::Mutex<Config> = <std::sync::Mutex<Config>>::new(Config { verbose: false });
+// CHECK: {{^}}-static mut LOG: Log = Log { lines: 0 };
+// CHECK-NEXT: {{^}}+static LOG: std::sync::Mutex<Log> = <std::sync::Mutex<Log>>::new(Log { lines: 0 });
+// CHECK: {{^}}-static mut COUNTER: u32 = 0;
+// CHECK-NEXT: {{^}}+static COUNTER: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+// CHECK: {{^}}-static mut STATE: State = State { a: 0, b: 0 };
+// CHECK-NEXT: {{^}}+static STATE: std::sync::Mutex<State> = <std::sync::Mutex<State>>::new(State { a: 0, b: 0 });
+
+// CHECK: {{^}}-        CONFIG = Config { verbose: true };
+// CHECK-NEXT: {{^}}+        assert!(CONFIG.set(Config { verbose: true }).is_ok());
+// CHECK: {{^}}-        LOG = Log { lines: 1 };
+// CHECK-NEXT: {{^}}+        *LOG.lock().unwrap() = Log { lines: 1 };
+// CHECK: {{^}}-    DEFAULTS = Config { verbose };
+// CHECK-NEXT: {{^}}+    *DEFAULTS.lock().unwrap() = Config { verbose };
+
+// A `OnceCell` that hasn't been set yet reads as the original initializer.
+// CHECK: {{^}}-    if CONFIG.verbose && DEFAULTS.verbose && COUNTER < LIMIT as u32 {
+// CHECK-NEXT: {{^}}-        COUNTER += 1;
+// CHECK-NEXT: {{^}}+    if (*CONFIG.get().unwrap_or(&(Config { verbose: false }))).verbose && (*DEFAULTS.lock().unwrap()).verbose && COUNTER.load(std::sync::atomic::Ordering::SeqCst) < LIMIT as u32 {
+// CHECK-NEXT: {{^}}+        COUNTER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+// CHECK: {{^}}-    COUNTER
+// CHECK-NEXT: {{^}}+    COUNTER.load(std::sync::atomic::Ordering::SeqCst)
+// CHECK: {{^}}-    LOG.lines
+// CHECK-NEXT: {{^}}+    (*LOG.lock().unwrap()).lines
+// CHECK: {{^}}-    COUNTER = 0;
+// CHECK-NEXT: {{^}}-    STATE.a = 0;
+// CHECK-NEXT: {{^}}-    STATE.b = 0;
+// CHECK-NEXT: {{^}}+    COUNTER.store(0, std::sync::atomic::Ordering::SeqCst);
+// CHECK-NEXT: {{^}}+    (*STATE.lock().unwrap()).a = 0;
+// CHECK-NEXT: {{^}}+    (*STATE.lock().unwrap()).b = 0;
+// CHECK-NOT: {{^}}+{{.*}}PAIR
@@ -0,0 +1,16 @@
+//! --stats
+
+// CHECK-LABEL: === statistics ===
+// CHECK: labeling {{.*}}s, peak memory
+// CHECK: solving {{.*}}s, peak memory
+// CHECK: pointers constraints function
+// CHECK-DAG: {{[0-9]+}} {{[0-9]+}} copy
+// CHECK-DAG: {{[0-9]+}} {{[0-9]+}} caller
+
+pub unsafe fn copy(p: *mut i32, q: *const i32) {
+    *p = *q;
+}
+
+pub unsafe fn caller(p: *mut i32) {
+    copy(p, p);
+}
@@ -0,0 +1,33 @@
+#![feature(thread_local)]
+
+// `_Thread_local` variables transpile to thread-local statics, which are accessed through
+// `Rvalue::ThreadLocalRef` instead of a constant pointer.
+
+#[thread_local]
+static mut ERRNO: i32 = 0;
+
+#[thread_local]
+static mut LAST: *mut i32 = 0 as *mut i32;
+
+// CHECK-LABEL: final labeling for "set_errno"
+pub unsafe fn set_errno(v: i32) {
+    // CHECK-DAG: ([[@LINE+2]]: p): {{.*}}type flags = {{.*}}STATIC{{.*}}#
+    // CHECK-DAG: ([[@LINE+1]]: p): &mut i32
+    let p = &mut ERRNO as *mut i32;
+    *p = v;
+}
+
+// CHECK-LABEL: final labeling for "remember"
+pub unsafe fn remember(p: *mut i32) {
+    LAST = p;
+}
+
+// CHECK-LABEL: final labeling for "recall"
+pub unsafe fn recall() -> i32 {
+    // CHECK-DAG: ([[@LINE+1]]: q): {{.*}}type = READ{{.*}}#
+    let q = LAST;
+    if q.is_null() {
+        return *&ERRNO;
+    }
+    *q
+}
@@ -0,0 +1,37 @@
+use std::mem;
+
+#[repr(C)]
+pub struct Pair {
+    a: *mut i32,
+    b: u32,
+}
+
+#[repr(C)]
+pub struct OtherPair {
+    x: *mut i32,
+    y: i32,
+}
+
+// CHECK-LABEL: final labeling for "same_layout"
+pub unsafe fn same_layout(p: *mut Pair) -> i32 {
+    // CHECK-DAG: ([[@LINE+1]]: q): {{.*}}type = READ{{.*}}#
+    let q = mem::transmute::<*mut Pair, *mut OtherPair>(p);
+    (*q).y
+}
+
+// CHECK-LABEL: final labeling for "int_to_ptr"
+pub unsafe fn int_to_ptr(addr: usize) -> i32 {
+    // CHECK-DAG: ([[@LINE+2]]: p): {{.*}}type flags = FIXED{{.*}}#
+    // CHECK-DAG: ([[@LINE+1]]: p): *mut i32
+    let p = addr as *mut i32;
+    // CHECK-DAG: ([[@LINE+1]]: q): *mut i32
+    let q = p;
+    *q
+}
+
+// CHECK-LABEL: final labeling for "transmute_from_int"
+pub unsafe fn transmute_from_int(addr: usize) -> i32 {
+    // CHECK-DAG: ([[@LINE+1]]: p): *const i32
+    let p = mem::transmute::<usize, *const i32>(addr);
+    *p
+}
@@ -0,0 +1,30 @@
+pub union PtrPtr {
+    pub a: *mut *mut i32,
+    pub b: *mut *mut u32,
+}
+
+// All fields of a union share storage, so the inner pointers of `a` and `b` must be labeled with
+// the same `PointerId`.
+// CHECK-LABEL: final labeling for "union_fields_alias"
+pub unsafe fn union_fields_alias(u: *mut PtrPtr) {
+    // CHECK-DAG: ([[@LINE+1]]: a): {{.*}}type = {{[lg][0-9]+}}#*mut *mut i32{{\[}}[[LABEL:[lg][0-9]+]]#*mut i32[NONE#i32[]]]
+    let a = (*u).a;
+    // CHECK-DAG: ([[@LINE+1]]: b): {{.*}}type = {{[lg][0-9]+}}#*mut *mut u32{{\[}}[[LABEL]]#*mut u32[NONE#u32[]]]
+    let b = (*u).b;
+    **a = 1;
+    let _x = **b;
+}
+
+pub union Depths {
+    pub a: *mut *mut i32,
+    pub b: *mut *mut i32,
+}
+
+// Only pointers in the same position of the fields are unified, so the outer pointer of `a`,
+// which is only read through, stays distinct from the inner one, which is only written through.
+// CHECK-LABEL: final labeling for "union_depths_distinct"
+pub unsafe fn union_depths_distinct(u: *mut Depths) {
+    // CHECK-DAG: ([[@LINE+1]]: a): {{.*}}type = {{[^#]*}}#*mut *mut i32{{\[}}WRITE{{( \| UNIQUE)?}}#*mut i32[
+    let a = (*u).a;
+    **a = 1;
+}
@@ -0,0 +1,21 @@
+//! --rewrite-diff
+use std::ptr;
+
+pub unsafe fn set(p: *mut i32) {
+    *p += 1;
+}
+
+// `*p = 1` would have to become a `Cell::set`, which can't be applied to the source yet, so the
+// type of `p` isn't changed either.
+// CHECK: warning: leaving cell_copy unchanged: can't apply [Cell{{.*}}] at {{.*}}unrendered.rs:
+pub unsafe fn cell_copy() -> i32 {
+    let mut x = 0;
+    let p: *mut i32 = ptr::addr_of_mut!(x);
+    let q: *mut i32 = ptr::addr_of_mut!(x);
+    *p = 1;
+    *q
+}
+
+// CHECK: {{^}}-pub unsafe fn set(p: *mut i32) {
+// CHECK-NEXT: {{^}}+pub unsafe fn set(p: &mut i32) {
+// CHECK-NOT: {{^}}+
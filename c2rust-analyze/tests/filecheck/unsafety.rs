@@ -0,0 +1,32 @@
+// CHECK-LABEL: === unsafe reduction ===
+// CHECK-NEXT: unsafe blocks: 4 -> 2
+// CHECK-NEXT: raw pointer types: 5 -> 2
+// CHECK-NEXT: raw pointer dereferences: 4 -> 2
+
+// `p` becomes `&i32`, so the block is no longer needed.
+pub fn read(p: *const i32) -> i32 {
+    unsafe { *p }
+}
+
+// `p` stays raw, so the block stays too.
+pub fn read_addr(addr: usize) -> i32 {
+    let p = addr as *const i32;
+    unsafe { *p }
+}
+
+// `q`'s type is inferred from `p`, so it becomes `&i32` too.
+pub fn read_copy(p: *const i32) -> i32 {
+    let q = p;
+    unsafe { *q }
+}
+
+// `p` could be `&mut i32`, but its type is inferred from `addr_of_mut!`, which isn't rewritten, so
+// it stays raw in the source.
+pub fn write_local() -> i32 {
+    let mut x = 1;
+    let p = std::ptr::addr_of_mut!(x);
+    unsafe {
+        *p = 2;
+    }
+    x
+}
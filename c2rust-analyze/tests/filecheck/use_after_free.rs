@@ -0,0 +1,33 @@
+extern crate libc;
+
+extern "C" {
+    fn malloc(_: libc::c_ulong) -> *mut libc::c_void;
+    fn free(_: *mut libc::c_void);
+}
+
+// `p` is read after it's freed, so replacing the `free` with `drop(p)` wouldn't compile, and `p`
+// stays a raw pointer.
+// CHECK-LABEL: final labeling for "read_after_free"
+pub unsafe fn read_after_free() -> i32 {
+    // CHECK-DAG: ([[@LINE+2]]: p): {{.*}}type = READ | WRITE | UNIQUE | FREE#
+    // CHECK-NOT: ([[@LINE+1]]: p): {{.*}}OWNED
+    let p = malloc(4) as *mut i32;
+    *p = 1;
+    free(p as *mut libc::c_void);
+    *p
+}
+// CHECK-LABEL: uses after free in "read_after_free"
+// CHECK: ({{.*}}*p{{.*}}): may be used after being passed to `free`
+
+// Reassigning `p` ends the move, so the second allocation is a separate owner.
+// CHECK-LABEL: final labeling for "reassign_after_free"
+pub unsafe fn reassign_after_free() {
+    // CHECK-DAG: ([[@LINE+1]]: mut p): std::boxed::Box<i32>
+    let mut p = malloc(4) as *mut i32;
+    *p = 1;
+    free(p as *mut libc::c_void);
+    p = malloc(4) as *mut i32;
+    *p = 2;
+    free(p as *mut libc::c_void);
+}
+// CHECK-NOT: uses after free in "reassign_after_free"
@@ -0,0 +1,34 @@
+#![feature(c_variadic)]
+
+extern crate libc;
+
+extern "C" {
+    fn printf(fmt: *const libc::c_char, ...) -> libc::c_int;
+}
+
+// CHECK-LABEL: final labeling for "print_int"
+pub unsafe fn print_int(fmt: *const libc::c_char, p: *const i32) {
+    // CHECK-DAG: ([[@LINE+1]]: q): {{.*}}type = READ{{.*}}#
+    let q = p;
+    printf(fmt, *q, q);
+}
+
+// CHECK-LABEL: final labeling for "sum"
+// CHECK-DAG: ([[@LINE+1]]: p): {{.*}}type = READ | WRITE{{.*}}#
+pub unsafe extern "C" fn sum(p: *mut i32, n: i32, mut args: ...) {
+    let mut i = 0;
+    while i < n {
+        *p += args.arg::<i32>();
+        i += 1;
+    }
+}
+// CHECK-LABEL: rewritten signature for "sum"
+// CHECK: fn sum(p: &mut i32, n: i32, mut args: ...)
+
+// CHECK-LABEL: final labeling for "call_sum"
+pub unsafe fn call_sum(x: *mut i32, y: *mut i32) {
+    sum(x, 1, 2);
+    // CHECK-DAG: ([[@LINE+1]]: z): {{.*}}type = READ | WRITE{{.*}}#
+    let z = y;
+    sum(x, 1, z);
+}
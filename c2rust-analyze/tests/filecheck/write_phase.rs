@@ -0,0 +1,26 @@
+// The last use of `p` writes through it, so it stays `&mut` throughout.
+// CHECK-LABEL: final labeling for "write_last"
+// CHECK-NOT: write phases for "write_last"
+pub unsafe fn write_last(p: *mut i32) {
+    let a = *p;
+    *p = a + 1;
+}
+
+// `p` is only written while initializing `*p`, so it's reborrowed as shared afterward.
+// CHECK-LABEL: write phases for "init_then_read"
+// CHECK: _1 (p): &mut until line [[@LINE+3]], then &
+pub unsafe fn init_then_read(p: *mut i32) -> i32 {
+    *p = 1;
+    *p += 1;
+    let a = *p;
+    a + *p
+}
+
+// Printing `p` borrows it, but only to read it, so it can still be split.
+// CHECK-LABEL: write phases for "init_then_print"
+// CHECK: _1 (p): &mut until line [[@LINE+2]], then &
+pub unsafe fn init_then_print(p: *mut i32) -> i32 {
+    *p = 1;
+    println!("{:p} = {}", p, *p);
+    *p
+}
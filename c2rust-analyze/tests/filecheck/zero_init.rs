@@ -0,0 +1,81 @@
+// Zero-initialized structs start out with every pointer field null, so the fields become
+// `Option`s.
+
+extern crate libc;
+
+extern "C" {
+    fn memset(_: *mut libc::c_void, _: libc::c_int, _: libc::c_ulong) -> *mut libc::c_void;
+    fn calloc(_: libc::c_ulong, _: libc::c_ulong) -> *mut libc::c_void;
+}
+
+// CHECK-LABEL: === rewritten struct definitions ===
+// CHECK-DAG: m: std::option::Option<&'a mut i32>,
+// CHECK-DAG: z: std::option::Option<&'a mut i32>,
+// CHECK-DAG: inner: Zeroed<'a>,
+// CHECK-DAG: l: std::option::Option<&'a mut i32>,
+// CHECK-DAG: c: std::option::Option<&'a mut i32>,
+// CHECK-DAG: plain: &'a mut i32,
+
+pub struct Memset {
+    pub m: *mut i32,
+}
+
+pub struct Zeroed {
+    pub z: *mut i32,
+}
+
+// Nested structs are zeroed too.
+pub struct Outer {
+    pub inner: Zeroed,
+}
+
+pub struct Literal {
+    pub l: *mut i32,
+}
+
+pub struct Calloced {
+    pub c: *mut i32,
+}
+
+pub struct Plain {
+    pub plain: *mut i32,
+}
+
+// struct Memset s;
+// memset(&s, 0, sizeof s);
+pub unsafe fn memset_init(x: *mut i32) {
+    let mut s: Memset = Memset { m: x };
+    memset(
+        &mut s as *mut Memset as *mut libc::c_void,
+        0 as libc::c_int,
+        ::std::mem::size_of::<Memset>() as libc::c_ulong,
+    );
+    s.m = x;
+    *s.m = 1;
+}
+
+pub unsafe fn zeroed_init(x: *mut i32) {
+    let mut o: Outer = ::std::mem::zeroed();
+    o.inner.z = x;
+    *o.inner.z = 1;
+}
+
+// struct Literal s = {0};
+pub unsafe fn literal_init(x: *mut i32) {
+    let mut s: Literal = Literal {
+        l: 0 as *mut i32,
+    };
+    s.l = x;
+    *s.l = 1;
+}
+
+pub unsafe fn calloc_init(x: *mut i32) {
+    let s = calloc(1, ::std::mem::size_of::<Calloced>() as libc::c_ulong) as *mut Calloced;
+    (*s).c = x;
+    *(*s).c = 1;
+}
+
+pub unsafe fn plain_init(x: *mut i32) {
+    let mut s: Plain = Plain { plain: x };
+    *s.plain = 1;
+}
@@ -0,0 +1,35 @@
+//! --html-report=tests/html_report/report.rs.html
+
+use std::ptr;
+
+// CHECK: <title>c2rust-analyze report for report</title>
+// CHECK: <span class="safe">rewritable</span> <span class="cell">needs Cell</span> <span class="raw">stuck raw</span>
+// CHECK-DAG: <tr><td><a href="#fn{{[0-9]+}}">safe</a></td><td>1</td><td>0</td><td>0</td></tr>
+// CHECK-DAG: <tr><td><a href="#fn{{[0-9]+}}">cell</a></td><td>0</td><td>2</td><td>0</td></tr>
+// CHECK-DAG: <tr><td><a href="#fn{{[0-9]+}}">raw</a></td><td>0</td><td>0</td><td>1</td></tr>
+// CHECK: </table>
+
+// The comparison in the source and the `&` in the type are escaped.
+// CHECK-DAG: <span class="safe" title="&amp;mut i32, {{[^"]*}}">p</span>
+// CHECK-DAG: if n &lt; 0 {
+pub unsafe fn safe(p: *mut i32, n: i32) {
+    if n < 0 {
+        *p = 0;
+    }
+}
+
+// CHECK-DAG: <span class="cell" title="&amp;std::cell::Cell&lt;i32&gt;, {{[^"]*}}">p</span>
+// CHECK-DAG: <span class="cell" title="&amp;std::cell::Cell&lt;i32&gt;, {{[^"]*}}">q</span>
+pub unsafe fn cell() -> i32 {
+    let mut x = 0;
+    let p = ptr::addr_of_mut!(x);
+    let q = ptr::addr_of_mut!(x);
+    *p = 1;
+    *q
+}
+
+// CHECK-DAG: <span class="raw" title="*mut i32, {{[^"]*}}">p</span>
+#[c2rust::keep_raw]
+pub unsafe fn raw(p: *mut i32) {
+    *p = 1;
+}
@@ -0,0 +1,49 @@
+use c2rust_analyze::{run_analysis, AnalysisConfig};
+use std::env;
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn library() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let crate_root = dir.join("tests/filecheck/json_output.rs");
+    let src = fs::read_to_string(&crate_root).unwrap();
+    let out_dir = env::temp_dir();
+    let config = AnalysisConfig {
+        crate_root,
+        rustc_args: vec![
+            "-L".to_owned(),
+            env!("C2RUST_TARGET_LIB_DIR").to_owned(),
+            "--crate-type".to_owned(),
+            "rlib".to_owned(),
+            "--out-dir".to_owned(),
+            out_dir.to_string_lossy().into_owned(),
+        ],
+        ..AnalysisConfig::default()
+    };
+    let results = run_analysis(&config).unwrap();
+
+    let set = results
+        .crate_results
+        .functions
+        .iter()
+        .find(|f| f.def_path == "set")
+        .unwrap();
+    let p = set
+        .pointers
+        .iter()
+        .find(|p| p.equiv.iter().any(|place| place == "_1 (p)"))
+        .unwrap();
+    assert!(p.perms.contains(&"WRITE".to_owned()));
+
+    // The results can also be looked up by source location, here the `q` in `let q = p;`.
+    let q_lo = src.find("let q = p;").unwrap() + "let ".len();
+    let q = results
+        .permissions_of(Path::new("json_output.rs"), q_lo, q_lo + 1)
+        .unwrap();
+    assert_eq!(q.text, "q");
+    assert_eq!(q.ty, "&mut i32");
+
+    // `p` becomes `&mut i32`, so the signature of `set` is rewritten.
+    assert!(results.edits.iter().any(|e| e.text.contains("&mut i32")));
+}
@@ -36,6 +36,7 @@ use crate::util::Convert;
 pub struct Instrumenter {
     mir_locs: Mutex<IndexSet<MirLoc>>,
     functions: Mutex<HashMap<FuncId, String>>,
+    pointer_locals: Mutex<HashMap<FuncId, Vec<c2rust_analysis_rt::mir_loc::Local>>>,
 }
 
 impl Instrumenter {
@@ -61,6 +62,16 @@ impl Instrumenter {
         debug!("Instrumenting function {}", function_name);
 
         self.add_fn(body_did, tcx);
+        let pointer_locals = body
+            .local_decls
+            .iter_enumerated()
+            .filter(|(_, decl)| is_region_or_unsafe_ptr(decl.ty))
+            .map(|(local, _)| local.as_u32().into())
+            .collect();
+        self.pointer_locals.lock().unwrap().insert(
+            FuncId(tcx.def_path_hash(body_did).convert()),
+            pointer_locals,
+        );
         debug!("Body before instrumentation: {:#?}", body);
         instrument_body(self, tcx, body, body_did);
         debug!("Body after instrumentation: {:#?}", body);
@@ -72,7 +83,12 @@ impl Instrumenter {
         let mut functions = self.functions.lock().unwrap();
         let locs = locs.drain(..).collect::<Vec<_>>();
         let functions = functions.drain().collect::<HashMap<_, _>>();
-        let metadata = Metadata { locs, functions };
+        let pointer_locals = std::mem::take(&mut *self.pointer_locals.lock().unwrap());
+        let metadata = Metadata {
+            locs,
+            functions,
+            pointer_locals,
+        };
         let bytes = bincode::serialize(&metadata).context("Location serialization failed")?;
         let mut file = OpenOptions::new()
             .append(true)
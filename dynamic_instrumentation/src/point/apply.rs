@@ -94,8 +94,8 @@ impl<'tcx, 'a> InstrumentationApplier<'tcx, 'a> {
                 args.iter_mut().for_each(|arg| *arg = arg.to_copy());
 
                 let place_ty = &place.ty(locals, tcx).ty;
-                // The return type of a hooked fn is always a raw ptr, reference, or unit
-                if place_ty.is_unit() {
+                // The return type of a hooked fn is always a raw ptr, reference, integer, or unit
+                if place_ty.is_unit() || place_ty.is_integral() {
                     // It's somewhat wrong to call unit an AddressUsize, but it has the pass-through
                     // semantics we want
                     InstrumentationArg::Op(ArgKind::AddressUsize(Operand::Copy(*place)))
@@ -1,4 +1,4 @@
-use c2rust_analysis_rt::mir_loc::{EventMetadata, TransferKind};
+use c2rust_analysis_rt::mir_loc::{EventMetadata, SourceSpan, TransferKind};
 use itertools::Itertools;
 use rustc_index::vec::Idx;
 use rustc_middle::{
@@ -81,6 +81,7 @@ impl<'a, 'tcx: 'a> CollectInstrumentationPoints<'a, 'tcx> {
             instrumentation_priority: Default::default(),
         }
         .debug_mir()
+        .span()
     }
 
     pub fn into_instrumentation_points(mut self) -> Vec<InstrumentationPoint<'tcx>> {
@@ -205,6 +206,25 @@ impl<'tcx> InstrumentationBuilder<'_, 'tcx> {
         self
     }
 
+    /// Set [`span`](EventMetadata::span)
+    /// to the source location of the [`original_location`](Self::original_location).
+    /// Spans in macro expansions are mapped to the outermost macro call.
+    fn span(mut self) -> Self {
+        let span = self.body.source_info(self.original_location).span;
+        let span = span.source_callsite();
+        self.point.metadata.span = if span.is_dummy() {
+            None
+        } else {
+            let loc = self.tcx.sess.source_map().lookup_char_pos(span.lo());
+            Some(SourceSpan {
+                file: loc.file.name.prefer_local().to_string(),
+                line: loc.line,
+                col: loc.col.0 + 1,
+            })
+        };
+        self
+    }
+
     /// Queue insertion of a call to [`func`].
     ///
     /// The call will be inserted before the statement
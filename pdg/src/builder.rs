@@ -1,4 +1,5 @@
 use crate::graph::{Graph, GraphId, Graphs, Node, NodeId, NodeKind};
+use crate::info::add_graph_info;
 use c2rust_analysis_rt::events::{Event, EventKind, Pointer};
 use c2rust_analysis_rt::metadata::Metadata;
 use c2rust_analysis_rt::mir_loc::{EventMetadata, Func, FuncId, Local, MirLoc, TransferKind};
@@ -6,16 +7,25 @@ use color_eyre::eyre;
 use fs_err::File;
 use indexmap::IndexSet;
 use itertools::Itertools;
-use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 use std::io::{self, BufReader};
 use std::iter;
+use std::mem;
 use std::path::Path;
 
 pub fn read_event_log(path: &Path) -> io::Result<Vec<Event>> {
+    Ok(stream_event_log(path)?.collect())
+}
+
+/// Read the events of an event log one at a time, without holding them all in memory.
+pub fn stream_event_log(path: &Path) -> io::Result<impl Iterator<Item = Event>> {
     let file = File::open(path)?;
     let mut reader = BufReader::new(file);
-    let events = iter::from_fn(|| bincode::deserialize_from(&mut reader).ok()).collect::<Vec<_>>();
-    Ok(events)
+    Ok(iter::from_fn(move || {
+        bincode::deserialize_from(&mut reader).ok()
+    }))
 }
 
 pub fn read_metadata(path: &Path) -> eyre::Result<Metadata> {
@@ -31,7 +41,13 @@ fn parent(e: &NodeKind, obj: (GraphId, NodeId)) -> Option<(GraphId, NodeId)> {
     }
 }
 
-type AddressTaken = IndexSet<(FuncId, Local)>;
+pub type AddressTaken = IndexSet<(FuncId, Local)>;
+
+/// The functions that were instrumented, which are those containing a [`MirLoc`].  Called
+/// functions are in [`Metadata::functions`] too, whether or not they were instrumented.
+pub fn instrumented_functions(metadata: &Metadata) -> HashSet<FuncId> {
+    metadata.locs.iter().map(|loc| loc.func.id).collect()
+}
 
 pub trait EventKindExt {
     fn ptr(&self, metadata: &EventMetadata) -> Option<Pointer>;
@@ -59,7 +75,7 @@ impl EventKindExt for EventKind {
             Alloc { ptr, .. } => ptr,
             AddrOfLocal(lhs, _) => lhs,
             Offset(ptr, _, _) => ptr,
-            Done | BeginFuncBody => return None,
+            Done | BeginFuncBody | Lock(_) | Unlock(_) | Join(_) => return None,
         })
     }
 
@@ -67,7 +83,7 @@ impl EventKindExt for EventKind {
         use EventKind::*;
         Some(match *self {
             Alloc { .. } => NodeKind::Alloc(1),
-            Realloc { .. } => NodeKind::Alloc(1),
+            Realloc { .. } => NodeKind::Realloc,
             Free { .. } => NodeKind::Free,
             CopyPtr(..) | CopyRef => NodeKind::Copy,
             Field(_, field) => NodeKind::Field(field.into()),
@@ -98,6 +114,8 @@ impl EventKindExt for EventKind {
             Ret(_) => return None,
             Offset(_, offset, _) => NodeKind::Offset(offset),
             Done => return None,
+            // Synchronization doesn't involve any pointers we track, only the order of events.
+            Lock(_) | Unlock(_) | Join(_) => return None,
         })
     }
 }
@@ -141,7 +159,8 @@ pub fn add_node(
     address_taken: &mut AddressTaken,
     event: &Event,
     metadata: &Metadata,
-) -> Option<NodeId> {
+    instrumented: &HashSet<FuncId>,
+) -> Option<(GraphId, NodeId)> {
     let MirLoc {
         func,
         mut basic_block_idx,
@@ -205,11 +224,46 @@ pub fn add_node(
         })
     });
 
+    // Without the old pointer, as for `realloc(NULL, size)`, a realloc is just an allocation.
+    let node_kind = match node_kind {
+        NodeKind::Realloc if source.or(direct_source).or(provenance).is_none() => {
+            NodeKind::Alloc(1)
+        }
+        kind => kind,
+    };
+
     let function = Func {
         id: dest_fn,
         name: metadata.functions[&dest_fn].clone(),
     };
 
+    let graph_id = source
+        .or(direct_source)
+        .or(provenance)
+        .and_then(|p| parent(&node_kind, p))
+        .map(|(gid, _)| gid)
+        .unwrap_or_else(|| graphs.graphs.push(Graph::new()));
+
+    // The node that produced the address `ptr` already knows its offset within the object.
+    let provenance_offset = provenance
+        .filter(|&(gid, _)| gid == graph_id)
+        .and_then(|(gid, nid)| graphs.graphs[gid].nodes[nid].byte_offset);
+    let (size, byte_offset) = match event.kind {
+        EventKind::Alloc { size, .. } | EventKind::Realloc { size, .. } => (Some(size), Some(0)),
+        EventKind::AddrOfLocal(..) => (None, Some(0)),
+        EventKind::Offset(ptr, _, new_ptr) => (
+            None,
+            provenance_offset.map(|offset| offset + new_ptr.wrapping_sub(ptr) as isize),
+        ),
+        EventKind::Field(..) => (None, None),
+        _ => (None, provenance_offset),
+    };
+
+    let extern_arg = matches!(
+        event_metadata.transfer_kind,
+        TransferKind::Arg(callee) if !instrumented.contains(&callee)
+    );
+
     let node = Node {
         function,
         block: basic_block_idx.into(),
@@ -221,14 +275,12 @@ pub fn add_node(
         dest: event_metadata.destination.clone(),
         debug_info: event_metadata.debug_info.clone(),
         info: None,
+        size,
+        byte_offset,
+        extern_arg,
+        thread: event.thread,
+        span: event_metadata.span.clone(),
     };
-
-    let graph_id = source
-        .or(direct_source)
-        .or(provenance)
-        .and_then(|p| parent(&node_kind, p))
-        .map(|(gid, _)| gid)
-        .unwrap_or_else(|| graphs.graphs.push(Graph::new()));
     let node_id = graphs.graphs[graph_id].nodes.push(node);
 
     update_provenance(
@@ -258,13 +310,14 @@ pub fn add_node(
         }
     }
 
-    Some(node_id)
+    Some((graph_id, node_id))
 }
 
 pub fn construct_pdg(events: &[Event], metadata: &Metadata) -> Graphs {
     let mut graphs = Graphs::new();
     let mut provenances = HashMap::new();
     let mut address_taken = AddressTaken::new();
+    let instrumented = instrumented_functions(metadata);
     for event in events {
         add_node(
             &mut graphs,
@@ -272,9 +325,231 @@ pub fn construct_pdg(events: &[Event], metadata: &Metadata) -> Graphs {
             &mut address_taken,
             event,
             metadata,
+            &instrumented,
         );
     }
     // TODO(kkysen) check if I have to remove any `GraphId`s from `graphs.latest_assignment`
     graphs.graphs = graphs.graphs.into_iter().unique().collect();
     graphs
 }
+
+/// Construct the PDG like [`construct_pdg`], but keep at most about `budget` [`Node`]s in memory,
+/// for event logs too large for that.  Each [`Graph`] is passed to `retire` once it's finished,
+/// with [`add_info`](crate::info::add_info) and
+/// [`remove_addr_of_local_sources`](Graphs::remove_addr_of_local_sources) already applied, and
+/// then dropped.  Duplicate graphs are only retired once.
+///
+/// We can't know that a graph is finished until the end of the event log, so when there are more
+/// than `budget` [`Node`]s, graphs are retired until only half that many are left, starting with
+/// those whose object was freed and then those that haven't been added to for the longest.  A
+/// later event using a pointer to a retired object can't find the [`Node`] it's derived from, and
+/// starts a new graph.  If `compact` is set, each graph is [compacted](Graph::compact) first.
+pub fn construct_pdg_streaming(
+    events: impl IntoIterator<Item = Event>,
+    metadata: &Metadata,
+    budget: usize,
+    compact: bool,
+    mut retire: impl FnMut(Graph) -> eyre::Result<()>,
+) -> eyre::Result<()> {
+    let mut graphs = Graphs::new();
+    let mut provenances = HashMap::new();
+    let mut address_taken = AddressTaken::new();
+    let instrumented = instrumented_functions(metadata);
+    // The time each graph in memory was last added to, and whether its object was freed.
+    let mut live = HashMap::<GraphId, (usize, bool)>::new();
+    let mut live_nodes = 0;
+    // The hashes of the retired graphs, to skip duplicates.
+    let mut retired = HashSet::<u64>::new();
+    let mut finish = |mut graph: Graph| -> eyre::Result<()> {
+        if compact {
+            graph.compact();
+        }
+        add_graph_info(&mut graph);
+        graph.remove_addr_of_local_sources();
+        graph.assert_head_has_no_source();
+        let mut hasher = DefaultHasher::new();
+        graph.hash(&mut hasher);
+        if retired.insert(hasher.finish()) {
+            retire(graph)?;
+        }
+        Ok(())
+    };
+
+    for (time, event) in events.into_iter().enumerate() {
+        let (graph_id, node_id) = match add_node(
+            &mut graphs,
+            &mut provenances,
+            &mut address_taken,
+            &event,
+            metadata,
+            &instrumented,
+        ) {
+            Some(ids) => ids,
+            None => continue,
+        };
+        live_nodes += 1;
+        let freed = graphs.graphs[graph_id].nodes[node_id].kind == NodeKind::Free;
+        let (last_time, was_freed) = live.entry(graph_id).or_default();
+        *last_time = time;
+        *was_freed |= freed;
+        if live_nodes <= budget {
+            continue;
+        }
+
+        let mut candidates = live
+            .iter()
+            .map(|(&graph_id, &(time, freed))| (!freed, time, graph_id))
+            .collect::<Vec<_>>();
+        candidates.sort();
+        let mut retiring = HashSet::new();
+        for (_, _, graph_id) in candidates {
+            if live_nodes <= budget / 2 {
+                break;
+            }
+            let graph = mem::take(&mut graphs.graphs[graph_id]);
+            live_nodes -= graph.nodes.len();
+            live.remove(&graph_id);
+            retiring.insert(graph_id);
+            finish(graph)?;
+        }
+        provenances.retain(|_, (graph_id, _)| !retiring.contains(graph_id));
+        graphs
+            .latest_assignment
+            .retain(|_, (graph_id, _)| !retiring.contains(graph_id));
+    }
+
+    for graph in graphs.graphs {
+        if !graph.nodes.is_empty() {
+            finish(graph)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::info::add_info;
+    use c2rust_analysis_rt::mir_loc::{MirLocId, MirPlace, SourceSpan};
+
+    fn place(local: u32) -> Option<MirPlace> {
+        Some(MirPlace {
+            local: local.into(),
+            projection: vec![],
+        })
+    }
+
+    fn event(mir_loc: MirLocId, kind: EventKind) -> Event {
+        Event {
+            mir_loc,
+            kind,
+            thread: 0,
+        }
+    }
+
+    /// ```rust
+    /// let a = malloc(4);
+    /// let b = a;
+    /// free(b);
+    /// let c = malloc(4);
+    /// *c = 1;
+    /// ```
+    fn metadata() -> Metadata {
+        let main = Func {
+            id: FuncId((1, 0).into()),
+            name: "main".into(),
+        };
+        let loc = |statement_idx, source, destination| MirLoc {
+            func: main.clone(),
+            basic_block_idx: 0,
+            statement_idx,
+            metadata: EventMetadata {
+                source,
+                destination,
+                ..Default::default()
+            },
+        };
+        Metadata {
+            locs: vec![
+                loc(0, None, place(1)),
+                loc(1, place(1), place(2)),
+                loc(2, place(2), None),
+                loc(3, None, place(3)),
+                loc(4, place(3), None),
+            ],
+            functions: [(main.id, main.name.clone())].into_iter().collect(),
+            pointer_locals: [(main.id, vec![1_u32.into(), 2_u32.into(), 3_u32.into()])]
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    /// The events of a run of the program in [`metadata`].
+    fn events() -> Vec<Event> {
+        vec![
+            event(
+                0,
+                EventKind::Alloc {
+                    size: 4,
+                    ptr: 0x1000,
+                },
+            ),
+            event(1, EventKind::CopyPtr(0x1000)),
+            event(2, EventKind::Free { ptr: 0x1000 }),
+            event(
+                3,
+                EventKind::Alloc {
+                    size: 4,
+                    ptr: 0x2000,
+                },
+            ),
+            event(4, EventKind::StoreAddr(0x2000)),
+        ]
+    }
+
+    fn streamed(metadata: &Metadata, budget: usize) -> Vec<Graph> {
+        let mut graphs = Vec::new();
+        construct_pdg_streaming(events(), metadata, budget, true, |graph| {
+            graphs.push(graph);
+            Ok(())
+        })
+        .unwrap();
+        graphs
+    }
+
+    /// Without retiring graphs early, streaming gives the same graphs as [`construct_pdg`] with
+    /// the same processing applied.  Retiring the graph of the freed object once the budget is
+    /// exceeded doesn't change them either, since nothing uses that object afterward.
+    #[test]
+    fn streaming_matches() {
+        let metadata = metadata();
+        let mut expected = construct_pdg(&events(), &metadata);
+        expected.compact();
+        add_info(&mut expected);
+        expected.remove_addr_of_local_sources();
+        assert_eq!(expected.graphs.len(), 2);
+
+        assert_eq!(streamed(&metadata, 100), expected.graphs.raw);
+        assert_eq!(streamed(&metadata, 4), expected.graphs.raw);
+    }
+
+    /// A node's span is the span of its [`MirLoc`], and nodes without one have none.
+    #[test]
+    fn spans() {
+        let span = SourceSpan {
+            file: "src/main.rs".into(),
+            line: 2,
+            col: 13,
+        };
+        let mut metadata = metadata();
+        metadata.locs[1].metadata.span = Some(span.clone());
+        let graphs = construct_pdg(&events(), &metadata);
+        let nodes = &graphs.graphs[0_u32.into()].nodes;
+        let copy = nodes.iter().find(|n| n.kind == NodeKind::Copy).unwrap();
+        assert_eq!(copy.span, Some(span));
+        assert!(nodes
+            .iter()
+            .filter(|n| n.kind != NodeKind::Copy)
+            .all(|n| n.span.is_none()));
+    }
+}
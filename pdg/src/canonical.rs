@@ -0,0 +1,227 @@
+//! Canonical numbering of [`Graphs`], for `--canonical`.
+//!
+//! [`Node`]s are numbered in the order their events arrived, and [`Graph`]s in the order their
+//! objects were created.  Within one thread, that's the same in every run of a deterministic
+//! program, but the events of different threads interleave differently each time, so two runs
+//! number the same [`Node`]s and [`Graph`]s differently.  [`Graphs::canonicalize`] renumbers them
+//! so that the output only depends on what happened, and can be diffed and cached:
+//!
+//! * The [`Node`]s of each [`Graph`] are renumbered in a traversal that visits each [`Node`] after
+//!   its [`Node::source`] and after the earlier [`Node`]s of the same thread, and otherwise picks
+//!   the next [`Node`] by its function, statement, and kind rather than by when it arrived.  So
+//!   the [`Node`]s of a [`Graph`] used by only one thread keep their order.
+//! * The [`Graph`]s are then sorted by their root [`Node`], so by the function, statement, and
+//!   kind of the allocation, and then by the rest of their [`Node`]s.
+//!
+//! The [`NodeId`]s in [`Node::info`] and the [`Graphs::latest_assignment`] are renumbered to match,
+//! but the [`NodeInfo`] isn't recomputed: it was computed from the order the events arrived, and
+//! still describes what happened then, like a conflict between two threads that the new order
+//! doesn't show.  So where the threads interleaved differently, the [`NodeInfo`] can differ too,
+//! and this has to run after everything that depends on the order of the [`Node`]s.
+
+use crate::graph::{Graph, GraphId, Graphs, Node, NodeId, NodeKind};
+use crate::info::NodeInfo;
+use c2rust_analysis_rt::events::ThreadId;
+use c2rust_analysis_rt::mir_loc::FuncId;
+use rustc_index::vec::IndexVec;
+use rustc_middle::mir::BasicBlock;
+use std::collections::{BTreeMap, VecDeque};
+
+/// What a [`Node`] is ordered by, other than its [`Node::source`].
+fn node_key(node: &Node) -> (FuncId, BasicBlock, usize, NodeKind, ThreadId) {
+    (
+        node.function.id,
+        node.block,
+        node.statement_idx,
+        node.kind,
+        node.thread,
+    )
+}
+
+/// Map all the [`NodeId`]s in `info` with `f`.
+fn map_info_ids(info: &mut NodeInfo, f: impl Fn(NodeId) -> NodeId) {
+    let flows_to = &mut info.flows_to;
+    for id in [
+        &mut flows_to.load,
+        &mut flows_to.store,
+        &mut flows_to.pos_offset,
+        &mut flows_to.neg_offset,
+        &mut flows_to.out_of_bounds,
+        &mut flows_to.free,
+        &mut info.flows_to_extern,
+    ] {
+        *id = id.map(&f);
+    }
+    info.last_use = f(info.last_use);
+}
+
+impl Graph {
+    /// Renumber the [`Node`]s in the canonical order described in the [module docs](self), and
+    /// return the new [`NodeId`] of each old one.
+    pub fn canonicalize(&mut self) -> IndexVec<NodeId, NodeId> {
+        // The `Node`s of each thread, in order.
+        let mut threads = BTreeMap::<ThreadId, VecDeque<NodeId>>::new();
+        for (id, node) in self.nodes.iter_enumerated() {
+            threads.entry(node.thread).or_default().push_back(id);
+        }
+        if threads.len() <= 1 {
+            return self.nodes.indices().collect();
+        }
+
+        let mut new_ids = IndexVec::<NodeId, Option<NodeId>>::from_elem_n(None, self.nodes.len());
+        let mut order = Vec::with_capacity(self.nodes.len());
+        while order.len() < self.nodes.len() {
+            // The arrival order is a valid order, so the earliest `Node` that hasn't been visited
+            // is always ready.
+            let next = threads
+                .values()
+                .filter_map(|nodes| nodes.front().copied())
+                .filter(|&id| {
+                    let source = self.nodes[id].source;
+                    source.map_or(true, |source| new_ids[source].is_some())
+                })
+                .min_by_key(|&id| node_key(&self.nodes[id]))
+                .expect("some `Node` is ready");
+            threads
+                .get_mut(&self.nodes[next].thread)
+                .unwrap()
+                .pop_front();
+            new_ids[next] = Some(NodeId::from_usize(order.len()));
+            order.push(next);
+        }
+        let new_ids = new_ids
+            .into_iter()
+            .map(|id| id.unwrap())
+            .collect::<IndexVec<NodeId, _>>();
+
+        let mut old_nodes = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .map(Some)
+            .collect::<IndexVec<NodeId, _>>();
+        self.nodes = order
+            .into_iter()
+            .map(|old_id| {
+                let mut node = old_nodes[old_id].take().unwrap();
+                node.source = node.source.map(|source| new_ids[source]);
+                if let Some(info) = &mut node.info {
+                    map_info_ids(info, |id| new_ids[id]);
+                }
+                node
+            })
+            .collect();
+        new_ids
+    }
+}
+
+impl Graphs {
+    /// Renumber the [`Graph`]s and their [`Node`]s in the canonical order described in the
+    /// [module docs](self).
+    pub fn canonicalize(&mut self) {
+        let new_node_ids = self
+            .graphs
+            .iter_mut()
+            .map(Graph::canonicalize)
+            .collect::<IndexVec<GraphId, _>>();
+
+        let mut order = self.graphs.indices().collect::<Vec<_>>();
+        order.sort_by(|&a, &b| {
+            let keys = |id: GraphId| {
+                self.graphs[id]
+                    .nodes
+                    .iter()
+                    .map(|node| (node_key(node), node.source))
+            };
+            keys(a).cmp(keys(b))
+        });
+        let mut new_graph_ids = IndexVec::from_elem_n(GraphId::from_u32(0), self.graphs.len());
+        for (new_id, &old_id) in order.iter().enumerate() {
+            new_graph_ids[old_id] = GraphId::from_usize(new_id);
+        }
+
+        let mut old_graphs = std::mem::take(&mut self.graphs)
+            .into_iter()
+            .map(Some)
+            .collect::<IndexVec<GraphId, _>>();
+        self.graphs = order
+            .into_iter()
+            .map(|old_id| old_graphs[old_id].take().unwrap())
+            .collect();
+        for (graph_id, node_id) in self.latest_assignment.values_mut() {
+            *node_id = new_node_ids[*graph_id][*node_id];
+            *graph_id = new_graph_ids[*graph_id];
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::GraphBuilder;
+    use crate::info::add_info;
+
+    /// Two threads that each copy a pointer and store through it, with their events arriving in
+    /// the order of `threads`.
+    fn graph(threads: [ThreadId; 2]) -> Graph {
+        let mut g = GraphBuilder::new();
+        let p = g.alloc(1);
+        let mut copies = [p; 2];
+        for thread in threads {
+            copies[thread as usize] = g.on_thread(thread).at(thread + 1, 0).copy(p);
+        }
+        for thread in threads {
+            g.on_thread(thread)
+                .at(thread + 1, 1)
+                .store_addr(copies[thread as usize]);
+        }
+        g.build()
+    }
+
+    #[test]
+    fn interleavings() {
+        let mut runs = Vec::new();
+        for threads in [[0, 1], [1, 0]] {
+            let mut single = GraphBuilder::new();
+            single.at(0_u32, 5).alloc(1);
+            let mut graphs = Graphs::new();
+            // The graphs arrive in a different order too.
+            if threads[0] == 0 {
+                graphs.graphs.push(graph(threads));
+                graphs.graphs.push(single.build());
+            } else {
+                graphs.graphs.push(single.build());
+                graphs.graphs.push(graph(threads));
+            }
+            add_info(&mut graphs);
+            graphs.canonicalize();
+            runs.push(graphs);
+        }
+
+        let shape = |graphs: &Graphs| {
+            let mut graphs = graphs.graphs.clone();
+            for node in graphs.iter_mut().flat_map(|graph| &mut graph.nodes) {
+                node.info = None;
+            }
+            graphs
+        };
+        assert_eq!(shape(&runs[0]), shape(&runs[1]));
+        let g = &runs[0].graphs[GraphId::from_u32(0)];
+        let locs = g
+            .nodes
+            .iter()
+            .map(|node| (node.block.as_u32(), node.statement_idx))
+            .collect::<Vec<_>>();
+        assert_eq!(locs, [(0, 0), (1, 0), (1, 1), (2, 0), (2, 1)]);
+
+        // The copies overlap in either order, but the last use is the store that was last.
+        let root = |graphs: &Graphs| {
+            graphs.graphs[GraphId::from_u32(0)].nodes[NodeId::from_u32(0)]
+                .info
+                .clone()
+                .unwrap()
+        };
+        let (first, second) = (root(&runs[0]), root(&runs[1]));
+        assert!(first.non_unique_cross_thread && second.non_unique_cross_thread);
+        assert_eq!(first.last_use, NodeId::from_u32(4));
+        assert_eq!(second.last_use, NodeId::from_u32(2));
+    }
+}
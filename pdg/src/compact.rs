@@ -0,0 +1,155 @@
+//! Compaction of chains of copies, which `--no-compact` turns off.
+//!
+//! A pointer passed down through several functions or locals leaves a chain of
+//! [`NodeKind::Copy`]s, one per assignment, each derived from the last and with no other uses.
+//! Large traces have many such chains, which take up memory and time in
+//! [`add_info`](crate::info::add_info) without telling it anything: the [`NodeInfo`] of a copy
+//! with only one use is that of the copy it flows to, except that it's alive from an earlier
+//! point.  [`Graph::compact`] collapses each chain into a single edge from its first copy, which
+//! keeps when the chain started, to its last copy, which keeps its uses, so the [`NodeInfo`] of
+//! every remaining [`Node`] is the same as without compaction.  The copies in between, and their
+//! [`Node::dest`]s, are gone from the output.
+//!
+//! [`NodeInfo`]: crate::info::NodeInfo
+
+use crate::graph::{Graph, Graphs, Node, NodeId, NodeKind};
+use rustc_index::vec::IndexVec;
+
+impl Graph {
+    /// Whether the [`Node`] `id` is in the middle of a chain of copies and can be removed by
+    /// [`Graph::compact`].
+    ///
+    /// It has to be a copy of a copy with only one use, and have only one use itself, which is a
+    /// copy.  It also has to be on the same thread as that use and not pass the pointer to an
+    /// un-instrumented function, as otherwise it adds to the [`NodeInfo`](crate::info::NodeInfo)
+    /// of its ancestors.
+    fn in_copy_chain(&self, id: NodeId, only_child: &IndexVec<NodeId, Option<NodeId>>) -> bool {
+        let node = &self.nodes[id];
+        let is_copy = |node: &Node| node.kind == NodeKind::Copy;
+        let source = match node.source {
+            Some(source) => source,
+            None => return false,
+        };
+        let child = match only_child[id] {
+            Some(child) => &self.nodes[child],
+            None => return false,
+        };
+        is_copy(node)
+            && !node.extern_arg
+            && is_copy(&self.nodes[source])
+            && only_child[source].is_some()
+            && is_copy(child)
+            && child.thread == node.thread
+    }
+
+    /// Remove the copies in the middle of chains of copies, deriving each copy after one from
+    /// the start of its chain, and return how many were removed.
+    ///
+    /// This has to be run before [`add_info`](crate::info::add_info), as it doesn't update the
+    /// [`NodeId`]s in [`Node::info`].
+    pub fn compact(&mut self) -> usize {
+        // The only child of each `Node`, if it has exactly one.
+        let mut only_child =
+            IndexVec::<NodeId, Option<NodeId>>::from_elem_n(None, self.nodes.len());
+        let mut has_children = IndexVec::<NodeId, bool>::from_elem_n(false, self.nodes.len());
+        for (id, node) in self.nodes.iter_enumerated() {
+            if let Some(source) = node.source {
+                only_child[source] = if has_children[source] { None } else { Some(id) };
+                has_children[source] = true;
+            }
+        }
+
+        let removed = self
+            .nodes
+            .indices()
+            .map(|id| self.in_copy_chain(id, &only_child))
+            .collect::<IndexVec<NodeId, _>>();
+        let num_removed = removed.iter().filter(|&&removed| removed).count();
+        if num_removed == 0 {
+            return 0;
+        }
+
+        // The new `NodeId` of each `Node`, or for a removed one, of the start of its chain.
+        let mut new_ids = IndexVec::<NodeId, NodeId>::with_capacity(self.nodes.len());
+        let mut nodes = IndexVec::with_capacity(self.nodes.len() - num_removed);
+        for (id, mut node) in std::mem::take(&mut self.nodes).into_iter_enumerated() {
+            debug_assert!(node.info.is_none(), "`compact` must run before `add_info`");
+            node.source = node.source.map(|source| new_ids[source]);
+            let new_id = if removed[id] {
+                node.source.expect("a copy in a chain has a source")
+            } else {
+                nodes.push(node)
+            };
+            new_ids.push(new_id);
+        }
+        self.nodes = nodes;
+        num_removed
+    }
+}
+
+impl Graphs {
+    /// [`Graph::compact`] every graph, and return how many [`Node`]s were removed in all.
+    pub fn compact(&mut self) -> usize {
+        self.graphs.iter_mut().map(Graph::compact).sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::GraphBuilder;
+    use crate::info::add_info;
+
+    /// ```rust
+    /// let mut a = 0;
+    /// let b = &mut a;
+    /// let c = b;
+    /// let d = c;
+    /// let x = &mut a;
+    /// *x = 1;
+    /// let e = d;
+    /// *e = 1;
+    /// *e = 2;
+    /// ```
+    #[test]
+    fn copy_chain() {
+        let mut g = GraphBuilder::new();
+        let a = g.addr_of_local(0_u32);
+        let b = g.copy(a);
+        let c = g.copy(b);
+        let d = g.copy(c);
+        let x = g.copy(a);
+        g.store_addr(x);
+        let e = g.copy(d);
+        g.store_addr(e);
+        g.store_addr(e);
+        let g = g.build();
+
+        let mut expected = Graphs::new();
+        expected.graphs.push(g.clone());
+        add_info(&mut expected);
+        let expected = &expected.graphs[0_u32.into()];
+
+        let mut compacted = Graphs::new();
+        compacted.graphs.push(g);
+        assert_eq!(compacted.compact(), 2);
+        add_info(&mut compacted);
+        let compacted = &compacted.graphs[0_u32.into()];
+
+        // `c` and `d` are removed, and `e` is derived from `b`.
+        let kept = [0, 1, 4, 5, 6, 7, 8].map(NodeId::from_u32);
+        assert_eq!(compacted.nodes.len(), kept.len());
+        assert_eq!(compacted.nodes[NodeId::from_u32(4)].source, Some(b));
+        for (new_id, old_id) in kept.into_iter().enumerate() {
+            let new = compacted.nodes[NodeId::from_usize(new_id)]
+                .info
+                .as_ref()
+                .unwrap();
+            let old = expected.nodes[old_id].info.as_ref().unwrap();
+            assert_eq!(new.unique, old.unique);
+            assert_eq!(new.flows_to.store.is_some(), old.flows_to.store.is_some());
+        }
+        // `b` is still alive while `x` is used.
+        assert!(!compacted.nodes[b].info.as_ref().unwrap().unique);
+    }
+}
@@ -0,0 +1,253 @@
+//! How much of the instrumented program the event logs exercised, for `--print coverage`.
+//!
+//! Everything derived from the graphs only describes what happened in the runs that produced the
+//! event logs, so a function that was never called, or a pointer that was never used, looks the
+//! same as one that can't do anything.  The instrumenter records every instrumentation point in
+//! [`Metadata::locs`], and the locals of pointer type in each function it instrumented in
+//! [`Metadata::pointer_locals`], so [`Coverage`] compares the events against those, and lists the
+//! instrumented functions that sent no events and the pointer locals that no event involved.
+//!
+//! An event involves the locals of its [`MirLoc`]'s [`EventMetadata::source`] and
+//! [`EventMetadata::destination`], where the destination of an argument passed to another
+//! function is a local of that function.  There are no [`Event`]s in graphs read with
+//! `--from-json`, so they have no coverage.
+//!
+//! [`MirLoc`]: c2rust_analysis_rt::mir_loc::MirLoc
+//! [`EventMetadata::source`]: c2rust_analysis_rt::mir_loc::EventMetadata::source
+//! [`EventMetadata::destination`]: c2rust_analysis_rt::mir_loc::EventMetadata::destination
+
+use c2rust_analysis_rt::events::Event;
+use c2rust_analysis_rt::metadata::Metadata;
+use c2rust_analysis_rt::mir_loc::{FuncId, Local, TransferKind};
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+
+/// The instrumented functions and pointer locals that the events of some runs didn't exercise.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    pub num_functions: usize,
+    pub num_points: usize,
+    /// The instrumentation points that sent at least one event.
+    pub num_points_hit: usize,
+    pub num_pointer_locals: usize,
+    /// The pointer locals that at least one event involved.
+    pub num_pointer_locals_hit: usize,
+    /// The names of the instrumented functions that sent no events, sorted.
+    pub unexercised_functions: Vec<String>,
+    /// The name of each function that sent events but has pointer locals that none of them
+    /// involved, with those locals, sorted.
+    pub unexercised_pointer_locals: Vec<(String, Vec<Local>)>,
+}
+
+impl Coverage {
+    pub fn new(events: &[Event], metadata: &Metadata) -> Self {
+        let mut points_hit = HashSet::new();
+        let mut functions = HashSet::<FuncId>::new();
+        let mut locals = HashSet::<(FuncId, Local)>::new();
+        for event in events {
+            if !points_hit.insert(event.mir_loc) {
+                continue;
+            }
+            let loc = metadata.get(event.mir_loc);
+            let func = loc.func.id;
+            functions.insert(func);
+            let loc_metadata = &loc.metadata;
+            if let Some(source) = &loc_metadata.source {
+                locals.insert((func, source.local));
+            }
+            if let Some(dest) = &loc_metadata.destination {
+                let dest_func = match loc_metadata.transfer_kind {
+                    TransferKind::Arg(callee) => callee,
+                    _ => func,
+                };
+                locals.insert((dest_func, dest.local));
+            }
+        }
+
+        let name = |func: &FuncId| {
+            metadata
+                .functions
+                .get(func)
+                .cloned()
+                .unwrap_or_else(|| format!("{func:?}"))
+        };
+        let mut unexercised_functions = Vec::new();
+        let mut unexercised_pointer_locals = Vec::new();
+        let mut num_pointer_locals = 0;
+        let mut num_pointer_locals_hit = 0;
+        for (func, pointers) in &metadata.pointer_locals {
+            num_pointer_locals += pointers.len();
+            num_pointer_locals_hit += pointers
+                .iter()
+                .filter(|&&local| locals.contains(&(*func, local)))
+                .count();
+            if !functions.contains(func) {
+                unexercised_functions.push(name(func));
+                continue;
+            }
+            let mut missed = pointers
+                .iter()
+                .copied()
+                .filter(|&local| !locals.contains(&(*func, local)))
+                .collect::<Vec<_>>();
+            if !missed.is_empty() {
+                missed.sort();
+                unexercised_pointer_locals.push((name(func), missed));
+            }
+        }
+        unexercised_functions.sort();
+        unexercised_pointer_locals.sort();
+
+        Self {
+            num_functions: metadata.pointer_locals.len(),
+            num_points: metadata.locs.len(),
+            num_points_hit: points_hit.len(),
+            num_pointer_locals,
+            num_pointer_locals_hit,
+            unexercised_functions,
+            unexercised_pointer_locals,
+        }
+    }
+}
+
+impl Display for Coverage {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let Self {
+            num_functions,
+            num_points,
+            num_points_hit,
+            num_pointer_locals,
+            num_pointer_locals_hit,
+            unexercised_functions,
+            unexercised_pointer_locals,
+        } = self;
+        writeln!(
+            f,
+            "exercised {} of {num_functions} instrumented functions",
+            num_functions - unexercised_functions.len()
+        )?;
+        writeln!(
+            f,
+            "exercised {num_points_hit} of {num_points} instrumentation points"
+        )?;
+        writeln!(
+            f,
+            "exercised {num_pointer_locals_hit} of {num_pointer_locals} pointer locals"
+        )?;
+        for func in unexercised_functions {
+            writeln!(f, "fn {func} sent no events")?;
+        }
+        for (func, locals) in unexercised_pointer_locals {
+            let locals = locals
+                .iter()
+                .map(|local| format!("{local:?}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(f, "fn {func}: {locals} never appeared")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use c2rust_analysis_rt::events::EventKind;
+    use c2rust_analysis_rt::mir_loc::{EventMetadata, Func, MirLoc, MirLocId, MirPlace};
+
+    fn func(id: u64, name: &str) -> Func {
+        Func {
+            id: FuncId((id, 0).into()),
+            name: name.into(),
+        }
+    }
+
+    fn place(local: u32) -> Option<MirPlace> {
+        Some(MirPlace {
+            local: local.into(),
+            projection: vec![],
+        })
+    }
+
+    fn event(mir_loc: MirLocId) -> Event {
+        Event {
+            mir_loc,
+            kind: EventKind::CopyPtr(0x1000),
+            thread: 0,
+        }
+    }
+
+    #[test]
+    fn unexercised() {
+        let (main, callee, unused) = (func(1, "main"), func(2, "callee"), func(3, "unused"));
+        let loc = |func: &Func, statement_idx, metadata| MirLoc {
+            func: func.clone(),
+            basic_block_idx: 0,
+            statement_idx,
+            metadata,
+        };
+        let metadata = Metadata {
+            locs: vec![
+                // `_2 = _1` in `main`.
+                loc(
+                    &main,
+                    0,
+                    EventMetadata {
+                        source: place(1),
+                        destination: place(2),
+                        ..Default::default()
+                    },
+                ),
+                // `callee(_2)`, passed to `callee`'s `_1`.
+                loc(
+                    &main,
+                    1,
+                    EventMetadata {
+                        source: place(2),
+                        destination: place(1),
+                        transfer_kind: TransferKind::Arg(callee.id),
+                        ..Default::default()
+                    },
+                ),
+                // `_3 = _1` in `main`, which never happens.
+                loc(
+                    &main,
+                    2,
+                    EventMetadata {
+                        source: place(1),
+                        destination: place(3),
+                        ..Default::default()
+                    },
+                ),
+                loc(&unused, 0, Default::default()),
+            ],
+            functions: [&main, &callee, &unused]
+                .into_iter()
+                .map(|func| (func.id, func.name.clone()))
+                .collect(),
+            pointer_locals: [
+                (main.id, vec![1_u32, 2, 3]),
+                (callee.id, vec![1]),
+                (unused.id, vec![1]),
+            ]
+            .into_iter()
+            .map(|(id, locals)| (id, locals.into_iter().map(Local::from).collect()))
+            .collect(),
+        };
+        let coverage = Coverage::new(&[event(0), event(1), event(0)], &metadata);
+        assert_eq!(
+            coverage,
+            Coverage {
+                num_functions: 3,
+                num_points: 4,
+                num_points_hit: 2,
+                num_pointer_locals: 5,
+                // `callee`'s `_1` was passed an argument.
+                num_pointer_locals_hit: 3,
+                // `callee` didn't send any events itself.
+                unexercised_functions: vec!["callee".into(), "unused".into()],
+                unexercised_pointer_locals: vec![("main".into(), vec![3_u32.into()])],
+            }
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Graphviz DOT export of [`Graphs`], for `--dot`.
+//!
+//! Each [`Graph`] becomes a cluster, and each [`Node`] is labeled with its [`NodeKind`], its
+//! [`Node::dest`] and location, and its [`NodeInfo`] if [`add_info`](crate::info::add_info) has
+//! been run.  Edges go from a [`Node::source`] to the [`Node`]s derived from it and are styled by
+//! the kind of the derived [`Node`]: copies are solid, field projections are dashed, and loads and
+//! stores through the pointer (derefs) are bold and red, so the uses that decide uniqueness stand
+//! out.  Render the output with, for example, `dot -Tsvg`.
+//!
+//! [`NodeInfo`]: crate::info::NodeInfo
+
+use crate::graph::{Graph, Graphs, Node, NodeId, NodeKind};
+use crate::util::ShortOption;
+use color_eyre::eyre;
+use std::fmt::{self, Write};
+use std::path::Path;
+
+/// Escape `s` for use in a double-quoted DOT string.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\l"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The DOT attributes of the edge from a [`Node`]'s source to a [`Node`] of kind `kind`.
+fn edge_style(kind: NodeKind) -> &'static str {
+    use NodeKind::*;
+    match kind {
+        Copy => "style=solid",
+        Field(_) => "style=dashed",
+        LoadAddr | StoreAddr => "style=bold, color=red",
+        _ => "style=dotted",
+    }
+}
+
+/// The DOT label of the [`Node`] `id`.
+fn node_label(id: NodeId, node: &Node) -> String {
+    let dest = ShortOption(node.dest.as_ref());
+    let mut label = format!(
+        "{id}: {}\n{dest} @ {:?}[{}]: fn {}\n",
+        node.kind, node.block, node.statement_idx, node.function,
+    );
+    if let Some(span) = &node.span {
+        label.push_str(&format!("{span}\n"));
+    }
+    if let Some(info) = &node.info {
+        label.push_str(&info.to_string());
+        label.push('\n');
+    }
+    if !node.debug_info.is_empty() {
+        label.push_str(&node.debug_info);
+        label.push('\n');
+    }
+    escape(&label)
+}
+
+fn write_graph(f: &mut String, i: usize, graph: &Graph) -> fmt::Result {
+    writeln!(f, "    subgraph cluster_{i} {{")?;
+    writeln!(f, "        label=\"g[{i}]\";")?;
+    for (id, node) in graph.nodes.iter_enumerated() {
+        let label = node_label(id, node);
+        writeln!(f, "        g{i}n{} [label=\"{label}\"];", id.as_usize())?;
+    }
+    for (id, node) in graph.nodes.iter_enumerated() {
+        if let Some(source) = node.source {
+            let style = edge_style(node.kind);
+            writeln!(
+                f,
+                "        g{i}n{} -> g{i}n{} [{style}];",
+                source.as_usize(),
+                id.as_usize()
+            )?;
+        }
+    }
+    writeln!(f, "    }}")
+}
+
+impl Graphs {
+    /// Render the graphs in Graphviz DOT format, with one cluster per [`Graph`].
+    pub fn to_dot(&self) -> String {
+        let mut f = String::new();
+        let result = (|| {
+            writeln!(f, "digraph pdg {{")?;
+            writeln!(f, "    node [shape=box, fontname=monospace];")?;
+            for (i, graph) in self.graphs.iter().enumerate() {
+                write_graph(&mut f, i, graph)?;
+            }
+            writeln!(f, "}}")
+        })();
+        result.expect("writing to a `String` can't fail");
+        f
+    }
+}
+
+/// Write the graphs to `path` in Graphviz DOT format.
+pub fn write_dot(graphs: &Graphs, path: &Path) -> eyre::Result<()> {
+    fs_err::write(path, graphs.to_dot())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::info::add_info;
+    use c2rust_analysis_rt::mir_loc::{Func, FuncId, SourceSpan};
+
+    fn mk_node(g: &mut Graph, kind: NodeKind, source: Option<NodeId>) -> NodeId {
+        g.nodes.push(Node {
+            function: Func {
+                id: FuncId((1, 2).into()),
+                name: "fake_function".into(),
+            },
+            block: 0_u32.into(),
+            statement_idx: 0,
+            dest: None,
+            kind,
+            source,
+            info: None,
+            debug_info: "".into(),
+            size: None,
+            byte_offset: None,
+            extern_arg: false,
+            thread: 0,
+            span: None,
+        })
+    }
+
+    #[test]
+    fn clusters_and_edge_styles() {
+        let mut g = Graph::new();
+        let a = mk_node(&mut g, NodeKind::Alloc(1), None);
+        g.nodes[a].span = Some(SourceSpan {
+            file: "src/main.rs".into(),
+            line: 3,
+            col: 13,
+        });
+        let b = mk_node(&mut g, NodeKind::Copy, Some(a));
+        let c = mk_node(&mut g, NodeKind::Field(0_u32.into()), Some(b));
+        mk_node(&mut g, NodeKind::StoreAddr, Some(c));
+        let mut pdg = Graphs::new();
+        pdg.graphs.push(g.clone());
+        pdg.graphs.push(g);
+        add_info(&mut pdg);
+
+        let dot = pdg.to_dot();
+        assert!(dot.starts_with("digraph pdg {"));
+        assert!(dot.contains("subgraph cluster_0 {"));
+        assert!(dot.contains("subgraph cluster_1 {"));
+        assert!(dot.contains("g0n0 -> g0n1 [style=solid];"));
+        assert!(dot.contains("g1n1 -> g1n2 [style=dashed];"));
+        assert!(dot.contains("g1n2 -> g1n3 [style=bold, color=red];"));
+        assert!(dot.contains("g0n0 [label=\"n[0]: alloc\\l"));
+        assert!(dot.contains("fn fake_function\\lsrc/main.rs:3:13\\l"));
+        assert!(dot.contains("unique; flows to store n[3]"));
+    }
+}
@@ -0,0 +1,95 @@
+//! Export of the [`NodeInfo`] facts that `c2rust-analyze --pdg` uses to refine its static results.
+//!
+//! Static analysis reasons about MIR locals, not individual [`Node`]s, so we only export the
+//! [`Node`]s whose [`Node::dest`] is a whole local.  Each one becomes a [`NodeFacts`] record
+//! identifying the function by its [`DefPathHash`] and the statement by its [`BasicBlock`] and
+//! index, as in [`Node`] itself.
+//!
+//! [`DefPathHash`]: c2rust_analysis_rt::mir_loc::DefPathHash
+//! [`BasicBlock`]: rustc_middle::mir::BasicBlock
+
+use crate::graph::{Graphs, Node, NodeKind};
+use crate::info::NodeInfo;
+use color_eyre::eyre;
+use fs_err::File;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::io::BufWriter;
+use std::path::Path;
+
+/// Force an import of [`NodeInfo`] just for docs.
+const _: Option<NodeInfo> = None;
+
+/// What the program did with the pointer that one [`Node`] assigned to a local.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NodeFacts {
+    /// The [`DefPathHash`](c2rust_analysis_rt::mir_loc::DefPathHash) of [`Node::function`].
+    pub def_path_hash: (u64, u64),
+    /// The name of [`Node::function`], for human readers.
+    pub function: String,
+    pub block: usize,
+    pub statement_idx: usize,
+    /// The local in [`Node::dest`].
+    pub local: u32,
+    /// The pointer, or a pointer derived from it, was loaded from.
+    pub load: bool,
+    /// The pointer, or a pointer derived from it, was stored to.
+    pub store: bool,
+    /// The pointer, or a pointer derived from it, was offset.
+    pub offset: bool,
+    /// [`NodeInfo::unique`].
+    pub unique: bool,
+}
+
+/// Collect the [`NodeFacts`] of every [`Node`] that assigns a whole local.
+///
+/// [`Graphs`] must already have had [`add_info`](crate::info::add_info) run on it.
+pub fn collect_facts(graphs: &Graphs) -> Vec<NodeFacts> {
+    let mut facts = Vec::new();
+    for graph in &graphs.graphs {
+        // [`FlowInfo::store`](crate::info::FlowInfo::store) also counts storing the pointer
+        // itself through some other pointer, which doesn't need write permission.
+        let writes = graph.needs_write_permission().collect::<HashSet<_>>();
+        for (node_id, node) in graph.nodes.iter_enumerated() {
+            let Node {
+                function,
+                block,
+                statement_idx,
+                dest,
+                info,
+                ..
+            } = node;
+            let dest = match dest {
+                Some(dest) if dest.projection.is_empty() => dest,
+                _ => continue,
+            };
+            let info = info.as_ref().expect("`add_info` was not run");
+            // [`FlowInfo::load`](crate::info::FlowInfo::load) also counts a
+            // [`NodeKind::LoadValue`] that produced the pointer, which isn't a load through it.
+            let load = info
+                .flows_to
+                .load
+                .map_or(false, |id| graph.nodes[id].kind == NodeKind::LoadAddr);
+            let offset = info.flows_to.pos_offset.is_some() || info.flows_to.neg_offset.is_some();
+            facts.push(NodeFacts {
+                def_path_hash: function.id.0.into(),
+                function: function.name.clone(),
+                block: block.as_usize(),
+                statement_idx: *statement_idx,
+                local: dest.local.index,
+                load,
+                store: writes.contains(&node_id),
+                offset,
+                unique: info.unique,
+            });
+        }
+    }
+    facts
+}
+
+/// Write the [`NodeFacts`] of `graphs` to `path` as JSON.
+pub fn write_facts(graphs: &Graphs, path: &Path) -> eyre::Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer(writer, &collect_facts(graphs))?;
+    Ok(())
+}
@@ -1,8 +1,10 @@
+use c2rust_analysis_rt::events::ThreadId;
 use c2rust_analysis_rt::mir_loc::{self, DefPathHash, Func};
-use c2rust_analysis_rt::mir_loc::{FuncId, MirPlace};
+use c2rust_analysis_rt::mir_loc::{FuncId, MirPlace, SourceSpan};
 use rustc_index::newtype_index;
 use rustc_index::vec::IndexVec;
 use rustc_middle::mir::{BasicBlock, Field, Local};
+use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 use std::{
     collections::HashMap,
@@ -13,7 +15,7 @@ use crate::info::NodeInfo;
 use crate::util::pad_columns;
 use crate::util::ShortOption;
 
-#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy, Serialize, Deserialize)]
 pub enum NodeKind {
     /// A copy from one [`Local`] to another.
     ///
@@ -26,7 +28,7 @@ pub enum NodeKind {
     /// Used for operations like `_2 = &(*_1).0`.
     /// Nested field accesses like `_4 = &(*_1).x.y.z`
     /// are broken into multiple [`Node`]s, each covering one level.
-    Field(Field),
+    Field(#[serde(with = "crate::json::idx")] Field),
 
     /// Pointer arithmetic.
     ///
@@ -47,7 +49,7 @@ pub enum NodeKind {
     /// even when those uses don't go through a pointer.
     ///
     /// Can't have a [`Node::source`].
-    AddrOfLocal(Local),
+    AddrOfLocal(#[serde(with = "crate::json::idx")] Local),
 
     /// Get the address of a static.
     ///
@@ -76,6 +78,19 @@ pub enum NodeKind {
     /// Can't be the [`Node::source`] of any other operation.
     Free,
 
+    /// Heap reallocation, which frees the object its [`Node::source`] points to, like a
+    /// [`Free`](Self::Free), and produces a pointer to a new one, like an [`Alloc`](Self::Alloc).
+    ///
+    /// The new object is described by the same [`Graph`] as the old one, with the pointers to it
+    /// derived from the [`Realloc`](Self::Realloc), so the [`Graph`] links them.  What happens to
+    /// the new object is about the pointers derived from the [`Realloc`](Self::Realloc), not the
+    /// old pointers to its source, so it doesn't flow back to them in [`NodeInfo`]: for them, the
+    /// [`Realloc`](Self::Realloc) is just a free, and their last use.
+    ///
+    /// Always has a [`Node::source`]; a reallocation of a pointer we didn't see created, like a
+    /// null one, is an [`Alloc`](Self::Alloc) instead.
+    Realloc,
+
     /// Int to pointer conversion.
     ///
     /// Details TBD.
@@ -134,6 +149,7 @@ impl Display for NodeKind {
                 Ok(())
             }
             Free => write!(f, "free"),
+            Realloc => write!(f, "realloc"),
             PtrToInt => write!(f, "ptr_to_int"),
             IntToPtr => write!(f, "int_to_ptr"),
             LoadValue => write!(f, "value.load"),
@@ -151,7 +167,7 @@ impl Display for NodeKind {
 /// Each operation occurs at a point in time, but the timestamp is not stored explicitly.  Instead,
 /// nodes in each graph are stored in sequential order, and timing relationships can be identified
 /// by comparing `NodeId`s.
-#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Serialize, Deserialize)]
 pub struct Node {
     /// The function that contains this operation.
     ///
@@ -162,6 +178,7 @@ pub struct Node {
     /// modified by the operation.
     pub function: Func,
     /// The basic block that contains this operation.
+    #[serde(with = "crate::json::idx")]
     pub block: BasicBlock,
     /// The index within the basic block of the MIR statement or terminator that performed this
     /// operation.  As in `rustc_middle::mir::Location`, an index less than the number of
@@ -175,11 +192,27 @@ pub struct Node {
     /// The kind of operation that was performed.
     pub kind: NodeKind,
     /// The `Node` that produced the input to this operation.
+    #[serde(with = "crate::json::opt_idx")]
     pub source: Option<NodeId>,
     /// Any string useful for debugging.
     pub debug_info: String,
     /// Information about the [`Node`] computed from the pdg.
     pub info: Option<NodeInfo>,
+    /// For an [`Alloc`](NodeKind::Alloc), the size in bytes of the allocation.
+    pub size: Option<usize>,
+    /// The offset in bytes from the start of the object of the pointer this operation produces,
+    /// or for operations that don't produce one, like [`LoadAddr`](NodeKind::LoadAddr), of the
+    /// pointer it uses.  This is `None` if the address isn't known relative to the start of the
+    /// object, as for a [`Field`](NodeKind::Field) projection.
+    pub byte_offset: Option<isize>,
+    /// Whether this operation passes the pointer as an argument to a function that isn't
+    /// instrumented, so what happens to it there isn't observed.
+    pub extern_arg: bool,
+    /// The thread that performed this operation.
+    pub thread: ThreadId,
+    /// Where in the source code this operation came from, if known.  For the copy of an argument
+    /// into a callee, this is the call.
+    pub span: Option<SourceSpan>,
 }
 
 struct BlockStatement<'a> {
@@ -208,6 +241,7 @@ impl Node {
             source,
             debug_info,
             info,
+            ..
         } = self;
         let src = ShortOption(source.as_ref());
         let dest = ShortOption(dest.as_ref());
@@ -260,11 +294,12 @@ impl Display for DisplayNode<'_> {
 }
 
 /// A pointer derivation graph, which tracks the handling of one object throughout its lifetime.
-#[derive(Debug, Default, Eq, PartialEq, Hash, Clone)]
+#[derive(Debug, Default, Eq, PartialEq, Hash, Clone, Serialize, Deserialize)]
 pub struct Graph {
     /// The nodes in the graph.  Nodes are stored in increasing order by timestamp.  The first
     /// node, called the "root node", creates the object described by this graph, and all other
     /// nodes are derived from it.
+    #[serde(with = "crate::json::index_vec")]
     pub nodes: IndexVec<NodeId, Node>,
 }
 
@@ -272,26 +307,190 @@ impl Graph {
     pub fn new() -> Self {
         Self::default()
     }
-}
 
-impl Display for Graph {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    /// [`Graphs::remove_addr_of_local_sources`] for one [`Graph`].
+    pub fn remove_addr_of_local_sources(&mut self) {
+        for node in &mut self.nodes {
+            if let NodeKind::AddrOfLocal(..) = node.kind {
+                node.dest = None;
+            }
+        }
+    }
+
+    /// Format the [`Node`]s `ids`, one per line, with their columns aligned.
+    pub fn format_nodes(&self, ids: impl IntoIterator<Item = NodeId>) -> Vec<String> {
         let sep = '|';
-        let lines = self
-            .nodes
-            .iter_enumerated()
-            .map(|(node_id, node)| {
+        let lines = ids
+            .into_iter()
+            .map(|id| {
                 DisplayNode {
-                    id: node_id,
-                    node,
+                    id,
+                    node: &self.nodes[id],
                     sep,
                 }
                 .to_string()
             })
             .collect::<Vec<_>>();
+        pad_columns(&lines, sep, " ")
+            .into_iter()
+            .map(|line| line.trim_end().to_owned())
+            .collect()
+    }
+}
+
+/// Builds a synthetic [`Graph`] one [`Node`] at a time, for tests and for tools that want a PDG
+/// without running an instrumented program.
+///
+/// Each [`Node`] is created at the current location, function, and thread of the builder, which
+/// start out as `bb0[0]` of a placeholder function on thread 0, and has no [`Node::dest`],
+/// [`Node::info`], size, offset, or span.  Use [`GraphBuilder::node_mut`] to set any of those.
+/// [`NodeId`]s are handed out in order, so each [`Node`] happens after all the previous ones, and
+/// the sources passed to the constructors should be [`NodeId`]s this builder returned.
+///
+/// ```ignore
+/// let mut g = GraphBuilder::new();
+/// let p = g.alloc(1);
+/// let q = g.field(p, 0_u32);
+/// g.store_addr(q);
+/// let graph = g.build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct GraphBuilder {
+    graph: Graph,
+    function: Func,
+    block: BasicBlock,
+    statement_idx: usize,
+    thread: ThreadId,
+}
+
+impl Default for GraphBuilder {
+    fn default() -> Self {
+        Self {
+            graph: Graph::new(),
+            function: Func {
+                id: FuncId((0, 0).into()),
+                name: "fake_function".into(),
+            },
+            block: BasicBlock::from_u32(0),
+            statement_idx: 0,
+            thread: 0,
+        }
+    }
+}
+
+impl GraphBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create the following [`Node`]s in `function`.
+    pub fn in_function(&mut self, function: Func) -> &mut Self {
+        self.function = function;
+        self
+    }
+
+    /// Create the following [`Node`]s at statement `statement_idx` of `block`.
+    pub fn at(&mut self, block: impl Into<BasicBlock>, statement_idx: usize) -> &mut Self {
+        self.block = block.into();
+        self.statement_idx = statement_idx;
+        self
+    }
+
+    /// Create the following [`Node`]s on `thread`.
+    pub fn on_thread(&mut self, thread: ThreadId) -> &mut Self {
+        self.thread = thread;
+        self
+    }
+
+    /// Add a [`Node`] of kind `kind` derived from `source`.
+    pub fn node(&mut self, kind: NodeKind, source: Option<NodeId>) -> NodeId {
+        self.graph.nodes.push(Node {
+            function: self.function.clone(),
+            block: self.block,
+            statement_idx: self.statement_idx,
+            dest: None,
+            kind,
+            source,
+            debug_info: String::new(),
+            info: None,
+            size: None,
+            byte_offset: None,
+            extern_arg: false,
+            thread: self.thread,
+            span: None,
+        })
+    }
+
+    /// The [`Node`] `id`, to set the fields the constructors don't.
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.graph.nodes[id]
+    }
+
+    pub fn copy(&mut self, source: NodeId) -> NodeId {
+        self.node(NodeKind::Copy, Some(source))
+    }
+
+    pub fn field(&mut self, source: NodeId, field: impl Into<Field>) -> NodeId {
+        self.node(NodeKind::Field(field.into()), Some(source))
+    }
+
+    pub fn offset(&mut self, source: NodeId, offset: isize) -> NodeId {
+        self.node(NodeKind::Offset(offset), Some(source))
+    }
+
+    pub fn addr_of_local(&mut self, local: impl Into<Local>) -> NodeId {
+        self.node(NodeKind::AddrOfLocal(local.into()), None)
+    }
+
+    pub fn addr_of_static(&mut self, static_: DefPathHash) -> NodeId {
+        self.node(NodeKind::_AddrOfStatic(static_), None)
+    }
+
+    pub fn alloc(&mut self, n: usize) -> NodeId {
+        self.node(NodeKind::Alloc(n), None)
+    }
+
+    pub fn free(&mut self, source: NodeId) -> NodeId {
+        self.node(NodeKind::Free, Some(source))
+    }
+
+    pub fn realloc(&mut self, source: NodeId) -> NodeId {
+        self.node(NodeKind::Realloc, Some(source))
+    }
+
+    pub fn int_to_ptr(&mut self) -> NodeId {
+        self.node(NodeKind::IntToPtr, None)
+    }
+
+    pub fn ptr_to_int(&mut self, source: NodeId) -> NodeId {
+        self.node(NodeKind::PtrToInt, Some(source))
+    }
+
+    pub fn load_value(&mut self) -> NodeId {
+        self.node(NodeKind::LoadValue, None)
+    }
+
+    pub fn store_value(&mut self, source: NodeId) -> NodeId {
+        self.node(NodeKind::StoreValue, Some(source))
+    }
+
+    pub fn load_addr(&mut self, source: NodeId) -> NodeId {
+        self.node(NodeKind::LoadAddr, Some(source))
+    }
+
+    pub fn store_addr(&mut self, source: NodeId) -> NodeId {
+        self.node(NodeKind::StoreAddr, Some(source))
+    }
+
+    pub fn build(self) -> Graph {
+        self.graph
+    }
+}
+
+impl Display for Graph {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         writeln!(f, "g {{")?;
-        for line in pad_columns(&lines, sep, " ") {
-            let line = line.trim_end();
+        for line in self.format_nodes(self.nodes.indices()) {
             writeln!(f, "\t{line}")?;
         }
         write!(f, "}}")?;
@@ -311,13 +510,17 @@ impl Display for GraphId {
 }
 
 /// A collection of graphs describing the handling of one or more objects within the program.
-#[derive(Debug, Default, Eq, PartialEq)]
+#[derive(Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Graphs {
     /// The graphs.  Each graph describes one object, or one group of objects that were all handled
     /// identically.
+    #[serde(with = "crate::json::index_vec")]
     pub graphs: IndexVec<GraphId, Graph>,
 
     /// Lookup table for finding all nodes in all graphs that store to a particular MIR local.
+    ///
+    /// This is only needed while building the graphs, so it isn't serialized.
+    #[serde(skip)]
     pub latest_assignment: HashMap<(FuncId, mir_loc::Local), (GraphId, NodeId)>,
 }
 
@@ -336,11 +539,7 @@ impl Graphs {
     /// [`Node::dest`]: crate::graph::Node::dest
     pub fn remove_addr_of_local_sources(&mut self) {
         for graph in &mut self.graphs {
-            for node in &mut graph.nodes {
-                if let NodeKind::AddrOfLocal(..) = node.kind {
-                    node.dest = None;
-                }
-            }
+            graph.remove_addr_of_local_sources();
         }
     }
 }
@@ -1,9 +1,14 @@
+use crate::graph::Graphs;
 use crate::graph::{Graph, Node, NodeId, NodeKind};
-use crate::Graphs;
+use crate::safety::MemoryError;
+use c2rust_analysis_rt::events::ThreadId;
+use rustc_index::vec::IndexVec;
 use rustc_middle::mir::Field;
-use std::cmp::{max, min};
-use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::cmp::max;
+use std::collections::HashMap;
 use std::fmt::{self, Debug, Display, Formatter};
+use std::mem;
 
 /// Force an import of [`Node`] just for docs.
 const _: Option<Node> = None;
@@ -12,17 +17,169 @@ const _: Option<Node> = None;
 ///
 /// Includes information about what kinds of [`Node`]s the [`Node`] flows to,
 /// as well as its ability to be used as a `&mut`.
-#[derive(Hash, Clone, PartialEq, Eq, Debug)]
+///
+/// It's (de)serialized as its [`NodeInfoRecord`].
+#[derive(Hash, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(into = "NodeInfoRecord", from = "NodeInfoRecord")]
 pub struct NodeInfo {
-    flows_to: FlowInfo,
+    pub flows_to: FlowInfo,
+
+    /// A [`Node`] that passed the pointer to a function that isn't instrumented, as found by
+    /// [`Node::extern_arg`].  The pointer may have been used there in ways we didn't observe, so
+    /// the rest of the [`NodeInfo`] is only a lower bound on what it needs.
+    pub flows_to_extern: Option<NodeId>,
 
     /// Whether the [`Node`] can be used as a `&mut`.
-    unique: bool,
+    pub unique: bool,
+
+    /// Whether the [`Node`] isn't [`unique`](Self::unique) because of pointers used on different
+    /// threads.  Such a pointer needs a `Mutex` or atomics rather than a `RefCell`.
+    pub non_unique_cross_thread: bool,
+
+    /// The chronologically last use of the pointer: the last [`Node`] derived from it, or the
+    /// [`Node`] itself if none are.  The pointer and all its aliases are dead after this, so this
+    /// is where a borrow of it can end.  [`Node::span`] of it is its location in the source.
+    pub last_use: NodeId,
+}
+
+impl NodeInfo {
+    /// The kinds of [`Node`]s that the [`Node`] flows to, with the first of each, in the order of
+    /// [`FlowKind`].
+    pub fn flows(&self) -> impl Iterator<Item = (FlowKind, NodeId)> {
+        let flows_to = &self.flows_to;
+        [
+            (FlowKind::Load, flows_to.load),
+            (FlowKind::Store, flows_to.store),
+            (FlowKind::PosOffset, flows_to.pos_offset),
+            (FlowKind::NegOffset, flows_to.neg_offset),
+            (FlowKind::OutOfBounds, flows_to.out_of_bounds),
+            (FlowKind::Free, flows_to.free),
+            (FlowKind::Extern, self.flows_to_extern),
+        ]
+        .into_iter()
+        .filter_map(|(kind, id)| Some((kind, id?)))
+    }
+
+    /// The structured form of the [`NodeInfo`], which is what the JSON exporter writes.
+    pub fn to_record(&self) -> NodeInfoRecord {
+        NodeInfoRecord {
+            flows_to: self
+                .flows()
+                .map(|(kind, node)| FlowRecord { kind, node })
+                .collect(),
+            unique: self.unique,
+            non_unique_cross_thread: self.non_unique_cross_thread,
+            last_use: self.last_use,
+        }
+    }
+
+    /// The [`NodeInfo`] that [`NodeInfo::to_record`] returned `record` for.
+    pub fn from_record(record: NodeInfoRecord) -> Self {
+        let mut info = NodeInfo {
+            flows_to: FlowInfo::default(),
+            flows_to_extern: None,
+            unique: record.unique,
+            non_unique_cross_thread: record.non_unique_cross_thread,
+            last_use: record.last_use,
+        };
+        for FlowRecord { kind, node } in record.flows_to {
+            let flows_to = &mut info.flows_to;
+            let id = match kind {
+                FlowKind::Load => &mut flows_to.load,
+                FlowKind::Store => &mut flows_to.store,
+                FlowKind::PosOffset => &mut flows_to.pos_offset,
+                FlowKind::NegOffset => &mut flows_to.neg_offset,
+                FlowKind::OutOfBounds => &mut flows_to.out_of_bounds,
+                FlowKind::Free => &mut flows_to.free,
+                FlowKind::Extern => &mut info.flows_to_extern,
+            };
+            *id = Some(node);
+        }
+        info
+    }
 }
 
+/// Whether the [`Node`] is [`unique`](NodeInfo::unique), then what it flows to, then its
+/// [`last_use`](NodeInfo::last_use), like `unique; flows to load n[2], +offset n[3]; last use n[3]`.
 impl Display for NodeInfo {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "")
+        let unique = if self.unique {
+            "unique"
+        } else if self.non_unique_cross_thread {
+            "not unique across threads"
+        } else {
+            "not unique"
+        };
+        write!(f, "{unique}; ")?;
+        for (i, (kind, id)) in self.flows().enumerate() {
+            let sep = if i == 0 { "flows to " } else { ", " };
+            write!(f, "{sep}{kind} {id}")?;
+        }
+        if self.flows().next().is_some() {
+            write!(f, "; ")?;
+        }
+        write!(f, "last use {}", self.last_use)
+    }
+}
+
+/// A kind of [`Node`] that a [`Node`] can flow to, one for each field of [`FlowInfo`] and
+/// [`NodeInfo::flows_to_extern`].
+#[derive(Debug, Hash, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlowKind {
+    Load,
+    Store,
+    PosOffset,
+    NegOffset,
+    OutOfBounds,
+    Free,
+    Extern,
+}
+
+impl Display for FlowKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let s = match self {
+            FlowKind::Load => "load",
+            FlowKind::Store => "store",
+            FlowKind::PosOffset => "+offset",
+            FlowKind::NegOffset => "-offset",
+            FlowKind::OutOfBounds => "out of bounds",
+            FlowKind::Free => "free",
+            FlowKind::Extern => "extern",
+        };
+        f.write_str(s)
+    }
+}
+
+/// One of the [`NodeInfo::flows`] of a [`NodeInfoRecord`].
+#[derive(Debug, Hash, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlowRecord {
+    pub kind: FlowKind,
+    #[serde(with = "crate::json::idx")]
+    pub node: NodeId,
+}
+
+/// The structured form of a [`NodeInfo`], from [`NodeInfo::to_record`], with the same facts as
+/// its [`Display`].  Only the kinds of [`Node`]s that it flows to are listed, so the JSON doesn't
+/// need a field for each one.
+#[derive(Debug, Hash, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeInfoRecord {
+    pub flows_to: Vec<FlowRecord>,
+    pub unique: bool,
+    pub non_unique_cross_thread: bool,
+    #[serde(with = "crate::json::idx")]
+    pub last_use: NodeId,
+}
+
+impl From<NodeInfo> for NodeInfoRecord {
+    fn from(info: NodeInfo) -> Self {
+        info.to_record()
+    }
+}
+
+impl From<NodeInfoRecord> for NodeInfo {
+    fn from(record: NodeInfoRecord) -> Self {
+        NodeInfo::from_record(record)
     }
 }
 
@@ -31,10 +188,17 @@ impl Display for NodeInfo {
 /// A node A is said to flow into B if it is the transitive 'source' of B.
 #[derive(Debug, Hash, Clone, Copy, PartialEq, Eq, Default)]
 pub struct FlowInfo {
-    load: Option<NodeId>,
-    store: Option<NodeId>,
-    pos_offset: Option<NodeId>,
-    neg_offset: Option<NodeId>,
+    pub load: Option<NodeId>,
+    pub store: Option<NodeId>,
+    pub pos_offset: Option<NodeId>,
+    pub neg_offset: Option<NodeId>,
+    /// An offset or access outside the bounds of the object, as found by
+    /// [`Graph::out_of_bounds`].  A pointer that's offset but never out of bounds of an
+    /// allocation larger than one element may still need to become a slice.
+    pub out_of_bounds: Option<NodeId>,
+    /// A [`NodeKind::Free`] or [`NodeKind::Realloc`] of the object.  The pointers that flow to one
+    /// are those that own the object in the end.
+    pub free: Option<NodeId>,
 }
 
 impl FlowInfo {
@@ -46,6 +210,8 @@ impl FlowInfo {
             store: matches!(k, StoreAddr | StoreValue).then(|| n_id),
             pos_offset: matches!(k, Offset(x) if x > 0).then(|| n_id),
             neg_offset: matches!(k, Offset(x) if x < 0).then(|| n_id),
+            out_of_bounds: None,
+            free: matches!(k, Free).then(|| n_id),
         }
     }
 }
@@ -54,157 +220,267 @@ impl FlowInfo {
 /// necessarily connected) for each [`Node`] in it whether there is a path following 'source' edges
 /// from any [`Node`] with a given property to the [`Node`] in question.
 fn set_flow_info(g: &mut Graph) {
-    let mut flow_map: HashMap<NodeId, FlowInfo> = HashMap::from_iter(
-        g.nodes
-            .iter_enumerated()
-            .map(|(idx, node)| (idx, FlowInfo::new(idx, node.kind))),
-    );
-    for (n_id, mut node) in g.nodes.iter_enumerated_mut().rev() {
-        let cur_node_flow_info: FlowInfo = flow_map.remove(&n_id).unwrap();
+    let mut flow_map = g
+        .nodes
+        .iter_enumerated()
+        .map(|(idx, node)| FlowInfo::new(idx, node.kind))
+        .collect::<IndexVec<NodeId, _>>();
+    let mut extern_map = g
+        .nodes
+        .iter_enumerated()
+        .map(|(idx, node)| node.extern_arg.then_some(idx))
+        .collect::<IndexVec<NodeId, _>>();
+    for error in g.out_of_bounds() {
+        if let MemoryError::OutOfBounds { node, .. } = error {
+            flow_map[node].out_of_bounds = Some(node);
+        }
+    }
+    for (n_id, node) in g.nodes.iter_enumerated_mut().rev() {
+        let cur_node_flow_info = flow_map[n_id];
         if let Some(p_id) = node.source {
-            let parent = flow_map.get_mut(&p_id).unwrap();
-            parent.load = parent.load.or(cur_node_flow_info.load);
-            parent.store = parent.store.or(cur_node_flow_info.store);
-            parent.pos_offset = parent.pos_offset.or(cur_node_flow_info.pos_offset);
-            parent.neg_offset = parent.neg_offset.or(cur_node_flow_info.neg_offset);
+            let parent = &mut flow_map[p_id];
+            if node.kind == NodeKind::Realloc {
+                // Only the free of the old object flows back from a realloc.
+                parent.free = parent.free.or(Some(n_id));
+            } else {
+                parent.load = parent.load.or(cur_node_flow_info.load);
+                parent.store = parent.store.or(cur_node_flow_info.store);
+                parent.pos_offset = parent.pos_offset.or(cur_node_flow_info.pos_offset);
+                parent.neg_offset = parent.neg_offset.or(cur_node_flow_info.neg_offset);
+                parent.out_of_bounds = parent.out_of_bounds.or(cur_node_flow_info.out_of_bounds);
+                parent.free = parent.free.or(cur_node_flow_info.free);
+                extern_map[p_id] = extern_map[p_id].or(extern_map[n_id]);
+            }
         }
         node.info = Some(NodeInfo {
             flows_to: cur_node_flow_info,
+            flows_to_extern: extern_map[n_id],
             unique: false,
+            non_unique_cross_thread: false,
+            last_use: n_id,
         });
     }
 }
 
 /// Maps each [`Node`] in a [`Graph`] to its chronologically (according to [`NodeId`]) final descendant.
+/// The descendants of a [`NodeKind::Realloc`] use the new object, so they don't count for the
+/// ancestors of the [`NodeKind::Realloc`], whose last use is the [`NodeKind::Realloc`] itself.
 ///
 /// The [`Graph`] is assumed to be acyclic and topologically sorted, but not necessarily connected.
-fn get_last_desc(g: &mut Graph) -> HashMap<NodeId, NodeId> {
-    let mut desc_map = g
-        .nodes
-        .indices()
-        .map(|idx| (idx, idx))
-        .collect::<HashMap<_, _>>();
-    for (child, parent) in g
-        .nodes
-        .iter_enumerated()
-        .rev()
-        .filter_map(|(child, child_node)| Some((child, child_node.source?)))
-    {
-        let child = desc_map[&child];
-        desc_map
-            .entry(parent)
-            .and_modify(|parent| *parent = max(*parent, child));
+fn get_last_desc(g: &Graph) -> IndexVec<NodeId, NodeId> {
+    let mut desc_map = g.nodes.indices().collect::<IndexVec<NodeId, _>>();
+    for (child, child_node) in g.nodes.iter_enumerated().rev() {
+        if let Some(parent) = child_node.source {
+            let last = match child_node.kind {
+                NodeKind::Realloc => child,
+                _ => desc_map[child],
+            };
+            desc_map[parent] = max(desc_map[parent], last);
+        }
     }
     desc_map
 }
 
-/// Finds the inverse of a [`Graph`], each [`Node`] mapping to a [`Vec`] of its immediate
-/// descendents and their relationship to the [`Node`] in terms of fields.
-fn collect_children(g: &Graph) -> HashMap<NodeId, Vec<(NodeId, Vec<Field>)>> {
-    let mut children = HashMap::<_, Vec<(NodeId, Vec<Field>)>>::new();
-    for parent in g.nodes.indices() {
-        children.entry(parent).or_default();
+/// The threads that a set of [`Node`]s ran on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Threads {
+    One(ThreadId),
+    Many,
+}
+
+impl Threads {
+    fn union(self, other: Self) -> Self {
+        if self == other {
+            self
+        } else {
+            Threads::Many
+        }
     }
-    for (parent, child, child_node) in g
+}
+
+/// Maps each [`Node`] in a [`Graph`] to the [`Threads`] that it and its descendants ran on, where
+/// as in [`get_last_desc`], the descendants of a [`NodeKind::Realloc`] don't count for its
+/// ancestors.
+///
+/// The [`Graph`] is assumed to be acyclic and topologically sorted, but not necessarily connected.
+fn get_threads(g: &Graph) -> IndexVec<NodeId, Threads> {
+    let mut threads = g
         .nodes
-        .iter_enumerated()
-        .rev()
-        .filter_map(|(child, child_node)| Some((child_node.source?, child, child_node)))
-    {
-        if let NodeKind::Field(f) = child_node.kind {
+        .iter()
+        .map(|node| Threads::One(node.thread))
+        .collect::<IndexVec<NodeId, _>>();
+    for (child, child_node) in g.nodes.iter_enumerated().rev() {
+        if let Some(parent) = child_node.source {
+            let child_threads = match child_node.kind {
+                NodeKind::Realloc => Threads::One(child_node.thread),
+                _ => threads[child],
+            };
+            threads[parent] = threads[parent].union(child_threads);
+        }
+    }
+    threads
+}
+
+/// One step of an [`AccessPath`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Projection {
+    Field(Field),
+    /// An offset by any amount.
+    Offset,
+}
+
+/// The projections from a pointer to a pointer derived from it, through [`NodeKind::Field`]s and
+/// [`NodeKind::Offset`]s.
+///
+/// Offsets are symbolic: we don't compare their amounts, so two paths that diverge after an
+/// offset may alias.  This matches `rustc`'s borrow checker, which rejects borrowing two elements
+/// of an array at the same time, and we need to reject it too, since we may rewrite the array
+/// pointer to something like a `&mut [_]` or a `Vec`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+struct AccessPath {
+    /// The projections, innermost first, as they're added from the bottom up.
+    rev_projections: Vec<Projection>,
+}
+
+impl AccessPath {
+    /// Add `projection` as the new outermost projection of the path.
+    fn push_outer(&mut self, projection: Projection) {
+        self.rev_projections.push(projection);
+    }
+
+    /// The projections, outermost first.
+    fn projections(&self) -> impl Iterator<Item = Projection> + '_ {
+        self.rev_projections.iter().rev().copied()
+    }
+
+    /// Whether the pointers at the ends of `self` and `other` may point to overlapping memory.
+    /// They can't only if they project different fields before either path has an offset.
+    fn may_alias(&self, other: &AccessPath) -> bool {
+        for projections in self.projections().zip(other.projections()) {
+            match projections {
+                (Projection::Field(f1), Projection::Field(f2)) if f1 == f2 => {}
+                (Projection::Field(_), Projection::Field(_)) => return false,
+                _ => return true,
+            }
+        }
+        true
+    }
+}
+
+/// The immediate descendants of a [`Node`] that aren't projections, and their [`AccessPath`]s
+/// from it, sorted by [`NodeId`].
+type Children = Vec<(NodeId, AccessPath)>;
+
+/// Finds the inverse of a [`Graph`], each [`Node`] mapping to a [`Vec`] of its immediate
+/// descendents and their relationship to the [`Node`] in terms of fields and offsets.
+/// The [`NodeKind::Field`]s and [`NodeKind::Offset`]s are skipped over, and their children added
+/// to the children of their parent, with the projection added to their [`AccessPath`]s.
+fn collect_children(g: &Graph) -> IndexVec<NodeId, Children> {
+    let mut children = IndexVec::<NodeId, Children>::from_elem_n(Vec::new(), g.nodes.len());
+    for (child, child_node) in g.nodes.iter_enumerated().rev() {
+        let parent = match child_node.source {
+            Some(parent) => parent,
+            None => continue,
+        };
+        let projection = match child_node.kind {
+            NodeKind::Field(f) => Some(Projection::Field(f)),
+            NodeKind::Offset(_) => Some(Projection::Offset),
+            _ => None,
+        };
+        if let Some(projection) = projection {
             let my_children =
-                children
-                    .remove(&child)
-                    .unwrap()
+                mem::take(&mut children[child])
                     .into_iter()
-                    .map(|(gchild, mut gchildf)| {
-                        (
-                            gchild,
-                            ({
-                                gchildf.push(f);
-                                gchildf
-                            }),
-                        )
+                    .map(|(gchild, mut gchild_path)| {
+                        gchild_path.push_outer(projection);
+                        (gchild, gchild_path)
                     });
-            children.insert(child, Vec::new());
-            children.get_mut(&parent).unwrap().extend(my_children);
+            children[parent].extend(my_children);
         } else {
-            children
-                .get_mut(&parent)
-                .unwrap()
-                .push((child, Vec::<_>::new()));
+            children[parent].push((child, AccessPath::default()));
         }
     }
+    for node_children in &mut children {
+        node_children.sort_by_key(|&(id, _)| id);
+    }
     children
 }
 
-/// Checks if fs1 and fs2 are either prefixes of each other, which would make them aliases.
-/// Note: the fields were pushed on from the bottom up, so they are in reverse order.
-fn prefix(fs1: &Vec<Field>, fs2: &Vec<Field>) -> bool {
-    let len1 = fs1.len();
-    let len2 = fs2.len();
-    for i in 0..min(len1, len2) {
-        if fs1[len1 - 1 - i] != fs2[len2 - 1 - i] {
-            return false;
-        }
-    }
-    true
+/// A conflict between the children of a [`Node`], ordered so the worse one is greater.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Conflict {
+    /// The conflicting children and their descendants all ran on the same thread.
+    SameThread,
+    /// The conflicting children or their descendants ran on different threads.
+    CrossThread,
 }
 
 /// Given a list of [`Node`]s of the same parent and information about them,
-/// determines if any have conflicts with any of the others.
-/// Children which are not a field cannot be live at the same time as any other child.
-/// Children which are a field cannot be live at the same time as any other one of the same field.
+/// determines if any have conflicts with any of the others, and the worst such [`Conflict`].
+/// Children cannot be live at the same time as any other child whose [`AccessPath`] may alias
+/// theirs.
 fn check_children_conflict(
-    parent: &NodeId,
-    children: &HashMap<NodeId, Vec<(NodeId, Vec<Field>)>>,
-    descs: &HashMap<NodeId, NodeId>,
-) -> bool {
-    let mut max_descs = HashMap::<Vec<Field>, NodeId>::new();
-    let mut node_children = children[parent].clone();
-    node_children.sort_by(|(id1, _), (id2, _)| id1.cmp(id2));
-    for (child, child_fields) in node_children {
-        let conflicts =
-            |fields| matches!(max_descs.get(fields), Some(max_desc) if max_desc > &child);
-        if max_descs
-            .keys()
-            .filter(|sib_fields| prefix(sib_fields, &child_fields))
-            .any(conflicts)
-        {
-            return true;
+    parent: NodeId,
+    children: &IndexVec<NodeId, Children>,
+    descs: &IndexVec<NodeId, NodeId>,
+    threads: &IndexVec<NodeId, Threads>,
+) -> Option<Conflict> {
+    let mut max_descs = HashMap::<&AccessPath, (NodeId, Threads)>::new();
+    let mut conflict = None;
+    for (child, child_path) in &children[parent] {
+        let child_threads = threads[*child];
+        for (sib_path, &(max_desc, sib_threads)) in &max_descs {
+            if max_desc > *child && sib_path.may_alias(child_path) {
+                let cur = match sib_threads.union(child_threads) {
+                    Threads::One(_) => Conflict::SameThread,
+                    Threads::Many => return Some(Conflict::CrossThread),
+                };
+                conflict = max(conflict, Some(cur));
+            }
         }
-        let cur = descs[&child];
+        let cur = descs[*child];
         max_descs
-            .entry(child_fields.clone())
-            .and_modify(|past| *past = max(*past, cur))
-            .or_insert(cur);
+            .entry(child_path)
+            .and_modify(|(past, past_threads)| {
+                *past = max(*past, cur);
+                *past_threads = past_threads.union(child_threads);
+            })
+            .or_insert((cur, child_threads));
     }
-    false
+    conflict
 }
 
-/// Compute and set [`NodeInfo::unique`].
+/// Compute and set [`NodeInfo::unique`] and [`NodeInfo::non_unique_cross_thread`], and
+/// [`NodeInfo::last_use`] from the same traversal.
 ///
-/// If a [`Node`] is not [`unique`], none of its descendents can be [`unique`].
+/// If a [`Node`] is not [`unique`], none of its descendents can be [`unique`], except through a
+/// [`NodeKind::Realloc`], whose pointer to the new object is unique to begin with.
 /// If any of a node's children conflict with each other, it is not [`unique`].
 /// Because we traverse the [`Graph`] visiting all parents before their children,
 /// just checking the immediate parent's [`unique`]ness status
 /// is sufficient to guarantee the first condition.
+/// A [`Node`] is [`non_unique_cross_thread`] if it or its parent has a [`Conflict::CrossThread`].
 ///
 /// [`unique`]: NodeInfo::unique
+/// [`non_unique_cross_thread`]: NodeInfo::non_unique_cross_thread
 fn set_uniqueness(g: &mut Graph) {
     let children = collect_children(g);
     let last_descs = get_last_desc(g);
-    let mut non_uniqueness = HashSet::new();
+    let threads = get_threads(g);
+    let mut non_unique = IndexVec::<NodeId, Option<Conflict>>::from_elem_n(None, g.nodes.len());
     for (child, child_node) in g.nodes.iter_enumerated() {
-        let parent = child_node.source;
-        if matches!(parent, Some(parent) if non_uniqueness.contains(&parent))
-            || check_children_conflict(&child, &children, &last_descs)
-        {
-            non_uniqueness.insert(child);
-        }
+        let inherited = match child_node.kind {
+            NodeKind::Realloc => None,
+            _ => child_node.source.and_then(|parent| non_unique[parent]),
+        };
+        let conflict = check_children_conflict(child, &children, &last_descs, &threads);
+        non_unique[child] = max(inherited, conflict);
     }
     for (n_id, node) in g.nodes.iter_enumerated_mut() {
-        node.info.as_mut().unwrap().unique = !non_uniqueness.contains(&n_id);
+        let info = node.info.as_mut().unwrap();
+        info.unique = non_unique[n_id].is_none();
+        info.non_unique_cross_thread = non_unique[n_id] == Some(Conflict::CrossThread);
+        info.last_use = last_descs[n_id];
     }
 }
 
@@ -214,54 +490,20 @@ fn set_uniqueness(g: &mut Graph) {
 /// this is an ancestor of with trait X", as well as whether the node can be considered unique.
 pub fn add_info(pdg: &mut Graphs) {
     for g in &mut pdg.graphs {
-        set_flow_info(g);
-        set_uniqueness(g);
+        add_graph_info(g);
     }
 }
 
+/// Initialize [`Node::info`] for each [`Node`] of one [`Graph`], as in [`add_info`].
+pub fn add_graph_info(g: &mut Graph) {
+    set_flow_info(g);
+    set_uniqueness(g);
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use c2rust_analysis_rt::mir_loc::Func;
-    use c2rust_analysis_rt::mir_loc::FuncId;
-    use rustc_middle::mir::Field;
-    use rustc_middle::mir::Local;
-
-    fn mk_node(g: &mut Graph, kind: NodeKind, source: Option<NodeId>) -> NodeId {
-        g.nodes.push(Node {
-            function: Func {
-                id: FuncId((1, 2).into()),
-                name: "fake_function".into(),
-            },
-            block: 0_u32.into(),
-            statement_idx: 0,
-            dest: None,
-            kind,
-            source,
-            info: None,
-            debug_info: "".into(),
-        })
-    }
-
-    fn mk_addr_of_local(g: &mut Graph, local: impl Into<Local>) -> NodeId {
-        mk_node(g, NodeKind::AddrOfLocal(local.into()), None)
-    }
-
-    fn mk_copy(g: &mut Graph, source: NodeId) -> NodeId {
-        mk_node(g, NodeKind::Copy, Some(source))
-    }
-
-    fn mk_store_addr(g: &mut Graph, source: NodeId) -> NodeId {
-        mk_node(g, NodeKind::StoreAddr, Some(source))
-    }
-
-    fn mk_field(g: &mut Graph, source: NodeId, field: impl Into<Field>) -> NodeId {
-        mk_node(g, NodeKind::Field(field.into()), Some(source))
-    }
-
-    fn mk_offset(g: &mut Graph, source: NodeId, i: isize) -> NodeId {
-        mk_node(g, NodeKind::Offset(i), Some(source))
-    }
+    use crate::graph::GraphBuilder;
 
     fn build_pdg(g: Graph) -> Graphs {
         let mut pdg = Graphs::default();
@@ -296,24 +538,24 @@ mod test {
     /// ```
     #[test]
     fn unique_interleave() {
-        let mut g = Graph::default();
+        let mut g = GraphBuilder::new();
 
         // let mut a = 0;
-        let a = mk_addr_of_local(&mut g, 0_u32);
+        let a = g.addr_of_local(0_u32);
         // let b = &mut a;
-        let b1 = mk_copy(&mut g, a);
+        let b1 = g.copy(a);
         // *b = 0;
-        let b2 = mk_store_addr(&mut g, b1);
+        let b2 = g.store_addr(b1);
         // let c = &mut a;
-        let c1 = mk_copy(&mut g, a);
+        let c1 = g.copy(a);
         // *c = 0;
-        let c2 = mk_store_addr(&mut g, c1);
+        let c2 = g.store_addr(c1);
         // *b = 0;
-        let b3 = mk_store_addr(&mut g, b1);
+        let b3 = g.store_addr(b1);
         // *c = 0;
-        let c3 = mk_store_addr(&mut g, c1);
+        let c3 = g.store_addr(c1);
 
-        let pdg = build_pdg(g);
+        let pdg = build_pdg(g.build());
         assert!(!info(&pdg, a).unique);
         assert!(!info(&pdg, b1).unique);
         assert!(!info(&pdg, b2).unique);
@@ -344,22 +586,22 @@ mod test {
     /// ```
     #[test]
     fn unique_interleave_onesided() {
-        let mut g = Graph::default();
+        let mut g = GraphBuilder::new();
 
         // let mut a = 0;   // A
-        let a = mk_addr_of_local(&mut g, 0_u32);
+        let a = g.addr_of_local(0_u32);
         // let b = &mut a;  // B1
-        let b1 = mk_copy(&mut g, a);
+        let b1 = g.copy(a);
         // *b = 0;          // B2
-        let b2 = mk_store_addr(&mut g, b1);
+        let b2 = g.store_addr(b1);
         // let c = &mut a;  // C1
-        let c1 = mk_copy(&mut g, a);
+        let c1 = g.copy(a);
         // *c = 0;          // C2
-        let c2 = mk_store_addr(&mut g, c1);
+        let c2 = g.store_addr(c1);
         // *b = 0;          // B3
-        let b3 = mk_store_addr(&mut g, b1);
+        let b3 = g.store_addr(b1);
 
-        let pdg = build_pdg(g);
+        let pdg = build_pdg(g.build());
         assert!(!info(&pdg, a).unique);
         assert!(!info(&pdg, b1).unique);
         assert!(!info(&pdg, b2).unique);
@@ -388,22 +630,22 @@ mod test {
     /// ```
     #[test]
     fn unique_sub_borrow() {
-        let mut g = Graph::default();
+        let mut g = GraphBuilder::new();
 
         // let mut a = 0;
-        let a = mk_addr_of_local(&mut g, 0_u32);
+        let a = g.addr_of_local(0_u32);
         // let b = &mut a;
-        let b1 = mk_copy(&mut g, a);
+        let b1 = g.copy(a);
         // *b = 0;
-        let b2 = mk_store_addr(&mut g, b1);
+        let b2 = g.store_addr(b1);
         // let c = &mut *b;
-        let c1 = mk_copy(&mut g, b1);
+        let c1 = g.copy(b1);
         // *c = 0;
-        let c2 = mk_store_addr(&mut g, c1);
+        let c2 = g.store_addr(c1);
         // *c = 0;
-        let b3 = mk_store_addr(&mut g, b1);
+        let b3 = g.store_addr(b1);
 
-        let pdg = build_pdg(g);
+        let pdg = build_pdg(g.build());
         assert!(info(&pdg, a).unique);
         assert!(info(&pdg, b1).unique);
         assert!(info(&pdg, b2).unique);
@@ -434,24 +676,24 @@ mod test {
     /// ```
     #[test]
     fn unique_sub_borrow_bad() {
-        let mut g = Graph::default();
+        let mut g = GraphBuilder::new();
 
         // let mut a = 0;
-        let a = mk_addr_of_local(&mut g, 0_u32);
+        let a = g.addr_of_local(0_u32);
         // let b = &mut a;
-        let b1 = mk_copy(&mut g, a);
+        let b1 = g.copy(a);
         // *b = 0;
-        let b2 = mk_store_addr(&mut g, b1);
+        let b2 = g.store_addr(b1);
         // let c = &mut *b;
-        let c1 = mk_copy(&mut g, b1);
+        let c1 = g.copy(b1);
         // *c = 0;
-        let c2 = mk_store_addr(&mut g, c1);
+        let c2 = g.store_addr(c1);
         // *b = 0;
-        let b3 = mk_store_addr(&mut g, b1);
+        let b3 = g.store_addr(b1);
         // *c = 0;
-        let c3 = mk_store_addr(&mut g, c1);
+        let c3 = g.store_addr(c1);
 
-        let pdg = build_pdg(g);
+        let pdg = build_pdg(g.build());
         assert!(info(&pdg, a).unique);
         assert!(!info(&pdg, b1).unique);
         assert!(!info(&pdg, b2).unique);
@@ -480,22 +722,22 @@ mod test {
     /// ```
     #[test]
     fn okay_use_different_fields() {
-        let mut g = Graph::default();
+        let mut g = GraphBuilder::new();
 
         // let mut a = Point { x: 0, y: 0 };
-        let a = mk_addr_of_local(&mut g, 0_u32);
+        let a = g.addr_of_local(0_u32);
         // let b = &mut a.x;
-        let b11 = mk_field(&mut g, a, 0_u32);
-        let b1 = mk_copy(&mut g, b11);
+        let b11 = g.field(a, 0_u32);
+        let b1 = g.copy(b11);
         // let c = &mut a.y;
-        let c11 = mk_field(&mut g, a, 1_u32);
-        let c1 = mk_copy(&mut g, c11);
+        let c11 = g.field(a, 1_u32);
+        let c1 = g.copy(c11);
         // *b = 1;
-        let b2 = mk_store_addr(&mut g, b1);
+        let b2 = g.store_addr(b1);
         // *c = 2;
-        let c2 = mk_store_addr(&mut g, c1);
+        let c2 = g.store_addr(c1);
 
-        let pdg = build_pdg(g);
+        let pdg = build_pdg(g.build());
         assert!(info(&pdg, a).unique);
         assert!(info(&pdg, b1).unique);
         assert!(info(&pdg, b2).unique);
@@ -528,27 +770,27 @@ mod test {
     /// ```
     #[test]
     fn same_fields_cousins() {
-        let mut g = Graph::default();
+        let mut g = GraphBuilder::new();
 
         // let mut a = Point { x: 0, y: 0 };
-        let a = mk_addr_of_local(&mut g, 0_u32);
+        let a = g.addr_of_local(0_u32);
         // let j = &mut a;
-        let j = mk_copy(&mut g, a);
+        let j = g.copy(a);
         // let b = &mut j.x;
-        let b11 = mk_field(&mut g, j, 0_u32);
-        let b1 = mk_copy(&mut g, b11);
+        let b11 = g.field(j, 0_u32);
+        let b1 = g.copy(b11);
         // let c = &mut j.x;
-        let c11 = mk_field(&mut g, j, 0_u32);
-        let c1 = mk_copy(&mut g, c11);
+        let c11 = g.field(j, 0_u32);
+        let c1 = g.copy(c11);
         // *b = 1;
-        let b2 = mk_store_addr(&mut g, b1);
+        let b2 = g.store_addr(b1);
         // *c = 2;
-        let c2 = mk_store_addr(&mut g, c1);
+        let c2 = g.store_addr(c1);
         // *(a.y) = 3;
-        let d1 = mk_field(&mut g, a, 1_u32);
-        let d2 = mk_store_addr(&mut g, d1);
+        let d1 = g.field(a, 1_u32);
+        let d2 = g.store_addr(d1);
 
-        let pdg = build_pdg(g);
+        let pdg = build_pdg(g.build());
         assert!(info(&pdg, a).unique);
         assert!(!info(&pdg, j).unique);
         assert!(!info(&pdg, b1).unique);
@@ -577,21 +819,21 @@ mod test {
     /// ```
     #[test]
     fn field_vs_raw() {
-        let mut g = Graph::default();
+        let mut g = GraphBuilder::new();
 
         // let mut a = Point { x: 0, y: 0 };
-        let a = mk_addr_of_local(&mut g, 0_u32);
+        let a = g.addr_of_local(0_u32);
         // let b = &mut a;
-        let b1 = mk_copy(&mut g, a);
+        let b1 = g.copy(a);
         // let c = &mut a.y;
-        let c11 = mk_field(&mut g, a, 1_u32);
-        let c1 = mk_copy(&mut g, c11);
+        let c11 = g.field(a, 1_u32);
+        let c1 = g.copy(c11);
         // *c = 2;
-        let c2 = mk_store_addr(&mut g, c1);
+        let c2 = g.store_addr(c1);
         // *b = 1;
-        let b2 = mk_store_addr(&mut g, b1);
+        let b2 = g.store_addr(b1);
 
-        let pdg = build_pdg(g);
+        let pdg = build_pdg(g.build());
         assert!(!info(&pdg, a).unique);
         assert!(!info(&pdg, b1).unique);
         assert!(!info(&pdg, b2).unique);
@@ -623,24 +865,24 @@ mod test {
     /// ```
     #[test]
     fn fields_different_levels() {
-        let mut g = Graph::default();
+        let mut g = GraphBuilder::new();
 
         // let mut a = Point { x: 0, y: 0 };
-        let a = mk_addr_of_local(&mut g, 0_u32);
+        let a = g.addr_of_local(0_u32);
         // let b = &mut a;
-        let b1 = mk_copy(&mut g, a);
+        let b1 = g.copy(a);
         // let c = &mut b.y;
-        let c1 = mk_field(&mut g, a, 1_u32);
-        let c2 = mk_copy(&mut g, c1);
+        let c1 = g.field(a, 1_u32);
+        let c2 = g.copy(c1);
         // let bb = &mut b.y;
-        let bb = mk_field(&mut g, b1, 1_u32);
-        let bb1 = mk_copy(&mut g, bb);
+        let bb = g.field(b1, 1_u32);
+        let bb1 = g.copy(bb);
         // *c = 2;
-        let c3 = mk_store_addr(&mut g, c2);
+        let c3 = g.store_addr(c2);
         // *bb = 1;
-        let b2 = mk_store_addr(&mut g, bb1);
+        let b2 = g.store_addr(bb1);
 
-        let pdg = build_pdg(g);
+        let pdg = build_pdg(g.build());
         assert!(!info(&pdg, a).unique);
         assert!(!info(&pdg, b1).unique);
         assert!(!info(&pdg, b2).unique);
@@ -667,48 +909,48 @@ mod test {
     /// ```
     #[test]
     fn lots_of_siblings() {
-        let mut g = Graph::default();
+        let mut g = GraphBuilder::new();
 
         let (x, y, z) = (0_u32, 1_u32, 2_u32);
         let (red, green, _blue) = (0_u32, 1_u32, 2_u32);
 
         // let mut a = ColorPoint { x: 0, y: 0, z: Color { r: 100, g: 100, b: 100 } };
-        let a = mk_addr_of_local(&mut g, 0_u32);
+        let a = g.addr_of_local(0_u32);
         // let b = &mut a.x;
-        let bb1 = mk_field(&mut g, a, x);
-        let b1 = mk_copy(&mut g, bb1);
+        let bb1 = g.field(a, x);
+        let b1 = g.copy(bb1);
         // let c = &mut a.y;
-        let cc1 = mk_field(&mut g, a, y);
-        let c1 = mk_copy(&mut g, cc1);
+        let cc1 = g.field(a, y);
+        let c1 = g.copy(cc1);
         // a.z.r = 200;
-        let x1 = mk_field(&mut g, a, z);
-        let x2 = mk_field(&mut g, x1, red);
-        let x3 = mk_store_addr(&mut g, x2);
+        let x1 = g.field(a, z);
+        let x2 = g.field(x1, red);
+        let x3 = g.store_addr(x2);
         // *b = 4;
-        let b2 = mk_store_addr(&mut g, b1);
+        let b2 = g.store_addr(b1);
         // *c = 2;
-        let c2 = mk_store_addr(&mut g, c1);
+        let c2 = g.store_addr(c1);
         // let d = &mut a;
-        let d1 = mk_copy(&mut g, a);
+        let d1 = g.copy(a);
         // *d = ColorPoint { x: 0, y: 0, z: Color { r: 20, g: 200, b: 20 } };
-        let d2 = mk_store_addr(&mut g, d1);
+        let d2 = g.store_addr(d1);
         // let e = &mut a.z;
-        let ee = mk_field(&mut g, a, z);
-        let e = mk_copy(&mut g, ee);
+        let ee = g.field(a, z);
+        let e = g.copy(ee);
         // let f = &mut e.g;
-        let ff1 = mk_field(&mut g, e, green);
-        let f1 = mk_copy(&mut g, ff1);
+        let ff1 = g.field(e, green);
+        let f1 = g.copy(ff1);
         // let g = &mut e.g;
-        let ggg = mk_field(&mut g, e, green);
-        let gg = mk_copy(&mut g, ggg);
+        let ggg = g.field(e, green);
+        let gg = g.copy(ggg);
         // *f = 3;
-        let f2 = mk_store_addr(&mut g, f1);
+        let f2 = g.store_addr(f1);
         // a.z.r = 100;
-        let x4 = mk_field(&mut g, a, z);
-        let x5 = mk_field(&mut g, x4, green);
-        let x6 = mk_store_addr(&mut g, x5);
+        let x4 = g.field(a, z);
+        let x5 = g.field(x4, green);
+        let x6 = g.store_addr(x5);
 
-        let pdg = build_pdg(g);
+        let pdg = build_pdg(g.build());
 
         assert!(info(&pdg, a).unique);
         assert!(info(&pdg, b1).unique);
@@ -753,26 +995,26 @@ mod test {
     /// ```
     #[test]
     fn field_no_conflict() {
-        let mut g = Graph::default();
+        let mut g = GraphBuilder::new();
 
         // let mut a = (1, (2, 3));
-        let a = mk_addr_of_local(&mut g, 0_u32);
+        let a = g.addr_of_local(0_u32);
         // let x = &mut a.0;
-        let x1 = mk_field(&mut g, a, 0_u32);
-        let x2 = mk_copy(&mut g, x1);
+        let x1 = g.field(a, 0_u32);
+        let x2 = g.copy(x1);
         // let y = &mut a.1;
-        let y1 = mk_field(&mut g, a, 1_u32);
-        let y2 = mk_copy(&mut g, y1);
+        let y1 = g.field(a, 1_u32);
+        let y2 = g.copy(y1);
         // *x = 1;
-        let x3 = mk_store_addr(&mut g, x2);
+        let x3 = g.store_addr(x2);
         // *y = 1;
-        let y3 = mk_store_addr(&mut g, y2);
+        let y3 = g.store_addr(y2);
         // *x = 2;
-        let x4 = mk_store_addr(&mut g, x3);
+        let x4 = g.store_addr(x3);
         // *y = 2;
-        let y4 = mk_store_addr(&mut g, y3);
+        let y4 = g.store_addr(y3);
 
-        let pdg = build_pdg(g);
+        let pdg = build_pdg(g.build());
 
         assert!(info(&pdg, a).unique);
         assert!(info(&pdg, x1).unique);
@@ -811,28 +1053,28 @@ mod test {
     /// ```
     #[test]
     fn nested_field_no_conflict() {
-        let mut g = Graph::default();
+        let mut g = GraphBuilder::new();
 
         // let mut a = (1, (2, 3));
-        let a = mk_addr_of_local(&mut g, 0_u32);
+        let a = g.addr_of_local(0_u32);
         // let x = &mut a.1.0;
-        let x1 = mk_field(&mut g, a, 1_u32);
-        let x2 = mk_field(&mut g, x1, 0_u32);
-        let x3 = mk_copy(&mut g, x2);
+        let x1 = g.field(a, 1_u32);
+        let x2 = g.field(x1, 0_u32);
+        let x3 = g.copy(x2);
         // let y = &mut a.1.1;
-        let y1 = mk_field(&mut g, a, 1_u32);
-        let y2 = mk_field(&mut g, y1, 1_u32);
-        let y3 = mk_copy(&mut g, y2);
+        let y1 = g.field(a, 1_u32);
+        let y2 = g.field(y1, 1_u32);
+        let y3 = g.copy(y2);
         // *x = 1;
-        let x4 = mk_store_addr(&mut g, x3);
+        let x4 = g.store_addr(x3);
         // *y = 1;
-        let y4 = mk_store_addr(&mut g, y3);
+        let y4 = g.store_addr(y3);
         // *x = 2;
-        let x5 = mk_store_addr(&mut g, x4);
+        let x5 = g.store_addr(x4);
         // *y = 2;
-        let y5 = mk_store_addr(&mut g, y4);
+        let y5 = g.store_addr(y4);
 
-        let pdg = build_pdg(g);
+        let pdg = build_pdg(g.build());
 
         assert!(info(&pdg, a).unique);
         assert!(info(&pdg, x1).unique);
@@ -865,24 +1107,24 @@ mod test {
     /// ```
     #[test]
     fn diff_field_conflict() {
-        let mut g = Graph::default();
+        let mut g = GraphBuilder::new();
 
         //let mut a = (1, (2, 3));
-        let a = mk_addr_of_local(&mut g, 0_u32);
+        let a = g.addr_of_local(0_u32);
         //let mut x = &mut a.1;
-        let x1 = mk_field(&mut g, a, 1_u32);
-        let x2 = mk_copy(&mut g, x1);
+        let x1 = g.field(a, 1_u32);
+        let x2 = g.copy(x1);
         //let mut y = &mut a.1;
-        let y1 = mk_field(&mut g, a, 1_u32);
-        let y2 = mk_copy(&mut g, y1);
+        let y1 = g.field(a, 1_u32);
+        let y2 = g.copy(y1);
         // *(x.0) = 4;
-        let x3 = mk_field(&mut g, x2, 0_u32);
-        let x4 = mk_store_addr(&mut g, x3);
+        let x3 = g.field(x2, 0_u32);
+        let x4 = g.store_addr(x3);
         // *(y.1) = 2;
-        let y3 = mk_field(&mut g, y2, 1_u32);
-        let y4 = mk_store_addr(&mut g, y3);
+        let y3 = g.field(y2, 1_u32);
+        let y4 = g.store_addr(y3);
 
-        let pdg = build_pdg(g);
+        let pdg = build_pdg(g.build());
 
         assert!(!info(&pdg, a).unique);
         assert!(!info(&pdg, x1).unique);
@@ -919,28 +1161,28 @@ mod test {
     /// ```
     #[test]
     fn nested_field_conflict() {
-        let mut g = Graph::default();
+        let mut g = GraphBuilder::new();
 
         // let mut a = (1, (2, 3));
-        let a = mk_addr_of_local(&mut g, 0_u32);
+        let a = g.addr_of_local(0_u32);
         // let x = &mut a.1.0;
-        let x1 = mk_field(&mut g, a, 1_u32);
-        let x2 = mk_field(&mut g, x1, 0_u32);
-        let x3 = mk_copy(&mut g, x2);
+        let x1 = g.field(a, 1_u32);
+        let x2 = g.field(x1, 0_u32);
+        let x3 = g.copy(x2);
         // let y = &mut a.1.0;
-        let y1 = mk_field(&mut g, a, 1_u32);
-        let y2 = mk_field(&mut g, y1, 0_u32);
-        let y3 = mk_copy(&mut g, y2);
+        let y1 = g.field(a, 1_u32);
+        let y2 = g.field(y1, 0_u32);
+        let y3 = g.copy(y2);
         // *x = 1;
-        let x4 = mk_store_addr(&mut g, x3);
+        let x4 = g.store_addr(x3);
         // *y = 1;
-        let y4 = mk_store_addr(&mut g, y3);
+        let y4 = g.store_addr(y3);
         // *x = 2;
-        let x5 = mk_store_addr(&mut g, x4);
+        let x5 = g.store_addr(x4);
         // *y = 2;
-        let y5 = mk_store_addr(&mut g, y4);
+        let y5 = g.store_addr(y4);
 
-        let pdg = build_pdg(g);
+        let pdg = build_pdg(g.build());
 
         assert!(!info(&pdg, a).unique);
         assert!(!info(&pdg, x1).unique);
@@ -981,28 +1223,28 @@ mod test {
     /// ```
     #[test]
     fn field_offset_conflict() {
-        let mut g = Graph::default();
+        let mut g = GraphBuilder::new();
 
         // let mut a = ([1, 2], [3, 4]);
-        let a = mk_addr_of_local(&mut g, 0_u32);
+        let a = g.addr_of_local(0_u32);
         // let x = &mut a.0[0];
-        let x1 = mk_field(&mut g, a, 1_u32);
-        let x2 = mk_offset(&mut g, x1, 0);
-        let x3 = mk_copy(&mut g, x2);
+        let x1 = g.field(a, 1_u32);
+        let x2 = g.offset(x1, 0);
+        let x3 = g.copy(x2);
         // let y = &mut a.0[1];
-        let y1 = mk_field(&mut g, a, 1_u32);
-        let y2 = mk_offset(&mut g, y1, 1);
-        let y3 = mk_copy(&mut g, y2);
+        let y1 = g.field(a, 1_u32);
+        let y2 = g.offset(y1, 1);
+        let y3 = g.copy(y2);
         // *x = 1;
-        let x4 = mk_store_addr(&mut g, x3);
+        let x4 = g.store_addr(x3);
         // *y = 1;
-        let y4 = mk_store_addr(&mut g, y3);
+        let y4 = g.store_addr(y3);
         // *x = 2;
-        let x5 = mk_store_addr(&mut g, x4);
+        let x5 = g.store_addr(x4);
         // *y = 2;
-        let y5 = mk_store_addr(&mut g, y4);
+        let y5 = g.store_addr(y4);
 
-        let pdg = build_pdg(g);
+        let pdg = build_pdg(g.build());
 
         assert!(!info(&pdg, a).unique);
         assert!(!info(&pdg, x1).unique);
@@ -1043,28 +1285,28 @@ mod test {
     /// ```
     #[test]
     fn field_offset_no_conflict() {
-        let mut g = Graph::default();
+        let mut g = GraphBuilder::new();
 
         // let mut a = ([1, 2], [3, 4]);
-        let a = mk_addr_of_local(&mut g, 0_u32);
+        let a = g.addr_of_local(0_u32);
         // let x = &mut a.0[0];
-        let x1 = mk_field(&mut g, a, 0_u32);
-        let x2 = mk_offset(&mut g, x1, 0);
-        let x3 = mk_copy(&mut g, x2);
+        let x1 = g.field(a, 0_u32);
+        let x2 = g.offset(x1, 0);
+        let x3 = g.copy(x2);
         // let y = &mut a.1[0];
-        let y1 = mk_field(&mut g, a, 1_u32);
-        let y2 = mk_offset(&mut g, y1, 0);
-        let y3 = mk_copy(&mut g, y2);
+        let y1 = g.field(a, 1_u32);
+        let y2 = g.offset(y1, 0);
+        let y3 = g.copy(y2);
         // *x = 1;
-        let x4 = mk_store_addr(&mut g, x3);
+        let x4 = g.store_addr(x3);
         // *y = 1;
-        let y4 = mk_store_addr(&mut g, y3);
+        let y4 = g.store_addr(y3);
         // *x = 2;
-        let x5 = mk_store_addr(&mut g, x4);
+        let x5 = g.store_addr(x4);
         // *y = 2;
-        let y5 = mk_store_addr(&mut g, y4);
+        let y5 = g.store_addr(y4);
 
-        let pdg = build_pdg(g);
+        let pdg = build_pdg(g.build());
 
         assert!(info(&pdg, a).unique);
         assert!(info(&pdg, x1).unique);
@@ -1113,29 +1355,29 @@ mod test {
     /// `rustc` would reject the modified code.
     #[test]
     fn offset_field_conflict() {
-        let mut g = Graph::default();
+        let mut g = GraphBuilder::new();
 
         // let mut a = ([1, 2], [3, 4]);
         // let p = &mut a;
-        let p = mk_addr_of_local(&mut g, 0_u32);
+        let p = g.addr_of_local(0_u32);
         // let x = &mut (*p)[0].0;
-        let x1 = mk_offset(&mut g, p, 0);
-        let x2 = mk_field(&mut g, x1, 0_u32);
-        let x3 = mk_copy(&mut g, x2);
+        let x1 = g.offset(p, 0);
+        let x2 = g.field(x1, 0_u32);
+        let x3 = g.copy(x2);
         // let y = &mut (*p)[0].1;
-        let y1 = mk_offset(&mut g, p, 0);
-        let y2 = mk_field(&mut g, y1, 1_u32);
-        let y3 = mk_copy(&mut g, y2);
+        let y1 = g.offset(p, 0);
+        let y2 = g.field(y1, 1_u32);
+        let y3 = g.copy(y2);
         // *x = 1;
-        let x4 = mk_store_addr(&mut g, x3);
+        let x4 = g.store_addr(x3);
         // *y = 1;
-        let y4 = mk_store_addr(&mut g, y3);
+        let y4 = g.store_addr(y3);
         // *x = 2;
-        let x5 = mk_store_addr(&mut g, x4);
+        let x5 = g.store_addr(x4);
         // *y = 2;
-        let y5 = mk_store_addr(&mut g, y4);
+        let y5 = g.store_addr(y4);
 
-        let pdg = build_pdg(g);
+        let pdg = build_pdg(g.build());
 
         assert!(!info(&pdg, p).unique);
         assert!(!info(&pdg, x1).unique);
@@ -1149,4 +1391,400 @@ mod test {
         assert!(!info(&pdg, y4).unique);
         assert!(!info(&pdg, y5).unique);
     }
+
+    /// ```rust
+    /// let mut a = [(1, 2), (3, 4)];
+    /// let p = &mut a[1];
+    /// let x = &mut *p;
+    /// let y = &mut *p;
+    /// *x = (5, 6);
+    /// *y = (7, 8);
+    /// *x = (9, 10);
+    /// ```
+    ///
+    /// ```text
+    /// A
+    /// P
+    /// +------.
+    /// X1     |
+    /// |      Y1
+    /// X2     |
+    /// |      Y2
+    /// X3     |
+    /// ```
+    ///
+    /// `x` and `y` alias through the offset, so `a` isn't unique either.
+    #[test]
+    fn offset_copies_conflict() {
+        let mut g = GraphBuilder::new();
+
+        // let mut a = [(1, 2), (3, 4)];
+        let a = g.addr_of_local(0_u32);
+        // let p = &mut a[1];
+        let p = g.offset(a, 1);
+        // let x = &mut *p;
+        let x1 = g.copy(p);
+        // let y = &mut *p;
+        let y1 = g.copy(p);
+        // *x = (5, 6);
+        let x2 = g.store_addr(x1);
+        // *y = (7, 8);
+        let y2 = g.store_addr(y1);
+        // *x = (9, 10);
+        let x3 = g.store_addr(x2);
+
+        let pdg = build_pdg(g.build());
+
+        assert!(!info(&pdg, a).unique);
+        assert!(!info(&pdg, p).unique);
+        assert!(!info(&pdg, x1).unique);
+        assert!(!info(&pdg, x2).unique);
+        assert!(!info(&pdg, x3).unique);
+        assert!(!info(&pdg, y1).unique);
+        assert!(!info(&pdg, y2).unique);
+    }
+
+    /// ```rust
+    /// struct Line { start: [Point; 2], end: [Point; 2] }
+    /// let mut a = Line { .. };
+    /// let x = &mut a.start[0].x;
+    /// let y = &mut a.end[1].x;
+    /// *x = 1;
+    /// *y = 1;
+    /// *x = 2;
+    /// *y = 2;
+    /// ```
+    ///
+    /// ```text
+    /// A
+    /// +------.
+    /// X1     |
+    /// X2     |
+    /// X3     |
+    /// X4     |
+    /// |      Y1
+    /// |      Y2
+    /// |      Y3
+    /// |      Y4
+    /// X5     |
+    /// |      Y5
+    /// X6     |
+    ///        Y6
+    /// ```
+    #[test]
+    fn field_offset_field_no_conflict() {
+        let mut g = GraphBuilder::new();
+
+        // let mut a = Line { .. };
+        let a = g.addr_of_local(0_u32);
+        // let x = &mut a.start[0].x;
+        let x1 = g.field(a, 0_u32);
+        let x2 = g.offset(x1, 0);
+        let x3 = g.field(x2, 0_u32);
+        let x4 = g.copy(x3);
+        // let y = &mut a.end[1].x;
+        let y1 = g.field(a, 1_u32);
+        let y2 = g.offset(y1, 1);
+        let y3 = g.field(y2, 0_u32);
+        let y4 = g.copy(y3);
+        // *x = 1;
+        let x5 = g.store_addr(x4);
+        // *y = 1;
+        let y5 = g.store_addr(y4);
+        // *x = 2;
+        let x6 = g.store_addr(x5);
+        // *y = 2;
+        let y6 = g.store_addr(y5);
+
+        let pdg = build_pdg(g.build());
+
+        for id in [a, x1, x2, x3, x4, x5, x6, y1, y2, y3, y4, y5, y6] {
+            assert!(info(&pdg, id).unique);
+        }
+    }
+
+    /// ```rust
+    /// let mut a = 0;        // A
+    /// let b = &mut a;       // B1
+    /// *b = 0;               // B2
+    /// // On another thread:
+    /// let c = &mut a;       // C1
+    /// *c = 0;               // C2
+    /// // On the first thread:
+    /// *b = 0;               // B3
+    /// ```
+    #[test]
+    fn non_unique_cross_thread() {
+        let mut g = GraphBuilder::new();
+
+        let a = g.addr_of_local(0_u32);
+        let b1 = g.copy(a);
+        let b2 = g.store_addr(b1);
+        let c1 = g.copy(a);
+        let c2 = g.store_addr(c1);
+        let b3 = g.store_addr(b1);
+        g.node_mut(c1).thread = 1;
+        g.node_mut(c2).thread = 1;
+
+        let pdg = build_pdg(g.build());
+        for id in [a, b1, b2, c1, c2, b3] {
+            assert!(!info(&pdg, id).unique);
+            assert!(info(&pdg, id).non_unique_cross_thread);
+        }
+    }
+
+    /// ```rust
+    /// let mut a = 0;        // A
+    /// let b = &mut a;       // B1
+    /// *b = 0;               // B2
+    /// let c = &mut a;       // C1
+    /// // On another thread:
+    /// *c = 0;               // C2
+    /// ```
+    #[test]
+    fn unique_cross_thread() {
+        let mut g = GraphBuilder::new();
+
+        let a = g.addr_of_local(0_u32);
+        let b1 = g.copy(a);
+        let b2 = g.store_addr(b1);
+        let c1 = g.copy(a);
+        let c2 = g.store_addr(c1);
+        g.node_mut(c2).thread = 1;
+
+        let pdg = build_pdg(g.build());
+        for id in [a, b1, b2, c1, c2] {
+            assert!(info(&pdg, id).unique);
+            assert!(!info(&pdg, id).non_unique_cross_thread);
+        }
+    }
+
+    /// ```rust
+    /// let mut a = 0;
+    /// let b = &mut a;
+    /// let c = &mut (*b).0;
+    /// extern_fn(c);
+    /// let d = &mut a;
+    /// *d = 0;
+    /// ```
+    #[test]
+    fn flows_to_extern() {
+        let mut g = GraphBuilder::new();
+
+        // let mut a = 0;
+        let a = g.addr_of_local(0_u32);
+        // let b = &mut a;
+        let b = g.copy(a);
+        // let c = &mut (*b).0;
+        let c = g.field(b, 0_u32);
+        // extern_fn(c);
+        let arg = g.copy(c);
+        g.node_mut(arg).extern_arg = true;
+        // let d = &mut a;
+        let d = g.copy(a);
+        // *d = 0;
+        let d2 = g.store_addr(d);
+
+        let pdg = build_pdg(g.build());
+        assert_eq!(info(&pdg, a).flows_to_extern, Some(arg));
+        assert_eq!(info(&pdg, b).flows_to_extern, Some(arg));
+        assert_eq!(info(&pdg, c).flows_to_extern, Some(arg));
+        assert_eq!(info(&pdg, arg).flows_to_extern, Some(arg));
+        assert_eq!(info(&pdg, d).flows_to_extern, None);
+        assert_eq!(info(&pdg, d2).flows_to_extern, None);
+    }
+
+    /// ```rust
+    /// let p = malloc(4);
+    /// let q = p;
+    /// *q = 1;
+    /// let r = p;
+    /// free(r);
+    /// ```
+    #[test]
+    fn flows_to_free() {
+        let mut g = GraphBuilder::new();
+
+        // let p = malloc(4);
+        let p = g.alloc(1);
+        // let q = p;
+        let q = g.copy(p);
+        // *q = 1;
+        let q2 = g.store_addr(q);
+        // let r = p;
+        let r = g.copy(p);
+        // free(r);
+        let free = g.free(r);
+
+        let pdg = build_pdg(g.build());
+        assert_eq!(info(&pdg, p).flows_to.free, Some(free));
+        assert_eq!(info(&pdg, r).flows_to.free, Some(free));
+        assert_eq!(info(&pdg, free).flows_to.free, Some(free));
+        assert_eq!(info(&pdg, q).flows_to.free, None);
+        assert_eq!(info(&pdg, q2).flows_to.free, None);
+    }
+
+    /// ```rust
+    /// let mut a = 0;
+    /// let b = &mut a;
+    /// *b = 0;
+    /// let c = &a;
+    /// let d = &mut (*b).0;
+    /// *d = 0;
+    /// ```
+    #[test]
+    fn last_use() {
+        let mut g = GraphBuilder::new();
+
+        // let mut a = 0;
+        let a = g.addr_of_local(0_u32);
+        // let b = &mut a;
+        let b1 = g.copy(a);
+        // *b = 0;
+        let b2 = g.store_addr(b1);
+        // let c = &a;
+        let c = g.copy(a);
+        // let d = &mut (*b).0;
+        let d1 = g.field(b1, 0_u32);
+        let d2 = g.copy(d1);
+        // *d = 0;
+        let d3 = g.store_addr(d2);
+
+        let pdg = build_pdg(g.build());
+        assert_eq!(info(&pdg, a).last_use, d3);
+        assert_eq!(info(&pdg, b1).last_use, d3);
+        assert_eq!(info(&pdg, b2).last_use, b2);
+        assert_eq!(info(&pdg, c).last_use, c);
+        assert_eq!(info(&pdg, d1).last_use, d3);
+    }
+
+    /// ```rust
+    /// let p = malloc(4);
+    /// let q = p;
+    /// let t = p;
+    /// *q = 1;
+    /// *t = 1;
+    /// *q = 1;
+    /// let r = realloc(p, 8);
+    /// let s = r;
+    /// *s = 2;
+    /// *r;
+    /// free(r);
+    /// ```
+    #[test]
+    fn realloc() {
+        let mut g = GraphBuilder::new();
+
+        // let p = malloc(4);
+        let p = g.alloc(1);
+        // let q = p;
+        let q = g.copy(p);
+        // let t = p;
+        let t = g.copy(p);
+        // *q = 1;
+        g.store_addr(q);
+        // *t = 1;
+        g.store_addr(t);
+        // *q = 1;
+        g.store_addr(q);
+        // let r = realloc(p, 8);
+        let r = g.realloc(p);
+        // let s = r;
+        let s = g.copy(r);
+        // *s = 2;
+        g.store_addr(s);
+        // *r;
+        let load = g.load_addr(r);
+        // free(r);
+        let free = g.free(r);
+
+        let pdg = build_pdg(g.build());
+        assert!(!info(&pdg, p).unique);
+        assert!(info(&pdg, r).unique);
+        assert!(info(&pdg, s).unique);
+        assert_eq!(info(&pdg, p).flows_to.load, None);
+        assert_eq!(info(&pdg, r).flows_to.load, Some(load));
+        assert_eq!(info(&pdg, p).flows_to.free, Some(r));
+        assert_eq!(info(&pdg, r).flows_to.free, Some(free));
+        assert_eq!(info(&pdg, p).last_use, r);
+        assert_eq!(info(&pdg, r).last_use, free);
+    }
+
+    /// ```rust
+    /// let p = malloc(8);
+    /// let q = p.offset(1);
+    /// let r = p.offset(-1);
+    /// *q;
+    /// free(p);
+    /// ```
+    #[test]
+    fn display_and_record() {
+        let mut g = GraphBuilder::new();
+        let p = g.alloc(2);
+        let q = g.offset(p, 1);
+        let r = g.offset(p, -1);
+        let load = g.load_addr(q);
+        let free = g.free(p);
+
+        let pdg = build_pdg(g.build());
+        assert_eq!(
+            info(&pdg, p).to_string(),
+            "unique; flows to load n[3], +offset n[1], -offset n[2], free n[4]; last use n[4]"
+        );
+        assert_eq!(
+            info(&pdg, r).to_string(),
+            "unique; flows to -offset n[2]; last use n[2]"
+        );
+        assert_eq!(
+            info(&pdg, free).to_string(),
+            "unique; flows to free n[4]; last use n[4]"
+        );
+
+        let record = info(&pdg, q).to_record();
+        assert_eq!(
+            record.flows_to,
+            vec![
+                FlowRecord {
+                    kind: FlowKind::Load,
+                    node: load
+                },
+                FlowRecord {
+                    kind: FlowKind::PosOffset,
+                    node: q
+                },
+            ]
+        );
+        assert_eq!(record.last_use, load);
+        for id in [p, q, r, load, free] {
+            let info = info(&pdg, id);
+            assert_eq!(&NodeInfo::from_record(info.to_record()), info);
+        }
+    }
+
+    /// Times [`add_info`] on a graph of three million [`Node`]s: chains of copies of one pointer,
+    /// each with a field projection that is stored through.  Run it with
+    /// `cargo test --release -- --ignored --nocapture add_info_benchmark`.
+    #[test]
+    #[ignore]
+    fn add_info_benchmark() {
+        let mut g = GraphBuilder::new();
+        let root = g.alloc(1);
+        let mut prev = root;
+        for i in 0..1_000_000_u32 {
+            if i % 16 == 0 {
+                prev = root;
+            }
+            let copy = g.copy(prev);
+            let field = g.field(copy, i % 4);
+            g.store_addr(field);
+            prev = copy;
+        }
+        let g = g.build();
+        assert_eq!(g.nodes.len(), 3_000_001);
+
+        let start = std::time::Instant::now();
+        let pdg = build_pdg(g);
+        eprintln!("add_info on 3000001 nodes: {:?}", start.elapsed());
+        assert!(!info(&pdg, root).unique);
+    }
 }
@@ -0,0 +1,215 @@
+//! JSON export and import of [`Graphs`], for `--json` and `--from-json`.
+//!
+//! The file is an object with two fields:
+//!
+//! * `schema_version`, which is [`SCHEMA_VERSION`].  It is incremented whenever the format of any
+//!   of the types below changes, and a file with a different version is rejected on import.
+//! * `graphs`, which is [`Graphs`] as serialized by `serde`: `{"graphs": [graph, ...]}`, where each
+//!   [`Graph`] is `{"nodes": [node, ...]}` and each [`Node`] is an object with the same fields as
+//!   the struct.
+//!
+//! rustc's index types ([`NodeId`], [`BasicBlock`], [`Field`], and [`Local`]) are written as plain
+//! integers, and an [`IndexVec`] as an array, so the file can be read without linking against
+//! rustc.  [`Node::source`] and the [`NodeId`]s in [`Node::info`] are indices into the `nodes` of
+//! the same [`Graph`].  [`Node::info`] is written as its
+//! [`NodeInfoRecord`](crate::info::NodeInfoRecord), which lists only the kinds of [`Node`]s it
+//! flows to, like `{"flows_to": [{"kind": "load", "node": 3}], "unique": true, ...}`.
+//! [`NodeKind`] uses `serde`'s default externally tagged representation, so a copy is `"Copy"` and
+//! a field projection is `{"Field": 2}`.  [`Func`](c2rust_analysis_rt::mir_loc::Func) and
+//! [`MirPlace`](c2rust_analysis_rt::mir_loc::MirPlace) are written as in the metadata file, and a
+//! [`SourceSpan`](c2rust_analysis_rt::mir_loc::SourceSpan) is an object with the fields `file`,
+//! `line`, and `col`.
+//!
+//! [`Graphs::latest_assignment`] is only used while building the graphs and isn't exported, so
+//! it's empty after an import.
+//!
+//! [`Node`]: crate::graph::Node
+//! [`Node::source`]: crate::graph::Node::source
+//! [`Node::info`]: crate::graph::Node::info
+//! [`NodeKind`]: crate::graph::NodeKind
+//! [`BasicBlock`]: rustc_middle::mir::BasicBlock
+//! [`Field`]: rustc_middle::mir::Field
+//! [`Local`]: rustc_middle::mir::Local
+
+use crate::graph::{Graph, Graphs, NodeId};
+use color_eyre::eyre::{self, ensure, eyre};
+use fs_err::File;
+use rustc_index::vec::IndexVec;
+use serde::{Deserialize, Serialize};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// Force an import of [`NodeId`] just for docs.
+const _: Option<NodeId> = None;
+
+/// The version of the format written by [`write_json`].
+pub const SCHEMA_VERSION: u64 = 9;
+
+#[derive(Serialize, Deserialize)]
+struct Document<G> {
+    schema_version: u64,
+    graphs: G,
+}
+
+/// Write `graphs` to `path` as JSON.
+pub fn write_json(graphs: &Graphs, path: &Path) -> eyre::Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    let doc = Document {
+        schema_version: SCHEMA_VERSION,
+        graphs,
+    };
+    serde_json::to_writer(writer, &doc)?;
+    Ok(())
+}
+
+/// Read [`Graphs`] written by [`write_json`] from `path`.
+pub fn read_json(path: &Path) -> eyre::Result<Graphs> {
+    let doc: serde_json::Value = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    let version = doc.get("schema_version").and_then(|v| v.as_u64());
+    ensure!(
+        version == Some(SCHEMA_VERSION),
+        eyre!(
+            "{}: unsupported PDG schema version {version:?}, expected {SCHEMA_VERSION}",
+            path.display()
+        )
+    );
+    let doc: Document<Graphs> = serde_json::from_value(doc)?;
+    Ok(doc.graphs)
+}
+
+/// Writes [`Graph`]s to a file one at a time, in the same format as [`write_json`], so they don't
+/// all have to be in memory at once.
+pub struct JsonGraphWriter {
+    writer: BufWriter<File>,
+    empty: bool,
+}
+
+impl JsonGraphWriter {
+    pub fn create(path: &Path) -> eyre::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write!(
+            writer,
+            r#"{{"schema_version":{SCHEMA_VERSION},"graphs":{{"graphs":["#
+        )?;
+        Ok(Self {
+            writer,
+            empty: true,
+        })
+    }
+
+    pub fn write(&mut self, graph: &Graph) -> eyre::Result<()> {
+        if !self.empty {
+            write!(self.writer, ",")?;
+        }
+        self.empty = false;
+        serde_json::to_writer(&mut self.writer, graph)?;
+        Ok(())
+    }
+
+    /// Finish the file.  It's incomplete if this isn't called.
+    pub fn finish(mut self) -> eyre::Result<()> {
+        write!(self.writer, "]}}}}")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// (De)serialize an [`Idx`](rustc_index::vec::Idx) as an integer, with
+/// `#[serde(with = "crate::json::idx")]`.
+pub mod idx {
+    use rustc_index::vec::Idx;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<I: Idx, S: Serializer>(idx: &I, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_u64(idx.index() as u64)
+    }
+
+    pub fn deserialize<'de, I: Idx, D: Deserializer<'de>>(d: D) -> Result<I, D::Error> {
+        usize::deserialize(d).map(I::new)
+    }
+}
+
+/// (De)serialize an `Option` of an [`Idx`](rustc_index::vec::Idx) as an integer or `null`, with
+/// `#[serde(with = "crate::json::opt_idx")]`.
+pub mod opt_idx {
+    use rustc_index::vec::Idx;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<I: Idx, S: Serializer>(idx: &Option<I>, s: S) -> Result<S::Ok, S::Error> {
+        idx.map(|idx| idx.index()).serialize(s)
+    }
+
+    pub fn deserialize<'de, I: Idx, D: Deserializer<'de>>(d: D) -> Result<Option<I>, D::Error> {
+        Option::<usize>::deserialize(d).map(|idx| idx.map(I::new))
+    }
+}
+
+/// (De)serialize an [`IndexVec`] as an array, with `#[serde(with = "crate::json::index_vec")]`.
+pub mod index_vec {
+    use super::IndexVec;
+    use rustc_index::vec::Idx;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<I: Idx, T: Serialize, S: Serializer>(
+        vec: &IndexVec<I, T>,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        vec.raw.serialize(s)
+    }
+
+    pub fn deserialize<'de, I: Idx, T: Deserialize<'de>, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<IndexVec<I, T>, D::Error> {
+        Vec::deserialize(d).map(IndexVec::from_raw)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::{Node, NodeKind};
+    use crate::info::add_info;
+    use c2rust_analysis_rt::mir_loc::{Func, FuncId};
+
+    fn mk_node(g: &mut Graph, kind: NodeKind, source: Option<NodeId>) -> NodeId {
+        g.nodes.push(Node {
+            function: Func {
+                id: FuncId((1, 2).into()),
+                name: "fake_function".into(),
+            },
+            block: 3_u32.into(),
+            statement_idx: 4,
+            dest: None,
+            kind,
+            source,
+            info: None,
+            debug_info: "".into(),
+            size: None,
+            byte_offset: None,
+            extern_arg: false,
+            thread: 0,
+            span: None,
+        })
+    }
+
+    #[test]
+    fn round_trip() -> eyre::Result<()> {
+        let mut g = Graph::new();
+        let a = mk_node(&mut g, NodeKind::AddrOfLocal(1_u32.into()), None);
+        let b = mk_node(&mut g, NodeKind::Field(2_u32.into()), Some(a));
+        let c = mk_node(&mut g, NodeKind::Offset(-1), Some(b));
+        mk_node(&mut g, NodeKind::LoadAddr, Some(c));
+        let mut graphs = Graphs::new();
+        graphs.graphs.push(g);
+        add_info(&mut graphs);
+
+        let json = serde_json::to_string(&Document {
+            schema_version: SCHEMA_VERSION,
+            graphs: &graphs,
+        })?;
+        let doc: Document<Graphs> = serde_json::from_str(&json)?;
+        assert_eq!(doc.schema_version, SCHEMA_VERSION);
+        assert_eq!(doc.graphs, graphs);
+        Ok(())
+    }
+}
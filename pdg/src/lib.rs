@@ -0,0 +1,39 @@
+//! The Pointer Derivation Graph (PDG) of a run of an instrumented program: the [`graph`]s that
+//! [`builder`] constructs from its event log, and the analyses and output formats built on them,
+//! as used by the `c2rust-pdg` command.
+#![feature(min_specialization)]
+#![feature(rustc_private)]
+#![feature(map_try_insert)]
+
+extern crate rustc_ast;
+extern crate rustc_const_eval;
+extern crate rustc_data_structures;
+extern crate rustc_driver;
+extern crate rustc_hir;
+extern crate rustc_index;
+extern crate rustc_interface;
+extern crate rustc_middle;
+extern crate rustc_mir_build;
+extern crate rustc_mir_transform;
+extern crate rustc_serialize;
+extern crate rustc_session;
+extern crate rustc_span;
+extern crate rustc_target;
+
+mod assert;
+pub mod builder;
+mod canonical;
+mod compact;
+pub mod coverage;
+pub mod dot;
+pub mod facts;
+pub mod graph;
+pub mod info;
+pub mod json;
+pub mod merge;
+pub mod permissions;
+pub mod query;
+pub mod races;
+mod safety;
+mod util;
+mod validate;
@@ -1,43 +1,20 @@
-#![feature(min_specialization)]
 #![feature(rustc_private)]
-#![feature(map_try_insert)]
-
-extern crate rustc_ast;
-extern crate rustc_const_eval;
-extern crate rustc_data_structures;
-extern crate rustc_driver;
-extern crate rustc_hir;
-extern crate rustc_index;
-extern crate rustc_interface;
-extern crate rustc_middle;
-extern crate rustc_mir_build;
-extern crate rustc_mir_transform;
-extern crate rustc_serialize;
-extern crate rustc_session;
-extern crate rustc_span;
-extern crate rustc_target;
-
-mod assert;
-mod builder;
-mod graph;
-mod info;
-mod query;
-mod util;
-
-use builder::{construct_pdg, read_event_log};
+
 use c2rust_analysis_rt::{events::Event, metadata::Metadata};
-use clap::{Parser, ValueEnum};
-use color_eyre::eyre;
-use graph::Graphs;
-use info::add_info;
+use c2rust_pdg::builder::{
+    construct_pdg, construct_pdg_streaming, read_event_log, read_metadata, stream_event_log,
+};
+use c2rust_pdg::graph::Graphs;
+use c2rust_pdg::info::add_info;
+use c2rust_pdg::{coverage, dot, facts, json, merge, permissions, query, races};
+use clap::{Parser, Subcommand, ValueEnum};
+use color_eyre::eyre::{self, eyre};
 use std::{
     fmt::{self, Display, Formatter},
     path::{Path, PathBuf},
     sync::Once,
 };
 
-use crate::builder::read_metadata;
-
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
 pub enum ToPrint {
     Graphs,
@@ -46,6 +23,9 @@ pub enum ToPrint {
     LatestAssignments,
     WritePermissions,
     Metadata,
+    MemoryErrors,
+    DataRaces,
+    Coverage,
 }
 
 impl Display for ToPrint {
@@ -60,13 +40,53 @@ pub struct Pdg {
     pub graphs: Graphs,
 }
 
+/// How to build and read [`Graphs`], from the command-line flags.
+#[derive(Debug, Clone, Copy)]
+pub struct BuildOptions {
+    /// Check the graphs with [`Graphs::validate`].
+    pub validate: bool,
+    /// Collapse chains of copies in graphs built from event logs with [`Graphs::compact`].
+    pub compact: bool,
+}
+
+/// Build the graphs of one run from its events, and compute their [`NodeInfo`](c2rust_pdg::info::NodeInfo).
+fn build_graphs(
+    events: &[Event],
+    metadata: &Metadata,
+    options: BuildOptions,
+) -> eyre::Result<Graphs> {
+    let mut graphs = construct_pdg(events, metadata);
+    if options.validate {
+        graphs.validate()?;
+    }
+    if options.compact {
+        let removed = graphs.compact();
+        log::debug!("compaction removed {removed} copies");
+    }
+    add_info(&mut graphs);
+    graphs.remove_addr_of_local_sources();
+    Ok(graphs)
+}
+
+/// Read the graphs from a file written by `--json`.  They were already compacted, if they were
+/// going to be, when they were built.
+fn read_graphs(path: &Path, options: BuildOptions) -> eyre::Result<Graphs> {
+    let graphs = json::read_json(path)?;
+    if options.validate {
+        graphs.validate()?;
+    }
+    Ok(graphs)
+}
+
 impl Pdg {
-    pub fn new(metadata_path: &Path, event_log_path: &Path) -> eyre::Result<Self> {
+    pub fn new(
+        metadata_path: &Path,
+        event_log_path: &Path,
+        options: BuildOptions,
+    ) -> eyre::Result<Self> {
         let events = read_event_log(event_log_path)?;
         let metadata = read_metadata(metadata_path)?;
-        let mut graphs = construct_pdg(&events, &metadata);
-        add_info(&mut graphs);
-        graphs.remove_addr_of_local_sources();
+        let graphs = build_graphs(&events, &metadata, options)?;
         Ok(Self {
             events,
             metadata,
@@ -74,6 +94,47 @@ impl Pdg {
         })
     }
 
+    /// Read the graphs from a file written by `--json` instead of building them from an event log.
+    /// There are no [`Event`]s in this case.
+    pub fn from_json(
+        metadata_path: &Path,
+        json_path: &Path,
+        options: BuildOptions,
+    ) -> eyre::Result<Self> {
+        Ok(Self {
+            events: Vec::new(),
+            metadata: read_metadata(metadata_path)?,
+            graphs: read_graphs(json_path, options)?,
+        })
+    }
+
+    /// Merge the graphs of several runs of the program, from event logs and from files written
+    /// by `--json`, as described in [`merge`](merge).  The [`Event`]s of all the event
+    /// logs are concatenated.
+    pub fn merge(
+        metadata_path: &Path,
+        event_log_paths: &[PathBuf],
+        json_paths: &[PathBuf],
+        options: BuildOptions,
+    ) -> eyre::Result<Self> {
+        let metadata = read_metadata(metadata_path)?;
+        let mut events = Vec::new();
+        let mut runs = Vec::new();
+        for path in event_log_paths {
+            let run_events = read_event_log(path)?;
+            runs.push(build_graphs(&run_events, &metadata, options)?);
+            events.extend(run_events);
+        }
+        for path in json_paths {
+            runs.push(read_graphs(path, options)?);
+        }
+        Ok(Self {
+            events,
+            metadata,
+            graphs: merge::merge(runs),
+        })
+    }
+
     pub fn repr<'a>(&'a self, to_print: &'a [ToPrint]) -> PdgRepr<'a> {
         PdgRepr {
             pdg: self,
@@ -135,6 +196,22 @@ impl Display for PdgRepr<'_> {
             }
         }
 
+        if should_print(ToPrint::MemoryErrors) {
+            for error in graphs.memory_errors() {
+                writeln!(f, "{error}")?;
+            }
+        }
+
+        if should_print(ToPrint::DataRaces) {
+            for race in races::find_races(events, metadata) {
+                writeln!(f, "{race}")?;
+            }
+        }
+
+        if should_print(ToPrint::Coverage) {
+            write!(f, "{}", coverage::Coverage::new(events, metadata))?;
+        }
+
         if should_print(ToPrint::Counts) {
             let num_graphs = graphs.graphs.len();
             let num_nodes = graphs
@@ -155,8 +232,15 @@ impl Display for PdgRepr<'_> {
 #[clap(author, version, about, long_about = None)]
 pub struct Args {
     /// Path to an event log from a run of an instrumented program.
+    ///
+    /// This may be given more than once, along with `--from-json`, to merge the graphs of several
+    /// runs of the same program.
+    #[clap(long, value_parser, required_unless_present = "from_json")]
+    event_log: Vec<PathBuf>,
+
+    /// Read the graphs from a file written by `--json` instead of building them from an event log.
     #[clap(long, value_parser)]
-    event_log: PathBuf,
+    from_json: Vec<PathBuf>,
 
     /// Path to the instrumented program's metadata generated at compile/instrumentation time.
     #[clap(long, value_parser)]
@@ -165,6 +249,69 @@ pub struct Args {
     /// What to print.
     #[clap(long, value_parser, default_value = "graphs")]
     print: Vec<ToPrint>,
+
+    /// Write the per-local facts read by `c2rust-analyze --pdg` to this file.
+    #[clap(long, value_parser)]
+    facts: Option<PathBuf>,
+
+    /// Write the `c2rust-analyze` permissions of each local at each statement to this file (see
+    /// the `permissions` module).
+    #[clap(long, value_parser)]
+    permissions: Option<PathBuf>,
+
+    /// Write the graphs to this file in Graphviz DOT format.
+    #[clap(long, value_parser)]
+    dot: Option<PathBuf>,
+
+    /// Write the graphs to this file as JSON (see the `json` module for the format).
+    #[clap(long, value_parser)]
+    json: Option<PathBuf>,
+
+    /// Build the graphs with at most about this many nodes in memory, writing each graph to the
+    /// `--json` file when it's finished, and do nothing else with them.
+    ///
+    /// Graphs are written out early when there are too many nodes, starting with those whose
+    /// object was freed, and a later use of such an object starts a new graph.
+    #[clap(long, value_parser, requires = "json")]
+    memory_budget: Option<usize>,
+
+    /// Check that the graphs are well-formed before using them, and fail with a description of
+    /// what's wrong if they aren't, as from a corrupt or truncated event log.
+    #[clap(long, conflicts_with = "memory_budget")]
+    validate: bool,
+
+    /// Keep every copy in the graphs built from event logs, instead of collapsing chains of copies
+    /// (see the `compact` module), which also removes them from the `--facts`.
+    #[clap(long)]
+    no_compact: bool,
+
+    /// Number the graphs and nodes so they're the same in every run of a deterministic program,
+    /// however its threads interleave (see the `canonical` module).  The nodes of each thread stay
+    /// in order, but the `--print memory-errors` between threads are then found in the new order.
+    #[clap(long, conflicts_with = "memory_budget")]
+    canonical: bool,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print the backward slice (the ancestors) or forward slice (the descendants) of a node,
+    /// instead of what `--print` selects.
+    Query {
+        /// The node, as `g[GRAPH]n[NODE]`.
+        #[clap(long, value_parser, required_unless_present = "at")]
+        node: Option<String>,
+
+        /// Slice every node at a MIR location, `FUNCTION:bbBLOCK[STATEMENT]` or `FUNCTION:bbBLOCK`.
+        #[clap(long, value_parser, conflicts_with = "node")]
+        at: Option<String>,
+
+        /// Which slice to print.
+        #[clap(long, value_enum, default_value = "backward")]
+        direction: query::Direction,
+    },
 }
 
 static INIT: Once = Once::new();
@@ -187,8 +334,61 @@ pub fn init() {
 fn main() -> eyre::Result<()> {
     init();
     let args = Args::parse();
-    let pdg = Pdg::new(&args.metadata, &args.event_log)?;
+    if let Some(budget) = args.memory_budget {
+        let event_log = match &args.event_log[..] {
+            [event_log] => event_log,
+            _ => return Err(eyre!("`--memory-budget` needs exactly one `--event-log`")),
+        };
+        let metadata = read_metadata(&args.metadata)?;
+        let json_path = args.json.as_deref().expect("required by `--memory-budget`");
+        let mut writer = json::JsonGraphWriter::create(json_path)?;
+        let events = stream_event_log(event_log)?;
+        construct_pdg_streaming(events, &metadata, budget, !args.no_compact, |graph| {
+            writer.write(&graph)
+        })?;
+        writer.finish()?;
+        return Ok(());
+    }
+    let options = BuildOptions {
+        validate: args.validate,
+        compact: !args.no_compact,
+    };
+    let mut pdg = match (&args.event_log[..], &args.from_json[..]) {
+        ([event_log], []) => Pdg::new(&args.metadata, event_log, options)?,
+        ([], [json]) => Pdg::from_json(&args.metadata, json, options)?,
+        (event_logs, jsons) => Pdg::merge(&args.metadata, event_logs, jsons, options)?,
+    };
     pdg.graphs.assert_all_tests();
+    if args.canonical {
+        pdg.graphs.canonicalize();
+    }
+    if let Some(path) = &args.facts {
+        facts::write_facts(&pdg.graphs, path)?;
+    }
+    if let Some(path) = &args.permissions {
+        permissions::write_permissions(&pdg.graphs, path)?;
+    }
+    if let Some(path) = &args.dot {
+        dot::write_dot(&pdg.graphs, path)?;
+    }
+    if let Some(path) = &args.json {
+        json::write_json(&pdg.graphs, path)?;
+    }
+    if let Some(Command::Query {
+        node,
+        at,
+        direction,
+    }) = &args.command
+    {
+        let nodes = query::select_nodes(&pdg.graphs, node.as_deref(), at.as_deref())?;
+        let slices = query::Slices {
+            graphs: &pdg.graphs,
+            nodes: &nodes,
+            direction: *direction,
+        };
+        print!("{slices}");
+        return Ok(());
+    }
     let repr = pdg.repr(&args.print);
     println!("{repr}");
     Ok(())
@@ -209,7 +409,7 @@ mod tests {
 
     use color_eyre::eyre::{self, ensure, eyre, Context};
 
-    use crate::{Pdg, ToPrint};
+    use crate::{BuildOptions, Pdg, ToPrint};
 
     pub enum Profile {
         Debug,
@@ -344,8 +544,19 @@ mod tests {
         let status = cmd.status()?;
         ensure!(status.success(), eyre!("{cmd:?} failed: {status}"));
 
-        let pdg = Pdg::new(&metadata_path, &event_log_path)?;
+        // Keep every copy, so the snapshots show the whole graph.
+        let options = BuildOptions {
+            validate: true,
+            compact: false,
+        };
+        let mut pdg = Pdg::new(&metadata_path, &event_log_path, options)?;
         pdg.graphs.assert_all_tests();
+        // The snapshots only cover the shape of the graphs.  `NodeInfo` is tested in `info`.
+        for graph in &mut pdg.graphs.graphs {
+            for node in &mut graph.nodes {
+                node.info = None;
+            }
+        }
         let repr = pdg.repr(to_print);
         Ok(repr.to_string())
     }
@@ -0,0 +1,234 @@
+//! Merging the [`Graphs`] of several runs of the same program.
+//!
+//! One run rarely exercises every code path, so we merge the graphs of all the runs of a test
+//! suite, and facts like [`NodeInfo::unique`] then reflect the behavior of every run.
+//!
+//! Objects are identified by where they're created: all the graphs whose first [`Node`] is the same
+//! operation, at the same statement of the same function, are merged into one.  Within such a
+//! graph, two [`Node`]s are the same if they're the same operation, at the same statement, storing
+//! to the same place, derived from the same [`Node`].  A [`Node`] first seen in a later graph is
+//! added after all the [`Node`]s already there, which keeps sources before the [`Node`]s derived
+//! from them, but loses the order between the runs.
+//!
+//! The relative timing of [`Node`]s from different runs doesn't mean anything, so the
+//! [`NodeInfo`] of each run is computed before merging, and then combined: a [`Node`] flows to a
+//! load, store, offset, free, or un-instrumented function if it does in any run, and is
+//! [`unique`](NodeInfo::unique) only if it is in every run.  A [`Node::size`] or
+//! [`Node::byte_offset`] that differs between runs becomes unknown, and the
+//! [`last_use`](NodeInfo::last_use) is the latest in the merged graph.
+
+use crate::graph::{Graph, GraphId, Graphs, Node, NodeId, NodeKind};
+use crate::info::{FlowInfo, NodeInfo};
+use c2rust_analysis_rt::mir_loc::{FuncId, MirPlace};
+use rustc_index::vec::IndexVec;
+use rustc_middle::mir::BasicBlock;
+use std::cmp::max;
+use std::collections::HashMap;
+
+/// What identifies a [`Node`] across runs.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct NodeKey {
+    /// The merged [`Node::source`].
+    source: Option<NodeId>,
+    function: FuncId,
+    block: BasicBlock,
+    statement_idx: usize,
+    kind: NodeKind,
+    dest: Option<MirPlace>,
+}
+
+impl NodeKey {
+    fn new(node: &Node, source: Option<NodeId>) -> Self {
+        Self {
+            source,
+            function: node.function.id,
+            block: node.block,
+            statement_idx: node.statement_idx,
+            kind: node.kind,
+            dest: node.dest.clone(),
+        }
+    }
+}
+
+/// Forget `merged` if it's different from `other`.
+fn merge_known<T: PartialEq>(merged: &mut Option<T>, other: Option<T>) {
+    if *merged != other {
+        *merged = None;
+    }
+}
+
+/// Combine the [`FlowInfo`] of one run into the merged one, mapping its [`NodeId`]s with `ids`.
+fn merge_flow_info(merged: &mut FlowInfo, other: FlowInfo, ids: &IndexVec<NodeId, NodeId>) {
+    let map = |id: Option<NodeId>| id.map(|id| ids[id]);
+    merged.load = merged.load.or_else(|| map(other.load));
+    merged.store = merged.store.or_else(|| map(other.store));
+    merged.pos_offset = merged.pos_offset.or_else(|| map(other.pos_offset));
+    merged.neg_offset = merged.neg_offset.or_else(|| map(other.neg_offset));
+    merged.out_of_bounds = merged.out_of_bounds.or_else(|| map(other.out_of_bounds));
+    merged.free = merged.free.or_else(|| map(other.free));
+}
+
+#[derive(Default)]
+struct Merger {
+    graphs: Graphs,
+    /// The merged graph for the objects created by each operation.
+    objects: HashMap<NodeKey, GraphId>,
+    /// The merged [`Node`]s of each graph.
+    nodes: HashMap<(GraphId, NodeKey), NodeId>,
+}
+
+impl Merger {
+    fn add_graph(&mut self, graph: Graph) {
+        let root = match graph.nodes.raw.first() {
+            Some(root) => NodeKey::new(root, None),
+            None => return,
+        };
+        let graph_id = *self
+            .objects
+            .entry(root)
+            .or_insert_with(|| self.graphs.graphs.push(Graph::new()));
+        let merged = &mut self.graphs.graphs[graph_id];
+
+        // The merged `NodeId` of each of the graph's `Node`s.
+        let mut ids = IndexVec::<NodeId, NodeId>::with_capacity(graph.nodes.len());
+        let mut infos = Vec::with_capacity(graph.nodes.len());
+        for mut node in graph.nodes {
+            node.source = node.source.map(|source| ids[source]);
+            infos.push(node.info.take());
+            let key = NodeKey::new(&node, node.source);
+            let id = match self.nodes.get(&(graph_id, key.clone())) {
+                Some(&id) => {
+                    let merged_node = &mut merged.nodes[id];
+                    merge_known(&mut merged_node.size, node.size);
+                    merge_known(&mut merged_node.byte_offset, node.byte_offset);
+                    id
+                }
+                None => {
+                    // The identity for combining `NodeInfo`s below.
+                    let info = NodeInfo {
+                        flows_to: FlowInfo::default(),
+                        flows_to_extern: None,
+                        unique: true,
+                        non_unique_cross_thread: false,
+                        last_use: merged.nodes.next_index(),
+                    };
+                    let id = merged.nodes.push(Node {
+                        info: Some(info),
+                        ..node
+                    });
+                    self.nodes.insert((graph_id, key), id);
+                    id
+                }
+            };
+            ids.push(id);
+        }
+
+        // Now that all the `NodeId`s are mapped, combine the `NodeInfo`s.
+        for (old_id, info) in infos.into_iter().enumerate() {
+            let merged_info = &mut merged.nodes[ids[NodeId::from_usize(old_id)]].info;
+            match (merged_info.as_mut(), info) {
+                (Some(merged_info), Some(info)) => {
+                    merge_flow_info(&mut merged_info.flows_to, info.flows_to, &ids);
+                    merged_info.flows_to_extern = merged_info
+                        .flows_to_extern
+                        .or_else(|| info.flows_to_extern.map(|id| ids[id]));
+                    merged_info.unique &= info.unique;
+                    merged_info.non_unique_cross_thread |= info.non_unique_cross_thread;
+                    merged_info.last_use = max(merged_info.last_use, ids[info.last_use]);
+                }
+                _ => *merged_info = None,
+            }
+        }
+    }
+}
+
+/// Merge the [`Graphs`] of several runs of the same program.  [`add_info`](crate::info::add_info)
+/// should already have been run on each.
+pub fn merge(runs: impl IntoIterator<Item = Graphs>) -> Graphs {
+    let mut merger = Merger::default();
+    for graphs in runs {
+        for graph in graphs.graphs {
+            merger.add_graph(graph);
+        }
+    }
+    merger.graphs
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::info::add_info;
+    use c2rust_analysis_rt::mir_loc::Func;
+
+    fn mk_node(g: &mut Graph, kind: NodeKind, source: Option<NodeId>, block: u32) -> NodeId {
+        g.nodes.push(Node {
+            function: Func {
+                id: FuncId((1, 2).into()),
+                name: "fake_function".into(),
+            },
+            block: block.into(),
+            statement_idx: 0,
+            dest: None,
+            kind,
+            source,
+            info: None,
+            debug_info: "".into(),
+            size: None,
+            byte_offset: None,
+            extern_arg: false,
+            thread: 0,
+            span: None,
+        })
+    }
+
+    fn run(g: Graph) -> Graphs {
+        let mut graphs = Graphs::new();
+        graphs.graphs.push(g);
+        add_info(&mut graphs);
+        graphs
+    }
+
+    /// ```rust
+    /// let p = malloc(4);  // bb0
+    /// let q = p;          // bb1
+    /// if run1 {
+    ///     *q = 1;         // bb2
+    /// } else {
+    ///     let r = p;      // bb3
+    ///     *q;             // bb4
+    ///     *r;             // bb5
+    ///     *q;             // bb4
+    /// }
+    /// ```
+    #[test]
+    fn merge_runs() {
+        let mut g1 = Graph::new();
+        let p = mk_node(&mut g1, NodeKind::Alloc(1), None, 0);
+        let q = mk_node(&mut g1, NodeKind::Copy, Some(p), 1);
+        let store = mk_node(&mut g1, NodeKind::StoreAddr, Some(q), 2);
+
+        let mut g2 = Graph::new();
+        let p2 = mk_node(&mut g2, NodeKind::Alloc(1), None, 0);
+        let q2 = mk_node(&mut g2, NodeKind::Copy, Some(p2), 1);
+        let r2 = mk_node(&mut g2, NodeKind::Copy, Some(p2), 3);
+        mk_node(&mut g2, NodeKind::LoadAddr, Some(q2), 4);
+        mk_node(&mut g2, NodeKind::LoadAddr, Some(r2), 5);
+        mk_node(&mut g2, NodeKind::LoadAddr, Some(q2), 4);
+
+        let merged = merge([run(g1), run(g2)]);
+        assert_eq!(merged.graphs.len(), 1);
+        let g = &merged.graphs[GraphId::from_u32(0)];
+        // The two loads at bb4 are the same operation.
+        assert_eq!(g.nodes.len(), 6);
+        let info = |id: NodeId| g.nodes[id].info.as_ref().unwrap();
+        let (r, load) = (NodeId::from_u32(3), NodeId::from_u32(4));
+        assert_eq!(g.nodes[r].source, Some(p));
+        assert_eq!(g.nodes[load].source, Some(q));
+        assert_eq!(info(q).flows_to.store, Some(store));
+        assert_eq!(info(q).flows_to.load, Some(load));
+        // `q` is unique in the first run, but not in the second.
+        assert!(!info(q).unique);
+        assert!(!info(p).unique);
+        assert!(info(store).unique);
+    }
+}
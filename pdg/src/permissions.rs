@@ -0,0 +1,225 @@
+//! Translation of the [`NodeInfo`] of each pointer into the permissions of `c2rust-analyze`, for
+//! `--permissions`.
+//!
+//! `c2rust-analyze` describes a pointer by a `PermissionSet`: what can be done with it, like
+//! `READ`, `WRITE`, and `OFFSET_ADD`, and whether it's `UNIQUE`.  This module summarizes what the
+//! program did at run time with each pointer that a [`Node`] assigned to a whole local in the same
+//! terms, so the two can be compared directly.  The summaries are keyed by the function, as its
+//! [`DefPathHash`], the statement, and the local, as in the [`facts`](crate::facts), and the
+//! [`Node`]s of all runs and all objects at the same statement are combined into one.  The
+//! permissions are written as the bits of [`PermissionSet`], which are those of `c2rust-analyze`,
+//! as in its function summaries, so it reads them with `PermissionSet::from_bits_truncate`.
+//!
+//! [`DefPathHash`]: c2rust_analysis_rt::mir_loc::DefPathHash
+
+use crate::graph::{Graphs, Node, NodeKind};
+use crate::info::NodeInfo;
+use bitflags::bitflags;
+use color_eyre::eyre;
+use fs_err::File;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::io::BufWriter;
+use std::path::Path;
+
+/// Force an import of [`NodeInfo`] just for docs.
+const _: Option<NodeInfo> = None;
+
+bitflags! {
+    /// The permissions of `c2rust-analyze`'s `PermissionSet`, with the same bits.
+    ///
+    /// A pointer gets a permission if it, or a pointer derived from it, was used in a way that
+    /// needs it, except for [`UNIQUE`](Self::UNIQUE), which it gets if it was never aliased.
+    /// Whether a pointer is `LINEAR` can't be observed at run time, so that's never set.
+    ///
+    /// [`Graph::needs_write_permission`]: crate::graph::Graph::needs_write_permission
+    #[derive(Default)]
+    pub struct PermissionSet: u16 {
+        /// Loaded from, as in [`FlowInfo::load`](crate::info::FlowInfo::load), but not counting
+        /// a [`NodeKind::LoadValue`] that produced the pointer.
+        const READ = 0x0001;
+        /// Stored to, as in [`Graph::needs_write_permission`].
+        const WRITE = 0x0002;
+        /// [`NodeInfo::unique`].
+        const UNIQUE = 0x0004;
+        /// Offset forward.
+        const OFFSET_ADD = 0x0010;
+        /// Offset backward.
+        const OFFSET_SUB = 0x0020;
+        /// Freed or reallocated.
+        const FREE = 0x0040;
+    }
+}
+
+/// What the program did with the pointers assigned to a local at one statement, as a
+/// [`PermissionSet`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PointerPermissions {
+    /// The [`DefPathHash`](c2rust_analysis_rt::mir_loc::DefPathHash) of [`Node::function`].
+    pub def_path_hash: (u64, u64),
+    /// The name of [`Node::function`], for human readers.
+    pub function: String,
+    pub block: usize,
+    pub statement_idx: usize,
+    /// The local in [`Node::dest`].
+    pub local: u32,
+    /// The bits of the [`PermissionSet`].
+    pub permissions: u16,
+}
+
+/// Compute the [`PermissionSet`] of every local assigned by a [`Node`] of `graphs` at each
+/// statement, sorted by function, statement, and local.
+///
+/// [`Graphs`] must already have had [`add_info`](crate::info::add_info) run on it.
+pub fn collect_permissions(graphs: &Graphs) -> Vec<PointerPermissions> {
+    // The function name and permissions of each (function, block, statement, local).
+    let mut perms = BTreeMap::<((u64, u64), usize, usize, u32), (&str, PermissionSet)>::new();
+    for graph in &graphs.graphs {
+        let writes = graph.needs_write_permission().collect::<HashSet<_>>();
+        for (node_id, node) in graph.nodes.iter_enumerated() {
+            let Node {
+                function,
+                block,
+                statement_idx,
+                dest,
+                info,
+                ..
+            } = node;
+            let dest = match dest {
+                Some(dest) if dest.projection.is_empty() => dest,
+                _ => continue,
+            };
+            let info = info.as_ref().expect("`add_info` was not run");
+            let flows_to = &info.flows_to;
+            let mut perm = PermissionSet::empty();
+            perm.set(
+                PermissionSet::READ,
+                flows_to
+                    .load
+                    .map_or(false, |id| graph.nodes[id].kind == NodeKind::LoadAddr),
+            );
+            perm.set(PermissionSet::WRITE, writes.contains(&node_id));
+            perm.set(PermissionSet::UNIQUE, info.unique);
+            perm.set(PermissionSet::OFFSET_ADD, flows_to.pos_offset.is_some());
+            perm.set(PermissionSet::OFFSET_SUB, flows_to.neg_offset.is_some());
+            perm.set(PermissionSet::FREE, flows_to.free.is_some());
+
+            let key = (
+                function.id.0.into(),
+                block.as_usize(),
+                *statement_idx,
+                dest.local.index,
+            );
+            perms
+                .entry(key)
+                .and_modify(|(_, merged)| {
+                    // Any use needs the permission, but it's only unique if it always was.
+                    let unique = merged.contains(PermissionSet::UNIQUE)
+                        && perm.contains(PermissionSet::UNIQUE);
+                    merged.insert(perm);
+                    merged.set(PermissionSet::UNIQUE, unique);
+                })
+                .or_insert((&function.name, perm));
+        }
+    }
+    perms
+        .into_iter()
+        .map(
+            |((def_path_hash, block, statement_idx, local), (function, perm))| PointerPermissions {
+                def_path_hash,
+                function: function.to_owned(),
+                block,
+                statement_idx,
+                local,
+                permissions: perm.bits(),
+            },
+        )
+        .collect()
+}
+
+/// Write the [`PointerPermissions`] of `graphs` to `path` as JSON.
+pub fn write_permissions(graphs: &Graphs, path: &Path) -> eyre::Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer(writer, &collect_permissions(graphs))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::{Graph, GraphBuilder, NodeId};
+    use crate::info::add_info;
+    use c2rust_analysis_rt::mir_loc::MirPlace;
+
+    /// Make `id` assign the local `local`.
+    fn assign(g: &mut GraphBuilder, id: NodeId, local: u32) {
+        g.node_mut(id).dest = Some(MirPlace {
+            local: local.into(),
+            projection: vec![],
+        });
+    }
+
+    /// ```rust
+    /// let p = malloc(8);   // bb0[0], _1
+    /// let q = p.add(1);    // bb0[1], _2
+    /// *q = 1;              // bb0[2]
+    /// let r = p;           // bb0[3], _3
+    /// *r;                  // bb0[4]
+    /// free(p);             // bb0[5]
+    /// ```
+    ///
+    /// If `aliased`, `r` is assigned before the store through `q`.
+    fn graph(aliased: bool) -> Graph {
+        let mut g = GraphBuilder::new();
+        let p = g.alloc(2);
+        assign(&mut g, p, 1);
+        let q = g.at(0_u32, 1).offset(p, 1);
+        assign(&mut g, q, 2);
+        let mut r = None;
+        if aliased {
+            r = Some(g.at(0_u32, 3).copy(p));
+        }
+        g.at(0_u32, 2).store_addr(q);
+        let r = r.unwrap_or_else(|| g.at(0_u32, 3).copy(p));
+        assign(&mut g, r, 3);
+        g.at(0_u32, 4).load_addr(r);
+        g.at(0_u32, 5).free(p);
+        g.build()
+    }
+
+    fn permissions(runs: &[bool]) -> Vec<(u32, PermissionSet)> {
+        let mut graphs = Graphs::new();
+        for &aliased in runs {
+            graphs.graphs.push(graph(aliased));
+        }
+        add_info(&mut graphs);
+        collect_permissions(&graphs)
+            .into_iter()
+            .map(|p| (p.local, PermissionSet::from_bits_truncate(p.permissions)))
+            .collect()
+    }
+
+    #[test]
+    fn collect() {
+        use PermissionSet as P;
+        let (read, write, unique) = (P::READ, P::WRITE, P::UNIQUE);
+        let (offset, free) = (P::OFFSET_ADD, P::FREE);
+        assert_eq!(
+            permissions(&[false]),
+            vec![
+                (1, read | write | unique | offset | free),
+                (2, write | unique | offset),
+                (3, read | unique),
+            ]
+        );
+        // The two objects are combined, and aren't unique because of the second.
+        assert_eq!(
+            permissions(&[false, true]),
+            vec![
+                (1, read | write | offset | free),
+                (2, write | offset),
+                (3, read),
+            ]
+        );
+    }
+}
@@ -11,9 +11,14 @@
 //! and be able to test if certain changes have any effect on the PDG output.
 //! We are thinking about using [`insta`](https://insta.rs/) for this.
 
+use clap::ValueEnum;
+use color_eyre::eyre::{self, eyre};
 use linked_hash_set::LinkedHashSet;
+use rustc_middle::mir::BasicBlock;
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
 
-use crate::graph::{Graph, NodeId, NodeKind};
+use crate::graph::{Graph, GraphId, Graphs, NodeId, NodeKind};
 
 impl Graph {
     /// Query an object [`Graph`] to determine which of its [`Node`]s (returned as [`NodeId`]s)
@@ -24,9 +29,10 @@ impl Graph {
     /// (in the same object [`Graph`], though there shouldn't be any paths out of an object [`Graph`] anyways).
     ///
     /// The way the PDG/[`Graph`]s is/are represented, it is actually easiest to work backwards from [`StoreAddr`] nodes
-    /// and mark all ancestor nodes as needing write permissions.
+    /// and mark all ancestor nodes as needing write permissions, up to any [`Realloc`].
     ///
     /// [`StoreAddr`]: NodeKind::StoreAddr
+    /// [`Realloc`]: NodeKind::Realloc
     /// [`Node`]: crate::graph::Node
     pub fn needs_write_permission(&self) -> impl Iterator<Item = NodeId> {
         let mut needs_write = LinkedHashSet::new();
@@ -38,6 +44,8 @@ impl Graph {
                     loop {
                         needs_write.insert(cur);
                         let source = match self.nodes[cur].source {
+                            // Writing to a reallocated object doesn't write to the old one.
+                            _ if self.nodes[cur].kind == NodeKind::Realloc => break,
                             None => break,
                             Some(source) => source,
                         };
@@ -50,4 +58,254 @@ impl Graph {
         }
         needs_write.into_iter()
     }
+
+    /// The backward slice of a [`Node`]: its [`Node::source`], that node's source, and so on up to
+    /// the root, in order from the root.
+    ///
+    /// [`Node`]: crate::graph::Node
+    /// [`Node::source`]: crate::graph::Node::source
+    pub fn backward_slice(&self, node_id: NodeId) -> Vec<NodeId> {
+        let mut slice = Vec::new();
+        let mut cur = self.nodes[node_id].source;
+        while let Some(source) = cur {
+            slice.push(source);
+            cur = self.nodes[source].source;
+        }
+        slice.reverse();
+        slice
+    }
+
+    /// The forward slice of a [`Node`]: every [`Node`] derived from it, directly or transitively,
+    /// in order.
+    ///
+    /// [`Node`]: crate::graph::Node
+    pub fn forward_slice(&self, node_id: NodeId) -> Vec<NodeId> {
+        let mut in_slice = HashSet::from([node_id]);
+        let mut slice = Vec::new();
+        // Nodes are sorted by time, so every descendant comes after its source.
+        for (id, node) in self.nodes.iter_enumerated().skip(node_id.as_usize() + 1) {
+            if node
+                .source
+                .map_or(false, |source| in_slice.contains(&source))
+            {
+                in_slice.insert(id);
+                slice.push(id);
+            }
+        }
+        slice
+    }
+}
+
+/// Which slice of a [`Node`](crate::graph::Node) to print.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
+pub enum Direction {
+    /// The nodes the node is derived from.
+    Backward,
+    /// The nodes derived from the node.
+    Forward,
+}
+
+/// Parse a node as it's printed, `g[GRAPH]n[NODE]`, or in DOT output, `gGRAPHnNODE`.
+pub fn parse_node(s: &str) -> Option<(GraphId, NodeId)> {
+    let s = s.replace(['[', ']', ' '], "");
+    let (graph, node) = s.strip_prefix('g')?.split_once('n')?;
+    Some((
+        GraphId::from_usize(graph.parse().ok()?),
+        NodeId::from_usize(node.parse().ok()?),
+    ))
+}
+
+/// A MIR location, `FUNCTION:bbBLOCK[STATEMENT]` as in the printed graphs, or `FUNCTION:bbBLOCK`
+/// for every statement of the block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub function: String,
+    pub block: BasicBlock,
+    pub statement_idx: Option<usize>,
+}
+
+impl Location {
+    pub fn parse(s: &str) -> Option<Self> {
+        let (function, loc) = s.rsplit_once(':')?;
+        let loc = loc.trim().strip_prefix("bb")?;
+        let (block, statement_idx) = match loc.split_once('[') {
+            Some((block, stmt)) => (block, Some(stmt.strip_suffix(']')?.parse().ok()?)),
+            None => (loc, None),
+        };
+        Some(Self {
+            function: function.trim().to_owned(),
+            block: BasicBlock::from_usize(block.parse().ok()?),
+            statement_idx,
+        })
+    }
+
+    /// Every node at this location, in any graph.
+    pub fn find_nodes(&self, graphs: &Graphs) -> Vec<(GraphId, NodeId)> {
+        let mut found = Vec::new();
+        for (graph_id, graph) in graphs.graphs.iter_enumerated() {
+            for (node_id, node) in graph.nodes.iter_enumerated() {
+                if node.function.name == self.function
+                    && node.block == self.block
+                    && self
+                        .statement_idx
+                        .map_or(true, |idx| idx == node.statement_idx)
+                {
+                    found.push((graph_id, node_id));
+                }
+            }
+        }
+        found
+    }
+}
+
+/// Find the nodes selected by a `query` command's `--node` or `--at`.
+pub fn select_nodes(
+    graphs: &Graphs,
+    node: Option<&str>,
+    at: Option<&str>,
+) -> eyre::Result<Vec<(GraphId, NodeId)>> {
+    let nodes = match (node, at) {
+        (Some(node), _) => {
+            let (graph_id, node_id) = parse_node(node)
+                .ok_or_else(|| eyre!("bad node {node:?}: expected `g[GRAPH]n[NODE]`"))?;
+            let exists = graphs
+                .graphs
+                .get(graph_id)
+                .map_or(false, |graph| graph.nodes.get(node_id).is_some());
+            if !exists {
+                return Err(eyre!("there is no node {graph_id}{node_id}"));
+            }
+            vec![(graph_id, node_id)]
+        }
+        (None, Some(at)) => {
+            let loc = Location::parse(at).ok_or_else(|| {
+                eyre!("bad location {at:?}: expected `FUNCTION:bbBLOCK[STATEMENT]`")
+            })?;
+            let nodes = loc.find_nodes(graphs);
+            if nodes.is_empty() {
+                return Err(eyre!("there are no nodes at {at}"));
+            }
+            nodes
+        }
+        (None, None) => return Err(eyre!("expected `--node` or `--at`")),
+    };
+    Ok(nodes)
+}
+
+/// The slices of some nodes, printed with the node itself included and marked with `*`.
+pub struct Slices<'a> {
+    pub graphs: &'a Graphs,
+    pub nodes: &'a [(GraphId, NodeId)],
+    pub direction: Direction,
+}
+
+impl Display for Slices<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for (i, &(graph_id, node_id)) in self.nodes.iter().enumerate() {
+            if i != 0 {
+                writeln!(f)?;
+            }
+            let graph = &self.graphs.graphs[graph_id];
+            let mut slice = match self.direction {
+                Direction::Backward => graph.backward_slice(node_id),
+                Direction::Forward => graph.forward_slice(node_id),
+            };
+            match self.direction {
+                Direction::Backward => slice.push(node_id),
+                Direction::Forward => slice.insert(0, node_id),
+            }
+            let direction = self.direction.to_possible_value().unwrap();
+            writeln!(
+                f,
+                "{} slice of {graph_id}{node_id} {{",
+                direction.get_name()
+            )?;
+            for (&id, line) in slice.iter().zip(graph.format_nodes(slice.iter().copied())) {
+                let mark = if id == node_id { '*' } else { ' ' };
+                writeln!(f, "{mark}\t{line}")?;
+            }
+            writeln!(f, "}}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::Node;
+    use c2rust_analysis_rt::mir_loc::{Func, FuncId};
+
+    fn mk_node(g: &mut Graph, kind: NodeKind, source: Option<NodeId>, block: u32) -> NodeId {
+        g.nodes.push(Node {
+            function: Func {
+                id: FuncId((1, 2).into()),
+                name: "fake_function".into(),
+            },
+            block: block.into(),
+            statement_idx: 0,
+            dest: None,
+            kind,
+            source,
+            info: None,
+            debug_info: "".into(),
+            size: None,
+            byte_offset: None,
+            extern_arg: false,
+            thread: 0,
+            span: None,
+        })
+    }
+
+    /// ```text
+    /// a -> b -> c -> d
+    ///        \-> e
+    /// f
+    /// ```
+    #[test]
+    fn slices() {
+        let mut g = Graph::new();
+        let a = mk_node(&mut g, NodeKind::Alloc(1), None, 0);
+        let b = mk_node(&mut g, NodeKind::Copy, Some(a), 1);
+        let c = mk_node(&mut g, NodeKind::Field(0_u32.into()), Some(b), 2);
+        let e = mk_node(&mut g, NodeKind::LoadAddr, Some(b), 3);
+        let d = mk_node(&mut g, NodeKind::StoreAddr, Some(c), 4);
+        let f = mk_node(&mut g, NodeKind::IntToPtr, None, 5);
+
+        assert_eq!(g.backward_slice(d), vec![a, b, c]);
+        assert_eq!(g.backward_slice(a), vec![]);
+        assert_eq!(g.forward_slice(b), vec![c, e, d]);
+        assert_eq!(g.forward_slice(d), vec![]);
+        assert_eq!(g.forward_slice(f), vec![]);
+
+        let mut graphs = Graphs::new();
+        graphs.graphs.push(g);
+        let g0 = GraphId::from_u32(0);
+        let found = Location::parse("fake_function:bb3")
+            .unwrap()
+            .find_nodes(&graphs);
+        assert_eq!(found, vec![(g0, e)]);
+        assert_eq!(
+            select_nodes(&graphs, Some("g[0]n[4]"), None).unwrap(),
+            vec![(g0, d)]
+        );
+        assert!(select_nodes(&graphs, Some("g0n9"), None).is_err());
+    }
+
+    #[test]
+    fn parse() {
+        let (g, n) = (GraphId::from_u32(2), NodeId::from_u32(5));
+        assert_eq!(parse_node("g[2]n[5]"), Some((g, n)));
+        assert_eq!(parse_node("g2n5"), Some((g, n)));
+        assert_eq!(parse_node("n[5]"), None);
+        assert_eq!(
+            Location::parse("main:bb3[10]"),
+            Some(Location {
+                function: "main".into(),
+                block: BasicBlock::from_u32(3),
+                statement_idx: Some(10),
+            })
+        );
+        assert_eq!(Location::parse("main:3[10]"), None);
+    }
 }
@@ -0,0 +1,372 @@
+//! Data races between threads, for `--print data-races`.
+//!
+//! Transpiled multithreaded C shares objects between threads, and whether a pointer to one can be
+//! rewritten to a plain `&mut` or has to go through a `Mutex` depends on whether the threads'
+//! accesses to it are synchronized.  [`find_races`] replays the event log with a vector clock per
+//! thread, and reports pairs of loads, stores, and frees through pointers to the same object, on
+//! different threads and not both loads, that aren't ordered by happens-before.
+//!
+//! The happens-before is a lite one, with only the synchronization the runtime records:
+//!
+//! * Unlocking a mutex ([`EventKind::Unlock`]) happens-before every later lock of it
+//!   ([`EventKind::Lock`]).
+//! * Everything a thread did happens-before a [`EventKind::Join`] of it.
+//! * `pthread_create` can't be hooked, so a thread is treated as if every other thread spawned it
+//!   just before its first event: everything logged before then happens-before it.  This orders
+//!   more than the real spawn does, so a race with something another thread did just before the
+//!   new thread started can be missed.
+//!
+//! Accesses ordered only by other synchronization, like atomics or condition variables, are
+//! reported as races.  Each access is checked against the last store to the object and the last
+//! load of it on each thread, so not every racing pair is reported, but like the
+//! [`MemoryError`](crate::safety::MemoryError)s, the races reported are ones that were possible
+//! in the run that produced the event log, whether or not the accesses ended up overlapping.
+//! There are no [`Event`]s in graphs read with `--from-json`, so nothing is reported for them.
+
+use crate::builder::{add_node, instrumented_functions, AddressTaken};
+use crate::graph::{GraphId, Graphs, Node, NodeId, NodeKind};
+use c2rust_analysis_rt::events::{Event, EventKind, Pointer, ThreadId};
+use c2rust_analysis_rt::metadata::Metadata;
+use c2rust_analysis_rt::mir_loc::FuncId;
+use rustc_middle::mir::BasicBlock;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display, Formatter};
+
+/// A vector clock: the latest time of each thread that happened-before some point.
+#[derive(Debug, Clone, Default)]
+struct Clock(Vec<u32>);
+
+impl Clock {
+    fn get(&self, thread: ThreadId) -> u32 {
+        self.0.get(thread as usize).copied().unwrap_or(0)
+    }
+
+    fn tick(&mut self, thread: ThreadId) {
+        let thread = thread as usize;
+        if self.0.len() <= thread {
+            self.0.resize(thread + 1, 0);
+        }
+        self.0[thread] += 1;
+    }
+
+    /// Make everything that happened-before `other` happen-before `self` too.
+    fn join(&mut self, other: &Clock) {
+        if self.0.len() < other.0.len() {
+            self.0.resize(other.0.len(), 0);
+        }
+        for (time, &other) in self.0.iter_mut().zip(&other.0) {
+            *time = (*time).max(other);
+        }
+    }
+}
+
+/// An access to an object by a thread at one of its times.
+#[derive(Debug, Clone, Copy)]
+struct Epoch {
+    thread: ThreadId,
+    time: u32,
+    node: NodeId,
+}
+
+/// The accesses to an object that later ones have to be checked against.
+#[derive(Debug, Default)]
+struct ObjectAccesses {
+    last_write: Option<Epoch>,
+    last_reads: HashMap<ThreadId, Epoch>,
+}
+
+/// The happens-before of one run, as described in the [module docs](self).
+#[derive(Debug, Default)]
+pub struct HappensBefore {
+    threads: HashMap<ThreadId, Clock>,
+    mutexes: HashMap<Pointer, Clock>,
+    objects: HashMap<GraphId, ObjectAccesses>,
+}
+
+impl HappensBefore {
+    /// The clock of `thread`, spawning it if it hasn't been seen before.
+    fn thread(&mut self, thread: ThreadId) -> &mut Clock {
+        if !self.threads.contains_key(&thread) {
+            let mut clock = Clock::default();
+            for (&other, other_clock) in &mut self.threads {
+                clock.join(other_clock);
+                other_clock.tick(other);
+            }
+            clock.tick(thread);
+            self.threads.insert(thread, clock);
+        }
+        self.threads.get_mut(&thread).unwrap()
+    }
+
+    /// `thread` locked `mutex`.
+    pub fn lock(&mut self, thread: ThreadId, mutex: Pointer) {
+        let clock = self.mutexes.get(&mutex).cloned().unwrap_or_default();
+        self.thread(thread).join(&clock);
+    }
+
+    /// `thread` unlocked `mutex`.
+    pub fn unlock(&mut self, thread: ThreadId, mutex: Pointer) {
+        let clock = self.thread(thread);
+        let released = clock.clone();
+        clock.tick(thread);
+        self.mutexes.insert(mutex, released);
+    }
+
+    /// `thread` waited for `joined` to exit.
+    pub fn join(&mut self, thread: ThreadId, joined: ThreadId) {
+        let joined = self.thread(joined).clone();
+        self.thread(thread).join(&joined);
+    }
+
+    /// `thread` accessed `object` with the [`Node`] `node` of kind `kind`.  Return the earlier
+    /// accesses to `object` that race with it.
+    ///
+    /// Only [`NodeKind::LoadAddr`]s, [`NodeKind::StoreAddr`]s, [`NodeKind::Free`]s, and
+    /// [`NodeKind::Realloc`]s are accesses, and the latter three write to the object.
+    pub fn access(
+        &mut self,
+        thread: ThreadId,
+        kind: NodeKind,
+        object: GraphId,
+        node: NodeId,
+    ) -> Vec<NodeId> {
+        let clock = self.thread(thread).clone();
+        let is_write = match kind {
+            NodeKind::LoadAddr => false,
+            NodeKind::StoreAddr | NodeKind::Free | NodeKind::Realloc => true,
+            _ => return Vec::new(),
+        };
+        let epoch = Epoch {
+            thread,
+            time: clock.get(thread),
+            node,
+        };
+        let ordered =
+            |earlier: &Epoch| earlier.thread == thread || earlier.time <= clock.get(earlier.thread);
+
+        let accesses = self.objects.entry(object).or_default();
+        let mut races = Vec::new();
+        races.extend(accesses.last_write.filter(|write| !ordered(write)));
+        if is_write {
+            races.extend(
+                accesses
+                    .last_reads
+                    .values()
+                    .copied()
+                    .filter(|read| !ordered(read)),
+            );
+            // Any later access that races with one of these also races with this write, or
+            // follows it.
+            accesses.last_reads.clear();
+            accesses.last_write = Some(epoch);
+        } else {
+            accesses.last_reads.insert(thread, epoch);
+        }
+        races.into_iter().map(|earlier| earlier.node).collect()
+    }
+}
+
+/// Two accesses to the same object on different threads that aren't ordered by happens-before,
+/// at least one of which writes to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataRace {
+    /// The [`Node`] that created the object.
+    pub object: Node,
+    /// The access that happened first in the run.
+    pub first: Node,
+    /// The access that happened second in the run.
+    pub second: Node,
+}
+
+/// Where a [`Node`] of a [`DataRace`] happened.
+struct Location<'a>(&'a Node);
+
+impl Display for Location<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let node = self.0;
+        write!(
+            f,
+            "{} at {:?}[{}] in fn {} on thread {}",
+            node.kind, node.block, node.statement_idx, node.function, node.thread
+        )?;
+        if let Some(span) = &node.span {
+            write!(f, " ({span})")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for DataRace {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let Self {
+            object,
+            first,
+            second,
+        } = self;
+        write!(
+            f,
+            "data race on the object from {}: {}, racing with {}",
+            Location(object),
+            Location(second),
+            Location(first)
+        )
+    }
+}
+
+/// What identifies a [`Node`] of a [`DataRace`] across threads and repeated executions.
+type NodeKey = (FuncId, BasicBlock, usize, NodeKind);
+
+fn node_key(node: &Node) -> NodeKey {
+    (node.function.id, node.block, node.statement_idx, node.kind)
+}
+
+/// Find the [`DataRace`]s in `events`, which may be the concatenated event logs of several runs,
+/// each ending with an [`EventKind::Done`].  Races between the same statements on the same
+/// objects are only reported once, however many threads or iterations they happened in.
+pub fn find_races(events: &[Event], metadata: &Metadata) -> Vec<DataRace> {
+    let instrumented = instrumented_functions(metadata);
+    let mut races = Vec::new();
+    let mut seen = HashSet::<(NodeKey, NodeKey, NodeKey)>::new();
+
+    let mut graphs = Graphs::new();
+    let mut provenances = HashMap::new();
+    let mut address_taken = AddressTaken::new();
+    let mut hb = HappensBefore::default();
+    for event in events {
+        let thread = event.thread;
+        // A thread starts at its first event, not just its first access or synchronization.
+        hb.thread(thread);
+        match event.kind {
+            EventKind::Lock(mutex) => hb.lock(thread, mutex),
+            EventKind::Unlock(mutex) => hb.unlock(thread, mutex),
+            EventKind::Join(joined) => hb.join(thread, joined),
+            EventKind::Done => {
+                graphs = Graphs::new();
+                provenances.clear();
+                address_taken.clear();
+                hb = HappensBefore::default();
+            }
+            _ => {}
+        }
+        let (graph_id, node_id) = match add_node(
+            &mut graphs,
+            &mut provenances,
+            &mut address_taken,
+            event,
+            metadata,
+            &instrumented,
+        ) {
+            Some(added) => added,
+            None => continue,
+        };
+        let graph = &graphs.graphs[graph_id];
+        let second = &graph.nodes[node_id];
+        for first in hb.access(thread, second.kind, graph_id, node_id) {
+            let object = &graph.nodes[NodeId::from_u32(0)];
+            let first = &graph.nodes[first];
+            if seen.insert((node_key(object), node_key(first), node_key(second))) {
+                races.push(DataRace {
+                    object: object.clone(),
+                    first: first.clone(),
+                    second: second.clone(),
+                });
+            }
+        }
+    }
+    races
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::{Graph, GraphBuilder};
+
+    /// Replay the accesses of `graph`, all to one object, with a [`HappensBefore`], calling
+    /// `sync` before each [`Node`], and return the pairs of racing [`NodeId`]s.
+    fn races(
+        graph: &Graph,
+        mut sync: impl FnMut(&mut HappensBefore, NodeId),
+    ) -> Vec<(NodeId, NodeId)> {
+        let mut hb = HappensBefore::default();
+        let object = GraphId::from_u32(0);
+        let mut races = Vec::new();
+        for (id, node) in graph.nodes.iter_enumerated() {
+            hb.thread(node.thread);
+            sync(&mut hb, id);
+            for first in hb.access(node.thread, node.kind, object, id) {
+                races.push((first, id));
+            }
+        }
+        races
+    }
+
+    /// ```rust
+    /// let p = malloc(4);  // thread 0
+    /// *p = 1;             // thread 0, n[1]
+    /// spawn(|| {
+    ///     lock(m);
+    ///     *p;             // thread 1, n[3]
+    ///     *p = 2;         // thread 1, n[4]
+    ///     unlock(m);
+    /// });
+    /// lock(m);
+    /// *p = 3;             // thread 0, n[6]
+    /// unlock(m);
+    /// join();
+    /// *p;                 // thread 0, n[8]
+    /// ```
+    fn graph() -> Graph {
+        let mut g = GraphBuilder::new();
+        let p = g.alloc(1);
+        g.store_addr(p);
+        let q = g.on_thread(1).copy(p);
+        g.load_addr(q);
+        g.store_addr(q);
+        let r = g.on_thread(0).copy(p);
+        g.store_addr(r);
+        let s = g.copy(p);
+        g.load_addr(s);
+        g.build()
+    }
+
+    #[test]
+    fn lock_and_join() {
+        let g = graph();
+        let n = NodeId::from_u32;
+        let m = 0x1000;
+        let sync = |locked: bool| {
+            move |hb: &mut HappensBefore, id: NodeId| match id.as_u32() {
+                3 => hb.lock(1, m),
+                5 => hb.unlock(1, m),
+                6 if locked => hb.lock(0, m),
+                7 => {
+                    if locked {
+                        hb.unlock(0, m);
+                    }
+                    hb.join(0, 1);
+                }
+                _ => {}
+            }
+        };
+        // The first store happens-before thread 1 starts, and the last load is after the join.
+        assert_eq!(races(&g, sync(true)), []);
+        // Without thread 0's lock, its second store races with thread 1's store, and so with the
+        // load before it, which isn't reported separately.
+        assert_eq!(races(&g, sync(false)), [(n(4), n(6))]);
+    }
+
+    #[test]
+    fn unsynchronized() {
+        let mut g = GraphBuilder::new();
+        let p = g.alloc(1);
+        let q = g.on_thread(1).copy(p);
+        let r = g.on_thread(2).copy(p);
+        g.on_thread(1).load_addr(q);
+        g.on_thread(2).load_addr(r);
+        // Loads don't race with each other, but the free races with the other thread's load.
+        g.on_thread(1).free(q);
+        let g = g.build();
+        let n = NodeId::from_u32;
+        assert_eq!(races(&g, |_, _| {}), [(n(4), n(5))]);
+    }
+}
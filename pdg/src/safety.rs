@@ -0,0 +1,439 @@
+//! Memory-safety errors seen in a run of the program.
+//!
+//! Each [`Graph`] describes one object, from the [`Node`] that creates it to the last use of a
+//! pointer to it, and the objects it's reallocated to by [`NodeKind::Realloc`]s, with its
+//! [`Node`]s in the order they happened.  This lets us check the run for misuses of the object,
+//! such as a load or store through a pointer to it after it was freed, and report where both
+//! operations happened.  [`NodeId`]s are in the order the operations happened, so they serve as
+//! timestamps within an object.  This only finds errors that actually happened in the run that
+//! produced the event log.
+
+use crate::graph::{Graph, GraphId, Graphs, Node, NodeId, NodeKind};
+use rustc_index::vec::IndexVec;
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+/// A misuse of an object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemoryError {
+    /// The load or store `access` through a pointer to the object happened after the object was
+    /// freed by `free`, which may be a [`NodeKind::Realloc`].
+    UseAfterFree { access: NodeId, free: NodeId },
+    /// The object was freed by `free` after it was already freed by `first_free`.  Either may be
+    /// a [`NodeKind::Realloc`].
+    DoubleFree { free: NodeId, first_free: NodeId },
+    /// `free` freed a pointer that was offset by `offset` elements from the start of the object,
+    /// through the [`NodeKind::Offset`]s in its ancestry.
+    InvalidFree { free: NodeId, offset: isize },
+    /// The pointer `node` produced or used is `byte_offset` bytes from the start of the object,
+    /// outside of its `size` bytes.
+    OutOfBounds {
+        node: NodeId,
+        byte_offset: isize,
+        size: usize,
+    },
+}
+
+/// Where a [`Node`] happened, for error messages.
+struct NodeLocation<'a> {
+    id: NodeId,
+    node: &'a Node,
+}
+
+impl Display for NodeLocation<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let Self { id, node } = *self;
+        write!(
+            f,
+            "{id} ({}) at {:?}[{}] in fn {}",
+            node.kind, node.block, node.statement_idx, node.function
+        )?;
+        if let Some(span) = &node.span {
+            write!(f, " ({span})")?;
+        }
+        if !node.debug_info.is_empty() {
+            write!(f, ": {}", node.debug_info)?;
+        }
+        Ok(())
+    }
+}
+
+/// A [`MemoryError`] in a particular [`Graph`], which can be displayed.
+pub struct DisplayMemoryError<'a> {
+    pub graph_id: GraphId,
+    pub graph: &'a Graph,
+    pub error: MemoryError,
+}
+
+impl Display for DisplayMemoryError<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let Self {
+            graph_id,
+            graph,
+            error,
+        } = *self;
+        let loc = |id| NodeLocation {
+            id,
+            node: &graph.nodes[id],
+        };
+        match error {
+            MemoryError::UseAfterFree { access, free } => write!(
+                f,
+                "use after free in {graph_id}: {}, after free {}",
+                loc(access),
+                loc(free)
+            ),
+            MemoryError::DoubleFree { free, first_free } => write!(
+                f,
+                "double free in {graph_id}: {}, after free {}",
+                loc(free),
+                loc(first_free)
+            ),
+            MemoryError::InvalidFree { free, offset } => write!(
+                f,
+                "invalid free in {graph_id}: {}, of a pointer at offset {offset}",
+                loc(free)
+            ),
+            MemoryError::OutOfBounds {
+                node,
+                byte_offset,
+                size,
+            } => write!(
+                f,
+                "out of bounds in {graph_id}: {}, at byte offset {byte_offset} of a {size}-byte object",
+                loc(node)
+            ),
+        }
+    }
+}
+
+impl Graph {
+    /// Map each [`Node`] to the one that created the object its pointer points to: the root, or
+    /// the [`NodeKind::Realloc`] it's derived from.
+    fn objects(&self) -> IndexVec<NodeId, NodeId> {
+        let mut objects = IndexVec::with_capacity(self.nodes.len());
+        for (id, node) in self.nodes.iter_enumerated() {
+            let object = match node.source {
+                Some(source) if node.kind != NodeKind::Realloc => objects[source],
+                _ => id,
+            };
+            objects.push(object);
+        }
+        objects
+    }
+
+    /// The object that the [`Node`] `id` frees, as one of [`Graph::objects`], if it's a
+    /// [`NodeKind::Free`] or a [`NodeKind::Realloc`].
+    fn freed_object(&self, objects: &IndexVec<NodeId, NodeId>, id: NodeId) -> Option<NodeId> {
+        let node = &self.nodes[id];
+        match node.kind {
+            NodeKind::Free => Some(objects[id]),
+            NodeKind::Realloc => node.source.map(|source| objects[source]),
+            _ => None,
+        }
+    }
+
+    /// Find the loads and stores through pointers to an object after it was freed.
+    pub fn use_after_free(&self) -> Vec<MemoryError> {
+        let objects = self.objects();
+        let mut errors = Vec::new();
+        let mut frees = HashMap::new();
+        for (id, node) in self.nodes.iter_enumerated() {
+            if let Some(object) = self.freed_object(&objects, id) {
+                frees.entry(object).or_insert(id);
+            }
+            if let NodeKind::LoadAddr | NodeKind::StoreAddr = node.kind {
+                if let Some(&free) = frees.get(&objects[id]) {
+                    errors.push(MemoryError::UseAfterFree { access: id, free });
+                }
+            }
+        }
+        errors
+    }
+
+    /// Find the frees of an object after the first, and the frees of pointers that don't point
+    /// to the start of the object.
+    pub fn invalid_frees(&self) -> Vec<MemoryError> {
+        let objects = self.objects();
+        let mut errors = Vec::new();
+        let mut first_frees = HashMap::new();
+        for id in self.nodes.indices() {
+            let object = match self.freed_object(&objects, id) {
+                Some(object) => object,
+                None => continue,
+            };
+            match first_frees.get(&object) {
+                None => {
+                    first_frees.insert(object, id);
+                }
+                Some(&first_free) => errors.push(MemoryError::DoubleFree {
+                    free: id,
+                    first_free,
+                }),
+            }
+            let offset = self
+                .backward_slice(id)
+                .into_iter()
+                .filter(|&id| objects[id] == object)
+                .map(|id| match self.nodes[id].kind {
+                    NodeKind::Offset(offset) => offset,
+                    _ => 0,
+                })
+                .sum::<isize>();
+            if offset != 0 {
+                errors.push(MemoryError::InvalidFree { free: id, offset });
+            }
+        }
+        errors
+    }
+
+    /// Find the offsets and accesses outside of the object, as far as [`Node::byte_offset`] and
+    /// [`Node::size`] are known.
+    ///
+    /// An offset to just past the end of the object is allowed, as in C, but a load or store there
+    /// isn't.  We don't know how many bytes a load or store accesses, so one that starts in bounds
+    /// but extends past the end isn't found.
+    pub fn out_of_bounds(&self) -> Vec<MemoryError> {
+        let objects = self.objects();
+        let mut errors = Vec::new();
+        for (id, node) in self.nodes.iter_enumerated() {
+            // A `realloc` creates an object of a new size.
+            let size = self.nodes[objects[id]].size;
+            let (size, byte_offset) = match (size, node.byte_offset) {
+                (Some(size), Some(byte_offset)) => (size, byte_offset),
+                _ => continue,
+            };
+            let in_bounds = match node.kind {
+                NodeKind::Offset(_) => (0..=size as isize).contains(&byte_offset),
+                NodeKind::LoadAddr | NodeKind::StoreAddr => {
+                    (0..size as isize).contains(&byte_offset)
+                }
+                _ => continue,
+            };
+            if !in_bounds {
+                errors.push(MemoryError::OutOfBounds {
+                    node: id,
+                    byte_offset,
+                    size,
+                });
+            }
+        }
+        errors
+    }
+
+    /// Find all the [`MemoryError`]s involving the object, in the order they happened.
+    pub fn memory_errors(&self) -> Vec<MemoryError> {
+        let mut errors = self.use_after_free();
+        errors.extend(self.invalid_frees());
+        errors.extend(self.out_of_bounds());
+        errors.sort_by_key(|error| match *error {
+            MemoryError::UseAfterFree { access: id, .. }
+            | MemoryError::DoubleFree { free: id, .. }
+            | MemoryError::InvalidFree { free: id, .. }
+            | MemoryError::OutOfBounds { node: id, .. } => id,
+        });
+        errors
+    }
+}
+
+impl Graphs {
+    /// Find all the [`MemoryError`]s in all the graphs.
+    pub fn memory_errors(&self) -> impl Iterator<Item = DisplayMemoryError> {
+        self.graphs.iter_enumerated().flat_map(|(graph_id, graph)| {
+            graph
+                .memory_errors()
+                .into_iter()
+                .map(move |error| DisplayMemoryError {
+                    graph_id,
+                    graph,
+                    error,
+                })
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use c2rust_analysis_rt::mir_loc::{Func, FuncId, SourceSpan};
+
+    fn mk_node(g: &mut Graph, kind: NodeKind, source: Option<NodeId>) -> NodeId {
+        g.nodes.push(Node {
+            function: Func {
+                id: FuncId((1, 2).into()),
+                name: "fake_function".into(),
+            },
+            block: 0_u32.into(),
+            statement_idx: g.nodes.len(),
+            dest: None,
+            kind,
+            source,
+            info: None,
+            debug_info: "".into(),
+            size: None,
+            byte_offset: None,
+            extern_arg: false,
+            thread: 0,
+            span: None,
+        })
+    }
+
+    /// ```rust
+    /// let p = malloc(4);
+    /// let q = p;
+    /// *q = 1;
+    /// free(p);
+    /// *q;
+    /// *q = 2;
+    /// ```
+    #[test]
+    fn use_after_free() {
+        let mut g = Graph::new();
+        let p = mk_node(&mut g, NodeKind::Alloc(1), None);
+        let q = mk_node(&mut g, NodeKind::Copy, Some(p));
+        mk_node(&mut g, NodeKind::StoreAddr, Some(q));
+        let free = mk_node(&mut g, NodeKind::Free, Some(p));
+        let load = mk_node(&mut g, NodeKind::LoadAddr, Some(q));
+        g.nodes[load].span = Some(SourceSpan {
+            file: "src/main.rs".into(),
+            line: 5,
+            col: 5,
+        });
+        let store = mk_node(&mut g, NodeKind::StoreAddr, Some(q));
+        assert_eq!(
+            g.use_after_free(),
+            vec![
+                MemoryError::UseAfterFree { access: load, free },
+                MemoryError::UseAfterFree {
+                    access: store,
+                    free
+                },
+            ]
+        );
+
+        let mut graphs = Graphs::new();
+        graphs.graphs.push(g);
+        let first = graphs.memory_errors().next().unwrap().to_string();
+        assert_eq!(
+            first,
+            "use after free in g[0]: \
+             n[4] (addr.load) at bb0[4] in fn fake_function (src/main.rs:5:5), \
+             after free n[3] (free) at bb0[3] in fn fake_function"
+        );
+    }
+
+    /// ```rust
+    /// let p = malloc(4);
+    /// *p = 1;
+    /// free(p);
+    /// ```
+    #[test]
+    fn no_use_after_free() {
+        let mut g = Graph::new();
+        let p = mk_node(&mut g, NodeKind::Alloc(1), None);
+        mk_node(&mut g, NodeKind::StoreAddr, Some(p));
+        mk_node(&mut g, NodeKind::Free, Some(p));
+        assert_eq!(g.use_after_free(), vec![]);
+        assert_eq!(g.invalid_frees(), vec![]);
+    }
+
+    /// ```rust
+    /// let p = malloc(8) as *mut i32;
+    /// let q = p.offset(2);
+    /// *q;
+    /// let r = p.offset(1);
+    /// *r = 1;
+    /// let s = p.offset(-1);
+    /// ```
+    #[test]
+    fn out_of_bounds() {
+        let mut g = Graph::new();
+        let p = mk_node(&mut g, NodeKind::Alloc(1), None);
+        g.nodes[p].size = Some(8);
+        g.nodes[p].byte_offset = Some(0);
+        let mk_at = |g: &mut Graph, kind: NodeKind, source: NodeId, byte_offset: isize| {
+            let id = mk_node(g, kind, Some(source));
+            g.nodes[id].byte_offset = Some(byte_offset);
+            id
+        };
+        let q = mk_at(&mut g, NodeKind::Offset(2), p, 8);
+        let load = mk_at(&mut g, NodeKind::LoadAddr, q, 8);
+        let r = mk_at(&mut g, NodeKind::Offset(1), p, 4);
+        mk_at(&mut g, NodeKind::StoreAddr, r, 4);
+        let s = mk_at(&mut g, NodeKind::Offset(-1), p, -4);
+        assert_eq!(
+            g.out_of_bounds(),
+            vec![
+                MemoryError::OutOfBounds {
+                    node: load,
+                    byte_offset: 8,
+                    size: 8
+                },
+                MemoryError::OutOfBounds {
+                    node: s,
+                    byte_offset: -4,
+                    size: 8
+                },
+            ]
+        );
+    }
+
+    /// ```rust
+    /// let p = malloc(8);
+    /// let q = p.offset(1);
+    /// let r = q.offset(-1);
+    /// free(r);
+    /// free(q);
+    /// ```
+    #[test]
+    fn double_and_invalid_free() {
+        let mut g = Graph::new();
+        let p = mk_node(&mut g, NodeKind::Alloc(1), None);
+        let q = mk_node(&mut g, NodeKind::Offset(1), Some(p));
+        let r = mk_node(&mut g, NodeKind::Offset(-1), Some(q));
+        let first_free = mk_node(&mut g, NodeKind::Free, Some(r));
+        let free = mk_node(&mut g, NodeKind::Free, Some(q));
+        assert_eq!(
+            g.memory_errors(),
+            vec![
+                MemoryError::DoubleFree { free, first_free },
+                MemoryError::InvalidFree { free, offset: 1 },
+            ]
+        );
+    }
+
+    /// ```rust
+    /// let p = malloc(4) as *mut i32;
+    /// *p = 1;
+    /// let q = realloc(p, 8);
+    /// let r = q.offset(1);
+    /// *r = 2;
+    /// *p;
+    /// free(q);
+    /// ```
+    #[test]
+    fn realloc() {
+        let mut g = Graph::new();
+        let mk_at = |g: &mut Graph, kind: NodeKind, source: Option<NodeId>, byte_offset: isize| {
+            let id = mk_node(g, kind, source);
+            g.nodes[id].byte_offset = Some(byte_offset);
+            id
+        };
+        let p = mk_at(&mut g, NodeKind::Alloc(1), None, 0);
+        g.nodes[p].size = Some(4);
+        mk_at(&mut g, NodeKind::StoreAddr, Some(p), 0);
+        let q = mk_at(&mut g, NodeKind::Realloc, Some(p), 0);
+        g.nodes[q].size = Some(8);
+        let r = mk_at(&mut g, NodeKind::Offset(1), Some(q), 4);
+        mk_at(&mut g, NodeKind::StoreAddr, Some(r), 4);
+        let load = mk_at(&mut g, NodeKind::LoadAddr, Some(p), 0);
+        mk_node(&mut g, NodeKind::Free, Some(q));
+        assert_eq!(
+            g.memory_errors(),
+            vec![MemoryError::UseAfterFree {
+                access: load,
+                free: q
+            }]
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! Checks of the invariants of constructed [`Graph`]s, for `--validate`.
+//!
+//! The rest of the crate assumes that each [`Graph`] is well-formed: its [`Node`]s are in the
+//! order the operations happened, and each [`Node::source`] is an earlier [`Node`] of the same
+//! [`Graph`] that produced a pointer.  A corrupt or truncated event log, or a hand-edited `--json`
+//! file, can break these, which then shows up as a confusing panic, such as an index out of bounds
+//! in [`add_info`](crate::info::add_info).  [`Graph::validate`] checks for them up front and says
+//! which [`Node`] is wrong.
+
+use crate::graph::{Graph, GraphId, Graphs, Node, NodeId, NodeKind};
+use color_eyre::eyre::{self, eyre};
+use std::fmt::{self, Display, Formatter};
+
+/// A broken invariant of a [`Graph`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GraphError {
+    /// The [`Node::source`] of `node` isn't a [`Node`] of the [`Graph`].
+    DanglingSource { node: NodeId, source: NodeId },
+    /// The [`Node::source`] of `node` doesn't happen before it.
+    SourceNotBefore { node: NodeId, source: NodeId },
+    /// `node` creates a pointer, so it can't have a [`Node::source`].
+    UnexpectedSource { node: NodeId, source: NodeId },
+    /// `node` is derived from `source`, which doesn't produce a pointer, like a
+    /// [`NodeKind::LoadAddr`].
+    InvalidSource { node: NodeId, source: NodeId },
+    /// The [`NodeInfo`](crate::info::NodeInfo) of `node` says its `what` is `id`, which isn't a
+    /// [`Node`] of the [`Graph`] at or after `node`.
+    InvalidInfo {
+        node: NodeId,
+        what: &'static str,
+        id: NodeId,
+    },
+}
+
+impl Display for GraphError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            GraphError::DanglingSource { node, source } => {
+                write!(f, "{node}: source {source} doesn't exist")
+            }
+            GraphError::SourceNotBefore { node, source } => {
+                write!(f, "{node}: source {source} isn't before it")
+            }
+            GraphError::UnexpectedSource { node, source } => {
+                write!(f, "{node}: creates a pointer but has source {source}")
+            }
+            GraphError::InvalidSource { node, source } => {
+                write!(f, "{node}: source {source} doesn't produce a pointer")
+            }
+            GraphError::InvalidInfo { node, what, id } => {
+                write!(f, "{node}: {what} {id} isn't a node at or after it")
+            }
+        }
+    }
+}
+
+/// Whether a [`Node`] of kind `kind` creates a pointer, rather than deriving it from another.
+fn is_root_kind(kind: NodeKind) -> bool {
+    use NodeKind::*;
+    matches!(
+        kind,
+        AddrOfLocal(_) | _AddrOfStatic(_) | Alloc(_) | IntToPtr | LoadValue
+    )
+}
+
+/// Whether a [`Node`] of kind `kind` produces a pointer that other operations can be derived from.
+fn produces_pointer(kind: NodeKind) -> bool {
+    use NodeKind::*;
+    !matches!(kind, Free | PtrToInt | LoadAddr | StoreAddr | StoreValue)
+}
+
+impl Graph {
+    /// Check the invariants of the graph, returning the broken ones in [`Node`] order.
+    ///
+    /// [`Node::info`] is only checked where it's been computed.
+    pub fn validate(&self) -> Vec<GraphError> {
+        let mut errors = Vec::new();
+        for (id, node) in self.nodes.iter_enumerated() {
+            if let Some(source) = node.source {
+                errors.extend(self.validate_source(id, node, source));
+            }
+            let info = match &node.info {
+                Some(info) => info,
+                None => continue,
+            };
+            let flows_to = &info.flows_to;
+            let ids = [
+                ("load", flows_to.load),
+                ("store", flows_to.store),
+                ("+offset", flows_to.pos_offset),
+                ("-offset", flows_to.neg_offset),
+                ("out of bounds", flows_to.out_of_bounds),
+                ("free", flows_to.free),
+                ("extern", info.flows_to_extern),
+                ("last use", Some(info.last_use)),
+            ];
+            for (what, info_id) in ids {
+                if let Some(info_id) = info_id {
+                    if info_id < id || info_id >= self.nodes.next_index() {
+                        errors.push(GraphError::InvalidInfo {
+                            node: id,
+                            what,
+                            id: info_id,
+                        });
+                    }
+                }
+            }
+        }
+        errors
+    }
+
+    fn validate_source(&self, id: NodeId, node: &Node, source: NodeId) -> Option<GraphError> {
+        let source_node = match self.nodes.get(source) {
+            Some(source_node) => source_node,
+            None => return Some(GraphError::DanglingSource { node: id, source }),
+        };
+        if source >= id {
+            Some(GraphError::SourceNotBefore { node: id, source })
+        } else if is_root_kind(node.kind) {
+            Some(GraphError::UnexpectedSource { node: id, source })
+        } else if !produces_pointer(source_node.kind) {
+            Some(GraphError::InvalidSource { node: id, source })
+        } else {
+            None
+        }
+    }
+}
+
+impl Graphs {
+    /// Check the invariants of all the graphs, as in [`Graph::validate`], and describe all the
+    /// broken ones in the error.
+    pub fn validate(&self) -> eyre::Result<()> {
+        let errors = self
+            .graphs
+            .iter_enumerated()
+            .flat_map(|(graph_id, graph)| {
+                graph
+                    .validate()
+                    .into_iter()
+                    .map(move |error| (graph_id, error))
+            })
+            .collect::<Vec<(GraphId, GraphError)>>();
+        if errors.is_empty() {
+            return Ok(());
+        }
+        let errors = errors
+            .iter()
+            .map(|(graph_id, error)| format!("\n    {graph_id}: {error}"))
+            .collect::<String>();
+        Err(eyre!("invalid PDG:{errors}"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::GraphBuilder;
+    use crate::info::add_info;
+
+    #[test]
+    fn valid() {
+        let mut g = GraphBuilder::new();
+        let p = g.alloc(1);
+        let q = g.field(p, 0_u32);
+        g.load_addr(q);
+        g.free(p);
+        let mut graphs = Graphs::new();
+        graphs.graphs.push(g.build());
+        assert_eq!(graphs.graphs[GraphId::from_u32(0)].validate(), vec![]);
+        add_info(&mut graphs);
+        assert!(graphs.validate().is_ok());
+    }
+
+    #[test]
+    fn invalid() {
+        let mut g = GraphBuilder::new();
+        let p = g.alloc(1);
+        let load = g.load_addr(p);
+        let field = g.field(load, 0_u32);
+        let alloc = g.node(NodeKind::Alloc(1), Some(p));
+        let copy = g.copy(NodeId::from_u32(4));
+        let dangling = g.copy(NodeId::from_u32(9));
+        let g = g.build();
+        assert_eq!(
+            g.validate(),
+            vec![
+                GraphError::InvalidSource {
+                    node: field,
+                    source: load
+                },
+                GraphError::UnexpectedSource {
+                    node: alloc,
+                    source: p
+                },
+                GraphError::SourceNotBefore {
+                    node: copy,
+                    source: copy
+                },
+                GraphError::DanglingSource {
+                    node: dangling,
+                    source: NodeId::from_u32(9)
+                },
+            ]
+        );
+
+        let mut graphs = Graphs::new();
+        graphs.graphs.push(g);
+        let error = graphs.validate().unwrap_err().to_string();
+        assert!(error.starts_with("invalid PDG:\n    g[0]: n[2]: source n[1] doesn't produce"));
+    }
+}